@@ -7,8 +7,7 @@ extern crate pathfinder;
 
 use euclid::Point2D;
 use memmap::{Mmap, Protection};
-use pathfinder::batch::GlyphRange;
-use pathfinder::charmap::CodepointRange;
+use pathfinder::charmap::{CodepointRange, GlyphRange};
 use pathfinder::otf::FontData;
 use std::char;
 use std::env;
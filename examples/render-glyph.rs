@@ -0,0 +1,72 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+// Renders one glyph on the CPU the way the font's `gasp` table asks for at the given size, and
+// prints it as text.
+//
+// Usage: render-glyph FONT CHARACTER PIXELS-PER-EM
+
+extern crate memmap;
+extern crate pathfinder;
+
+use memmap::{Mmap, Protection};
+use pathfinder::hinting::Hinter;
+use pathfinder::otf::FontData;
+use pathfinder::outline::Transform;
+use pathfinder::rendering::RenderingPolicy;
+use pathfinder::software_rasterizer::{CoverageMask, GrayImage};
+use std::env;
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let file = Mmap::open_path(args.next().unwrap(), Protection::Read).unwrap();
+    let character = args.next().unwrap().chars().next().unwrap();
+    let pixels_per_em: f32 = args.next().unwrap().parse().unwrap();
+
+    let font = FontData::new(unsafe { file.as_slice() });
+    let cmap = font.cmap_table().unwrap();
+    let glyph_id = cmap.glyph_for_codepoint(character as u32).unwrap().unwrap_or(0);
+
+    let mode = RenderingPolicy::new(&font).mode(pixels_per_em);
+    println!("{:?}", mode);
+
+    // Grid-fitted outlines are already in pixels. Either way, flip y to point down and put the
+    // baseline most of the way down the image.
+    let size = (pixels_per_em * 1.5).ceil() as u32;
+    let baseline = pixels_per_em.ceil();
+    let (commands, scale) = if mode.grid_fit {
+        let mut hinter = Hinter::new(&font).unwrap();
+        hinter.set_size(pixels_per_em.round() as u16, mode.is_antialiased()).unwrap();
+        (hinter.hint_glyph(glyph_id).unwrap().path_commands(), 1.0)
+    } else {
+        let head = font.head_table().unwrap();
+        let loca = font.loca_table(&head).unwrap();
+        let glyf = font.glyf_table().unwrap();
+        (glyf.path_commands(&loca, glyph_id as u32).unwrap(),
+         pixels_per_em / head.units_per_em as f32)
+    };
+    let transform = Transform::new(scale, 0.0, 0.0, -scale, 0.0, baseline);
+
+    let mut mask = CoverageMask::new(size, size);
+    mask.fill_path(&commands, &transform);
+    let mut image = GrayImage::new(size, size);
+    image.pixels = mask.coverage()
+                       .iter()
+                       .map(|coverage| (coverage * 255.0).round() as u8)
+                       .collect();
+    if !mode.is_antialiased() {
+        image.threshold(128)
+    }
+
+    for y in 0..size {
+        let row: String = (0..size).map(|x| {
+            match image.pixel(x, y) {
+                0 => ' ',
+                1...127 => '.',
+                128...254 => '+',
+                _ => '#',
+            }
+        }).collect();
+        println!("{}", row)
+    }
+}
@@ -0,0 +1,23 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate pathfinder;
+
+use pathfinder::charmap::CodepointRange;
+use pathfinder::otf::FontData;
+use std::u32;
+
+fuzz_target!(|data: &[u8]| {
+    let font = FontData::new(data);
+    if let Ok(cmap) = font.cmap_table() {
+        // The whole BMP, a little of the astral planes, and the very end of the code space.
+        let _ = cmap.glyph_ranges_for_codepoint_ranges(&[
+            CodepointRange::new(0, 0x10010),
+            CodepointRange::new(u32::MAX - 1, u32::MAX),
+        ]);
+    }
+});
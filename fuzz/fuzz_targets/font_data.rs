@@ -0,0 +1,71 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate pathfinder;
+
+use pathfinder::otf::FontData;
+use pathfinder::otf::font::Font;
+use pathfinder::otf::loader;
+
+// Faces past this are skipped so that a huge collection header doesn't stall the fuzzer.
+const MAX_FACES: u32 = 8;
+
+fn exercise(font: &FontData) {
+    if let Ok(records) = font.table_records() {
+        for record in records {
+            let _ = font.table(record.tag);
+        }
+    }
+    let _ = font.verify_checksums();
+
+    let _ = font.cbdt_table();
+    let _ = font.cblc_table();
+    let _ = font.cmap_table();
+    let _ = font.colr_table();
+    let _ = font.cpal_table();
+    let _ = font.cvt_table().map(|cvt| cvt.values());
+    let _ = font.ebdt_table();
+    let _ = font.eblc_table();
+    let _ = font.ebsc_table().map(|ebsc| ebsc.scales());
+    let _ = font.fpgm_table();
+    let _ = font.gasp_table().map(|gasp| gasp.ranges());
+    let _ = font.glyf_table();
+    let _ = font.prep_table();
+    let _ = font.svg_table();
+    if let Ok(head) = font.head_table() {
+        let _ = font.loca_table(&head);
+    }
+    if let Ok(hhea) = font.hhea_table() {
+        let _ = font.hmtx_table(&hhea);
+    }
+    if let Ok(maxp) = font.maxp_table() {
+        let _ = font.sbix_table(&maxp);
+    }
+    if let Ok(vhea) = font.vhea_table() {
+        let _ = font.vmtx_table(&vhea);
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    exercise(&FontData::new(data));
+
+    if let Ok(font) = loader::load_font(data) {
+        for index in 0..font.face_count().min(MAX_FACES) {
+            if let Ok(face) = font.face(index) {
+                exercise(&face)
+            }
+        }
+    }
+
+    if let Ok(font) = Font::new(data.to_vec()) {
+        let _ = font.head_table();
+        let _ = font.loca_table();
+        let _ = font.hmtx_table();
+        let _ = font.vmtx_table();
+        let _ = font.sbix_table();
+    }
+});
@@ -0,0 +1,32 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate pathfinder;
+
+use pathfinder::otf::FontData;
+
+fuzz_target!(|data: &[u8]| {
+    let font = FontData::new(data);
+    let (glyf, head) = match (font.glyf_table(), font.head_table()) {
+        (Ok(glyf), Ok(head)) => (glyf, head),
+        _ => return,
+    };
+    let loca = match font.loca_table(&head) {
+        Ok(loca) => loca,
+        Err(_) => return,
+    };
+
+    // `loca` bounds the glyph IDs, so walk them until it runs out.
+    let mut glyph_id = 0;
+    while loca.location_of(glyph_id).is_ok() {
+        let _ = glyf.for_each_point(&loca, glyph_id, |_| {});
+        let _ = glyf.path_commands(&loca, glyph_id);
+        let _ = glyf.simple_glyph(&loca, glyph_id);
+        let _ = glyf.bounding_rect(&loca, glyph_id);
+        glyph_id += 1
+    }
+});
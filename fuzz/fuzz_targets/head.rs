@@ -0,0 +1,17 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate pathfinder;
+
+use pathfinder::otf::FontData;
+
+fuzz_target!(|data: &[u8]| {
+    let font = FontData::new(data);
+    let _ = font.head_table();
+    // Exercises the `checkSumAdjustment` lookup in `head` as well as the table directory.
+    let _ = font.verify_checksums();
+});
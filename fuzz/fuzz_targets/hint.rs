@@ -0,0 +1,34 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate pathfinder;
+
+use pathfinder::hinting::Hinter;
+use pathfinder::otf::FontData;
+
+fuzz_target!(|data: &[u8]| {
+    let font = FontData::new(data);
+    let num_glyphs = match font.maxp_table() {
+        Ok(maxp) => maxp.num_glyphs,
+        Err(_) => return,
+    };
+    let mut hinter = match Hinter::new(&font) {
+        Ok(hinter) => hinter,
+        Err(_) => return,
+    };
+
+    // A small monochrome size and a larger grayscale one take different paths through the
+    // font's programs.
+    for &(pixels_per_em, grayscale) in &[(9, false), (24, true)] {
+        if hinter.set_size(pixels_per_em, grayscale).is_err() {
+            continue
+        }
+        for glyph_id in 0..num_glyphs {
+            let _ = hinter.hint_glyph(glyph_id);
+        }
+    }
+});
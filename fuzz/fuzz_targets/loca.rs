@@ -0,0 +1,25 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate pathfinder;
+
+use pathfinder::otf::FontData;
+use std::u32;
+
+fuzz_target!(|data: &[u8]| {
+    let font = FontData::new(data);
+    let loca = match font.head_table().and_then(|head| font.loca_table(&head)) {
+        Ok(loca) => loca,
+        Err(_) => return,
+    };
+
+    let mut glyph_id = 0;
+    while loca.location_of(glyph_id).is_ok() {
+        glyph_id += 1
+    }
+    let _ = loca.location_of(u32::MAX);
+});
@@ -0,0 +1,18 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate pathfinder;
+
+use pathfinder::otf::FontData;
+use pathfinder::otf::sanitizer;
+
+fuzz_target!(|data: &[u8]| {
+    // Whatever the sanitizer produces must itself come through sanitizing unchanged.
+    if let Ok(sanitized) = sanitizer::sanitize(&FontData::new(data)) {
+        assert_eq!(sanitizer::sanitize(&FontData::new(&sanitized)).as_ref(), Ok(&sanitized));
+    }
+});
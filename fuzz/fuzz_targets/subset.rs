@@ -0,0 +1,43 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate pathfinder;
+
+use pathfinder::error::Error;
+use pathfinder::otf::FontData;
+use pathfinder::otf::subset::SubsetBuilder;
+
+fn points(font: &FontData, glyph_id: u16) -> Result<Vec<(i16, i16, bool)>, Error> {
+    let head = try!(font.head_table());
+    let loca = try!(font.loca_table(&head));
+    let glyf = try!(font.glyf_table());
+    let mut points = vec![];
+    try!(glyf.for_each_point(&loca, glyph_id as u32, |point| {
+        points.push((point.position.x, point.position.y, point.on_curve))
+    }));
+    Ok(points)
+}
+
+fuzz_target!(|data: &[u8]| {
+    let font = FontData::new(data);
+    let mut builder = SubsetBuilder::new();
+    for codepoint in 0x20..0x80 {
+        builder.add_codepoint(codepoint)
+    }
+    builder.add_glyph_id(1);
+
+    // Every glyph of a subset has the outline it had in the original font.
+    if let Ok(subset) = builder.build(&font) {
+        let subset_font = FontData::new(&subset.data);
+        for (glyph_id, original_glyph_id) in subset.original_glyph_ids.iter().enumerate() {
+            if let Some(original_glyph_id) = *original_glyph_id {
+                assert_eq!(points(&subset_font, glyph_id as u16),
+                           points(&font, original_glyph_id));
+            }
+        }
+    }
+});
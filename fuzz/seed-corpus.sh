@@ -0,0 +1,19 @@
+#!/bin/sh
+#
+# Seeds the corpus of every fuzz target with the bundled Nimbus Sans. Run it once from anywhere,
+# then fuzz with `cargo fuzz run <target>` from the repository root, for example:
+#
+#     ./fuzz/seed-corpus.sh && cargo fuzz run glyf
+#
+# Targets: font_data, cmap, glyf, loca, head, sanitize, subset, hint. Crashing inputs end up in
+# `fuzz/artifacts/`; once a fix lands, add a regression test for the input under `src/tests/`.
+
+set -e
+
+FUZZ_DIR=$(cd "$(dirname "$0")" && pwd)
+SEED="$FUZZ_DIR/../resources/tests/nimbus-sans/NimbusSanL-Regu.ttf"
+
+for target in font_data cmap glyf loca head sanitize subset hint; do
+    mkdir -p "$FUZZ_DIR/corpus/$target"
+    cp "$SEED" "$FUZZ_DIR/corpus/$target/nimbus-sans.ttf"
+done
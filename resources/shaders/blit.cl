@@ -0,0 +1,39 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Copies embedded bitmap glyphs into the output atlas.
+//
+// Bitmaps already have their coverage, so they skip the draw and accumulation stages. This runs
+// after accumulation, which writes every pixel of the atlas, so that the bitmaps aren't
+// overwritten.
+
+struct BitmapDescriptor {
+    uint2 atlasPosition;
+    uint2 size;
+    uint startPixel;
+};
+
+typedef struct BitmapDescriptor BitmapDescriptor;
+
+__kernel void blit(__global const BitmapDescriptor *gBitmaps,
+                   __global const uchar *gBitmapPixels,
+                   __write_only image2d_t gTexture) {
+    BitmapDescriptor bitmap = gBitmaps[get_global_id(0)];
+
+    for (uint y = 0; y < bitmap.size.y; y++) {
+        for (uint x = 0; x < bitmap.size.x; x++) {
+            uint coverage = gBitmapPixels[bitmap.startPixel + y * bitmap.size.x + x];
+
+            // Store coverage inverted in the red channel, as the accumulation stage does.
+            int2 point = convert_int2(bitmap.atlasPosition + (uint2)(x, y));
+            write_imageui(gTexture, point, (uint4)(255 - coverage, 255, 255, 255));
+        }
+    }
+}
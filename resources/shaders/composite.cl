@@ -0,0 +1,73 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Composites the layers of color glyphs into an RGBA atlas.
+//
+// Each layer's coverage has already been accumulated into the grayscale atlas. Working bottom to
+// top, we fill each layer's coverage with its color and blend it over the layers beneath using
+// the Porter-Duff "source over" operator, in premultiplied alpha.
+
+struct ColorImageDescriptor {
+    uint2 atlasPosition;
+    uint2 size;
+    uint startLayer;
+    uint layerCount;
+};
+
+typedef struct ColorImageDescriptor ColorImageDescriptor;
+
+struct ColorLayerDescriptor {
+    uint2 coveragePosition;
+    uint2 offset;
+    uint2 size;
+    uint color;
+};
+
+typedef struct ColorLayerDescriptor ColorLayerDescriptor;
+
+// Unpacks an R, G, B, A color (red in the low byte) and premultiplies it.
+float4 unpackColor(uint color) {
+    float4 unpacked = convert_float4((uint4)(color & 0xff,
+                                             (color >> 8) & 0xff,
+                                             (color >> 16) & 0xff,
+                                             color >> 24)) / 255.0f;
+    return (float4)(unpacked.xyz * unpacked.w, unpacked.w);
+}
+
+__kernel void composite(__global const ColorImageDescriptor *gColorImages,
+                        __global const ColorLayerDescriptor *gColorLayers,
+                        __read_only image2d_t gCoverage,
+                        __write_only image2d_t gColor) {
+    ColorImageDescriptor image = gColorImages[get_global_id(0)];
+
+    for (uint y = 0; y < image.size.y; y++) {
+        for (uint x = 0; x < image.size.x; x++) {
+            uint2 point = (uint2)(x, y);
+            float4 color = (float4)(0.0f);
+
+            for (uint layerIndex = 0; layerIndex < image.layerCount; layerIndex++) {
+                ColorLayerDescriptor layer = gColorLayers[image.startLayer + layerIndex];
+                if (any(point < layer.offset) || any(point >= layer.offset + layer.size))
+                    continue;
+
+                // The accumulation stage stores coverage inverted in the red channel.
+                uint2 coveragePoint = layer.coveragePosition + point - layer.offset;
+                uint4 pixel = read_imageui(gCoverage, convert_int2(coveragePoint));
+                float coverage = 1.0f - convert_float(pixel.x) / 255.0f;
+
+                float4 layerColor = unpackColor(layer.color) * coverage;
+                color = layerColor + color * (1.0f - layerColor.w);
+            }
+
+            uint4 rgba = convert_uint4_sat_rte(color * 255.0f);
+            write_imageui(gColor, convert_int2(image.atlasPosition + point), rgba);
+        }
+    }
+}
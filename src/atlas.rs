@@ -8,9 +8,11 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use error::Error;
 use euclid::{Point2D, Rect, Size2D};
 
 /// TODO(pcwalton): Track width of last shelf.
+#[derive(Clone)]
 pub struct Atlas {
     free_rects: Vec<Rect<u32>>,
     available_width: u32,
@@ -29,7 +31,13 @@ impl Atlas {
         }
     }
 
-    pub fn place(&mut self, size: &Size2D<u32>) -> Result<Point2D<u32>, ()> {
+    /// Reserves space for an image of the given size and returns its origin. Fails with
+    /// `AtlasFull` if the image is wider than the atlas or taller than a shelf.
+    pub fn place(&mut self, size: &Size2D<u32>) -> Result<Point2D<u32>, Error> {
+        if size.width > self.available_width || size.height > self.shelf_height {
+            return Err(Error::AtlasFull)
+        }
+
         let chosen_index_and_rect =
             self.free_rects
                 .iter()
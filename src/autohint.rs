@@ -0,0 +1,524 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A light automatic hinter, in the style of FreeType's "light" autohinting mode.
+//!
+//! Only vertical positions are changed. The hinter measures the font's blue zones (the heights
+//! that many glyphs share, like the baseline and the x-height) from the outlines of reference
+//! characters, finds the horizontal edges and stems of each glyph, snaps them to the pixel grid,
+//! and interpolates the remaining points between them.
+//!
+//! It works on outlines in font units from any source, like `GlyfTable::path_commands` or a
+//! PostScript font's charstrings, and returns them hinted, still in font units.
+
+use euclid::Point2D;
+use outline::PathCommand;
+use std::cmp::Ordering;
+use std::f32;
+
+// The characters that each blue zone is measured from, as (flat characters, round characters).
+// Round characters overshoot the zone slightly.
+const CAP_HEIGHT_CHARACTERS: (&'static str, &'static str) = ("THEZ", "OCQS");
+const X_HEIGHT_CHARACTERS: (&'static str, &'static str) = ("xzvw", "oesc");
+const BASELINE_CHARACTERS: (&'static str, &'static str) = ("HEZLxz", "OCUSos");
+const DESCENDER_CHARACTERS: (&'static str, &'static str) = ("pq", "gj");
+
+// These tolerances are fractions of the em.
+//
+// How far a point may stray from a horizontal segment's height and still belong to it.
+const SEGMENT_FLATNESS: f32 = 1.0 / 256.0;
+// The shortest horizontal segment that counts as an edge.
+const MINIMUM_SEGMENT_LENGTH: f32 = 1.0 / 40.0;
+// How close segments must be to merge into one edge.
+const EDGE_MERGE_DISTANCE: f32 = 1.0 / 100.0;
+// The thickest stroke that counts as a stem.
+const MAXIMUM_STEM_WIDTH: f32 = 1.0 / 4.0;
+// How far outside a blue zone an edge may be and still snap to it.
+const BLUE_ZONE_FUZZ: f32 = 1.0 / 64.0;
+
+/// The heights that blue zones mark.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlueZoneKind {
+    /// The top of capital letters, like "H".
+    CapHeight,
+    /// The top of lowercase letters without ascenders, like "x".
+    XHeight,
+    /// The bottom of letters without descenders.
+    Baseline,
+    /// The bottom of lowercase descenders, like in "p".
+    Descender,
+}
+
+impl BlueZoneKind {
+    /// Returns true if this zone aligns the tops of shapes rather than their bottoms.
+    #[inline]
+    pub fn is_top(self) -> bool {
+        match self {
+            BlueZoneKind::CapHeight | BlueZoneKind::XHeight => true,
+            BlueZoneKind::Baseline | BlueZoneKind::Descender => false,
+        }
+    }
+}
+
+/// A range of heights that edges snap to, in font units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlueZone {
+    pub kind: BlueZoneKind,
+    /// The height of flat shapes, like the top of "x".
+    pub reference: f32,
+    /// The height of round shapes, like the top of "o", which overshoot the reference slightly.
+    pub overshoot: f32,
+}
+
+/// The blue zones of a font.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlueZones {
+    pub zones: Vec<BlueZone>,
+}
+
+impl BlueZones {
+    /// Measures the blue zones from the outlines of reference characters.
+    ///
+    /// `outline` returns the outline of a character in font units, or `None` if the font doesn't
+    /// have it. Zones whose flat reference characters are all missing are left out.
+    pub fn detect<F>(mut outline: F) -> BlueZones
+                     where F: FnMut(char) -> Option<Vec<PathCommand>> {
+        let mut zones = vec![];
+        for &(kind, characters) in &[(BlueZoneKind::CapHeight, CAP_HEIGHT_CHARACTERS),
+                                     (BlueZoneKind::XHeight, X_HEIGHT_CHARACTERS),
+                                     (BlueZoneKind::Baseline, BASELINE_CHARACTERS),
+                                     (BlueZoneKind::Descender, DESCENDER_CHARACTERS)] {
+            let (flat_characters, round_characters) = characters;
+            let reference = match average_extreme(&mut outline, flat_characters, kind.is_top()) {
+                Some(reference) => reference,
+                None => continue,
+            };
+            let overshoot = average_extreme(&mut outline, round_characters, kind.is_top());
+
+            // Round shapes that don't overshoot don't need their own height.
+            let overshoot = match overshoot {
+                Some(overshoot) if kind.is_top() && overshoot > reference => overshoot,
+                Some(overshoot) if !kind.is_top() && overshoot < reference => overshoot,
+                _ => reference,
+            };
+
+            zones.push(BlueZone {
+                kind: kind,
+                reference: reference,
+                overshoot: overshoot,
+            })
+        }
+        BlueZones {
+            zones: zones,
+        }
+    }
+}
+
+// Returns the average of the highest (or lowest) on-curve points of the given characters.
+fn average_extreme<F>(outline: &mut F, characters: &str, top: bool) -> Option<f32>
+                      where F: FnMut(char) -> Option<Vec<PathCommand>> {
+    let (mut sum, mut count) = (0.0, 0);
+    for character in characters.chars() {
+        let commands = match outline(character) {
+            Some(commands) => commands,
+            None => continue,
+        };
+        let extreme = commands.iter().filter_map(|command| {
+            match *command {
+                PathCommand::MoveTo(point) |
+                PathCommand::LineTo(point) |
+                PathCommand::QuadTo(_, point) |
+                PathCommand::CurveTo(_, _, point) => Some(point.y),
+                PathCommand::ClosePath => None,
+            }
+        }).fold(None, |extreme: Option<f32>, y| {
+            match extreme {
+                Some(extreme) if top => Some(extreme.max(y)),
+                Some(extreme) => Some(extreme.min(y)),
+                None => Some(y),
+            }
+        });
+        if let Some(extreme) = extreme {
+            sum += extreme;
+            count += 1
+        }
+    }
+    if count == 0 {
+        None
+    } else {
+        Some(sum / count as f32)
+    }
+}
+
+/// Grid-fits outlines vertically at a given size.
+#[derive(Clone, Debug)]
+pub struct AutoHinter {
+    units_per_em: f32,
+    blue_zones: BlueZones,
+}
+
+impl AutoHinter {
+    #[inline]
+    pub fn new(units_per_em: u16, blue_zones: BlueZones) -> AutoHinter {
+        AutoHinter {
+            units_per_em: units_per_em as f32,
+            blue_zones: blue_zones,
+        }
+    }
+
+    #[inline]
+    pub fn blue_zones(&self) -> &BlueZones {
+        &self.blue_zones
+    }
+
+    /// Returns the outline with its horizontal edges moved onto pixel boundaries at the given
+    /// size. The commands are in font units, both before and after.
+    pub fn hint(&self, commands: &[PathCommand], pixels_per_em: f32) -> Vec<PathCommand> {
+        if !(pixels_per_em > 0.0) || !(self.units_per_em > 0.0) {
+            return commands.to_vec()
+        }
+        let scale = pixels_per_em / self.units_per_em;
+
+        let outline = PointOutline::new(commands);
+        let segments = self.find_segments(&outline);
+        let mut edges = self.build_edges(&segments);
+        self.fit_edges(&mut edges, scale);
+
+        // Snap the points on the fitted edges, and interpolate the rest between them.
+        let mut anchors: Vec<(f32, f32)> = edges.iter().filter_map(|edge| {
+            edge.fitted.map(|fitted| (edge.position, fitted / scale))
+        }).collect();
+        anchors.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+        let mut hinted_y: Vec<_> = outline.points.iter().map(|point| {
+            interpolate(&anchors, point.y)
+        }).collect();
+        for edge in &edges {
+            if let Some(fitted) = edge.fitted {
+                for &segment_index in &edge.segments {
+                    for &point_index in &segments[segment_index].points {
+                        hinted_y[point_index] = fitted / scale
+                    }
+                }
+            }
+        }
+
+        outline.rebuild(commands, &hinted_y)
+    }
+
+    fn find_segments(&self, outline: &PointOutline) -> Vec<Segment> {
+        let flatness = SEGMENT_FLATNESS * self.units_per_em;
+        let minimum_length = MINIMUM_SEGMENT_LENGTH * self.units_per_em;
+
+        // Outer contours wind counterclockwise in PostScript fonts and clockwise in TrueType
+        // fonts. That, and the direction of a segment, tells which side the ink is on.
+        let counterclockwise = outline.signed_area() >= 0.0;
+
+        let mut segments = vec![];
+        for contour in &outline.contours {
+            let points = &outline.points[contour.clone()];
+            let count = points.len();
+            if count < 2 {
+                continue
+            }
+
+            // Start at a point that doesn't continue a flat run from its predecessor.
+            let start = match (0..count).find(|&index| {
+                (points[index].y - points[(index + count - 1) % count].y).abs() > flatness
+            }) {
+                Some(start) => start,
+                None => continue,
+            };
+
+            let mut offset = 0;
+            while offset < count {
+                let first = (start + offset) % count;
+                let mut run = vec![first];
+                offset += 1;
+                while offset < count {
+                    let index = (start + offset) % count;
+                    if (points[index].y - points[first].y).abs() > flatness {
+                        break
+                    }
+                    run.push(index);
+                    offset += 1
+                }
+
+                let (first_x, last_x) = (points[run[0]].x, points[run[run.len() - 1]].x);
+                if (last_x - first_x).abs() < minimum_length {
+                    continue
+                }
+                let (min_x, max_x) = run.iter().fold((f32::INFINITY, f32::NEG_INFINITY),
+                                                     |(min_x, max_x), &index| {
+                    (min_x.min(points[index].x), max_x.max(points[index].x))
+                });
+                let position = run.iter().map(|&index| points[index].y).sum::<f32>() /
+                    run.len() as f32;
+                segments.push(Segment {
+                    position: position,
+                    min_x: min_x,
+                    max_x: max_x,
+                    ink_above: (last_x > first_x) == counterclockwise,
+                    points: run.iter().map(|&index| contour.start + index).collect(),
+                })
+            }
+        }
+        segments
+    }
+
+    fn build_edges(&self, segments: &[Segment]) -> Vec<Edge> {
+        let merge_distance = EDGE_MERGE_DISTANCE * self.units_per_em;
+
+        let mut order: Vec<_> = (0..segments.len()).collect();
+        order.sort_by(|&a, &b| {
+            segments[a].position.partial_cmp(&segments[b].position).unwrap_or(Ordering::Equal)
+        });
+
+        let mut edges: Vec<Edge> = vec![];
+        for segment_index in order {
+            let segment = &segments[segment_index];
+            let length = segment.max_x - segment.min_x;
+            if let Some(edge) = edges.iter_mut().rev().find(|edge| {
+                edge.ink_above == segment.ink_above &&
+                    (segment.position - edge.position).abs() <= merge_distance
+            }) {
+                // Weight the position by length, so that long segments win.
+                edge.position = (edge.position * edge.length + segment.position * length) /
+                    (edge.length + length);
+                edge.length += length;
+                edge.min_x = edge.min_x.min(segment.min_x);
+                edge.max_x = edge.max_x.max(segment.max_x);
+                edge.segments.push(segment_index);
+                continue
+            }
+            edges.push(Edge {
+                position: segment.position,
+                length: length,
+                min_x: segment.min_x,
+                max_x: segment.max_x,
+                ink_above: segment.ink_above,
+                segments: vec![segment_index],
+                fitted: None,
+            })
+        }
+        edges.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap_or(Ordering::Equal));
+        edges
+    }
+
+    fn fit_edges(&self, edges: &mut [Edge], scale: f32) {
+        let fuzz = BLUE_ZONE_FUZZ * self.units_per_em;
+
+        // Snap edges in blue zones first. Round shapes keep at most a pixel of overshoot, and
+        // none at all at sizes where it would be less than half a pixel.
+        for edge in edges.iter_mut() {
+            let zone = self.blue_zones.zones.iter().find(|zone| {
+                let (low, high) = if zone.reference < zone.overshoot {
+                    (zone.reference, zone.overshoot)
+                } else {
+                    (zone.overshoot, zone.reference)
+                };
+                zone.kind.is_top() != edge.ink_above && edge.position >= low - fuzz &&
+                    edge.position <= high + fuzz
+            });
+            if let Some(zone) = zone {
+                let reference = (zone.reference * scale).round();
+                let overshoot = (zone.overshoot - zone.reference) * scale;
+                let is_round = (edge.position - zone.overshoot).abs() <
+                    (edge.position - zone.reference).abs();
+                edge.fitted = Some(if is_round && overshoot.abs() >= 0.5 {
+                    reference + overshoot.signum()
+                } else {
+                    reference
+                })
+            }
+        }
+
+        // Pair each bottom edge with the nearest top edge above it that overlaps it, and keep
+        // the closest pair for each top edge.
+        let maximum_stem_width = MAXIMUM_STEM_WIDTH * self.units_per_em;
+        let mut partners: Vec<Option<usize>> = vec![None; edges.len()];
+        for bottom in 0..edges.len() {
+            if !edges[bottom].ink_above {
+                continue
+            }
+            let top = ((bottom + 1)..edges.len()).find(|&top| {
+                !edges[top].ink_above &&
+                    edges[top].position - edges[bottom].position <= maximum_stem_width &&
+                    edges[top].min_x < edges[bottom].max_x &&
+                    edges[bottom].min_x < edges[top].max_x
+            });
+            if let Some(top) = top {
+                let width = edges[top].position - edges[bottom].position;
+                match partners[top] {
+                    Some(other) if edges[top].position - edges[other].position <= width => {}
+                    _ => {
+                        if let Some(other) = partners[top] {
+                            partners[other] = None
+                        }
+                        partners[top] = Some(bottom);
+                        partners[bottom] = Some(top)
+                    }
+                }
+            }
+        }
+
+        // Round stem widths to whole pixels, and place them next to their blue edges, or
+        // else centered on where they were.
+        for bottom in 0..edges.len() {
+            let top = match partners[bottom] {
+                Some(top) if top > bottom => top,
+                _ => continue,
+            };
+            let width = (edges[top].position - edges[bottom].position) * scale;
+            let fitted_width = width.round().max(1.0);
+            match (edges[bottom].fitted, edges[top].fitted) {
+                (Some(_), Some(_)) => {}
+                (Some(bottom_position), None) => {
+                    edges[top].fitted = Some(bottom_position + fitted_width)
+                }
+                (None, Some(top_position)) => {
+                    edges[bottom].fitted = Some(top_position - fitted_width)
+                }
+                (None, None) => {
+                    let center = (edges[bottom].position + edges[top].position) * 0.5 * scale;
+                    let bottom_position = (center - fitted_width * 0.5).round();
+                    edges[bottom].fitted = Some(bottom_position);
+                    edges[top].fitted = Some(bottom_position + fitted_width)
+                }
+            }
+        }
+    }
+}
+
+// Maps an original height to a hinted one by interpolating between the fitted edges around it,
+// or shifting along with the nearest one outside them.
+fn interpolate(anchors: &[(f32, f32)], y: f32) -> f32 {
+    let upper = match anchors.iter().position(|&(position, _)| position >= y) {
+        Some(upper) => upper,
+        None => {
+            return match anchors.last() {
+                Some(&(position, fitted)) => y + fitted - position,
+                None => y,
+            }
+        }
+    };
+    let (upper_position, upper_fitted) = anchors[upper];
+    if upper == 0 || upper_position == y {
+        return y + upper_fitted - upper_position
+    }
+    let (lower_position, lower_fitted) = anchors[upper - 1];
+    let t = (y - lower_position) / (upper_position - lower_position);
+    lower_fitted + (upper_fitted - lower_fitted) * t
+}
+
+// A run of nearly horizontal points along a contour.
+#[derive(Clone, Debug)]
+struct Segment {
+    position: f32,
+    min_x: f32,
+    max_x: f32,
+    // Whether the inside of the shape is above the segment, making it a bottom edge.
+    ink_above: bool,
+    points: Vec<usize>,
+}
+
+// Segments at the same height with the ink on the same side.
+#[derive(Clone, Debug)]
+struct Edge {
+    position: f32,
+    length: f32,
+    min_x: f32,
+    max_x: f32,
+    ink_above: bool,
+    segments: Vec<usize>,
+    // The grid-fitted position in pixels.
+    fitted: Option<f32>,
+}
+
+// The points of an outline, including control points, in contour order.
+struct PointOutline {
+    points: Vec<Point2D<f32>>,
+    contours: Vec<::std::ops::Range<usize>>,
+}
+
+impl PointOutline {
+    fn new(commands: &[PathCommand]) -> PointOutline {
+        let mut outline = PointOutline {
+            points: vec![],
+            contours: vec![],
+        };
+        let mut contour_start = 0;
+        for command in commands {
+            match *command {
+                PathCommand::MoveTo(point) => {
+                    outline.close_contour(contour_start);
+                    contour_start = outline.points.len();
+                    outline.points.push(point)
+                }
+                PathCommand::LineTo(point) => outline.points.push(point),
+                PathCommand::QuadTo(control_point, point) => {
+                    outline.points.push(control_point);
+                    outline.points.push(point)
+                }
+                PathCommand::CurveTo(control_point_0, control_point_1, point) => {
+                    outline.points.push(control_point_0);
+                    outline.points.push(control_point_1);
+                    outline.points.push(point)
+                }
+                PathCommand::ClosePath => {}
+            }
+        }
+        outline.close_contour(contour_start);
+        outline
+    }
+
+    fn close_contour(&mut self, start: usize) {
+        if start < self.points.len() {
+            self.contours.push(start..self.points.len())
+        }
+    }
+
+    // Twice the signed area enclosed by the contours, treating control points as vertices.
+    fn signed_area(&self) -> f32 {
+        self.contours.iter().map(|contour| {
+            let points = &self.points[contour.clone()];
+            (0..points.len()).map(|index| {
+                let (a, b) = (points[index], points[(index + 1) % points.len()]);
+                a.x * b.y - b.x * a.y
+            }).sum::<f32>()
+        }).sum()
+    }
+
+    // Replaces the heights of the points in the commands they came from.
+    fn rebuild(&self, commands: &[PathCommand], hinted_y: &[f32]) -> Vec<PathCommand> {
+        let mut index = 0;
+        let mut next = || {
+            let point = Point2D::new(self.points[index].x, hinted_y[index]);
+            index += 1;
+            point
+        };
+        commands.iter().map(|command| {
+            match *command {
+                PathCommand::MoveTo(_) => PathCommand::MoveTo(next()),
+                PathCommand::LineTo(_) => PathCommand::LineTo(next()),
+                PathCommand::QuadTo(..) => {
+                    let control_point = next();
+                    PathCommand::QuadTo(control_point, next())
+                }
+                PathCommand::CurveTo(..) => {
+                    let control_point_0 = next();
+                    let control_point_1 = next();
+                    PathCommand::CurveTo(control_point_0, control_point_1, next())
+                }
+                PathCommand::ClosePath => PathCommand::ClosePath,
+            }
+        }).collect()
+    }
+}
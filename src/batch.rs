@@ -9,9 +9,17 @@
 // except according to those terms.
 
 use atlas::Atlas;
+use bitmap::{BitmapGlyph, EmbeddedBitmaps};
+pub use charmap::{GlyphRange, GlyphRangeIter};
 use compute_shader::buffer::{Buffer, BufferData, HostAllocatedData, Protection};
 use compute_shader::device::Device;
+use error::Error;
+use euclid::{Point2D, Rect, Size2D};
 use glyph_buffer::GlyphBufferBuilder;
+use limits::{Limit, Limits};
+use otf::cpal::Color;
+use paint::ColorGlyphImage;
+use software_rasterizer::RgbaImage;
 use std::u16;
 
 const POINTS_PER_SEGMENT: u32 = 32;
@@ -21,6 +29,22 @@ pub struct BatchBuilder {
     pub indices: Vec<u16>,
     pub images: Vec<ImageDescriptor>,
     pub point_count: u32,
+    /// Where color glyphs are composited. Their layers' coverage goes in `atlas` like any other
+    /// glyph's.
+    pub color_atlas: Atlas,
+    pub color_images: Vec<ColorImageDescriptor>,
+    pub color_layers: Vec<ColorLayerDescriptor>,
+    /// Embedded bitmap glyphs, which `Rasterizer::draw_atlas()` copies into `atlas` after
+    /// rasterizing the outlines.
+    pub bitmaps: Vec<BitmapDescriptor>,
+    /// The coverage of every bitmap in `bitmaps`, one byte per pixel, each in row-major order.
+    pub bitmap_pixels: Vec<u8>,
+    /// If set, `add_glyph_or_bitmap()` uses embedded bitmaps at the sizes they exist for instead
+    /// of rasterizing outlines.
+    pub use_embedded_bitmaps: bool,
+    /// Glyphs that would take the batch past these fail with `Error::LimitExceeded`, leaving
+    /// the batch as it was.
+    pub limits: Limits,
 }
 
 impl BatchBuilder {
@@ -32,6 +56,13 @@ impl BatchBuilder {
             indices: vec![],
             images: vec![],
             point_count: 0,
+            color_atlas: Atlas::new(available_width, shelf_height),
+            color_images: vec![],
+            color_layers: vec![],
+            bitmaps: vec![],
+            bitmap_pixels: vec![],
+            use_embedded_bitmaps: false,
+            limits: Limits::default(),
         }
     }
 
@@ -39,12 +70,188 @@ impl BatchBuilder {
                      glyph_buffer_builder: &GlyphBufferBuilder,
                      glyph_index: u32,
                      point_size: f32)
-                     -> Result<(), ()> {
-        let descriptor = &glyph_buffer_builder.descriptors[glyph_index as usize];
+                     -> Result<(), Error> {
+        self.add_image(glyph_buffer_builder, glyph_index, point_size).map(drop)
+    }
+
+    /// Adds a color glyph made of the given layers, bottommost first. Each layer's coverage is
+    /// rendered into `atlas` as usual, and `Rasterizer::composite_color_atlas()` then blends the
+    /// layers, filled with their colors, into `color_atlas`.
+    pub fn add_color_glyph(&mut self,
+                           glyph_buffer_builder: &GlyphBufferBuilder,
+                           layers: &[ColorGlyphLayer],
+                           point_size: f32)
+                           -> Result<(), Error> {
+        if layers.is_empty() {
+            return Err(Error::InvalidArgument)
+        }
+
+        // The color glyph covers the union of its layers. Check the layers against the limits
+        // up front, so that a glyph that exceeds them adds nothing.
+        let mut layer_rects = Vec::with_capacity(layers.len());
+        let mut point_count = self.point_count;
+        for layer in layers {
+            let descriptor = try!(glyph_buffer_builder.descriptors
+                                                      .get(layer.glyph_index as usize)
+                                                      .ok_or(Error::InvalidArgument));
+            let layer_rect = descriptor.pixel_rect(point_size);
+            try!(self.image_size(&layer_rect.size));
+            point_count = point_count.saturating_add(descriptor.point_count as u32);
+            layer_rects.push(layer_rect)
+        }
+        try!(self.limits.check(Limit::BatchPoints, point_count));
+
+        // Either atlas can still fill up, so undo the layers' coverage images if it does.
+        let (atlas, color_atlas) = (self.atlas.clone(), self.color_atlas.clone());
+        let (index_count, image_count) = (self.indices.len(), self.images.len());
+        let point_count = self.point_count;
+        let result = self.add_color_layers(glyph_buffer_builder, layers, &layer_rects, point_size);
+        if result.is_err() {
+            self.atlas = atlas;
+            self.color_atlas = color_atlas;
+            self.indices.truncate(index_count);
+            self.images.truncate(image_count);
+            self.point_count = point_count;
+        }
+        result
+    }
+
+    // Places the coverage images of a color glyph's layers and then its color image, and only
+    // then records the descriptors, so that the layers of every color image exist.
+    fn add_color_layers(&mut self,
+                        glyph_buffer_builder: &GlyphBufferBuilder,
+                        layers: &[ColorGlyphLayer],
+                        layer_rects: &[Rect<f32>],
+                        point_size: f32)
+                        -> Result<(), Error> {
+        let mut coverage_origins = Vec::with_capacity(layers.len());
+        for layer in layers {
+            coverage_origins.push(try!(self.add_image(glyph_buffer_builder,
+                                                      layer.glyph_index,
+                                                      point_size)))
+        }
+
+        let bounds = layer_rects.iter().skip(1).fold(layer_rects[0], |bounds, rect| {
+            bounds.union(rect)
+        });
+        let pixel_size = try!(self.image_size(&bounds.size));
+        let atlas_origin = try!(self.color_atlas.place(&pixel_size));
+
+        let mut layer_descriptors = Vec::with_capacity(layers.len());
+        for ((layer, layer_rect), coverage_origin) in
+                layers.iter().zip(layer_rects.iter()).zip(coverage_origins) {
+            let layer_size = try!(self.image_size(&layer_rect.size));
+
+            // Atlas rows run top to bottom, so measure the vertical offset from the top.
+            layer_descriptors.push(ColorLayerDescriptor {
+                coverage_x: coverage_origin.x,
+                coverage_y: coverage_origin.y,
+                offset_x: (layer_rect.origin.x - bounds.origin.x).floor() as u32,
+                offset_y: (bounds.max_y() - layer_rect.max_y()).floor() as u32,
+                width: layer_size.width,
+                height: layer_size.height,
+                color: layer.color.to_packed_rgba(),
+                padding: 0,
+            })
+        }
+
+        self.color_images.push(ColorImageDescriptor {
+            atlas_x: atlas_origin.x,
+            atlas_y: atlas_origin.y,
+            width: pixel_size.width,
+            height: pixel_size.height,
+            start_layer: self.color_layers.len() as u32,
+            layer_count: layers.len() as u32,
+        });
+        self.color_layers.extend(layer_descriptors);
+        Ok(())
+    }
 
-        // FIXME(pcwalton): I think this will check for negative values and panic, which is
-        // unnecessary.
-        let pixel_size = descriptor.pixel_rect(point_size).size.ceil().cast().unwrap();
+    /// Adds an embedded bitmap glyph, scaled from its strike to the given size if necessary.
+    ///
+    /// Bitmap glyphs need no rasterization, so the image is copied straight into
+    /// `color_atlas_pixels`, the CPU-side contents of the color atlas, at the position
+    /// `color_atlas` assigns it. That position is returned.
+    pub fn add_bitmap_glyph(&mut self,
+                            glyph: &BitmapGlyph,
+                            pixels_per_em: f32,
+                            color_atlas_pixels: &mut RgbaImage)
+                            -> Result<Point2D<u32>, Error> {
+        let scaled_glyph;
+        let glyph = if glyph.pixels_per_em == pixels_per_em {
+            glyph
+        } else {
+            scaled_glyph = try!(glyph.scaled_to(pixels_per_em, &self.limits));
+            &scaled_glyph
+        };
+
+        try!(self.check_image_size(glyph.image.width, glyph.image.height));
+        let atlas_origin = try!(self.color_atlas.place(&Size2D::new(glyph.image.width,
+                                                                     glyph.image.height)));
+        try!(color_atlas_pixels.blit(&glyph.image, &atlas_origin));
+        Ok(atlas_origin)
+    }
+
+    /// Adds a color glyph rendered on the CPU, such as an SVG glyph or a `COLR` version 1 glyph,
+    /// copying its image into `color_atlas_pixels` as `add_bitmap_glyph()` does. Returns the
+    /// position of the image in the color atlas.
+    pub fn add_rendered_color_glyph(&mut self,
+                                    glyph: &ColorGlyphImage,
+                                    color_atlas_pixels: &mut RgbaImage)
+                                    -> Result<Point2D<u32>, Error> {
+        try!(self.check_image_size(glyph.image.width, glyph.image.height));
+        let atlas_origin = try!(self.color_atlas.place(&Size2D::new(glyph.image.width,
+                                                                     glyph.image.height)));
+        try!(color_atlas_pixels.blit(&glyph.image, &atlas_origin));
+        Ok(atlas_origin)
+    }
+
+    /// Adds a glyph as `add_glyph()` does, unless `use_embedded_bitmaps` is set and `bitmaps` has
+    /// a strike at exactly this size with a bitmap for `glyph_id`. In that case, the bitmap is
+    /// placed in `atlas` and recorded in `bitmaps` and `bitmap_pixels`, and its position in the
+    /// atlas is returned.
+    pub fn add_glyph_or_bitmap(&mut self,
+                               glyph_buffer_builder: &GlyphBufferBuilder,
+                               glyph_index: u32,
+                               glyph_id: u16,
+                               point_size: f32,
+                               bitmaps: &EmbeddedBitmaps)
+                               -> Result<Option<Point2D<u32>>, Error> {
+        if self.use_embedded_bitmaps {
+            if let Some(bitmap) = try!(bitmaps.glyph(glyph_id, point_size)) {
+                let image = &bitmap.image;
+                try!(self.check_image_size(image.width, image.height));
+                let atlas_origin = try!(self.atlas.place(&Size2D::new(image.width,
+                                                                       image.height)));
+                self.bitmaps.push(BitmapDescriptor {
+                    atlas_x: atlas_origin.x,
+                    atlas_y: atlas_origin.y,
+                    width: image.width,
+                    height: image.height,
+                    start_pixel: self.bitmap_pixels.len() as u32,
+                    padding: 0,
+                });
+                self.bitmap_pixels.extend_from_slice(&image.pixels);
+                return Ok(Some(atlas_origin))
+            }
+        }
+
+        try!(self.add_glyph(glyph_buffer_builder, glyph_index, point_size));
+        Ok(None)
+    }
+
+    fn add_image(&mut self,
+                 glyph_buffer_builder: &GlyphBufferBuilder,
+                 glyph_index: u32,
+                 point_size: f32)
+                 -> Result<Point2D<u32>, Error> {
+        let descriptor = try!(glyph_buffer_builder.descriptors
+                                                  .get(glyph_index as usize)
+                                                  .ok_or(Error::InvalidArgument));
+
+        let pixel_size = try!(self.image_size(&descriptor.pixel_rect(point_size).size));
+        let point_count = self.point_count.saturating_add(descriptor.point_count as u32);
+        try!(self.limits.check(Limit::BatchPoints, point_count));
         let atlas_origin = try!(self.atlas.place(&pixel_size));
 
         if self.point_count % POINTS_PER_SEGMENT == 0 {
@@ -60,18 +267,56 @@ impl BatchBuilder {
             point_count: descriptor.point_count as u32,
         });
 
-        self.point_count += descriptor.point_count as u32;
+        self.point_count = point_count;
 
-        Ok(())
+        Ok(atlas_origin)
     }
 
-    pub fn finish(&mut self, device: &Device) -> Result<Batch, ()> {
+    // Returns the size in whole pixels of an image covering a rectangle of the given size.
+    fn image_size(&self, size: &Size2D<f32>) -> Result<Size2D<u32>, Error> {
+        let size = size.ceil();
+        // Bounding boxes with negative sizes are malformed.
+        if !(size.width >= 0.0 && size.height >= 0.0) {
+            return Err(Error::Malformed)
+        }
+        let max_image_size = self.limits.max_image_size as f32;
+        if size.width > max_image_size || size.height > max_image_size {
+            return Err(Error::LimitExceeded(Limit::ImageSize))
+        }
+        Ok(Size2D::new(size.width as u32, size.height as u32))
+    }
+
+    fn check_image_size(&self, width: u32, height: u32) -> Result<(), Error> {
+        try!(self.limits.check(Limit::ImageSize, width));
+        self.limits.check(Limit::ImageSize, height)
+    }
+
+    pub fn finish(&mut self, device: &Device) -> Result<Batch, Error> {
         let indices = BufferData::HostAllocated(HostAllocatedData::new(&self.indices));
         let images = BufferData::HostAllocated(HostAllocatedData::new(&self.images));
+        let color_images =
+            BufferData::HostAllocated(HostAllocatedData::new(&self.color_images));
+        let color_layers =
+            BufferData::HostAllocated(HostAllocatedData::new(&self.color_layers));
+        let bitmaps = BufferData::HostAllocated(HostAllocatedData::new(&self.bitmaps));
+        let bitmap_pixels =
+            BufferData::HostAllocated(HostAllocatedData::new(&self.bitmap_pixels));
         Ok(Batch {
-            indices: try!(device.create_buffer(Protection::ReadOnly, indices).map_err(drop)),
-            images: try!(device.create_buffer(Protection::ReadOnly, images).map_err(drop)),
+            indices: try!(device.create_buffer(Protection::ReadOnly, indices)
+                                .map_err(|_| Error::DeviceFailure)),
+            images: try!(device.create_buffer(Protection::ReadOnly, images)
+                               .map_err(|_| Error::DeviceFailure)),
             point_count: self.point_count,
+            color_images: try!(device.create_buffer(Protection::ReadOnly, color_images)
+                                     .map_err(|_| Error::DeviceFailure)),
+            color_layers: try!(device.create_buffer(Protection::ReadOnly, color_layers)
+                                     .map_err(|_| Error::DeviceFailure)),
+            color_image_count: self.color_images.len() as u32,
+            bitmaps: try!(device.create_buffer(Protection::ReadOnly, bitmaps)
+                                .map_err(|_| Error::DeviceFailure)),
+            bitmap_pixels: try!(device.create_buffer(Protection::ReadOnly, bitmap_pixels)
+                                      .map_err(|_| Error::DeviceFailure)),
+            bitmap_count: self.bitmaps.len() as u32,
         })
     }
 }
@@ -80,43 +325,21 @@ pub struct Batch {
     pub indices: Buffer,
     pub images: Buffer,
     pub point_count: u32,
+    pub color_images: Buffer,
+    pub color_layers: Buffer,
+    pub color_image_count: u32,
+    pub bitmaps: Buffer,
+    pub bitmap_pixels: Buffer,
+    pub bitmap_count: u32,
 }
 
+/// One layer of a color glyph to be added to a batch.
 #[derive(Clone, Copy, Debug)]
-pub struct GlyphRange {
-    pub start: u16,
-    pub end: u16,
-}
-
-impl GlyphRange {
-    #[inline]
-    pub fn iter(&self) -> GlyphRangeIter {
-        GlyphRangeIter {
-            start: self.start,
-            end: self.end,
-        }
-    }
-}
-
-#[derive(Clone)]
-pub struct GlyphRangeIter {
-    start: u16,
-    end: u16,
-}
-
-impl Iterator for GlyphRangeIter {
-    type Item = u16;
-
-    #[inline]
-    fn next(&mut self) -> Option<u16> {
-        if self.start > self.end {
-            None
-        } else {
-            let item = self.start;
-            self.start += 1;
-            Some(item)
-        }
-    }
+pub struct ColorGlyphLayer {
+    /// The index of the layer's outline in the `GlyphBufferBuilder`.
+    pub glyph_index: u32,
+    /// The color to fill the layer with, with any foreground color already substituted.
+    pub color: Color,
 }
 
 #[repr(C)]
@@ -130,3 +353,42 @@ pub struct ImageDescriptor {
     point_count: u32,
 }
 
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ColorImageDescriptor {
+    atlas_x: u32,
+    atlas_y: u32,
+    width: u32,
+    height: u32,
+    start_layer: u32,
+    layer_count: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ColorLayerDescriptor {
+    coverage_x: u32,
+    coverage_y: u32,
+    offset_x: u32,
+    offset_y: u32,
+    width: u32,
+    height: u32,
+    color: u32,
+    // The CL struct's `uint2` fields align it to 8 bytes.
+    padding: u32,
+}
+
+/// An embedded bitmap glyph in a batch, whose coverage starts at `start_pixel` in the batch's
+/// `bitmap_pixels`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct BitmapDescriptor {
+    pub atlas_x: u32,
+    pub atlas_y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub start_pixel: u32,
+    // The CL struct's `uint2` fields align it to 8 bytes.
+    padding: u32,
+}
@@ -0,0 +1,405 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A bounds-checked, zero-copy reader for the big-endian data in font tables.
+//!
+//! Fixed-size values implement `FromData`. A `Reader` reads them one after another, and a
+//! `LazyArray` decodes elements of a run of them only when they're looked at. Offsets are read as
+//! `Offset16` or `Offset32` and resolved against the data they're relative to, usually the start
+//! of the table or subtable. The `binary_record!` macro declares a struct of fields read in order.
+//!
+//! Every read checks its bounds and fails with `Error::UnexpectedEof` instead of panicking, so this
+//! can parse tables from untrusted fonts, including ones this crate doesn't know about. The one
+//! thing it can't check is a `FromData` implementation that reads more than its `SIZE`: values are
+//! parsed from slices of exactly `SIZE` bytes, so such an implementation panics on every value.
+
+use byteorder::{BigEndian, ByteOrder};
+use error::Error;
+use otf::Tag;
+use std::cmp::Ordering;
+use std::fmt::{self, Debug, Formatter};
+use std::marker::PhantomData;
+use util::Jump;
+
+/// Declares a record: a `pub` struct with `pub` fields, each of a type implementing `FromData`,
+/// that are stored one after another with no padding. The struct implements `FromData` itself, so
+/// records can be read with a `Reader`, put in a `LazyArray`, or nested in other records.
+///
+/// Doc comments and other attributes can be put on the struct and its fields. The struct derives
+/// `Clone`, `Copy`, and `Debug`.
+#[macro_export]
+macro_rules! binary_record {
+    (
+        $(#[$attr:meta])*
+        pub struct $name:ident {
+            $($(#[$field_attr:meta])* pub $field:ident: $field_type:ty),* $(,)*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug)]
+        pub struct $name {
+            $($(#[$field_attr])* pub $field: $field_type),*
+        }
+
+        impl $crate::binary::FromData for $name {
+            const SIZE: usize = 0 $(+ <$field_type as $crate::binary::FromData>::SIZE)*;
+
+            #[allow(unused_assignments)]
+            #[inline]
+            fn parse(data: &[u8]) -> $name {
+                let mut data = data;
+                $(
+                    let size = <$field_type as $crate::binary::FromData>::SIZE;
+                    let $field = <$field_type as $crate::binary::FromData>::parse(&data[..size]);
+                    data = &data[size..];
+                )*
+                $name {
+                    $($field: $field),*
+                }
+            }
+        }
+    }
+}
+
+/// A value that's stored in a fixed number of bytes.
+pub trait FromData: Sized {
+    /// The number of bytes the value takes up.
+    const SIZE: usize;
+
+    /// Decodes the value from `data`, which is exactly `SIZE` bytes long.
+    ///
+    /// `Reader` and `LazyArray` check bounds before calling this, so it may index `data` freely,
+    /// but reading past `SIZE` bytes panics.
+    fn parse(data: &[u8]) -> Self;
+}
+
+impl FromData for u8 {
+    const SIZE: usize = 1;
+
+    #[inline]
+    fn parse(data: &[u8]) -> u8 {
+        data[0]
+    }
+}
+
+impl FromData for i8 {
+    const SIZE: usize = 1;
+
+    #[inline]
+    fn parse(data: &[u8]) -> i8 {
+        data[0] as i8
+    }
+}
+
+impl FromData for u16 {
+    const SIZE: usize = 2;
+
+    #[inline]
+    fn parse(data: &[u8]) -> u16 {
+        BigEndian::read_u16(data)
+    }
+}
+
+impl FromData for i16 {
+    const SIZE: usize = 2;
+
+    #[inline]
+    fn parse(data: &[u8]) -> i16 {
+        BigEndian::read_i16(data)
+    }
+}
+
+impl FromData for u32 {
+    const SIZE: usize = 4;
+
+    #[inline]
+    fn parse(data: &[u8]) -> u32 {
+        BigEndian::read_u32(data)
+    }
+}
+
+impl FromData for i32 {
+    const SIZE: usize = 4;
+
+    #[inline]
+    fn parse(data: &[u8]) -> i32 {
+        BigEndian::read_i32(data)
+    }
+}
+
+impl FromData for Tag {
+    const SIZE: usize = 4;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Tag {
+        Tag(BigEndian::read_u32(data))
+    }
+}
+
+/// An offset that's relative to the start of some enclosing data, such as a table or subtable.
+/// By convention, a zero offset often means that there's nothing there.
+pub trait Offset: FromData {
+    fn to_usize(&self) -> usize;
+
+    #[inline]
+    fn is_null(&self) -> bool {
+        self.to_usize() == 0
+    }
+
+    /// Returns the data from this offset to the end of `base`, the data the offset is relative
+    /// to.
+    #[inline]
+    fn resolve<'a>(&self, base: &'a [u8]) -> Result<&'a [u8], Error> {
+        let mut data = base;
+        try!(data.jump(self.to_usize()));
+        Ok(data)
+    }
+
+    /// Like `resolve`, but returns `None` for a zero offset.
+    #[inline]
+    fn resolve_nullable<'a>(&self, base: &'a [u8]) -> Result<Option<&'a [u8]>, Error> {
+        if self.is_null() {
+            Ok(None)
+        } else {
+            self.resolve(base).map(Some)
+        }
+    }
+}
+
+/// A 16-bit offset, as used by most subtables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Offset16(pub u16);
+
+impl FromData for Offset16 {
+    const SIZE: usize = 2;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Offset16 {
+        Offset16(BigEndian::read_u16(data))
+    }
+}
+
+impl Offset for Offset16 {
+    #[inline]
+    fn to_usize(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A 32-bit offset, as used by table directories and some large subtables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Offset32(pub u32);
+
+impl FromData for Offset32 {
+    const SIZE: usize = 4;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Offset32 {
+        Offset32(BigEndian::read_u32(data))
+    }
+}
+
+impl Offset for Offset32 {
+    #[inline]
+    fn to_usize(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Reads values one after another from a byte slice.
+#[derive(Clone, Copy, Debug)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    #[inline]
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader {
+            data: data,
+            position: 0,
+        }
+    }
+
+    /// Returns a reader that starts `offset` bytes into `data`.
+    #[inline]
+    pub fn at(data: &'a [u8], offset: usize) -> Result<Reader<'a>, Error> {
+        let mut reader = Reader::new(data);
+        try!(reader.skip_bytes(offset));
+        Ok(reader)
+    }
+
+    /// The number of bytes read or skipped so far.
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the data that hasn't been read yet.
+    #[inline]
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.position..]
+    }
+
+    #[inline]
+    pub fn read<T: FromData>(&mut self) -> Result<T, Error> {
+        let data = try!(self.read_bytes(T::SIZE));
+        Ok(T::parse(data))
+    }
+
+    /// Skips over a value without decoding it.
+    #[inline]
+    pub fn skip<T: FromData>(&mut self) -> Result<(), Error> {
+        self.skip_bytes(T::SIZE)
+    }
+
+    #[inline]
+    pub fn skip_bytes(&mut self, length: usize) -> Result<(), Error> {
+        let mut data = self.remaining();
+        try!(data.jump(length));
+        self.position += length;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        let data = self.remaining();
+        try!(self.skip_bytes(length));
+        Ok(&data[..length])
+    }
+
+    /// Reads a run of `count` values, which are decoded only when they're accessed.
+    #[inline]
+    pub fn read_array<T: FromData>(&mut self, count: usize) -> Result<LazyArray<'a, T>, Error> {
+        let length = try!(count.checked_mul(T::SIZE).ok_or(Error::UnexpectedEof));
+        let data = try!(self.read_bytes(length));
+        Ok(LazyArray::new(data))
+    }
+}
+
+/// A run of values stored back to back, which are decoded as they're accessed.
+pub struct LazyArray<'a, T> {
+    data: &'a [u8],
+    phantom: PhantomData<T>,
+}
+
+impl<'a, T> Clone for LazyArray<'a, T> {
+    #[inline]
+    fn clone(&self) -> LazyArray<'a, T> {
+        *self
+    }
+}
+
+impl<'a, T> Copy for LazyArray<'a, T> {}
+
+impl<'a, T: FromData + Debug> Debug for LazyArray<'a, T> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T: FromData> LazyArray<'a, T> {
+    /// Treats `data` as an array of as many values as it holds. Any partial value at the end is
+    /// ignored.
+    #[inline]
+    pub fn new(data: &'a [u8]) -> LazyArray<'a, T> {
+        LazyArray {
+            data: data,
+            phantom: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len().checked_div(T::SIZE).unwrap_or(0)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<T> {
+        if index < self.len() {
+            Some(self.parse_element(index))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn iter(&self) -> LazyArrayIter<'a, T> {
+        LazyArrayIter {
+            array: *self,
+            index: 0,
+        }
+    }
+
+    /// Binary searches an array sorted in the order `compare` expects, as `[T]::binary_search_by`
+    /// does.
+    pub fn binary_search_by<F>(&self, mut compare: F) -> Result<usize, usize>
+                               where F: FnMut(&T) -> Ordering {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let value = self.parse_element(middle);
+            match compare(&value) {
+                Ordering::Less => low = middle + 1,
+                Ordering::Greater => high = middle,
+                Ordering::Equal => return Ok(middle),
+            }
+        }
+        Err(low)
+    }
+
+    #[inline]
+    fn parse_element(&self, index: usize) -> T {
+        let start = index * T::SIZE;
+        T::parse(&self.data[start..(start + T::SIZE)])
+    }
+}
+
+impl<'a, T: FromData> IntoIterator for LazyArray<'a, T> {
+    type Item = T;
+    type IntoIter = LazyArrayIter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> LazyArrayIter<'a, T> {
+        self.iter()
+    }
+}
+
+pub struct LazyArrayIter<'a, T> {
+    array: LazyArray<'a, T>,
+    index: usize,
+}
+
+impl<'a, T: FromData> Iterator for LazyArrayIter<'a, T> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> {
+        let value = self.array.get(self.index);
+        if value.is_some() {
+            self.index += 1
+        }
+        value
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.array.len() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, T: FromData> ExactSizeIterator for LazyArrayIter<'a, T> {}
@@ -0,0 +1,315 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Embedded bitmap glyphs, as found in color emoji fonts.
+//!
+//! These glyphs have no outlines; instead, each strike (set of bitmaps for one pixel size) has an
+//! image per glyph. For color glyphs, we pick the strike closest to the requested size and scale
+//! its image to fit. Monochrome and grayscale bitmaps are hand-tuned for the sizes they are drawn
+//! at, so they are only used at exactly those sizes, or at the sizes `EBSC` maps onto them.
+
+use error::Error;
+use euclid::Point2D;
+use limits::{Limit, Limits};
+use otf::cbdt::CbdtTable;
+use otf::cblc::{BitmapGlyphMetrics, BitmapStrike, CblcTable};
+use otf::ebdt::{EbdtGlyphData, EbdtTable};
+use otf::ebsc::{BitmapScale, EbscTable};
+use otf::sbix::{self, SbixTable};
+use png;
+use software_rasterizer::{GrayImage, RgbaImage};
+use std::cmp::Ordering;
+use std::u8;
+
+// Composite bitmap glyphs may nest, but not deeper than this.
+const MAX_COMPONENT_DEPTH: u32 = 8;
+
+/// A decoded bitmap glyph.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitmapGlyph {
+    pub image: RgbaImage,
+    /// The position of the glyph origin in the image, in pixels from the top left.
+    pub origin: Point2D<i32>,
+    /// The size the image is drawn at. This is the size of the strike it came from, unless it has
+    /// been scaled.
+    pub pixels_per_em: f32,
+}
+
+impl BitmapGlyph {
+    /// Looks up a glyph in the `CBLC` and `CBDT` tables, using the best strike for the given size
+    /// that has an image for it. Returns `None` if no strike does.
+    ///
+    /// Images larger than `limits` allow fail with `Error::LimitExceeded` before they're decoded.
+    pub fn from_cbdt(cblc: &CblcTable,
+                     cbdt: &CbdtTable,
+                     glyph_id: u16,
+                     pixels_per_em: f32,
+                     limits: &Limits)
+                     -> Result<Option<BitmapGlyph>, Error> {
+        let strikes = try!(cblc.strikes());
+        let ppems: Vec<_> = strikes.iter().map(|strike| strike.ppem_y as u16).collect();
+        for strike_index in strike_order(&ppems, pixels_per_em) {
+            let strike = &strikes[strike_index];
+            let location = match try!(cblc.glyph_location(strike, glyph_id)) {
+                None => continue,
+                Some(location) => location,
+            };
+
+            let glyph = try!(cbdt.glyph(&location));
+            let image = try!(png::decode(glyph.png, limits));
+            return Ok(Some(BitmapGlyph {
+                image: image,
+                origin: Point2D::new(-glyph.metrics.bearing_x as i32,
+                                     glyph.metrics.bearing_y as i32),
+                pixels_per_em: strike.ppem_y as f32,
+            }))
+        }
+        Ok(None)
+    }
+
+    /// Looks up a glyph in the `sbix` table, using the best strike for the given size that has an
+    /// image for it. Returns `None` if no strike does.
+    ///
+    /// Duplicated images are followed to the originals. Only PNG images are supported, so strikes
+    /// whose image for the glyph is in another format are passed over. Images are checked against
+    /// `limits` as in `from_cbdt()`.
+    pub fn from_sbix(sbix: &SbixTable, glyph_id: u16, pixels_per_em: f32, limits: &Limits)
+                     -> Result<Option<BitmapGlyph>, Error> {
+        let strikes = try!(sbix.strikes());
+        let ppems: Vec<_> = strikes.iter().map(|strike| strike.ppem).collect();
+        for strike_index in strike_order(&ppems, pixels_per_em) {
+            let strike = &strikes[strike_index];
+            let glyph = match try!(sbix.glyph(strike, glyph_id)) {
+                None => continue,
+                Some(glyph) => glyph,
+            };
+            if glyph.graphic_type != sbix::GRAPHIC_TYPE_PNG {
+                continue
+            }
+
+            // The origin offset locates the bottom left corner of the image.
+            let image = try!(png::decode(glyph.data, limits));
+            let origin = Point2D::new(-glyph.origin_offset.x as i32,
+                                      image.height as i32 + glyph.origin_offset.y as i32);
+            return Ok(Some(BitmapGlyph {
+                image: image,
+                origin: origin,
+                pixels_per_em: strike.ppem as f32,
+            }))
+        }
+        Ok(None)
+    }
+
+    /// Returns this glyph resized to the given size, which must keep the image within `limits`.
+    pub fn scaled_to(&self, pixels_per_em: f32, limits: &Limits) -> Result<BitmapGlyph, Error> {
+        if !(self.pixels_per_em > 0.0) {
+            return Err(Error::InvalidArgument)
+        }
+        let scale = pixels_per_em / self.pixels_per_em;
+        let max_image_size = limits.max_image_size as f32;
+        if (self.image.width as f32 * scale).ceil() > max_image_size ||
+                (self.image.height as f32 * scale).ceil() > max_image_size {
+            return Err(Error::LimitExceeded(Limit::ImageSize))
+        }
+        Ok(BitmapGlyph {
+            image: try!(self.image.scaled(scale)),
+            origin: Point2D::new((self.origin.x as f32 * scale).round() as i32,
+                                 (self.origin.y as f32 * scale).round() as i32),
+            pixels_per_em: pixels_per_em,
+        })
+    }
+}
+
+/// Returns the indices of the given strike sizes in order of preference for rendering at the
+/// given size: first the strikes at least that large, smallest first, since downscaling looks
+/// better than upscaling; then the smaller strikes, largest first.
+pub fn strike_order(ppems: &[u16], pixels_per_em: f32) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..ppems.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (ppems[a], ppems[b]);
+        match (a as f32 >= pixels_per_em, b as f32 >= pixels_per_em) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (true, true) => a.cmp(&b),
+            (false, false) => b.cmp(&a),
+        }
+    });
+    order
+}
+
+/// A decoded monochrome or grayscale bitmap glyph, as coverage.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GrayBitmapGlyph {
+    pub image: GrayImage,
+    /// The position of the glyph origin in the image, in pixels from the top left.
+    pub origin: Point2D<i32>,
+    pub pixels_per_em: f32,
+}
+
+/// The monochrome and grayscale bitmap strikes of a font, from its `EBLC`, `EBDT`, and optional
+/// `EBSC` tables.
+pub struct EmbeddedBitmaps<'a> {
+    eblc: CblcTable<'a>,
+    ebdt: EbdtTable<'a>,
+    strikes: Vec<BitmapStrike>,
+    scales: Vec<BitmapScale>,
+}
+
+impl<'a> EmbeddedBitmaps<'a> {
+    pub fn new(eblc: CblcTable<'a>, ebdt: EbdtTable<'a>, ebsc: Option<&EbscTable>)
+               -> Result<EmbeddedBitmaps<'a>, Error> {
+        let strikes = try!(eblc.strikes());
+        let scales = match ebsc {
+            Some(ebsc) => try!(ebsc.scales()),
+            None => vec![],
+        };
+        Ok(EmbeddedBitmaps {
+            eblc: eblc,
+            ebdt: ebdt,
+            strikes: strikes,
+            scales: scales,
+        })
+    }
+
+    /// Returns true if there is a strike, or an `EBSC` substitute for one, at exactly the given
+    /// size.
+    pub fn has_strike(&self, pixels_per_em: f32) -> bool {
+        match exact_ppem(pixels_per_em) {
+            None => false,
+            Some(ppem) => {
+                self.strike(ppem).is_some() || self.scales.iter().any(|scale| scale.ppem_y == ppem)
+            }
+        }
+    }
+
+    /// Returns the bitmap of the given glyph at exactly the given size, or `None` if there is no
+    /// strike for that size or the strike lacks the glyph.
+    pub fn glyph(&self, glyph_id: u16, pixels_per_em: f32)
+                 -> Result<Option<GrayBitmapGlyph>, Error> {
+        let ppem = match exact_ppem(pixels_per_em) {
+            None => return Ok(None),
+            Some(ppem) => ppem,
+        };
+
+        if let Some(strike) = self.strike(ppem) {
+            if let Some((image, metrics)) = try!(self.decode(strike, glyph_id, 0)) {
+                return Ok(Some(GrayBitmapGlyph {
+                    image: image,
+                    origin: Point2D::new(-metrics.bearing_x as i32, metrics.bearing_y as i32),
+                    pixels_per_em: pixels_per_em,
+                }))
+            }
+        }
+
+        // Fall back to scaling the strike `EBSC` substitutes for this size.
+        let scale = match self.scales.iter().find(|scale| scale.ppem_y == ppem) {
+            None => return Ok(None),
+            Some(scale) => scale,
+        };
+        let strike = match self.strike(scale.substitute_ppem_y) {
+            None => return Ok(None),
+            Some(strike) => strike,
+        };
+        let (image, metrics) = match try!(self.decode(strike, glyph_id, 0)) {
+            None => return Ok(None),
+            Some(decoded) => decoded,
+        };
+        let factor = ppem as f32 / scale.substitute_ppem_y as f32;
+        Ok(Some(GrayBitmapGlyph {
+            image: try!(image.scaled(factor)),
+            origin: Point2D::new((-metrics.bearing_x as f32 * factor).round() as i32,
+                                 (metrics.bearing_y as f32 * factor).round() as i32),
+            pixels_per_em: pixels_per_em,
+        }))
+    }
+
+    fn strike(&self, ppem: u8) -> Option<&BitmapStrike> {
+        self.strikes.iter().find(|strike| strike.ppem_y == ppem)
+    }
+
+    // Decodes a glyph to coverage, drawing the components of composite glyphs.
+    fn decode(&self, strike: &BitmapStrike, glyph_id: u16, depth: u32)
+              -> Result<Option<(GrayImage, BitmapGlyphMetrics)>, Error> {
+        if depth > MAX_COMPONENT_DEPTH {
+            return Err(Error::Malformed)
+        }
+        let location = match try!(self.eblc.glyph_location(strike, glyph_id)) {
+            None => return Ok(None),
+            Some(location) => location,
+        };
+        let glyph = try!(self.ebdt.glyph(&location));
+        let metrics = glyph.metrics;
+        let mut image = GrayImage::new(metrics.width as u32, metrics.height as u32);
+
+        match glyph.data {
+            EbdtGlyphData::Bitmap { data, byte_aligned } => {
+                let bit_depth = strike.bit_depth as usize;
+                if bit_depth != 1 && bit_depth != 2 && bit_depth != 4 && bit_depth != 8 {
+                    return Err(Error::Malformed)
+                }
+                let max_value = (1 << bit_depth) - 1;
+                let mut row_bits = metrics.width as usize * bit_depth;
+                if byte_aligned {
+                    row_bits = (row_bits + 7) & !7
+                }
+                if (row_bits * metrics.height as usize + 7) / 8 > data.len() {
+                    return Err(Error::Malformed)
+                }
+
+                for y in 0..(metrics.height as usize) {
+                    for x in 0..(metrics.width as usize) {
+                        let bit = y * row_bits + x * bit_depth;
+                        let shift = 8 - bit_depth - bit % 8;
+                        let value = (data[bit / 8] >> shift) as u32 & max_value;
+                        image.pixels[y * metrics.width as usize + x] =
+                            (value * 255 / max_value) as u8
+                    }
+                }
+            }
+            EbdtGlyphData::Composite(ref components) => {
+                for component in components {
+                    let (component_image, _) =
+                        match try!(self.decode(strike, component.glyph_id, depth + 1)) {
+                            None => continue,
+                            Some(decoded) => decoded,
+                        };
+
+                    // Components may overlap each other and hang off the edges.
+                    for y in 0..component_image.height {
+                        let target_y = y as i32 + component.y_offset as i32;
+                        if target_y < 0 || target_y >= image.height as i32 {
+                            continue
+                        }
+                        for x in 0..component_image.width {
+                            let target_x = x as i32 + component.x_offset as i32;
+                            if target_x < 0 || target_x >= image.width as i32 {
+                                continue
+                            }
+                            let index =
+                                target_y as usize * image.width as usize + target_x as usize;
+                            let coverage = component_image.pixel(x, y);
+                            image.pixels[index] = image.pixels[index].max(coverage)
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Some((image, metrics)))
+    }
+}
+
+fn exact_ppem(pixels_per_em: f32) -> Option<u8> {
+    if pixels_per_em.fract() == 0.0 && pixels_per_em >= 1.0 && pixels_per_em <= u8::MAX as f32 {
+        Some(pixels_per_em as u8)
+    } else {
+        None
+    }
+}
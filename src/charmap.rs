@@ -53,3 +53,40 @@ impl Iterator for CodepointRangeIter {
     }
 }
 
+/// An inclusive glyph ID range.
+#[derive(Clone, Copy, Debug)]
+pub struct GlyphRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl GlyphRange {
+    #[inline]
+    pub fn iter(&self) -> GlyphRangeIter {
+        GlyphRangeIter {
+            start: self.start,
+            end: self.end,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GlyphRangeIter {
+    start: u16,
+    end: u16,
+}
+
+impl Iterator for GlyphRangeIter {
+    type Item = u16;
+
+    #[inline]
+    fn next(&mut self) -> Option<u16> {
+        if self.start > self.end {
+            None
+        } else {
+            let item = self.start;
+            self.start += 1;
+            Some(item)
+        }
+    }
+}
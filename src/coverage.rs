@@ -10,6 +10,7 @@
 
 use compute_shader::buffer::{Buffer, BufferData, Protection};
 use compute_shader::device::Device;
+use error::Error;
 use euclid::size::Size2D;
 use std::mem;
 
@@ -18,10 +19,11 @@ pub struct CoverageBuffer {
 }
 
 impl CoverageBuffer {
-    pub fn new(device: &Device, size: &Size2D<u32>) -> Result<CoverageBuffer, ()> {
+    pub fn new(device: &Device, size: &Size2D<u32>) -> Result<CoverageBuffer, Error> {
         let size = size.width as usize * size.height as usize * mem::size_of::<u32>();
         let buffer = try!(device.create_buffer(Protection::ReadWrite,
-                                               BufferData::Uninitialized(size)).map_err(drop));
+                                               BufferData::Uninitialized(size))
+                                .map_err(|_| Error::DeviceFailure));
         Ok(CoverageBuffer {
             buffer: buffer,
         })
@@ -0,0 +1,128 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The error type returned throughout the crate.
+
+use limits::Limit;
+use otf::Tag;
+#[cfg(feature = "std")]
+use std::error;
+use std::fmt::{self, Display, Formatter};
+#[cfg(feature = "std")]
+use std::io;
+
+/// Everything that can go wrong loading fonts and rendering glyphs.
+///
+/// Table tags are the raw values of `otf::Tag`s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The data doesn't start with a recognized sfnt version or font container signature.
+    UnsupportedSfntVersion(u32),
+    /// A table the operation needs isn't in the font.
+    MissingTable(u32),
+    /// A table, or a subtable in it, has a format or version that isn't supported.
+    UnsupportedFormat {
+        table: u32,
+        format: u32,
+    },
+    /// A table ended before the data its parser needed. `table_length` is how many bytes of the
+    /// table there were.
+    Truncated {
+        table: u32,
+        table_length: usize,
+    },
+    /// Data ended early somewhere other than a table being loaded, such as a PNG image, a
+    /// Type 1 font, or a table that was already loaded.
+    UnexpectedEof,
+    /// The data is present but invalid, such as an offset that points outside its table.
+    Malformed,
+    /// The glyph ID is out of range for the font or table.
+    InvalidGlyphId(u32),
+    /// An argument is out of range, or the operation isn't possible in the current state.
+    InvalidArgument,
+    /// The atlas has no room left for the glyph.
+    AtlasFull,
+    /// The GPU device failed to create a buffer, texture, or program, or to run a program.
+    DeviceFailure,
+    /// A glyph or batch needs more of a resource than its `Limits` allow.
+    LimitExceeded(Limit),
+}
+
+impl Error {
+    /// Attributes a premature end of data to the given table, which is `table_length` bytes long.
+    #[inline]
+    pub fn in_table(self, table: u32, table_length: usize) -> Error {
+        match self {
+            Error::UnexpectedEof => {
+                Error::Truncated {
+                    table: table,
+                    table_length: table_length,
+                }
+            }
+            error => error,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            Error::UnsupportedSfntVersion(version) => {
+                write!(formatter, "unsupported sfnt version {:#010x}", version)
+            }
+            Error::MissingTable(table) => write!(formatter, "missing `{}` table", Tag(table)),
+            Error::UnsupportedFormat { table, format } => {
+                write!(formatter, "unsupported format {} in `{}` table", format, Tag(table))
+            }
+            Error::Truncated { table, table_length } => {
+                write!(formatter, "`{}` table truncated at {} bytes", Tag(table), table_length)
+            }
+            Error::UnexpectedEof => formatter.write_str("unexpected end of data"),
+            Error::Malformed => formatter.write_str("malformed font data"),
+            Error::InvalidGlyphId(glyph_id) => write!(formatter, "invalid glyph ID {}", glyph_id),
+            Error::InvalidArgument => formatter.write_str("invalid argument"),
+            Error::AtlasFull => formatter.write_str("atlas full"),
+            Error::DeviceFailure => formatter.write_str("GPU device failure"),
+            Error::LimitExceeded(limit) => write!(formatter, "limit on {} exceeded", limit),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::UnsupportedSfntVersion(_) => "unsupported sfnt version",
+            Error::MissingTable(_) => "missing table",
+            Error::UnsupportedFormat { .. } => "unsupported format",
+            Error::Truncated { .. } => "truncated table",
+            Error::UnexpectedEof => "unexpected end of data",
+            Error::Malformed => "malformed font data",
+            Error::InvalidGlyphId(_) => "invalid glyph ID",
+            Error::InvalidArgument => "invalid argument",
+            Error::AtlasFull => "atlas full",
+            Error::DeviceFailure => "GPU device failure",
+            Error::LimitExceeded(_) => "resource limit exceeded",
+        }
+    }
+}
+
+/// Reading past the end of a byte slice is an `UnexpectedEof`. Any other failure, such as a
+/// decompressor rejecting its input, means the data is malformed.
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    #[inline]
+    fn from(error: io::Error) -> Error {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => Error::UnexpectedEof,
+            _ => Error::Malformed,
+        }
+    }
+}
@@ -8,17 +8,34 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+#[cfg(feature = "gpu")]
 use compute_shader::buffer::{Buffer, BufferData, HostAllocatedData, Protection};
+#[cfg(feature = "gpu")]
 use compute_shader::device::Device;
+use error::Error;
 use euclid::{Point2D, Rect, Size2D};
+use limits::{Limit, Limits};
 use otf::glyf::GlyfTable;
 use otf::head::HeadTable;
 use otf::loca::LocaTable;
+use outline::PathCommand;
+use std::cmp;
+use std::i16;
+use std::u16;
+
+// How far, in font units, the quadratic curves that stand in for a cubic one may stray from it.
+// Coordinates are rounded to whole units anyway.
+const CUBIC_APPROXIMATION_TOLERANCE: f32 = 0.25;
+// Bounds the number of quadratic curves a single cubic one becomes.
+const MAX_QUADRATICS_PER_CUBIC: u32 = 32;
 
 pub struct GlyphBufferBuilder {
     pub coordinates: Vec<(i16, i16)>,
     pub operations: Vec<u8>,
     pub descriptors: Vec<GlyphDescriptor>,
+    /// Glyphs that need more than these fail with `Error::LimitExceeded`. They replace the
+    /// limits of the `GlyfTable` that glyphs are added from.
+    pub limits: Limits,
 }
 
 impl GlyphBufferBuilder {
@@ -28,6 +45,7 @@ impl GlyphBufferBuilder {
             coordinates: vec![],
             operations: vec![],
             descriptors: vec![],
+            limits: Limits::default(),
         }
     }
 
@@ -36,18 +54,16 @@ impl GlyphBufferBuilder {
                      head_table: &HeadTable,
                      loca_table: &LocaTable,
                      glyf_table: &GlyfTable)
-                     -> Result<(), ()> {
-        let mut point_index = self.coordinates.len() / 2;
-        let start_point = point_index;
-        let mut operations = if point_index % 4 == 0 {
-            0
-        } else {
-            self.operations.pop().unwrap()
+                     -> Result<(), Error> {
+        let glyf_table = GlyfTable {
+            limits: self.limits,
+            ..*glyf_table
         };
 
+        // Collect the points first, so that a glyph that fails partway leaves the buffers as
+        // they were.
+        let mut points = vec![];
         try!(glyf_table.for_each_point(loca_table, glyph_id, |point| {
-            self.coordinates.push((point.position.x, point.position.y));
-
             let operation = if point.first_point_in_contour {
                 0
             } else if point.on_curve {
@@ -55,48 +71,188 @@ impl GlyphBufferBuilder {
             } else {
                 2
             };
-
-            operations |= operation << (point_index % 4 * 2);
-
-            point_index += 1;
-            if point_index % 4 == 0 {
-                self.operations.push(operation)
-            }
+            points.push(((point.position.x, point.position.y), operation))
         }));
 
-        if point_index % 4 != 0 {
-            self.operations.push(operations)
-        }
-
         // TODO(pcwalton): Add a glyph descriptor.
         let bounding_rect = try!(glyf_table.bounding_rect(loca_table, glyph_id));
+        let start_point = try!(self.push_points(&points));
         self.descriptors.push(GlyphDescriptor {
             left: bounding_rect.origin.x,
             bottom: bounding_rect.origin.y,
             width: bounding_rect.size.width,
             height: bounding_rect.size.height,
             units_per_em: head_table.units_per_em,
-            point_count: (point_index - start_point) as u16,
-            start_point: start_point as u32,
+            point_count: points.len() as u16,
+            start_point: start_point,
+        });
+
+        Ok(())
+    }
+
+    /// Adds a glyph from an outline in font units, such as one that has been hinted.
+    ///
+    /// Coordinates are rounded to whole font units. The buffers can only represent quadratic
+    /// curves, so cubic ones are approximated with them.
+    pub fn add_path_commands(&mut self, commands: &[PathCommand], units_per_em: u16)
+                             -> Result<(), Error> {
+        let mut points = vec![];
+        let mut contour_start = None;
+        let mut current = Point2D::new(0.0, 0.0);
+        for command in commands {
+            match *command {
+                PathCommand::MoveTo(point) => {
+                    close_contour(&mut points, contour_start.take());
+                    contour_start = Some(point);
+                    points.push((point, 0));
+                    current = point
+                }
+                PathCommand::LineTo(point) => {
+                    points.push((point, 1));
+                    current = point
+                }
+                PathCommand::QuadTo(control_point, point) => {
+                    points.push((control_point, 2));
+                    points.push((point, 1));
+                    current = point
+                }
+                PathCommand::CurveTo(control_point_0, control_point_1, point) => {
+                    approximate_cubic(&mut points,
+                                      &[current, control_point_0, control_point_1, point]);
+                    current = point
+                }
+                PathCommand::ClosePath => {
+                    if let Some(start) = contour_start.take() {
+                        close_contour(&mut points, Some(start));
+                        current = start
+                    }
+                }
+            }
+        }
+        close_contour(&mut points, contour_start);
+
+        let contour_count = points.iter().filter(|&&(_, operation)| operation == 0).count();
+        try!(self.limits.check(Limit::ContoursPerGlyph, contour_count as u32));
+        try!(self.limits.check(Limit::PointsPerGlyph, points.len() as u32));
+
+        let points: Vec<_> = points.iter().map(|&(point, operation)| {
+            ((point.x.round() as i16, point.y.round() as i16), operation)
+        }).collect();
+        let (mut min, mut max) = ((i16::MAX, i16::MAX), (i16::MIN, i16::MIN));
+        for &(position, _) in &points {
+            min = (cmp::min(min.0, position.0), cmp::min(min.1, position.1));
+            max = (cmp::max(max.0, position.0), cmp::max(max.1, position.1));
+        }
+        if points.is_empty() {
+            min = (0, 0);
+            max = (0, 0)
+        }
+
+        let start_point = try!(self.push_points(&points));
+        self.descriptors.push(GlyphDescriptor {
+            left: min.0,
+            bottom: min.1,
+            width: max.0.wrapping_sub(min.0),
+            height: max.1.wrapping_sub(min.1),
+            units_per_em: units_per_em,
+            point_count: points.len() as u16,
+            start_point: start_point,
         });
 
         Ok(())
     }
 
-    pub fn finish(&self, device: &Device) -> Result<GlyphBuffers, ()> {
+    // Appends the points of a glyph and their operations, packed four to a byte, and returns the
+    // index of the first one.
+    fn push_points(&mut self, points: &[((i16, i16), u8)]) -> Result<u32, Error> {
+        // The limit on points may be higher than a descriptor can count.
+        if points.len() > u16::MAX as usize {
+            return Err(Error::LimitExceeded(Limit::PointsPerGlyph))
+        }
+
+        let start_point = self.coordinates.len();
+        for (point_index, &(position, operation)) in (start_point..).zip(points.iter()) {
+            if point_index % 4 == 0 {
+                self.operations.push(0)
+            }
+            *self.operations.last_mut().unwrap() |= operation << (point_index % 4 * 2);
+            self.coordinates.push(position)
+        }
+        Ok(start_point as u32)
+    }
+
+    /// Uploads the outlines to the GPU.
+    #[cfg(feature = "gpu")]
+    pub fn finish(&self, device: &Device) -> Result<GlyphBuffers, Error> {
         let coordinates = BufferData::HostAllocated(HostAllocatedData::new(&self.coordinates));
         let operations = BufferData::HostAllocated(HostAllocatedData::new(&self.operations));
         let descriptors = BufferData::HostAllocated(HostAllocatedData::new(&self.descriptors));
         Ok(GlyphBuffers {
             coordinates: try!(device.create_buffer(Protection::ReadOnly, coordinates)
-                                    .map_err(drop)),
-            operations: try!(device.create_buffer(Protection::ReadOnly, operations).map_err(drop)),
+                                    .map_err(|_| Error::DeviceFailure)),
+            operations: try!(device.create_buffer(Protection::ReadOnly, operations)
+                                   .map_err(|_| Error::DeviceFailure)),
             descriptors: try!(device.create_buffer(Protection::ReadOnly, descriptors)
-                                    .map_err(drop)),
+                                    .map_err(|_| Error::DeviceFailure)),
         })
     }
 }
 
+// Ends a contour at its starting point, as `GlyfTable::for_each_point` does, unless it already
+// ends there.
+fn close_contour(points: &mut Vec<(Point2D<f32>, u8)>, start: Option<Point2D<f32>>) {
+    if let Some(start) = start {
+        match points.last() {
+            Some(&(point, _)) if point == start => {}
+            _ => points.push((start, 1)),
+        }
+    }
+}
+
+// Appends quadratic curves that follow the cubic curve with the given control points, splitting
+// it into as many pieces as it takes to stay within `CUBIC_APPROXIMATION_TOLERANCE`.
+//
+// Each piece becomes the quadratic curve whose control point is the average of the ones its
+// tangents extend to. That strays from a cubic piece by at most √3/36 of the length of its third
+// difference, which shrinks with the cube of the number of pieces.
+fn approximate_cubic(points: &mut Vec<(Point2D<f32>, u8)>, cubic: &[Point2D<f32>; 4]) {
+    let third_difference = cubic[3] - cubic[2] * 3.0 + cubic[1] * 3.0 - cubic[0];
+    let error = (third_difference.x * third_difference.x +
+                 third_difference.y * third_difference.y).sqrt() * 3.0f32.sqrt() / 36.0;
+    let pieces = if error.is_finite() {
+        ((error / CUBIC_APPROXIMATION_TOLERANCE).cbrt().ceil() as u32)
+            .max(1)
+            .min(MAX_QUADRATICS_PER_CUBIC)
+    } else {
+        1
+    };
+
+    let point_at = |t: f32| {
+        let u = 1.0 - t;
+        cubic[0] * (u * u * u) + cubic[1] * (3.0 * u * u * t) + cubic[2] * (3.0 * u * t * t) +
+            cubic[3] * (t * t * t)
+    };
+    let derivative_at = |t: f32| {
+        let u = 1.0 - t;
+        (cubic[1] - cubic[0]) * (3.0 * u * u) + (cubic[2] - cubic[1]) * (6.0 * u * t) +
+            (cubic[3] - cubic[2]) * (3.0 * t * t)
+    };
+
+    let step = 1.0 / pieces as f32;
+    let mut start = cubic[0];
+    for piece in 0..pieces {
+        let (t0, t1) = (piece as f32 * step, (piece + 1) as f32 * step);
+        let end = if piece + 1 == pieces { cubic[3] } else { point_at(t1) };
+        let control_point_0 = start + derivative_at(t0) * (step / 3.0);
+        let control_point_1 = end - derivative_at(t1) * (step / 3.0);
+        let control_point = ((control_point_0 + control_point_1) * 3.0 - start - end) * 0.25;
+        points.push((control_point, 2));
+        points.push((end, 1));
+        start = end
+    }
+}
+
+#[cfg(feature = "gpu")]
 pub struct GlyphBuffers {
     pub coordinates: Buffer,
     pub operations: Buffer,
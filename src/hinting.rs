@@ -0,0 +1,2434 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A TrueType bytecode interpreter, which grid-fits glyph outlines by running the font program
+//! (`fpgm`), the control value program (`prep`), and each glyph's own instructions.
+//!
+//! The interpreter follows FreeType's version 35 interpreter, including the undocumented behavior
+//! of the Microsoft rasterizer that FreeType emulates, so that hinted points match FreeType's.
+//! Coordinates are 26.6 fixed point pixels and vectors are 2.14 fixed point, as in the TrueType
+//! specification. Unlike FreeType, stack underflow always stops the program, as it does in
+//! FreeType's pedantic mode; other invalid references are ignored.
+
+use error::Error;
+use euclid::{Point2D, Rect, Size2D};
+use limits::{Limit, Limits};
+use otf::FontData;
+use otf::glyf::GlyfTable;
+use otf::hhea::HheaTable;
+use otf::hmtx::HmtxTable;
+use otf::loca::LocaTable;
+use otf::maxp::MaxpTable;
+use outline::PathCommand;
+use std::cmp;
+use std::u16;
+
+const TWILIGHT_ZONE: usize = 0;
+const GLYPH_ZONE: usize = 1;
+
+// The phantom points give the hinter control over the glyph's origin, advance width, and
+// vertical metrics. They follow the outline points in the glyph zone.
+const PHANTOM_POINT_COUNT: usize = 4;
+
+// FreeType reserves this many extra stack slots and twilight points beyond what `maxp` asks for,
+// because fonts often get those limits wrong.
+const EXTRA_STACK_ELEMENTS: usize = 32;
+const EXTRA_TWILIGHT_POINTS: usize = 4;
+
+const MAX_CALL_DEPTH: usize = 32;
+const MAX_INSTRUCTIONS: u32 = 1_000_000;
+
+// The value `GETINFO` reports as the rasterizer version.
+const INTERPRETER_VERSION: i32 = 35;
+
+const ONE_2_14: i32 = 0x4000;
+
+const DEFAULT_MINIMUM_DISTANCE: i32 = 64;
+const DEFAULT_CONTROL_VALUE_CUT_IN: i32 = 68;
+const DEFAULT_DELTA_BASE: i32 = 9;
+const DEFAULT_DELTA_SHIFT: i32 = 3;
+
+bitflags! {
+    flags PointFlags: u8 {
+        const ON_CURVE = 1 << 0,
+        const TOUCHED_X = 1 << 1,
+        const TOUCHED_Y = 1 << 2,
+    }
+}
+
+/// A point of a grid-fitted glyph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HintedPoint {
+    /// The position in 26.6 fixed point pixels, with y pointing up.
+    pub position: Point2D<i32>,
+    pub on_curve: bool,
+}
+
+/// A glyph outline grid-fitted to a particular size.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HintedGlyph {
+    pub points: Vec<HintedPoint>,
+    /// The index of the last point of each contour.
+    pub contour_end_indices: Vec<u16>,
+    /// The advance width in 26.6 fixed point pixels. This is always a whole number of pixels.
+    pub advance_width: i32,
+}
+
+impl HintedGlyph {
+    /// Returns the outline as path commands in pixels, with y pointing up.
+    pub fn path_commands(&self) -> Vec<PathCommand> {
+        let mut commands = vec![];
+        let mut start_index = 0;
+        for &end_index in &self.contour_end_indices {
+            let end_index = end_index as usize;
+            if end_index < start_index || end_index >= self.points.len() {
+                break
+            }
+            let contour = &self.points[start_index..(end_index + 1)];
+            start_index = end_index + 1;
+
+            // A contour may start at an off-curve point, in which case it starts at the last point
+            // if that's on the curve, or else midway between the two.
+            let (first, last) = (contour[0], contour[contour.len() - 1]);
+            let (start, rest) = if first.on_curve {
+                (pixels(first.position), &contour[1..])
+            } else if last.on_curve {
+                (pixels(last.position), &contour[..(contour.len() - 1)])
+            } else {
+                (midpoint(pixels(first.position), pixels(last.position)), contour)
+            };
+
+            commands.push(PathCommand::MoveTo(start));
+            let mut control_point = None;
+            for point in rest {
+                let position = pixels(point.position);
+                if point.on_curve {
+                    match control_point.take() {
+                        Some(control_point) => {
+                            commands.push(PathCommand::QuadTo(control_point, position))
+                        }
+                        None => commands.push(PathCommand::LineTo(position)),
+                    }
+                } else {
+                    if let Some(control_point) = control_point {
+                        commands.push(PathCommand::QuadTo(control_point,
+                                                          midpoint(control_point, position)))
+                    }
+                    control_point = Some(position)
+                }
+            }
+            if let Some(control_point) = control_point {
+                commands.push(PathCommand::QuadTo(control_point, start))
+            }
+            commands.push(PathCommand::ClosePath)
+        }
+        commands
+    }
+}
+
+#[inline]
+fn pixels(position: Point2D<i32>) -> Point2D<f32> {
+    Point2D::new(position.x as f32 / 64.0, position.y as f32 / 64.0)
+}
+
+#[inline]
+fn midpoint(a: Point2D<f32>, b: Point2D<f32>) -> Point2D<f32> {
+    Point2D::new((a.x + b.x) * 0.5, (a.y + b.y) * 0.5)
+}
+
+/// Grid-fits the glyphs of a font with TrueType outlines.
+pub struct Hinter<'a> {
+    glyf_table: GlyfTable<'a>,
+    loca_table: LocaTable<'a>,
+    hhea_table: HheaTable,
+    hmtx_table: HmtxTable<'a>,
+    vmtx_table: Option<HmtxTable<'a>>,
+    maxp_table: MaxpTable,
+    units_per_em: u16,
+    font_program: &'a [u8],
+    control_value_program: &'a [u8],
+    unscaled_cvt: Vec<i16>,
+    // The state left behind by the font program.
+    font_state: State,
+    // The state left behind by the control value program for the current size.
+    size_state: Option<State>,
+}
+
+impl<'a> Hinter<'a> {
+    /// Loads the hinting tables of a font and runs its font program.
+    pub fn new(font: &'a FontData<'a>) -> Result<Hinter<'a>, Error> {
+        let head_table = try!(font.head_table());
+        let maxp_table = try!(font.maxp_table());
+        let hhea_table = try!(font.hhea_table());
+        let hmtx_table = try!(font.hmtx_table(&hhea_table));
+        let vmtx_table = font.vhea_table().and_then(|vhea_table| font.vmtx_table(&vhea_table)).ok();
+        let glyf_table = try!(font.glyf_table());
+        let loca_table = try!(font.loca_table(&head_table));
+
+        // All of the hinting tables are optional.
+        let unscaled_cvt = match font.cvt_table() {
+            Ok(cvt_table) => try!(cvt_table.values()),
+            Err(_) => vec![],
+        };
+        let font_program = font.fpgm_table().map(|table| table.bytes).unwrap_or(&[]);
+        let control_value_program = font.prep_table().map(|table| table.bytes).unwrap_or(&[]);
+
+        let mut hinter = Hinter {
+            glyf_table: glyf_table,
+            loca_table: loca_table,
+            hhea_table: hhea_table,
+            hmtx_table: hmtx_table,
+            vmtx_table: vmtx_table,
+            maxp_table: maxp_table,
+            units_per_em: head_table.units_per_em,
+            font_program: font_program,
+            control_value_program: control_value_program,
+            font_state: State {
+                definitions: Definitions {
+                    functions: vec![],
+                    instructions: vec![],
+                    max_functions: maxp_table.max_function_defs as usize,
+                    max_instructions: maxp_table.max_instruction_defs as usize,
+                },
+                graphics_state: GraphicsState::default(),
+                cvt: vec![0; unscaled_cvt.len()],
+                storage: vec![0; maxp_table.max_storage as usize],
+                twilight: Zone::new(maxp_table.max_twilight_points as usize +
+                                    EXTRA_TWILIGHT_POINTS),
+                pixels_per_em: 0,
+                scale: 0,
+                grayscale: false,
+            },
+            unscaled_cvt: unscaled_cvt,
+            size_state: None,
+        };
+
+        // The font program runs at no particular size.
+        let state = hinter.font_state.clone();
+        let mut machine = hinter.machine(state, Zone::new(0), &[]);
+        try!(machine.run(CodeRange::Font));
+        hinter.font_state.definitions = machine.state.definitions;
+        Ok(hinter)
+    }
+
+    /// Sets the size in pixels per em to grid-fit glyphs to, and runs the control value program.
+    ///
+    /// `grayscale` tells the font's programs, through `GETINFO`, whether the glyphs will be
+    /// rendered with antialiasing.
+    pub fn set_size(&mut self, pixels_per_em: u16, grayscale: bool) -> Result<(), Error> {
+        self.size_state = None;
+
+        let scale = div_fix((pixels_per_em as i32) << 6, self.units_per_em as i32);
+        let mut state = self.font_state.clone();
+        state.cvt = self.unscaled_cvt.iter().map(|&value| mul_fix(value as i32, scale)).collect();
+        state.pixels_per_em = pixels_per_em;
+        state.scale = scale;
+        state.grayscale = grayscale;
+
+        let mut machine = self.machine(state, Zone::new(0), &[]);
+        try!(machine.run(CodeRange::ControlValue));
+
+        // The control value program can't change these for the glyph programs.
+        let mut state = machine.state;
+        state.graphics_state = machine.graphics_state;
+        state.graphics_state.reset_for_glyph();
+        self.size_state = Some(state);
+        Ok(())
+    }
+
+    /// Returns the grid-fitted outline of a glyph at the size last passed to `set_size`.
+    ///
+    /// The components of composite glyphs are hinted with their own programs and put in place
+    /// before the composite glyph's program runs on them all, as in FreeType. Errors in a glyph's
+    /// program leave the points where the program left them, as FreeType does.
+    pub fn hint_glyph(&self, glyph_id: u16) -> Result<HintedGlyph, Error> {
+        let state = try!(self.size_state.as_ref().ok_or(Error::InvalidArgument));
+        let glyph = try!(self.load_glyph(state, glyph_id, 0, &mut Usage::default()));
+        if glyph.points.is_empty() {
+            let horizontal_metrics = try!(self.hmtx_table.metrics(glyph_id));
+            let advance = mul_fix(horizontal_metrics.advance as i32, state.scale);
+            return Ok(HintedGlyph {
+                points: vec![],
+                contour_end_indices: vec![],
+                advance_width: pixel_round(advance),
+            })
+        }
+
+        // Move the origin to the hinted left phantom point.
+        let (origin, advance_point) = (glyph.phantom_points[0], glyph.phantom_points[1]);
+        let points = glyph.points.iter().map(|point| {
+            HintedPoint {
+                position: Point2D::new(point.position.x.wrapping_sub(origin.x), point.position.y),
+                on_curve: point.on_curve,
+            }
+        }).collect();
+        Ok(HintedGlyph {
+            points: points,
+            contour_end_indices: glyph.contour_end_indices,
+            advance_width: pixel_round(advance_point.x.wrapping_sub(origin.x)),
+        })
+    }
+
+    // Grid-fits a glyph that may be a component, at the given depth, of a composite glyph.
+    // `usage` accumulates what the outermost glyph has used so far.
+    fn load_glyph(&self, state: &State, glyph_id: u16, depth: u32, usage: &mut Usage)
+                  -> Result<LoadedGlyph, Error> {
+        if try!(self.glyf_table.is_composite(&self.loca_table, glyph_id as u32)) {
+            return self.load_composite_glyph(state, glyph_id, depth, usage)
+        }
+
+        let glyph = match try!(self.glyf_table.simple_glyph(&self.loca_table, glyph_id as u32)) {
+            Some(ref glyph) if !glyph.points.is_empty() => glyph.clone(),
+            _ => {
+                let bounds = Rect::new(Point2D::new(0, 0), Size2D::new(0, 0));
+                let unscaled = try!(self.phantom_points(glyph_id, &bounds));
+                return Ok(LoadedGlyph {
+                    points: vec![],
+                    contour_end_indices: vec![],
+                    phantom_points: self.scale_phantom_points(state, &unscaled),
+                })
+            }
+        };
+        try!(usage.add(&self.glyf_table.limits,
+                       glyph.points.len(),
+                       glyph.contour_end_indices.len()));
+
+        let mut unscaled: Vec<_> = glyph.points.iter().map(|point| {
+            Point2D::new(point.position.x as i32, point.position.y as i32)
+        }).collect();
+        unscaled.extend_from_slice(&try!(self.phantom_points(glyph_id, &glyph.bounds)));
+
+        let scaled: Vec<_> = unscaled.iter().map(|point| {
+            Point2D::new(mul_fix(point.x, state.scale), mul_fix(point.y, state.scale))
+        }).collect();
+        let mut flags: Vec<_> = glyph.points.iter().map(|point| {
+            if point.on_curve {
+                ON_CURVE
+            } else {
+                PointFlags::empty()
+            }
+        }).collect();
+        flags.extend(vec![PointFlags::empty(); PHANTOM_POINT_COUNT]);
+
+        let zone = Zone {
+            unscaled: unscaled,
+            original: scaled.clone(),
+            current: scaled,
+            flags: flags,
+            contour_end_indices: glyph.contour_end_indices,
+        };
+        Ok(self.run_glyph_program(state, zone, glyph.instructions, state.scale))
+    }
+
+    fn load_composite_glyph(&self, state: &State, glyph_id: u16, depth: u32, usage: &mut Usage)
+                            -> Result<LoadedGlyph, Error> {
+        let limits = &self.glyf_table.limits;
+        try!(limits.check(Limit::CompositeDepth, depth + 1));
+        let glyph = match try!(self.glyf_table.composite_glyph(&self.loca_table,
+                                                               glyph_id as u32)) {
+            Some(glyph) => glyph,
+            None => return Err(Error::Malformed),
+        };
+
+        let unscaled = try!(self.phantom_points(glyph_id, &glyph.bounds));
+        let mut phantom_points = self.scale_phantom_points(state, &unscaled);
+        let (mut points, mut contour_end_indices) = (vec![], vec![]);
+        for component in &glyph.components {
+            usage.components += 1;
+            try!(limits.check(Limit::ComponentsPerGlyph, usage.components));
+            let loaded = try!(self.load_glyph(state, component.glyph_id, depth + 1, usage));
+            if component.use_my_metrics {
+                phantom_points = loaded.phantom_points
+            }
+
+            // The component's points are already hinted, so transform them as they are. The
+            // offset is scaled separately, and maybe rounded so as not to undo the hinting.
+            let transform = &component.transform;
+            let matrix = [to_16_16(transform.xx), to_16_16(transform.yx),
+                          to_16_16(transform.xy), to_16_16(transform.yy)];
+            let mut offset = Point2D::new(mul_fix(transform.dx as i32, state.scale),
+                                          mul_fix(transform.dy as i32, state.scale));
+            if component.round_offset_to_grid {
+                offset = Point2D::new(pixel_round(offset.x), pixel_round(offset.y))
+            }
+
+            let first_point = points.len() as u16;
+            contour_end_indices.extend(loaded.contour_end_indices.iter().map(|&end_index| {
+                end_index + first_point
+            }));
+            points.extend(loaded.points.iter().map(|point| {
+                let position = point.position;
+                let x = mul_fix(position.x, matrix[0]).wrapping_add(mul_fix(position.y, matrix[2]));
+                let y = mul_fix(position.x, matrix[1]).wrapping_add(mul_fix(position.y, matrix[3]));
+                HintedPoint {
+                    position: Point2D::new(x.wrapping_add(offset.x), y.wrapping_add(offset.y)),
+                    on_curve: point.on_curve,
+                }
+            }))
+        }
+
+        let hinting_enabled = (state.graphics_state.instruct_control & 1) == 0;
+        if !hinting_enabled || glyph.instructions.is_empty() || points.is_empty() {
+            return Ok(LoadedGlyph {
+                points: points,
+                contour_end_indices: contour_end_indices,
+                phantom_points: phantom_points,
+            })
+        }
+
+        // The composite glyph's program measures everything from the hinted components, so
+        // those stand in for the unscaled points too, at a scale of one.
+        let mut current: Vec<_> = points.iter().map(|point| point.position).collect();
+        current.extend_from_slice(&phantom_points);
+        let mut flags: Vec<_> = points.iter().map(|point| {
+            if point.on_curve {
+                ON_CURVE
+            } else {
+                PointFlags::empty()
+            }
+        }).collect();
+        flags.extend(vec![PointFlags::empty(); PHANTOM_POINT_COUNT]);
+
+        let zone = Zone {
+            unscaled: current.clone(),
+            original: current.clone(),
+            current: current,
+            flags: flags,
+            contour_end_indices: contour_end_indices,
+        };
+        Ok(self.run_glyph_program(state, zone, glyph.instructions, 1 << 16))
+    }
+
+    // Rounds the phantom points of a glyph zone, runs the glyph program on it, and returns the
+    // points as the program leaves them. `unscaled_scale` takes the zone's unscaled points to
+    // 26.6 pixels, in 16.16.
+    fn run_glyph_program(&self,
+                         state: &State,
+                         mut zone: Zone,
+                         instructions: &'a [u8],
+                         unscaled_scale: i32)
+                         -> LoadedGlyph {
+        let phantom_start = zone.current.len() - PHANTOM_POINT_COUNT;
+        let hinting_enabled = (state.graphics_state.instruct_control & 1) == 0;
+        if hinting_enabled {
+            zone.current[phantom_start].x = pixel_round(zone.current[phantom_start].x);
+            zone.current[phantom_start + 1].x = pixel_round(zone.current[phantom_start + 1].x);
+            zone.current[phantom_start + 2].y = pixel_round(zone.current[phantom_start + 2].y);
+            zone.current[phantom_start + 3].y = pixel_round(zone.current[phantom_start + 3].y);
+
+            if !instructions.is_empty() {
+                let mut machine = self.machine(state.clone(), zone, instructions);
+                machine.unscaled_scale = unscaled_scale;
+                if (state.graphics_state.instruct_control & 2) != 0 {
+                    machine.graphics_state = GraphicsState::default()
+                }
+                let _ = machine.run(CodeRange::Glyph);
+                zone = machine.glyph_zone
+            }
+        }
+
+        let points = zone.current[..phantom_start].iter().zip(zone.flags.iter()).map(|(point,
+                                                                                      flags)| {
+            HintedPoint {
+                position: *point,
+                on_curve: flags.contains(ON_CURVE),
+            }
+        }).collect();
+        LoadedGlyph {
+            points: points,
+            contour_end_indices: zone.contour_end_indices,
+            phantom_points: [zone.current[phantom_start],
+                             zone.current[phantom_start + 1],
+                             zone.current[phantom_start + 2],
+                             zone.current[phantom_start + 3]],
+        }
+    }
+
+    // Returns the phantom points of a glyph in font units: the origin, the advance, and the top
+    // and bottom of the vertical advance.
+    fn phantom_points(&self, glyph_id: u16, bounds: &Rect<i16>)
+                      -> Result<[Point2D<i32>; PHANTOM_POINT_COUNT], Error> {
+        let horizontal_metrics = try!(self.hmtx_table.metrics(glyph_id));
+
+        // Without vertical metrics, FreeType falls back to the typographic ascender and
+        // descender from `OS/2`. We use the ones from `hhea`.
+        //
+        // The bounds come straight from the glyph header, which may have `yMin` above `yMax`, so
+        // undo the wrapping subtraction that made the height instead of adding in `i16`.
+        let y_max = bounds.origin.y.wrapping_add(bounds.size.height) as i32;
+        let (top_side_bearing, advance_height) = match self.vmtx_table {
+            Some(ref vmtx_table) => {
+                let vertical_metrics = try!(vmtx_table.metrics(glyph_id));
+                (vertical_metrics.side_bearing as i32, vertical_metrics.advance as i32)
+            }
+            None => {
+                let (ascender, descender) = (self.hhea_table.ascender as i32,
+                                             self.hhea_table.descender as i32);
+                (ascender - y_max, (ascender - descender).abs())
+            }
+        };
+
+        let origin_x = bounds.origin.x as i32 - horizontal_metrics.side_bearing as i32;
+        let top_y = y_max + top_side_bearing;
+        Ok([
+            Point2D::new(origin_x, 0),
+            Point2D::new(origin_x + horizontal_metrics.advance as i32, 0),
+            Point2D::new(0, top_y),
+            Point2D::new(0, top_y - advance_height),
+        ])
+    }
+
+    // Scales phantom points to 26.6 pixels, rounding them to the grid if hinting is enabled.
+    fn scale_phantom_points(&self, state: &State, unscaled: &[Point2D<i32>; PHANTOM_POINT_COUNT])
+                            -> [Point2D<i32>; PHANTOM_POINT_COUNT] {
+        let mut scaled = [Point2D::new(0, 0); PHANTOM_POINT_COUNT];
+        for (scaled, unscaled) in scaled.iter_mut().zip(unscaled.iter()) {
+            *scaled = Point2D::new(mul_fix(unscaled.x, state.scale),
+                                   mul_fix(unscaled.y, state.scale))
+        }
+        if (state.graphics_state.instruct_control & 1) == 0 {
+            scaled[0].x = pixel_round(scaled[0].x);
+            scaled[1].x = pixel_round(scaled[1].x);
+            scaled[2].y = pixel_round(scaled[2].y);
+            scaled[3].y = pixel_round(scaled[3].y);
+        }
+        scaled
+    }
+
+    fn machine(&self, state: State, glyph_zone: Zone, glyph_program: &'a [u8]) -> Machine<'a> {
+        let scale = state.scale;
+        Machine {
+            programs: [self.font_program, self.control_value_program, glyph_program],
+            graphics_state: state.graphics_state,
+            state: state,
+            glyph_zone: glyph_zone,
+            stack: vec![],
+            stack_limit: self.maxp_table.max_stack_elements as usize + EXTRA_STACK_ELEMENTS,
+            call_stack: vec![],
+            range: CodeRange::Font,
+            initial_range: CodeRange::Font,
+            ip: 0,
+            next_ip: 0,
+            f_dot_p: ONE_2_14,
+            instruction_count: 0,
+            unscaled_scale: scale,
+        }
+    }
+}
+
+// A glyph grid-fitted where it stands, before its origin is moved to its left phantom point.
+struct LoadedGlyph {
+    points: Vec<HintedPoint>,
+    contour_end_indices: Vec<u16>,
+    phantom_points: [Point2D<i32>; PHANTOM_POINT_COUNT],
+}
+
+// The resources that a glyph has used so far, including all of its components.
+#[derive(Clone, Copy, Default)]
+struct Usage {
+    points: u32,
+    contours: u32,
+    components: u32,
+}
+
+impl Usage {
+    fn add(&mut self, limits: &Limits, points: usize, contours: usize) -> Result<(), Error> {
+        self.points = self.points.saturating_add(points as u32);
+        self.contours = self.contours.saturating_add(contours as u32);
+        try!(limits.check(Limit::PointsPerGlyph, self.points));
+        try!(limits.check(Limit::ContoursPerGlyph, self.contours));
+
+        // The contour end indices of the whole glyph have to fit in 16 bits.
+        if self.points > u16::MAX as u32 {
+            return Err(Error::Malformed)
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CodeRange {
+    Font = 0,
+    ControlValue = 1,
+    Glyph = 2,
+}
+
+// A function defined with `FDEF` or an instruction defined with `IDEF`.
+#[derive(Clone, Copy, Debug)]
+struct Definition {
+    number: u32,
+    range: CodeRange,
+    // The first instruction of the body and the closing `ENDF`.
+    start: usize,
+    end: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Definitions {
+    functions: Vec<Definition>,
+    instructions: Vec<Definition>,
+    max_functions: usize,
+    max_instructions: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CallRecord {
+    caller_range: CodeRange,
+    caller_ip: usize,
+    // How many more times to run the function, for `LOOPCALL`.
+    count: i32,
+    definition: Definition,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RoundState {
+    ToGrid,
+    ToHalfGrid,
+    ToDoubleGrid,
+    DownToGrid,
+    UpToGrid,
+    Off,
+    Super,
+    Super45,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct GraphicsState {
+    rp0: usize,
+    rp1: usize,
+    rp2: usize,
+    dual_vector: Point2D<i32>,
+    projection_vector: Point2D<i32>,
+    freedom_vector: Point2D<i32>,
+    // The zone each zone pointer refers to.
+    zp0: usize,
+    zp1: usize,
+    zp2: usize,
+    loop_count: i32,
+    minimum_distance: i32,
+    round_state: RoundState,
+    // The parameters of `SROUND` and `S45ROUND`, in 26.6.
+    period: i32,
+    phase: i32,
+    threshold: i32,
+    auto_flip: bool,
+    control_value_cut_in: i32,
+    single_width_cut_in: i32,
+    single_width_value: i32,
+    delta_base: i32,
+    delta_shift: i32,
+    instruct_control: u8,
+    scan_control: bool,
+    scan_type: i32,
+}
+
+impl Default for GraphicsState {
+    fn default() -> GraphicsState {
+        GraphicsState {
+            rp0: 0,
+            rp1: 0,
+            rp2: 0,
+            dual_vector: Point2D::new(ONE_2_14, 0),
+            projection_vector: Point2D::new(ONE_2_14, 0),
+            freedom_vector: Point2D::new(ONE_2_14, 0),
+            zp0: GLYPH_ZONE,
+            zp1: GLYPH_ZONE,
+            zp2: GLYPH_ZONE,
+            loop_count: 1,
+            minimum_distance: DEFAULT_MINIMUM_DISTANCE,
+            round_state: RoundState::ToGrid,
+            period: 64,
+            phase: 0,
+            threshold: 0,
+            auto_flip: true,
+            control_value_cut_in: DEFAULT_CONTROL_VALUE_CUT_IN,
+            single_width_cut_in: 0,
+            single_width_value: 0,
+            delta_base: DEFAULT_DELTA_BASE,
+            delta_shift: DEFAULT_DELTA_SHIFT,
+            instruct_control: 0,
+            scan_control: false,
+            scan_type: 0,
+        }
+    }
+}
+
+impl GraphicsState {
+    // Resets the parts of the state that every program starts with fresh.
+    fn reset_for_glyph(&mut self) {
+        self.zp0 = GLYPH_ZONE;
+        self.zp1 = GLYPH_ZONE;
+        self.zp2 = GLYPH_ZONE;
+        self.dual_vector = Point2D::new(ONE_2_14, 0);
+        self.projection_vector = Point2D::new(ONE_2_14, 0);
+        self.freedom_vector = Point2D::new(ONE_2_14, 0);
+        self.round_state = RoundState::ToGrid;
+        self.loop_count = 1;
+    }
+}
+
+// The points the instructions operate on. The twilight zone holds points that aren't part of the
+// outline, for use as references.
+#[derive(Clone, Debug)]
+struct Zone {
+    // In font units. These are always zero in the twilight zone.
+    unscaled: Vec<Point2D<i32>>,
+    original: Vec<Point2D<i32>>,
+    current: Vec<Point2D<i32>>,
+    flags: Vec<PointFlags>,
+    contour_end_indices: Vec<u16>,
+}
+
+impl Zone {
+    fn new(point_count: usize) -> Zone {
+        Zone {
+            unscaled: vec![Point2D::new(0, 0); point_count],
+            original: vec![Point2D::new(0, 0); point_count],
+            current: vec![Point2D::new(0, 0); point_count],
+            flags: vec![PointFlags::empty(); point_count],
+            contour_end_indices: vec![],
+        }
+    }
+}
+
+// The state that outlives a single program: everything the font program leaves for the control
+// value program, and everything that leaves for the glyph programs.
+#[derive(Clone, Debug)]
+struct State {
+    definitions: Definitions,
+    graphics_state: GraphicsState,
+    cvt: Vec<i32>,
+    storage: Vec<i32>,
+    twilight: Zone,
+    pixels_per_em: u16,
+    // The scale from font units to 26.6 pixels, in 16.16.
+    scale: i32,
+    grayscale: bool,
+}
+
+struct Machine<'a> {
+    // Indexed by `CodeRange`.
+    programs: [&'a [u8]; 3],
+    state: State,
+    graphics_state: GraphicsState,
+    glyph_zone: Zone,
+    stack: Vec<i32>,
+    stack_limit: usize,
+    call_stack: Vec<CallRecord>,
+    range: CodeRange,
+    // The range the current program started in.
+    initial_range: CodeRange,
+    ip: usize,
+    next_ip: usize,
+    // The projection of the freedom vector onto the projection vector, in 2.14.
+    f_dot_p: i32,
+    instruction_count: u32,
+    // The scale from the glyph zone's unscaled points to 26.6 pixels, in 16.16.
+    unscaled_scale: i32,
+}
+
+impl<'a> Machine<'a> {
+    fn run(&mut self, range: CodeRange) -> Result<(), Error> {
+        self.graphics_state.reset_for_glyph();
+        self.compute_f_dot_p();
+        self.stack.clear();
+        self.call_stack.clear();
+        self.range = range;
+        self.initial_range = range;
+        self.ip = 0;
+
+        loop {
+            let code = self.programs[self.range as usize];
+            if self.ip >= code.len() {
+                // Running off the end of a function is an error.
+                return if self.call_stack.is_empty() {
+                    Ok(())
+                } else {
+                    Err(Error::Malformed)
+                }
+            }
+
+            self.instruction_count += 1;
+            if self.instruction_count > MAX_INSTRUCTIONS {
+                return Err(Error::Malformed)
+            }
+
+            let opcode = code[self.ip];
+            self.next_ip = self.ip + try!(instruction_length(code, self.ip));
+            try!(self.execute(opcode));
+            self.ip = self.next_ip
+        }
+    }
+
+    // Moves to the next instruction without running the current one, and returns its opcode.
+    fn skip_instruction(&mut self) -> Result<u8, Error> {
+        let code = self.programs[self.range as usize];
+        self.ip = self.next_ip;
+        if self.ip >= code.len() {
+            return Err(Error::Malformed)
+        }
+        self.next_ip = self.ip + try!(instruction_length(code, self.ip));
+        Ok(code[self.ip])
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Result<i32, Error> {
+        self.stack.pop().ok_or(Error::Malformed)
+    }
+
+    #[inline]
+    fn pop_point(&mut self) -> Result<usize, Error> {
+        self.pop().map(|value| value as u16 as usize)
+    }
+
+    #[inline]
+    fn push(&mut self, value: i32) -> Result<(), Error> {
+        if self.stack.len() >= self.stack_limit {
+            return Err(Error::Malformed)
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    #[inline]
+    fn zone(&self, zone: usize) -> &Zone {
+        if zone == TWILIGHT_ZONE {
+            &self.state.twilight
+        } else {
+            &self.glyph_zone
+        }
+    }
+
+    #[inline]
+    fn zone_mut(&mut self, zone: usize) -> &mut Zone {
+        if zone == TWILIGHT_ZONE {
+            &mut self.state.twilight
+        } else {
+            &mut self.glyph_zone
+        }
+    }
+
+    #[inline]
+    fn has_point(&self, zone: usize, point: usize) -> bool {
+        point < self.zone(zone).current.len()
+    }
+
+    #[inline]
+    fn current(&self, zone: usize, point: usize) -> Point2D<i32> {
+        self.zone(zone).current[point]
+    }
+
+    #[inline]
+    fn original(&self, zone: usize, point: usize) -> Point2D<i32> {
+        self.zone(zone).original[point]
+    }
+
+    #[inline]
+    fn unscaled(&self, zone: usize, point: usize) -> Point2D<i32> {
+        self.zone(zone).unscaled[point]
+    }
+
+    #[inline]
+    fn project(&self, a: Point2D<i32>, b: Point2D<i32>) -> i32 {
+        let vector = self.graphics_state.projection_vector;
+        dot_2_14(a.x.wrapping_sub(b.x), a.y.wrapping_sub(b.y), vector.x, vector.y)
+    }
+
+    #[inline]
+    fn dual_project(&self, a: Point2D<i32>, b: Point2D<i32>) -> i32 {
+        let vector = self.graphics_state.dual_vector;
+        dot_2_14(a.x.wrapping_sub(b.x), a.y.wrapping_sub(b.y), vector.x, vector.y)
+    }
+
+    #[inline]
+    fn fast_project(&self, a: Point2D<i32>) -> i32 {
+        self.project(a, Point2D::new(0, 0))
+    }
+
+    fn compute_f_dot_p(&mut self) {
+        let (freedom, projection) = (self.graphics_state.freedom_vector,
+                                     self.graphics_state.projection_vector);
+        let f_dot_p = ((projection.x as i64 * freedom.x as i64 +
+                        projection.y as i64 * freedom.y as i64) >> 14) as i32;
+
+        // Nearly perpendicular vectors would make moves explode.
+        self.f_dot_p = if f_dot_p.abs() < 0x400 {
+            ONE_2_14
+        } else {
+            f_dot_p
+        }
+    }
+
+    // Moves a point along the freedom vector so that its projection moves by `distance`.
+    fn move_point(&mut self, zone: usize, point: usize, distance: i32) {
+        let (freedom, f_dot_p) = (self.graphics_state.freedom_vector, self.f_dot_p);
+        let zone = self.zone_mut(zone);
+        if freedom.x != 0 {
+            let delta = mul_div(distance, freedom.x, f_dot_p);
+            zone.current[point].x = zone.current[point].x.wrapping_add(delta);
+            zone.flags[point].insert(TOUCHED_X)
+        }
+        if freedom.y != 0 {
+            let delta = mul_div(distance, freedom.y, f_dot_p);
+            zone.current[point].y = zone.current[point].y.wrapping_add(delta);
+            zone.flags[point].insert(TOUCHED_Y)
+        }
+    }
+
+    // Like `move_point`, but moves the original position and doesn't touch the point.
+    fn move_original_point(&mut self, zone: usize, point: usize, distance: i32) {
+        let (freedom, f_dot_p) = (self.graphics_state.freedom_vector, self.f_dot_p);
+        let zone = self.zone_mut(zone);
+        if freedom.x != 0 {
+            let delta = mul_div(distance, freedom.x, f_dot_p);
+            zone.original[point].x = zone.original[point].x.wrapping_add(delta)
+        }
+        if freedom.y != 0 {
+            let delta = mul_div(distance, freedom.y, f_dot_p);
+            zone.original[point].y = zone.original[point].y.wrapping_add(delta)
+        }
+    }
+
+    // Shifts a point in zone 2 by a displacement already projected onto the freedom vector.
+    fn shift_point(&mut self, point: usize, dx: i32, dy: i32, touch: bool) {
+        let (freedom, zone) = (self.graphics_state.freedom_vector, self.graphics_state.zp2);
+        let zone = self.zone_mut(zone);
+        if freedom.x != 0 {
+            zone.current[point].x = zone.current[point].x.wrapping_add(dx);
+            if touch {
+                zone.flags[point].insert(TOUCHED_X)
+            }
+        }
+        if freedom.y != 0 {
+            zone.current[point].y = zone.current[point].y.wrapping_add(dy);
+            if touch {
+                zone.flags[point].insert(TOUCHED_Y)
+            }
+        }
+    }
+
+    fn round(&self, distance: i32) -> i32 {
+        let state = &self.graphics_state;
+        match state.round_state {
+            RoundState::ToGrid => {
+                if distance >= 0 {
+                    cmp::max(distance.wrapping_add(32) & !63, 0)
+                } else {
+                    cmp::min(-(32i32.wrapping_sub(distance) & !63), 0)
+                }
+            }
+            RoundState::ToHalfGrid => {
+                if distance >= 0 {
+                    let value = (distance & !63) + 32;
+                    if value < 0 { 32 } else { value }
+                } else {
+                    let value = -((distance.wrapping_neg() & !63) + 32);
+                    if value > 0 { -32 } else { value }
+                }
+            }
+            RoundState::ToDoubleGrid => {
+                if distance >= 0 {
+                    cmp::max(distance.wrapping_add(16) & !31, 0)
+                } else {
+                    cmp::min(-(16i32.wrapping_sub(distance) & !31), 0)
+                }
+            }
+            RoundState::DownToGrid => {
+                if distance >= 0 {
+                    distance & !63
+                } else {
+                    -(distance.wrapping_neg() & !63)
+                }
+            }
+            RoundState::UpToGrid => {
+                if distance >= 0 {
+                    cmp::max(distance.wrapping_add(63) & !63, 0)
+                } else {
+                    cmp::min(-(63i32.wrapping_sub(distance) & !63), 0)
+                }
+            }
+            RoundState::Off => distance,
+            RoundState::Super => {
+                let (period, phase, threshold) = (state.period, state.phase, state.threshold);
+                if distance >= 0 {
+                    let value = (distance.wrapping_add(threshold - phase) & -period) + phase;
+                    if value < 0 { phase } else { value }
+                } else {
+                    let value = -((threshold - phase).wrapping_sub(distance) & -period) - phase;
+                    if value > 0 { -phase } else { value }
+                }
+            }
+            RoundState::Super45 => {
+                let (period, phase, threshold) = (state.period, state.phase, state.threshold);
+                if distance >= 0 {
+                    let value = distance.wrapping_add(threshold - phase) / period * period +
+                        phase;
+                    if value < 0 { phase } else { value }
+                } else {
+                    let value = -((threshold - phase).wrapping_sub(distance) / period * period) -
+                        phase;
+                    if value > 0 { -phase } else { value }
+                }
+            }
+        }
+    }
+
+    fn set_super_round(&mut self, grid_period: i32, selector: i32) {
+        let period = match selector & 0xc0 {
+            0x00 => grid_period / 2,
+            0x80 => grid_period * 2,
+            _ => grid_period,
+        };
+        let phase = match selector & 0x30 {
+            0x00 => 0,
+            0x10 => period / 4,
+            0x20 => period / 2,
+            _ => period * 3 / 4,
+        };
+        let threshold = match selector & 0x0f {
+            0 => period - 1,
+            threshold => (threshold - 4) * period / 8,
+        };
+
+        // Convert from 2.14-ish grid units to 26.6.
+        self.graphics_state.period = period >> 8;
+        self.graphics_state.phase = phase >> 8;
+        self.graphics_state.threshold = threshold >> 8
+    }
+
+    // Computes the unit vector along the line between two points for `SPVTL`, `SFVTL`, and
+    // `SDPVTL`. The first point is in zone 2 and the second in zone 1.
+    fn line_vector(&self, p1: usize, p2: usize, original: bool, perpendicular: &mut bool)
+                   -> Option<Point2D<i32>> {
+        let (zp1, zp2) = (self.graphics_state.zp1, self.graphics_state.zp2);
+        let (a, b) = if original {
+            (self.original(zp1, p2), self.original(zp2, p1))
+        } else {
+            (self.current(zp1, p2), self.current(zp2, p1))
+        };
+        let (mut dx, mut dy) = (a.x.wrapping_sub(b.x), a.y.wrapping_sub(b.y));
+
+        // Coincident points give the X axis.
+        if dx == 0 && dy == 0 {
+            dx = ONE_2_14;
+            *perpendicular = false
+        }
+        if *perpendicular {
+            let (x, y) = (dx, dy);
+            dx = y.wrapping_neg();
+            dy = x
+        }
+        normalize(dx, dy)
+    }
+
+    // Returns how far the reference point used by `SHP`, `SHC`, and `SHZ` has moved, along
+    // with its zone and index.
+    fn point_displacement(&self, opcode: u8) -> Option<(i32, i32, usize, usize)> {
+        let state = &self.graphics_state;
+        let (zone, point) = if (opcode & 1) != 0 {
+            (state.zp0, state.rp1)
+        } else {
+            (state.zp1, state.rp2)
+        };
+        if !self.has_point(zone, point) {
+            return None
+        }
+        let distance = self.project(self.current(zone, point), self.original(zone, point));
+        Some((mul_div(distance, state.freedom_vector.x, self.f_dot_p),
+              mul_div(distance, state.freedom_vector.y, self.f_dot_p),
+              zone,
+              point))
+    }
+
+    fn jump(&mut self, offset: i32) -> Result<(), Error> {
+        // A jump to itself with nothing else on the stack can only loop forever.
+        if offset == 0 && self.stack.is_empty() {
+            return Err(Error::Malformed)
+        }
+        let target = self.ip as i64 + offset as i64;
+        if target < 0 {
+            return Err(Error::Malformed)
+        }
+        if let Some(call) = self.call_stack.last() {
+            if target > call.definition.end as i64 {
+                return Err(Error::Malformed)
+            }
+        }
+        self.next_ip = target as usize;
+        Ok(())
+    }
+
+    fn call(&mut self, definition: Definition, count: i32) -> Result<(), Error> {
+        if self.call_stack.len() >= MAX_CALL_DEPTH {
+            return Err(Error::Malformed)
+        }
+        if count > 0 {
+            self.call_stack.push(CallRecord {
+                caller_range: self.range,
+                caller_ip: self.next_ip,
+                count: count,
+                definition: definition,
+            });
+            self.range = definition.range;
+            self.next_ip = definition.start
+        }
+        Ok(())
+    }
+
+    fn function(&self, number: i32) -> Result<Definition, Error> {
+        self.state
+            .definitions
+            .functions
+            .iter()
+            .find(|definition| definition.number == number as u32)
+            .cloned()
+            .ok_or(Error::Malformed)
+    }
+
+    // Handles `FDEF` and `IDEF`.
+    fn define(&mut self, instruction: bool) -> Result<(), Error> {
+        if self.initial_range == CodeRange::Glyph {
+            return Err(Error::Malformed)
+        }
+        let number = try!(self.pop()) as u32;
+        if number > if instruction { 0xff } else { 0xffff } {
+            return Err(Error::Malformed)
+        }
+
+        let start = self.next_ip;
+        loop {
+            match try!(self.skip_instruction()) {
+                0x2c | 0x89 => return Err(Error::Malformed),
+                0x2d => break,
+                _ => {}
+            }
+        }
+        let definition = Definition {
+            number: number,
+            range: self.range,
+            start: start,
+            end: self.ip,
+        };
+
+        // Fonts may redefine functions.
+        let definitions = &mut self.state.definitions;
+        let (list, max_count) = if instruction {
+            (&mut definitions.instructions, definitions.max_instructions)
+        } else {
+            (&mut definitions.functions, definitions.max_functions)
+        };
+        if let Some(existing) = list.iter_mut().find(|existing| existing.number == number) {
+            *existing = definition;
+            return Ok(())
+        }
+        if list.len() >= max_count {
+            return Err(Error::Malformed)
+        }
+        list.push(definition);
+        Ok(())
+    }
+
+    // Skips to the matching `ELSE` or `EIF` for `IF` (`stop_at_else`), or to the matching `EIF`
+    // for `ELSE`.
+    fn skip_conditional(&mut self, stop_at_else: bool) -> Result<(), Error> {
+        let mut depth = 1;
+        loop {
+            match try!(self.skip_instruction()) {
+                0x58 => depth += 1,
+                0x1b if stop_at_else && depth == 1 => return Ok(()),
+                0x59 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(())
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn interpolate_untouched_points(&mut self, x_axis: bool) {
+        let zone = &mut self.glyph_zone;
+        if zone.contour_end_indices.is_empty() {
+            return
+        }
+
+        let touched = if x_axis { TOUCHED_X } else { TOUCHED_Y };
+        let coordinate = |point: &Point2D<i32>| if x_axis { point.x } else { point.y };
+        let mut axis = Axis {
+            unscaled: zone.unscaled.iter().map(&coordinate).collect(),
+            original: zone.original.iter().map(&coordinate).collect(),
+            current: zone.current.iter().map(&coordinate).collect(),
+        };
+
+        let point_count = zone.current.len();
+        let mut point = 0;
+        for &end_point in &zone.contour_end_indices {
+            let end_point = cmp::min(end_point as usize, point_count - 1);
+            let first_point = point;
+            while point <= end_point && !zone.flags[point].contains(touched) {
+                point += 1
+            }
+            if point > end_point {
+                continue
+            }
+
+            let first_touched = point;
+            let mut current_touched = point;
+            point += 1;
+            while point <= end_point {
+                if zone.flags[point].contains(touched) {
+                    axis.interpolate(current_touched + 1, point - 1, current_touched, point);
+                    current_touched = point
+                }
+                point += 1
+            }
+
+            if current_touched == first_touched {
+                axis.shift(first_point, end_point, current_touched)
+            } else {
+                axis.interpolate(current_touched + 1, end_point, current_touched, first_touched);
+                if first_touched > 0 {
+                    axis.interpolate(first_point, first_touched - 1, current_touched, first_touched)
+                }
+            }
+        }
+
+        for (point, &value) in zone.current.iter_mut().zip(axis.current.iter()) {
+            if x_axis {
+                point.x = value
+            } else {
+                point.y = value
+            }
+        }
+    }
+
+    fn execute(&mut self, opcode: u8) -> Result<(), Error> {
+        match opcode {
+            0x00...0x05 => {
+                // SVTCA, SPVTCA, SFVTCA
+                let axis = if (opcode & 1) != 0 {
+                    Point2D::new(ONE_2_14, 0)
+                } else {
+                    Point2D::new(0, ONE_2_14)
+                };
+                if opcode < 0x04 {
+                    self.graphics_state.projection_vector = axis;
+                    self.graphics_state.dual_vector = axis
+                }
+                if opcode < 0x02 || opcode >= 0x04 {
+                    self.graphics_state.freedom_vector = axis
+                }
+                self.compute_f_dot_p()
+            }
+            0x06...0x09 => {
+                // SPVTL, SFVTL
+                let p1 = try!(self.pop_point());
+                let p2 = try!(self.pop_point());
+                let (zp1, zp2) = (self.graphics_state.zp1, self.graphics_state.zp2);
+                if !self.has_point(zp2, p1) || !self.has_point(zp1, p2) {
+                    return Ok(())
+                }
+                let mut perpendicular = (opcode & 1) != 0;
+                let vector = self.line_vector(p1, p2, false, &mut perpendicular);
+                if opcode < 0x08 {
+                    if let Some(vector) = vector {
+                        self.graphics_state.projection_vector = vector
+                    }
+                    self.graphics_state.dual_vector = self.graphics_state.projection_vector
+                } else if let Some(vector) = vector {
+                    self.graphics_state.freedom_vector = vector
+                }
+                self.compute_f_dot_p()
+            }
+            0x0a...0x0b => {
+                // SPVFS, SFVFS
+                let y = try!(self.pop()) as i16 as i32;
+                let x = try!(self.pop()) as i16 as i32;
+                let vector = normalize(x, y);
+                if opcode == 0x0a {
+                    if let Some(vector) = vector {
+                        self.graphics_state.projection_vector = vector
+                    }
+                    self.graphics_state.dual_vector = self.graphics_state.projection_vector
+                } else if let Some(vector) = vector {
+                    self.graphics_state.freedom_vector = vector
+                }
+                self.compute_f_dot_p()
+            }
+            0x0c...0x0d => {
+                // GPV, GFV
+                let vector = if opcode == 0x0c {
+                    self.graphics_state.projection_vector
+                } else {
+                    self.graphics_state.freedom_vector
+                };
+                try!(self.push(vector.x));
+                try!(self.push(vector.y))
+            }
+            0x0e => {
+                // SFVTPV
+                self.graphics_state.freedom_vector = self.graphics_state.projection_vector;
+                self.compute_f_dot_p()
+            }
+            0x0f => {
+                // ISECT
+                let b1 = try!(self.pop_point());
+                let b0 = try!(self.pop_point());
+                let a1 = try!(self.pop_point());
+                let a0 = try!(self.pop_point());
+                let point = try!(self.pop_point());
+                let state = self.graphics_state;
+                if !self.has_point(state.zp0, b0) || !self.has_point(state.zp0, b1) ||
+                        !self.has_point(state.zp1, a0) || !self.has_point(state.zp1, a1) ||
+                        !self.has_point(state.zp2, point) {
+                    return Ok(())
+                }
+
+                let (a0, a1) = (self.current(state.zp1, a0), self.current(state.zp1, a1));
+                let (b0, b1) = (self.current(state.zp0, b0), self.current(state.zp0, b1));
+                let (dbx, dby) = (b1.x.wrapping_sub(b0.x), b1.y.wrapping_sub(b0.y));
+                let (dax, day) = (a1.x.wrapping_sub(a0.x), a1.y.wrapping_sub(a0.y));
+                let (dx, dy) = (b0.x.wrapping_sub(a0.x), b0.y.wrapping_sub(a0.y));
+                let discriminant = mul_div(dax, -dby, 0x40).wrapping_add(mul_div(day, dbx, 0x40));
+                let dot_product = mul_div(dax, dbx, 0x40).wrapping_add(mul_div(day, dby, 0x40));
+
+                // Reject lines within about 3 degrees of parallel, and use the middle of the
+                // four points instead.
+                let position = if 19 * (discriminant as i64).abs() > (dot_product as i64).abs() {
+                    let value = mul_div(dx, -dby, 0x40).wrapping_add(mul_div(dy, dbx, 0x40));
+                    Point2D::new(a0.x.wrapping_add(mul_div(value, dax, discriminant)),
+                                 a0.y.wrapping_add(mul_div(value, day, discriminant)))
+                } else {
+                    Point2D::new(a0.x.wrapping_add(a1.x).wrapping_add(b0.x).wrapping_add(b1.x) / 4,
+                                 a0.y.wrapping_add(a1.y).wrapping_add(b0.y).wrapping_add(b1.y) / 4)
+                };
+                let zone = self.zone_mut(state.zp2);
+                zone.current[point] = position;
+                zone.flags[point].insert(TOUCHED_X | TOUCHED_Y)
+            }
+            0x10 => self.graphics_state.rp0 = try!(self.pop_point()),   // SRP0
+            0x11 => self.graphics_state.rp1 = try!(self.pop_point()),   // SRP1
+            0x12 => self.graphics_state.rp2 = try!(self.pop_point()),   // SRP2
+            0x13...0x16 => {
+                // SZP0, SZP1, SZP2, SZPS
+                let zone = match try!(self.pop()) {
+                    0 => TWILIGHT_ZONE,
+                    1 => GLYPH_ZONE,
+                    _ => return Err(Error::Malformed),
+                };
+                match opcode {
+                    0x13 => self.graphics_state.zp0 = zone,
+                    0x14 => self.graphics_state.zp1 = zone,
+                    0x15 => self.graphics_state.zp2 = zone,
+                    _ => {
+                        self.graphics_state.zp0 = zone;
+                        self.graphics_state.zp1 = zone;
+                        self.graphics_state.zp2 = zone
+                    }
+                }
+            }
+            0x17 => {
+                // SLOOP
+                let count = try!(self.pop());
+                if count < 0 {
+                    return Err(Error::Malformed)
+                }
+                self.graphics_state.loop_count = cmp::min(count, 0xffff)
+            }
+            0x18 => self.graphics_state.round_state = RoundState::ToGrid,       // RTG
+            0x19 => self.graphics_state.round_state = RoundState::ToHalfGrid,   // RTHG
+            0x1a => self.graphics_state.minimum_distance = try!(self.pop()),    // SMD
+            0x1b => try!(self.skip_conditional(false)),                         // ELSE
+            0x1c => {
+                // JMPR
+                let offset = try!(self.pop());
+                try!(self.jump(offset))
+            }
+            0x1d => self.graphics_state.control_value_cut_in = try!(self.pop()),  // SCVTCI
+            0x1e => self.graphics_state.single_width_cut_in = try!(self.pop()),   // SSWCI
+            0x1f => {
+                // SSW
+                let value = try!(self.pop());
+                self.graphics_state.single_width_value = mul_fix(value, self.state.scale)
+            }
+            0x20 => {
+                // DUP
+                let value = try!(self.pop());
+                try!(self.push(value));
+                try!(self.push(value))
+            }
+            0x21 => drop(try!(self.pop())),     // POP
+            0x22 => self.stack.clear(),         // CLEAR
+            0x23 => {
+                // SWAP
+                let a = try!(self.pop());
+                let b = try!(self.pop());
+                try!(self.push(a));
+                try!(self.push(b))
+            }
+            0x24 => {
+                // DEPTH
+                let depth = self.stack.len() as i32;
+                try!(self.push(depth))
+            }
+            0x25 => {
+                // CINDEX
+                let index = try!(self.pop());
+                let depth = self.stack.len();
+                let value = if index <= 0 || index as usize > depth {
+                    0
+                } else {
+                    self.stack[depth - index as usize]
+                };
+                try!(self.push(value))
+            }
+            0x26 => {
+                // MINDEX
+                let index = try!(self.pop());
+                let depth = self.stack.len();
+                if index > 0 && index as usize <= depth {
+                    let value = self.stack.remove(depth - index as usize);
+                    self.stack.push(value)
+                }
+            }
+            0x27 => {
+                // ALIGNPTS
+                let p2 = try!(self.pop_point());
+                let p1 = try!(self.pop_point());
+                let (zp0, zp1) = (self.graphics_state.zp0, self.graphics_state.zp1);
+                if !self.has_point(zp1, p1) || !self.has_point(zp0, p2) {
+                    return Ok(())
+                }
+                let distance = self.project(self.current(zp0, p2), self.current(zp1, p1)) / 2;
+                self.move_point(zp1, p1, distance);
+                self.move_point(zp0, p2, -distance)
+            }
+            0x29 => {
+                // UTP
+                let point = try!(self.pop_point());
+                let (zp0, freedom) = (self.graphics_state.zp0,
+                                      self.graphics_state.freedom_vector);
+                if !self.has_point(zp0, point) {
+                    return Ok(())
+                }
+                let flags = &mut self.zone_mut(zp0).flags[point];
+                if freedom.x != 0 {
+                    flags.remove(TOUCHED_X)
+                }
+                if freedom.y != 0 {
+                    flags.remove(TOUCHED_Y)
+                }
+            }
+            0x2a => {
+                // LOOPCALL
+                let number = try!(self.pop());
+                let count = try!(self.pop());
+                let definition = try!(self.function(number));
+                try!(self.call(definition, count))
+            }
+            0x2b => {
+                // CALL
+                let number = try!(self.pop());
+                let definition = try!(self.function(number));
+                try!(self.call(definition, 1))
+            }
+            0x2c => try!(self.define(false)),   // FDEF
+            0x2d => {
+                // ENDF
+                let mut call = try!(self.call_stack.pop().ok_or(Error::Malformed));
+                call.count -= 1;
+                if call.count > 0 {
+                    self.next_ip = call.definition.start;
+                    self.call_stack.push(call)
+                } else {
+                    self.range = call.caller_range;
+                    self.next_ip = call.caller_ip
+                }
+            }
+            0x2e...0x2f => {
+                // MDAP
+                let point = try!(self.pop_point());
+                let zp0 = self.graphics_state.zp0;
+                if !self.has_point(zp0, point) {
+                    return Ok(())
+                }
+                let distance = if (opcode & 1) != 0 {
+                    let distance = self.fast_project(self.current(zp0, point));
+                    self.round(distance).wrapping_sub(distance)
+                } else {
+                    0
+                };
+                self.move_point(zp0, point, distance);
+                self.graphics_state.rp0 = point;
+                self.graphics_state.rp1 = point
+            }
+            0x30...0x31 => self.interpolate_untouched_points((opcode & 1) != 0),  // IUP
+            0x32...0x33 => {
+                // SHP
+                if self.stack.len() < self.graphics_state.loop_count as usize {
+                    return Err(Error::Malformed)
+                }
+                let (dx, dy, _, _) = match self.point_displacement(opcode) {
+                    Some(displacement) => displacement,
+                    None => return Ok(()),
+                };
+                let zp2 = self.graphics_state.zp2;
+                while self.graphics_state.loop_count > 0 {
+                    let point = try!(self.pop_point());
+                    if self.has_point(zp2, point) {
+                        self.shift_point(point, dx, dy, true)
+                    }
+                    self.graphics_state.loop_count -= 1
+                }
+                self.graphics_state.loop_count = 1
+            }
+            0x34...0x35 => {
+                // SHC
+                let contour = try!(self.pop_point());
+                let zp2 = self.graphics_state.zp2;
+                let contour_count = if zp2 == TWILIGHT_ZONE {
+                    1
+                } else {
+                    self.glyph_zone.contour_end_indices.len()
+                };
+                if contour >= contour_count {
+                    return Ok(())
+                }
+                let (dx, dy, zone, reference_point) = match self.point_displacement(opcode) {
+                    Some(displacement) => displacement,
+                    None => return Ok(()),
+                };
+
+                // The twilight zone has no contours, so use all of its points.
+                let (start, end) = if zp2 == TWILIGHT_ZONE {
+                    (0, self.state.twilight.current.len())
+                } else {
+                    let ends = &self.glyph_zone.contour_end_indices;
+                    let start = if contour == 0 { 0 } else { ends[contour - 1] as usize + 1 };
+                    (start, ends[contour] as usize + 1)
+                };
+                let end = cmp::min(end, self.zone(zp2).current.len());
+                for point in start..end {
+                    if zone != zp2 || point != reference_point {
+                        self.shift_point(point, dx, dy, true)
+                    }
+                }
+            }
+            0x36...0x37 => {
+                // SHZ
+                let zone_number = try!(self.pop());
+                if zone_number < 0 || zone_number > 1 {
+                    return Ok(())
+                }
+                let (dx, dy, zone, reference_point) = match self.point_displacement(opcode) {
+                    Some(displacement) => displacement,
+                    None => return Ok(()),
+                };
+
+                // Like FreeType, shift zone 2 rather than the zone given, and leave out the
+                // phantom points. The points aren't touched.
+                let zp2 = self.graphics_state.zp2;
+                let end = if zp2 == TWILIGHT_ZONE {
+                    self.state.twilight.current.len()
+                } else {
+                    self.glyph_zone.contour_end_indices.last().map_or(0, |&end| end as usize + 1)
+                };
+                let end = cmp::min(end, self.zone(zp2).current.len());
+                for point in 0..end {
+                    if zone != zp2 || point != reference_point {
+                        self.shift_point(point, dx, dy, false)
+                    }
+                }
+            }
+            0x38 => {
+                // SHPIX
+                let distance = try!(self.pop());
+                if self.stack.len() < self.graphics_state.loop_count as usize {
+                    return Err(Error::Malformed)
+                }
+                let freedom = self.graphics_state.freedom_vector;
+                let (dx, dy) = (mul_2_14(distance, freedom.x), mul_2_14(distance, freedom.y));
+                let zp2 = self.graphics_state.zp2;
+                while self.graphics_state.loop_count > 0 {
+                    let point = try!(self.pop_point());
+                    if self.has_point(zp2, point) {
+                        self.shift_point(point, dx, dy, true)
+                    }
+                    self.graphics_state.loop_count -= 1
+                }
+                self.graphics_state.loop_count = 1
+            }
+            0x39 => try!(self.interpolate_point()),   // IP
+            0x3a...0x3b => {
+                // MSIRP
+                let distance = try!(self.pop());
+                let point = try!(self.pop_point());
+                let state = self.graphics_state;
+                if !self.has_point(state.zp1, point) || !self.has_point(state.zp0, state.rp0) {
+                    return Ok(())
+                }
+
+                // Twilight points start at the reference point's original position.
+                if state.zp1 == TWILIGHT_ZONE {
+                    let reference = self.original(state.zp0, state.rp0);
+                    self.state.twilight.original[point] = reference;
+                    self.move_original_point(TWILIGHT_ZONE, point, distance);
+                    self.state.twilight.current[point] = self.state.twilight.original[point]
+                }
+
+                let current_distance = self.project(self.current(state.zp1, point),
+                                                    self.current(state.zp0, state.rp0));
+                self.move_point(state.zp1, point, distance.wrapping_sub(current_distance));
+                self.graphics_state.rp1 = state.rp0;
+                self.graphics_state.rp2 = point;
+                if (opcode & 1) != 0 {
+                    self.graphics_state.rp0 = point
+                }
+            }
+            0x3c => {
+                // ALIGNRP
+                let state = self.graphics_state;
+                if self.stack.len() < state.loop_count as usize {
+                    return Err(Error::Malformed)
+                }
+                if self.has_point(state.zp0, state.rp0) {
+                    let reference = self.current(state.zp0, state.rp0);
+                    while self.graphics_state.loop_count > 0 {
+                        let point = try!(self.pop_point());
+                        if self.has_point(state.zp1, point) {
+                            let distance = self.project(self.current(state.zp1, point), reference);
+                            self.move_point(state.zp1, point, distance.wrapping_neg())
+                        }
+                        self.graphics_state.loop_count -= 1
+                    }
+                }
+                self.graphics_state.loop_count = 1
+            }
+            0x3d => self.graphics_state.round_state = RoundState::ToDoubleGrid,   // RTDG
+            0x3e...0x3f => {
+                // MIAP
+                let cvt_index = try!(self.pop()) as u32 as usize;
+                let point = try!(self.pop_point());
+                let state = self.graphics_state;
+                if self.has_point(state.zp0, point) && cvt_index < self.state.cvt.len() {
+                    let mut distance = self.state.cvt[cvt_index];
+
+                    // In the twilight zone, the original position is set from the control value
+                    // and the cut-in test is skipped.
+                    if state.zp0 == TWILIGHT_ZONE {
+                        let freedom = state.freedom_vector;
+                        let position = Point2D::new(mul_2_14(distance, freedom.x),
+                                                    mul_2_14(distance, freedom.y));
+                        self.state.twilight.original[point] = position;
+                        self.state.twilight.current[point] = position
+                    }
+
+                    let original_distance = self.fast_project(self.current(state.zp0, point));
+                    if (opcode & 1) != 0 {
+                        if distance.wrapping_sub(original_distance).wrapping_abs() >
+                                state.control_value_cut_in {
+                            distance = original_distance
+                        }
+                        distance = self.round(distance)
+                    }
+                    self.move_point(state.zp0, point, distance.wrapping_sub(original_distance))
+                }
+                self.graphics_state.rp0 = point;
+                self.graphics_state.rp1 = point
+            }
+            0x40 | 0x41 | 0xb0...0xbf => {
+                // NPUSHB, NPUSHW, PUSHB, PUSHW
+                let code = self.programs[self.range as usize];
+                let (words, count, start) = match opcode {
+                    0x40 => (false, code[self.ip + 1] as usize, self.ip + 2),
+                    0x41 => (true, code[self.ip + 1] as usize, self.ip + 2),
+                    0xb0...0xb7 => (false, (opcode - 0xb0) as usize + 1, self.ip + 1),
+                    _ => (true, (opcode - 0xb8) as usize + 1, self.ip + 1),
+                };
+                for index in 0..count {
+                    let value = if words {
+                        let offset = start + index * 2;
+                        (((code[offset] as u16) << 8) | code[offset + 1] as u16) as i16 as i32
+                    } else {
+                        code[start + index] as i32
+                    };
+                    try!(self.push(value))
+                }
+            }
+            0x42 => {
+                // WS
+                let value = try!(self.pop());
+                let index = try!(self.pop()) as u32 as usize;
+                if let Some(slot) = self.state.storage.get_mut(index) {
+                    *slot = value
+                }
+            }
+            0x43 => {
+                // RS
+                let index = try!(self.pop()) as u32 as usize;
+                let value = self.state.storage.get(index).cloned().unwrap_or(0);
+                try!(self.push(value))
+            }
+            0x44 | 0x70 => {
+                // WCVTP, WCVTF
+                let mut value = try!(self.pop());
+                let index = try!(self.pop()) as u32 as usize;
+                if opcode == 0x70 {
+                    value = mul_fix(value, self.state.scale)
+                }
+                if let Some(slot) = self.state.cvt.get_mut(index) {
+                    *slot = value
+                }
+            }
+            0x45 => {
+                // RCVT
+                let index = try!(self.pop()) as u32 as usize;
+                let value = self.state.cvt.get(index).cloned().unwrap_or(0);
+                try!(self.push(value))
+            }
+            0x46...0x47 => {
+                // GC
+                let point = try!(self.pop()) as u32 as usize;
+                let zp2 = self.graphics_state.zp2;
+                let value = if !self.has_point(zp2, point) {
+                    0
+                } else if (opcode & 1) != 0 {
+                    let dual = self.graphics_state.dual_vector;
+                    let original = self.original(zp2, point);
+                    dot_2_14(original.x, original.y, dual.x, dual.y)
+                } else {
+                    self.fast_project(self.current(zp2, point))
+                };
+                try!(self.push(value))
+            }
+            0x48 => {
+                // SCFS
+                let value = try!(self.pop());
+                let point = try!(self.pop_point());
+                let zp2 = self.graphics_state.zp2;
+                if !self.has_point(zp2, point) {
+                    return Ok(())
+                }
+                let current = self.fast_project(self.current(zp2, point));
+                self.move_point(zp2, point, value.wrapping_sub(current));
+                if zp2 == TWILIGHT_ZONE {
+                    self.state.twilight.original[point] = self.state.twilight.current[point]
+                }
+            }
+            0x49...0x4a => {
+                // MD
+                let k = try!(self.pop_point());
+                let l = try!(self.pop_point());
+                let state = self.graphics_state;
+                let distance = if !self.has_point(state.zp0, l) || !self.has_point(state.zp1, k) {
+                    0
+                } else if (opcode & 1) != 0 {
+                    self.project(self.current(state.zp0, l), self.current(state.zp1, k))
+                } else if state.zp0 == TWILIGHT_ZONE || state.zp1 == TWILIGHT_ZONE {
+                    self.dual_project(self.original(state.zp0, l), self.original(state.zp1, k))
+                } else {
+                    let distance = self.dual_project(self.unscaled(state.zp0, l),
+                                                     self.unscaled(state.zp1, k));
+                    mul_fix(distance, self.unscaled_scale)
+                };
+                try!(self.push(distance))
+            }
+            0x4b...0x4c => {
+                // MPPEM, MPS
+                let pixels_per_em = self.state.pixels_per_em as i32;
+                try!(self.push(pixels_per_em))
+            }
+            0x4d => self.graphics_state.auto_flip = true,    // FLIPON
+            0x4e => self.graphics_state.auto_flip = false,   // FLIPOFF
+            0x4f => return Err(Error::Malformed),                          // DEBUG
+            0x50...0x55 => {
+                // LT, LTEQ, GT, GTEQ, EQ, NEQ
+                let b = try!(self.pop());
+                let a = try!(self.pop());
+                let result = match opcode {
+                    0x50 => a < b,
+                    0x51 => a <= b,
+                    0x52 => a > b,
+                    0x53 => a >= b,
+                    0x54 => a == b,
+                    _ => a != b,
+                };
+                try!(self.push(result as i32))
+            }
+            0x56...0x57 => {
+                // ODD, EVEN
+                let value = try!(self.pop());
+                let rounded = self.round(value) & 127;
+                try!(self.push((rounded == if opcode == 0x56 { 64 } else { 0 }) as i32))
+            }
+            0x58 => {
+                // IF
+                if try!(self.pop()) == 0 {
+                    try!(self.skip_conditional(true))
+                }
+            }
+            0x59 => {}   // EIF
+            0x5a...0x5b => {
+                // AND, OR
+                let b = try!(self.pop());
+                let a = try!(self.pop());
+                let result = if opcode == 0x5a {
+                    a != 0 && b != 0
+                } else {
+                    a != 0 || b != 0
+                };
+                try!(self.push(result as i32))
+            }
+            0x5c => {
+                // NOT
+                let value = try!(self.pop());
+                try!(self.push((value == 0) as i32))
+            }
+            0x5d | 0x71 | 0x72 | 0x73...0x75 => try!(self.delta(opcode)),   // DELTAP, DELTAC
+            0x5e => self.graphics_state.delta_base = try!(self.pop()) as u16 as i32,   // SDB
+            0x5f => {
+                // SDS
+                let shift = try!(self.pop());
+                if shift as u32 > 6 {
+                    return Err(Error::Malformed)
+                }
+                self.graphics_state.delta_shift = shift
+            }
+            0x60...0x63 | 0x8b...0x8c => {
+                // ADD, SUB, DIV, MUL, MAX, MIN
+                let b = try!(self.pop());
+                let a = try!(self.pop());
+                let result = match opcode {
+                    0x60 => a.wrapping_add(b),
+                    0x61 => a.wrapping_sub(b),
+                    0x62 => {
+                        if b == 0 {
+                            return Err(Error::Malformed)
+                        }
+                        mul_div_no_round(a, 64, b)
+                    }
+                    0x63 => mul_div(a, b, 64),
+                    0x8b => cmp::max(a, b),
+                    _ => cmp::min(a, b),
+                };
+                try!(self.push(result))
+            }
+            0x64...0x67 => {
+                // ABS, NEG, FLOOR, CEILING
+                let value = try!(self.pop());
+                let result = match opcode {
+                    0x64 => value.wrapping_abs(),
+                    0x65 => value.wrapping_neg(),
+                    0x66 => value & !63,
+                    _ => value.wrapping_add(63) & !63,
+                };
+                try!(self.push(result))
+            }
+            0x68...0x6b => {
+                // ROUND
+                let value = try!(self.pop());
+                let rounded = self.round(value);
+                try!(self.push(rounded))
+            }
+            0x6c...0x6f => {}   // NROUND, which does nothing without engine compensation
+            0x76...0x77 => {
+                // SROUND, S45ROUND
+                let selector = try!(self.pop());
+                if opcode == 0x76 {
+                    self.set_super_round(0x4000, selector);
+                    self.graphics_state.round_state = RoundState::Super
+                } else {
+                    self.set_super_round(0x2d41, selector);
+                    self.graphics_state.round_state = RoundState::Super45
+                }
+            }
+            0x78...0x79 => {
+                // JROT, JROF
+                let condition = try!(self.pop());
+                let offset = try!(self.pop());
+                if (condition != 0) == (opcode == 0x78) {
+                    try!(self.jump(offset))
+                }
+            }
+            0x7a => self.graphics_state.round_state = RoundState::Off,          // ROFF
+            0x7c => self.graphics_state.round_state = RoundState::UpToGrid,     // RUTG
+            0x7d => self.graphics_state.round_state = RoundState::DownToGrid,   // RDTG
+            0x7e...0x7f => drop(try!(self.pop())),                              // SANGW, AA
+            0x80 => {
+                // FLIPPT
+                if self.stack.len() < self.graphics_state.loop_count as usize {
+                    return Err(Error::Malformed)
+                }
+                while self.graphics_state.loop_count > 0 {
+                    let point = try!(self.pop_point());
+                    if let Some(flags) = self.glyph_zone.flags.get_mut(point) {
+                        flags.toggle(ON_CURVE)
+                    }
+                    self.graphics_state.loop_count -= 1
+                }
+                self.graphics_state.loop_count = 1
+            }
+            0x81...0x82 => {
+                // FLIPRGON, FLIPRGOFF
+                let high = try!(self.pop_point());
+                let low = try!(self.pop_point());
+                let point_count = self.glyph_zone.flags.len();
+                if high >= point_count || low >= point_count {
+                    return Ok(())
+                }
+                for flags in &mut self.glyph_zone.flags[low..cmp::max(low, high + 1)] {
+                    if opcode == 0x81 {
+                        flags.insert(ON_CURVE)
+                    } else {
+                        flags.remove(ON_CURVE)
+                    }
+                }
+            }
+            0x85 => {
+                // SCANCTRL. Glyphs are never rotated or stretched here.
+                let value = try!(self.pop());
+                let threshold = value & 0xff;
+                let pixels_per_em = self.state.pixels_per_em as i32;
+                if threshold == 0xff {
+                    self.graphics_state.scan_control = true
+                } else if threshold == 0 {
+                    self.graphics_state.scan_control = false
+                } else {
+                    if (value & 0x100) != 0 && pixels_per_em <= threshold {
+                        self.graphics_state.scan_control = true
+                    }
+                    if (value & 0x800) != 0 && pixels_per_em > threshold {
+                        self.graphics_state.scan_control = false
+                    }
+                }
+            }
+            0x86...0x87 => {
+                // SDPVTL
+                let p1 = try!(self.pop_point());
+                let p2 = try!(self.pop_point());
+                let (zp1, zp2) = (self.graphics_state.zp1, self.graphics_state.zp2);
+                if !self.has_point(zp2, p1) || !self.has_point(zp1, p2) {
+                    return Ok(())
+                }
+                let mut perpendicular = (opcode & 1) != 0;
+                if let Some(vector) = self.line_vector(p1, p2, true, &mut perpendicular) {
+                    self.graphics_state.dual_vector = vector
+                }
+                if let Some(vector) = self.line_vector(p1, p2, false, &mut perpendicular) {
+                    self.graphics_state.projection_vector = vector
+                }
+                self.compute_f_dot_p()
+            }
+            0x88 => {
+                // GETINFO
+                let selector = try!(self.pop());
+                let mut result = 0;
+                if (selector & 1) != 0 {
+                    result = INTERPRETER_VERSION
+                }
+                if (selector & 32) != 0 && self.state.grayscale {
+                    result |= 1 << 12
+                }
+                try!(self.push(result))
+            }
+            0x89 => try!(self.define(true)),   // IDEF
+            0x8a => {
+                // ROLL
+                let a = try!(self.pop());
+                let b = try!(self.pop());
+                let c = try!(self.pop());
+                try!(self.push(b));
+                try!(self.push(a));
+                try!(self.push(c))
+            }
+            0x8d => {
+                // SCANTYPE
+                let value = try!(self.pop());
+                if value >= 0 {
+                    self.graphics_state.scan_type = value & 0xffff
+                }
+            }
+            0x8e => {
+                // INSTCTRL, which only takes effect in the control value program.
+                let selector = try!(self.pop());
+                let value = try!(self.pop());
+                if selector < 1 || selector > 3 {
+                    return Ok(())
+                }
+                let flag = 1 << (selector - 1);
+                if self.initial_range == CodeRange::ControlValue {
+                    let instruct_control = &mut self.graphics_state.instruct_control;
+                    *instruct_control &= !flag;
+                    if value != 0 {
+                        *instruct_control |= flag
+                    }
+                }
+            }
+            0xc0...0xdf => try!(self.move_direct_relative_point(opcode)),     // MDRP
+            0xe0...0xff => try!(self.move_indirect_relative_point(opcode)),   // MIRP
+            _ => {
+                // Instructions defined with `IDEF`.
+                let definition = try!(self.state
+                                          .definitions
+                                          .instructions
+                                          .iter()
+                                          .find(|definition| definition.number == opcode as u32)
+                                          .cloned()
+                                          .ok_or(Error::Malformed));
+                try!(self.call(definition, 1))
+            }
+        }
+        Ok(())
+    }
+
+    fn interpolate_point(&mut self) -> Result<(), Error> {
+        let state = self.graphics_state;
+        if self.stack.len() < state.loop_count as usize {
+            return Err(Error::Malformed)
+        }
+
+        // In the twilight zone, there are no unscaled points to measure from, so use the
+        // original positions.
+        let twilight = state.zp0 == TWILIGHT_ZONE || state.zp1 == TWILIGHT_ZONE ||
+            state.zp2 == TWILIGHT_ZONE;
+        let base_is_valid = self.has_point(state.zp0, state.rp1);
+        let (original_base, current_base) = if base_is_valid {
+            let original_base = if twilight {
+                self.original(state.zp0, state.rp1)
+            } else {
+                self.unscaled(state.zp0, state.rp1)
+            };
+            (original_base, self.current(state.zp0, state.rp1))
+        } else {
+            (Point2D::new(0, 0), Point2D::new(0, 0))
+        };
+
+        let (original_range, current_range) = if !base_is_valid ||
+                !self.has_point(state.zp1, state.rp2) {
+            (0, 0)
+        } else {
+            let original = if twilight {
+                self.original(state.zp1, state.rp2)
+            } else {
+                self.unscaled(state.zp1, state.rp2)
+            };
+            (self.dual_project(original, original_base),
+             self.project(self.current(state.zp1, state.rp2), current_base))
+        };
+
+        while self.graphics_state.loop_count > 0 {
+            let point = try!(self.pop_point());
+            self.graphics_state.loop_count -= 1;
+            if !base_is_valid || !self.has_point(state.zp2, point) {
+                continue
+            }
+
+            let original = if twilight {
+                self.original(state.zp2, point)
+            } else {
+                self.unscaled(state.zp2, point)
+            };
+            let original_distance = self.dual_project(original, original_base);
+            let current_distance = self.project(self.current(state.zp2, point), current_base);
+            let new_distance = if original_distance == 0 {
+                0
+            } else if original_range != 0 {
+                mul_div(original_distance, current_range, original_range)
+            } else {
+                original_distance
+            };
+            self.move_point(state.zp2, point, new_distance.wrapping_sub(current_distance))
+        }
+        self.graphics_state.loop_count = 1;
+        Ok(())
+    }
+
+    fn delta(&mut self, opcode: u8) -> Result<(), Error> {
+        let count = try!(self.pop()) as u32;
+        let (points, range) = match opcode {
+            0x5d => (true, 0),
+            0x71 => (true, 16),
+            0x72 => (true, 32),
+            0x73 => (false, 0),
+            0x74 => (false, 16),
+            _ => (false, 32),
+        };
+        let state = self.graphics_state;
+        for _ in 0..count {
+            if self.stack.len() < 2 {
+                self.stack.clear();
+                break
+            }
+            let target = try!(self.pop()) as u32 as usize;
+            let argument = try!(self.pop()) as u32;
+
+            let pixels_per_em = ((argument & 0xf0) >> 4) as i32 + range + state.delta_base;
+            if pixels_per_em != self.state.pixels_per_em as i32 {
+                continue
+            }
+            let mut steps = (argument & 0xf) as i32 - 8;
+            if steps >= 0 {
+                steps += 1
+            }
+            let distance = steps * (1 << (6 - state.delta_shift));
+
+            if points {
+                if self.has_point(state.zp0, target) {
+                    self.move_point(state.zp0, target, distance)
+                }
+            } else if let Some(value) = self.state.cvt.get_mut(target) {
+                *value = value.wrapping_add(distance)
+            }
+        }
+        Ok(())
+    }
+
+    fn move_direct_relative_point(&mut self, opcode: u8) -> Result<(), Error> {
+        let point = try!(self.pop_point());
+        let state = self.graphics_state;
+        if self.has_point(state.zp1, point) && self.has_point(state.zp0, state.rp0) {
+            let mut original_distance = if state.zp0 == TWILIGHT_ZONE ||
+                    state.zp1 == TWILIGHT_ZONE {
+                self.dual_project(self.original(state.zp1, point),
+                                  self.original(state.zp0, state.rp0))
+            } else {
+                let distance = self.dual_project(self.unscaled(state.zp1, point),
+                                                 self.unscaled(state.zp0, state.rp0));
+                mul_fix(distance, self.unscaled_scale)
+            };
+
+            if state.single_width_cut_in > 0 &&
+                    original_distance < state.single_width_value + state.single_width_cut_in &&
+                    original_distance > state.single_width_value - state.single_width_cut_in {
+                original_distance = if original_distance >= 0 {
+                    state.single_width_value
+                } else {
+                    -state.single_width_value
+                }
+            }
+
+            let mut distance = if (opcode & 4) != 0 {
+                self.round(original_distance)
+            } else {
+                original_distance
+            };
+            if (opcode & 8) != 0 {
+                distance = apply_minimum_distance(distance,
+                                                  original_distance,
+                                                  state.minimum_distance)
+            }
+
+            let current_distance = self.project(self.current(state.zp1, point),
+                                                self.current(state.zp0, state.rp0));
+            self.move_point(state.zp1, point, distance.wrapping_sub(current_distance))
+        }
+
+        self.graphics_state.rp1 = state.rp0;
+        self.graphics_state.rp2 = point;
+        if (opcode & 16) != 0 {
+            self.graphics_state.rp0 = point
+        }
+        Ok(())
+    }
+
+    fn move_indirect_relative_point(&mut self, opcode: u8) -> Result<(), Error> {
+        // Entry -1 of the control value table always reads as zero.
+        let cvt_entry = (try!(self.pop()) as u32).wrapping_add(1) as usize;
+        let point = try!(self.pop_point());
+        let state = self.graphics_state;
+        if self.has_point(state.zp1, point) && cvt_entry <= self.state.cvt.len() &&
+                self.has_point(state.zp0, state.rp0) {
+            let mut cvt_distance = if cvt_entry == 0 {
+                0
+            } else {
+                self.state.cvt[cvt_entry - 1]
+            };
+
+            if cvt_distance.wrapping_sub(state.single_width_value).wrapping_abs() <
+                    state.single_width_cut_in {
+                cvt_distance = if cvt_distance >= 0 {
+                    state.single_width_value
+                } else {
+                    -state.single_width_value
+                }
+            }
+
+            // Twilight points start at the reference point, offset by the control value.
+            if state.zp1 == TWILIGHT_ZONE {
+                let (reference, freedom) = (self.original(state.zp0, state.rp0),
+                                            state.freedom_vector);
+                let position =
+                    Point2D::new(reference.x.wrapping_add(mul_2_14(cvt_distance, freedom.x)),
+                                 reference.y.wrapping_add(mul_2_14(cvt_distance, freedom.y)));
+                self.state.twilight.original[point] = position;
+                self.state.twilight.current[point] = position
+            }
+
+            let original_distance = self.dual_project(self.original(state.zp1, point),
+                                                      self.original(state.zp0, state.rp0));
+            let current_distance = self.project(self.current(state.zp1, point),
+                                                self.current(state.zp0, state.rp0));
+
+            if state.auto_flip && (original_distance ^ cvt_distance) < 0 {
+                cvt_distance = cvt_distance.wrapping_neg()
+            }
+
+            let mut distance = if (opcode & 4) != 0 {
+                // The cut-in test only applies when both points are in the same zone.
+                if state.zp0 == state.zp1 &&
+                        cvt_distance.wrapping_sub(original_distance).wrapping_abs() >
+                        state.control_value_cut_in {
+                    cvt_distance = original_distance
+                }
+                self.round(cvt_distance)
+            } else {
+                cvt_distance
+            };
+            if (opcode & 8) != 0 {
+                distance = apply_minimum_distance(distance,
+                                                  original_distance,
+                                                  state.minimum_distance)
+            }
+
+            self.move_point(state.zp1, point, distance.wrapping_sub(current_distance))
+        }
+
+        self.graphics_state.rp1 = state.rp0;
+        if (opcode & 16) != 0 {
+            self.graphics_state.rp0 = point
+        }
+        self.graphics_state.rp2 = point;
+        Ok(())
+    }
+}
+
+// One coordinate of each point in the glyph zone, for `IUP`.
+struct Axis {
+    unscaled: Vec<i32>,
+    original: Vec<i32>,
+    current: Vec<i32>,
+}
+
+impl Axis {
+    // Shifts the points in `start..=end`, other than `touched`, by as much as `touched` moved.
+    fn shift(&mut self, start: usize, end: usize, touched: usize) {
+        let delta = self.current[touched].wrapping_sub(self.original[touched]);
+        if delta == 0 {
+            return
+        }
+        for point in (start..(end + 1)).filter(|&point| point != touched) {
+            self.current[point] = self.current[point].wrapping_add(delta)
+        }
+    }
+
+    // Interpolates the points in `start..=end` between the touched points `a` and `b`. Points
+    // outside the pair are shifted along with the nearer one.
+    fn interpolate(&mut self, start: usize, end: usize, mut a: usize, mut b: usize) {
+        if start > end || a >= self.current.len() || b >= self.current.len() {
+            return
+        }
+        if self.unscaled[a] > self.unscaled[b] {
+            let (old_a, old_b) = (a, b);
+            a = old_b;
+            b = old_a
+        }
+
+        let (unscaled_a, unscaled_b) = (self.unscaled[a], self.unscaled[b]);
+        let (original_a, original_b) = (self.original[a], self.original[b]);
+        let (current_a, current_b) = (self.current[a], self.current[b]);
+        let (delta_a, delta_b) = (current_a.wrapping_sub(original_a),
+                                  current_b.wrapping_sub(original_b));
+
+        let mut scale = None;
+        for point in start..(end + 1) {
+            let original = self.original[point];
+            self.current[point] = if original <= original_a {
+                original.wrapping_add(delta_a)
+            } else if original >= original_b {
+                original.wrapping_add(delta_b)
+            } else if current_a == current_b || unscaled_a == unscaled_b {
+                current_a
+            } else {
+                let scale = *scale.get_or_insert_with(|| {
+                    div_fix(current_b.wrapping_sub(current_a), unscaled_b.wrapping_sub(unscaled_a))
+                });
+                current_a.wrapping_add(mul_fix(self.unscaled[point].wrapping_sub(unscaled_a),
+                                               scale))
+            }
+        }
+    }
+}
+
+fn apply_minimum_distance(distance: i32, original_distance: i32, minimum_distance: i32) -> i32 {
+    if original_distance >= 0 {
+        cmp::max(distance, minimum_distance)
+    } else {
+        cmp::min(distance, minimum_distance.wrapping_neg())
+    }
+}
+
+fn instruction_length(code: &[u8], ip: usize) -> Result<usize, Error> {
+    let opcode = code[ip];
+    let length = match opcode {
+        0x40 => 2 + *try!(code.get(ip + 1).ok_or(Error::Malformed)) as usize,
+        0x41 => 2 + *try!(code.get(ip + 1).ok_or(Error::Malformed)) as usize * 2,
+        0xb0...0xb7 => 2 + (opcode - 0xb0) as usize,
+        0xb8...0xbf => 3 + (opcode - 0xb8) as usize * 2,
+        _ => 1,
+    };
+    if ip + length > code.len() {
+        return Err(Error::Malformed)
+    }
+    Ok(length)
+}
+
+// Rounds a 26.6 value to the nearest pixel.
+#[inline]
+fn pixel_round(value: i32) -> i32 {
+    value.wrapping_add(32) & !63
+}
+
+// Converts an entry of a component's transform, which is stored in 2.14, to 16.16.
+#[inline]
+fn to_16_16(value: f32) -> i32 {
+    (value * 65536.0) as i32
+}
+
+// The fixed point helpers below round exactly as FreeType's do, so that results match it bit
+// for bit.
+
+// Computes `a * b / 0x10000`, rounding halves away from zero.
+fn mul_fix(a: i32, b: i32) -> i32 {
+    let negative = (a < 0) != (b < 0);
+    let product = ((a as i64).abs() * (b as i64).abs() + 0x8000) >> 16;
+    if negative {
+        -product as i32
+    } else {
+        product as i32
+    }
+}
+
+// Computes `a * 0x10000 / b`, rounding halves away from zero.
+fn div_fix(a: i32, b: i32) -> i32 {
+    let negative = (a < 0) != (b < 0);
+    let (a, b) = ((a as i64).abs(), (b as i64).abs());
+    let quotient = if b == 0 {
+        0x7fffffff
+    } else {
+        ((a << 16) + (b >> 1)) / b
+    };
+    if negative {
+        -quotient as i32
+    } else {
+        quotient as i32
+    }
+}
+
+// Computes `a * b / c`, rounding halves away from zero.
+fn mul_div(a: i32, b: i32, c: i32) -> i32 {
+    let negative = (a < 0) ^ (b < 0) ^ (c < 0);
+    let (a, b, c) = ((a as i64).abs(), (b as i64).abs(), (c as i64).abs());
+    let result = if c == 0 {
+        0x7fffffff
+    } else {
+        (a * b + (c >> 1)) / c
+    };
+    if negative {
+        -result as i32
+    } else {
+        result as i32
+    }
+}
+
+// Computes `a * b / c`, truncating.
+fn mul_div_no_round(a: i32, b: i32, c: i32) -> i32 {
+    let negative = (a < 0) ^ (b < 0) ^ (c < 0);
+    let (a, b, c) = ((a as i64).abs(), (b as i64).abs(), (c as i64).abs());
+    let result = if c == 0 {
+        0x7fffffff
+    } else {
+        a * b / c
+    };
+    if negative {
+        -result as i32
+    } else {
+        result as i32
+    }
+}
+
+// Multiplies a value by a 2.14 number, rounding halves up.
+#[inline]
+fn mul_2_14(a: i32, b: i32) -> i32 {
+    let product = a as i64 * b as i64;
+    ((product + 0x2000 + (product >> 63)) >> 14) as i32
+}
+
+// Projects a vector onto a 2.14 unit vector.
+#[inline]
+fn dot_2_14(ax: i32, ay: i32, bx: i32, by: i32) -> i32 {
+    let product = ax as i64 * bx as i64 + ay as i64 * by as i64;
+    ((product + 0x2000 + (product >> 63)) >> 14) as i32
+}
+
+// Scales a vector to unit length in 2.14, using FreeType's integer Newton iteration. Returns
+// `None` for the zero vector.
+fn normalize(x: i32, y: i32) -> Option<Point2D<i32>> {
+    if x == 0 && y == 0 {
+        return None
+    }
+    let (mut ux, mut uy) = ((x as i64).abs() as u32, (y as i64).abs() as u32);
+    if ux == 0 {
+        return Some(Point2D::new(0, if y < 0 { -ONE_2_14 } else { ONE_2_14 }))
+    }
+    if uy == 0 {
+        return Some(Point2D::new(if x < 0 { -ONE_2_14 } else { ONE_2_14 }, 0))
+    }
+
+    // Estimate the length, and shift so that it's between 2/3 and 4/3 in 16.16.
+    let estimate = |x: u32, y: u32| {
+        if x > y {
+            x.wrapping_add(y >> 1)
+        } else {
+            y.wrapping_add(x >> 1)
+        }
+    };
+    let mut length = estimate(ux, uy);
+    let mut shift = length.leading_zeros() as i32;
+    shift -= 15 + (length >= (0xaaaaaaaa >> shift)) as i32;
+    if shift > 0 {
+        ux <<= shift;
+        uy <<= shift;
+        length = estimate(ux, uy)
+    } else {
+        ux >>= -shift;
+        uy >>= -shift;
+        length >>= -shift
+    }
+
+    // Refine the reciprocal of the length, less one.
+    let mut b = 0x10000i32.wrapping_sub(length as i32);
+    let (xi, yi) = (ux as i32, uy as i32);
+    let (mut u, mut v);
+    loop {
+        u = xi.wrapping_add(xi.wrapping_mul(b) >> 16) as u32;
+        v = yi.wrapping_add(yi.wrapping_mul(b) >> 16) as u32;
+        let mut z = (u.wrapping_mul(u).wrapping_add(v.wrapping_mul(v)) as i32).wrapping_neg() /
+            0x200;
+        z = z.wrapping_mul((0x10000 + b) >> 8) / 0x10000;
+        b += z;
+        if z <= 0 {
+            break
+        }
+    }
+
+    let (u, v) = (u as i32, v as i32);
+    Some(Point2D::new(if x < 0 { -u } else { u } / 4, if y < 0 { -v } else { v } / 4))
+}
@@ -8,30 +8,145 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+//! Without the default `std` feature, only the sfnt parsers in `otf` and `charmap` are built, and
+//! they need nothing beyond `core` and `alloc`. Their points and rectangles are then minimal
+//! stand-ins for the `euclid` types, with the same fields. Either way they are reachable as
+//! `pathfinder::euclid`, so callers can name them without caring which one they got. The `gpu`
+//! feature, also on by default, adds the `compute-shader` rasterizer and the buffer uploads that
+//! feed it.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(test, feature(test))]
 
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
 #[macro_use]
 extern crate bitflags;
+#[cfg(all(test, feature = "std"))]
+extern crate brotli;
+#[cfg(feature = "std")]
+extern crate brotli_decompressor;
 extern crate byteorder;
+#[cfg(feature = "gpu")]
 extern crate compute_shader;
-extern crate euclid;
-#[cfg(test)]
+#[cfg(feature = "std")]
+pub extern crate euclid;
+#[cfg(feature = "std")]
+extern crate flate2;
+#[cfg(feature = "std")]
 extern crate memmap;
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 #[macro_use]
 extern crate quickcheck;
 #[cfg(test)]
 extern crate test;
 
+#[cfg(feature = "std")]
 pub mod atlas;
+#[cfg(feature = "std")]
+pub mod autohint;
+#[cfg(feature = "gpu")]
 pub mod batch;
+#[macro_use]
+pub mod binary;
+#[cfg(feature = "std")]
+pub mod bitmap;
 pub mod charmap;
+#[cfg(feature = "gpu")]
 pub mod coverage;
+pub mod error;
+#[cfg(feature = "std")]
 pub mod glyph_buffer;
+#[cfg(feature = "std")]
+pub mod hinting;
+pub mod limits;
 pub mod otf;
+pub mod outline;
+#[cfg(feature = "std")]
+pub mod paint;
+#[cfg(feature = "std")]
+pub mod png;
+#[cfg(feature = "gpu")]
 pub mod rasterizer;
+#[cfg(feature = "std")]
+pub mod rendering;
+#[cfg(feature = "std")]
+pub mod software_rasterizer;
+#[cfg(feature = "std")]
+pub mod svg;
+#[cfg(feature = "std")]
+pub mod type1;
 mod util;
 
 #[cfg(test)]
 mod tests;
 
+// Lets `use std::...` paths resolve to `core` and `alloc` when the standard library isn't there.
+#[cfg(not(feature = "std"))]
+mod std {
+    pub use core::*;
+    pub use alloc::{collections, vec};
+}
+
+/// Stand-ins for the `euclid` geometry types used by the sfnt parsers.
+///
+/// `euclid` needs the standard library, so without the `std` feature the parsers get their
+/// points, sizes, and rectangles from here instead. The field names match `euclid`, so code that
+/// only reads fields works against either.
+#[cfg(not(feature = "std"))]
+pub mod euclid {
+    /// A 2D point, like `euclid::Point2D`.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct Point2D<T> {
+        pub x: T,
+        pub y: T,
+    }
+
+    impl<T> Point2D<T> {
+        #[inline]
+        /// Creates a point from its coordinates.
+        pub fn new(x: T, y: T) -> Point2D<T> {
+            Point2D {
+                x: x,
+                y: y,
+            }
+        }
+    }
+
+    /// A 2D size, like `euclid::Size2D`.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct Size2D<T> {
+        pub width: T,
+        pub height: T,
+    }
+
+    impl<T> Size2D<T> {
+        #[inline]
+        /// Creates a size from its width and height.
+        pub fn new(width: T, height: T) -> Size2D<T> {
+            Size2D {
+                width: width,
+                height: height,
+            }
+        }
+    }
+
+    /// An axis-aligned rectangle, like `euclid::Rect`.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct Rect<T> {
+        pub origin: Point2D<T>,
+        pub size: Size2D<T>,
+    }
+
+    impl<T> Rect<T> {
+        #[inline]
+        /// Creates a rectangle from its origin and size.
+        pub fn new(origin: Point2D<T>, size: Size2D<T>) -> Rect<T> {
+            Rect {
+                origin: origin,
+                size: size,
+            }
+        }
+    }
+}
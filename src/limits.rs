@@ -0,0 +1,102 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Limits on the work and memory that a single glyph or batch may take.
+//!
+//! Fonts uploaded by users can describe glyphs that are cheap to store but expensive to
+//! process: 65535 points, composites nested hundreds of levels deep, or outlines that cover
+//! the whole atlas at a large size. `GlyfTable`, `GlyphBufferBuilder`, and `BatchBuilder` each
+//! carry a `Limits` and fail with `Error::LimitExceeded` instead of doing that work.
+
+use error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// One of the resources that `Limits` bounds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    /// Points in one glyph, counting the points of all components of a composite glyph.
+    PointsPerGlyph,
+    /// Contours in one glyph, counting the contours of all components of a composite glyph.
+    ContoursPerGlyph,
+    /// Levels of composite glyphs nested inside one another.
+    CompositeDepth,
+    /// Components in one composite glyph, counting nested components.
+    ComponentsPerGlyph,
+    /// Points in all the glyphs of a batch.
+    BatchPoints,
+    /// The width or height of one image in an atlas, in pixels.
+    ImageSize,
+}
+
+impl Display for Limit {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str(match *self {
+            Limit::PointsPerGlyph => "points per glyph",
+            Limit::ContoursPerGlyph => "contours per glyph",
+            Limit::CompositeDepth => "composite glyph depth",
+            Limit::ComponentsPerGlyph => "components per glyph",
+            Limit::BatchPoints => "points per batch",
+            Limit::ImageSize => "image size",
+        })
+    }
+}
+
+/// The largest amount of each resource that is allowed.
+///
+/// The defaults are comfortably above what real fonts need.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub max_points_per_glyph: u32,
+    pub max_contours_per_glyph: u32,
+    pub max_composite_depth: u32,
+    pub max_components_per_glyph: u32,
+    pub max_batch_points: u32,
+    /// The largest width or height of an image, in pixels.
+    pub max_image_size: u32,
+}
+
+impl Default for Limits {
+    #[inline]
+    fn default() -> Limits {
+        Limits {
+            max_points_per_glyph: 16384,
+            max_contours_per_glyph: 4096,
+            max_composite_depth: 8,
+            max_components_per_glyph: 1024,
+            max_batch_points: 1 << 24,
+            max_image_size: 4096,
+        }
+    }
+}
+
+impl Limits {
+    /// Returns the maximum allowed for the given resource.
+    #[inline]
+    pub fn max(&self, limit: Limit) -> u32 {
+        match limit {
+            Limit::PointsPerGlyph => self.max_points_per_glyph,
+            Limit::ContoursPerGlyph => self.max_contours_per_glyph,
+            Limit::CompositeDepth => self.max_composite_depth,
+            Limit::ComponentsPerGlyph => self.max_components_per_glyph,
+            Limit::BatchPoints => self.max_batch_points,
+            Limit::ImageSize => self.max_image_size,
+        }
+    }
+
+    /// Fails with `Error::LimitExceeded` if `value` is more than the resource allows.
+    #[inline]
+    pub fn check(&self, limit: Limit, value: u32) -> Result<(), Error> {
+        if value > self.max(limit) {
+            Err(Error::LimitExceeded(limit))
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `CBDT` table, which holds color bitmap glyphs as PNG images.
+
+use byteorder::BigEndian;
+use error::Error;
+use otf::{CBDT, FontTable};
+use otf::cblc::{BitmapGlyphMetrics, BitmapLocation};
+use util::{Jump, ReadBytes};
+
+/// PNG data with small metrics.
+const IMAGE_FORMAT_SMALL_METRICS_PNG: u16 = 17;
+/// PNG data with big metrics.
+const IMAGE_FORMAT_BIG_METRICS_PNG: u16 = 18;
+/// PNG data with metrics in `CBLC`.
+const IMAGE_FORMAT_PNG: u16 = 19;
+
+/// The PNG image of one glyph and its metrics.
+#[derive(Clone, Copy, Debug)]
+pub struct CbdtGlyph<'a> {
+    pub metrics: BitmapGlyphMetrics,
+    pub png: &'a [u8],
+}
+
+#[derive(Clone, Copy)]
+pub struct CbdtTable<'a> {
+    pub table: FontTable<'a>,
+}
+
+impl<'a> CbdtTable<'a> {
+    pub fn new(table: FontTable) -> Result<CbdtTable, Error> {
+        let mut reader = table.bytes;
+        let major_version = try!(reader.read_u16::<BigEndian>());
+        let minor_version = try!(reader.read_u16::<BigEndian>());
+        if (major_version, minor_version) != (3, 0) {
+            return Err(Error::UnsupportedFormat {
+                table: CBDT,
+                format: ((major_version as u32) << 16) | minor_version as u32,
+            })
+        }
+
+        Ok(CbdtTable {
+            table: table,
+        })
+    }
+
+    /// Reads the glyph at a location found in the `CBLC` table.
+    pub fn glyph(&self, location: &BitmapLocation) -> Result<CbdtGlyph<'a>, Error> {
+        let start = location.offset as usize;
+        let end = try!(start.checked_add(location.length as usize).ok_or(Error::Malformed));
+        if end > self.table.bytes.len() {
+            return Err(Error::Malformed)
+        }
+        let mut reader = &self.table.bytes[start..end];
+
+        let metrics = match location.image_format {
+            IMAGE_FORMAT_SMALL_METRICS_PNG => try!(BitmapGlyphMetrics::read_small(&mut reader)),
+            IMAGE_FORMAT_BIG_METRICS_PNG => try!(BitmapGlyphMetrics::read_big(&mut reader)),
+            IMAGE_FORMAT_PNG => try!(location.metrics.ok_or(Error::Malformed)),
+            _ => return Err(Error::UnsupportedFormat {
+                    table: CBDT,
+                    format: location.image_format as u32,
+                }),
+        };
+
+        let data_length = try!(reader.read_u32::<BigEndian>()) as usize;
+        let png = reader;
+        try!(reader.jump(data_length));
+        Ok(CbdtGlyph {
+            metrics: metrics,
+            png: &png[0..data_length],
+        })
+    }
+}
@@ -0,0 +1,262 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `CBLC` table, which locates the color bitmaps in `CBDT` for each strike (pixel size).
+//!
+//! `CBLC` has the same layout as the older `EBLC` table, differing only in its version number.
+
+use byteorder::BigEndian;
+use error::Error;
+use otf::{CBLC, FontTable};
+use std::mem;
+use std::vec::Vec;
+use util::{Jump, ReadBytes};
+
+const BITMAP_SIZE_RECORD_SIZE: usize = 48;
+const BIG_GLYPH_METRICS_SIZE: usize = 8;
+
+/// The metrics of one bitmap glyph, in pixels. Only horizontal metrics are kept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitmapGlyphMetrics {
+    pub width: u8,
+    pub height: u8,
+    /// The distance from the glyph origin to the left edge of the bitmap.
+    pub bearing_x: i8,
+    /// The distance from the glyph origin up to the top edge of the bitmap.
+    pub bearing_y: i8,
+    pub advance: u8,
+}
+
+impl BitmapGlyphMetrics {
+    /// Reads a `SmallGlyphMetrics` record.
+    pub fn read_small(reader: &mut &[u8]) -> Result<BitmapGlyphMetrics, Error> {
+        let height = try!(reader.read_u8());
+        let width = try!(reader.read_u8());
+        let bearing_x = try!(reader.read_i8());
+        let bearing_y = try!(reader.read_i8());
+        let advance = try!(reader.read_u8());
+        Ok(BitmapGlyphMetrics {
+            width: width,
+            height: height,
+            bearing_x: bearing_x,
+            bearing_y: bearing_y,
+            advance: advance,
+        })
+    }
+
+    /// Reads a `BigGlyphMetrics` record, skipping the vertical metrics.
+    pub fn read_big(reader: &mut &[u8]) -> Result<BitmapGlyphMetrics, Error> {
+        let metrics = try!(BitmapGlyphMetrics::read_small(reader));
+        try!(reader.jump(BIG_GLYPH_METRICS_SIZE - 5));
+        Ok(metrics)
+    }
+}
+
+/// A set of bitmaps for one pixel size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitmapStrike {
+    pub ppem_x: u8,
+    pub ppem_y: u8,
+    pub bit_depth: u8,
+    pub start_glyph_id: u16,
+    pub end_glyph_id: u16,
+    index_subtable_array_offset: u32,
+    number_of_index_subtables: u32,
+}
+
+/// Where the bitmap of one glyph lives in the bitmap data table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitmapLocation {
+    pub image_format: u16,
+    /// The offset of the bitmap from the start of the data table.
+    pub offset: u32,
+    pub length: u32,
+    /// The glyph metrics, for image formats that store them here instead of with the bitmap.
+    pub metrics: Option<BitmapGlyphMetrics>,
+}
+
+#[derive(Clone, Copy)]
+pub struct CblcTable<'a> {
+    pub table: FontTable<'a>,
+    pub major_version: u16,
+    num_sizes: u32,
+}
+
+impl<'a> CblcTable<'a> {
+    /// Reads a `CBLC` table, or an `EBLC` table, which has version 2.
+    pub fn new(table: FontTable) -> Result<CblcTable, Error> {
+        let mut reader = table.bytes;
+        let major_version = try!(reader.read_u16::<BigEndian>());
+        let minor_version = try!(reader.read_u16::<BigEndian>());
+        if (major_version != 2 && major_version != 3) || minor_version != 0 {
+            return Err(Error::UnsupportedFormat {
+                table: CBLC,
+                format: ((major_version as u32) << 16) | minor_version as u32,
+            })
+        }
+
+        let num_sizes = try!(reader.read_u32::<BigEndian>());
+        Ok(CblcTable {
+            table: table,
+            major_version: major_version,
+            num_sizes: num_sizes,
+        })
+    }
+
+    pub fn strikes(&self) -> Result<Vec<BitmapStrike>, Error> {
+        let mut reader = self.table.bytes;
+        try!(reader.jump(mem::size_of::<u16>() * 2 + mem::size_of::<u32>()));
+
+        let mut strikes = vec![];
+        for _ in 0..self.num_sizes {
+            let mut record_reader = reader;
+            try!(reader.jump(BITMAP_SIZE_RECORD_SIZE));
+
+            let index_subtable_array_offset =
+                try!(record_reader.read_u32::<BigEndian>());
+            try!(record_reader.jump(mem::size_of::<u32>()));
+            let number_of_index_subtables =
+                try!(record_reader.read_u32::<BigEndian>());
+
+            // Skip the color reference and the horizontal and vertical line metrics.
+            try!(record_reader.jump(mem::size_of::<u32>() + 12 * 2));
+
+            let start_glyph_id = try!(record_reader.read_u16::<BigEndian>());
+            let end_glyph_id = try!(record_reader.read_u16::<BigEndian>());
+            let ppem_x = try!(record_reader.read_u8());
+            let ppem_y = try!(record_reader.read_u8());
+            let bit_depth = try!(record_reader.read_u8());
+            strikes.push(BitmapStrike {
+                ppem_x: ppem_x,
+                ppem_y: ppem_y,
+                bit_depth: bit_depth,
+                start_glyph_id: start_glyph_id,
+                end_glyph_id: end_glyph_id,
+                index_subtable_array_offset: index_subtable_array_offset,
+                number_of_index_subtables: number_of_index_subtables,
+            })
+        }
+        Ok(strikes)
+    }
+
+    /// Returns the location of the bitmap of the given glyph in the given strike, or `None` if the
+    /// strike has no bitmap for that glyph.
+    pub fn glyph_location(&self, strike: &BitmapStrike, glyph_id: u16)
+                          -> Result<Option<BitmapLocation>, Error> {
+        if glyph_id < strike.start_glyph_id || glyph_id > strike.end_glyph_id {
+            return Ok(None)
+        }
+
+        let array_offset = strike.index_subtable_array_offset as usize;
+        let mut reader = self.table.bytes;
+        try!(reader.jump(array_offset));
+        for _ in 0..strike.number_of_index_subtables {
+            let first_glyph_id = try!(reader.read_u16::<BigEndian>());
+            let last_glyph_id = try!(reader.read_u16::<BigEndian>());
+            let additional_offset = try!(reader.read_u32::<BigEndian>());
+            if glyph_id < first_glyph_id || glyph_id > last_glyph_id {
+                continue
+            }
+
+            let mut subtable_reader = self.table.bytes;
+            try!(subtable_reader.jump(array_offset + additional_offset as usize));
+            return read_index_subtable(subtable_reader, glyph_id - first_glyph_id, glyph_id)
+        }
+
+        Ok(None)
+    }
+}
+
+// Looks up a glyph in an index subtable. `index` is the glyph's position in the subtable's range.
+fn read_index_subtable(mut reader: &[u8], index: u16, glyph_id: u16)
+                       -> Result<Option<BitmapLocation>, Error> {
+    let index_format = try!(reader.read_u16::<BigEndian>());
+    let image_format = try!(reader.read_u16::<BigEndian>());
+    let image_data_offset = try!(reader.read_u32::<BigEndian>());
+
+    let (offset, length, metrics) = match index_format {
+        1 | 3 => {
+            // Offsets for every glyph in the range, plus one to find the length of the last.
+            let (start, end) = if index_format == 1 {
+                try!(reader.jump(index as usize * mem::size_of::<u32>()));
+                (try!(reader.read_u32::<BigEndian>()),
+                 try!(reader.read_u32::<BigEndian>()))
+            } else {
+                try!(reader.jump(index as usize * mem::size_of::<u16>()));
+                (try!(reader.read_u16::<BigEndian>()) as u32,
+                 try!(reader.read_u16::<BigEndian>()) as u32)
+            };
+            if end < start {
+                return Err(Error::Malformed)
+            }
+            (start, end - start, None)
+        }
+        2 => {
+            // Every glyph in the range has the same size and metrics.
+            let image_size = try!(reader.read_u32::<BigEndian>());
+            let metrics = try!(BitmapGlyphMetrics::read_big(&mut reader));
+            (image_size * index as u32, image_size, Some(metrics))
+        }
+        4 => {
+            // A sparse list of glyph IDs and offsets, with a final entry for the end.
+            let num_glyphs = try!(reader.read_u32::<BigEndian>());
+            let mut found = None;
+            for _ in 0..num_glyphs {
+                let current_glyph_id = try!(reader.read_u16::<BigEndian>());
+                let offset = try!(reader.read_u16::<BigEndian>()) as u32;
+                if current_glyph_id == glyph_id {
+                    try!(reader.jump(mem::size_of::<u16>()));
+                    let end = try!(reader.read_u16::<BigEndian>()) as u32;
+                    if end < offset {
+                        return Err(Error::Malformed)
+                    }
+                    found = Some((offset, end - offset, None));
+                    break
+                }
+            }
+            match found {
+                Some(found) => found,
+                None => return Ok(None),
+            }
+        }
+        5 => {
+            // A sparse list of glyph IDs, all with the same size and metrics.
+            let image_size = try!(reader.read_u32::<BigEndian>());
+            let metrics = try!(BitmapGlyphMetrics::read_big(&mut reader));
+            let num_glyphs = try!(reader.read_u32::<BigEndian>());
+            let mut found = None;
+            for position in 0..num_glyphs {
+                if try!(reader.read_u16::<BigEndian>()) == glyph_id {
+                    found = Some(position);
+                    break
+                }
+            }
+            match found {
+                Some(position) => (image_size * position, image_size, Some(metrics)),
+                None => return Ok(None),
+            }
+        }
+        _ => return Err(Error::UnsupportedFormat {
+            table: CBLC,
+            format: index_format as u32,
+        }),
+    };
+
+    if length == 0 {
+        return Ok(None)
+    }
+
+    Ok(Some(BitmapLocation {
+        image_format: image_format,
+        offset: try!(image_data_offset.checked_add(offset).ok_or(Error::Malformed)),
+        length: length,
+        metrics: metrics,
+    }))
+}
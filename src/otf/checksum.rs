@@ -0,0 +1,166 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Verification of table checksums and of `checkSumAdjustment` in `head`.
+//!
+//! Nothing checks checksums when loading a font, since doing so reads every byte of it. Services
+//! that accept fonts from untrusted sources can call `FontData::verify_checksums` to flag
+//! corrupted files up front.
+
+use byteorder::{BigEndian, ByteOrder};
+use error::Error;
+use otf::woff;
+use otf::{self, FontData, Tag};
+use std::mem;
+
+/// The offset of `checkSumAdjustment` within `head`.
+pub const HEAD_CHECKSUM_ADJUSTMENT_OFFSET: usize = 8;
+/// The value that the checksum of a whole font, including its `checkSumAdjustment`, sums to.
+pub const CHECKSUM_MAGIC: u32 = 0xb1b0afba;
+
+const HEAD: Tag = Tag(otf::HEAD);
+
+/// A checksum stored in the font that doesn't match the data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    pub tag: Tag,
+    /// The checksum the font claims.
+    pub stored: u32,
+    /// The checksum of the data actually present.
+    pub computed: u32,
+}
+
+/// The results of `FontData::verify_checksums`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChecksumReport {
+    /// The tables whose checksums in the table directory are wrong, in directory order.
+    pub mismatched_tables: Vec<ChecksumMismatch>,
+    /// The `checkSumAdjustment` in `head`, if it's wrong. It covers the whole file, so any
+    /// corruption, including in the table directory, shows up here. It's not checked for faces of
+    /// collections, where it has no defined meaning, or for fonts without `head`.
+    pub checksum_adjustment: Option<ChecksumMismatch>,
+}
+
+impl ChecksumReport {
+    /// Returns true if every checksum matched.
+    #[inline]
+    pub fn is_ok(&self) -> bool {
+        self.mismatched_tables.is_empty() && self.checksum_adjustment.is_none()
+    }
+}
+
+impl<'a> FontData<'a> {
+    /// Checks the checksum of every table in the directory, and the whole-font
+    /// `checkSumAdjustment` in `head`.
+    ///
+    /// Mismatches are reported, not returned as errors. Errors mean the table directory can't be
+    /// read, or a table extends past the end of the data.
+    pub fn verify_checksums(&self) -> Result<ChecksumReport, Error> {
+        let mut report = ChecksumReport {
+            mismatched_tables: vec![],
+            checksum_adjustment: None,
+        };
+
+        let mut head = None;
+        for record in try!(self.table_records()) {
+            let table = try!(self.table_for_record(&record));
+            let mut computed = woff::calculate_checksum(table.bytes);
+            if record.tag == HEAD {
+                // `head`'s checksum is computed as though `checkSumAdjustment` were zero.
+                let adjustment = try!(read_checksum_adjustment(table.bytes));
+                computed = computed.wrapping_sub(adjustment);
+                head = Some((record.offset as usize, adjustment));
+            }
+            if computed != record.checksum {
+                report.mismatched_tables.push(ChecksumMismatch {
+                    tag: record.tag,
+                    stored: record.checksum,
+                    computed: computed,
+                })
+            }
+        }
+
+        if let (0, Some((head_offset, adjustment))) = (self.header_offset, head) {
+            let position = head_offset + HEAD_CHECKSUM_ADJUSTMENT_OFFSET;
+            let font_checksum = woff::calculate_checksum(self.bytes)
+                .wrapping_sub(word_contribution(position, adjustment));
+            let computed = CHECKSUM_MAGIC.wrapping_sub(font_checksum);
+            if computed != adjustment {
+                report.checksum_adjustment = Some(ChecksumMismatch {
+                    tag: HEAD,
+                    stored: adjustment,
+                    computed: computed,
+                })
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// Recomputes the checksum of `head` and its `checkSumAdjustment` in a single-face sfnt whose
+/// other tables and directory are final. A font without a `head` table, or with one too short to
+/// hold `checkSumAdjustment`, is left as it is.
+pub fn fix_checksum_adjustment(sfnt: &mut [u8]) -> Result<(), Error> {
+    let (record_index, head) = {
+        let records = try!(FontData::new(sfnt).table_records());
+        match records.enumerate().find(|&(_, record)| record.tag == HEAD) {
+            Some(found) => found,
+            None => return Ok(()),
+        }
+    };
+
+    let (head_offset, head_length) = (head.offset as usize, head.length as usize);
+    match head_offset.checked_add(head_length) {
+        Some(end) if end <= sfnt.len() => {}
+        _ => {
+            return Err(Error::Truncated {
+                table: otf::HEAD,
+                table_length: sfnt.len().saturating_sub(head_offset),
+            })
+        }
+    }
+    if head_length < HEAD_CHECKSUM_ADJUSTMENT_OFFSET + mem::size_of::<u32>() {
+        return Ok(())
+    }
+
+    let record = 12 + record_index * 16;
+    let adjustment_offset = head_offset + HEAD_CHECKSUM_ADJUSTMENT_OFFSET;
+    BigEndian::write_u32(&mut sfnt[adjustment_offset..], 0);
+    let head_checksum = woff::calculate_checksum(&sfnt[head_offset..(head_offset + head_length)]);
+    BigEndian::write_u32(&mut sfnt[(record + 4)..], head_checksum);
+
+    let font_checksum = woff::calculate_checksum(sfnt);
+    BigEndian::write_u32(&mut sfnt[adjustment_offset..],
+                         CHECKSUM_MAGIC.wrapping_sub(font_checksum));
+    Ok(())
+}
+
+fn read_checksum_adjustment(head: &[u8]) -> Result<u32, Error> {
+    let end = HEAD_CHECKSUM_ADJUSTMENT_OFFSET + 4;
+    if head.len() < end {
+        return Err(Error::Truncated {
+            table: HEAD.0,
+            table_length: head.len(),
+        })
+    }
+    Ok(BigEndian::read_u32(&head[HEAD_CHECKSUM_ADJUSTMENT_OFFSET..end]))
+}
+
+// Returns what the big-endian word `value`, stored at byte `position`, adds to the checksum of the
+// data around it. Tables are supposed to be 4-byte aligned, but nothing guarantees it.
+fn word_contribution(position: usize, value: u32) -> u32 {
+    let mut bytes = [0; 4];
+    BigEndian::write_u32(&mut bytes, value);
+    bytes.iter().enumerate().fold(0u32, |sum, (index, &byte)| {
+        let shift = (3 - (position + index) % 4) * 8;
+        sum.wrapping_add((byte as u32) << shift)
+    })
+}
@@ -8,14 +8,15 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use batch::GlyphRange;
-use byteorder::{BigEndian, ReadBytesExt};
-use charmap::CodepointRange;
-use otf::FontTable;
+use byteorder::BigEndian;
+use charmap::{CodepointRange, GlyphRange};
+use error::Error;
+use otf::{CMAP, FontTable};
 use std::cmp;
 use std::mem;
 use std::u16;
-use util::Jump;
+use std::vec::Vec;
+use util::{Jump, ReadBytes};
 
 const PLATFORM_ID_UNICODE: u16 = 0;
 const PLATFORM_ID_MICROSOFT: u16 = 3;
@@ -24,6 +25,10 @@ const MICROSOFT_ENCODING_ID_UNICODE_BMP: u16 = 1;
 const MICROSOFT_ENCODING_ID_UNICODE_UCS4: u16 = 10;
 
 const FORMAT_SEGMENT_MAPPING_TO_DELTA_VALUES: u16 = 4;
+const FORMAT_SEGMENTED_COVERAGE: u16 = 12;
+
+const ENCODING_RECORD_SIZE: usize = 8;
+const SEQUENTIAL_MAP_GROUP_SIZE: usize = 12;
 
 const MISSING_GLYPH: u16 = 0;
 
@@ -40,46 +45,58 @@ impl<'a> CmapTable<'a> {
     }
 
     pub fn glyph_ranges_for_codepoint_ranges(&self, codepoint_ranges: &[CodepointRange])
-                                             -> Result<Vec<GlyphRange>, ()> {
+                                             -> Result<Vec<GlyphRange>, Error> {
         let mut cmap_reader = self.table.bytes;
 
         // Check version.
-        if try!(cmap_reader.read_u16::<BigEndian>().map_err(drop)) != 0 {
-            return Err(())
+        let version = try!(cmap_reader.read_u16::<BigEndian>());
+        if version != 0 {
+            return Err(Error::UnsupportedFormat {
+                table: CMAP,
+                format: version as u32,
+            })
         }
 
-        let num_tables = try!(cmap_reader.read_u16::<BigEndian>().map_err(drop));
+        let num_tables = try!(cmap_reader.read_u16::<BigEndian>());
 
         // Check platform ID and encoding.
         // TODO(pcwalton): Handle more.
         // TODO(pcwalton): Search for one that we can handle.
-        let platform_id = try!(cmap_reader.read_u16::<BigEndian>().map_err(drop));
-        let encoding_id = try!(cmap_reader.read_u16::<BigEndian>().map_err(drop));
+        let platform_id = try!(cmap_reader.read_u16::<BigEndian>());
+        let encoding_id = try!(cmap_reader.read_u16::<BigEndian>());
         match (platform_id, encoding_id) {
             (PLATFORM_ID_UNICODE, _) |
             (PLATFORM_ID_MICROSOFT, MICROSOFT_ENCODING_ID_UNICODE_BMP) |
             (PLATFORM_ID_MICROSOFT, MICROSOFT_ENCODING_ID_UNICODE_UCS4) => {}
-            _ => return Err(())
+            _ => {
+                return Err(Error::UnsupportedFormat {
+                    table: CMAP,
+                    format: ((platform_id as u32) << 16) | encoding_id as u32,
+                })
+            }
         }
 
         // Move to the mapping table.
-        let offset = try!(cmap_reader.read_u32::<BigEndian>().map_err(drop));
+        let offset = try!(cmap_reader.read_u32::<BigEndian>());
         cmap_reader = self.table.bytes;
         try!(cmap_reader.jump(offset as usize));
 
         // Check the mapping table format.
-        let format = try!(cmap_reader.read_u16::<BigEndian>().map_err(drop));
+        let format = try!(cmap_reader.read_u16::<BigEndian>());
         if format != FORMAT_SEGMENT_MAPPING_TO_DELTA_VALUES {
-            return Err(())
+            return Err(Error::UnsupportedFormat {
+                table: CMAP,
+                format: format as u32,
+            })
         }
 
         // Read the mapping table header.
-        let length = try!(cmap_reader.read_u16::<BigEndian>().map_err(drop));
-        let language = try!(cmap_reader.read_u16::<BigEndian>().map_err(drop));
-        let seg_count = try!(cmap_reader.read_u16::<BigEndian>().map_err(drop)) / 2;
-        let search_range = try!(cmap_reader.read_u16::<BigEndian>().map_err(drop));
-        let entry_selector = try!(cmap_reader.read_u16::<BigEndian>().map_err(drop));
-        let range_shift = try!(cmap_reader.read_u16::<BigEndian>().map_err(drop));
+        let length = try!(cmap_reader.read_u16::<BigEndian>());
+        let language = try!(cmap_reader.read_u16::<BigEndian>());
+        let seg_count = try!(cmap_reader.read_u16::<BigEndian>()) / 2;
+        let search_range = try!(cmap_reader.read_u16::<BigEndian>());
+        let entry_selector = try!(cmap_reader.read_u16::<BigEndian>());
+        let range_shift = try!(cmap_reader.read_u16::<BigEndian>());
 
         // Set up parallel array pointers.
         //
@@ -101,16 +118,19 @@ impl<'a> CmapTable<'a> {
             let mut codepoint_range = *codepoint_range;
             while codepoint_range.end >= codepoint_range.start {
                 if codepoint_range.start > u16::MAX as u32 {
-                    codepoint_range.start += 1;
                     glyph_ranges.push(GlyphRange {
                         start: MISSING_GLYPH,
                         end: MISSING_GLYPH,
                     });
+                    match codepoint_range.start.checked_add(1) {
+                        Some(start) => codepoint_range.start = start,
+                        None => break,
+                    }
                     continue
                 }
 
                 let start_codepoint_range = codepoint_range.start as u16;
-                let mut end_codepoint_range = codepoint_range.end as u16;
+                let mut end_codepoint_range = cmp::min(codepoint_range.end, u16::MAX as u32) as u16;
 
                 // Binary search to find the segment.
                 let (mut low, mut high) = (0, seg_count);
@@ -120,7 +140,7 @@ impl<'a> CmapTable<'a> {
 
                     let mut end_code = end_codes;
                     try!(end_code.jump(mid as usize * 2));
-                    let end_code = try!(end_code.read_u16::<BigEndian>().map_err(drop));
+                    let end_code = try!(end_code.read_u16::<BigEndian>());
                     if start_codepoint_range > end_code {
                         low = mid + 1;
                         continue
@@ -128,7 +148,7 @@ impl<'a> CmapTable<'a> {
 
                     let mut start_code = start_codes;
                     try!(start_code.jump(mid as usize * 2));
-                    let start_code = try!(start_code.read_u16::<BigEndian>().map_err(drop));
+                    let start_code = try!(start_code.read_u16::<BigEndian>());
                     if start_codepoint_range < start_code {
                         high = mid;
                         continue
@@ -159,13 +179,13 @@ impl<'a> CmapTable<'a> {
                 try!(end_code.jump(segment_index as usize * 2));
                 try!(id_range_offset.jump(segment_index as usize * 2));
                 try!(id_delta.jump(segment_index as usize * 2));
-                let start_code = try!(start_code.read_u16::<BigEndian>().map_err(drop));
-                let end_code = try!(end_code.read_u16::<BigEndian>().map_err(drop));
-                let id_range_offset = try!(id_range_offset.read_u16::<BigEndian>().map_err(drop));
-                let id_delta = try!(id_delta.read_i16::<BigEndian>().map_err(drop));
+                let start_code = try!(start_code.read_u16::<BigEndian>());
+                let end_code = try!(end_code.read_u16::<BigEndian>());
+                let id_range_offset = try!(id_range_offset.read_u16::<BigEndian>());
+                let id_delta = try!(id_delta.read_i16::<BigEndian>());
 
                 end_codepoint_range = cmp::min(end_codepoint_range, end_code);
-                codepoint_range.start = (end_codepoint_range + 1) as u32;
+                codepoint_range.start = end_codepoint_range as u32 + 1;
 
                 let start_code_offset = start_codepoint_range - start_code;
                 let end_code_offset = end_codepoint_range - start_code;
@@ -184,10 +204,10 @@ impl<'a> CmapTable<'a> {
                 }
 
                 // Otherwise, look up the glyphs individually.
-                for code_offset in start_code_offset..(end_code_offset + 1) {
+                for code_offset in start_code_offset as u32..(end_code_offset as u32 + 1) {
                     let mut glyph_id = glyph_ids;
                     try!(glyph_id.jump((id_range_offset as usize + code_offset as usize) * 2));
-                    let mut glyph_id = try!(glyph_id.read_u16::<BigEndian>().map_err(drop));
+                    let mut glyph_id = try!(glyph_id.read_u16::<BigEndian>());
                     if glyph_id == 0 {
                         glyph_ranges.push(GlyphRange {
                             start: MISSING_GLYPH,
@@ -206,5 +226,150 @@ impl<'a> CmapTable<'a> {
 
         Ok(glyph_ranges)
     }
+
+    /// Returns the glyph that the given codepoint maps to, or `None` if it isn't mapped.
+    ///
+    /// Unlike `glyph_ranges_for_codepoint_ranges()`, this looks through all the encoding records
+    /// for a Unicode subtable, preferring format 12, which covers codepoints outside the Basic
+    /// Multilingual Plane, to format 4.
+    pub fn glyph_for_codepoint(&self, codepoint: u32) -> Result<Option<u16>, Error> {
+        let (format, mut reader) = try!(self.unicode_subtable());
+        let glyph_id = if format == FORMAT_SEGMENTED_COVERAGE {
+            try!(reader.jump(mem::size_of::<u16>() * 2 + mem::size_of::<u32>() * 2));
+            let num_groups = try!(reader.read_u32::<BigEndian>()) as usize;
+            let groups_length = try!(num_groups.checked_mul(SEQUENTIAL_MAP_GROUP_SIZE)
+                                               .ok_or(Error::UnexpectedEof));
+            if reader.len() < groups_length {
+                return Err(Error::UnexpectedEof)
+            }
+
+            // Binary search for the group.
+            let (mut low, mut high) = (0, num_groups);
+            let mut glyph_id = MISSING_GLYPH as u32;
+            while low < high {
+                let mid = (low + high) / 2;
+                let mut group = &reader[(mid * SEQUENTIAL_MAP_GROUP_SIZE)..];
+                let start_char_code = try!(group.read_u32::<BigEndian>());
+                let end_char_code = try!(group.read_u32::<BigEndian>());
+                if codepoint < start_char_code {
+                    high = mid;
+                    continue
+                }
+                if codepoint > end_char_code {
+                    low = mid + 1;
+                    continue
+                }
+                let start_glyph_id = try!(group.read_u32::<BigEndian>());
+                glyph_id = start_glyph_id.wrapping_add(codepoint - start_char_code);
+                break
+            }
+            if glyph_id > u16::MAX as u32 {
+                return Err(Error::Malformed)
+            }
+            glyph_id as u16
+        } else {
+            if codepoint > u16::MAX as u32 {
+                return Ok(None)
+            }
+            let codepoint = codepoint as u16;
+
+            try!(reader.jump(mem::size_of::<u16>() * 3));
+            let seg_count = try!(reader.read_u16::<BigEndian>()) as usize / 2;
+            try!(reader.jump(mem::size_of::<u16>() * 3));
+            let end_codes = reader;
+            let mut start_codes = end_codes;
+            try!(start_codes.jump((seg_count + 1) * mem::size_of::<u16>()));
+            let mut id_deltas = start_codes;
+            try!(id_deltas.jump(seg_count * mem::size_of::<u16>()));
+            let mut id_range_offsets = id_deltas;
+            try!(id_range_offsets.jump(seg_count * mem::size_of::<u16>()));
+            if id_range_offsets.len() < seg_count * mem::size_of::<u16>() {
+                return Err(Error::UnexpectedEof)
+            }
+
+            // Find the first segment that ends at or after the codepoint.
+            let (mut low, mut high) = (0, seg_count);
+            while low < high {
+                let mid = (low + high) / 2;
+                if codepoint > try!((&end_codes[(mid * 2)..]).read_u16::<BigEndian>()) {
+                    low = mid + 1
+                } else {
+                    high = mid
+                }
+            }
+            if low == seg_count {
+                return Ok(None)
+            }
+
+            let start_code = try!((&start_codes[(low * 2)..]).read_u16::<BigEndian>());
+            if codepoint < start_code {
+                return Ok(None)
+            }
+            let id_delta = try!((&id_deltas[(low * 2)..]).read_u16::<BigEndian>());
+            let offset = try!((&id_range_offsets[(low * 2)..]).read_u16::<BigEndian>()) as usize;
+            if offset == 0 {
+                codepoint.wrapping_add(id_delta)
+            } else {
+                // The offset is relative to the `idRangeOffset` entry itself.
+                let mut glyph_id_reader = &id_range_offsets[(low * 2)..];
+                try!(glyph_id_reader.jump(offset + (codepoint - start_code) as usize * 2));
+                match try!(glyph_id_reader.read_u16::<BigEndian>()) {
+                    MISSING_GLYPH => MISSING_GLYPH,
+                    glyph_id => glyph_id.wrapping_add(id_delta),
+                }
+            }
+        };
+
+        if glyph_id == MISSING_GLYPH {
+            Ok(None)
+        } else {
+            Ok(Some(glyph_id))
+        }
+    }
+
+    // Returns the format and data of the best subtable for looking up Unicode codepoints.
+    fn unicode_subtable(&self) -> Result<(u16, &'a [u8]), Error> {
+        let mut reader = self.table.bytes;
+        let version = try!(reader.read_u16::<BigEndian>());
+        if version != 0 {
+            return Err(Error::UnsupportedFormat {
+                table: CMAP,
+                format: version as u32,
+            })
+        }
+
+        let num_tables = try!(reader.read_u16::<BigEndian>()) as usize;
+        if reader.len() < num_tables * ENCODING_RECORD_SIZE {
+            return Err(Error::UnexpectedEof)
+        }
+        let mut best_subtable = None;
+        for _ in 0..num_tables {
+            let platform_id = try!(reader.read_u16::<BigEndian>());
+            let encoding_id = try!(reader.read_u16::<BigEndian>());
+            let offset = try!(reader.read_u32::<BigEndian>());
+            match (platform_id, encoding_id) {
+                (PLATFORM_ID_UNICODE, _) |
+                (PLATFORM_ID_MICROSOFT, MICROSOFT_ENCODING_ID_UNICODE_BMP) |
+                (PLATFORM_ID_MICROSOFT, MICROSOFT_ENCODING_ID_UNICODE_UCS4) => {}
+                _ => continue,
+            }
+
+            let mut subtable = self.table.bytes;
+            try!(subtable.jump(offset as usize));
+            let format = try!((&subtable[..]).read_u16::<BigEndian>());
+            match (format, best_subtable) {
+                (FORMAT_SEGMENTED_COVERAGE, _) => return Ok((format, subtable)),
+                (FORMAT_SEGMENT_MAPPING_TO_DELTA_VALUES, None) => {
+                    best_subtable = Some((format, subtable))
+                }
+                _ => {}
+            }
+        }
+
+        best_subtable.ok_or(Error::UnsupportedFormat {
+            table: CMAP,
+            format: FORMAT_SEGMENT_MAPPING_TO_DELTA_VALUES as u32,
+        })
+    }
 }
 
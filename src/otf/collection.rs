@@ -0,0 +1,113 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! TrueType/OpenType Collections (`.ttc`/`.otc`).
+//!
+//! A collection stores several faces in one file. Each face has its own table directory, but the
+//! tables themselves may be shared, so every face is a view into the same underlying bytes.
+
+use byteorder::{BigEndian, ReadBytesExt};
+use error::Error;
+use otf::{FontData, FontTable};
+use std::mem;
+use util::Jump;
+
+pub const TTCF: u32 = ((b't' as u32) << 24) |
+                      ((b't' as u32) << 16) |
+                      ((b'c' as u32) << 8)  |
+                       (b'f' as u32);
+const DSIG: u32 = ((b'D' as u32) << 24) |
+                  ((b'S' as u32) << 16) |
+                  ((b'I' as u32) << 8)  |
+                   (b'G' as u32);
+
+#[derive(Clone, Copy, Debug)]
+pub struct FontCollection<'a> {
+    pub bytes: &'a [u8],
+    pub major_version: u16,
+    pub minor_version: u16,
+    num_fonts: u32,
+    /// The digital signature, present only in version 2 headers, and only if the font is signed.
+    pub dsig_table: Option<FontTable<'a>>,
+}
+
+impl<'a> FontCollection<'a> {
+    pub fn new<'b>(bytes: &'b [u8]) -> Result<FontCollection<'b>, Error> {
+        let mut reader = bytes;
+        let signature = try!(reader.read_u32::<BigEndian>());
+        if signature != TTCF {
+            return Err(Error::UnsupportedSfntVersion(signature))
+        }
+
+        let major_version = try!(reader.read_u16::<BigEndian>());
+        let minor_version = try!(reader.read_u16::<BigEndian>());
+        if major_version != 1 && major_version != 2 {
+            return Err(Error::UnsupportedFormat {
+                table: TTCF,
+                format: ((major_version as u32) << 16) | minor_version as u32,
+            })
+        }
+
+        // Make sure the whole offset table is present so that `face` can't fail on truncation.
+        let num_fonts = try!(reader.read_u32::<BigEndian>());
+        let mut dsig_reader = reader;
+        let offsets_length = try!((num_fonts as usize).checked_mul(mem::size_of::<u32>())
+                                                        .ok_or(Error::UnexpectedEof));
+        try!(dsig_reader.jump(offsets_length));
+
+        // Version 2 headers append the location of the digital signature.
+        let mut dsig_table = None;
+        if major_version == 2 {
+            let dsig_tag = try!(dsig_reader.read_u32::<BigEndian>());
+            let dsig_length = try!(dsig_reader.read_u32::<BigEndian>()) as usize;
+            let dsig_offset = try!(dsig_reader.read_u32::<BigEndian>()) as usize;
+            if dsig_tag == DSIG {
+                let end = try!(dsig_offset.checked_add(dsig_length).ok_or(Error::Malformed));
+                if end > bytes.len() {
+                    return Err(Error::Malformed)
+                }
+                dsig_table = Some(FontTable {
+                    bytes: &bytes[dsig_offset..end],
+                })
+            }
+        }
+
+        Ok(FontCollection {
+            bytes: bytes,
+            major_version: major_version,
+            minor_version: minor_version,
+            num_fonts: num_fonts,
+            dsig_table: dsig_table,
+        })
+    }
+
+    /// Returns the number of faces in this collection.
+    #[inline]
+    pub fn face_count(&self) -> u32 {
+        self.num_fonts
+    }
+
+    /// Returns a view of face `index`. The returned font borrows the collection's bytes, so
+    /// tables shared between faces are not copied.
+    pub fn face(&self, index: u32) -> Result<FontData<'a>, Error> {
+        if index >= self.num_fonts {
+            return Err(Error::InvalidArgument)
+        }
+
+        let mut reader = self.bytes;
+        try!(reader.jump(mem::size_of::<u32>() * 3 + index as usize * mem::size_of::<u32>()));
+        let header_offset = try!(reader.read_u32::<BigEndian>()) as usize;
+        if header_offset >= self.bytes.len() {
+            return Err(Error::Malformed)
+        }
+
+        Ok(FontData::with_header_offset(self.bytes, header_offset))
+    }
+}
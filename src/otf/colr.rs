@@ -0,0 +1,692 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `COLR` table, which describes color glyphs as stacks of ordinary glyphs, each filled with
+//! a color from `CPAL`.
+//!
+//! Version 1 adds a graph of paint tables with gradients, transforms, and compositing. Variable
+//! paints are read with their default values, since we don't support font variations.
+
+use byteorder::{BigEndian, ReadBytesExt};
+use error::Error;
+use euclid::{Point2D, Rect, Size2D};
+use otf::{COLR, FontTable};
+use otf::cpal::Color;
+use outline::Transform;
+use std::f32::consts::PI;
+use std::mem;
+use util::Jump;
+
+/// The palette index that means "use the text foreground color".
+pub const FOREGROUND_PALETTE_INDEX: u16 = 0xffff;
+
+const BASE_GLYPH_RECORD_SIZE: usize = 6;
+const LAYER_RECORD_SIZE: usize = 4;
+const BASE_GLYPH_PAINT_RECORD_SIZE: usize = 6;
+const CLIP_RECORD_SIZE: usize = 7;
+
+const PAINT_FORMAT_COLR_LAYERS: u8 = 1;
+const PAINT_FORMAT_SOLID: u8 = 2;
+const PAINT_FORMAT_VAR_SOLID: u8 = 3;
+const PAINT_FORMAT_LINEAR_GRADIENT: u8 = 4;
+const PAINT_FORMAT_VAR_LINEAR_GRADIENT: u8 = 5;
+const PAINT_FORMAT_RADIAL_GRADIENT: u8 = 6;
+const PAINT_FORMAT_VAR_RADIAL_GRADIENT: u8 = 7;
+const PAINT_FORMAT_SWEEP_GRADIENT: u8 = 8;
+const PAINT_FORMAT_VAR_SWEEP_GRADIENT: u8 = 9;
+const PAINT_FORMAT_GLYPH: u8 = 10;
+const PAINT_FORMAT_COLR_GLYPH: u8 = 11;
+const PAINT_FORMAT_TRANSFORM: u8 = 12;
+const PAINT_FORMAT_VAR_TRANSFORM: u8 = 13;
+const PAINT_FORMAT_TRANSLATE: u8 = 14;
+const PAINT_FORMAT_VAR_TRANSLATE: u8 = 15;
+const PAINT_FORMAT_SCALE: u8 = 16;
+const PAINT_FORMAT_VAR_SCALE_AROUND_CENTER: u8 = 19;
+const PAINT_FORMAT_SCALE_UNIFORM: u8 = 20;
+const PAINT_FORMAT_VAR_SCALE_UNIFORM_AROUND_CENTER: u8 = 23;
+const PAINT_FORMAT_ROTATE: u8 = 24;
+const PAINT_FORMAT_VAR_ROTATE_AROUND_CENTER: u8 = 27;
+const PAINT_FORMAT_SKEW: u8 = 28;
+const PAINT_FORMAT_VAR_SKEW_AROUND_CENTER: u8 = 31;
+const PAINT_FORMAT_COMPOSITE: u8 = 32;
+
+const EXTEND_PAD: u8 = 0;
+const EXTEND_REPEAT: u8 = 1;
+const EXTEND_REFLECT: u8 = 2;
+
+/// A reference to a paint table, as its offset from the start of the `COLR` table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PaintOffset(pub u32);
+
+/// A node in a version 1 paint graph.
+///
+/// The many transform paint formats are all represented as `Transform`. Coordinates are in font
+/// units, and angles in degrees counterclockwise.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Paint {
+    /// Paints `layer_count` layers from the layer list, bottommost first, each composited over
+    /// the ones below.
+    ColrLayers {
+        first_layer_index: u32,
+        layer_count: u8,
+    },
+    Solid {
+        palette_index: u16,
+        alpha: f32,
+    },
+    LinearGradient {
+        color_line: ColorLine,
+        start: Point2D<f32>,
+        end: Point2D<f32>,
+        /// Together with `start`, defines the direction along which the color is constant.
+        rotation_point: Point2D<f32>,
+    },
+    RadialGradient {
+        color_line: ColorLine,
+        start_center: Point2D<f32>,
+        start_radius: f32,
+        end_center: Point2D<f32>,
+        end_radius: f32,
+    },
+    SweepGradient {
+        color_line: ColorLine,
+        center: Point2D<f32>,
+        start_angle: f32,
+        end_angle: f32,
+    },
+    /// Fills the outline of `glyph_id` with `paint`.
+    Glyph {
+        paint: PaintOffset,
+        glyph_id: u16,
+    },
+    /// Paints the version 1 color glyph `glyph_id`.
+    ColrGlyph {
+        glyph_id: u16,
+    },
+    Transform {
+        paint: PaintOffset,
+        transform: Transform,
+    },
+    Composite {
+        source: PaintOffset,
+        mode: CompositeMode,
+        backdrop: PaintOffset,
+    },
+}
+
+/// How a gradient continues outside the range of its color stops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Extend {
+    Pad,
+    Repeat,
+    Reflect,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorStop {
+    pub offset: f32,
+    /// An index into the current palette, or `FOREGROUND_PALETTE_INDEX`.
+    pub palette_index: u16,
+    pub alpha: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorLine {
+    pub extend: Extend,
+    /// The color stops, in the order they appear in the font. They may not be sorted.
+    pub stops: Vec<ColorStop>,
+}
+
+/// The ways `PaintComposite` can combine its source and backdrop, in the order of their values
+/// in the font.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompositeMode {
+    Clear,
+    Source,
+    Destination,
+    SourceOver,
+    DestinationOver,
+    SourceIn,
+    DestinationIn,
+    SourceOut,
+    DestinationOut,
+    SourceAtop,
+    DestinationAtop,
+    Xor,
+    Plus,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Multiply,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+static COMPOSITE_MODES: [CompositeMode; 28] = [
+    CompositeMode::Clear,
+    CompositeMode::Source,
+    CompositeMode::Destination,
+    CompositeMode::SourceOver,
+    CompositeMode::DestinationOver,
+    CompositeMode::SourceIn,
+    CompositeMode::DestinationIn,
+    CompositeMode::SourceOut,
+    CompositeMode::DestinationOut,
+    CompositeMode::SourceAtop,
+    CompositeMode::DestinationAtop,
+    CompositeMode::Xor,
+    CompositeMode::Plus,
+    CompositeMode::Screen,
+    CompositeMode::Overlay,
+    CompositeMode::Darken,
+    CompositeMode::Lighten,
+    CompositeMode::ColorDodge,
+    CompositeMode::ColorBurn,
+    CompositeMode::HardLight,
+    CompositeMode::SoftLight,
+    CompositeMode::Difference,
+    CompositeMode::Exclusion,
+    CompositeMode::Multiply,
+    CompositeMode::Hue,
+    CompositeMode::Saturation,
+    CompositeMode::Color,
+    CompositeMode::Luminosity,
+];
+
+#[derive(Clone, Copy)]
+pub struct ColrTable<'a> {
+    pub table: FontTable<'a>,
+    pub version: u16,
+    num_base_glyph_records: u16,
+    base_glyph_records_offset: u32,
+    layer_records_offset: u32,
+    num_layer_records: u16,
+    base_glyph_list_offset: u32,
+    layer_list_offset: u32,
+    clip_list_offset: u32,
+}
+
+/// One layer of a color glyph: an outline glyph and the palette entry to fill it with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorLayer {
+    pub glyph_id: u16,
+    /// An index into the current palette, or `FOREGROUND_PALETTE_INDEX`.
+    pub palette_index: u16,
+}
+
+impl ColorLayer {
+    /// Returns the color of this layer given the selected palette and the text foreground color.
+    pub fn color(&self, palette: &[Color], foreground_color: Color) -> Result<Color, Error> {
+        if self.palette_index == FOREGROUND_PALETTE_INDEX {
+            Ok(foreground_color)
+        } else {
+            palette.get(self.palette_index as usize).cloned().ok_or(Error::Malformed)
+        }
+    }
+}
+
+impl<'a> ColrTable<'a> {
+    pub fn new(table: FontTable) -> Result<ColrTable, Error> {
+        let mut reader = table.bytes;
+
+        // Version 1 tables start with a version 0 header, so we can read the layers of either.
+        let version = try!(reader.read_u16::<BigEndian>());
+        if version > 1 {
+            return Err(Error::UnsupportedFormat {
+                table: COLR,
+                format: version as u32,
+            })
+        }
+
+        let num_base_glyph_records = try!(reader.read_u16::<BigEndian>());
+        let base_glyph_records_offset = try!(reader.read_u32::<BigEndian>());
+        let layer_records_offset = try!(reader.read_u32::<BigEndian>());
+        let num_layer_records = try!(reader.read_u16::<BigEndian>());
+
+        let (mut base_glyph_list_offset, mut layer_list_offset, mut clip_list_offset) = (0, 0, 0);
+        if version >= 1 {
+            base_glyph_list_offset = try!(reader.read_u32::<BigEndian>());
+            layer_list_offset = try!(reader.read_u32::<BigEndian>());
+            clip_list_offset = try!(reader.read_u32::<BigEndian>());
+        }
+
+        Ok(ColrTable {
+            table: table,
+            version: version,
+            num_base_glyph_records: num_base_glyph_records,
+            base_glyph_records_offset: base_glyph_records_offset,
+            layer_records_offset: layer_records_offset,
+            num_layer_records: num_layer_records,
+            base_glyph_list_offset: base_glyph_list_offset,
+            layer_list_offset: layer_list_offset,
+            clip_list_offset: clip_list_offset,
+        })
+    }
+
+    /// Returns the layers of the given glyph, bottommost first, or `None` if it has no version 0
+    /// color glyph.
+    pub fn layers(&self, glyph_id: u16) -> Result<Option<Vec<ColorLayer>>, Error> {
+        let mut base_glyph_records = self.table.bytes;
+        try!(base_glyph_records.jump(self.base_glyph_records_offset as usize));
+
+        // Binary search for the base glyph record.
+        let (mut low, mut high) = (0, self.num_base_glyph_records);
+        while low < high {
+            let mid = (low + high) / 2;
+            let mut reader = base_glyph_records;
+            try!(reader.jump(mid as usize * BASE_GLYPH_RECORD_SIZE));
+
+            let current_glyph_id = try!(reader.read_u16::<BigEndian>());
+            if glyph_id < current_glyph_id {
+                high = mid;
+                continue
+            }
+            if glyph_id > current_glyph_id {
+                low = mid + 1;
+                continue
+            }
+
+            let first_layer_index = try!(reader.read_u16::<BigEndian>());
+            let num_layers = try!(reader.read_u16::<BigEndian>());
+            if first_layer_index as u32 + num_layers as u32 > self.num_layer_records as u32 {
+                return Err(Error::Malformed)
+            }
+
+            let mut layer_reader = self.table.bytes;
+            try!(layer_reader.jump(self.layer_records_offset as usize +
+                                   first_layer_index as usize * LAYER_RECORD_SIZE));
+            let mut layers = Vec::with_capacity(num_layers as usize);
+            for _ in 0..num_layers {
+                let glyph_id = try!(layer_reader.read_u16::<BigEndian>());
+                let palette_index = try!(layer_reader.read_u16::<BigEndian>());
+                layers.push(ColorLayer {
+                    glyph_id: glyph_id,
+                    palette_index: palette_index,
+                })
+            }
+            return Ok(Some(layers))
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the IDs of all glyphs that have version 0 color layers, in ascending order.
+    pub fn base_glyph_ids(&self) -> Result<Vec<u16>, Error> {
+        let mut reader = self.table.bytes;
+        try!(reader.jump(self.base_glyph_records_offset as usize));
+
+        let mut glyph_ids = Vec::with_capacity(self.num_base_glyph_records as usize);
+        for _ in 0..self.num_base_glyph_records {
+            glyph_ids.push(try!(reader.read_u16::<BigEndian>()));
+            try!(reader.jump(mem::size_of::<u16>() * 2));
+        }
+        Ok(glyph_ids)
+    }
+
+    /// Returns the root of the version 1 paint graph for the given glyph, if it has one.
+    pub fn base_glyph_paint(&self, glyph_id: u16) -> Result<Option<PaintOffset>, Error> {
+        if self.base_glyph_list_offset == 0 {
+            return Ok(None)
+        }
+
+        let mut reader = self.table.bytes;
+        try!(reader.jump(self.base_glyph_list_offset as usize));
+        let num_records = try!(reader.read_u32::<BigEndian>());
+
+        let (mut low, mut high) = (0, num_records);
+        while low < high {
+            let mid = (low + high) / 2;
+            let mut record_reader = reader;
+            try!(record_reader.jump(mid as usize * BASE_GLYPH_PAINT_RECORD_SIZE));
+
+            let current_glyph_id = try!(record_reader.read_u16::<BigEndian>());
+            if glyph_id < current_glyph_id {
+                high = mid;
+                continue
+            }
+            if glyph_id > current_glyph_id {
+                low = mid + 1;
+                continue
+            }
+
+            let paint_offset = try!(record_reader.read_u32::<BigEndian>());
+            return self.paint_offset(self.base_glyph_list_offset, paint_offset).map(Some)
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the paint at the given index in the layer list.
+    pub fn layer_paint(&self, layer_index: u32) -> Result<PaintOffset, Error> {
+        if self.layer_list_offset == 0 {
+            return Err(Error::Malformed)
+        }
+
+        let mut reader = self.table.bytes;
+        try!(reader.jump(self.layer_list_offset as usize));
+        let num_layers = try!(reader.read_u32::<BigEndian>());
+        if layer_index >= num_layers {
+            return Err(Error::Malformed)
+        }
+        try!(reader.jump(layer_index as usize * mem::size_of::<u32>()));
+        let paint_offset = try!(reader.read_u32::<BigEndian>());
+        self.paint_offset(self.layer_list_offset, paint_offset)
+    }
+
+    /// Returns the clip box of the given glyph in font units, if it has one.
+    pub fn clip_box(&self, glyph_id: u16) -> Result<Option<Rect<f32>>, Error> {
+        if self.clip_list_offset == 0 {
+            return Ok(None)
+        }
+
+        let mut reader = self.table.bytes;
+        try!(reader.jump(self.clip_list_offset as usize));
+        let format = try!(reader.read_u8());
+        if format != 1 {
+            return Err(Error::UnsupportedFormat {
+                table: COLR,
+                format: format as u32,
+            })
+        }
+        let num_clips = try!(reader.read_u32::<BigEndian>());
+
+        // Clip records are sorted by glyph ID and don't overlap.
+        let (mut low, mut high) = (0, num_clips);
+        while low < high {
+            let mid = (low + high) / 2;
+            let mut record_reader = reader;
+            try!(record_reader.jump(mid as usize * CLIP_RECORD_SIZE));
+
+            let start_glyph_id = try!(record_reader.read_u16::<BigEndian>());
+            let end_glyph_id = try!(record_reader.read_u16::<BigEndian>());
+            if glyph_id < start_glyph_id {
+                high = mid;
+                continue
+            }
+            if glyph_id > end_glyph_id {
+                low = mid + 1;
+                continue
+            }
+
+            // Both clip box formats start the same way; format 2 adds variation indices.
+            let clip_box_offset = try!(record_reader.read_u24::<BigEndian>());
+            let mut clip_box_reader = self.table.bytes;
+            try!(clip_box_reader.jump(self.clip_list_offset as usize + clip_box_offset as usize));
+            match try!(clip_box_reader.read_u8()) {
+                1 | 2 => {}
+                format => {
+                    return Err(Error::UnsupportedFormat {
+                        table: COLR,
+                        format: format as u32,
+                    })
+                }
+            }
+            let x_min = try!(clip_box_reader.read_i16::<BigEndian>()) as f32;
+            let y_min = try!(clip_box_reader.read_i16::<BigEndian>()) as f32;
+            let x_max = try!(clip_box_reader.read_i16::<BigEndian>()) as f32;
+            let y_max = try!(clip_box_reader.read_i16::<BigEndian>()) as f32;
+            return Ok(Some(Rect::new(Point2D::new(x_min, y_min),
+                                     Size2D::new(x_max - x_min, y_max - y_min))))
+        }
+
+        Ok(None)
+    }
+
+    /// Reads the paint table at the given offset.
+    pub fn paint(&self, offset: PaintOffset) -> Result<Paint, Error> {
+        let PaintOffset(offset) = offset;
+        let mut reader = self.table.bytes;
+        try!(reader.jump(offset as usize));
+
+        let format = try!(reader.read_u8());
+        match format {
+            PAINT_FORMAT_COLR_LAYERS => {
+                let layer_count = try!(reader.read_u8());
+                let first_layer_index = try!(reader.read_u32::<BigEndian>());
+                Ok(Paint::ColrLayers {
+                    first_layer_index: first_layer_index,
+                    layer_count: layer_count,
+                })
+            }
+            PAINT_FORMAT_SOLID | PAINT_FORMAT_VAR_SOLID => {
+                let palette_index = try!(reader.read_u16::<BigEndian>());
+                let alpha = try!(read_f2dot14(&mut reader));
+                Ok(Paint::Solid {
+                    palette_index: palette_index,
+                    alpha: alpha,
+                })
+            }
+            PAINT_FORMAT_LINEAR_GRADIENT | PAINT_FORMAT_VAR_LINEAR_GRADIENT => {
+                let color_line = try!(self.color_line(offset, &mut reader, format));
+                let start = try!(read_point(&mut reader));
+                let end = try!(read_point(&mut reader));
+                let rotation_point = try!(read_point(&mut reader));
+                Ok(Paint::LinearGradient {
+                    color_line: color_line,
+                    start: start,
+                    end: end,
+                    rotation_point: rotation_point,
+                })
+            }
+            PAINT_FORMAT_RADIAL_GRADIENT | PAINT_FORMAT_VAR_RADIAL_GRADIENT => {
+                let color_line = try!(self.color_line(offset, &mut reader, format));
+                let start_center = try!(read_point(&mut reader));
+                let start_radius = try!(reader.read_u16::<BigEndian>()) as f32;
+                let end_center = try!(read_point(&mut reader));
+                let end_radius = try!(reader.read_u16::<BigEndian>()) as f32;
+                Ok(Paint::RadialGradient {
+                    color_line: color_line,
+                    start_center: start_center,
+                    start_radius: start_radius,
+                    end_center: end_center,
+                    end_radius: end_radius,
+                })
+            }
+            PAINT_FORMAT_SWEEP_GRADIENT | PAINT_FORMAT_VAR_SWEEP_GRADIENT => {
+                let color_line = try!(self.color_line(offset, &mut reader, format));
+                let center = try!(read_point(&mut reader));
+                // Sweep angles are biased by 1.0 so that they can span a full turn.
+                let start_angle = (try!(read_f2dot14(&mut reader)) + 1.0) * 180.0;
+                let end_angle = (try!(read_f2dot14(&mut reader)) + 1.0) * 180.0;
+                Ok(Paint::SweepGradient {
+                    color_line: color_line,
+                    center: center,
+                    start_angle: start_angle,
+                    end_angle: end_angle,
+                })
+            }
+            PAINT_FORMAT_GLYPH => {
+                let paint = try!(read_child_offset(offset, &mut reader));
+                let glyph_id = try!(reader.read_u16::<BigEndian>());
+                Ok(Paint::Glyph {
+                    paint: paint,
+                    glyph_id: glyph_id,
+                })
+            }
+            PAINT_FORMAT_COLR_GLYPH => {
+                let glyph_id = try!(reader.read_u16::<BigEndian>());
+                Ok(Paint::ColrGlyph {
+                    glyph_id: glyph_id,
+                })
+            }
+            PAINT_FORMAT_TRANSFORM | PAINT_FORMAT_VAR_TRANSFORM => {
+                let paint = try!(read_child_offset(offset, &mut reader));
+                let transform_offset = try!(reader.read_u24::<BigEndian>());
+                let mut transform_reader = self.table.bytes;
+                try!(transform_reader.jump(offset as usize + transform_offset as usize));
+                let mut values = [0.0; 6];
+                for value in &mut values {
+                    *value = try!(transform_reader.read_i32::<BigEndian>()) as f32 /
+                        65536.0
+                }
+                Ok(Paint::Transform {
+                    paint: paint,
+                    transform: Transform::new(values[0],
+                                              values[1],
+                                              values[2],
+                                              values[3],
+                                              values[4],
+                                              values[5]),
+                })
+            }
+            PAINT_FORMAT_TRANSLATE | PAINT_FORMAT_VAR_TRANSLATE => {
+                let paint = try!(read_child_offset(offset, &mut reader));
+                let translation = try!(read_point(&mut reader));
+                Ok(Paint::Transform {
+                    paint: paint,
+                    transform: Transform::translation(translation.x, translation.y),
+                })
+            }
+            PAINT_FORMAT_SCALE...PAINT_FORMAT_VAR_SCALE_AROUND_CENTER => {
+                // Even formats are static and odd ones variable; formats 18 and 19 have a center.
+                let paint = try!(read_child_offset(offset, &mut reader));
+                let scale_x = try!(read_f2dot14(&mut reader));
+                let scale_y = try!(read_f2dot14(&mut reader));
+                let transform = Transform::scale(scale_x, scale_y);
+                let transform = try!(around_center(&mut reader, format, transform, 18));
+                Ok(Paint::Transform {
+                    paint: paint,
+                    transform: transform,
+                })
+            }
+            PAINT_FORMAT_SCALE_UNIFORM...PAINT_FORMAT_VAR_SCALE_UNIFORM_AROUND_CENTER => {
+                let paint = try!(read_child_offset(offset, &mut reader));
+                let scale = try!(read_f2dot14(&mut reader));
+                let transform = Transform::scale(scale, scale);
+                let transform = try!(around_center(&mut reader, format, transform, 22));
+                Ok(Paint::Transform {
+                    paint: paint,
+                    transform: transform,
+                })
+            }
+            PAINT_FORMAT_ROTATE...PAINT_FORMAT_VAR_ROTATE_AROUND_CENTER => {
+                let paint = try!(read_child_offset(offset, &mut reader));
+                let angle = try!(read_f2dot14(&mut reader)) * PI;
+                let transform = Transform::rotation(angle);
+                let transform = try!(around_center(&mut reader, format, transform, 26));
+                Ok(Paint::Transform {
+                    paint: paint,
+                    transform: transform,
+                })
+            }
+            PAINT_FORMAT_SKEW...PAINT_FORMAT_VAR_SKEW_AROUND_CENTER => {
+                let paint = try!(read_child_offset(offset, &mut reader));
+                let x_skew_angle = try!(read_f2dot14(&mut reader)) * PI;
+                let y_skew_angle = try!(read_f2dot14(&mut reader)) * PI;
+                let transform =
+                    Transform::new(1.0, y_skew_angle.tan(), -x_skew_angle.tan(), 1.0, 0.0, 0.0);
+                let transform = try!(around_center(&mut reader, format, transform, 30));
+                Ok(Paint::Transform {
+                    paint: paint,
+                    transform: transform,
+                })
+            }
+            PAINT_FORMAT_COMPOSITE => {
+                let source = try!(read_child_offset(offset, &mut reader));
+                let mode = try!(reader.read_u8());
+                let mode = *try!(COMPOSITE_MODES.get(mode as usize).ok_or(Error::Malformed));
+                let backdrop = try!(read_child_offset(offset, &mut reader));
+                Ok(Paint::Composite {
+                    source: source,
+                    mode: mode,
+                    backdrop: backdrop,
+                })
+            }
+            _ => Err(Error::UnsupportedFormat {
+                table: COLR,
+                format: format as u32,
+            }),
+        }
+    }
+
+    fn paint_offset(&self, base: u32, offset: u32) -> Result<PaintOffset, Error> {
+        match base.checked_add(offset) {
+            Some(offset) if (offset as usize) < self.table.bytes.len() => Ok(PaintOffset(offset)),
+            _ => Err(Error::Malformed),
+        }
+    }
+
+    // Reads the color line that the reader points to the offset of. Variable paint formats use
+    // color lines whose stops have variation indices.
+    fn color_line(&self, paint_offset: u32, reader: &mut &[u8], format: u8)
+                  -> Result<ColorLine, Error> {
+        let color_line_offset = try!(reader.read_u24::<BigEndian>());
+        let mut color_line_reader = self.table.bytes;
+        try!(color_line_reader.jump(paint_offset as usize + color_line_offset as usize));
+
+        let extend = match try!(color_line_reader.read_u8()) {
+            EXTEND_REPEAT => Extend::Repeat,
+            EXTEND_REFLECT => Extend::Reflect,
+            // Unknown extend modes are to be treated as padding.
+            EXTEND_PAD | _ => Extend::Pad,
+        };
+
+        let variable = format % 2 == 1;
+        let num_stops = try!(color_line_reader.read_u16::<BigEndian>());
+        let mut stops = Vec::with_capacity(num_stops as usize);
+        for _ in 0..num_stops {
+            let offset = try!(read_f2dot14(&mut color_line_reader));
+            let palette_index = try!(color_line_reader.read_u16::<BigEndian>());
+            let alpha = try!(read_f2dot14(&mut color_line_reader));
+            if variable {
+                try!(color_line_reader.jump(mem::size_of::<u32>()));
+            }
+            stops.push(ColorStop {
+                offset: offset,
+                palette_index: palette_index,
+                alpha: alpha,
+            })
+        }
+
+        Ok(ColorLine {
+            extend: extend,
+            stops: stops,
+        })
+    }
+}
+
+fn read_f2dot14(reader: &mut &[u8]) -> Result<f32, Error> {
+    reader.read_i16::<BigEndian>().map_err(Error::from).map(|value| value as f32 / 16384.0)
+}
+
+fn read_point(reader: &mut &[u8]) -> Result<Point2D<f32>, Error> {
+    let x = try!(reader.read_i16::<BigEndian>()) as f32;
+    let y = try!(reader.read_i16::<BigEndian>()) as f32;
+    Ok(Point2D::new(x, y))
+}
+
+// Child paint offsets are 24-bit and relative to the parent paint.
+fn read_child_offset(paint_offset: u32, reader: &mut &[u8]) -> Result<PaintOffset, Error> {
+    let offset = try!(reader.read_u24::<BigEndian>());
+    Ok(PaintOffset(paint_offset + offset))
+}
+
+// Formats `first_centered_format` and the one after it are followed by a center point about which
+// the transform applies.
+fn around_center(reader: &mut &[u8],
+                 format: u8,
+                 transform: Transform,
+                 first_centered_format: u8)
+                 -> Result<Transform, Error> {
+    if format < first_centered_format {
+        return Ok(transform)
+    }
+    let center = try!(read_point(reader));
+    Ok(transform.around(&center))
+}
@@ -0,0 +1,177 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `CPAL` table, which holds the color palettes used by `COLR`.
+
+use byteorder::BigEndian;
+use error::Error;
+use otf::{CPAL, FontTable};
+use std::mem;
+use std::vec::Vec;
+use util::{Jump, ReadBytes};
+
+const COLOR_RECORD_SIZE: usize = 4;
+
+bitflags! {
+    pub flags PaletteType: u32 {
+        const USABLE_WITH_LIGHT_BACKGROUND = 1 << 0,
+        const USABLE_WITH_DARK_BACKGROUND = 1 << 1,
+    }
+}
+
+/// A non-premultiplied sRGB color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    #[inline]
+    pub fn new(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color {
+            r: r,
+            g: g,
+            b: b,
+            a: a,
+        }
+    }
+
+    /// Packs this color into a `u32` whose bytes, in little-endian order, are R, G, B, A. This is
+    /// the layout that the compositing shader expects.
+    #[inline]
+    pub fn to_packed_rgba(&self) -> u32 {
+        (self.r as u32) | ((self.g as u32) << 8) | ((self.b as u32) << 16) | ((self.a as u32) << 24)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CpalTable<'a> {
+    pub table: FontTable<'a>,
+    pub version: u16,
+    num_palette_entries: u16,
+    num_palettes: u16,
+    num_color_records: u16,
+    color_records_offset: u32,
+    color_record_indices: &'a [u8],
+    palette_types_offset: u32,
+}
+
+impl<'a> CpalTable<'a> {
+    pub fn new(table: FontTable) -> Result<CpalTable, Error> {
+        let mut reader = table.bytes;
+        let version = try!(reader.read_u16::<BigEndian>());
+        if version > 1 {
+            return Err(Error::UnsupportedFormat {
+                table: CPAL,
+                format: version as u32,
+            })
+        }
+
+        let num_palette_entries = try!(reader.read_u16::<BigEndian>());
+        let num_palettes = try!(reader.read_u16::<BigEndian>());
+        let num_color_records = try!(reader.read_u16::<BigEndian>());
+        let color_records_offset = try!(reader.read_u32::<BigEndian>());
+
+        let color_record_indices = reader;
+        try!(reader.jump(num_palettes as usize * mem::size_of::<u16>()));
+
+        // Version 1 adds palette types and labels. We don't expose the labels.
+        let palette_types_offset = if version >= 1 {
+            try!(reader.read_u32::<BigEndian>())
+        } else {
+            0
+        };
+
+        Ok(CpalTable {
+            table: table,
+            version: version,
+            num_palette_entries: num_palette_entries,
+            num_palettes: num_palettes,
+            num_color_records: num_color_records,
+            color_records_offset: color_records_offset,
+            color_record_indices: color_record_indices,
+            palette_types_offset: palette_types_offset,
+        })
+    }
+
+    #[inline]
+    pub fn palette_count(&self) -> u16 {
+        self.num_palettes
+    }
+
+    /// Returns the number of colors in each palette.
+    #[inline]
+    pub fn palette_entry_count(&self) -> u16 {
+        self.num_palette_entries
+    }
+
+    /// Returns the colors of the given palette.
+    pub fn palette(&self, palette_index: u16) -> Result<Vec<Color>, Error> {
+        if palette_index >= self.num_palettes {
+            return Err(Error::InvalidArgument)
+        }
+
+        let mut reader = self.color_record_indices;
+        try!(reader.jump(palette_index as usize * mem::size_of::<u16>()));
+        let first_color_index = try!(reader.read_u16::<BigEndian>());
+        if first_color_index as u32 + self.num_palette_entries as u32 >
+                self.num_color_records as u32 {
+            return Err(Error::Malformed)
+        }
+
+        let mut reader = self.table.bytes;
+        try!(reader.jump(self.color_records_offset as usize +
+                         first_color_index as usize * COLOR_RECORD_SIZE));
+        let mut colors = Vec::with_capacity(self.num_palette_entries as usize);
+        for _ in 0..self.num_palette_entries {
+            // Color records are stored as BGRA.
+            let b = try!(reader.read_u8());
+            let g = try!(reader.read_u8());
+            let r = try!(reader.read_u8());
+            let a = try!(reader.read_u8());
+            colors.push(Color::new(r, g, b, a))
+        }
+        Ok(colors)
+    }
+
+    /// Returns the type flags of the given palette. Version 0 tables, and version 1 tables
+    /// without a palette type array, report no flags.
+    pub fn palette_type(&self, palette_index: u16) -> Result<PaletteType, Error> {
+        if palette_index >= self.num_palettes {
+            return Err(Error::InvalidArgument)
+        }
+        if self.palette_types_offset == 0 {
+            return Ok(PaletteType::empty())
+        }
+
+        let mut reader = self.table.bytes;
+        try!(reader.jump(self.palette_types_offset as usize +
+                         palette_index as usize * mem::size_of::<u32>()));
+        let palette_type = try!(reader.read_u32::<BigEndian>());
+        Ok(PaletteType::from_bits_truncate(palette_type))
+    }
+
+    /// Chooses a palette for the given background. This is the first palette whose type includes
+    /// all of `preferred_type`, or palette 0, the default, if there is no such palette.
+    pub fn select_palette(&self, preferred_type: PaletteType) -> Result<u16, Error> {
+        if self.num_palettes == 0 {
+            return Err(Error::Malformed)
+        }
+        for palette_index in 0..self.num_palettes {
+            if try!(self.palette_type(palette_index)).contains(preferred_type) {
+                return Ok(palette_index)
+            }
+        }
+        Ok(0)
+    }
+}
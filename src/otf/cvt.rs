@@ -0,0 +1,42 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+
+//! The `cvt ` table, which holds the control values that TrueType hinting programs refer to.
+
+use byteorder::BigEndian;
+use error::Error;
+use otf::FontTable;
+use std::vec::Vec;
+use util::ReadBytes;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CvtTable<'a> {
+    pub table: FontTable<'a>,
+}
+
+impl<'a> CvtTable<'a> {
+    #[inline]
+    pub fn new(table: FontTable) -> CvtTable {
+        CvtTable {
+            table: table,
+        }
+    }
+
+    /// Returns the control values in font units.
+    pub fn values(&self) -> Result<Vec<i16>, Error> {
+        let mut reader = self.table.bytes;
+        let mut values = Vec::with_capacity(reader.len() / 2);
+        while reader.len() >= 2 {
+            values.push(try!(reader.read_i16::<BigEndian>()))
+        }
+        Ok(values)
+    }
+}
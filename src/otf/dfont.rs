@@ -0,0 +1,229 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Mac OS resource forks, as found in `.dfont` files and classic font suitcases.
+//!
+//! Outline fonts are stored as `sfnt` resources, and each `FOND` resource associates some of them
+//! with a font family and style.
+//!
+//! See "Inside Macintosh: More Macintosh Toolbox", chapter 1, and "Inside Macintosh: Text",
+//! chapter 4.
+
+use byteorder::{BigEndian, ReadBytesExt};
+use error::Error;
+use otf::FontData;
+use std::mem;
+use util::Jump;
+
+pub const SFNT: u32 = ((b's' as u32) << 24) |
+                      ((b'f' as u32) << 16) |
+                      ((b'n' as u32) << 8)  |
+                       (b't' as u32);
+pub const FOND: u32 = ((b'F' as u32) << 24) |
+                      ((b'O' as u32) << 16) |
+                      ((b'N' as u32) << 8)  |
+                       (b'D' as u32);
+
+const NO_NAME: u16 = 0xffff;
+
+// The offset of the font association table within a `FOND` resource.
+const FOND_FONT_ASSOCIATION_TABLE_OFFSET: usize = 52;
+
+/// A resource fork.
+#[derive(Clone, Copy, Debug)]
+pub struct ResourceFork<'a> {
+    pub bytes: &'a [u8],
+    data: &'a [u8],
+    map: &'a [u8],
+    type_list_offset: usize,
+    name_list_offset: usize,
+}
+
+/// A single resource.
+#[derive(Clone, Copy, Debug)]
+pub struct Resource<'a> {
+    pub id: i16,
+    pub name: Option<&'a [u8]>,
+    pub attributes: u8,
+    pub data: &'a [u8],
+}
+
+/// An outline font stored in a resource fork.
+#[derive(Clone, Copy, Debug)]
+pub struct DfontFace<'a> {
+    /// The ID of the `sfnt` resource.
+    pub resource_id: i16,
+    /// The name of the `sfnt` resource, in Mac OS Roman.
+    pub name: Option<&'a [u8]>,
+    /// The family that this face belongs to, if any `FOND` resource refers to it.
+    pub family: Option<FamilyAssociation<'a>>,
+    /// The font itself. This borrows the resource data without copying.
+    pub font: FontData<'a>,
+}
+
+/// The association of a face with a font family, from a `FOND` resource.
+#[derive(Clone, Copy, Debug)]
+pub struct FamilyAssociation<'a> {
+    /// The family ID (`ffFamID`).
+    pub family_id: i16,
+    /// The family name: the name of the `FOND` resource, in Mac OS Roman.
+    pub family_name: Option<&'a [u8]>,
+    /// The QuickDraw style bits (bold = 1, italic = 2, and so on) of this face within the family.
+    pub style: i16,
+}
+
+impl<'a> ResourceFork<'a> {
+    pub fn new<'b>(bytes: &'b [u8]) -> Result<ResourceFork<'b>, Error> {
+        let mut reader = bytes;
+        let data_offset = try!(reader.read_u32::<BigEndian>()) as usize;
+        let map_offset = try!(reader.read_u32::<BigEndian>()) as usize;
+        let data_length = try!(reader.read_u32::<BigEndian>()) as usize;
+        let map_length = try!(reader.read_u32::<BigEndian>()) as usize;
+
+        let data = try!(slice(bytes, data_offset, data_length));
+        let map = try!(slice(bytes, map_offset, map_length));
+
+        // Skip the copy of the header, the next map handle, the file reference number, and the
+        // attributes.
+        let mut reader = map;
+        try!(reader.jump(16 + mem::size_of::<u32>() + mem::size_of::<u16>() * 2));
+        let type_list_offset = try!(reader.read_u16::<BigEndian>()) as usize;
+        let name_list_offset = try!(reader.read_u16::<BigEndian>()) as usize;
+        if type_list_offset > map.len() || name_list_offset > map.len() {
+            return Err(Error::Malformed)
+        }
+
+        Ok(ResourceFork {
+            bytes: bytes,
+            data: data,
+            map: map,
+            type_list_offset: type_list_offset,
+            name_list_offset: name_list_offset,
+        })
+    }
+
+    /// Returns all resources of the given type, in the order they appear in the map.
+    pub fn resources(&self, resource_type: u32) -> Result<Vec<Resource<'a>>, Error> {
+        let type_list = &self.map[self.type_list_offset..];
+        let mut reader = type_list;
+        let type_count = try!(reader.read_u16::<BigEndian>()).wrapping_add(1);
+
+        let mut resources = vec![];
+        for _ in 0..type_count {
+            let current_type = try!(reader.read_u32::<BigEndian>());
+            let resource_count = try!(reader.read_u16::<BigEndian>()) as usize + 1;
+            let reference_list_offset = try!(reader.read_u16::<BigEndian>());
+            if current_type != resource_type {
+                continue
+            }
+
+            let mut reference_reader = type_list;
+            try!(reference_reader.jump(reference_list_offset as usize));
+            for _ in 0..resource_count {
+                resources.push(try!(self.read_reference(&mut reference_reader)))
+            }
+        }
+
+        Ok(resources)
+    }
+
+    /// Returns every `sfnt` resource as a face, along with its `FOND` family association.
+    pub fn faces(&self) -> Result<Vec<DfontFace<'a>>, Error> {
+        let families = try!(self.resources(FOND));
+        let sfnts = try!(self.resources(SFNT));
+
+        let mut faces = Vec::with_capacity(sfnts.len());
+        for sfnt in sfnts {
+            let mut family = None;
+            for fond in &families {
+                if let Some(association) = try!(find_association(fond, sfnt.id)) {
+                    family = Some(association);
+                    break
+                }
+            }
+
+            faces.push(DfontFace {
+                resource_id: sfnt.id,
+                name: sfnt.name,
+                family: family,
+                font: FontData::new(sfnt.data),
+            })
+        }
+
+        Ok(faces)
+    }
+
+    fn read_reference(&self, reader: &mut &[u8]) -> Result<Resource<'a>, Error> {
+        let id = try!(reader.read_i16::<BigEndian>());
+        let name_offset = try!(reader.read_u16::<BigEndian>());
+        let attributes_and_data_offset = try!(reader.read_u32::<BigEndian>());
+        try!(reader.jump(mem::size_of::<u32>()));
+
+        let attributes = (attributes_and_data_offset >> 24) as u8;
+        let data_offset = (attributes_and_data_offset & 0x00ffffff) as usize;
+
+        // Each resource's data is preceded by its length.
+        let mut data_reader = self.data;
+        try!(data_reader.jump(data_offset));
+        let data_length = try!(data_reader.read_u32::<BigEndian>()) as usize;
+        let data = try!(slice(data_reader, 0, data_length));
+
+        // Names are Pascal strings.
+        let name = if name_offset == NO_NAME {
+            None
+        } else {
+            let mut name_reader = self.map;
+            try!(name_reader.jump(self.name_list_offset + name_offset as usize));
+            let name_length = try!(name_reader.read_u8()) as usize;
+            Some(try!(slice(name_reader, 0, name_length)))
+        };
+
+        Ok(Resource {
+            id: id,
+            name: name,
+            attributes: attributes,
+            data: data,
+        })
+    }
+}
+
+// Looks for an outline font (size 0) with the given resource ID in a `FOND` resource's font
+// association table.
+fn find_association<'a>(fond: &Resource<'a>, sfnt_id: i16)
+                        -> Result<Option<FamilyAssociation<'a>>, Error> {
+    let mut reader = fond.data;
+    try!(reader.jump(mem::size_of::<i16>()));
+    let family_id = try!(reader.read_i16::<BigEndian>());
+
+    let mut reader = fond.data;
+    try!(reader.jump(FOND_FONT_ASSOCIATION_TABLE_OFFSET));
+    let association_count = try!(reader.read_u16::<BigEndian>()) as usize + 1;
+    for _ in 0..association_count {
+        let size = try!(reader.read_i16::<BigEndian>());
+        let style = try!(reader.read_i16::<BigEndian>());
+        let id = try!(reader.read_i16::<BigEndian>());
+        if size == 0 && id == sfnt_id {
+            return Ok(Some(FamilyAssociation {
+                family_id: family_id,
+                family_name: fond.name,
+                style: style,
+            }))
+        }
+    }
+
+    Ok(None)
+}
+
+fn slice(bytes: &[u8], offset: usize, length: usize) -> Result<&[u8], Error> {
+    match offset.checked_add(length) {
+        Some(end) if end <= bytes.len() => Ok(&bytes[offset..end]),
+        _ => Err(Error::Malformed),
+    }
+}
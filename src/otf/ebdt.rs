@@ -0,0 +1,130 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `EBDT` table, which holds monochrome and grayscale bitmap glyphs. The `EBLC` table, read
+//! with `CblcTable`, locates them.
+
+use byteorder::BigEndian;
+use error::Error;
+use otf::{EBDT, FontTable};
+use otf::cblc::{BitmapGlyphMetrics, BitmapLocation};
+use std::vec::Vec;
+use util::{Jump, ReadBytes};
+
+/// The bitmap data of a glyph.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EbdtGlyphData<'a> {
+    /// Rows of pixels at the strike's bit depth. Byte-aligned rows each start on a new byte;
+    /// otherwise rows follow one another with no padding.
+    Bitmap {
+        data: &'a [u8],
+        byte_aligned: bool,
+    },
+    /// Other glyphs of the same strike, drawn at offsets from the top left of this one.
+    Composite(Vec<EbdtComponent>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EbdtComponent {
+    pub glyph_id: u16,
+    pub x_offset: i8,
+    pub y_offset: i8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EbdtGlyph<'a> {
+    pub metrics: BitmapGlyphMetrics,
+    pub data: EbdtGlyphData<'a>,
+}
+
+#[derive(Clone, Copy)]
+pub struct EbdtTable<'a> {
+    pub table: FontTable<'a>,
+}
+
+impl<'a> EbdtTable<'a> {
+    pub fn new(table: FontTable) -> Result<EbdtTable, Error> {
+        let mut reader = table.bytes;
+        let major_version = try!(reader.read_u16::<BigEndian>());
+        let minor_version = try!(reader.read_u16::<BigEndian>());
+        if (major_version, minor_version) != (2, 0) {
+            return Err(Error::UnsupportedFormat {
+                table: EBDT,
+                format: ((major_version as u32) << 16) | minor_version as u32,
+            })
+        }
+
+        Ok(EbdtTable {
+            table: table,
+        })
+    }
+
+    /// Reads the glyph at a location found in the `EBLC` table.
+    ///
+    /// Image formats 1, 2, 5, 6, 7, 8, and 9 are supported. The obsolete format 3 and the
+    /// compressed format 4 aren't.
+    pub fn glyph(&self, location: &BitmapLocation) -> Result<EbdtGlyph<'a>, Error> {
+        let start = location.offset as usize;
+        let end = try!(start.checked_add(location.length as usize).ok_or(Error::Malformed));
+        if end > self.table.bytes.len() {
+            return Err(Error::Malformed)
+        }
+        let mut reader = &self.table.bytes[start..end];
+
+        let metrics = match location.image_format {
+            1 | 2 | 8 => try!(BitmapGlyphMetrics::read_small(&mut reader)),
+            5 => try!(location.metrics.ok_or(Error::Malformed)),
+            6 | 7 | 9 => try!(BitmapGlyphMetrics::read_big(&mut reader)),
+            _ => return Err(Error::UnsupportedFormat {
+                    table: EBDT,
+                    format: location.image_format as u32,
+                }),
+        };
+
+        let data = match location.image_format {
+            1 | 6 => {
+                EbdtGlyphData::Bitmap {
+                    data: reader,
+                    byte_aligned: true,
+                }
+            }
+            2 | 5 | 7 => {
+                EbdtGlyphData::Bitmap {
+                    data: reader,
+                    byte_aligned: false,
+                }
+            }
+            _ => {
+                // Format 8 pads its small metrics to an even length.
+                if location.image_format == 8 {
+                    try!(reader.jump(1));
+                }
+                let num_components = try!(reader.read_u16::<BigEndian>());
+                let mut components = Vec::with_capacity(num_components as usize);
+                for _ in 0..num_components {
+                    let glyph_id = try!(reader.read_u16::<BigEndian>());
+                    let x_offset = try!(reader.read_i8());
+                    let y_offset = try!(reader.read_i8());
+                    components.push(EbdtComponent {
+                        glyph_id: glyph_id,
+                        x_offset: x_offset,
+                        y_offset: y_offset,
+                    })
+                }
+                EbdtGlyphData::Composite(components)
+            }
+        };
+
+        Ok(EbdtGlyph {
+            metrics: metrics,
+            data: data,
+        })
+    }
+}
@@ -0,0 +1,76 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `EBSC` table, which names bitmap strikes to scale for sizes that have none of their own.
+
+use byteorder::BigEndian;
+use error::Error;
+use otf::{EBSC, FontTable};
+use std::mem;
+use std::vec::Vec;
+use util::{Jump, ReadBytes};
+
+const BITMAP_SCALE_RECORD_SIZE: usize = 28;
+
+/// Says that glyphs at one size should be drawn by scaling the bitmaps of another strike.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitmapScale {
+    pub ppem_x: u8,
+    pub ppem_y: u8,
+    pub substitute_ppem_x: u8,
+    pub substitute_ppem_y: u8,
+}
+
+#[derive(Clone, Copy)]
+pub struct EbscTable<'a> {
+    pub table: FontTable<'a>,
+    num_sizes: u32,
+}
+
+impl<'a> EbscTable<'a> {
+    pub fn new(table: FontTable) -> Result<EbscTable, Error> {
+        let mut reader = table.bytes;
+        let major_version = try!(reader.read_u16::<BigEndian>());
+        let minor_version = try!(reader.read_u16::<BigEndian>());
+        if (major_version, minor_version) != (2, 0) {
+            return Err(Error::UnsupportedFormat {
+                table: EBSC,
+                format: ((major_version as u32) << 16) | minor_version as u32,
+            })
+        }
+
+        let num_sizes = try!(reader.read_u32::<BigEndian>());
+        Ok(EbscTable {
+            table: table,
+            num_sizes: num_sizes,
+        })
+    }
+
+    pub fn scales(&self) -> Result<Vec<BitmapScale>, Error> {
+        let mut reader = self.table.bytes;
+        try!(reader.jump(mem::size_of::<u16>() * 2 + mem::size_of::<u32>()));
+
+        let mut scales = vec![];
+        for _ in 0..self.num_sizes {
+            let mut record_reader = reader;
+            try!(reader.jump(BITMAP_SCALE_RECORD_SIZE));
+
+            // Skip the horizontal and vertical line metrics.
+            try!(record_reader.jump(12 * 2));
+            scales.push(BitmapScale {
+                ppem_x: try!(record_reader.read_u8()),
+                ppem_y: try!(record_reader.read_u8()),
+                substitute_ppem_x: try!(record_reader.read_u8()),
+                substitute_ppem_y: try!(record_reader.read_u8()),
+            })
+        }
+        Ok(scales)
+    }
+}
@@ -0,0 +1,367 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An owned font that parses its table directory once and caches the tables it has parsed.
+//!
+//! `FontData` borrows its bytes and looks tables up anew on every call, which is fine for
+//! one-off queries. `Font` owns its bytes, so it can be stored and shared between threads, and
+//! remembers every table the first time it's asked for.
+
+use byteorder::{BigEndian, ReadBytesExt};
+use error::Error;
+use memmap::Mmap;
+use otf::cbdt::CbdtTable;
+use otf::cblc::CblcTable;
+use otf::cmap::CmapTable;
+use otf::collection::{FontCollection, TTCF};
+use otf::colr::ColrTable;
+use otf::cpal::CpalTable;
+use otf::cvt::CvtTable;
+use otf::ebdt::EbdtTable;
+use otf::ebsc::EbscTable;
+use otf::gasp::GaspTable;
+use otf::glyf::GlyfTable;
+use otf::gsub::GsubTable;
+use otf::head::HeadTable;
+use otf::hhea::HheaTable;
+use otf::hmtx::HmtxTable;
+use otf::loca::LocaTable;
+use otf::maxp::MaxpTable;
+use otf::sbix::SbixTable;
+use otf::svg::SvgTable;
+use otf::{self, FontData, FontTable, TableRecord, Tag};
+use std::slice;
+use std::sync::{Arc, RwLock};
+
+/// The storage behind a `Font`: a memory-mapped file, a `Vec<u8>`, or an `Arc<[u8]>`.
+pub struct FontBytes(Storage);
+
+enum Storage {
+    Mmap(Mmap),
+    Vec(Vec<u8>),
+    Shared(Arc<[u8]>),
+}
+
+impl FontBytes {
+    /// Uses a memory-mapped file as font storage.
+    ///
+    /// This is unsafe because the font reads the mapped memory directly. The file must not be
+    /// modified or truncated, by this process or any other, while the font is alive; otherwise
+    /// parsed tables may change underneath their borrowers, or reads may fault.
+    #[inline]
+    pub unsafe fn from_mmap(mmap: Mmap) -> FontBytes {
+        FontBytes(Storage::Mmap(mmap))
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        match self.0 {
+            Storage::Mmap(ref mmap) => unsafe { mmap.as_slice() },
+            Storage::Vec(ref bytes) => bytes,
+            Storage::Shared(ref bytes) => bytes,
+        }
+    }
+}
+
+impl From<Vec<u8>> for FontBytes {
+    #[inline]
+    fn from(bytes: Vec<u8>) -> FontBytes {
+        FontBytes(Storage::Vec(bytes))
+    }
+}
+
+impl From<Arc<[u8]>> for FontBytes {
+    #[inline]
+    fn from(bytes: Arc<[u8]>) -> FontBytes {
+        FontBytes(Storage::Shared(bytes))
+    }
+}
+
+/// A font face that owns its data.
+///
+/// Table accessors take `&self` and don't need other tables passed in, so a `Font` can be shared
+/// between rendering threads behind an `Arc`. Each table is parsed the first time it's asked for,
+/// and the result, including any error, is returned on every later call.
+pub struct Font {
+    // The cached tables borrow from `bytes`, so they must come first to be dropped first.
+    tables: TableCache,
+    records: Vec<TableRecord>,
+    header_offset: usize,
+    bytes: FontBytes,
+}
+
+#[derive(Default)]
+struct TableCache {
+    cbdt: Lazy<CbdtTable<'static>>,
+    cblc: Lazy<CblcTable<'static>>,
+    cmap: Lazy<CmapTable<'static>>,
+    colr: Lazy<ColrTable<'static>>,
+    cpal: Lazy<CpalTable<'static>>,
+    cvt: Lazy<CvtTable<'static>>,
+    ebdt: Lazy<EbdtTable<'static>>,
+    eblc: Lazy<CblcTable<'static>>,
+    ebsc: Lazy<EbscTable<'static>>,
+    fpgm: Lazy<FontTable<'static>>,
+    gasp: Lazy<GaspTable<'static>>,
+    glyf: Lazy<GlyfTable<'static>>,
+    gsub: Lazy<GsubTable<'static>>,
+    head: Lazy<HeadTable>,
+    hhea: Lazy<HheaTable>,
+    hmtx: Lazy<HmtxTable<'static>>,
+    loca: Lazy<LocaTable<'static>>,
+    maxp: Lazy<MaxpTable>,
+    prep: Lazy<FontTable<'static>>,
+    sbix: Lazy<SbixTable<'static>>,
+    svg: Lazy<SvgTable<'static>>,
+    vhea: Lazy<HheaTable>,
+    vmtx: Lazy<HmtxTable<'static>>,
+}
+
+// A parsed table, filled in on first use. Two threads may race to parse the same table; both get
+// the same result, and one of them is kept.
+struct Lazy<T>(RwLock<Option<Result<T, Error>>>);
+
+impl<T> Default for Lazy<T> {
+    #[inline]
+    fn default() -> Lazy<T> {
+        Lazy(RwLock::new(None))
+    }
+}
+
+impl<T> Lazy<T> where T: Copy {
+    fn get<F>(&self, load: F) -> Result<T, Error> where F: FnOnce() -> Result<T, Error> {
+        if let Some(result) = *self.0.read().unwrap() {
+            return result
+        }
+        let result = load();
+        *self.0.write().unwrap() = Some(result);
+        result
+    }
+}
+
+impl Font {
+    /// Creates a font from an sfnt file, or from the first face of a TrueType collection.
+    #[inline]
+    pub fn new<B>(bytes: B) -> Result<Font, Error> where B: Into<FontBytes> {
+        Font::with_face_index(bytes, 0)
+    }
+
+    /// Creates a font from face `index` of a TrueType collection. Bare sfnt files have a single
+    /// face, with index 0.
+    pub fn with_face_index<B>(bytes: B, index: u32) -> Result<Font, Error>
+                              where B: Into<FontBytes> {
+        let bytes = bytes.into();
+        let (header_offset, records) = {
+            let data = bytes.as_slice();
+            let header_offset = if (&data[..]).read_u32::<BigEndian>().ok() == Some(TTCF) {
+                try!(try!(FontCollection::new(data)).face(index)).header_offset
+            } else if index == 0 {
+                0
+            } else {
+                return Err(Error::InvalidArgument)
+            };
+            let font_data = FontData::with_header_offset(data, header_offset);
+            let mut records: Vec<_> = try!(font_data.table_records()).collect();
+
+            // The directory is supposed to be sorted already, but sort it anyway so that a font
+            // that isn't can't hide tables from the binary search.
+            records.sort_by_key(|record| record.tag);
+            (header_offset, records)
+        };
+
+        Ok(Font {
+            tables: TableCache::default(),
+            records: records,
+            header_offset: header_offset,
+            bytes: bytes,
+        })
+    }
+
+    /// Returns all of the font's data. For faces of a collection, this is the whole collection.
+    #[inline]
+    pub fn bytes(&self) -> &[u8] {
+        self.bytes.as_slice()
+    }
+
+    /// Returns a borrowed view of this font, for use with APIs that take `FontData`.
+    #[inline]
+    pub fn font_data(&self) -> FontData {
+        FontData::with_header_offset(self.bytes(), self.header_offset)
+    }
+
+    // Returns the font's bytes with a lifetime that isn't tied to `self`, so that parsed tables
+    // can be cached alongside them.
+    //
+    // This is sound because the bytes never move or change while the font is alive: a `Vec` or
+    // `Arc` keeps its contents in place when moved, a mapping doesn't move at all, and `bytes` is
+    // never reassigned. The cached tables are dropped before the bytes, and accessors hand them
+    // out only with the lifetime of `&self`.
+    #[inline]
+    fn static_bytes(&self) -> &'static [u8] {
+        let bytes = self.bytes.as_slice();
+        unsafe { slice::from_raw_parts(bytes.as_ptr(), bytes.len()) }
+    }
+
+    /// Returns the records of the table directory, sorted by tag.
+    #[inline]
+    pub fn table_records(&self) -> &[TableRecord] {
+        &self.records
+    }
+
+    /// Returns the raw data of the table with the given tag, or `None` if the font doesn't have
+    /// one.
+    pub fn table(&self, tag: Tag) -> Result<Option<FontTable>, Error> {
+        match self.records.binary_search_by(|record| record.tag.cmp(&tag)) {
+            Ok(index) => self.font_data().table_for_record(&self.records[index]).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn load<T, F>(&self, table_id: u32, parse: F) -> Result<T, Error>
+                  where F: FnOnce(FontTable<'static>) -> Result<T, Error> {
+        let font_data = FontData::with_header_offset(self.static_bytes(), self.header_offset);
+        let table = match self.records.binary_search_by(|record| record.tag.0.cmp(&table_id)) {
+            Ok(index) => Some(try!(font_data.table_for_record(&self.records[index]))),
+            Err(_) => None,
+        };
+        otf::parse_table(table_id, table, parse)
+    }
+
+    #[inline]
+    pub fn cbdt_table(&self) -> Result<CbdtTable, Error> {
+        self.tables.cbdt.get(|| self.load(otf::CBDT, CbdtTable::new))
+    }
+
+    #[inline]
+    pub fn cblc_table(&self) -> Result<CblcTable, Error> {
+        self.tables.cblc.get(|| self.load(otf::CBLC, CblcTable::new))
+    }
+
+    #[inline]
+    pub fn cmap_table(&self) -> Result<CmapTable, Error> {
+        self.tables.cmap.get(|| self.load(otf::CMAP, |table| Ok(CmapTable::new(table))))
+    }
+
+    #[inline]
+    pub fn colr_table(&self) -> Result<ColrTable, Error> {
+        self.tables.colr.get(|| self.load(otf::COLR, ColrTable::new))
+    }
+
+    #[inline]
+    pub fn cpal_table(&self) -> Result<CpalTable, Error> {
+        self.tables.cpal.get(|| self.load(otf::CPAL, CpalTable::new))
+    }
+
+    #[inline]
+    pub fn cvt_table(&self) -> Result<CvtTable, Error> {
+        self.tables.cvt.get(|| self.load(otf::CVT, |table| Ok(CvtTable::new(table))))
+    }
+
+    #[inline]
+    pub fn ebdt_table(&self) -> Result<EbdtTable, Error> {
+        self.tables.ebdt.get(|| self.load(otf::EBDT, EbdtTable::new))
+    }
+
+    #[inline]
+    pub fn eblc_table(&self) -> Result<CblcTable, Error> {
+        self.tables.eblc.get(|| self.load(otf::EBLC, CblcTable::new))
+    }
+
+    #[inline]
+    pub fn ebsc_table(&self) -> Result<EbscTable, Error> {
+        self.tables.ebsc.get(|| self.load(otf::EBSC, EbscTable::new))
+    }
+
+    #[inline]
+    pub fn fpgm_table(&self) -> Result<FontTable, Error> {
+        self.tables.fpgm.get(|| self.load(otf::FPGM, Ok))
+    }
+
+    #[inline]
+    pub fn gasp_table(&self) -> Result<GaspTable, Error> {
+        self.tables.gasp.get(|| self.load(otf::GASP, GaspTable::new))
+    }
+
+    #[inline]
+    pub fn glyf_table(&self) -> Result<GlyfTable, Error> {
+        self.tables.glyf.get(|| self.load(otf::GLYF, |table| Ok(GlyfTable::new(table))))
+    }
+
+    #[inline]
+    pub fn gsub_table(&self) -> Result<GsubTable, Error> {
+        self.tables.gsub.get(|| self.load(otf::GSUB, GsubTable::new))
+    }
+
+    #[inline]
+    pub fn head_table(&self) -> Result<HeadTable, Error> {
+        self.tables.head.get(|| self.load(otf::HEAD, HeadTable::new))
+    }
+
+    #[inline]
+    pub fn hhea_table(&self) -> Result<HheaTable, Error> {
+        self.tables.hhea.get(|| self.load(otf::HHEA, HheaTable::new))
+    }
+
+    /// Returns the `hmtx` table, reading the number of metrics from `hhea`.
+    #[inline]
+    pub fn hmtx_table(&self) -> Result<HmtxTable, Error> {
+        self.tables.hmtx.get(|| {
+            let hhea_table = try!(self.hhea_table());
+            self.load(otf::HMTX, |table| HmtxTable::new(table, &hhea_table))
+        })
+    }
+
+    /// Returns the `loca` table, reading its format from `head`.
+    #[inline]
+    pub fn loca_table(&self) -> Result<LocaTable, Error> {
+        self.tables.loca.get(|| {
+            let head_table = try!(self.head_table());
+            self.load(otf::LOCA, |table| LocaTable::new(table, &head_table))
+        })
+    }
+
+    #[inline]
+    pub fn maxp_table(&self) -> Result<MaxpTable, Error> {
+        self.tables.maxp.get(|| self.load(otf::MAXP, MaxpTable::new))
+    }
+
+    #[inline]
+    pub fn prep_table(&self) -> Result<FontTable, Error> {
+        self.tables.prep.get(|| self.load(otf::PREP, Ok))
+    }
+
+    /// Returns the `sbix` table, reading the number of glyphs from `maxp`.
+    #[inline]
+    pub fn sbix_table(&self) -> Result<SbixTable, Error> {
+        self.tables.sbix.get(|| {
+            let maxp_table = try!(self.maxp_table());
+            self.load(otf::SBIX, |table| SbixTable::new(table, &maxp_table))
+        })
+    }
+
+    #[inline]
+    pub fn svg_table(&self) -> Result<SvgTable, Error> {
+        self.tables.svg.get(|| self.load(otf::SVG, SvgTable::new))
+    }
+
+    #[inline]
+    pub fn vhea_table(&self) -> Result<HheaTable, Error> {
+        self.tables.vhea.get(|| self.load(otf::VHEA, HheaTable::new))
+    }
+
+    /// Returns the `vmtx` table, reading the number of metrics from `vhea`.
+    #[inline]
+    pub fn vmtx_table(&self) -> Result<HmtxTable, Error> {
+        self.tables.vmtx.get(|| {
+            let vhea_table = try!(self.vhea_table());
+            self.load(otf::VMTX, |table| HmtxTable::new(table, &vhea_table))
+        })
+    }
+}
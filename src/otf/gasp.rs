@@ -0,0 +1,111 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `gasp` table, which says how glyphs should be grid-fitted and antialiased at each size.
+
+use binary::{FromData, LazyArray, Reader};
+use error::Error;
+use otf::{GASP, FontTable};
+use std::vec::Vec;
+
+bitflags! {
+    pub flags GaspBehavior: u16 {
+        /// Grid-fit the outlines.
+        const GRIDFIT = 1 << 0,
+        /// Antialias with grayscale.
+        const DOGRAY = 1 << 1,
+        /// Grid-fit as ClearType-style renderers do, mostly in the vertical direction. Version 1
+        /// only.
+        const SYMMETRIC_GRIDFIT = 1 << 2,
+        /// Antialias in the vertical direction as well as the horizontal one when rendering with
+        /// ClearType-style subpixel antialiasing. Version 1 only.
+        const SYMMETRIC_SMOOTHING = 1 << 3,
+    }
+}
+
+impl FromData for GaspBehavior {
+    const SIZE: usize = 2;
+
+    #[inline]
+    fn parse(data: &[u8]) -> GaspBehavior {
+        GaspBehavior::from_bits_truncate(u16::parse(data))
+    }
+}
+
+binary_record! {
+    /// The behavior for a range of sizes, from just past the previous range's maximum up to and
+    /// including `max_pixels_per_em`.
+    #[derive(PartialEq, Eq)]
+    pub struct GaspRange {
+        pub max_pixels_per_em: u16,
+        pub behavior: GaspBehavior,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GaspTable<'a> {
+    pub table: FontTable<'a>,
+    pub version: u16,
+    ranges: LazyArray<'a, GaspRange>,
+}
+
+impl<'a> GaspTable<'a> {
+    pub fn new(table: FontTable) -> Result<GaspTable, Error> {
+        let mut reader = Reader::new(table.bytes);
+        let version = try!(reader.read::<u16>());
+        if version > 1 {
+            return Err(Error::UnsupportedFormat {
+                table: GASP,
+                format: version as u32,
+            })
+        }
+        let num_ranges = try!(reader.read::<u16>());
+        let ranges = try!(reader.read_array(num_ranges as usize));
+        Ok(GaspTable {
+            table: table,
+            version: version,
+            ranges: ranges,
+        })
+    }
+
+    /// Returns the ranges in order of increasing size.
+    ///
+    /// Version 0 tables can't use the symmetric flags, so those are cleared.
+    pub fn ranges(&self) -> Vec<GaspRange> {
+        let mask = self.behavior_mask();
+        self.ranges.iter().map(|range| {
+            GaspRange {
+                max_pixels_per_em: range.max_pixels_per_em,
+                behavior: range.behavior & mask,
+            }
+        }).collect()
+    }
+
+    /// Returns the behavior for the given size, or `None` if the table has no ranges.
+    ///
+    /// Well-formed tables end with a range covering every size up to 0xffff. In tables that don't,
+    /// sizes past the last range use it anyway, as FreeType does.
+    pub fn behavior(&self, pixels_per_em: u16) -> Option<GaspBehavior> {
+        let mask = self.behavior_mask();
+        self.ranges
+            .iter()
+            .find(|range| pixels_per_em <= range.max_pixels_per_em)
+            .or_else(|| self.ranges.len().checked_sub(1).and_then(|last| self.ranges.get(last)))
+            .map(|range| range.behavior & mask)
+    }
+
+    fn behavior_mask(&self) -> GaspBehavior {
+        if self.version == 0 {
+            GRIDFIT | DOGRAY
+        } else {
+            GaspBehavior::all()
+        }
+    }
+}
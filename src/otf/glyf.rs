@@ -8,12 +8,17 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::BigEndian;
+use error::Error;
 use euclid::{Point2D, Rect, Size2D};
+use limits::{Limit, Limits};
 use otf::FontTable;
 use otf::loca::LocaTable;
+use outline::{PathCommand, Transform};
+use std::i16;
 use std::mem;
-use util::Jump;
+use std::vec::Vec;
+use util::{Jump, ReadBytes};
 
 bitflags! {
     flags Flags: u8 {
@@ -26,17 +31,76 @@ bitflags! {
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+bitflags! {
+    flags ComponentFlags: u16 {
+        const ARG_1_AND_2_ARE_WORDS = 1 << 0,
+        const ARGS_ARE_XY_VALUES = 1 << 1,
+        const ROUND_XY_TO_GRID = 1 << 2,
+        const WE_HAVE_A_SCALE = 1 << 3,
+        const MORE_COMPONENTS = 1 << 5,
+        const WE_HAVE_AN_X_AND_Y_SCALE = 1 << 6,
+        const WE_HAVE_A_TWO_BY_TWO = 1 << 7,
+        const WE_HAVE_INSTRUCTIONS = 1 << 8,
+        const USE_MY_METRICS = 1 << 9,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Point {
     pub position: Point2D<i16>,
     pub on_curve: bool,
     pub first_point_in_contour: bool,
 }
 
+/// The raw data of a simple glyph, as the TrueType hinting interpreter sees it.
+#[derive(Clone, Debug)]
+pub struct SimpleGlyph<'a> {
+    /// The points in font units. Unlike `for_each_point`, this doesn't insert the implied
+    /// on-curve points between consecutive off-curve points or repeat the start of each contour.
+    pub points: Vec<Point>,
+    /// The index of the last point of each contour.
+    pub contour_end_indices: Vec<u16>,
+    /// The glyph program.
+    pub instructions: &'a [u8],
+    pub bounds: Rect<i16>,
+}
+
+/// The raw data of a composite glyph, as the TrueType hinting interpreter sees it.
+#[derive(Clone, Debug)]
+pub struct CompositeGlyph<'a> {
+    pub components: Vec<ComponentPlacement>,
+    /// The glyph program, which runs after the components have been hinted and put in place.
+    pub instructions: &'a [u8],
+    pub bounds: Rect<i16>,
+}
+
+/// Where a composite glyph puts one of its components.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComponentPlacement {
+    pub glyph_id: u16,
+    /// Maps the component's points into the composite glyph, in font units.
+    pub transform: Transform,
+    /// Whether a hinter should round the offset of the component to the pixel grid.
+    pub round_offset_to_grid: bool,
+    /// Whether the composite glyph takes its metrics from this component.
+    pub use_my_metrics: bool,
+}
+
+/// A reference from a composite glyph to one of its components.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Component {
+    pub glyph_id: u16,
+    /// Where the component's glyph ID is stored, relative to the start of the composite glyph's
+    /// data. Subsetters rewrite it there when renumbering glyphs.
+    pub glyph_id_offset: usize,
+}
+
 /// TODO(pcwalton): Add some caching so we don't keep going to the `loca` table all the time.
 #[derive(Clone, Copy, Debug)]
 pub struct GlyfTable<'a> {
     pub table: FontTable<'a>,
+    /// Glyphs that need more than these fail with `Error::LimitExceeded`.
+    pub limits: Limits,
 }
 
 impl<'a> GlyfTable<'a> {
@@ -44,29 +108,59 @@ impl<'a> GlyfTable<'a> {
     pub fn new(table: FontTable) -> GlyfTable {
         GlyfTable {
             table: table,
+            limits: Limits::default(),
         }
     }
 
+    /// Calls `callback` for each point of the glyph's outline, inserting the implied on-curve
+    /// points between consecutive off-curve points and repeating the start of each contour to
+    /// close it. The components of composite glyphs are transformed into place.
     pub fn for_each_point<F>(&self, loca_table: &LocaTable, glyph_id: u32, mut callback: F)
-                             -> Result<(), ()> where F: FnMut(&Point) {
-        let mut reader = self.table.bytes;
-        let offset = try!(loca_table.location_of(glyph_id));
-        try!(reader.jump(offset as usize));
+                             -> Result<(), Error> where F: FnMut(&Point) {
+        let mut usage = Usage::default();
+        self.for_each_point_in_glyph(loca_table, glyph_id, None, 0, &mut usage, &mut callback)
+    }
 
-        let number_of_contours = try!(reader.read_i16::<BigEndian>().map_err(drop));
+    // Does the work of `for_each_point()` for a glyph that may be a component, at the given
+    // depth, of a composite glyph. `usage` accumulates what the outermost glyph has used so far.
+    fn for_each_point_in_glyph<F>(&self,
+                                  loca_table: &LocaTable,
+                                  glyph_id: u32,
+                                  transform: Option<&Transform>,
+                                  depth: u32,
+                                  usage: &mut Usage,
+                                  callback: &mut F)
+                                  -> Result<(), Error> where F: FnMut(&Point) {
+        let mut reader = match try!(self.glyph_data(loca_table, glyph_id)) {
+            Some(data) => data,
+            None => return Ok(()),
+        };
+        let number_of_contours = try!(reader.read_i16::<BigEndian>());
+        try!(reader.jump(mem::size_of::<i16>() * 4));
         if number_of_contours < 0 {
-            // TODO(pcwalton): Composite glyphs.
-            return Err(())
+            return self.for_each_point_in_composite(loca_table,
+                                                    reader,
+                                                    transform,
+                                                    depth,
+                                                    usage,
+                                                    callback)
         }
-        try!(reader.jump(mem::size_of::<i16>() * 4));
+        if number_of_contours == 0 {
+            return Ok(())
+        }
+        usage.contours += number_of_contours as u32;
+        try!(self.limits.check(Limit::ContoursPerGlyph, usage.contours));
 
         // Find out how many points we have.
         let mut endpoints_reader = reader;
         try!(reader.jump(mem::size_of::<u16>() as usize * (number_of_contours as usize - 1)));
-        let number_of_points = try!(reader.read_u16::<BigEndian>().map_err(drop)) + 1;
+        let last_endpoint = try!(reader.read_u16::<BigEndian>());
+        let number_of_points = try!(last_endpoint.checked_add(1).ok_or(Error::Malformed));
+        usage.points += number_of_points as u32;
+        try!(self.limits.check(Limit::PointsPerGlyph, usage.points));
 
         // Skip over hinting instructions.
-        let instruction_length = try!(reader.read_u16::<BigEndian>().map_err(drop));
+        let instruction_length = try!(reader.read_u16::<BigEndian>());
         try!(reader.jump(instruction_length as usize));
 
         // Find the offsets of the X and Y coordinates.
@@ -77,14 +171,30 @@ impl<'a> GlyfTable<'a> {
         // Set up the streams.
         let mut flag_parser = try!(FlagParser::new(flags_reader));
         let mut x_coordinate_reader = reader;
-        try!(reader.jump(x_coordinate_length as usize));
+        try!(reader.jump(x_coordinate_length));
         let mut y_coordinate_reader = reader;
 
+        // Components are positioned by their transforms.
+        let mut emit = |point: Point| {
+            match transform {
+                None => callback(&point),
+                Some(transform) => {
+                    callback(&Point {
+                        position: transform_position(transform, &point.position),
+                        ..point
+                    })
+                }
+            }
+        };
+
         // Now parse the contours.
-        let (mut position, mut point_index) = (Point2D::new(0, 0), 0);
+        let (mut position, mut point_index) = (Point2D::new(0i16, 0i16), 0u32);
         for _ in 0..number_of_contours {
-            let contour_point_count =
-                try!(endpoints_reader.read_u16::<BigEndian>().map_err(drop)) - point_index + 1;
+            let end_point_index = try!(endpoints_reader.read_u16::<BigEndian>()) as u32;
+            if end_point_index < point_index {
+                return Err(Error::Malformed)
+            }
+            let contour_point_count = end_point_index - point_index + 1;
             let (mut starting_point, mut last_point_was_off_curve) = (Point2D::new(0, 0), false);
             for contour_point_index in 0..contour_point_count {
                 let flags = Flags::from_bits_truncate(*flag_parser.current);
@@ -92,38 +202,40 @@ impl<'a> GlyfTable<'a> {
 
                 let mut delta = Point2D::new(0, 0);
                 if flags.contains(X_SHORT_VECTOR) {
-                    delta.x = try!(x_coordinate_reader.read_u8().map_err(drop)) as i16;
+                    delta.x = try!(x_coordinate_reader.read_u8()) as i16;
                     if !flags.contains(THIS_X_IS_SAME) {
                         delta.x = -delta.x
                     }
                 } else if !flags.contains(THIS_X_IS_SAME) {
-                    delta.x = try!(x_coordinate_reader.read_i16::<BigEndian>().map_err(drop))
+                    delta.x = try!(x_coordinate_reader.read_i16::<BigEndian>())
                 }
                 if flags.contains(Y_SHORT_VECTOR) {
-                    delta.y = try!(y_coordinate_reader.read_u8().map_err(drop)) as i16;
+                    delta.y = try!(y_coordinate_reader.read_u8()) as i16;
                     if !flags.contains(THIS_Y_IS_SAME) {
                         delta.y = -delta.y
                     }
                 } else if !flags.contains(THIS_Y_IS_SAME) {
-                    delta.y = try!(y_coordinate_reader.read_i16::<BigEndian>().map_err(drop))
+                    delta.y = try!(y_coordinate_reader.read_i16::<BigEndian>())
                 }
 
                 if last_point_was_off_curve && !flags.contains(ON_CURVE) {
-                    callback(&Point {
-                        position: position + delta / 2,
+                    emit(Point {
+                        position: Point2D::new(position.x.wrapping_add(delta.x / 2),
+                                               position.y.wrapping_add(delta.y / 2)),
                         on_curve: true,
                         first_point_in_contour: false,
                     })
                 }
 
-                position = position + delta;
+                position = Point2D::new(position.x.wrapping_add(delta.x),
+                                        position.y.wrapping_add(delta.y));
 
                 let first_point_in_contour = contour_point_index == 0;
                 if first_point_in_contour {
                     starting_point = position
                 }
 
-                callback(&Point {
+                emit(Point {
                     position: position,
                     on_curve: flags.contains(ON_CURVE),
                     first_point_in_contour: first_point_in_contour,
@@ -134,7 +246,7 @@ impl<'a> GlyfTable<'a> {
             }
 
             // Close the path.
-            callback(&Point {
+            emit(Point {
                 position: starting_point,
                 on_curve: true,
                 first_point_in_contour: false,
@@ -144,32 +256,379 @@ impl<'a> GlyfTable<'a> {
         Ok(())
     }
 
-    pub fn bounding_rect(&self, loca_table: &LocaTable, glyph_id: u32) -> Result<Rect<i16>, ()> {
+    // Visits the points of each component of a composite glyph. `reader` points to the first
+    // component record.
+    fn for_each_point_in_composite<F>(&self,
+                                      loca_table: &LocaTable,
+                                      mut reader: &[u8],
+                                      transform: Option<&Transform>,
+                                      depth: u32,
+                                      usage: &mut Usage,
+                                      callback: &mut F)
+                                      -> Result<(), Error> where F: FnMut(&Point) {
+        try!(self.limits.check(Limit::CompositeDepth, depth + 1));
+        loop {
+            let (component, flags) = try!(read_component(&mut reader));
+            usage.components += 1;
+            try!(self.limits.check(Limit::ComponentsPerGlyph, usage.components));
+
+            let component_transform = match transform {
+                None => component.transform,
+                Some(transform) => transform.pre_transform(&component.transform),
+            };
+            try!(self.for_each_point_in_glyph(loca_table,
+                                              component.glyph_id as u32,
+                                              Some(&component_transform),
+                                              depth + 1,
+                                              usage,
+                                              callback));
+
+            if !flags.contains(MORE_COMPONENTS) {
+                return Ok(())
+            }
+        }
+    }
+
+    /// Returns the outline of a glyph as path commands, in font units. Glyphs with no outline
+    /// produce no commands.
+    pub fn path_commands(&self, loca_table: &LocaTable, glyph_id: u32)
+                         -> Result<Vec<PathCommand>, Error> {
+        if try!(loca_table.location_of(glyph_id)) ==
+                try!(loca_table.location_of(glyph_id + 1)) {
+            return Ok(vec![])
+        }
+
+        let mut commands = vec![];
+        let mut control_point = None;
+        try!(self.for_each_point(loca_table, glyph_id, |point| {
+            let position = Point2D::new(point.position.x as f32, point.position.y as f32);
+            if point.first_point_in_contour {
+                if !commands.is_empty() {
+                    commands.push(PathCommand::ClosePath)
+                }
+                commands.push(PathCommand::MoveTo(position));
+                control_point = None
+            } else if !point.on_curve {
+                control_point = Some(position)
+            } else {
+                match control_point.take() {
+                    Some(control_point) => {
+                        commands.push(PathCommand::QuadTo(control_point, position))
+                    }
+                    None => commands.push(PathCommand::LineTo(position)),
+                }
+            }
+        }));
+        if !commands.is_empty() {
+            commands.push(PathCommand::ClosePath)
+        }
+        Ok(commands)
+    }
+
+    /// Returns the points and instructions of a simple glyph, or `None` if the glyph has no
+    /// outline. Composite glyphs fail with `Error::Malformed`; see `composite_glyph()`.
+    pub fn simple_glyph(&self, loca_table: &LocaTable, glyph_id: u32)
+                        -> Result<Option<SimpleGlyph<'a>>, Error> {
+        let mut reader = match try!(self.glyph_data(loca_table, glyph_id)) {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        let number_of_contours = try!(reader.read_i16::<BigEndian>());
+        if number_of_contours < 0 {
+            return Err(Error::Malformed)
+        }
+        let bounds = try!(read_bounds(&mut reader));
+
+        try!(self.limits.check(Limit::ContoursPerGlyph, number_of_contours as u32));
+        let mut contour_end_indices = Vec::with_capacity(number_of_contours as usize);
+        for _ in 0..number_of_contours {
+            let end_index = try!(reader.read_u16::<BigEndian>());
+            if contour_end_indices.last().map_or(false, |&last| end_index <= last) {
+                return Err(Error::Malformed)
+            }
+            contour_end_indices.push(end_index)
+        }
+
+        let instruction_length = try!(reader.read_u16::<BigEndian>()) as usize;
+        if reader.len() < instruction_length {
+            return Err(Error::Malformed)
+        }
+        let instructions = &reader[..instruction_length];
+        try!(reader.jump(instruction_length));
+
+        let number_of_points = match contour_end_indices.last() {
+            Some(&end_index) => try!(end_index.checked_add(1).ok_or(Error::Malformed)),
+            None => {
+                return Ok(Some(SimpleGlyph {
+                    points: vec![],
+                    contour_end_indices: contour_end_indices,
+                    instructions: instructions,
+                    bounds: bounds,
+                }))
+            }
+        };
+        try!(self.limits.check(Limit::PointsPerGlyph, number_of_points as u32));
+
+        let flags_reader = reader;
+        let x_coordinate_length = try!(calculate_size_of_x_coordinates(&mut reader,
+                                                                       number_of_points));
+        let mut flag_parser = try!(FlagParser::new(flags_reader));
+        let mut x_coordinate_reader = reader;
+        try!(reader.jump(x_coordinate_length));
+        let mut y_coordinate_reader = reader;
+
+        let mut points = Vec::with_capacity(number_of_points as usize);
+        let mut position = Point2D::new(0i16, 0i16);
+        let mut contour_start = 0;
+        for point_index in 0..number_of_points {
+            let flags = Flags::from_bits_truncate(*flag_parser.current);
+            if point_index + 1 < number_of_points {
+                try!(flag_parser.next());
+            }
+
+            if flags.contains(X_SHORT_VECTOR) {
+                let delta = try!(x_coordinate_reader.read_u8()) as i16;
+                position.x = if flags.contains(THIS_X_IS_SAME) {
+                    position.x.wrapping_add(delta)
+                } else {
+                    position.x.wrapping_sub(delta)
+                }
+            } else if !flags.contains(THIS_X_IS_SAME) {
+                let delta = try!(x_coordinate_reader.read_i16::<BigEndian>());
+                position.x = position.x.wrapping_add(delta)
+            }
+            if flags.contains(Y_SHORT_VECTOR) {
+                let delta = try!(y_coordinate_reader.read_u8()) as i16;
+                position.y = if flags.contains(THIS_Y_IS_SAME) {
+                    position.y.wrapping_add(delta)
+                } else {
+                    position.y.wrapping_sub(delta)
+                }
+            } else if !flags.contains(THIS_Y_IS_SAME) {
+                let delta = try!(y_coordinate_reader.read_i16::<BigEndian>());
+                position.y = position.y.wrapping_add(delta)
+            }
+
+            points.push(Point {
+                position: position,
+                on_curve: flags.contains(ON_CURVE),
+                first_point_in_contour: point_index == contour_start,
+            });
+            if contour_end_indices.contains(&point_index) {
+                contour_start = point_index + 1
+            }
+        }
+
+        Ok(Some(SimpleGlyph {
+            points: points,
+            contour_end_indices: contour_end_indices,
+            instructions: instructions,
+            bounds: bounds,
+        }))
+    }
+
+    /// Returns the components and instructions of a composite glyph, or `None` if the glyph has no
+    /// outline. Simple glyphs fail with `Error::Malformed`.
+    pub fn composite_glyph(&self, loca_table: &LocaTable, glyph_id: u32)
+                           -> Result<Option<CompositeGlyph<'a>>, Error> {
+        let mut reader = match try!(self.glyph_data(loca_table, glyph_id)) {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        if try!(reader.read_i16::<BigEndian>()) >= 0 {
+            return Err(Error::Malformed)
+        }
+        let bounds = try!(read_bounds(&mut reader));
+
+        let mut components = vec![];
+        loop {
+            let (component, flags) = try!(read_component(&mut reader));
+            components.push(component);
+            try!(self.limits.check(Limit::ComponentsPerGlyph, components.len() as u32));
+            if flags.contains(MORE_COMPONENTS) {
+                continue
+            }
+
+            // The instructions follow the last component, whose flags say whether there are any.
+            let instructions = if flags.contains(WE_HAVE_INSTRUCTIONS) {
+                let instruction_length = try!(reader.read_u16::<BigEndian>()) as usize;
+                if reader.len() < instruction_length {
+                    return Err(Error::Malformed)
+                }
+                &reader[..instruction_length]
+            } else {
+                &[]
+            };
+            return Ok(Some(CompositeGlyph {
+                components: components,
+                instructions: instructions,
+                bounds: bounds,
+            }))
+        }
+    }
+
+    /// Returns true if the glyph is a composite glyph. Glyphs with no outline aren't.
+    pub fn is_composite(&self, loca_table: &LocaTable, glyph_id: u32) -> Result<bool, Error> {
+        match try!(self.glyph_data(loca_table, glyph_id)) {
+            Some(mut reader) => Ok(try!(reader.read_i16::<BigEndian>()) < 0),
+            None => Ok(false),
+        }
+    }
+
+    /// Returns the components of a composite glyph, in order. Simple glyphs have none.
+    pub fn components(&self, loca_table: &LocaTable, glyph_id: u32)
+                      -> Result<Vec<Component>, Error> {
+        let data = match try!(self.glyph_data(loca_table, glyph_id)) {
+            Some(data) => data,
+            None => return Ok(vec![]),
+        };
+        let mut reader = data;
+        if try!(reader.read_i16::<BigEndian>()) >= 0 {
+            return Ok(vec![])
+        }
+        try!(reader.jump(mem::size_of::<i16>() * 4));
+
+        let mut components = vec![];
+        loop {
+            let flags = ComponentFlags::from_bits_truncate(try!(reader.read_u16::<BigEndian>()));
+            let glyph_id_offset = data.len() - reader.len();
+            components.push(Component {
+                glyph_id: try!(reader.read_u16::<BigEndian>()),
+                glyph_id_offset: glyph_id_offset,
+            });
+            try!(self.limits.check(Limit::ComponentsPerGlyph, components.len() as u32));
+
+            let argument_size = if flags.contains(ARG_1_AND_2_ARE_WORDS) { 4 } else { 2 };
+            let transform_size = if flags.contains(WE_HAVE_A_SCALE) {
+                2
+            } else if flags.contains(WE_HAVE_AN_X_AND_Y_SCALE) {
+                4
+            } else if flags.contains(WE_HAVE_A_TWO_BY_TWO) {
+                8
+            } else {
+                0
+            };
+            try!(reader.jump(argument_size + transform_size));
+
+            if !flags.contains(MORE_COMPONENTS) {
+                return Ok(components)
+            }
+        }
+    }
+
+    /// Returns the data of the given glyph, or `None` if the glyph has no outline.
+    pub fn glyph_data(&self, loca_table: &LocaTable, glyph_id: u32)
+                      -> Result<Option<&'a [u8]>, Error> {
+        let start = try!(loca_table.location_of(glyph_id)) as usize;
+        let end = try!(loca_table.location_of(glyph_id + 1)) as usize;
+        if start > end || end > self.table.bytes.len() {
+            return Err(Error::Malformed)
+        }
+        if start == end {
+            return Ok(None)
+        }
+        Ok(Some(&self.table.bytes[start..end]))
+    }
+
+    pub fn bounding_rect(&self, loca_table: &LocaTable, glyph_id: u32) -> Result<Rect<i16>, Error> {
         let mut reader = self.table.bytes;
         let offset = try!(loca_table.location_of(glyph_id));
         try!(reader.jump(offset as usize));
 
-        let number_of_contours = try!(reader.read_i16::<BigEndian>().map_err(drop));
-        let x_min = try!(reader.read_i16::<BigEndian>().map_err(drop));
-        let y_min = try!(reader.read_i16::<BigEndian>().map_err(drop));
-        let x_max = try!(reader.read_i16::<BigEndian>().map_err(drop));
-        let y_max = try!(reader.read_i16::<BigEndian>().map_err(drop));
-        Ok(Rect::new(Point2D::new(x_min, y_min), Size2D::new(x_max - x_min, y_max - y_min)))
+        let number_of_contours = try!(reader.read_i16::<BigEndian>());
+        let x_min = try!(reader.read_i16::<BigEndian>());
+        let y_min = try!(reader.read_i16::<BigEndian>());
+        let x_max = try!(reader.read_i16::<BigEndian>());
+        let y_max = try!(reader.read_i16::<BigEndian>());
+        Ok(Rect::new(Point2D::new(x_min, y_min),
+                     Size2D::new(x_max.wrapping_sub(x_min), y_max.wrapping_sub(y_min))))
+    }
+}
+
+// The resources that a glyph has used so far, including all of its components.
+#[derive(Clone, Copy, Default)]
+struct Usage {
+    points: u32,
+    contours: u32,
+    components: u32,
+}
+
+// Reads the bounding box in a glyph header.
+fn read_bounds(reader: &mut &[u8]) -> Result<Rect<i16>, Error> {
+    let x_min = try!(reader.read_i16::<BigEndian>());
+    let y_min = try!(reader.read_i16::<BigEndian>());
+    let x_max = try!(reader.read_i16::<BigEndian>());
+    let y_max = try!(reader.read_i16::<BigEndian>());
+    Ok(Rect::new(Point2D::new(x_min, y_min),
+                 Size2D::new(x_max.wrapping_sub(x_min), y_max.wrapping_sub(y_min))))
+}
+
+// Reads one component record of a composite glyph.
+fn read_component(reader: &mut &[u8]) -> Result<(ComponentPlacement, ComponentFlags), Error> {
+    let flags = ComponentFlags::from_bits_truncate(try!(reader.read_u16::<BigEndian>()));
+    let glyph_id = try!(reader.read_u16::<BigEndian>());
+    let (dx, dy) = if flags.contains(ARG_1_AND_2_ARE_WORDS) {
+        (try!(reader.read_i16::<BigEndian>()), try!(reader.read_i16::<BigEndian>()))
+    } else {
+        (try!(reader.read_i8()) as i16, try!(reader.read_i8()) as i16)
+    };
+    if !flags.contains(ARGS_ARE_XY_VALUES) {
+        // TODO(pcwalton): Components positioned by matching points.
+        return Err(Error::Malformed)
     }
+
+    let (mut xx, mut yx, mut xy, mut yy) = (1.0, 0.0, 0.0, 1.0);
+    if flags.contains(WE_HAVE_A_SCALE) {
+        xx = try!(read_f2dot14(reader));
+        yy = xx
+    } else if flags.contains(WE_HAVE_AN_X_AND_Y_SCALE) {
+        xx = try!(read_f2dot14(reader));
+        yy = try!(read_f2dot14(reader))
+    } else if flags.contains(WE_HAVE_A_TWO_BY_TWO) {
+        xx = try!(read_f2dot14(reader));
+        yx = try!(read_f2dot14(reader));
+        xy = try!(read_f2dot14(reader));
+        yy = try!(read_f2dot14(reader))
+    }
+
+    Ok((ComponentPlacement {
+        glyph_id: glyph_id,
+        transform: Transform::new(xx, yx, xy, yy, dx as f32, dy as f32),
+        round_offset_to_grid: flags.contains(ROUND_XY_TO_GRID),
+        use_my_metrics: flags.contains(USE_MY_METRICS),
+    }, flags))
+}
+
+fn read_f2dot14(reader: &mut &[u8]) -> Result<f32, Error> {
+    reader.read_i16::<BigEndian>().map_err(Error::from).map(|value| value as f32 / 16384.0)
+}
+
+// Moves a component's point into place, rounding to the nearest font unit.
+fn transform_position(transform: &Transform, position: &Point2D<i16>) -> Point2D<i16> {
+    let position = transform.transform_point(&Point2D::new(position.x as f32,
+                                                           position.y as f32));
+    Point2D::new(clamp_to_i16(position.x), clamp_to_i16(position.y))
+}
+
+// Rounds half away from zero by hand, since `f32::round` needs `std`.
+#[inline]
+fn clamp_to_i16(value: f32) -> i16 {
+    let value = value.max(i16::MIN as f32).min(i16::MAX as f32);
+    (if value < 0.0 { value - 0.5 } else { value + 0.5 }) as i16
 }
 
 // Given a reader pointing to the start of the list of flags, returns the size in bytes of the list
 // of X coordinates and positions the reader at the start of that list.
 #[inline]
 fn calculate_size_of_x_coordinates<'a, 'b>(reader: &'a mut &'b [u8], number_of_points: u16)
-                                           -> Result<u16, ()> {
-    let (mut x_coordinate_length, mut points_left) = (0, number_of_points);
+                                           -> Result<usize, Error> {
+    let (mut x_coordinate_length, mut points_left) = (0, number_of_points as usize);
     while points_left > 0 {
-        let flags = Flags::from_bits_truncate(try!(reader.read_u8().map_err(drop)));
+        let flags = Flags::from_bits_truncate(try!(reader.read_u8()));
         let repeat_count = if !flags.contains(REPEAT) {
             1
         } else {
-            try!(reader.read_u8().map_err(drop)) as u16 + 1
+            try!(reader.read_u8()) as usize + 1
         };
 
         if flags.contains(X_SHORT_VECTOR) {
@@ -178,7 +637,8 @@ fn calculate_size_of_x_coordinates<'a, 'b>(reader: &'a mut &'b [u8], number_of_p
             x_coordinate_length += repeat_count * 2
         }
 
-        points_left -= repeat_count
+        // A repeat count that runs past the last point is tolerated, as most rasterizers do.
+        points_left = points_left.saturating_sub(repeat_count)
     }
 
     Ok(x_coordinate_length)
@@ -192,10 +652,10 @@ struct FlagParser<'a> {
 
 impl<'a> FlagParser<'a> {
     #[inline]
-    fn new(buffer: &[u8]) -> Result<FlagParser, ()> {
+    fn new(buffer: &[u8]) -> Result<FlagParser, Error> {
         let mut parser = FlagParser {
             next: buffer,
-            current: &buffer[0],
+            current: try!(buffer.get(0).ok_or(Error::Malformed)),
             repeats_left: 0,
         };
         try!(parser.next());
@@ -203,18 +663,18 @@ impl<'a> FlagParser<'a> {
     }
 
     #[inline]
-    fn next(&mut self) -> Result<(), ()> {
+    fn next(&mut self) -> Result<(), Error> {
         if self.repeats_left > 0 {
             self.repeats_left -= 1;
             return Ok(())
         }
 
-        self.current = try!(self.next.get(0).ok_or(()));
+        self.current = try!(self.next.get(0).ok_or(Error::Malformed));
         let flags = Flags::from_bits_truncate(*self.current);
         self.next = &self.next[1..];
 
         if flags.contains(REPEAT) {
-            self.repeats_left = *try!(self.next.get(0).ok_or(()));
+            self.repeats_left = *try!(self.next.get(0).ok_or(Error::Malformed));
             self.next = &self.next[1..];
         } else {
             self.repeats_left = 0
@@ -0,0 +1,257 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `GSUB` table, which substitutes glyphs during shaping.
+//!
+//! This crate doesn't shape text, so all that's read here is which glyphs substitutions can
+//! produce, for subsetting.
+
+use byteorder::BigEndian;
+use error::Error;
+use otf::{FontTable, GSUB};
+use std::collections::BTreeSet;
+use std::mem;
+use std::ops::Bound::Included;
+use std::vec::Vec;
+use util::{Jump, ReadBytes};
+
+const LOOKUP_TYPE_SINGLE: u16 = 1;
+const LOOKUP_TYPE_MULTIPLE: u16 = 2;
+const LOOKUP_TYPE_ALTERNATE: u16 = 3;
+const LOOKUP_TYPE_LIGATURE: u16 = 4;
+const LOOKUP_TYPE_EXTENSION: u16 = 7;
+const LOOKUP_TYPE_REVERSE_CHAINING_SINGLE: u16 = 8;
+
+#[derive(Clone, Copy)]
+pub struct GsubTable<'a> {
+    pub table: FontTable<'a>,
+    lookup_list_offset: u16,
+}
+
+impl<'a> GsubTable<'a> {
+    pub fn new(table: FontTable) -> Result<GsubTable, Error> {
+        let mut reader = table.bytes;
+        let major_version = try!(reader.read_u16::<BigEndian>());
+        if major_version != 1 {
+            return Err(Error::UnsupportedFormat {
+                table: GSUB,
+                format: (major_version as u32) << 16,
+            })
+        }
+
+        // Skip the minor version and the script and feature lists.
+        try!(reader.jump(mem::size_of::<u16>() * 3));
+        let lookup_list_offset = try!(reader.read_u16::<BigEndian>());
+        Ok(GsubTable {
+            table: table,
+            lookup_list_offset: lookup_list_offset,
+        })
+    }
+
+    /// Adds to `glyph_ids` every glyph that a substitution can replace the glyphs in the set
+    /// with, repeating until there are no more.
+    ///
+    /// Contextual lookups only apply their nested lookups, which are in the lookup list anyway,
+    /// so each lookup is treated as though it could apply anywhere. This may keep more glyphs
+    /// than shaping can ever produce, but never fewer.
+    pub fn closure(&self, glyph_ids: &mut BTreeSet<u16>) -> Result<(), Error> {
+        let mut lookup_list = self.table.bytes;
+        try!(lookup_list.jump(self.lookup_list_offset as usize));
+        let mut reader = lookup_list;
+        let lookup_count = try!(reader.read_u16::<BigEndian>());
+
+        loop {
+            let mut new_glyph_ids = vec![];
+            let mut lookup_offsets = reader;
+            for _ in 0..lookup_count {
+                let mut lookup = try!(subtable(lookup_list, &mut lookup_offsets));
+                let lookup_table = lookup;
+                let lookup_type = try!(lookup.read_u16::<BigEndian>());
+                try!(lookup.jump(mem::size_of::<u16>()));
+                let subtable_count = try!(lookup.read_u16::<BigEndian>());
+                for _ in 0..subtable_count {
+                    let subtable = try!(subtable(lookup_table, &mut lookup));
+                    try!(substitutions(lookup_type, subtable, glyph_ids, &mut new_glyph_ids))
+                }
+            }
+
+            let count = glyph_ids.len();
+            glyph_ids.extend(new_glyph_ids);
+            if glyph_ids.len() == count {
+                return Ok(())
+            }
+        }
+    }
+}
+
+// Adds the glyphs that one lookup subtable can substitute for glyphs in `glyph_ids` to
+// `new_glyph_ids`. Contextual lookups and unknown lookup types add nothing.
+fn substitutions(lookup_type: u16,
+                 subtable_data: &[u8],
+                 glyph_ids: &BTreeSet<u16>,
+                 new_glyph_ids: &mut Vec<u16>)
+                 -> Result<(), Error> {
+    let mut reader = subtable_data;
+    let format = try!(reader.read_u16::<BigEndian>());
+    match (lookup_type, format) {
+        (LOOKUP_TYPE_SINGLE, 1) => {
+            let coverage = try!(subtable(subtable_data, &mut reader));
+            let delta_glyph_id = try!(reader.read_i16::<BigEndian>());
+            for (_, glyph_id) in try!(covered_glyphs(coverage, glyph_ids)) {
+                new_glyph_ids.push((glyph_id as i16).wrapping_add(delta_glyph_id) as u16)
+            }
+        }
+        (LOOKUP_TYPE_SINGLE, 2) | (LOOKUP_TYPE_REVERSE_CHAINING_SINGLE, 1) => {
+            let coverage = try!(subtable(subtable_data, &mut reader));
+            if lookup_type == LOOKUP_TYPE_REVERSE_CHAINING_SINGLE {
+                // Skip the backtrack and lookahead coverage tables.
+                for _ in 0..2 {
+                    let count = try!(reader.read_u16::<BigEndian>());
+                    try!(reader.jump(count as usize * mem::size_of::<u16>()))
+                }
+            }
+            let substitutes = try!(array(&mut reader));
+            for (coverage_index, _) in try!(covered_glyphs(coverage, glyph_ids)) {
+                new_glyph_ids.push(try!(glyph_at(substitutes, coverage_index)))
+            }
+        }
+        (LOOKUP_TYPE_MULTIPLE, 1) | (LOOKUP_TYPE_ALTERNATE, 1) => {
+            let coverage = try!(subtable(subtable_data, &mut reader));
+            let sets = try!(array(&mut reader));
+            for (coverage_index, _) in try!(covered_glyphs(coverage, glyph_ids)) {
+                let mut offset = try!(offset_at(sets, coverage_index));
+                let mut set = try!(subtable(subtable_data, &mut offset));
+                new_glyph_ids.extend_from_slice(&try!(glyphs(try!(array(&mut set)))))
+            }
+        }
+        (LOOKUP_TYPE_LIGATURE, 1) => {
+            let coverage = try!(subtable(subtable_data, &mut reader));
+            let ligature_sets = try!(array(&mut reader));
+            for (coverage_index, _) in try!(covered_glyphs(coverage, glyph_ids)) {
+                let mut offset = try!(offset_at(ligature_sets, coverage_index));
+                let ligature_set_data = try!(subtable(subtable_data, &mut offset));
+                let mut ligature_set = ligature_set_data;
+                let ligatures = try!(array(&mut ligature_set));
+                for ligature_index in 0..(ligatures.len() / mem::size_of::<u16>()) {
+                    let mut offset = try!(offset_at(ligatures, ligature_index as u16));
+                    let mut ligature = try!(subtable(ligature_set_data, &mut offset));
+                    let ligature_glyph_id = try!(ligature.read_u16::<BigEndian>());
+
+                    // The first component is the covered glyph.
+                    let component_count = try!(ligature.read_u16::<BigEndian>());
+                    let mut all_present = true;
+                    for _ in 1..component_count {
+                        all_present &= glyph_ids.contains(&try!(ligature.read_u16::<BigEndian>()))
+                    }
+                    if all_present {
+                        new_glyph_ids.push(ligature_glyph_id)
+                    }
+                }
+            }
+        }
+        (LOOKUP_TYPE_EXTENSION, 1) => {
+            let extension_lookup_type = try!(reader.read_u16::<BigEndian>());
+            let offset = try!(reader.read_u32::<BigEndian>());
+            if extension_lookup_type == LOOKUP_TYPE_EXTENSION {
+                return Err(Error::Malformed)
+            }
+            let mut extension = subtable_data;
+            try!(extension.jump(offset as usize));
+            try!(substitutions(extension_lookup_type, extension, glyph_ids, new_glyph_ids))
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// Returns the coverage index and glyph ID of each glyph in `glyph_ids` that a coverage table
+// covers.
+fn covered_glyphs(coverage: &[u8], glyph_ids: &BTreeSet<u16>) -> Result<Vec<(u16, u16)>, Error> {
+    let mut reader = coverage;
+    let format = try!(reader.read_u16::<BigEndian>());
+    let mut covered = vec![];
+    match format {
+        1 => {
+            for (coverage_index, glyph_id) in try!(glyphs(try!(array(&mut reader))))
+                                                  .into_iter()
+                                                  .enumerate() {
+                if glyph_ids.contains(&glyph_id) {
+                    covered.push((coverage_index as u16, glyph_id))
+                }
+            }
+        }
+        2 => {
+            let range_count = try!(reader.read_u16::<BigEndian>());
+            for _ in 0..range_count {
+                let start_glyph_id = try!(reader.read_u16::<BigEndian>());
+                let end_glyph_id = try!(reader.read_u16::<BigEndian>());
+                let start_coverage_index = try!(reader.read_u16::<BigEndian>());
+                if start_glyph_id > end_glyph_id {
+                    return Err(Error::Malformed)
+                }
+                for &glyph_id in glyph_ids.range((Included(start_glyph_id),
+                                                  Included(end_glyph_id))) {
+                    let coverage_index =
+                        start_coverage_index.wrapping_add(glyph_id - start_glyph_id);
+                    covered.push((coverage_index, glyph_id))
+                }
+            }
+        }
+        _ => {
+            return Err(Error::UnsupportedFormat {
+                table: GSUB,
+                format: format as u32,
+            })
+        }
+    }
+    Ok(covered)
+}
+
+// Reads a 16-bit offset from `reader` and returns the data at that offset from `base`.
+fn subtable<'a>(base: &'a [u8], reader: &mut &[u8]) -> Result<&'a [u8], Error> {
+    let offset = try!(reader.read_u16::<BigEndian>());
+    let mut subtable = base;
+    try!(subtable.jump(offset as usize));
+    Ok(subtable)
+}
+
+// Reads a count followed by that many 16-bit glyph IDs or offsets, returning their data.
+fn array<'a>(reader: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+    let count = try!(reader.read_u16::<BigEndian>()) as usize * mem::size_of::<u16>();
+    if reader.len() < count {
+        return Err(Error::UnexpectedEof)
+    }
+    let array = &reader[0..count];
+    try!(reader.jump(count));
+    Ok(array)
+}
+
+fn glyphs(array: &[u8]) -> Result<Vec<u16>, Error> {
+    let mut reader = array;
+    (0..(array.len() / mem::size_of::<u16>())).map(|_| {
+        reader.read_u16::<BigEndian>().map_err(Error::from)
+    }).collect()
+}
+
+fn glyph_at(array: &[u8], index: u16) -> Result<u16, Error> {
+    let mut reader = array;
+    try!(reader.jump(index as usize * mem::size_of::<u16>()));
+    reader.read_u16::<BigEndian>().map_err(Error::from)
+}
+
+// Returns a reader positioned at the offset with the given index in an array of offsets.
+fn offset_at(array: &[u8], index: u16) -> Result<&[u8], Error> {
+    let mut reader = array;
+    try!(reader.jump(index as usize * mem::size_of::<u16>()));
+    if reader.len() < mem::size_of::<u16>() {
+        return Err(Error::UnexpectedEof)
+    }
+    Ok(reader)
+}
@@ -8,51 +8,59 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use byteorder::{BigEndian, ReadBytesExt};
-use otf::FontTable;
+use byteorder::BigEndian;
+use error::Error;
+use otf::{HEAD, FontTable};
 use std::mem;
-use util::Jump;
+use util::{Jump, ReadBytes};
 
 const MAGIC_NUMBER: u32 = 0x5f0f3cf5;
 
+#[derive(Clone, Copy, Debug)]
 pub struct HeadTable {
     pub units_per_em: u16,
     pub index_to_loc_format: i16,
 }
 
 impl HeadTable {
-    pub fn new(table: FontTable) -> Result<HeadTable, ()> {
+    pub fn new(table: FontTable) -> Result<HeadTable, Error> {
         let mut reader = table.bytes;
 
         // Check the version.
-        let major_version = try!(reader.read_u16::<BigEndian>().map_err(drop));
-        let minor_version = try!(reader.read_u16::<BigEndian>().map_err(drop));
+        let major_version = try!(reader.read_u16::<BigEndian>());
+        let minor_version = try!(reader.read_u16::<BigEndian>());
         if (major_version, minor_version) != (1, 0) {
-            return Err(())
+            return Err(Error::UnsupportedFormat {
+                table: HEAD,
+                format: ((major_version as u32) << 16) | minor_version as u32,
+            })
         }
 
         // Check the magic number.
         try!(reader.jump(mem::size_of::<u32>() * 2));
-        let magic_number = try!(reader.read_u32::<BigEndian>().map_err(drop));
+        let magic_number = try!(reader.read_u32::<BigEndian>());
         if magic_number != MAGIC_NUMBER {
-            return Err(())
+            return Err(Error::Malformed)
         }
 
         // Read the units per em.
         try!(reader.jump(mem::size_of::<u16>()));
-        let units_per_em = try!(reader.read_u16::<BigEndian>().map_err(drop));
+        let units_per_em = try!(reader.read_u16::<BigEndian>());
 
         // Read the index-to-location format.
         try!(reader.jump(mem::size_of::<i64>() * 2 +
                          mem::size_of::<i16>() * 4 + 
                          mem::size_of::<u16>() * 2 +
                          mem::size_of::<i16>()));
-        let index_to_loc_format = try!(reader.read_i16::<BigEndian>().map_err(drop));
+        let index_to_loc_format = try!(reader.read_i16::<BigEndian>());
 
         // Check the glyph data format.
-        let glyph_data_format = try!(reader.read_i16::<BigEndian>().map_err(drop));
+        let glyph_data_format = try!(reader.read_i16::<BigEndian>());
         if glyph_data_format != 0 {
-            return Err(())
+            return Err(Error::UnsupportedFormat {
+                table: HEAD,
+                format: glyph_data_format as u32,
+            })
         }
 
         Ok(HeadTable {
@@ -0,0 +1,59 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+
+//! The `hhea` and `vhea` tables, which hold the font-wide metrics for horizontal and vertical
+//! layout respectively. Both share one layout.
+
+use byteorder::BigEndian;
+use error::Error;
+use otf::{HHEA, FontTable};
+use std::mem;
+use util::{Jump, ReadBytes};
+
+#[derive(Clone, Copy, Debug)]
+pub struct HheaTable {
+    pub ascender: i16,
+    pub descender: i16,
+    pub line_gap: i16,
+    /// The number of full metrics records in the matching `hmtx` or `vmtx` table.
+    pub number_of_metrics: u16,
+}
+
+impl HheaTable {
+    pub fn new(table: FontTable) -> Result<HheaTable, Error> {
+        let mut reader = table.bytes;
+
+        // `vhea` 1.1 only renames fields, so accept any minor version.
+        let major_version = try!(reader.read_u16::<BigEndian>());
+        if major_version != 1 {
+            return Err(Error::UnsupportedFormat {
+                table: HHEA,
+                format: (major_version as u32) << 16,
+            })
+        }
+        try!(reader.jump(mem::size_of::<u16>()));
+
+        let ascender = try!(reader.read_i16::<BigEndian>());
+        let descender = try!(reader.read_i16::<BigEndian>());
+        let line_gap = try!(reader.read_i16::<BigEndian>());
+
+        // Skip the extents, the caret, the reserved fields, and the metric data format.
+        try!(reader.jump(mem::size_of::<i16>() * 12));
+        let number_of_metrics = try!(reader.read_u16::<BigEndian>());
+
+        Ok(HheaTable {
+            ascender: ascender,
+            descender: descender,
+            line_gap: line_gap,
+            number_of_metrics: number_of_metrics,
+        })
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+
+//! The `hmtx` and `vmtx` tables, which hold the advance and side bearing of each glyph.
+
+use byteorder::BigEndian;
+#[cfg(feature = "std")]
+use byteorder::WriteBytesExt;
+use error::Error;
+use otf::FontTable;
+use otf::hhea::HheaTable;
+use util::{Jump, ReadBytes};
+
+/// The metrics of one glyph along the layout direction, in font units.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GlyphMetrics {
+    pub advance: u16,
+    /// The left side bearing for `hmtx`, or the top side bearing for `vmtx`.
+    pub side_bearing: i16,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct HmtxTable<'a> {
+    pub table: FontTable<'a>,
+    number_of_metrics: u16,
+}
+
+impl<'a> HmtxTable<'a> {
+    pub fn new(table: FontTable<'a>, hhea_table: &HheaTable) -> Result<HmtxTable<'a>, Error> {
+        if hhea_table.number_of_metrics == 0 ||
+                table.bytes.len() < hhea_table.number_of_metrics as usize * 4 {
+            return Err(Error::Malformed)
+        }
+
+        Ok(HmtxTable {
+            table: table,
+            number_of_metrics: hhea_table.number_of_metrics,
+        })
+    }
+
+    pub fn metrics(&self, glyph_id: u16) -> Result<GlyphMetrics, Error> {
+        let mut reader = self.table.bytes;
+        if glyph_id < self.number_of_metrics {
+            try!(reader.jump(glyph_id as usize * 4));
+            let advance = try!(reader.read_u16::<BigEndian>());
+            let side_bearing = try!(reader.read_i16::<BigEndian>());
+            return Ok(GlyphMetrics {
+                advance: advance,
+                side_bearing: side_bearing,
+            })
+        }
+
+        // Glyphs past the last full record share its advance and store only a side bearing.
+        let mut last_reader = reader;
+        try!(last_reader.jump((self.number_of_metrics as usize - 1) * 4));
+        let advance = try!(last_reader.read_u16::<BigEndian>());
+        let offset = self.number_of_metrics as usize * 4 +
+            (glyph_id - self.number_of_metrics) as usize * 2;
+        if offset + 2 > reader.len() {
+            return Err(Error::InvalidGlyphId(glyph_id as u32))
+        }
+        try!(reader.jump(offset));
+        let side_bearing = try!(reader.read_i16::<BigEndian>());
+        Ok(GlyphMetrics {
+            advance: advance,
+            side_bearing: side_bearing,
+        })
+    }
+}
+
+/// Builds an `hmtx` or `vmtx` table from the metrics of each glyph. Glyphs at the end that share
+/// the advance of the glyph before them store only their side bearings. Returns the table and the
+/// number of full metrics records, for `hhea` or `vhea`.
+#[cfg(feature = "std")]
+pub fn build_hmtx_table(metrics: &[GlyphMetrics]) -> (Vec<u8>, u16) {
+    let mut number_of_metrics = metrics.len();
+    while number_of_metrics > 1 &&
+            metrics[number_of_metrics - 1].advance == metrics[number_of_metrics - 2].advance {
+        number_of_metrics -= 1
+    }
+
+    let mut hmtx = vec![];
+    for (index, glyph_metrics) in metrics.iter().enumerate() {
+        if index < number_of_metrics {
+            hmtx.write_u16::<BigEndian>(glyph_metrics.advance).unwrap()
+        }
+        hmtx.write_i16::<BigEndian>(glyph_metrics.side_bearing).unwrap()
+    }
+    (hmtx, number_of_metrics as u16)
+}
@@ -0,0 +1,188 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Font format detection and a single entry point for loading any supported font file.
+
+use byteorder::{BigEndian, ByteOrder};
+use error::Error;
+use otf::collection::{FontCollection, TTCF};
+use otf::dfont::{DfontFace, ResourceFork};
+use otf::woff::{WOFF, WoffFont};
+use otf::woff2::{WOFF2, Woff2Font};
+use otf::{FontData, SFNT_VERSION_OTTO, SFNT_VERSION_TRUE, SFNT_VERSION_TRUETYPE};
+
+const RESOURCE_FORK_HEADER_SIZE: usize = 16;
+const RESOURCE_MAP_MIN_SIZE: usize = 30;
+
+/// The container format of a font file, as determined by its leading bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FontFormat {
+    /// A bare sfnt with TrueType outlines (version `0x00010000` or `true`).
+    TrueType,
+    /// A bare sfnt with CFF outlines (version `OTTO`).
+    Cff,
+    /// A TrueType/OpenType Collection (`ttcf`).
+    Collection,
+    /// A WOFF 1.0 file.
+    Woff,
+    /// A WOFF 2.0 file.
+    Woff2,
+    /// A Mac OS resource fork stored as a flat file, as in `.dfont`.
+    Dfont,
+    /// A PostScript Type 1 font, either binary (`.pfb`) or ASCII (`.pfa`).
+    Type1,
+    /// A bitmap-only format: BDF, PCF, PSF or Windows `.fnt`/`.fon`.
+    Bitmap,
+    /// None of the above.
+    Unknown,
+}
+
+impl FontFormat {
+    /// Determines the format of a font file from its leading bytes.
+    pub fn detect(bytes: &[u8]) -> FontFormat {
+        if bytes.len() >= 4 {
+            match BigEndian::read_u32(bytes) {
+                SFNT_VERSION_TRUETYPE | SFNT_VERSION_TRUE => return FontFormat::TrueType,
+                SFNT_VERSION_OTTO => return FontFormat::Cff,
+                TTCF => return FontFormat::Collection,
+                WOFF => return FontFormat::Woff,
+                WOFF2 => return FontFormat::Woff2,
+                _ => {}
+            }
+        }
+
+        if bytes.len() >= 2 && bytes[0] == 0x80 && (bytes[1] == 0x01 || bytes[1] == 0x02) {
+            return FontFormat::Type1
+        }
+        if bytes.starts_with(b"%!PS-AdobeFont") || bytes.starts_with(b"%!FontType1") {
+            return FontFormat::Type1
+        }
+
+        if bytes.starts_with(b"STARTFONT ") ||
+                bytes.starts_with(b"\x01fcp") ||
+                bytes.starts_with(b"\x36\x04") ||
+                bytes.starts_with(b"\x72\xb5\x4a\x86") ||
+                bytes.starts_with(b"MZ") {
+            return FontFormat::Bitmap
+        }
+
+        if looks_like_resource_fork(bytes) {
+            return FontFormat::Dfont
+        }
+
+        FontFormat::Unknown
+    }
+}
+
+/// Why a font file couldn't be loaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadError {
+    /// The file is in a format that this crate can recognize but not read.
+    UnsupportedFormat(FontFormat),
+    /// The file claimed to be in a supported format but failed to parse, for the given reason.
+    Malformed(FontFormat, Error),
+}
+
+/// A font file, decoded as far as necessary to reach its sfnt tables.
+pub enum LoadedFont<'a> {
+    /// A bare sfnt, borrowed from the input without copying.
+    Sfnt(FontData<'a>),
+    /// A collection, borrowed from the input without copying.
+    Collection(FontCollection<'a>),
+    /// A decoded WOFF 1.0 file.
+    Woff(WoffFont),
+    /// A decoded WOFF 2.0 file, which may itself be a collection.
+    Woff2(Woff2Font),
+    /// The `sfnt` resources of a resource fork, borrowed from the input without copying.
+    Dfont(Vec<DfontFace<'a>>),
+}
+
+impl<'a> LoadedFont<'a> {
+    /// Returns the number of faces in this font file.
+    pub fn face_count(&self) -> u32 {
+        match *self {
+            LoadedFont::Sfnt(_) | LoadedFont::Woff(_) => 1,
+            LoadedFont::Collection(ref collection) => collection.face_count(),
+            LoadedFont::Dfont(ref faces) => faces.len() as u32,
+            LoadedFont::Woff2(ref woff2) => {
+                if woff2.is_collection() {
+                    woff2.collection().map(|collection| collection.face_count()).unwrap_or(0)
+                } else {
+                    1
+                }
+            }
+        }
+    }
+
+    /// Returns a view of face `index`.
+    pub fn face(&self, index: u32) -> Result<FontData, Error> {
+        match *self {
+            LoadedFont::Sfnt(font) if index == 0 => Ok(font),
+            LoadedFont::Woff(ref woff) if index == 0 => Ok(woff.font_data()),
+            LoadedFont::Collection(ref collection) => collection.face(index),
+            LoadedFont::Woff2(ref woff2) if woff2.is_collection() => {
+                woff2.collection().and_then(|collection| collection.face(index))
+            }
+            LoadedFont::Woff2(ref woff2) if index == 0 => Ok(woff2.font_data()),
+            LoadedFont::Dfont(ref faces) => {
+                faces.get(index as usize).map(|face| face.font).ok_or(Error::InvalidArgument)
+            }
+            _ => Err(Error::InvalidArgument),
+        }
+    }
+}
+
+/// Detects the format of a font file and decodes it.
+///
+/// Type 1 fonts have no sfnt tables, so they're reported as unsupported here; read them with
+/// `type1::Type1Font` instead.
+pub fn load_font(bytes: &[u8]) -> Result<LoadedFont, LoadError> {
+    let format = FontFormat::detect(bytes);
+    match format {
+        FontFormat::TrueType | FontFormat::Cff => Ok(LoadedFont::Sfnt(FontData::new(bytes))),
+        FontFormat::Collection => {
+            FontCollection::new(bytes).map(LoadedFont::Collection)
+                                      .map_err(|error| LoadError::Malformed(format, error))
+        }
+        FontFormat::Woff => {
+            WoffFont::new(bytes).map(LoadedFont::Woff)
+                                .map_err(|error| LoadError::Malformed(format, error))
+        }
+        FontFormat::Woff2 => {
+            Woff2Font::new(bytes).map(LoadedFont::Woff2)
+                                 .map_err(|error| LoadError::Malformed(format, error))
+        }
+        FontFormat::Dfont => {
+            ResourceFork::new(bytes).and_then(|fork| fork.faces())
+                                    .map(LoadedFont::Dfont)
+                                    .map_err(|error| LoadError::Malformed(format, error))
+        }
+        FontFormat::Type1 | FontFormat::Bitmap | FontFormat::Unknown => {
+            Err(LoadError::UnsupportedFormat(format))
+        }
+    }
+}
+
+// Resource forks have no magic number, so check that the header describes a plausible layout:
+// the data area and the map both lie within the file, and the map ends at the end of the file.
+fn looks_like_resource_fork(bytes: &[u8]) -> bool {
+    if bytes.len() < RESOURCE_FORK_HEADER_SIZE + RESOURCE_MAP_MIN_SIZE {
+        return false
+    }
+
+    let data_offset = BigEndian::read_u32(&bytes[0..]) as u64;
+    let map_offset = BigEndian::read_u32(&bytes[4..]) as u64;
+    let data_length = BigEndian::read_u32(&bytes[8..]) as u64;
+    let map_length = BigEndian::read_u32(&bytes[12..]) as u64;
+    data_offset >= RESOURCE_FORK_HEADER_SIZE as u64 &&
+        map_length >= RESOURCE_MAP_MIN_SIZE as u64 &&
+        data_offset + data_length <= map_offset &&
+        map_offset + map_length == bytes.len() as u64
+}
@@ -8,22 +8,31 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use byteorder::{BigEndian, ReadBytesExt};
-use otf::FontTable;
+use byteorder::BigEndian;
+#[cfg(feature = "std")]
+use byteorder::WriteBytesExt;
+use error::Error;
+use otf::{LOCA, FontTable};
 use otf::head::HeadTable;
-use util::Jump;
+#[cfg(feature = "std")]
+use std::u16;
+use util::{Jump, ReadBytes};
 
+#[derive(Clone, Copy, Debug)]
 pub struct LocaTable<'a> {
     table: FontTable<'a>,
     pub long: bool,
 }
 
 impl<'a> LocaTable<'a> {
-    pub fn new(loca_table: FontTable<'a>, head_table: &HeadTable) -> Result<LocaTable<'a>, ()> {
+    pub fn new(loca_table: FontTable<'a>, head_table: &HeadTable) -> Result<LocaTable<'a>, Error> {
         let long = match head_table.index_to_loc_format {
             0 => false,
             1 => true,
-            _ => return Err(()),
+            _ => return Err(Error::UnsupportedFormat {
+                table: LOCA,
+                format: head_table.index_to_loc_format as u32,
+            }),
         };
 
         Ok(LocaTable {
@@ -32,15 +41,37 @@ impl<'a> LocaTable<'a> {
         })
     }
 
-    pub fn location_of(&self, glyph_id: u32) -> Result<u32, ()> {
+    pub fn location_of(&self, glyph_id: u32) -> Result<u32, Error> {
         let mut reader = self.table.bytes;
+        let entry_size = if self.long { 4 } else { 2 };
+        if (glyph_id as u64 + 1) * entry_size > reader.len() as u64 {
+            return Err(Error::InvalidGlyphId(glyph_id))
+        }
         if !self.long {
             try!(reader.jump(glyph_id as usize * 2));
-            Ok(try!(reader.read_u16::<BigEndian>().map_err(drop)) as u32 * 2)
+            Ok(try!(reader.read_u16::<BigEndian>()) as u32 * 2)
         } else {
             try!(reader.jump(glyph_id as usize * 4));
-            reader.read_u32::<BigEndian>().map_err(drop)
+            reader.read_u32::<BigEndian>().map_err(Error::from)
         }
     }
 }
 
+/// Builds a `loca` table from the offset of each glyph in `glyf`, followed by the end of the last
+/// glyph. The offsets must be even. Returns the table and the `indexToLocFormat` value for `head`,
+/// which is the short format if all the offsets fit in it.
+#[cfg(feature = "std")]
+pub fn build_loca_table(offsets: &[usize]) -> (Vec<u8>, i16) {
+    let mut loca = vec![];
+    if offsets.iter().all(|&offset| offset / 2 <= u16::MAX as usize) {
+        for &offset in offsets {
+            loca.write_u16::<BigEndian>((offset / 2) as u16).unwrap()
+        }
+        (loca, 0)
+    } else {
+        for &offset in offsets {
+            loca.write_u32::<BigEndian>(offset as u32).unwrap()
+        }
+        (loca, 1)
+    }
+}
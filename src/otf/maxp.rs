@@ -0,0 +1,70 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use byteorder::BigEndian;
+use error::Error;
+use otf::{MAXP, FontTable};
+use std::mem;
+use util::{Jump, ReadBytes};
+
+// Version 0.5, used by fonts with CFF outlines, only has the glyph count.
+const VERSION_0_5: u32 = 0x00005000;
+const VERSION_1_0: u32 = 0x00010000;
+
+#[derive(Clone, Copy, Debug)]
+pub struct MaxpTable {
+    pub num_glyphs: u16,
+    /// The limits that the TrueType hinting programs promise to stay within. These are all zero
+    /// in version 0.5 tables.
+    pub max_zones: u16,
+    pub max_twilight_points: u16,
+    pub max_storage: u16,
+    pub max_function_defs: u16,
+    pub max_instruction_defs: u16,
+    pub max_stack_elements: u16,
+}
+
+impl MaxpTable {
+    pub fn new(table: FontTable) -> Result<MaxpTable, Error> {
+        let mut reader = table.bytes;
+        let version = try!(reader.read_u32::<BigEndian>());
+        match version {
+            VERSION_0_5 | VERSION_1_0 => {}
+            _ => return Err(Error::UnsupportedFormat {
+                    table: MAXP,
+                    format: version,
+                }),
+        }
+
+        let num_glyphs = try!(reader.read_u16::<BigEndian>());
+        let mut maxp_table = MaxpTable {
+            num_glyphs: num_glyphs,
+            max_zones: 0,
+            max_twilight_points: 0,
+            max_storage: 0,
+            max_function_defs: 0,
+            max_instruction_defs: 0,
+            max_stack_elements: 0,
+        };
+        if version == VERSION_0_5 {
+            return Ok(maxp_table)
+        }
+
+        // Skip the point and contour counts.
+        try!(reader.jump(mem::size_of::<u16>() * 4));
+        maxp_table.max_zones = try!(reader.read_u16::<BigEndian>());
+        maxp_table.max_twilight_points = try!(reader.read_u16::<BigEndian>());
+        maxp_table.max_storage = try!(reader.read_u16::<BigEndian>());
+        maxp_table.max_function_defs = try!(reader.read_u16::<BigEndian>());
+        maxp_table.max_instruction_defs = try!(reader.read_u16::<BigEndian>());
+        maxp_table.max_stack_elements = try!(reader.read_u16::<BigEndian>());
+        Ok(maxp_table)
+    }
+}
@@ -8,40 +8,216 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ByteOrder};
+use error::Error;
+use otf::cbdt::CbdtTable;
+use otf::cblc::CblcTable;
 use otf::cmap::CmapTable;
+#[cfg(feature = "std")]
+use otf::colr::ColrTable;
+use otf::cpal::CpalTable;
+use otf::cvt::CvtTable;
+use otf::ebdt::EbdtTable;
+use otf::ebsc::EbscTable;
+use otf::gasp::GaspTable;
 use otf::glyf::GlyfTable;
+use otf::gsub::GsubTable;
 use otf::head::HeadTable;
+use otf::hhea::HheaTable;
+use otf::hmtx::HmtxTable;
 use otf::loca::LocaTable;
+use otf::maxp::MaxpTable;
+use otf::sbix::SbixTable;
+#[cfg(feature = "std")]
+use otf::svg::SvgTable;
+use std::fmt::{self, Debug, Display, Formatter};
 use std::mem;
+use std::str::FromStr;
 use std::u16;
-use util::Jump;
+use util::{Jump, ReadBytes};
 
+pub mod cbdt;
+pub mod cblc;
+#[cfg(feature = "std")]
+pub mod checksum;
 pub mod cmap;
+#[cfg(feature = "std")]
+pub mod collection;
+#[cfg(feature = "std")]
+pub mod colr;
+pub mod cpal;
+pub mod cvt;
+#[cfg(feature = "std")]
+pub mod dfont;
+pub mod ebdt;
+pub mod ebsc;
+#[cfg(feature = "std")]
+pub mod font;
+pub mod gasp;
 pub mod glyf;
+pub mod gsub;
 pub mod head;
+pub mod hhea;
+pub mod hmtx;
+#[cfg(feature = "std")]
+pub mod loader;
 pub mod loca;
+pub mod maxp;
+#[cfg(feature = "std")]
+pub mod sanitizer;
+pub mod sbix;
+#[cfg(feature = "std")]
+pub mod subset;
+#[cfg(feature = "std")]
+pub mod svg;
+#[cfg(feature = "std")]
+pub mod woff;
+#[cfg(feature = "std")]
+pub mod woff2;
+#[cfg(feature = "std")]
+pub mod writer;
 
+/// The sfnt version of fonts with TrueType outlines.
+pub const SFNT_VERSION_TRUETYPE: u32 = 0x00010000;
+/// The sfnt version used by some Apple fonts with TrueType outlines.
+pub const SFNT_VERSION_TRUE: u32 = ((b't' as u32) << 24) |
+                                   ((b'r' as u32) << 16) |
+                                   ((b'u' as u32) << 8)  |
+                                    (b'e' as u32);
+/// The sfnt version of fonts with CFF outlines.
+pub const SFNT_VERSION_OTTO: u32 = ((b'O' as u32) << 24) |
+                                   ((b'T' as u32) << 16) |
+                                   ((b'T' as u32) << 8)  |
+                                    (b'O' as u32);
+
+const TABLE_RECORD_SIZE: usize = 16;
+
+const CBDT: u32 = ((b'C' as u32) << 24) |
+                  ((b'B' as u32) << 16) |
+                  ((b'D' as u32) << 8)  |
+                   (b'T' as u32);
+const CBLC: u32 = ((b'C' as u32) << 24) |
+                  ((b'B' as u32) << 16) |
+                  ((b'L' as u32) << 8)  |
+                   (b'C' as u32);
 const CMAP: u32 = ((b'c' as u32) << 24) |
                   ((b'm' as u32) << 16) |
                   ((b'a' as u32) << 8)  |
                    (b'p' as u32);
+#[cfg(feature = "std")]
+const COLR: u32 = ((b'C' as u32) << 24) |
+                  ((b'O' as u32) << 16) |
+                  ((b'L' as u32) << 8)  |
+                   (b'R' as u32);
+const CPAL: u32 = ((b'C' as u32) << 24) |
+                  ((b'P' as u32) << 16) |
+                  ((b'A' as u32) << 8)  |
+                   (b'L' as u32);
+const CVT: u32 = ((b'c' as u32) << 24) |
+                 ((b'v' as u32) << 16) |
+                 ((b't' as u32) << 8)  |
+                  (b' ' as u32);
+const EBDT: u32 = ((b'E' as u32) << 24) |
+                  ((b'B' as u32) << 16) |
+                  ((b'D' as u32) << 8)  |
+                   (b'T' as u32);
+const EBLC: u32 = ((b'E' as u32) << 24) |
+                  ((b'B' as u32) << 16) |
+                  ((b'L' as u32) << 8)  |
+                   (b'C' as u32);
+const EBSC: u32 = ((b'E' as u32) << 24) |
+                  ((b'B' as u32) << 16) |
+                  ((b'S' as u32) << 8)  |
+                   (b'C' as u32);
+const FPGM: u32 = ((b'f' as u32) << 24) |
+                  ((b'p' as u32) << 16) |
+                  ((b'g' as u32) << 8)  |
+                   (b'm' as u32);
+const GASP: u32 = ((b'g' as u32) << 24) |
+                  ((b'a' as u32) << 16) |
+                  ((b's' as u32) << 8)  |
+                   (b'p' as u32);
+#[cfg(feature = "std")]
+const GDEF: u32 = ((b'G' as u32) << 24) |
+                  ((b'D' as u32) << 16) |
+                  ((b'E' as u32) << 8)  |
+                   (b'F' as u32);
 const GLYF: u32 = ((b'g' as u32) << 24) |
                   ((b'l' as u32) << 16) |
                   ((b'y' as u32) << 8)  |
                    (b'f' as u32);
+#[cfg(feature = "std")]
+const GPOS: u32 = ((b'G' as u32) << 24) |
+                  ((b'P' as u32) << 16) |
+                  ((b'O' as u32) << 8)  |
+                   (b'S' as u32);
+const GSUB: u32 = ((b'G' as u32) << 24) |
+                  ((b'S' as u32) << 16) |
+                  ((b'U' as u32) << 8)  |
+                   (b'B' as u32);
 const HEAD: u32 = ((b'h' as u32) << 24) |
                   ((b'e' as u32) << 16) |
                   ((b'a' as u32) << 8)  |
                    (b'd' as u32);
+const HHEA: u32 = ((b'h' as u32) << 24) |
+                  ((b'h' as u32) << 16) |
+                  ((b'e' as u32) << 8)  |
+                   (b'a' as u32);
+const HMTX: u32 = ((b'h' as u32) << 24) |
+                  ((b'm' as u32) << 16) |
+                  ((b't' as u32) << 8)  |
+                   (b'x' as u32);
 const LOCA: u32 = ((b'l' as u32) << 24) |
                   ((b'o' as u32) << 16) |
                   ((b'c' as u32) << 8)  |
                    (b'a' as u32);
+const MAXP: u32 = ((b'm' as u32) << 24) |
+                  ((b'a' as u32) << 16) |
+                  ((b'x' as u32) << 8)  |
+                   (b'p' as u32);
+#[cfg(feature = "std")]
+const NAME: u32 = ((b'n' as u32) << 24) |
+                  ((b'a' as u32) << 16) |
+                  ((b'm' as u32) << 8)  |
+                   (b'e' as u32);
+#[cfg(feature = "std")]
+const OS_2: u32 = ((b'O' as u32) << 24) |
+                  ((b'S' as u32) << 16) |
+                  ((b'/' as u32) << 8)  |
+                   (b'2' as u32);
+#[cfg(feature = "std")]
+const POST: u32 = ((b'p' as u32) << 24) |
+                  ((b'o' as u32) << 16) |
+                  ((b's' as u32) << 8)  |
+                   (b't' as u32);
+const PREP: u32 = ((b'p' as u32) << 24) |
+                  ((b'r' as u32) << 16) |
+                  ((b'e' as u32) << 8)  |
+                   (b'p' as u32);
+const SBIX: u32 = ((b's' as u32) << 24) |
+                  ((b'b' as u32) << 16) |
+                  ((b'i' as u32) << 8)  |
+                   (b'x' as u32);
+#[cfg(feature = "std")]
+const SVG: u32 = ((b'S' as u32) << 24) |
+                 ((b'V' as u32) << 16) |
+                 ((b'G' as u32) << 8)  |
+                  (b' ' as u32);
+const VHEA: u32 = ((b'v' as u32) << 24) |
+                  ((b'h' as u32) << 16) |
+                  ((b'e' as u32) << 8)  |
+                   (b'a' as u32);
+const VMTX: u32 = ((b'v' as u32) << 24) |
+                  ((b'm' as u32) << 16) |
+                  ((b't' as u32) << 8)  |
+                   (b'x' as u32);
 
 #[derive(Clone, Copy, Debug)]
 pub struct FontData<'a> {
     pub bytes: &'a [u8],
+    /// The offset of this face's table directory within `bytes`. This is always zero for bare
+    /// sfnt files, but faces within a collection share one buffer and each have their own header.
+    header_offset: usize,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -49,76 +225,344 @@ pub struct FontTable<'a> {
     pub bytes: &'a [u8],
 }
 
+/// A four-character table tag, like `cmap`, stored big-endian as in the font.
+///
+/// Tags parse from and display as their characters. Tags shorter than four characters are padded
+/// with spaces, as `cvt ` is.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tag(pub u32);
+
+impl Tag {
+    #[inline]
+    pub fn new(bytes: &[u8; 4]) -> Tag {
+        Tag(BigEndian::read_u32(bytes))
+    }
+
+    #[inline]
+    pub fn to_bytes(&self) -> [u8; 4] {
+        let mut bytes = [0; 4];
+        BigEndian::write_u32(&mut bytes, self.0);
+        bytes
+    }
+}
+
+impl FromStr for Tag {
+    type Err = Error;
+
+    /// Parses one to four printable ASCII characters, padding with spaces.
+    fn from_str(string: &str) -> Result<Tag, Error> {
+        if string.is_empty() || string.len() > 4 ||
+                !string.bytes().all(|byte| byte >= 0x20 && byte < 0x7f) {
+            return Err(Error::InvalidArgument)
+        }
+        let mut bytes = [b' '; 4];
+        bytes[0..string.len()].copy_from_slice(string.as_bytes());
+        Ok(Tag::new(&bytes))
+    }
+}
+
+impl Display for Tag {
+    /// Writes the tag's characters, escaping any that aren't printable ASCII.
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        for &byte in &self.to_bytes() {
+            if byte >= 0x20 && byte < 0x7f {
+                try!(write!(formatter, "{}", byte as char))
+            } else {
+                try!(write!(formatter, "\\x{:02x}", byte))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Debug for Tag {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "Tag(\"{}\")", self)
+    }
+}
+
+/// An entry in a font's table directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TableRecord {
+    pub tag: Tag,
+    pub checksum: u32,
+    /// The offset of the table from the start of the font's bytes, which for faces of a
+    /// collection is the start of the collection.
+    pub offset: u32,
+    pub length: u32,
+}
+
+impl TableRecord {
+    #[inline]
+    fn read(bytes: &[u8]) -> TableRecord {
+        TableRecord {
+            tag: Tag(BigEndian::read_u32(&bytes[0..4])),
+            checksum: BigEndian::read_u32(&bytes[4..8]),
+            offset: BigEndian::read_u32(&bytes[8..12]),
+            length: BigEndian::read_u32(&bytes[12..16]),
+        }
+    }
+}
+
+/// An iterator over the records of a font's table directory.
+#[derive(Clone)]
+pub struct TableRecords<'a> {
+    records: &'a [u8],
+}
+
+impl<'a> Iterator for TableRecords<'a> {
+    type Item = TableRecord;
+
+    #[inline]
+    fn next(&mut self) -> Option<TableRecord> {
+        if self.records.is_empty() {
+            return None
+        }
+        let record = TableRecord::read(self.records);
+        self.records = &self.records[TABLE_RECORD_SIZE..];
+        Some(record)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.records.len() / TABLE_RECORD_SIZE;
+        (count, Some(count))
+    }
+}
+
+impl<'a> ExactSizeIterator for TableRecords<'a> {}
+
 impl<'a> FontData<'a> {
     #[inline]
     pub fn new<'b>(bytes: &'b [u8]) -> FontData<'b> {
         FontData {
             bytes: bytes,
+            header_offset: 0,
         }
     }
 
-    fn table(&self, table_id: u32) -> Result<Option<FontTable>, ()> {
-        let mut reader = self.bytes;
-        let sfnt_version = try!(reader.read_u32::<BigEndian>().map_err(drop));
-        if sfnt_version != 0x10000 {
-            return Err(())
+    /// Creates a view of a single face whose table directory begins at `header_offset`. Table
+    /// offsets in the directory remain relative to the start of `bytes`.
+    #[inline]
+    pub fn with_header_offset<'b>(bytes: &'b [u8], header_offset: usize) -> FontData<'b> {
+        FontData {
+            bytes: bytes,
+            header_offset: header_offset,
         }
+    }
 
-        let num_tables = try!(reader.read_u16::<BigEndian>().map_err(drop));
-        try!(reader.jump(mem::size_of::<u16>() * 3));
+    /// Returns the records of the table directory, in the order they're stored, which is sorted
+    /// by tag in well-formed fonts.
+    pub fn table_records(&self) -> Result<TableRecords<'a>, Error> {
+        Ok(TableRecords {
+            records: try!(self.table_directory()),
+        })
+    }
 
-        let (mut low, mut high) = (0, num_tables);
+    /// Returns the raw data of the table with the given tag, or `None` if the font doesn't have
+    /// one. This works for any table, including ones this crate can't parse.
+    pub fn table(&self, tag: Tag) -> Result<Option<FontTable<'a>>, Error> {
+        // Tables are sorted by tag, so binary search.
+        let records = try!(self.table_directory());
+        let (mut low, mut high) = (0, records.len() / TABLE_RECORD_SIZE);
         while low < high {
-            let mut reader = reader;
             let mid = (low + high) / 2;
-            try!(reader.jump(mid as usize * mem::size_of::<u32>() * 4));
-
-            let current_table_id = try!(reader.read_u32::<BigEndian>().map_err(drop));
-            if table_id < current_table_id {
+            let record = TableRecord::read(&records[(mid * TABLE_RECORD_SIZE)..]);
+            if tag < record.tag {
                 high = mid;
                 continue
             }
-            if table_id > current_table_id {
+            if tag > record.tag {
                 low = mid + 1;
                 continue
             }
+            return self.table_for_record(&record).map(Some)
+        }
 
-            // Skip the checksum, and slurp the offset and length.
-            try!(reader.read_u32::<BigEndian>().map_err(drop));
-            let offset = try!(reader.read_u32::<BigEndian>().map_err(drop)) as usize;
-            let length = try!(reader.read_u32::<BigEndian>().map_err(drop)) as usize;
+        Ok(None)
+    }
 
-            let end = offset + length;
-            if end > self.bytes.len() {
-                return Err(())
-            }
-            return Ok(Some(FontTable {
-                bytes: &self.bytes[offset..end],
-            }))
+    // Returns the table records, making sure that they're all present.
+    fn table_directory(&self) -> Result<&'a [u8], Error> {
+        let mut reader = self.bytes;
+        try!(reader.jump(self.header_offset));
+        let sfnt_version = try!(reader.read_u32::<BigEndian>());
+        match sfnt_version {
+            SFNT_VERSION_TRUETYPE | SFNT_VERSION_TRUE | SFNT_VERSION_OTTO => {}
+            _ => return Err(Error::UnsupportedSfntVersion(sfnt_version)),
         }
 
-        Ok(None)
+        let num_tables = try!(reader.read_u16::<BigEndian>()) as usize;
+        try!(reader.jump(mem::size_of::<u16>() * 3));
+        if reader.len() < num_tables * TABLE_RECORD_SIZE {
+            return Err(Error::UnexpectedEof)
+        }
+        Ok(&reader[0..(num_tables * TABLE_RECORD_SIZE)])
+    }
+
+    fn table_for_record(&self, record: &TableRecord) -> Result<FontTable<'a>, Error> {
+        let (offset, length) = (record.offset as usize, record.length as usize);
+        let end = try!(offset.checked_add(length).ok_or(Error::Malformed));
+        if end > self.bytes.len() {
+            return Err(Error::Truncated {
+                table: record.tag.0,
+                table_length: self.bytes.len().saturating_sub(offset),
+            })
+        }
+        Ok(FontTable {
+            bytes: &self.bytes[offset..end],
+        })
+    }
+
+    /// Looks up the table with the given tag and parses it with `parse`.
+    fn load<T, F>(&self, table_id: u32, parse: F) -> Result<T, Error>
+                  where F: FnOnce(FontTable<'a>) -> Result<T, Error> {
+        parse_table(table_id, try!(self.table(Tag(table_id))), parse)
+    }
+
+    #[inline]
+    pub fn cbdt_table(&self) -> Result<CbdtTable, Error> {
+        self.load(CBDT, CbdtTable::new)
+    }
+
+    #[inline]
+    pub fn cblc_table(&self) -> Result<CblcTable, Error> {
+        self.load(CBLC, CblcTable::new)
+    }
+
+    #[inline]
+    pub fn cmap_table(&self) -> Result<CmapTable, Error> {
+        self.load(CMAP, |table| Ok(CmapTable::new(table)))
+    }
+
+    #[inline]
+    #[cfg(feature = "std")]
+    pub fn colr_table(&self) -> Result<ColrTable, Error> {
+        self.load(COLR, ColrTable::new)
     }
 
     #[inline]
-    pub fn cmap_table(&self) -> Result<CmapTable, ()> {
-        self.table(CMAP).and_then(|table| table.ok_or(()).map(CmapTable::new))
+    pub fn cpal_table(&self) -> Result<CpalTable, Error> {
+        self.load(CPAL, CpalTable::new)
     }
 
     #[inline]
-    pub fn glyf_table(&self) -> Result<GlyfTable, ()> {
-        self.table(GLYF).and_then(|table| table.ok_or(()).map(GlyfTable::new))
+    pub fn cvt_table(&self) -> Result<CvtTable, Error> {
+        self.load(CVT, |table| Ok(CvtTable::new(table)))
     }
 
     #[inline]
-    pub fn head_table(&self) -> Result<HeadTable, ()> {
-        self.table(HEAD).and_then(|table| table.ok_or(()).and_then(HeadTable::new))
+    pub fn ebdt_table(&self) -> Result<EbdtTable, Error> {
+        self.load(EBDT, EbdtTable::new)
     }
 
+    /// Returns the `EBLC` table, which has the same layout as `CBLC`.
     #[inline]
-    pub fn loca_table(&self, head_table: &HeadTable) -> Result<LocaTable, ()> {
-        let loca_table = try!(self.table(LOCA).and_then(|table| table.ok_or(())));
-        LocaTable::new(loca_table, head_table)
+    pub fn eblc_table(&self) -> Result<CblcTable, Error> {
+        self.load(EBLC, CblcTable::new)
+    }
+
+    #[inline]
+    pub fn ebsc_table(&self) -> Result<EbscTable, Error> {
+        self.load(EBSC, EbscTable::new)
+    }
+
+    /// Returns the font program, the TrueType bytecode run once when a font is loaded.
+    #[inline]
+    pub fn fpgm_table(&self) -> Result<FontTable, Error> {
+        self.load(FPGM, Ok)
+    }
+
+    #[inline]
+    pub fn gasp_table(&self) -> Result<GaspTable, Error> {
+        self.load(GASP, GaspTable::new)
+    }
+
+    #[inline]
+    pub fn glyf_table(&self) -> Result<GlyfTable, Error> {
+        self.load(GLYF, |table| Ok(GlyfTable::new(table)))
+    }
+
+    #[inline]
+    pub fn gsub_table(&self) -> Result<GsubTable, Error> {
+        self.load(GSUB, GsubTable::new)
+    }
+
+    #[inline]
+    pub fn head_table(&self) -> Result<HeadTable, Error> {
+        self.load(HEAD, HeadTable::new)
+    }
+
+    #[inline]
+    pub fn hhea_table(&self) -> Result<HheaTable, Error> {
+        self.load(HHEA, HheaTable::new)
+    }
+
+    #[inline]
+    pub fn hmtx_table(&self, hhea_table: &HheaTable) -> Result<HmtxTable, Error> {
+        self.load(HMTX, |table| HmtxTable::new(table, hhea_table))
+    }
+
+    #[inline]
+    pub fn loca_table(&self, head_table: &HeadTable) -> Result<LocaTable, Error> {
+        self.load(LOCA, |table| LocaTable::new(table, head_table))
+    }
+
+    #[inline]
+    pub fn maxp_table(&self) -> Result<MaxpTable, Error> {
+        self.load(MAXP, MaxpTable::new)
+    }
+
+    /// Returns the control value program, the TrueType bytecode run whenever the size changes.
+    #[inline]
+    pub fn prep_table(&self) -> Result<FontTable, Error> {
+        self.load(PREP, Ok)
+    }
+
+    #[inline]
+    pub fn sbix_table(&self, maxp_table: &MaxpTable) -> Result<SbixTable, Error> {
+        self.load(SBIX, |table| SbixTable::new(table, maxp_table))
+    }
+
+    #[inline]
+    #[cfg(feature = "std")]
+    pub fn svg_table(&self) -> Result<SvgTable, Error> {
+        self.load(SVG, SvgTable::new)
+    }
+
+    /// Returns the `vhea` table, which has the same layout as `hhea`.
+    #[inline]
+    pub fn vhea_table(&self) -> Result<HheaTable, Error> {
+        self.load(VHEA, HheaTable::new)
+    }
+
+    /// Returns the `vmtx` table, which has the same layout as `hmtx`.
+    #[inline]
+    pub fn vmtx_table(&self, vhea_table: &HheaTable) -> Result<HmtxTable, Error> {
+        self.load(VMTX, |table| HmtxTable::new(table, vhea_table))
     }
 }
 
+/// Parses a table with `parse`, attributing any read past the end of the table, or any
+/// unsupported version, to that table. Some parsers handle several tables with the same layout,
+/// like `hhea` and `vhea`, so they can't know which.
+fn parse_table<'b, T, F>(table_id: u32, table: Option<FontTable<'b>>, parse: F)
+                         -> Result<T, Error>
+                         where F: FnOnce(FontTable<'b>) -> Result<T, Error> {
+    let table = match table {
+        Some(table) => table,
+        None => return Err(Error::MissingTable(table_id)),
+    };
+    let length = table.bytes.len();
+    parse(table).map_err(|error| {
+        match error {
+            Error::UnsupportedFormat { format, .. } => {
+                Error::UnsupportedFormat {
+                    table: table_id,
+                    format: format,
+                }
+            }
+            error => error.in_table(table_id, length),
+        }
+    })
+}
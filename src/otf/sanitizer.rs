@@ -0,0 +1,722 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Rewrites untrusted fonts into clean sfnt files, in the manner of the OpenType Sanitizer.
+//!
+//! `sanitize` validates each table this crate understands and copies only those that pass into a
+//! new font. Tables it doesn't understand, like `GSUB`, are dropped, so the result is always a
+//! TrueType-flavored sfnt. Glyphs whose outlines don't parse are emptied, and `loca` is rewritten
+//! to match. The output has a sorted table directory and correct checksums.
+//!
+//! Only `head`, `maxp`, `hhea`, and `hmtx` are required; if any of them is missing or invalid,
+//! the font is rejected with the error that parsing it produced. So is a font whose `glyf` and
+//! `loca` tables can't be rebuilt, since dropping them would leave it without outlines, and a font
+//! with CFF outlines, which this crate can't validate. Any other table that fails validation is
+//! dropped along with the tables that depend on it.
+
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
+use error::Error;
+use otf::cblc::{BitmapLocation, CblcTable};
+use otf::colr::{ColrTable, FOREGROUND_PALETTE_INDEX, Paint, PaintOffset};
+use otf::ebdt::EbdtGlyphData;
+use otf::head::HeadTable;
+use otf::loca::{self, LocaTable};
+use otf::maxp::MaxpTable;
+use otf::writer::FontBuilder;
+use otf::{CBDT, CBLC, CMAP, COLR, CPAL, CVT, EBDT, EBLC, EBSC, FPGM, GASP, GLYF, HEAD, HHEA};
+use otf::{HMTX, LOCA, MAXP, NAME, OS_2, POST, PREP, SBIX, SVG, VHEA};
+use otf::{FontData, FontTable, SFNT_VERSION_OTTO, Tag, VMTX};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::mem;
+use std::u16;
+use util::Jump;
+
+/// The deepest a paint graph may nest, as `paint::ColorGlyphRenderer` allows.
+const MAX_PAINT_DEPTH: u32 = 64;
+
+const HEAD_INDEX_TO_LOC_FORMAT_OFFSET: usize = 50;
+
+const CMAP_ENCODING_RECORD_SIZE: usize = 8;
+const CMAP_SEQUENTIAL_MAP_GROUP_SIZE: usize = 12;
+const CMAP_MAX_CODEPOINT: u32 = 0x10ffff;
+
+const NAME_RECORD_SIZE: usize = 12;
+const NAME_LANG_TAG_RECORD_SIZE: usize = 4;
+
+const POST_HEADER_SIZE: usize = 32;
+const POST_STANDARD_GLYPH_NAME_COUNT: u16 = 258;
+
+/// Validates the tables of a font and serializes the valid ones into a new sfnt.
+///
+/// Returns the new font, or the reason the font was rejected.
+pub fn sanitize(font: &FontData) -> Result<Vec<u8>, Error> {
+    if try!(has_cff_outlines(font)) {
+        return Err(Error::UnsupportedSfntVersion(SFNT_VERSION_OTTO))
+    }
+
+    let head_table = try!(font.head_table());
+    let maxp_table = try!(font.maxp_table());
+    let hhea_table = try!(font.hhea_table());
+    let hmtx_table = try!(font.hmtx_table(&hhea_table));
+    let num_glyphs = maxp_table.num_glyphs;
+    for glyph_id in 0..num_glyphs {
+        try!(hmtx_table.metrics(glyph_id));
+    }
+
+    let mut tables = Tables {
+        font: *font,
+        builder: FontBuilder::new(),
+    };
+    for &tag in &[MAXP, HHEA, HMTX] {
+        try!(tables.copy(tag));
+    }
+
+    // Rebuild the outlines, noting which `loca` format they need. Fonts with only bitmap glyphs
+    // have neither table.
+    let mut head = try!(tables.data(HEAD)).to_vec();
+    if try!(font.table(Tag(GLYF))).is_some() || try!(font.table(Tag(LOCA))).is_some() {
+        let (glyf, loca, index_to_loc_format) =
+            try!(rebuild_glyphs(font, &head_table, num_glyphs));
+        BigEndian::write_i16(&mut head[HEAD_INDEX_TO_LOC_FORMAT_OFFSET..], index_to_loc_format);
+        tables.insert(GLYF, Cow::Owned(glyf));
+        tables.insert(LOCA, Cow::Owned(loca));
+    }
+    tables.insert(HEAD, Cow::Owned(head));
+
+    if let Ok(cmap) = rebuild_cmap(font) {
+        tables.insert(CMAP, Cow::Owned(cmap))
+    }
+
+    // Hinting data. The programs run in a sandboxed interpreter, so their contents aren't
+    // checked.
+    if tables.data(CVT).map(|cvt| cvt.len() % 2 == 0).unwrap_or(false) {
+        try!(tables.copy(CVT))
+    }
+    try!(tables.copy(FPGM));
+    try!(tables.copy(PREP));
+    if font.gasp_table().is_ok() {
+        try!(tables.copy(GASP))
+    }
+
+    // Tables this crate doesn't parse, but that other engines need.
+    if tables.data(NAME).and_then(check_name).is_ok() {
+        try!(tables.copy(NAME))
+    }
+    if tables.data(OS_2).and_then(check_os_2).is_ok() {
+        try!(tables.copy(OS_2))
+    }
+    if tables.data(POST).and_then(|post| check_post(post, num_glyphs)).is_ok() {
+        try!(tables.copy(POST))
+    }
+
+    // Vertical metrics come as a pair.
+    if check_vertical_metrics(font, num_glyphs).is_ok() {
+        try!(tables.copy(VHEA));
+        try!(tables.copy(VMTX))
+    }
+
+    // Color glyphs. `COLR` needs the palettes in `CPAL`.
+    if check_cpal(font).is_ok() {
+        try!(tables.copy(CPAL));
+        if check_colr(font, num_glyphs).is_ok() {
+            try!(tables.copy(COLR))
+        }
+    }
+
+    // Bitmaps. Each location table comes with its data table, and `EBSC` refers to the strikes
+    // in `EBLC`.
+    if check_cbdt(font, num_glyphs).is_ok() {
+        try!(tables.copy(CBLC));
+        try!(tables.copy(CBDT))
+    }
+    if check_ebdt(font, num_glyphs).is_ok() {
+        try!(tables.copy(EBLC));
+        try!(tables.copy(EBDT));
+        if font.ebsc_table().and_then(|ebsc| ebsc.scales()).is_ok() {
+            try!(tables.copy(EBSC))
+        }
+    }
+    if check_sbix(font, &maxp_table).is_ok() {
+        try!(tables.copy(SBIX))
+    }
+    if check_svg(font, num_glyphs).is_ok() {
+        try!(tables.copy(SVG))
+    }
+
+    tables.builder.build()
+}
+
+// The tables of the sanitized font.
+struct Tables<'a> {
+    font: FontData<'a>,
+    builder: FontBuilder<'a>,
+}
+
+impl<'a> Tables<'a> {
+    // Returns the data of a table in the original font.
+    fn data(&self, tag: u32) -> Result<&'a [u8], Error> {
+        match try!(self.font.table(Tag(tag))) {
+            Some(table) => Ok(table.bytes),
+            None => Err(Error::MissingTable(tag)),
+        }
+    }
+
+    // Copies a table of the original font unchanged, if it has one.
+    fn copy(&mut self, tag: u32) -> Result<(), Error> {
+        if let Some(table) = try!(self.font.table(Tag(tag))) {
+            self.insert(tag, Cow::Borrowed(table.bytes))
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn insert(&mut self, tag: u32, data: Cow<'a, [u8]>) {
+        self.builder.add_table(Tag(tag), data)
+    }
+}
+
+// Returns true if the font has CFF outlines, in either its sfnt version or its tables.
+fn has_cff_outlines(font: &FontData) -> Result<bool, Error> {
+    let mut reader = font.bytes;
+    try!(reader.jump(font.header_offset));
+    if try!(reader.read_u32::<BigEndian>()) == SFNT_VERSION_OTTO {
+        return Ok(true)
+    }
+    for tag in &[b"CFF ", b"CFF2"] {
+        if try!(font.table(Tag::new(tag))).is_some() {
+            return Ok(true)
+        }
+    }
+    Ok(false)
+}
+
+// Copies every glyph whose outline parses into a new `glyf` table, leaving the others empty, and
+// builds the `loca` table for it. Returns the tables and the `indexToLocFormat` they use.
+fn rebuild_glyphs(font: &FontData, head_table: &HeadTable, num_glyphs: u16)
+                  -> Result<(Vec<u8>, Vec<u8>, i16), Error> {
+    // Only look at the entries of `loca` for glyphs in the font, so that composite glyphs can't
+    // refer to glyphs past the end.
+    let loca_table = try!(font.loca_table(head_table));
+    let entry_size = if loca_table.long { 4 } else { 2 };
+    let loca_length = (num_glyphs as usize + 1) * entry_size;
+    let loca_table = match try!(font.table(Tag(LOCA))) {
+        Some(ref table) if table.bytes.len() >= loca_length => {
+            try!(LocaTable::new(FontTable { bytes: &table.bytes[0..loca_length] }, head_table))
+        }
+        _ => return Err(Error::Malformed),
+    };
+    let glyf_table = try!(font.glyf_table());
+
+    let mut glyf = vec![];
+    let mut offsets = Vec::with_capacity(num_glyphs as usize + 1);
+    for glyph_id in 0..(num_glyphs as u32) {
+        offsets.push(glyf.len());
+        let data = match glyf_table.glyph_data(&loca_table, glyph_id) {
+            Ok(Some(data)) => data,
+            Ok(None) | Err(_) => continue,
+        };
+        if glyf_table.for_each_point(&loca_table, glyph_id, |_| {}).is_err() {
+            continue
+        }
+        glyf.extend_from_slice(data);
+        while glyf.len() % 4 != 0 {
+            glyf.push(0)
+        }
+    }
+    offsets.push(glyf.len());
+
+    let (loca, index_to_loc_format) = loca::build_loca_table(&offsets);
+    Ok((glyf, loca, index_to_loc_format))
+}
+
+// Builds a new `cmap` table from the subtables of the original that are valid, dropping the
+// rest. Fails if none are.
+fn rebuild_cmap(font: &FontData) -> Result<Vec<u8>, Error> {
+    let cmap = match try!(font.table(Tag(CMAP))) {
+        Some(table) => table.bytes,
+        None => return Err(Error::MissingTable(CMAP)),
+    };
+
+    let mut reader = cmap;
+    let version = try!(reader.read_u16::<BigEndian>());
+    if version != 0 {
+        return Err(Error::UnsupportedFormat {
+            table: CMAP,
+            format: version as u32,
+        })
+    }
+
+    // Collect the valid subtables, keeping one copy of each that several records share.
+    let num_tables = try!(reader.read_u16::<BigEndian>());
+    let mut records = BTreeMap::new();
+    let mut subtables: Vec<(u32, &[u8])> = vec![];
+    for _ in 0..num_tables {
+        let platform_id = try!(reader.read_u16::<BigEndian>());
+        let encoding_id = try!(reader.read_u16::<BigEndian>());
+        let offset = try!(reader.read_u32::<BigEndian>());
+        if records.contains_key(&(platform_id, encoding_id)) {
+            continue
+        }
+        let index = match subtables.iter().position(|&(other, _)| other == offset) {
+            Some(index) => index,
+            None => {
+                let mut subtable = cmap;
+                if subtable.jump(offset as usize).is_err() {
+                    continue
+                }
+                match cmap_subtable(subtable) {
+                    Ok(subtable) => subtables.push((offset, subtable)),
+                    Err(_) => continue,
+                }
+                subtables.len() - 1
+            }
+        };
+        records.insert((platform_id, encoding_id), index);
+    }
+    if records.is_empty() {
+        return Err(Error::Malformed)
+    }
+
+    // Write the encoding records, sorted by platform and encoding, followed by the subtables in
+    // the order the records refer to them, so that sanitizing the result doesn't change it.
+    let mut order: Vec<usize> = vec![];
+    for &index in records.values() {
+        if !order.contains(&index) {
+            order.push(index)
+        }
+    }
+    let mut subtable_offsets = vec![0; subtables.len()];
+    let mut offset = mem::size_of::<u16>() * 2 + records.len() * CMAP_ENCODING_RECORD_SIZE;
+    for &index in &order {
+        subtable_offsets[index] = offset as u32;
+        offset += (subtables[index].1.len() + 3) & !3
+    }
+
+    let mut new_cmap = Vec::with_capacity(offset);
+    new_cmap.write_u16::<BigEndian>(0).unwrap();
+    new_cmap.write_u16::<BigEndian>(records.len() as u16).unwrap();
+    for (&(platform_id, encoding_id), &index) in &records {
+        new_cmap.write_u16::<BigEndian>(platform_id).unwrap();
+        new_cmap.write_u16::<BigEndian>(encoding_id).unwrap();
+        new_cmap.write_u32::<BigEndian>(subtable_offsets[index]).unwrap();
+    }
+    for &index in &order {
+        new_cmap.extend_from_slice(subtables[index].1);
+        while new_cmap.len() % 4 != 0 {
+            new_cmap.push(0)
+        }
+    }
+    Ok(new_cmap)
+}
+
+// Validates a `cmap` subtable of format 0, 4, 6, or 12, starting at the start of `data`.
+// Returns the subtable, trimmed to its length.
+fn cmap_subtable(data: &[u8]) -> Result<&[u8], Error> {
+    let mut reader = data;
+    let format = try!(reader.read_u16::<BigEndian>());
+    let length = match format {
+        0 | 4 | 6 => try!(reader.read_u16::<BigEndian>()) as usize,
+        12 => {
+            try!(reader.jump(mem::size_of::<u16>()));
+            try!(reader.read_u32::<BigEndian>()) as usize
+        }
+        _ => {
+            return Err(Error::UnsupportedFormat {
+                table: CMAP,
+                format: format as u32,
+            })
+        }
+    };
+    if length > data.len() {
+        return Err(Error::Malformed)
+    }
+    let subtable = &data[0..length];
+
+    match format {
+        // Byte encoding table: 256 one-byte glyph IDs.
+        0 => {
+            if length != mem::size_of::<u16>() * 3 + 256 {
+                return Err(Error::Malformed)
+            }
+        }
+
+        // Segment mapping to delta values.
+        4 => {
+            let mut reader = subtable;
+            try!(reader.jump(mem::size_of::<u16>() * 3));
+            let seg_count_x2 = try!(reader.read_u16::<BigEndian>()) as usize;
+            if seg_count_x2 == 0 || seg_count_x2 % 2 != 0 {
+                return Err(Error::Malformed)
+            }
+            let seg_count = seg_count_x2 / 2;
+
+            let end_codes_offset = mem::size_of::<u16>() * 7;
+            let start_codes_offset = end_codes_offset + seg_count_x2 + mem::size_of::<u16>();
+            let id_range_offsets_offset = start_codes_offset + seg_count_x2 * 2;
+            if id_range_offsets_offset + seg_count_x2 > length {
+                return Err(Error::Malformed)
+            }
+
+            let mut previous_end_code = None;
+            for segment in 0..seg_count {
+                let end_code = BigEndian::read_u16(&subtable[(end_codes_offset +
+                                                              segment * 2)..]);
+                let start_code = BigEndian::read_u16(&subtable[(start_codes_offset +
+                                                                segment * 2)..]);
+                let id_range_offset_position = id_range_offsets_offset + segment * 2;
+                let id_range_offset = BigEndian::read_u16(&subtable[id_range_offset_position..]);
+                if start_code > end_code || previous_end_code.map_or(false, |previous_end_code| {
+                        start_code <= previous_end_code
+                    }) {
+                    return Err(Error::Malformed)
+                }
+
+                // The glyph IDs of the segment must all be in the subtable.
+                if id_range_offset != 0 {
+                    let end = id_range_offset_position + id_range_offset as usize +
+                        (end_code - start_code) as usize * 2 + mem::size_of::<u16>();
+                    if end > length {
+                        return Err(Error::Malformed)
+                    }
+                }
+                previous_end_code = Some(end_code)
+            }
+            if previous_end_code != Some(u16::MAX) {
+                return Err(Error::Malformed)
+            }
+        }
+
+        // Trimmed table mapping.
+        6 => {
+            let mut reader = subtable;
+            try!(reader.jump(mem::size_of::<u16>() * 3));
+            let first_code = try!(reader.read_u16::<BigEndian>()) as usize;
+            let entry_count = try!(reader.read_u16::<BigEndian>()) as usize;
+            if first_code + entry_count > u16::MAX as usize + 1 ||
+                    mem::size_of::<u16>() * (5 + entry_count) > length {
+                return Err(Error::Malformed)
+            }
+        }
+
+        // Segmented coverage.
+        _ => {
+            let mut reader = subtable;
+            try!(reader.jump(mem::size_of::<u16>() * 2 + mem::size_of::<u32>() * 2));
+            let num_groups = try!(reader.read_u32::<BigEndian>()) as usize;
+            if num_groups > reader.len() / CMAP_SEQUENTIAL_MAP_GROUP_SIZE {
+                return Err(Error::Malformed)
+            }
+            let mut previous_end_char_code = None;
+            for _ in 0..num_groups {
+                let start_char_code = try!(reader.read_u32::<BigEndian>());
+                let end_char_code = try!(reader.read_u32::<BigEndian>());
+                try!(reader.jump(mem::size_of::<u32>()));
+                if start_char_code > end_char_code || end_char_code > CMAP_MAX_CODEPOINT ||
+                        previous_end_char_code.map_or(false, |previous_end_char_code| {
+                            start_char_code <= previous_end_char_code
+                        }) {
+                    return Err(Error::Malformed)
+                }
+                previous_end_char_code = Some(end_char_code)
+            }
+        }
+    }
+
+    Ok(subtable)
+}
+
+// Checks that every name record's string lies within the string storage.
+fn check_name(name: &[u8]) -> Result<(), Error> {
+    let mut reader = name;
+    let format = try!(reader.read_u16::<BigEndian>());
+    if format > 1 {
+        return Err(Error::UnsupportedFormat {
+            table: NAME,
+            format: format as u32,
+        })
+    }
+    let count = try!(reader.read_u16::<BigEndian>()) as usize;
+    let storage_offset = try!(reader.read_u16::<BigEndian>()) as usize;
+    if storage_offset > name.len() {
+        return Err(Error::Malformed)
+    }
+    let storage = &name[storage_offset..];
+
+    let check_string = |reader: &mut &[u8]| -> Result<(), Error> {
+        let length = try!(reader.read_u16::<BigEndian>()) as usize;
+        let offset = try!(reader.read_u16::<BigEndian>()) as usize;
+        if offset + length > storage.len() {
+            return Err(Error::Malformed)
+        }
+        Ok(())
+    };
+
+    for _ in 0..count {
+        try!(reader.jump(NAME_RECORD_SIZE - mem::size_of::<u16>() * 2));
+        try!(check_string(&mut reader))
+    }
+    if format == 1 {
+        let lang_tag_count = try!(reader.read_u16::<BigEndian>()) as usize;
+        if reader.len() < lang_tag_count * NAME_LANG_TAG_RECORD_SIZE {
+            return Err(Error::UnexpectedEof)
+        }
+        for _ in 0..lang_tag_count {
+            try!(check_string(&mut reader))
+        }
+    }
+    Ok(())
+}
+
+// Checks that `OS/2` is as long as its version requires.
+fn check_os_2(os_2: &[u8]) -> Result<(), Error> {
+    let version = try!((&os_2[..]).read_u16::<BigEndian>());
+    let minimum_length = match version {
+        0 => 78,
+        1 => 86,
+        2...4 => 96,
+        5 => 100,
+        _ => {
+            return Err(Error::UnsupportedFormat {
+                table: OS_2,
+                format: version as u32,
+            })
+        }
+    };
+    if os_2.len() < minimum_length {
+        return Err(Error::UnexpectedEof)
+    }
+    Ok(())
+}
+
+// Checks `post`, including the glyph names of version 2 tables. Version 2.5 is deprecated and
+// isn't accepted.
+fn check_post(post: &[u8], num_glyphs: u16) -> Result<(), Error> {
+    if post.len() < POST_HEADER_SIZE {
+        return Err(Error::UnexpectedEof)
+    }
+    let version = BigEndian::read_u32(post);
+    match version {
+        0x00010000 | 0x00030000 => return Ok(()),
+        0x00020000 => {}
+        _ => {
+            return Err(Error::UnsupportedFormat {
+                table: POST,
+                format: version,
+            })
+        }
+    }
+
+    let mut reader = &post[POST_HEADER_SIZE..];
+    if try!(reader.read_u16::<BigEndian>()) != num_glyphs {
+        return Err(Error::Malformed)
+    }
+    let mut max_name_index = None;
+    for _ in 0..num_glyphs {
+        let glyph_name_index = try!(reader.read_u16::<BigEndian>());
+        if glyph_name_index >= POST_STANDARD_GLYPH_NAME_COUNT {
+            let name_index = glyph_name_index - POST_STANDARD_GLYPH_NAME_COUNT;
+            if max_name_index.map_or(true, |max_name_index| name_index > max_name_index) {
+                max_name_index = Some(name_index)
+            }
+        }
+    }
+
+    // Every name a glyph refers to must be present, as a Pascal string.
+    if let Some(max_name_index) = max_name_index {
+        for _ in 0..(max_name_index as u32 + 1) {
+            let length = try!(reader.read_u8());
+            try!(reader.jump(length as usize))
+        }
+    }
+    Ok(())
+}
+
+fn check_vertical_metrics(font: &FontData, num_glyphs: u16) -> Result<(), Error> {
+    let vhea_table = try!(font.vhea_table());
+    let vmtx_table = try!(font.vmtx_table(&vhea_table));
+    for glyph_id in 0..num_glyphs {
+        try!(vmtx_table.metrics(glyph_id));
+    }
+    Ok(())
+}
+
+fn check_cpal(font: &FontData) -> Result<(), Error> {
+    let cpal_table = try!(font.cpal_table());
+    for palette_index in 0..cpal_table.palette_count() {
+        try!(cpal_table.palette(palette_index));
+        try!(cpal_table.palette_type(palette_index));
+    }
+    Ok(())
+}
+
+// Checks the version 0 layers and the version 1 paint graphs of every color glyph.
+fn check_colr(font: &FontData, num_glyphs: u16) -> Result<(), Error> {
+    let colr_table = try!(font.colr_table());
+    let cpal_table = try!(font.cpal_table());
+    let mut checker = PaintChecker {
+        colr_table: &colr_table,
+        num_glyphs: num_glyphs,
+        palette_entry_count: cpal_table.palette_entry_count(),
+        checked: HashSet::new(),
+    };
+
+    for glyph_id in try!(colr_table.base_glyph_ids()) {
+        try!(checker.check_glyph_id(glyph_id));
+        for layer in try!(colr_table.layers(glyph_id)).unwrap_or(vec![]) {
+            try!(checker.check_glyph_id(layer.glyph_id));
+            try!(checker.check_palette_index(layer.palette_index));
+        }
+    }
+
+    for glyph_id in 0..num_glyphs {
+        if let Some(paint) = try!(colr_table.base_glyph_paint(glyph_id)) {
+            try!(checker.check_paint(paint, 1));
+        }
+        try!(colr_table.clip_box(glyph_id));
+    }
+    Ok(())
+}
+
+// Walks paint graphs, visiting each paint once so that shared subgraphs don't take exponential
+// time. A cycle nests forever, so it fails the depth check.
+struct PaintChecker<'a, 'b: 'a> {
+    colr_table: &'a ColrTable<'b>,
+    num_glyphs: u16,
+    palette_entry_count: u16,
+    checked: HashSet<u32>,
+}
+
+impl<'a, 'b> PaintChecker<'a, 'b> {
+    fn check_paint(&mut self, paint: PaintOffset, depth: u32) -> Result<(), Error> {
+        if depth > MAX_PAINT_DEPTH {
+            return Err(Error::Malformed)
+        }
+        if self.checked.contains(&paint.0) {
+            return Ok(())
+        }
+
+        match try!(self.colr_table.paint(paint)) {
+            Paint::ColrLayers { first_layer_index, layer_count } => {
+                for layer_index in 0..(layer_count as u32) {
+                    let layer_index = try!(first_layer_index.checked_add(layer_index)
+                                                            .ok_or(Error::Malformed));
+                    let layer_paint = try!(self.colr_table.layer_paint(layer_index));
+                    try!(self.check_paint(layer_paint, depth + 1))
+                }
+            }
+            Paint::Solid { palette_index, .. } => try!(self.check_palette_index(palette_index)),
+            Paint::LinearGradient { ref color_line, .. } |
+            Paint::RadialGradient { ref color_line, .. } |
+            Paint::SweepGradient { ref color_line, .. } => {
+                for stop in &color_line.stops {
+                    try!(self.check_palette_index(stop.palette_index))
+                }
+            }
+            Paint::Glyph { paint, glyph_id } => {
+                try!(self.check_glyph_id(glyph_id));
+                try!(self.check_paint(paint, depth + 1))
+            }
+            Paint::ColrGlyph { glyph_id } => {
+                try!(self.check_glyph_id(glyph_id));
+                if let Some(paint) = try!(self.colr_table.base_glyph_paint(glyph_id)) {
+                    try!(self.check_paint(paint, depth + 1))
+                }
+            }
+            Paint::Transform { paint, .. } => try!(self.check_paint(paint, depth + 1)),
+            Paint::Composite { source, backdrop, .. } => {
+                try!(self.check_paint(source, depth + 1));
+                try!(self.check_paint(backdrop, depth + 1))
+            }
+        }
+
+        self.checked.insert(paint.0);
+        Ok(())
+    }
+
+    fn check_glyph_id(&self, glyph_id: u16) -> Result<(), Error> {
+        if glyph_id >= self.num_glyphs {
+            return Err(Error::InvalidGlyphId(glyph_id as u32))
+        }
+        Ok(())
+    }
+
+    fn check_palette_index(&self, palette_index: u16) -> Result<(), Error> {
+        if palette_index >= self.palette_entry_count && palette_index != FOREGROUND_PALETTE_INDEX {
+            return Err(Error::Malformed)
+        }
+        Ok(())
+    }
+}
+
+fn check_cbdt(font: &FontData, num_glyphs: u16) -> Result<(), Error> {
+    let cbdt_table = try!(font.cbdt_table());
+    check_bitmap_locations(&try!(font.cblc_table()), num_glyphs, |location| {
+        cbdt_table.glyph(location).map(|_| ())
+    })
+}
+
+fn check_ebdt(font: &FontData, num_glyphs: u16) -> Result<(), Error> {
+    let ebdt_table = try!(font.ebdt_table());
+    check_bitmap_locations(&try!(font.eblc_table()), num_glyphs, |location| {
+        if let EbdtGlyphData::Composite(components) = try!(ebdt_table.glyph(location)).data {
+            for component in components {
+                if component.glyph_id >= num_glyphs {
+                    return Err(Error::InvalidGlyphId(component.glyph_id as u32))
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+// Checks that every strike covers only glyphs in the font, and calls `check_glyph` with the
+// location of every bitmap.
+fn check_bitmap_locations<F>(location_table: &CblcTable, num_glyphs: u16, mut check_glyph: F)
+                             -> Result<(), Error>
+                             where F: FnMut(&BitmapLocation) -> Result<(), Error> {
+    for strike in try!(location_table.strikes()) {
+        if strike.start_glyph_id > strike.end_glyph_id || strike.end_glyph_id >= num_glyphs {
+            return Err(Error::Malformed)
+        }
+        for glyph_id in strike.start_glyph_id..(strike.end_glyph_id + 1) {
+            if let Some(location) = try!(location_table.glyph_location(&strike, glyph_id)) {
+                try!(check_glyph(&location))
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_sbix(font: &FontData, maxp_table: &MaxpTable) -> Result<(), Error> {
+    let sbix_table = try!(font.sbix_table(maxp_table));
+    for strike in try!(sbix_table.strikes()) {
+        for glyph_id in 0..maxp_table.num_glyphs {
+            try!(sbix_table.glyph(&strike, glyph_id));
+        }
+    }
+    Ok(())
+}
+
+// Checks that every SVG document can be read, decompressing each one once.
+fn check_svg(font: &FontData, num_glyphs: u16) -> Result<(), Error> {
+    let svg_table = try!(font.svg_table());
+    let mut glyph_id = 0;
+    while glyph_id < num_glyphs as u32 {
+        match try!(svg_table.document(glyph_id as u16)) {
+            Some(document) => {
+                try!(document.text());
+                glyph_id = document.end_glyph_id as u32 + 1
+            }
+            None => glyph_id += 1,
+        }
+    }
+    Ok(())
+}
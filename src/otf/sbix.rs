@@ -0,0 +1,173 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `sbix` table, Apple's format for bitmap glyphs stored as PNG, JPEG, or TIFF images.
+
+use byteorder::BigEndian;
+use error::Error;
+use euclid::Point2D;
+use otf::{SBIX, FontTable};
+use otf::maxp::MaxpTable;
+use std::mem;
+use std::vec::Vec;
+use util::{Jump, ReadBytes};
+
+pub const GRAPHIC_TYPE_PNG: u32 = ((b'p' as u32) << 24) |
+                                  ((b'n' as u32) << 16) |
+                                  ((b'g' as u32) << 8)  |
+                                   (b' ' as u32);
+pub const GRAPHIC_TYPE_JPG: u32 = ((b'j' as u32) << 24) |
+                                  ((b'p' as u32) << 16) |
+                                  ((b'g' as u32) << 8)  |
+                                   (b' ' as u32);
+pub const GRAPHIC_TYPE_TIFF: u32 = ((b't' as u32) << 24) |
+                                   ((b'i' as u32) << 16) |
+                                   ((b'f' as u32) << 8)  |
+                                    (b'f' as u32);
+/// The glyph reuses the image of the glyph whose ID is the data.
+const GRAPHIC_TYPE_DUPE: u32 = ((b'd' as u32) << 24) |
+                               ((b'u' as u32) << 16) |
+                               ((b'p' as u32) << 8)  |
+                                (b'e' as u32);
+
+/// Set if the glyph outlines should be drawn on top of the bitmaps.
+const FLAG_DRAW_OUTLINES: u16 = 1 << 1;
+
+const GLYPH_HEADER_SIZE: usize = 8;
+
+/// The images for one pixel size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SbixStrike {
+    pub ppem: u16,
+    /// The resolution the images were designed for, in pixels per inch.
+    pub ppi: u16,
+    offset: u32,
+}
+
+/// The image of one glyph in a strike.
+#[derive(Clone, Copy, Debug)]
+pub struct SbixGlyph<'a> {
+    /// The position of the bottom left corner of the image relative to the glyph origin, in
+    /// pixels, with y pointing up.
+    pub origin_offset: Point2D<i16>,
+    /// One of the `GRAPHIC_TYPE_` constants.
+    pub graphic_type: u32,
+    pub data: &'a [u8],
+}
+
+#[derive(Clone, Copy)]
+pub struct SbixTable<'a> {
+    pub table: FontTable<'a>,
+    pub flags: u16,
+    num_strikes: u32,
+    num_glyphs: u16,
+}
+
+impl<'a> SbixTable<'a> {
+    pub fn new(table: FontTable<'a>, maxp_table: &MaxpTable) -> Result<SbixTable<'a>, Error> {
+        let mut reader = table.bytes;
+        let version = try!(reader.read_u16::<BigEndian>());
+        if version != 1 {
+            return Err(Error::UnsupportedFormat {
+                table: SBIX,
+                format: version as u32,
+            })
+        }
+
+        let flags = try!(reader.read_u16::<BigEndian>());
+        let num_strikes = try!(reader.read_u32::<BigEndian>());
+        Ok(SbixTable {
+            table: table,
+            flags: flags,
+            num_strikes: num_strikes,
+            num_glyphs: maxp_table.num_glyphs,
+        })
+    }
+
+    /// Returns true if outlines should be drawn over the bitmaps.
+    #[inline]
+    pub fn draws_outlines(&self) -> bool {
+        (self.flags & FLAG_DRAW_OUTLINES) != 0
+    }
+
+    pub fn strikes(&self) -> Result<Vec<SbixStrike>, Error> {
+        let mut reader = self.table.bytes;
+        try!(reader.jump(mem::size_of::<u16>() * 2 + mem::size_of::<u32>()));
+
+        let mut strikes = vec![];
+        for _ in 0..self.num_strikes {
+            let offset = try!(reader.read_u32::<BigEndian>());
+            let mut strike_reader = self.table.bytes;
+            try!(strike_reader.jump(offset as usize));
+            let ppem = try!(strike_reader.read_u16::<BigEndian>());
+            let ppi = try!(strike_reader.read_u16::<BigEndian>());
+            strikes.push(SbixStrike {
+                ppem: ppem,
+                ppi: ppi,
+                offset: offset,
+            })
+        }
+        Ok(strikes)
+    }
+
+    /// Returns the image of the given glyph in the given strike, or `None` if it has none there.
+    pub fn glyph(&self, strike: &SbixStrike, glyph_id: u16)
+                 -> Result<Option<SbixGlyph<'a>>, Error> {
+        match try!(self.glyph_data(strike, glyph_id)) {
+            Some(glyph) if glyph.graphic_type == GRAPHIC_TYPE_DUPE => {
+                // Only follow one level of duplication, so that cycles can't hang us.
+                let mut data = glyph.data;
+                let original_glyph_id = try!(data.read_u16::<BigEndian>());
+                match try!(self.glyph_data(strike, original_glyph_id)) {
+                    Some(ref original) if original.graphic_type == GRAPHIC_TYPE_DUPE => {
+                        Err(Error::Malformed)
+                    }
+                    original => Ok(original),
+                }
+            }
+            glyph => Ok(glyph),
+        }
+    }
+
+    fn glyph_data(&self, strike: &SbixStrike, glyph_id: u16)
+                  -> Result<Option<SbixGlyph<'a>>, Error> {
+        if glyph_id >= self.num_glyphs {
+            return Err(Error::InvalidGlyphId(glyph_id as u32))
+        }
+
+        let mut reader = self.table.bytes;
+        try!(reader.jump(strike.offset as usize + mem::size_of::<u16>() * 2 +
+                         glyph_id as usize * mem::size_of::<u32>()));
+        let start = try!(reader.read_u32::<BigEndian>()) as usize;
+        let end = try!(reader.read_u32::<BigEndian>()) as usize;
+        if end == start {
+            return Ok(None)
+        }
+        if end < start + GLYPH_HEADER_SIZE {
+            return Err(Error::Malformed)
+        }
+
+        let mut reader = self.table.bytes;
+        try!(reader.jump(strike.offset as usize + start));
+        let origin_offset_x = try!(reader.read_i16::<BigEndian>());
+        let origin_offset_y = try!(reader.read_i16::<BigEndian>());
+        let graphic_type = try!(reader.read_u32::<BigEndian>());
+        let data_length = end - start - GLYPH_HEADER_SIZE;
+        if data_length > reader.len() {
+            return Err(Error::Malformed)
+        }
+
+        Ok(Some(SbixGlyph {
+            origin_offset: Point2D::new(origin_offset_x, origin_offset_y),
+            graphic_type: graphic_type,
+            data: &reader[0..data_length],
+        }))
+    }
+}
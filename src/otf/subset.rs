@@ -0,0 +1,400 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Subsetting: writing a new TrueType font with only some of the glyphs of another, for
+//! embedding in documents.
+//!
+//! The subset keeps `.notdef`, the requested glyphs, the glyphs of the requested codepoints, any
+//! glyphs that `GSUB` can substitute for those, and the components of composite glyphs. `glyf`,
+//! `loca`, `cmap`, `hmtx`, `hhea`, `maxp`, `post`, `name`, and the vertical metrics are rewritten
+//! for the new glyphs; `OS/2` and the hinting tables are copied. Layout tables refer to glyphs by
+//! ID, so they're only kept when glyph IDs are. Everything else is dropped.
+
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
+use error::Error;
+use otf::glyf::GlyfTable;
+use otf::hmtx::{self, GlyphMetrics, HmtxTable};
+use otf::loca::LocaTable;
+use otf::writer::{CmapBuilder, FontBuilder, GlyfBuilder, NameBuilder};
+use otf::{CMAP, CVT, FPGM, GASP, GDEF, GLYF, GPOS, GSUB, HEAD, HHEA, HMTX, LOCA, MAXP, NAME};
+use otf::{FontData, OS_2, POST, PREP, Tag, VHEA, VMTX};
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+use std::u16;
+use util::Jump;
+
+const HEAD_INDEX_TO_LOC_FORMAT_OFFSET: usize = 50;
+const HHEA_NUMBER_OF_METRICS_OFFSET: usize = 34;
+const MAXP_NUM_GLYPHS_OFFSET: usize = 4;
+const OS_2_FIRST_CHAR_INDEX_OFFSET: usize = 64;
+
+/// Name IDs up to this one, the copyright, family, style, unique ID, full name, version, and
+/// PostScript name, are kept. Names past it mostly describe the font for font pickers.
+const LAST_KEPT_NAME_ID: u16 = 6;
+/// Language IDs from here up refer to language tags, which the subset doesn't keep.
+const FIRST_LANG_TAG_LANGUAGE_ID: u16 = 0x8000;
+
+const POST_HEADER_SIZE: usize = 32;
+const POST_VERSION_2: u32 = 0x00020000;
+const POST_VERSION_3: u32 = 0x00030000;
+const POST_STANDARD_GLYPH_NAME_COUNT: u16 = 258;
+
+/// Chooses the glyphs of a subset.
+#[derive(Clone, Debug, Default)]
+pub struct SubsetBuilder {
+    /// If true, glyphs keep their IDs, and the glyphs in between that aren't kept are left
+    /// empty. The font is a little bigger, but `GSUB`, `GPOS`, and `GDEF` stay valid and are
+    /// kept.
+    pub retain_glyph_ids: bool,
+    codepoints: BTreeSet<u32>,
+    glyph_ids: BTreeSet<u16>,
+}
+
+/// A font made by `SubsetBuilder`.
+#[derive(Clone, Debug)]
+pub struct Subset {
+    /// The new font, as a TrueType sfnt.
+    pub data: Vec<u8>,
+    /// The ID in the original font of each glyph of the new one, or `None` for the empty glyphs
+    /// that fill the gaps when glyph IDs are retained.
+    pub original_glyph_ids: Vec<Option<u16>>,
+    new_glyph_ids: BTreeMap<u16, u16>,
+}
+
+impl Subset {
+    /// Returns the ID in the new font of a glyph of the original, or `None` if it wasn't kept.
+    #[inline]
+    pub fn glyph_id(&self, original_glyph_id: u16) -> Option<u16> {
+        self.new_glyph_ids.get(&original_glyph_id).cloned()
+    }
+}
+
+impl SubsetBuilder {
+    #[inline]
+    pub fn new() -> SubsetBuilder {
+        SubsetBuilder::default()
+    }
+
+    /// Keeps the glyph that `codepoint` maps to, and maps it in the new font too. Codepoints
+    /// the font doesn't map are ignored.
+    #[inline]
+    pub fn add_codepoint(&mut self, codepoint: u32) {
+        self.codepoints.insert(codepoint);
+    }
+
+    #[inline]
+    pub fn add_glyph_id(&mut self, glyph_id: u16) {
+        self.glyph_ids.insert(glyph_id);
+    }
+
+    /// Writes the subset of `font`. The font must have TrueType outlines, and every glyph kept
+    /// must be readable.
+    pub fn build(&self, font: &FontData) -> Result<Subset, Error> {
+        let head_table = try!(font.head_table());
+        let maxp_table = try!(font.maxp_table());
+        let hhea_table = try!(font.hhea_table());
+        let hmtx_table = try!(font.hmtx_table(&hhea_table));
+        let loca_table = try!(font.loca_table(&head_table));
+        let glyf_table = try!(font.glyf_table());
+        let num_glyphs = maxp_table.num_glyphs;
+
+        // Gather `.notdef`, the requested glyphs, and the glyphs of the requested codepoints.
+        let mut glyph_ids = BTreeSet::new();
+        glyph_ids.insert(0);
+        for &glyph_id in &self.glyph_ids {
+            if glyph_id >= num_glyphs {
+                return Err(Error::InvalidGlyphId(glyph_id as u32))
+            }
+            glyph_ids.insert(glyph_id);
+        }
+        let mut mappings = vec![];
+        if !self.codepoints.is_empty() {
+            let cmap_table = try!(font.cmap_table());
+            for &codepoint in &self.codepoints {
+                match try!(cmap_table.glyph_for_codepoint(codepoint)) {
+                    Some(glyph_id) if glyph_id < num_glyphs => {
+                        mappings.push((codepoint, glyph_id));
+                        glyph_ids.insert(glyph_id);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // Add the glyphs that substitutions can produce, and then the components of composite
+        // glyphs.
+        if try!(font.table(Tag(GSUB))).is_some() {
+            try!(try!(font.gsub_table()).closure(&mut glyph_ids));
+            glyph_ids = glyph_ids.into_iter().filter(|&glyph_id| glyph_id < num_glyphs).collect()
+        }
+        let mut pending: Vec<_> = glyph_ids.iter().cloned().collect();
+        while let Some(glyph_id) = pending.pop() {
+            for component in try!(glyf_table.components(&loca_table, glyph_id as u32)) {
+                if component.glyph_id >= num_glyphs {
+                    return Err(Error::InvalidGlyphId(component.glyph_id as u32))
+                }
+                if glyph_ids.insert(component.glyph_id) {
+                    pending.push(component.glyph_id)
+                }
+            }
+        }
+
+        // Number the glyphs.
+        let original_glyph_ids: Vec<_> = if self.retain_glyph_ids {
+            let last_glyph_id = *glyph_ids.iter().next_back().unwrap() as u32;
+            (0..(last_glyph_id + 1)).map(|glyph_id| {
+                let glyph_id = glyph_id as u16;
+                if glyph_ids.contains(&glyph_id) { Some(glyph_id) } else { None }
+            }).collect()
+        } else {
+            glyph_ids.iter().map(|&glyph_id| Some(glyph_id)).collect()
+        };
+        let new_glyph_ids: BTreeMap<_, _> =
+            original_glyph_ids.iter().enumerate().filter_map(|(new_glyph_id, glyph_id)| {
+                glyph_id.map(|glyph_id| (glyph_id, new_glyph_id as u16))
+            }).collect();
+        let mut mappings: Vec<_> = mappings.into_iter().map(|(codepoint, glyph_id)| {
+            (codepoint, new_glyph_ids[&glyph_id])
+        }).collect();
+        mappings.sort();
+
+        let mut font_builder = FontBuilder::new();
+
+        // Outlines.
+        let (glyf, loca, index_to_loc_format) =
+            try!(subset_glyphs(&glyf_table, &loca_table, &original_glyph_ids, &new_glyph_ids));
+        let mut head = try!(table_data(font, HEAD)).to_vec();
+        BigEndian::write_i16(&mut head[HEAD_INDEX_TO_LOC_FORMAT_OFFSET..], index_to_loc_format);
+        font_builder.add_table(Tag(GLYF), glyf);
+        font_builder.add_table(Tag(LOCA), loca);
+        font_builder.add_table(Tag(HEAD), head);
+
+        let mut maxp = try!(table_data(font, MAXP)).to_vec();
+        BigEndian::write_u16(&mut maxp[MAXP_NUM_GLYPHS_OFFSET..], original_glyph_ids.len() as u16);
+        font_builder.add_table(Tag(MAXP), maxp);
+
+        // Metrics.
+        let (hhea, hmtx) = try!(subset_metrics(try!(table_data(font, HHEA)),
+                                               &hmtx_table,
+                                               &original_glyph_ids));
+        font_builder.add_table(Tag(HHEA), hhea);
+        font_builder.add_table(Tag(HMTX), hmtx);
+        if let Ok(vhea_table) = font.vhea_table() {
+            if let Ok(vmtx_table) = font.vmtx_table(&vhea_table) {
+                let (vhea, vmtx) = try!(subset_metrics(try!(table_data(font, VHEA)),
+                                                       &vmtx_table,
+                                                       &original_glyph_ids));
+                font_builder.add_table(Tag(VHEA), vhea);
+                font_builder.add_table(Tag(VMTX), vmtx);
+            }
+        }
+
+        // Names. These are optional, so drop them if they can't be read.
+        let mut cmap_builder = CmapBuilder::new();
+        for &(codepoint, glyph_id) in &mappings {
+            cmap_builder.add_mapping(codepoint, glyph_id)
+        }
+        font_builder.add_table(Tag(CMAP), cmap_builder.build());
+        if let Some(post) = try!(font.table(Tag(POST))) {
+            if let Ok(post) = subset_post(post.bytes, num_glyphs, &original_glyph_ids) {
+                font_builder.add_table(Tag(POST), post);
+            }
+        }
+        if let Some(name) = try!(font.table(Tag(NAME))) {
+            if let Ok(name) = subset_name(name.bytes) {
+                font_builder.add_table(Tag(NAME), name);
+            }
+        }
+        if let Some(os_2) = try!(font.table(Tag(OS_2))) {
+            let mut os_2 = os_2.bytes.to_vec();
+            let bmp_mappings: Vec<_> =
+                mappings.iter().filter(|mapping| mapping.0 <= u16::MAX as u32).collect();
+            if let (Some(first), Some(last)) = (bmp_mappings.first(), bmp_mappings.last()) {
+                if os_2.len() >= OS_2_FIRST_CHAR_INDEX_OFFSET + mem::size_of::<u16>() * 2 {
+                    BigEndian::write_u16(&mut os_2[OS_2_FIRST_CHAR_INDEX_OFFSET..],
+                                         first.0 as u16);
+                    BigEndian::write_u16(&mut os_2[(OS_2_FIRST_CHAR_INDEX_OFFSET + 2)..],
+                                         last.0 as u16);
+                }
+            }
+            font_builder.add_table(Tag(OS_2), os_2);
+        }
+
+        // Tables that don't refer to glyphs, or that only stay valid if glyph IDs do.
+        let mut copied_tables = vec![CVT, FPGM, GASP, PREP];
+        if self.retain_glyph_ids {
+            copied_tables.extend_from_slice(&[GDEF, GPOS, GSUB])
+        }
+        for &tag in &copied_tables {
+            if let Some(table) = try!(font.table(Tag(tag))) {
+                font_builder.add_table(Tag(tag), table.bytes);
+            }
+        }
+
+        Ok(Subset {
+            data: try!(font_builder.build()),
+            original_glyph_ids: original_glyph_ids,
+            new_glyph_ids: new_glyph_ids,
+        })
+    }
+}
+
+fn table_data<'a>(font: &FontData<'a>, tag: u32) -> Result<&'a [u8], Error> {
+    match try!(font.table(Tag(tag))) {
+        Some(table) => Ok(table.bytes),
+        None => Err(Error::MissingTable(tag)),
+    }
+}
+
+// Copies the kept glyphs into a new `glyf` table, renumbering the components of composite
+// glyphs, and builds its `loca` table. Returns those and the `indexToLocFormat` they need.
+fn subset_glyphs(glyf_table: &GlyfTable,
+                 loca_table: &LocaTable,
+                 original_glyph_ids: &[Option<u16>],
+                 new_glyph_ids: &BTreeMap<u16, u16>)
+                 -> Result<(Vec<u8>, Vec<u8>, i16), Error> {
+    let mut glyf_builder = GlyfBuilder::new();
+    for original_glyph_id in original_glyph_ids {
+        let glyph_id = match *original_glyph_id {
+            Some(glyph_id) => glyph_id as u32,
+            None => {
+                glyf_builder.add_empty_glyph();
+                continue
+            }
+        };
+        let data = match try!(glyf_table.glyph_data(loca_table, glyph_id)) {
+            Some(data) => data,
+            None => {
+                glyf_builder.add_empty_glyph();
+                continue
+            }
+        };
+
+        // Padding could make a truncated glyph readable, so check that the glyph is whole first.
+        try!(glyf_table.for_each_point(loca_table, glyph_id, |_| {}));
+        let mut glyph = data.to_vec();
+        for component in try!(glyf_table.components(loca_table, glyph_id)) {
+            BigEndian::write_u16(&mut glyph[component.glyph_id_offset..],
+                                 new_glyph_ids[&component.glyph_id])
+        }
+        glyf_builder.add_raw_glyph(&glyph);
+    }
+    Ok(glyf_builder.build())
+}
+
+// Builds the `hmtx` or `vmtx` table for the kept glyphs and updates the number of full metrics
+// records in a copy of `hhea` or `vhea` to match. The empty glyphs that fill gaps get zero
+// metrics.
+fn subset_metrics(hhea: &[u8], hmtx_table: &HmtxTable, original_glyph_ids: &[Option<u16>])
+                  -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let mut metrics = Vec::with_capacity(original_glyph_ids.len());
+    for original_glyph_id in original_glyph_ids {
+        metrics.push(match *original_glyph_id {
+            Some(glyph_id) => try!(hmtx_table.metrics(glyph_id)),
+            None => GlyphMetrics { advance: 0, side_bearing: 0 },
+        })
+    }
+
+    let (hmtx, number_of_metrics) = hmtx::build_hmtx_table(&metrics);
+    let mut hhea = hhea.to_vec();
+    BigEndian::write_u16(&mut hhea[HHEA_NUMBER_OF_METRICS_OFFSET..], number_of_metrics);
+    Ok((hhea, hmtx))
+}
+
+// Renumbers the glyph names of a version 2 `post` table, keeping only the names of kept glyphs.
+// Other versions either have no names or name glyphs by their IDs, which renumbering breaks, so
+// they become version 3, which has no names.
+fn subset_post(post: &[u8], num_glyphs: u16, original_glyph_ids: &[Option<u16>])
+               -> Result<Vec<u8>, Error> {
+    if post.len() < POST_HEADER_SIZE {
+        return Err(Error::UnexpectedEof)
+    }
+    let mut new_post = post[0..POST_HEADER_SIZE].to_vec();
+    if BigEndian::read_u32(post) != POST_VERSION_2 {
+        BigEndian::write_u32(&mut new_post, POST_VERSION_3);
+        return Ok(new_post)
+    }
+
+    let mut reader = &post[POST_HEADER_SIZE..];
+    if try!(reader.read_u16::<BigEndian>()) != num_glyphs {
+        return Err(Error::Malformed)
+    }
+    let mut glyph_name_indices = Vec::with_capacity(num_glyphs as usize);
+    for _ in 0..num_glyphs {
+        glyph_name_indices.push(try!(reader.read_u16::<BigEndian>()))
+    }
+    let mut names = vec![];
+    while !reader.is_empty() {
+        let length = try!(reader.read_u8()) as usize;
+        if reader.len() < length {
+            return Err(Error::UnexpectedEof)
+        }
+        names.push(&reader[0..length]);
+        try!(reader.jump(length))
+    }
+
+    // Names past the standard Macintosh ones are numbered in the order they're first used.
+    let mut new_name_indices = BTreeMap::new();
+    let mut new_names = vec![];
+    new_post.write_u16::<BigEndian>(original_glyph_ids.len() as u16).unwrap();
+    for original_glyph_id in original_glyph_ids {
+        let glyph_name_index = match *original_glyph_id {
+            Some(glyph_id) => glyph_name_indices[glyph_id as usize],
+            None => 0,
+        };
+        let glyph_name_index = if glyph_name_index < POST_STANDARD_GLYPH_NAME_COUNT {
+            glyph_name_index
+        } else {
+            let name_index = (glyph_name_index - POST_STANDARD_GLYPH_NAME_COUNT) as usize;
+            let name = try!(names.get(name_index).ok_or(Error::Malformed));
+            *new_name_indices.entry(name_index).or_insert_with(|| {
+                new_names.push(*name);
+                POST_STANDARD_GLYPH_NAME_COUNT + new_names.len() as u16 - 1
+            })
+        };
+        new_post.write_u16::<BigEndian>(glyph_name_index).unwrap()
+    }
+    for name in new_names {
+        new_post.push(name.len() as u8);
+        new_post.extend_from_slice(name)
+    }
+    Ok(new_post)
+}
+
+// Keeps the essential names of a `name` table, writing them as a format 0 table.
+fn subset_name(name: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut reader = name;
+    try!(reader.jump(mem::size_of::<u16>()));
+    let count = try!(reader.read_u16::<BigEndian>());
+    let storage_offset = try!(reader.read_u16::<BigEndian>()) as usize;
+
+    let mut name_builder = NameBuilder::new();
+    for _ in 0..count {
+        let platform_id = try!(reader.read_u16::<BigEndian>());
+        let encoding_id = try!(reader.read_u16::<BigEndian>());
+        let language_id = try!(reader.read_u16::<BigEndian>());
+        let name_id = try!(reader.read_u16::<BigEndian>());
+        let length = try!(reader.read_u16::<BigEndian>()) as usize;
+        let offset = try!(reader.read_u16::<BigEndian>()) as usize;
+        if name_id > LAST_KEPT_NAME_ID || language_id >= FIRST_LANG_TAG_LANGUAGE_ID {
+            continue
+        }
+        let start = storage_offset + offset;
+        if start + length > name.len() {
+            return Err(Error::Malformed)
+        }
+        name_builder.add_record(platform_id,
+                                encoding_id,
+                                language_id,
+                                name_id,
+                                &name[start..(start + length)])
+    }
+    name_builder.build()
+}
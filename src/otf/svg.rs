@@ -0,0 +1,125 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `SVG ` table, which holds color glyphs as SVG documents.
+
+use byteorder::{BigEndian, ReadBytesExt};
+use error::Error;
+use flate2::read::GzDecoder;
+use otf::{SVG, FontTable};
+use std::borrow::Cow;
+use std::io::Read;
+use util::Jump;
+
+const DOCUMENT_RECORD_SIZE: usize = 12;
+
+// Deflate can't expand its input by more than this factor, so a compressed document that
+// inflates beyond it is corrupt.
+const MAX_DEFLATE_RATIO: u64 = 1032;
+
+/// An SVG document holding the glyphs in a range of glyph IDs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SvgDocument<'a> {
+    pub start_glyph_id: u16,
+    pub end_glyph_id: u16,
+    /// The document as stored in the font, possibly gzip-compressed.
+    pub data: &'a [u8],
+}
+
+impl<'a> SvgDocument<'a> {
+    /// Returns the text of the document, decompressing it if necessary.
+    pub fn text(&self) -> Result<Cow<'a, [u8]>, Error> {
+        if !self.data.starts_with(&[0x1f, 0x8b]) {
+            return Ok(Cow::Borrowed(self.data))
+        }
+        let decoder = try!(GzDecoder::new(self.data).map_err(|_| Error::Malformed));
+        let limit = self.data.len() as u64 * MAX_DEFLATE_RATIO;
+        let mut text = vec![];
+        try!(decoder.take(limit + 1).read_to_end(&mut text).map_err(|_| Error::Malformed));
+        if text.len() as u64 > limit {
+            return Err(Error::Malformed)
+        }
+        Ok(Cow::Owned(text))
+    }
+}
+
+/// Returns the ID of the element that draws the given glyph within its document.
+pub fn glyph_element_id(glyph_id: u16) -> String {
+    format!("glyph{}", glyph_id)
+}
+
+#[derive(Clone, Copy)]
+pub struct SvgTable<'a> {
+    pub table: FontTable<'a>,
+    document_list_offset: u32,
+}
+
+impl<'a> SvgTable<'a> {
+    pub fn new(table: FontTable) -> Result<SvgTable, Error> {
+        let mut reader = table.bytes;
+        let version = try!(reader.read_u16::<BigEndian>());
+        if version != 0 {
+            return Err(Error::UnsupportedFormat {
+                table: SVG,
+                format: version as u32,
+            })
+        }
+
+        let document_list_offset = try!(reader.read_u32::<BigEndian>());
+        Ok(SvgTable {
+            table: table,
+            document_list_offset: document_list_offset,
+        })
+    }
+
+    /// Returns the document containing the given glyph, or `None` if the glyph has no SVG
+    /// version.
+    pub fn document(&self, glyph_id: u16) -> Result<Option<SvgDocument<'a>>, Error> {
+        let mut list = self.table.bytes;
+        try!(list.jump(self.document_list_offset as usize));
+        let mut reader = list;
+        let num_entries = try!(reader.read_u16::<BigEndian>()) as usize;
+        let records = reader;
+        if records.len() < num_entries * DOCUMENT_RECORD_SIZE {
+            return Err(Error::Malformed)
+        }
+
+        // The records are sorted by glyph ID and don't overlap, so binary search them.
+        let (mut low, mut high) = (0, num_entries);
+        while low < high {
+            let mid = (low + high) / 2;
+            let mut record = &records[(mid * DOCUMENT_RECORD_SIZE)..];
+            let start_glyph_id = try!(record.read_u16::<BigEndian>());
+            let end_glyph_id = try!(record.read_u16::<BigEndian>());
+            if glyph_id < start_glyph_id {
+                high = mid;
+                continue
+            }
+            if glyph_id > end_glyph_id {
+                low = mid + 1;
+                continue
+            }
+
+            // Document offsets are relative to the start of the document list.
+            let offset = try!(record.read_u32::<BigEndian>()) as usize;
+            let length = try!(record.read_u32::<BigEndian>()) as usize;
+            let end = try!(offset.checked_add(length).ok_or(Error::Malformed));
+            if end > list.len() {
+                return Err(Error::Malformed)
+            }
+            return Ok(Some(SvgDocument {
+                start_glyph_id: start_glyph_id,
+                end_glyph_id: end_glyph_id,
+                data: &list[offset..end],
+            }))
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,275 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! WOFF 1.0 decoding.
+//!
+//! See: https://www.w3.org/TR/WOFF/
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use error::Error;
+use flate2::read::ZlibDecoder;
+use otf::FontData;
+use std::cmp;
+use std::io::Read;
+use std::u16;
+use std::u32;
+
+pub const WOFF: u32 = ((b'w' as u32) << 24) |
+                      ((b'O' as u32) << 16) |
+                      ((b'F' as u32) << 8)  |
+                       (b'F' as u32);
+
+/// The largest font or metadata block that WOFF and WOFF2 decoding will produce, in bytes.
+///
+/// Both formats declare how big their decoded data is, but a small file can declare anything, so
+/// declared sizes over this are rejected before decompressing. Like OTS's limit, this is well
+/// above the size of real web fonts, but it leaves room for large CJK collections.
+pub const MAX_DECODED_SIZE: u32 = 128 * 1024 * 1024;
+
+const HEADER_SIZE: usize = 44;
+const TABLE_DIRECTORY_ENTRY_SIZE: usize = 20;
+
+/// A decoded WOFF file.
+pub struct WoffFont {
+    /// The `flavor` field from the header: the sfnt version of the wrapped font.
+    pub flavor: u32,
+    /// The major version of the font (not of the WOFF format).
+    pub major_version: u16,
+    /// The minor version of the font (not of the WOFF format).
+    pub minor_version: u16,
+    /// The reconstructed sfnt.
+    pub sfnt: Vec<u8>,
+    /// The extended metadata block, decompressed. This is uninterpreted XML.
+    pub metadata: Option<Vec<u8>>,
+    /// The private data block, verbatim.
+    pub private_data: Option<Vec<u8>>,
+}
+
+/// A table, ready to be written into an sfnt.
+pub struct SfntTable<'a> {
+    pub tag: u32,
+    pub checksum: u32,
+    pub data: &'a [u8],
+}
+
+struct TableDirectoryEntry {
+    tag: u32,
+    offset: u32,
+    comp_length: u32,
+    orig_length: u32,
+    orig_checksum: u32,
+}
+
+impl WoffFont {
+    pub fn new(bytes: &[u8]) -> Result<WoffFont, Error> {
+        let mut reader = bytes;
+        let signature = try!(reader.read_u32::<BigEndian>());
+        if signature != WOFF {
+            return Err(Error::UnsupportedSfntVersion(signature))
+        }
+
+        let flavor = try!(reader.read_u32::<BigEndian>());
+        let length = try!(reader.read_u32::<BigEndian>());
+        let num_tables = try!(reader.read_u16::<BigEndian>());
+        let reserved = try!(reader.read_u16::<BigEndian>());
+        if length as usize != bytes.len() || reserved != 0 || num_tables == 0 {
+            return Err(Error::Malformed)
+        }
+
+        let total_sfnt_size = try!(reader.read_u32::<BigEndian>());
+        if total_sfnt_size > MAX_DECODED_SIZE {
+            return Err(Error::Malformed)
+        }
+        let major_version = try!(reader.read_u16::<BigEndian>());
+        let minor_version = try!(reader.read_u16::<BigEndian>());
+        let meta_offset = try!(reader.read_u32::<BigEndian>());
+        let meta_length = try!(reader.read_u32::<BigEndian>());
+        let meta_orig_length = try!(reader.read_u32::<BigEndian>());
+        let priv_offset = try!(reader.read_u32::<BigEndian>());
+        let priv_length = try!(reader.read_u32::<BigEndian>());
+
+        // Read and validate the table directory. The decompressed font can't be bigger than
+        // `totalSfntSize` says, which bounds how much inflating it can allocate.
+        let data_start = HEADER_SIZE + num_tables as usize * TABLE_DIRECTORY_ENTRY_SIZE;
+        let mut sfnt_size = 12 + num_tables as u64 * 16;
+        let mut entries = Vec::with_capacity(num_tables as usize);
+        for _ in 0..num_tables {
+            let entry = TableDirectoryEntry {
+                tag: try!(reader.read_u32::<BigEndian>()),
+                offset: try!(reader.read_u32::<BigEndian>()),
+                comp_length: try!(reader.read_u32::<BigEndian>()),
+                orig_length: try!(reader.read_u32::<BigEndian>()),
+                orig_checksum: try!(reader.read_u32::<BigEndian>()),
+            };
+
+            if let Some(last_entry) = entries.last() {
+                let last_entry: &TableDirectoryEntry = last_entry;
+                if entry.tag <= last_entry.tag {
+                    return Err(Error::Malformed)
+                }
+            }
+            if (entry.offset as usize) < data_start || entry.comp_length > entry.orig_length {
+                return Err(Error::Malformed)
+            }
+            try!(block(bytes, entry.offset, entry.comp_length));
+            sfnt_size += (entry.orig_length as u64 + 3) & !3;
+            if sfnt_size > total_sfnt_size as u64 {
+                return Err(Error::Malformed)
+            }
+
+            entries.push(entry)
+        }
+
+        // Decompress the tables.
+        let mut tables = Vec::with_capacity(entries.len());
+        for entry in &entries {
+            let compressed = try!(block(bytes, entry.offset, entry.comp_length));
+            let data = if entry.comp_length < entry.orig_length {
+                try!(inflate(compressed, entry.orig_length))
+            } else {
+                compressed.to_vec()
+            };
+            tables.push((entry, data))
+        }
+
+        let sfnt = try!(build_sfnt(flavor, &tables.iter().map(|&(entry, ref data)| {
+            SfntTable {
+                tag: entry.tag,
+                checksum: entry.orig_checksum,
+                data: data,
+            }
+        }).collect::<Vec<_>>()));
+
+        // Extract the metadata and private data blocks.
+        let metadata = if meta_length == 0 {
+            None
+        } else {
+            if (meta_offset as usize) < data_start || meta_orig_length > MAX_DECODED_SIZE {
+                return Err(Error::Malformed)
+            }
+            Some(try!(inflate(try!(block(bytes, meta_offset, meta_length)), meta_orig_length)))
+        };
+
+        let private_data = if priv_length == 0 {
+            None
+        } else {
+            if (priv_offset as usize) < data_start {
+                return Err(Error::Malformed)
+            }
+            Some(try!(block(bytes, priv_offset, priv_length)).to_vec())
+        };
+
+        Ok(WoffFont {
+            flavor: flavor,
+            major_version: major_version,
+            minor_version: minor_version,
+            sfnt: sfnt,
+            metadata: metadata,
+            private_data: private_data,
+        })
+    }
+
+    /// Returns a view of the reconstructed sfnt, suitable for the usual table accessors.
+    #[inline]
+    pub fn font_data(&self) -> FontData {
+        FontData::new(&self.sfnt)
+    }
+}
+
+/// Serializes a list of tables, which must be sorted by tag, into an sfnt with the given version.
+/// Each table is padded to a four-byte boundary. Fails with `InvalidArgument` if there are more
+/// tables than an sfnt can hold or the tables don't fit in 32-bit offsets.
+pub fn build_sfnt(sfnt_version: u32, tables: &[SfntTable]) -> Result<Vec<u8>, Error> {
+    let directory_size = 12 + tables.len() * 16;
+    let data_size: usize = tables.iter().map(|table| (table.data.len() + 3) & !3).sum();
+    if directory_size + data_size > u32::MAX as usize {
+        return Err(Error::InvalidArgument)
+    }
+    let mut sfnt = Vec::with_capacity(directory_size + data_size);
+    try!(write_sfnt_header(&mut sfnt, sfnt_version, tables.len()));
+
+    let mut offset = directory_size;
+    for table in tables {
+        sfnt.write_u32::<BigEndian>(table.tag).unwrap();
+        sfnt.write_u32::<BigEndian>(table.checksum).unwrap();
+        sfnt.write_u32::<BigEndian>(offset as u32).unwrap();
+        sfnt.write_u32::<BigEndian>(table.data.len() as u32).unwrap();
+        offset += (table.data.len() + 3) & !3;
+    }
+
+    for table in tables {
+        sfnt.extend_from_slice(table.data);
+        while sfnt.len() % 4 != 0 {
+            sfnt.push(0)
+        }
+    }
+
+    Ok(sfnt)
+}
+
+/// Writes the header of an sfnt table directory with `num_tables` entries, failing with
+/// `InvalidArgument` if that doesn't fit in `numTables`.
+///
+/// `searchRange` and `rangeShift` are computed in 32 bits and saturate, since past 4095 tables
+/// their true values don't fit in 16 bits. Readers treat them as hints.
+pub fn write_sfnt_header(sfnt: &mut Vec<u8>, sfnt_version: u32, num_tables: usize)
+                         -> Result<(), Error> {
+    if num_tables > u16::MAX as usize {
+        return Err(Error::InvalidArgument)
+    }
+    let num_tables = num_tables as u32;
+    let mut entry_selector = 0;
+    while (2 << entry_selector) <= num_tables {
+        entry_selector += 1
+    }
+    let search_range = (1 << entry_selector) * 16;
+    let range_shift = num_tables * 16 - search_range;
+
+    sfnt.write_u32::<BigEndian>(sfnt_version).unwrap();
+    sfnt.write_u16::<BigEndian>(num_tables as u16).unwrap();
+    sfnt.write_u16::<BigEndian>(cmp::min(search_range, u16::MAX as u32) as u16).unwrap();
+    sfnt.write_u16::<BigEndian>(entry_selector as u16).unwrap();
+    sfnt.write_u16::<BigEndian>(cmp::min(range_shift, u16::MAX as u32) as u16).unwrap();
+    Ok(())
+}
+
+/// Computes the checksum of a table: the sum of its big-endian 32-bit words, zero-padded.
+pub fn calculate_checksum(bytes: &[u8]) -> u32 {
+    let mut checksum = 0u32;
+    for word in bytes.chunks(4) {
+        let mut value = 0;
+        for index in 0..4 {
+            value = (value << 8) | *word.get(index).unwrap_or(&0) as u32
+        }
+        checksum = checksum.wrapping_add(value)
+    }
+    checksum
+}
+
+fn block(bytes: &[u8], offset: u32, length: u32) -> Result<&[u8], Error> {
+    let (offset, length) = (offset as usize, length as usize);
+    match offset.checked_add(length) {
+        Some(end) if end <= bytes.len() => Ok(&bytes[offset..end]),
+        _ => Err(Error::Malformed),
+    }
+}
+
+// Inflates a zlib stream, failing unless it decompresses to exactly `orig_length` bytes.
+fn inflate(compressed: &[u8], orig_length: u32) -> Result<Vec<u8>, Error> {
+    // Don't trust the declared length for the up-front reservation; a tiny stream can claim
+    // gigabytes.
+    let mut data = Vec::with_capacity(cmp::min(orig_length as usize, compressed.len() * 32));
+    let decoder = ZlibDecoder::new(compressed);
+    try!(decoder.take(orig_length as u64 + 1).read_to_end(&mut data));
+    if data.len() != orig_length as usize {
+        return Err(Error::Malformed)
+    }
+    Ok(data)
+}
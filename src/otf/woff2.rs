@@ -0,0 +1,922 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! WOFF 2.0 decoding.
+//!
+//! The `glyf`, `loca` and `hmtx` tables may be stored in transformed form; these are rebuilt into
+//! ordinary TrueType tables. The rebuilt `glyf` table is not byte-for-byte identical to the one
+//! the file was encoded from, but it describes the same outlines.
+//!
+//! See: https://www.w3.org/TR/WOFF2/
+
+use brotli_decompressor::Decompressor;
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
+use error::Error;
+use otf::FontData;
+use otf::checksum;
+use otf::collection::{FontCollection, TTCF};
+use otf::woff::{self, SfntTable};
+use std::cmp;
+use std::i16;
+use std::io::Read;
+use std::mem;
+use std::u16;
+use std::u32;
+use util::Jump;
+
+pub const WOFF2: u32 = ((b'w' as u32) << 24) |
+                       ((b'O' as u32) << 16) |
+                       ((b'F' as u32) << 8)  |
+                        (b'2' as u32);
+
+const GLYF: u32 = ((b'g' as u32) << 24) |
+                  ((b'l' as u32) << 16) |
+                  ((b'y' as u32) << 8)  |
+                   (b'f' as u32);
+const HHEA: u32 = ((b'h' as u32) << 24) |
+                  ((b'h' as u32) << 16) |
+                  ((b'e' as u32) << 8)  |
+                   (b'a' as u32);
+const HMTX: u32 = ((b'h' as u32) << 24) |
+                  ((b'm' as u32) << 16) |
+                  ((b't' as u32) << 8)  |
+                   (b'x' as u32);
+const LOCA: u32 = ((b'l' as u32) << 24) |
+                  ((b'o' as u32) << 16) |
+                  ((b'c' as u32) << 8)  |
+                   (b'a' as u32);
+
+/// Tags that can be encoded in the low six bits of a table directory entry's flags.
+static KNOWN_TAGS: [&'static [u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post",
+    b"cvt ", b"fpgm", b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT",
+    b"EBLC", b"gasp", b"hdmx", b"kern", b"LTSH", b"PCLT", b"VDMX", b"vhea",
+    b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC", b"JSTF", b"MATH",
+    b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar",
+    b"gvar", b"hsty", b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop",
+    b"trak", b"Zapf", b"Silf", b"Glat", b"Gloc", b"Feat", b"Sill",
+];
+
+const ARBITRARY_TAG_INDEX: u8 = 63;
+
+const HHEA_NUMBER_OF_H_METRICS_OFFSET: usize = 34;
+
+const GLYF_TRANSFORM_HEADER_SIZE: usize = 36;
+const GLYF_OPTION_OVERLAP_SIMPLE_BITMAP: u16 = 1 << 0;
+
+const HMTX_TRANSFORM_PROPORTIONAL_LSBS_OMITTED: u8 = 1 << 0;
+const HMTX_TRANSFORM_MONOSPACED_LSBS_OMITTED: u8 = 1 << 1;
+
+// Simple glyph flags, as written into the rebuilt `glyf` table.
+const ON_CURVE_POINT: u8 = 1 << 0;
+const X_SHORT_VECTOR: u8 = 1 << 1;
+const Y_SHORT_VECTOR: u8 = 1 << 2;
+const REPEAT_FLAG: u8 = 1 << 3;
+const X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR: u8 = 1 << 4;
+const Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR: u8 = 1 << 5;
+const OVERLAP_SIMPLE: u8 = 1 << 6;
+
+// Composite glyph flags.
+const ARG_1_AND_2_ARE_WORDS: u16 = 1 << 0;
+const WE_HAVE_A_SCALE: u16 = 1 << 3;
+const MORE_COMPONENTS: u16 = 1 << 5;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 1 << 6;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 1 << 7;
+const WE_HAVE_INSTRUCTIONS: u16 = 1 << 8;
+
+/// A decoded WOFF2 file.
+pub struct Woff2Font {
+    /// The `flavor` field from the header. This is `ttcf` for collections.
+    pub flavor: u32,
+    /// The major version of the font (not of the WOFF2 format).
+    pub major_version: u16,
+    /// The minor version of the font (not of the WOFF2 format).
+    pub minor_version: u16,
+    /// The reconstructed sfnt, or TrueType Collection if `flavor` is `ttcf`.
+    pub sfnt: Vec<u8>,
+    /// The extended metadata block, decompressed. This is uninterpreted XML.
+    pub metadata: Option<Vec<u8>>,
+    /// The private data block, verbatim.
+    pub private_data: Option<Vec<u8>>,
+}
+
+struct TableDirectoryEntry {
+    tag: u32,
+    orig_length: u32,
+    transformed: bool,
+    /// The offset and length of this table within the decompressed stream.
+    offset: usize,
+    length: usize,
+}
+
+struct CollectionFontEntry {
+    flavor: u32,
+    table_indices: Vec<u16>,
+}
+
+impl Woff2Font {
+    pub fn new(bytes: &[u8]) -> Result<Woff2Font, Error> {
+        let mut reader = bytes;
+        let signature = try!(reader.read_u32::<BigEndian>());
+        if signature != WOFF2 {
+            return Err(Error::UnsupportedSfntVersion(signature))
+        }
+
+        let flavor = try!(reader.read_u32::<BigEndian>());
+        let length = try!(reader.read_u32::<BigEndian>());
+        let num_tables = try!(reader.read_u16::<BigEndian>());
+        let reserved = try!(reader.read_u16::<BigEndian>());
+        if length as usize != bytes.len() || reserved != 0 || num_tables == 0 {
+            return Err(Error::Malformed)
+        }
+
+        let total_sfnt_size = try!(reader.read_u32::<BigEndian>());
+        if total_sfnt_size > woff::MAX_DECODED_SIZE {
+            return Err(Error::Malformed)
+        }
+        let total_compressed_size = try!(reader.read_u32::<BigEndian>());
+        let major_version = try!(reader.read_u16::<BigEndian>());
+        let minor_version = try!(reader.read_u16::<BigEndian>());
+        let meta_offset = try!(reader.read_u32::<BigEndian>());
+        let meta_length = try!(reader.read_u32::<BigEndian>());
+        let meta_orig_length = try!(reader.read_u32::<BigEndian>());
+        let priv_offset = try!(reader.read_u32::<BigEndian>());
+        let priv_length = try!(reader.read_u32::<BigEndian>());
+
+        // Read the table directory. The tables can't add up to more than `totalSfntSize` says,
+        // which bounds how much rebuilding the font can allocate.
+        let mut entries = Vec::with_capacity(num_tables as usize);
+        let mut stream_length = 0usize;
+        let mut sfnt_size = 12 + num_tables as u64 * 16;
+        for _ in 0..num_tables {
+            let flags = try!(reader.read_u8());
+            let tag = match flags & 0x3f {
+                ARBITRARY_TAG_INDEX => try!(reader.read_u32::<BigEndian>()),
+                tag_index => BigEndian::read_u32(KNOWN_TAGS[tag_index as usize]),
+            };
+
+            // For `glyf` and `loca`, transform version 0 is the transform and 3 is the null
+            // transform. For everything else, version 0 is the null transform.
+            let transform_version = flags >> 6;
+            let transformed = if tag == GLYF || tag == LOCA {
+                match transform_version {
+                    0 => true,
+                    3 => false,
+                    _ => {
+                        return Err(Error::UnsupportedFormat {
+                            table: tag,
+                            format: transform_version as u32,
+                        })
+                    }
+                }
+            } else {
+                transform_version != 0
+            };
+
+            let orig_length = try!(read_uint_base128(&mut reader));
+            let length = if transformed {
+                try!(read_uint_base128(&mut reader))
+            } else {
+                orig_length
+            };
+            if tag == LOCA && transformed && length != 0 {
+                return Err(Error::Malformed)
+            }
+            sfnt_size += (orig_length as u64 + 3) & !3;
+            if sfnt_size > total_sfnt_size as u64 {
+                return Err(Error::Malformed)
+            }
+
+            entries.push(TableDirectoryEntry {
+                tag: tag,
+                orig_length: orig_length,
+                transformed: transformed,
+                offset: stream_length,
+                length: length as usize,
+            });
+            stream_length =
+                try!(stream_length.checked_add(length as usize).ok_or(Error::Malformed));
+        }
+
+        // The transformed tables declare their own lengths, so the stream needs its own bound.
+        if stream_length > woff::MAX_DECODED_SIZE as usize {
+            return Err(Error::Malformed)
+        }
+
+        // Read the collection directory, if this is a collection.
+        let mut collection_fonts = None;
+        let mut collection_version = 0;
+        if flavor == TTCF {
+            collection_version = try!(reader.read_u32::<BigEndian>());
+            if collection_version != 0x00010000 && collection_version != 0x00020000 {
+                return Err(Error::UnsupportedFormat {
+                    table: TTCF,
+                    format: collection_version,
+                })
+            }
+
+            let num_fonts = try!(read_255_u16(&mut reader));
+            let mut fonts = Vec::with_capacity(num_fonts as usize);
+            for _ in 0..num_fonts {
+                let num_tables = try!(read_255_u16(&mut reader));
+                let flavor = try!(reader.read_u32::<BigEndian>());
+                let mut table_indices = Vec::with_capacity(num_tables as usize);
+                for _ in 0..num_tables {
+                    let table_index = try!(read_255_u16(&mut reader));
+                    if table_index as usize >= entries.len() {
+                        return Err(Error::Malformed)
+                    }
+                    table_indices.push(table_index)
+                }
+                fonts.push(CollectionFontEntry {
+                    flavor: flavor,
+                    table_indices: table_indices,
+                })
+            }
+            collection_fonts = Some(fonts)
+        }
+
+        // Decompress the table data.
+        let compressed_data_offset = bytes.len() - reader.len();
+        let compressed_data = try!(block(bytes, compressed_data_offset, total_compressed_size));
+        let mut stream = Vec::with_capacity(cmp::min(stream_length, bytes.len() * 32));
+        let decompressor = Decompressor::new(compressed_data, 4096);
+        try!(decompressor.take(stream_length as u64 + 1).read_to_end(&mut stream));
+        if stream.len() != stream_length {
+            return Err(Error::Malformed)
+        }
+
+        // The default font is the whole table directory.
+        let fonts = match collection_fonts {
+            Some(fonts) => fonts,
+            None => {
+                vec![CollectionFontEntry {
+                    flavor: flavor,
+                    table_indices: (0..num_tables).collect(),
+                }]
+            }
+        };
+
+        let tables = try!(reconstruct_tables(&entries, &stream, &fonts));
+        let sfnt = if flavor == TTCF {
+            try!(build_collection(collection_version, &fonts, &entries, &tables))
+        } else {
+            let mut sfnt_tables: Vec<_> = tables.iter().zip(entries.iter()).map(|(data, entry)| {
+                SfntTable {
+                    tag: entry.tag,
+                    checksum: woff::calculate_checksum(data),
+                    data: data,
+                }
+            }).collect();
+            sfnt_tables.sort_by_key(|table| table.tag);
+            let mut sfnt = try!(woff::build_sfnt(flavor, &sfnt_tables));
+            try!(checksum::fix_checksum_adjustment(&mut sfnt));
+            sfnt
+        };
+
+        // Extract the metadata and private data blocks.
+        let data_end = compressed_data_offset + total_compressed_size as usize;
+        let metadata = if meta_length == 0 {
+            None
+        } else {
+            if (meta_offset as usize) < data_end || meta_orig_length > woff::MAX_DECODED_SIZE {
+                return Err(Error::Malformed)
+            }
+            let compressed_metadata = try!(block(bytes, meta_offset as usize, meta_length));
+            let mut metadata = Vec::with_capacity(cmp::min(meta_orig_length as usize,
+                                                           compressed_metadata.len() * 32));
+            let decompressor = Decompressor::new(compressed_metadata, 4096);
+            try!(decompressor.take(meta_orig_length as u64 + 1)
+                             .read_to_end(&mut metadata)
+                             .map_err(|_| Error::Malformed));
+            if metadata.len() != meta_orig_length as usize {
+                return Err(Error::Malformed)
+            }
+            Some(metadata)
+        };
+
+        let private_data = if priv_length == 0 {
+            None
+        } else {
+            if (priv_offset as usize) < data_end {
+                return Err(Error::Malformed)
+            }
+            Some(try!(block(bytes, priv_offset as usize, priv_length)).to_vec())
+        };
+
+        Ok(Woff2Font {
+            flavor: flavor,
+            major_version: major_version,
+            minor_version: minor_version,
+            sfnt: sfnt,
+            metadata: metadata,
+            private_data: private_data,
+        })
+    }
+
+    #[inline]
+    pub fn is_collection(&self) -> bool {
+        self.flavor == TTCF
+    }
+
+    /// Returns a view of the reconstructed sfnt. For collections, use `collection` instead.
+    #[inline]
+    pub fn font_data(&self) -> FontData {
+        FontData::new(&self.sfnt)
+    }
+
+    /// Returns the reconstructed collection, if this file was a collection.
+    #[inline]
+    pub fn collection(&self) -> Result<FontCollection, Error> {
+        FontCollection::new(&self.sfnt)
+    }
+}
+
+// Undoes the table transforms, returning the final contents of every table in directory order.
+fn reconstruct_tables(entries: &[TableDirectoryEntry],
+                      stream: &[u8],
+                      fonts: &[CollectionFontEntry])
+                      -> Result<Vec<Vec<u8>>, Error> {
+    let mut tables: Vec<Option<Vec<u8>>> = entries.iter().map(|entry| {
+        if entry.transformed {
+            None
+        } else {
+            Some(stream[entry.offset..(entry.offset + entry.length)].to_vec())
+        }
+    }).collect();
+
+    // The x-minima of each rebuilt `glyf` table, by table index. The `hmtx` transform needs these.
+    let mut x_mins = vec![None; entries.len()];
+
+    // Rebuild `glyf` and `loca` first. The transformed `loca` must directly follow its `glyf`.
+    for (glyf_index, entry) in entries.iter().enumerate() {
+        if entry.tag != GLYF || !entry.transformed {
+            continue
+        }
+        let loca_index = glyf_index + 1;
+        match entries.get(loca_index) {
+            Some(loca_entry) if loca_entry.tag == LOCA && loca_entry.transformed => {}
+            _ => return Err(Error::Malformed),
+        }
+
+        let glyf_data = &stream[entry.offset..(entry.offset + entry.length)];
+        let glyf = try!(reconstruct_glyf(glyf_data));
+        if glyf.loca.len() != entries[loca_index].orig_length as usize {
+            return Err(Error::Malformed)
+        }
+        tables[glyf_index] = Some(glyf.glyf);
+        tables[loca_index] = Some(glyf.loca);
+        x_mins[glyf_index] = Some(glyf.x_mins);
+    }
+
+    // Then rebuild `hmtx`, using the `hhea` and `glyf` tables of a font that contains it.
+    for (hmtx_index, entry) in entries.iter().enumerate() {
+        if !entry.transformed || entry.tag == GLYF || entry.tag == LOCA {
+            continue
+        }
+        if entry.tag != HMTX {
+            return Err(Error::Malformed)
+        }
+
+        let font = try!(fonts.iter().find(|font| {
+            font.table_indices.contains(&(hmtx_index as u16))
+        }).ok_or(Error::Malformed));
+        let table_with_tag = |tag| {
+            font.table_indices.iter().cloned().find(|&index| entries[index as usize].tag == tag)
+        };
+        let hhea_index = try!(table_with_tag(HHEA).ok_or(Error::Malformed)) as usize;
+        let glyf_index = try!(table_with_tag(GLYF).ok_or(Error::Malformed)) as usize;
+
+        let hhea = try!(tables[hhea_index].as_ref().ok_or(Error::Malformed));
+        let x_mins = try!(x_mins[glyf_index].as_ref().ok_or(Error::Malformed));
+        if hhea.len() < HHEA_NUMBER_OF_H_METRICS_OFFSET + mem::size_of::<u16>() {
+            return Err(Error::Malformed)
+        }
+        let number_of_h_metrics = BigEndian::read_u16(&hhea[HHEA_NUMBER_OF_H_METRICS_OFFSET..]);
+
+        let hmtx_data = &stream[entry.offset..(entry.offset + entry.length)];
+        tables[hmtx_index] = Some(try!(reconstruct_hmtx(hmtx_data, number_of_h_metrics, x_mins)));
+    }
+
+    tables.into_iter().map(|table| table.ok_or(Error::Malformed)).collect()
+}
+
+struct ReconstructedGlyf {
+    glyf: Vec<u8>,
+    loca: Vec<u8>,
+    x_mins: Vec<i16>,
+}
+
+fn reconstruct_glyf(data: &[u8]) -> Result<ReconstructedGlyf, Error> {
+    let mut reader = data;
+    let version = try!(reader.read_u16::<BigEndian>());
+    if version != 0 {
+        return Err(Error::UnsupportedFormat {
+            table: GLYF,
+            format: version as u32,
+        })
+    }
+    let option_flags = try!(reader.read_u16::<BigEndian>());
+    let num_glyphs = try!(reader.read_u16::<BigEndian>());
+    let index_format = try!(reader.read_u16::<BigEndian>());
+    if index_format > 1 {
+        return Err(Error::UnsupportedFormat {
+            table: GLYF,
+            format: index_format as u32,
+        })
+    }
+
+    // Split out the substreams.
+    let mut offset = GLYF_TRANSFORM_HEADER_SIZE;
+    let mut substreams = [&data[0..0]; 7];
+    for substream in &mut substreams {
+        let size = try!(reader.read_u32::<BigEndian>());
+        *substream = try!(block(data, offset, size));
+        offset += size as usize;
+    }
+    let (mut n_contour_stream, mut n_points_stream, mut flag_stream, mut glyph_stream) =
+        (substreams[0], substreams[1], substreams[2], substreams[3]);
+    let (mut composite_stream, bbox_stream, mut instruction_stream) =
+        (substreams[4], substreams[5], substreams[6]);
+
+    let bbox_bitmap_length = ((num_glyphs as usize + 31) >> 5) << 2;
+    if bbox_stream.len() < bbox_bitmap_length {
+        return Err(Error::Malformed)
+    }
+    let (bbox_bitmap, mut bbox_values) = bbox_stream.split_at(bbox_bitmap_length);
+
+    let overlap_bitmap = if option_flags & GLYF_OPTION_OVERLAP_SIMPLE_BITMAP != 0 {
+        Some(try!(block(data, offset, (num_glyphs as u32 + 7) >> 3)))
+    } else {
+        None
+    };
+
+    let mut glyf = vec![];
+    let mut loca = vec![];
+    let mut x_mins = Vec::with_capacity(num_glyphs as usize);
+    let mut points = vec![];
+    for glyph_id in 0..(num_glyphs as usize) {
+        write_loca_entry(&mut loca, glyf.len(), index_format);
+
+        let has_bbox = bit_is_set(bbox_bitmap, glyph_id);
+        let number_of_contours = try!(n_contour_stream.read_i16::<BigEndian>());
+        match number_of_contours {
+            0 => {
+                // Empty glyph.
+                if has_bbox {
+                    return Err(Error::Malformed)
+                }
+                x_mins.push(0);
+                continue
+            }
+            -1 => {
+                // Composite glyph. Its bounding box must be given explicitly.
+                if !has_bbox {
+                    return Err(Error::Malformed)
+                }
+                glyf.write_i16::<BigEndian>(-1).unwrap();
+                let x_min = try!(copy_bbox(&mut bbox_values, &mut glyf));
+                x_mins.push(x_min);
+
+                let components_start = composite_stream;
+                let mut have_instructions = false;
+                loop {
+                    let flags = try!(composite_stream.read_u16::<BigEndian>());
+                    have_instructions |= flags & WE_HAVE_INSTRUCTIONS != 0;
+
+                    let mut argument_size = mem::size_of::<u16>();
+                    argument_size += if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+                    if flags & WE_HAVE_A_SCALE != 0 {
+                        argument_size += 2
+                    } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                        argument_size += 4
+                    } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                        argument_size += 8
+                    }
+                    try!(composite_stream.jump(argument_size));
+
+                    if flags & MORE_COMPONENTS == 0 {
+                        break
+                    }
+                }
+                let components_length = components_start.len() - composite_stream.len();
+                glyf.extend_from_slice(&components_start[..components_length]);
+
+                if have_instructions {
+                    let instruction_length = try!(read_255_u16(&mut glyph_stream));
+                    glyf.write_u16::<BigEndian>(instruction_length).unwrap();
+                    try!(copy_bytes(&mut instruction_stream,
+                                    &mut glyf,
+                                    instruction_length as usize));
+                }
+            }
+            number_of_contours if number_of_contours > 0 => {
+                // Simple glyph. First, decode the points.
+                let mut end_points = Vec::with_capacity(number_of_contours as usize);
+                let mut point_count = 0u32;
+                for _ in 0..number_of_contours {
+                    point_count += try!(read_255_u16(&mut n_points_stream)) as u32;
+                    if point_count == 0 || point_count > u16::MAX as u32 + 1 {
+                        return Err(Error::Malformed)
+                    }
+                    end_points.push((point_count - 1) as u16)
+                }
+
+                points.clear();
+                let flags = try!(take(&mut flag_stream, point_count as usize));
+                try!(decode_triplets(flags, &mut glyph_stream, &mut points));
+
+                glyf.write_i16::<BigEndian>(number_of_contours).unwrap();
+                if has_bbox {
+                    let x_min = try!(copy_bbox(&mut bbox_values, &mut glyf));
+                    x_mins.push(x_min);
+                } else {
+                    let x_min = try!(write_computed_bbox(&points, &mut glyf));
+                    x_mins.push(x_min);
+                }
+
+                for &end_point in &end_points {
+                    glyf.write_u16::<BigEndian>(end_point).unwrap();
+                }
+
+                let instruction_length = try!(read_255_u16(&mut glyph_stream));
+                glyf.write_u16::<BigEndian>(instruction_length).unwrap();
+                try!(copy_bytes(&mut instruction_stream, &mut glyf, instruction_length as usize));
+
+                let overlap = match overlap_bitmap {
+                    Some(overlap_bitmap) => bit_is_set(overlap_bitmap, glyph_id),
+                    None => false,
+                };
+                write_simple_glyph_points(&points, overlap, &mut glyf);
+            }
+            _ => return Err(Error::Malformed),
+        }
+
+        // Pad each glyph to a four-byte boundary, which keeps short `loca` offsets even.
+        while glyf.len() % 4 != 0 {
+            glyf.push(0)
+        }
+    }
+
+    if index_format == 0 && glyf.len() > (u16::MAX as usize) * 2 {
+        return Err(Error::Malformed)
+    }
+    write_loca_entry(&mut loca, glyf.len(), index_format);
+
+    Ok(ReconstructedGlyf {
+        glyf: glyf,
+        loca: loca,
+        x_mins: x_mins,
+    })
+}
+
+#[derive(Clone, Copy)]
+struct TripletPoint {
+    x: i32,
+    y: i32,
+    on_curve: bool,
+}
+
+// Decodes the triplet-encoded coordinates of a simple glyph into absolute positions.
+fn decode_triplets(flags: &[u8], glyph_stream: &mut &[u8], points: &mut Vec<TripletPoint>)
+                   -> Result<(), Error> {
+    fn with_sign(flag: u8, value: i32) -> i32 {
+        if flag & 1 != 0 {
+            value
+        } else {
+            -value
+        }
+    }
+
+    let (mut x, mut y) = (0i32, 0i32);
+    for &flag in flags {
+        let on_curve = flag & 0x80 == 0;
+        let flag = flag & 0x7f;
+        let data_length = if flag < 84 {
+            1
+        } else if flag < 120 {
+            2
+        } else if flag < 124 {
+            3
+        } else {
+            4
+        };
+        let data = try!(take(glyph_stream, data_length));
+
+        let (dx, dy);
+        if flag < 10 {
+            dx = 0;
+            dy = with_sign(flag, (((flag & 14) as i32) << 7) + data[0] as i32);
+        } else if flag < 20 {
+            dx = with_sign(flag, ((((flag - 10) & 14) as i32) << 7) + data[0] as i32);
+            dy = 0;
+        } else if flag < 84 {
+            let (b0, b1) = ((flag - 20) as i32, data[0] as i32);
+            dx = with_sign(flag, 1 + (b0 & 0x30) + (b1 >> 4));
+            dy = with_sign(flag >> 1, 1 + ((b0 & 0x0c) << 2) + (b1 & 0x0f));
+        } else if flag < 120 {
+            let b0 = (flag - 84) as i32;
+            dx = with_sign(flag, 1 + ((b0 / 12) << 8) + data[0] as i32);
+            dy = with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + data[1] as i32);
+        } else if flag < 124 {
+            let b2 = data[1] as i32;
+            dx = with_sign(flag, ((data[0] as i32) << 4) + (b2 >> 4));
+            dy = with_sign(flag >> 1, ((b2 & 0x0f) << 8) + data[2] as i32);
+        } else {
+            dx = with_sign(flag, ((data[0] as i32) << 8) + data[1] as i32);
+            dy = with_sign(flag >> 1, ((data[2] as i32) << 8) + data[3] as i32);
+        }
+
+        x += dx;
+        y += dy;
+        if x < i16::MIN as i32 || x > i16::MAX as i32 || y < i16::MIN as i32 ||
+                y > i16::MAX as i32 {
+            return Err(Error::Malformed)
+        }
+        points.push(TripletPoint {
+            x: x,
+            y: y,
+            on_curve: on_curve,
+        })
+    }
+
+    Ok(())
+}
+
+// Writes the flags and coordinates of a simple glyph in the standard TrueType encoding.
+fn write_simple_glyph_points(points: &[TripletPoint], overlap: bool, glyf: &mut Vec<u8>) {
+    let mut flags = Vec::with_capacity(points.len());
+    let mut x_coordinates = vec![];
+    let mut y_coordinates = vec![];
+    let (mut last_x, mut last_y) = (0, 0);
+    for (point_index, point) in points.iter().enumerate() {
+        let mut flag = if point.on_curve { ON_CURVE_POINT } else { 0 };
+        if point_index == 0 && overlap {
+            flag |= OVERLAP_SIMPLE
+        }
+
+        let (dx, dy) = (point.x - last_x, point.y - last_y);
+        flag |= write_coordinate(dx,
+                                 X_SHORT_VECTOR,
+                                 X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR,
+                                 &mut x_coordinates);
+        flag |= write_coordinate(dy,
+                                 Y_SHORT_VECTOR,
+                                 Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR,
+                                 &mut y_coordinates);
+        flags.push(flag);
+
+        last_x = point.x;
+        last_y = point.y;
+    }
+
+    // Run-length encode the flags.
+    let mut flag_index = 0;
+    while flag_index < flags.len() {
+        let flag = flags[flag_index];
+        let mut repeat_count = 0;
+        while repeat_count < 255 && flag_index + repeat_count + 1 < flags.len() &&
+                flags[flag_index + repeat_count + 1] == flag {
+            repeat_count += 1
+        }
+        if repeat_count > 0 {
+            glyf.push(flag | REPEAT_FLAG);
+            glyf.push(repeat_count as u8);
+        } else {
+            glyf.push(flag)
+        }
+        flag_index += repeat_count + 1
+    }
+
+    glyf.extend_from_slice(&x_coordinates);
+    glyf.extend_from_slice(&y_coordinates);
+}
+
+fn write_coordinate(delta: i32, short_flag: u8, same_or_positive_flag: u8, output: &mut Vec<u8>)
+                    -> u8 {
+    if delta == 0 {
+        same_or_positive_flag
+    } else if delta > -256 && delta < 256 {
+        output.push(delta.abs() as u8);
+        if delta > 0 {
+            short_flag | same_or_positive_flag
+        } else {
+            short_flag
+        }
+    } else {
+        output.write_i16::<BigEndian>(delta as i16).unwrap();
+        0
+    }
+}
+
+fn write_computed_bbox(points: &[TripletPoint], glyf: &mut Vec<u8>) -> Result<i16, Error> {
+    let first_point = try!(points.first().ok_or(Error::Malformed));
+    let (mut x_min, mut y_min) = (first_point.x, first_point.y);
+    let (mut x_max, mut y_max) = (first_point.x, first_point.y);
+    for point in points {
+        x_min = cmp::min(x_min, point.x);
+        y_min = cmp::min(y_min, point.y);
+        x_max = cmp::max(x_max, point.x);
+        y_max = cmp::max(y_max, point.y);
+    }
+    for &value in &[x_min, y_min, x_max, y_max] {
+        glyf.write_i16::<BigEndian>(value as i16).unwrap();
+    }
+    Ok(x_min as i16)
+}
+
+fn copy_bbox(bbox_values: &mut &[u8], glyf: &mut Vec<u8>) -> Result<i16, Error> {
+    let bbox = try!(take(bbox_values, mem::size_of::<i16>() * 4));
+    glyf.extend_from_slice(bbox);
+    Ok(BigEndian::read_i16(bbox))
+}
+
+fn reconstruct_hmtx(data: &[u8], number_of_h_metrics: u16, x_mins: &[i16])
+                    -> Result<Vec<u8>, Error> {
+    let mut reader = data;
+    let flags = try!(reader.read_u8());
+    let proportional_lsbs_omitted = flags & HMTX_TRANSFORM_PROPORTIONAL_LSBS_OMITTED != 0;
+    let monospaced_lsbs_omitted = flags & HMTX_TRANSFORM_MONOSPACED_LSBS_OMITTED != 0;
+    if flags & !(HMTX_TRANSFORM_PROPORTIONAL_LSBS_OMITTED |
+                 HMTX_TRANSFORM_MONOSPACED_LSBS_OMITTED) != 0 ||
+            !(proportional_lsbs_omitted || monospaced_lsbs_omitted) {
+        return Err(Error::Malformed)
+    }
+
+    let num_glyphs = x_mins.len();
+    let number_of_h_metrics = number_of_h_metrics as usize;
+    if number_of_h_metrics == 0 || number_of_h_metrics > num_glyphs {
+        return Err(Error::Malformed)
+    }
+
+    let advance_widths = try!(take(&mut reader, number_of_h_metrics * 2));
+    let mut lsbs = Vec::with_capacity(num_glyphs);
+    for glyph_id in 0..num_glyphs {
+        let omitted = if glyph_id < number_of_h_metrics {
+            proportional_lsbs_omitted
+        } else {
+            monospaced_lsbs_omitted
+        };
+        if omitted {
+            lsbs.push(x_mins[glyph_id])
+        } else {
+            lsbs.push(try!(reader.read_i16::<BigEndian>()))
+        }
+    }
+
+    let mut hmtx = Vec::with_capacity(number_of_h_metrics * 2 + num_glyphs * 2);
+    for (glyph_id, &lsb) in lsbs.iter().enumerate() {
+        if glyph_id < number_of_h_metrics {
+            hmtx.extend_from_slice(&advance_widths[(glyph_id * 2)..(glyph_id * 2 + 2)]);
+        }
+        hmtx.write_i16::<BigEndian>(lsb).unwrap();
+    }
+    Ok(hmtx)
+}
+
+// Lays out a TrueType Collection. Tables are written once and shared between faces.
+fn build_collection(version: u32,
+                    fonts: &[CollectionFontEntry],
+                    entries: &[TableDirectoryEntry],
+                    tables: &[Vec<u8>])
+                    -> Result<Vec<u8>, Error> {
+    let mut header_size = 12 + fonts.len() * 4;
+    if version == 0x00020000 {
+        header_size += 12
+    }
+    let directories_size: usize =
+        fonts.iter().map(|font| 12 + font.table_indices.len() * 16).sum();
+
+    let mut table_offsets = Vec::with_capacity(tables.len());
+    let mut offset = header_size + directories_size;
+    for table in tables {
+        table_offsets.push(offset);
+        offset += (table.len() + 3) & !3;
+    }
+    if offset > u32::MAX as usize {
+        return Err(Error::Malformed)
+    }
+
+    let mut collection = Vec::with_capacity(offset);
+    collection.write_u32::<BigEndian>(TTCF).unwrap();
+    collection.write_u32::<BigEndian>(version).unwrap();
+    collection.write_u32::<BigEndian>(fonts.len() as u32).unwrap();
+    let mut directory_offset = header_size;
+    for font in fonts {
+        collection.write_u32::<BigEndian>(directory_offset as u32).unwrap();
+        directory_offset += 12 + font.table_indices.len() * 16;
+    }
+    if version == 0x00020000 {
+        // The digital signature is invalidated by decoding, so drop it.
+        collection.extend_from_slice(&[0; 12]);
+    }
+
+    for font in fonts {
+        let mut table_indices = font.table_indices.clone();
+        table_indices.sort_by_key(|&index| entries[index as usize].tag);
+
+        try!(woff::write_sfnt_header(&mut collection, font.flavor, table_indices.len()));
+
+        for &table_index in &table_indices {
+            let table = &tables[table_index as usize];
+            collection.write_u32::<BigEndian>(entries[table_index as usize].tag).unwrap();
+            collection.write_u32::<BigEndian>(woff::calculate_checksum(table)).unwrap();
+            collection.write_u32::<BigEndian>(table_offsets[table_index as usize] as u32).unwrap();
+            collection.write_u32::<BigEndian>(table.len() as u32).unwrap();
+        }
+    }
+
+    for table in tables {
+        collection.extend_from_slice(table);
+        while collection.len() % 4 != 0 {
+            collection.push(0)
+        }
+    }
+
+    Ok(collection)
+}
+
+fn write_loca_entry(loca: &mut Vec<u8>, offset: usize, index_format: u16) {
+    if index_format == 0 {
+        loca.write_u16::<BigEndian>((offset / 2) as u16).unwrap()
+    } else {
+        loca.write_u32::<BigEndian>(offset as u32).unwrap()
+    }
+}
+
+#[inline]
+fn bit_is_set(bitmap: &[u8], index: usize) -> bool {
+    bitmap[index >> 3] & (0x80 >> (index & 7)) != 0
+}
+
+fn block(bytes: &[u8], offset: usize, length: u32) -> Result<&[u8], Error> {
+    match offset.checked_add(length as usize) {
+        Some(end) if end <= bytes.len() => Ok(&bytes[offset..end]),
+        _ => Err(Error::Malformed),
+    }
+}
+
+fn take<'a>(reader: &mut &'a [u8], length: usize) -> Result<&'a [u8], Error> {
+    if length > reader.len() {
+        return Err(Error::Malformed)
+    }
+    let (taken, rest) = reader.split_at(length);
+    *reader = rest;
+    Ok(taken)
+}
+
+fn copy_bytes(reader: &mut &[u8], output: &mut Vec<u8>, length: usize) -> Result<(), Error> {
+    output.extend_from_slice(try!(take(reader, length)));
+    Ok(())
+}
+
+/// Reads a `UIntBase128`: a big-endian base-128 integer of at most five bytes.
+fn read_uint_base128(reader: &mut &[u8]) -> Result<u32, Error> {
+    let mut value = 0u32;
+    for byte_index in 0..5 {
+        let byte = try!(reader.read_u8());
+
+        // Leading zeros are invalid.
+        if byte_index == 0 && byte == 0x80 {
+            return Err(Error::Malformed)
+        }
+        // Overflow is invalid.
+        if value & 0xfe000000 != 0 {
+            return Err(Error::Malformed)
+        }
+
+        value = (value << 7) | (byte & 0x7f) as u32;
+        if byte & 0x80 == 0 {
+            return Ok(value)
+        }
+    }
+    Err(Error::Malformed)
+}
+
+/// Reads a `255UInt16`: a variable-length encoding of a 16-bit unsigned integer.
+fn read_255_u16(reader: &mut &[u8]) -> Result<u16, Error> {
+    const ONE_MORE_BYTE_CODE_1: u8 = 255;
+    const ONE_MORE_BYTE_CODE_2: u8 = 254;
+    const WORD_CODE: u8 = 253;
+    const LOWEST_U_CODE: u16 = 253;
+
+    let code = try!(reader.read_u8());
+    match code {
+        WORD_CODE => reader.read_u16::<BigEndian>().map_err(Error::from),
+        ONE_MORE_BYTE_CODE_1 => {
+            Ok(try!(reader.read_u8()) as u16 + LOWEST_U_CODE)
+        }
+        ONE_MORE_BYTE_CODE_2 => {
+            Ok(try!(reader.read_u8()) as u16 + LOWEST_U_CODE * 2)
+        }
+        _ => Ok(code as u16),
+    }
+}
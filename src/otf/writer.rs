@@ -0,0 +1,721 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Writing fonts.
+//!
+//! `FontBuilder` assembles tables into an sfnt, and the other builders here produce the tables
+//! that this crate reads: `head`, `maxp`, `cmap`, `glyf` and `loca`, `hhea` and `hmtx`, and
+//! `name`. Together they let tests and tools make fonts with exactly the glyphs and encodings
+//! they need, and read them back with `FontData`.
+
+use byteorder::{BigEndian, WriteBytesExt};
+use error::Error;
+use otf::checksum;
+use otf::hmtx::{self, GlyphMetrics};
+use otf::loca;
+use otf::woff::{self, SfntTable};
+use otf::{SFNT_VERSION_TRUETYPE, Tag};
+use outline::Transform;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::i16;
+use std::i8;
+use std::mem;
+use std::u16;
+
+const HEAD_MAGIC_NUMBER: u32 = 0x5f0f3cf5;
+
+const PLATFORM_ID_MICROSOFT: u16 = 3;
+const MICROSOFT_ENCODING_ID_UNICODE_BMP: u16 = 1;
+const MICROSOFT_ENCODING_ID_UNICODE_UCS4: u16 = 10;
+const MICROSOFT_LANGUAGE_ID_ENGLISH_US: u16 = 0x0409;
+
+const CMAP_ENCODING_RECORD_SIZE: usize = 8;
+const NAME_RECORD_SIZE: usize = 12;
+
+// Simple glyph flags.
+const ON_CURVE: u8 = 1 << 0;
+const X_SHORT_VECTOR: u8 = 1 << 1;
+const Y_SHORT_VECTOR: u8 = 1 << 2;
+const REPEAT: u8 = 1 << 3;
+const X_IS_SAME_OR_POSITIVE: u8 = 1 << 4;
+const Y_IS_SAME_OR_POSITIVE: u8 = 1 << 5;
+
+// Component flags.
+const ARG_1_AND_2_ARE_WORDS: u16 = 1 << 0;
+const ARGS_ARE_XY_VALUES: u16 = 1 << 1;
+const WE_HAVE_A_SCALE: u16 = 1 << 3;
+const MORE_COMPONENTS: u16 = 1 << 5;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 1 << 6;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 1 << 7;
+
+/// Assembles tables into an sfnt.
+///
+/// The table directory is sorted by tag, each table starts on a four-byte boundary and is padded
+/// with zeroes, and the checksums are computed, including `checkSumAdjustment` in `head` if
+/// there is one. The tables themselves are written as given.
+#[derive(Clone, Debug)]
+pub struct FontBuilder<'a> {
+    /// The version at the start of the font. This is `SFNT_VERSION_TRUETYPE` by default.
+    pub sfnt_version: u32,
+    tables: BTreeMap<u32, Cow<'a, [u8]>>,
+}
+
+impl<'a> FontBuilder<'a> {
+    #[inline]
+    pub fn new() -> FontBuilder<'a> {
+        FontBuilder {
+            sfnt_version: SFNT_VERSION_TRUETYPE,
+            tables: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a table, replacing any table already added with the same tag.
+    #[inline]
+    pub fn add_table<T>(&mut self, tag: Tag, data: T) where T: Into<Cow<'a, [u8]>> {
+        self.tables.insert(tag.0, data.into());
+    }
+
+    #[inline]
+    pub fn remove_table(&mut self, tag: Tag) {
+        self.tables.remove(&tag.0);
+    }
+
+    #[inline]
+    pub fn has_table(&self, tag: Tag) -> bool {
+        self.tables.contains_key(&tag.0)
+    }
+
+    /// Writes the font. Fails with `InvalidArgument` if there are more tables than an sfnt can
+    /// hold or they don't fit in 32-bit offsets.
+    pub fn build(&self) -> Result<Vec<u8>, Error> {
+        let tables: Vec<_> = self.tables.iter().map(|(&tag, data)| {
+            SfntTable {
+                tag: tag,
+                checksum: woff::calculate_checksum(data),
+                data: data,
+            }
+        }).collect();
+        let mut sfnt = try!(woff::build_sfnt(self.sfnt_version, &tables));
+        try!(checksum::fix_checksum_adjustment(&mut sfnt));
+        Ok(sfnt)
+    }
+}
+
+/// The fields of a `head` table. `checkSumAdjustment` is left for `FontBuilder` to fill in.
+#[derive(Clone, Copy, Debug)]
+pub struct HeadBuilder {
+    pub font_revision: u32,
+    pub flags: u16,
+    pub units_per_em: u16,
+    /// The union of the bounding boxes of all the glyphs, as `(x_min, y_min, x_max, y_max)`.
+    pub bounds: (i16, i16, i16, i16),
+    pub mac_style: u16,
+    pub lowest_rec_ppem: u16,
+    /// 0 for a `loca` table of 16-bit offsets, 1 for 32-bit ones. `GlyfBuilder::build()` returns
+    /// the format of the `loca` table it builds.
+    pub index_to_loc_format: i16,
+}
+
+impl Default for HeadBuilder {
+    fn default() -> HeadBuilder {
+        HeadBuilder {
+            font_revision: 0x00010000,
+            flags: 0,
+            units_per_em: 1000,
+            bounds: (0, 0, 0, 0),
+            mac_style: 0,
+            lowest_rec_ppem: 8,
+            index_to_loc_format: 0,
+        }
+    }
+}
+
+impl HeadBuilder {
+    #[inline]
+    pub fn new() -> HeadBuilder {
+        HeadBuilder::default()
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut head = vec![];
+        head.write_u32::<BigEndian>(0x00010000).unwrap();
+        head.write_u32::<BigEndian>(self.font_revision).unwrap();
+        head.write_u32::<BigEndian>(0).unwrap();
+        head.write_u32::<BigEndian>(HEAD_MAGIC_NUMBER).unwrap();
+        head.write_u16::<BigEndian>(self.flags).unwrap();
+        head.write_u16::<BigEndian>(self.units_per_em).unwrap();
+
+        // The creation and modification dates.
+        head.write_i64::<BigEndian>(0).unwrap();
+        head.write_i64::<BigEndian>(0).unwrap();
+
+        let (x_min, y_min, x_max, y_max) = self.bounds;
+        for &value in &[x_min, y_min, x_max, y_max] {
+            head.write_i16::<BigEndian>(value).unwrap()
+        }
+        head.write_u16::<BigEndian>(self.mac_style).unwrap();
+        head.write_u16::<BigEndian>(self.lowest_rec_ppem).unwrap();
+
+        // The font direction hint, which is deprecated and always 2.
+        head.write_i16::<BigEndian>(2).unwrap();
+        head.write_i16::<BigEndian>(self.index_to_loc_format).unwrap();
+        head.write_i16::<BigEndian>(0).unwrap();
+        head
+    }
+}
+
+/// The fields of a version 1.0 `maxp` table. The point, contour, and component maxima are only
+/// hints for rasterizers, so they're left zero.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxpBuilder {
+    pub num_glyphs: u16,
+    pub max_zones: u16,
+    pub max_twilight_points: u16,
+    pub max_storage: u16,
+    pub max_function_defs: u16,
+    pub max_instruction_defs: u16,
+    pub max_stack_elements: u16,
+}
+
+impl Default for MaxpBuilder {
+    fn default() -> MaxpBuilder {
+        MaxpBuilder {
+            num_glyphs: 0,
+            max_zones: 2,
+            max_twilight_points: 0,
+            max_storage: 0,
+            max_function_defs: 0,
+            max_instruction_defs: 0,
+            max_stack_elements: 0,
+        }
+    }
+}
+
+impl MaxpBuilder {
+    #[inline]
+    pub fn new(num_glyphs: u16) -> MaxpBuilder {
+        MaxpBuilder {
+            num_glyphs: num_glyphs,
+            ..MaxpBuilder::default()
+        }
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut maxp = vec![];
+        maxp.write_u32::<BigEndian>(0x00010000).unwrap();
+        maxp.write_u16::<BigEndian>(self.num_glyphs).unwrap();
+        for _ in 0..4 {
+            maxp.write_u16::<BigEndian>(0).unwrap()
+        }
+        for &value in &[self.max_zones,
+                        self.max_twilight_points,
+                        self.max_storage,
+                        self.max_function_defs,
+                        self.max_instruction_defs,
+                        self.max_stack_elements] {
+            maxp.write_u16::<BigEndian>(value).unwrap()
+        }
+        for _ in 0..3 {
+            maxp.write_u16::<BigEndian>(0).unwrap()
+        }
+        maxp
+    }
+}
+
+/// Builds a `cmap` table mapping Unicode codepoints to glyphs.
+///
+/// The table has a Windows Unicode BMP subtable in format 4, plus a Windows Unicode full
+/// repertoire subtable in format 12 if any codepoints are outside the Basic Multilingual Plane or
+/// there are too many ranges for format 4.
+#[derive(Clone, Debug, Default)]
+pub struct CmapBuilder {
+    /// If true, the format 12 subtable is written even if format 4 could hold every mapping.
+    pub format_12: bool,
+    mappings: BTreeMap<u32, u16>,
+}
+
+impl CmapBuilder {
+    #[inline]
+    pub fn new() -> CmapBuilder {
+        CmapBuilder::default()
+    }
+
+    /// Maps a codepoint to a glyph, replacing any glyph it was already mapped to.
+    #[inline]
+    pub fn add_mapping(&mut self, codepoint: u32, glyph_id: u16) {
+        self.mappings.insert(codepoint, glyph_id);
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mappings: Vec<_> = self.mappings.iter().map(|(&codepoint, &glyph_id)| {
+            (codepoint, glyph_id)
+        }).collect();
+        let bmp_mappings: Vec<_> = mappings.iter().cloned().filter(|&(codepoint, _)| {
+            codepoint < u16::MAX as u32
+        }).collect();
+        let format_4 = build_cmap_format_4(&bmp_mappings);
+        let format_12 = if self.format_12 || format_4.is_none() ||
+                bmp_mappings.len() < mappings.len() {
+            Some(build_cmap_format_12(&mappings))
+        } else {
+            None
+        };
+
+        let mut subtables = vec![];
+        if let Some(ref format_4) = format_4 {
+            subtables.push((MICROSOFT_ENCODING_ID_UNICODE_BMP, format_4))
+        }
+        if let Some(ref format_12) = format_12 {
+            subtables.push((MICROSOFT_ENCODING_ID_UNICODE_UCS4, format_12))
+        }
+
+        let mut cmap = vec![];
+        cmap.write_u16::<BigEndian>(0).unwrap();
+        cmap.write_u16::<BigEndian>(subtables.len() as u16).unwrap();
+        let mut offset = mem::size_of::<u16>() * 2 + subtables.len() * CMAP_ENCODING_RECORD_SIZE;
+        for &(encoding_id, subtable) in &subtables {
+            cmap.write_u16::<BigEndian>(PLATFORM_ID_MICROSOFT).unwrap();
+            cmap.write_u16::<BigEndian>(encoding_id).unwrap();
+            cmap.write_u32::<BigEndian>(offset as u32).unwrap();
+            offset += subtable.len()
+        }
+        for &(_, subtable) in &subtables {
+            cmap.extend_from_slice(subtable)
+        }
+        cmap
+    }
+}
+
+// Groups sorted mappings into runs of consecutive codepoints mapped to consecutive glyphs,
+// returning the first codepoint, last codepoint, and first glyph of each.
+fn runs(mappings: &[(u32, u16)]) -> Vec<(u32, u32, u16)> {
+    let mut runs: Vec<(u32, u32, u16)> = vec![];
+    for &(codepoint, glyph_id) in mappings {
+        if let Some(run) = runs.last_mut() {
+            if codepoint == run.1 + 1 && glyph_id as u32 == run.2 as u32 + (codepoint - run.0) {
+                run.1 = codepoint;
+                continue
+            }
+        }
+        runs.push((codepoint, codepoint, glyph_id))
+    }
+    runs
+}
+
+// Builds a format 4 subtable with one delta segment per run, or returns `None` if it would be
+// too long.
+fn build_cmap_format_4(mappings: &[(u32, u16)]) -> Option<Vec<u8>> {
+    let mut segments: Vec<_> = runs(mappings).into_iter().map(|(start, end, glyph_id)| {
+        (start as u16, end as u16, glyph_id.wrapping_sub(start as u16))
+    }).collect();
+    segments.push((u16::MAX, u16::MAX, 1));
+
+    let seg_count = segments.len();
+    let length = mem::size_of::<u16>() * (8 + seg_count * 4);
+    if length > u16::MAX as usize {
+        return None
+    }
+    let mut entry_selector = 0;
+    while (2 << entry_selector) <= seg_count {
+        entry_selector += 1
+    }
+    let search_range = 2 << entry_selector;
+
+    let mut subtable = vec![];
+    for &value in &[4, length as u16, 0, seg_count as u16 * 2, search_range as u16,
+                    entry_selector as u16, (seg_count * 2 - search_range) as u16] {
+        subtable.write_u16::<BigEndian>(value).unwrap()
+    }
+    for segment in &segments {
+        subtable.write_u16::<BigEndian>(segment.1).unwrap()
+    }
+    subtable.write_u16::<BigEndian>(0).unwrap();
+    for segment in &segments {
+        subtable.write_u16::<BigEndian>(segment.0).unwrap()
+    }
+    for segment in &segments {
+        subtable.write_u16::<BigEndian>(segment.2).unwrap()
+    }
+    for _ in &segments {
+        subtable.write_u16::<BigEndian>(0).unwrap()
+    }
+    Some(subtable)
+}
+
+fn build_cmap_format_12(mappings: &[(u32, u16)]) -> Vec<u8> {
+    let runs = runs(mappings);
+    let mut subtable = vec![];
+    subtable.write_u16::<BigEndian>(12).unwrap();
+    subtable.write_u16::<BigEndian>(0).unwrap();
+    subtable.write_u32::<BigEndian>(16 + runs.len() as u32 * 12).unwrap();
+    subtable.write_u32::<BigEndian>(0).unwrap();
+    subtable.write_u32::<BigEndian>(runs.len() as u32).unwrap();
+    for &(start, end, glyph_id) in &runs {
+        subtable.write_u32::<BigEndian>(start).unwrap();
+        subtable.write_u32::<BigEndian>(end).unwrap();
+        subtable.write_u32::<BigEndian>(glyph_id as u32).unwrap();
+    }
+    subtable
+}
+
+/// One component of a composite glyph.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompositeComponent {
+    pub glyph_id: u16,
+    /// Places the component in the composite glyph. The offset is rounded to font units, and the
+    /// rest to 2.14 fixed point.
+    pub transform: Transform,
+}
+
+impl CompositeComponent {
+    #[inline]
+    pub fn new(glyph_id: u16, transform: Transform) -> CompositeComponent {
+        CompositeComponent {
+            glyph_id: glyph_id,
+            transform: transform,
+        }
+    }
+}
+
+/// Builds `glyf` and `loca` tables. Glyphs get IDs in the order they're added, starting at 0.
+#[derive(Clone, Debug, Default)]
+pub struct GlyfBuilder {
+    glyphs: Vec<Vec<u8>>,
+    // The bounding box of each glyph, or `None` if it's empty or was added raw.
+    bounds: Vec<Option<(i16, i16, i16, i16)>>,
+}
+
+impl GlyfBuilder {
+    #[inline]
+    pub fn new() -> GlyfBuilder {
+        GlyfBuilder::default()
+    }
+
+    #[inline]
+    pub fn glyph_count(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    /// Adds a glyph with no outline, like a space.
+    pub fn add_empty_glyph(&mut self) -> u16 {
+        self.add_glyph(vec![], None)
+    }
+
+    /// Adds a glyph whose data is written as given, valid or not.
+    pub fn add_raw_glyph(&mut self, data: &[u8]) -> u16 {
+        self.add_glyph(data.to_vec(), None)
+    }
+
+    /// Adds a simple glyph with the given contours of `(x, y, on_curve)` points and glyph
+    /// program. Coordinates are stored in the smallest form that holds them, and runs of
+    /// repeated flags are compressed.
+    pub fn add_simple_glyph(&mut self, contours: &[&[(i16, i16, bool)]], instructions: &[u8])
+                            -> u16 {
+        let points: Vec<_> = contours.iter().flat_map(|contour| contour.iter()).cloned().collect();
+        if points.is_empty() {
+            return self.add_empty_glyph()
+        }
+        let bounds = points.iter().fold((i16::MAX, i16::MAX, i16::MIN, i16::MIN),
+                                        |bounds, &(x, y, _)| {
+            (bounds.0.min(x), bounds.1.min(y), bounds.2.max(x), bounds.3.max(y))
+        });
+
+        let mut glyph = vec![];
+        glyph.write_i16::<BigEndian>(contours.len() as i16).unwrap();
+        for &value in &[bounds.0, bounds.1, bounds.2, bounds.3] {
+            glyph.write_i16::<BigEndian>(value).unwrap()
+        }
+        let mut end_point = 0;
+        for contour in contours {
+            end_point += contour.len();
+            glyph.write_u16::<BigEndian>((end_point as u16).wrapping_sub(1)).unwrap()
+        }
+        glyph.write_u16::<BigEndian>(instructions.len() as u16).unwrap();
+        glyph.extend_from_slice(instructions);
+
+        // Encode each point relative to the one before it.
+        let (mut flags, mut x_coordinates, mut y_coordinates) = (vec![], vec![], vec![]);
+        let mut last_position = (0, 0);
+        for &(x, y, on_curve) in &points {
+            let mut point_flags = if on_curve { ON_CURVE } else { 0 };
+            point_flags |= encode_coordinate(x.wrapping_sub(last_position.0),
+                                             X_SHORT_VECTOR,
+                                             X_IS_SAME_OR_POSITIVE,
+                                             &mut x_coordinates);
+            point_flags |= encode_coordinate(y.wrapping_sub(last_position.1),
+                                             Y_SHORT_VECTOR,
+                                             Y_IS_SAME_OR_POSITIVE,
+                                             &mut y_coordinates);
+            flags.push(point_flags);
+            last_position = (x, y)
+        }
+
+        let mut index = 0;
+        while index < flags.len() {
+            let mut repeat_count = 0;
+            while repeat_count < 255 && index + repeat_count + 1 < flags.len() &&
+                    flags[index + repeat_count + 1] == flags[index] {
+                repeat_count += 1
+            }
+            if repeat_count > 0 {
+                glyph.push(flags[index] | REPEAT);
+                glyph.push(repeat_count as u8)
+            } else {
+                glyph.push(flags[index])
+            }
+            index += repeat_count + 1
+        }
+        glyph.extend_from_slice(&x_coordinates);
+        glyph.extend_from_slice(&y_coordinates);
+        self.add_glyph(glyph, Some(bounds))
+    }
+
+    /// Adds a composite glyph made of the given components.
+    ///
+    /// The bounding box in its header covers the transformed bounding boxes of the components
+    /// that were already added as simple or composite glyphs.
+    pub fn add_composite_glyph(&mut self, components: &[CompositeComponent]) -> u16 {
+        let mut glyph = vec![];
+        let mut bounds: Option<(i16, i16, i16, i16)> = None;
+        for (index, component) in components.iter().enumerate() {
+            let transform = &component.transform;
+            let (dx, dy) = (round_to_i16(transform.dx), round_to_i16(transform.dy));
+            let mut flags = ARGS_ARE_XY_VALUES;
+            let fits_in_i8 = |value: i16| value >= i8::MIN as i16 && value <= i8::MAX as i16;
+            if !fits_in_i8(dx) || !fits_in_i8(dy) {
+                flags |= ARG_1_AND_2_ARE_WORDS
+            }
+            if index + 1 < components.len() {
+                flags |= MORE_COMPONENTS
+            }
+            let scale = if transform.yx != 0.0 || transform.xy != 0.0 {
+                flags |= WE_HAVE_A_TWO_BY_TWO;
+                vec![transform.xx, transform.yx, transform.xy, transform.yy]
+            } else if transform.xx != transform.yy {
+                flags |= WE_HAVE_AN_X_AND_Y_SCALE;
+                vec![transform.xx, transform.yy]
+            } else if transform.xx != 1.0 {
+                flags |= WE_HAVE_A_SCALE;
+                vec![transform.xx]
+            } else {
+                vec![]
+            };
+
+            glyph.write_u16::<BigEndian>(flags).unwrap();
+            glyph.write_u16::<BigEndian>(component.glyph_id).unwrap();
+            if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+                glyph.write_i16::<BigEndian>(dx).unwrap();
+                glyph.write_i16::<BigEndian>(dy).unwrap()
+            } else {
+                glyph.write_i8(dx as i8).unwrap();
+                glyph.write_i8(dy as i8).unwrap()
+            }
+            for &value in &scale {
+                glyph.write_i16::<BigEndian>(round_to_i16(value * 16384.0)).unwrap()
+            }
+
+            // Transform the corners of the component's bounding box into place.
+            let component_bounds = self.bounds.get(component.glyph_id as usize).cloned();
+            if let Some(Some((x_min, y_min, x_max, y_max))) = component_bounds {
+                for &(x, y) in &[(x_min, y_min), (x_min, y_max), (x_max, y_min), (x_max, y_max)] {
+                    let (x, y) = (x as f32, y as f32);
+                    let new_x = round_to_i16(transform.xx * x + transform.xy * y + dx as f32);
+                    let new_y = round_to_i16(transform.yx * x + transform.yy * y + dy as f32);
+                    bounds = Some(match bounds {
+                        None => (new_x, new_y, new_x, new_y),
+                        Some(bounds) => {
+                            (bounds.0.min(new_x),
+                             bounds.1.min(new_y),
+                             bounds.2.max(new_x),
+                             bounds.3.max(new_y))
+                        }
+                    })
+                }
+            }
+        }
+
+        let mut header = vec![];
+        header.write_i16::<BigEndian>(-1).unwrap();
+        let (x_min, y_min, x_max, y_max) = bounds.unwrap_or((0, 0, 0, 0));
+        for &value in &[x_min, y_min, x_max, y_max] {
+            header.write_i16::<BigEndian>(value).unwrap()
+        }
+        header.extend_from_slice(&glyph);
+        self.add_glyph(header, bounds)
+    }
+
+    fn add_glyph(&mut self, data: Vec<u8>, bounds: Option<(i16, i16, i16, i16)>) -> u16 {
+        self.glyphs.push(data);
+        self.bounds.push(bounds);
+        (self.glyphs.len() - 1) as u16
+    }
+
+    /// Writes the glyphs, each padded to a four-byte boundary. Returns the `glyf` table, the
+    /// `loca` table, and the `indexToLocFormat` of the `loca` table for `head`.
+    pub fn build(&self) -> (Vec<u8>, Vec<u8>, i16) {
+        let mut glyf = vec![];
+        let mut offsets = Vec::with_capacity(self.glyphs.len() + 1);
+        for glyph in &self.glyphs {
+            offsets.push(glyf.len());
+            glyf.extend_from_slice(glyph);
+            while glyf.len() % 4 != 0 {
+                glyf.push(0)
+            }
+        }
+        offsets.push(glyf.len());
+
+        let (loca, index_to_loc_format) = loca::build_loca_table(&offsets);
+        (glyf, loca, index_to_loc_format)
+    }
+}
+
+// Writes one coordinate delta of a simple glyph, returning the flags that describe how.
+fn encode_coordinate(delta: i16, short_vector: u8, same_or_positive: u8, coordinates: &mut Vec<u8>)
+                     -> u8 {
+    if delta == 0 {
+        same_or_positive
+    } else if delta > -256 && delta < 256 {
+        coordinates.push(delta.abs() as u8);
+        if delta > 0 { short_vector | same_or_positive } else { short_vector }
+    } else {
+        coordinates.write_i16::<BigEndian>(delta).unwrap();
+        0
+    }
+}
+
+#[inline]
+fn round_to_i16(value: f32) -> i16 {
+    value.round().max(i16::MIN as f32).min(i16::MAX as f32) as i16
+}
+
+/// Builds an `hhea` table and the `hmtx` table it describes. Since `vhea` and `vmtx` have the
+/// same layout, it builds those too.
+#[derive(Clone, Debug, Default)]
+pub struct HheaBuilder {
+    pub ascender: i16,
+    pub descender: i16,
+    pub line_gap: i16,
+    /// The metrics of each glyph, in glyph ID order.
+    pub metrics: Vec<GlyphMetrics>,
+}
+
+impl HheaBuilder {
+    #[inline]
+    pub fn new(ascender: i16, descender: i16, line_gap: i16) -> HheaBuilder {
+        HheaBuilder {
+            ascender: ascender,
+            descender: descender,
+            line_gap: line_gap,
+            metrics: vec![],
+        }
+    }
+
+    #[inline]
+    pub fn add_metrics(&mut self, advance: u16, side_bearing: i16) {
+        self.metrics.push(GlyphMetrics {
+            advance: advance,
+            side_bearing: side_bearing,
+        })
+    }
+
+    /// Writes the `hhea` table and the `hmtx` table. The extents that need the glyphs' bounding
+    /// boxes are left zero.
+    pub fn build(&self) -> (Vec<u8>, Vec<u8>) {
+        let (hmtx, number_of_metrics) = hmtx::build_hmtx_table(&self.metrics);
+        let advance_max = self.metrics.iter().map(|metrics| metrics.advance).max().unwrap_or(0);
+        let min_side_bearing =
+            self.metrics.iter().map(|metrics| metrics.side_bearing).min().unwrap_or(0);
+
+        let mut hhea = vec![];
+        hhea.write_u32::<BigEndian>(0x00010000).unwrap();
+        for &value in &[self.ascender, self.descender, self.line_gap] {
+            hhea.write_i16::<BigEndian>(value).unwrap()
+        }
+        hhea.write_u16::<BigEndian>(advance_max).unwrap();
+        hhea.write_i16::<BigEndian>(min_side_bearing).unwrap();
+
+        // The minimum right side bearing, the maximum extent, the caret slope rise and run, the
+        // caret offset, four reserved fields, and the metric data format.
+        for &value in &[0, 0, 1, 0, 0, 0, 0, 0, 0, 0] {
+            hhea.write_i16::<BigEndian>(value).unwrap()
+        }
+        hhea.write_u16::<BigEndian>(number_of_metrics).unwrap();
+        (hhea, hmtx)
+    }
+}
+
+/// Builds a format 0 `name` table.
+#[derive(Clone, Debug, Default)]
+pub struct NameBuilder {
+    // The platform, encoding, language, and name IDs of each record, and its string.
+    records: Vec<([u16; 4], Vec<u8>)>,
+}
+
+impl NameBuilder {
+    #[inline]
+    pub fn new() -> NameBuilder {
+        NameBuilder::default()
+    }
+
+    /// Adds a name in US English for Windows, which is the record most software looks for.
+    pub fn add_name(&mut self, name_id: u16, name: &str) {
+        let mut data = vec![];
+        for code_unit in name.encode_utf16() {
+            data.write_u16::<BigEndian>(code_unit).unwrap()
+        }
+        self.add_record(PLATFORM_ID_MICROSOFT,
+                        MICROSOFT_ENCODING_ID_UNICODE_BMP,
+                        MICROSOFT_LANGUAGE_ID_ENGLISH_US,
+                        name_id,
+                        &data)
+    }
+
+    /// Adds a name record whose string is already encoded for its platform.
+    #[inline]
+    pub fn add_record(&mut self,
+                      platform_id: u16,
+                      encoding_id: u16,
+                      language_id: u16,
+                      name_id: u16,
+                      data: &[u8]) {
+        self.records.push(([platform_id, encoding_id, language_id, name_id], data.to_vec()))
+    }
+
+    /// Writes the table, with its records sorted by their IDs as the format requires.
+    ///
+    /// Returns `InvalidArgument` if the strings don't fit in the 64 KB the record offsets can
+    /// address.
+    pub fn build(&self) -> Result<Vec<u8>, Error> {
+        let mut records: Vec<_> = self.records.iter().collect();
+        records.sort_by_key(|record| record.0);
+
+        let mut name = vec![];
+        name.write_u16::<BigEndian>(0).unwrap();
+        name.write_u16::<BigEndian>(records.len() as u16).unwrap();
+        name.write_u16::<BigEndian>((6 + records.len() * NAME_RECORD_SIZE) as u16).unwrap();
+        let mut offset = 0;
+        for &&(ref ids, ref string) in &records {
+            if offset + string.len() > u16::MAX as usize {
+                return Err(Error::InvalidArgument)
+            }
+            for &value in ids {
+                name.write_u16::<BigEndian>(value).unwrap()
+            }
+            name.write_u16::<BigEndian>(string.len() as u16).unwrap();
+            name.write_u16::<BigEndian>(offset as u16).unwrap();
+            offset += string.len()
+        }
+        for &&(_, ref string) in &records {
+            name.extend_from_slice(string)
+        }
+        Ok(name)
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Glyph outlines as sequences of path commands, and the affine transforms applied to them.
+
+use euclid::Point2D;
+use std::vec::Vec;
+
+/// A single drawing command in a glyph outline, in font units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathCommand {
+    /// Starts a new contour at the given point.
+    MoveTo(Point2D<f32>),
+    /// Draws a straight line to the given point.
+    LineTo(Point2D<f32>),
+    /// Draws a quadratic Bézier curve with the given control point to the second point.
+    QuadTo(Point2D<f32>, Point2D<f32>),
+    /// Draws a cubic Bézier curve with the given two control points to the third point.
+    CurveTo(Point2D<f32>, Point2D<f32>, Point2D<f32>),
+    /// Closes the current contour.
+    ClosePath,
+}
+
+/// The outline and metrics of one glyph.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outline {
+    pub commands: Vec<PathCommand>,
+    /// The left side bearing point.
+    pub side_bearing: Point2D<f32>,
+    /// The advance vector.
+    pub advance: Point2D<f32>,
+}
+
+/// A 2D affine transform, mapping `(x, y)` to `(xx * x + xy * y + dx, yx * x + yy * y + dy)`.
+///
+/// The field order follows the OpenType `Affine2x3` record.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub xx: f32,
+    pub yx: f32,
+    pub xy: f32,
+    pub yy: f32,
+    pub dx: f32,
+    pub dy: f32,
+}
+
+impl Transform {
+    #[inline]
+    pub fn identity() -> Transform {
+        Transform::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+    }
+
+    #[inline]
+    pub fn new(xx: f32, yx: f32, xy: f32, yy: f32, dx: f32, dy: f32) -> Transform {
+        Transform {
+            xx: xx,
+            yx: yx,
+            xy: xy,
+            yy: yy,
+            dx: dx,
+            dy: dy,
+        }
+    }
+
+    #[inline]
+    pub fn translation(dx: f32, dy: f32) -> Transform {
+        Transform::new(1.0, 0.0, 0.0, 1.0, dx, dy)
+    }
+
+    #[inline]
+    pub fn scale(sx: f32, sy: f32) -> Transform {
+        Transform::new(sx, 0.0, 0.0, sy, 0.0, 0.0)
+    }
+
+    /// A counterclockwise rotation by the given angle in radians, in a y-up coordinate system.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn rotation(angle: f32) -> Transform {
+        let (sin, cos) = angle.sin_cos();
+        Transform::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    /// Returns the transform that applies `other` first and then `self`.
+    pub fn pre_transform(&self, other: &Transform) -> Transform {
+        Transform {
+            xx: self.xx * other.xx + self.xy * other.yx,
+            yx: self.yx * other.xx + self.yy * other.yx,
+            xy: self.xx * other.xy + self.xy * other.yy,
+            yy: self.yx * other.xy + self.yy * other.yy,
+            dx: self.xx * other.dx + self.xy * other.dy + self.dx,
+            dy: self.yx * other.dx + self.yy * other.dy + self.dy,
+        }
+    }
+
+    /// Returns the same transform applied about `center` instead of the origin.
+    pub fn around(&self, center: &Point2D<f32>) -> Transform {
+        Transform::translation(center.x, center.y).pre_transform(self)
+                                                  .pre_transform(&Transform::translation(
+                                                      -center.x, -center.y))
+    }
+
+    /// Returns the inverse of this transform, or `None` if it's singular.
+    pub fn inverse(&self) -> Option<Transform> {
+        let determinant = self.xx * self.yy - self.xy * self.yx;
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None
+        }
+        let (xx, yx) = (self.yy / determinant, -self.yx / determinant);
+        let (xy, yy) = (-self.xy / determinant, self.xx / determinant);
+        Some(Transform {
+            xx: xx,
+            yx: yx,
+            xy: xy,
+            yy: yy,
+            dx: -(xx * self.dx + xy * self.dy),
+            dy: -(yx * self.dx + yy * self.dy),
+        })
+    }
+
+    #[inline]
+    pub fn transform_point(&self, point: &Point2D<f32>) -> Point2D<f32> {
+        Point2D::new(self.xx * point.x + self.xy * point.y + self.dx,
+                     self.yx * point.x + self.yy * point.y + self.dy)
+    }
+}
@@ -0,0 +1,738 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Renders `COLR` color glyphs to RGBA images on the CPU.
+//!
+//! Version 1 paint graphs need gradients and blend modes that the GPU compositing path doesn't
+//! support, so they are evaluated here instead. Each node of the graph is rendered into its own
+//! premultiplied floating point buffer covering the whole image, and parents combine the buffers
+//! of their children.
+
+use error::Error;
+use euclid::{Point2D, Rect, Size2D};
+use limits::{Limit, Limits};
+use otf::colr::{ColorLine, ColrTable, CompositeMode, Extend, FOREGROUND_PALETTE_INDEX, Paint};
+use otf::colr::PaintOffset;
+use otf::cpal::Color;
+use otf::glyf::GlyfTable;
+use otf::head::HeadTable;
+use otf::loca::LocaTable;
+use outline::{PathCommand, Transform};
+use software_rasterizer::{CoverageMask, RgbaImage};
+use std::f32::consts::PI;
+use util::to_i32;
+
+// Paint graphs can reference themselves through `PaintColrGlyph`; this bounds the recursion.
+const MAX_PAINT_DEPTH: u32 = 64;
+// Paint graphs can also share subgraphs, so a small table can call for exponentially many
+// visits, each of which allocates a buffer the size of the image. This bounds the total number
+// of paints one glyph may visit.
+const MAX_PAINT_VISITS: u32 = 8192;
+
+// A premultiplied RGBA color with components from 0 to 1.
+type Rgba = [f32; 4];
+
+const TRANSPARENT: Rgba = [0.0; 4];
+
+/// A rendered color glyph.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorGlyphImage {
+    pub image: RgbaImage,
+    /// The position of the glyph origin in the image, in pixels from the top left.
+    pub origin: Point2D<i32>,
+}
+
+/// Renders the color glyphs of a font with a particular palette and foreground color.
+pub struct ColorGlyphRenderer<'a> {
+    colr: ColrTable<'a>,
+    glyf: GlyfTable<'a>,
+    loca: LocaTable<'a>,
+    units_per_em: u16,
+    palette: Vec<Color>,
+    foreground_color: Color,
+    /// Glyphs whose images would be wider or taller than `max_image_size` fail with
+    /// `Error::LimitExceeded` before anything is drawn.
+    pub limits: Limits,
+}
+
+impl<'a> ColorGlyphRenderer<'a> {
+    pub fn new(colr: ColrTable<'a>,
+               glyf: GlyfTable<'a>,
+               loca: LocaTable<'a>,
+               head: &HeadTable,
+               palette: Vec<Color>,
+               foreground_color: Color)
+               -> ColorGlyphRenderer<'a> {
+        ColorGlyphRenderer {
+            colr: colr,
+            glyf: glyf,
+            loca: loca,
+            units_per_em: head.units_per_em,
+            palette: palette,
+            foreground_color: foreground_color,
+            limits: Limits::default(),
+        }
+    }
+
+    /// Renders the given glyph at the given size, using its version 1 paint graph if it has one
+    /// and its version 0 layers otherwise.
+    ///
+    /// Returns `None` if the glyph isn't a color glyph.
+    pub fn render(&self, glyph_id: u16, pixels_per_em: f32)
+                  -> Result<Option<ColorGlyphImage>, Error> {
+        if self.units_per_em == 0 {
+            return Err(Error::Malformed)
+        }
+        let scale = pixels_per_em / self.units_per_em as f32;
+
+        let paint = try!(self.colr.base_glyph_paint(glyph_id));
+        let layers = match paint {
+            Some(_) => None,
+            None => {
+                match try!(self.colr.layers(glyph_id)) {
+                    None => return Ok(None),
+                    Some(layers) => Some(layers),
+                }
+            }
+        };
+
+        // Find the extent of the glyph in font units.
+        let mut visits = 0;
+        let bounds = match paint {
+            Some(paint) => {
+                match try!(self.colr.clip_box(glyph_id)) {
+                    Some(clip_box) => Some(clip_box),
+                    None => try!(self.paint_bounds(paint, &Transform::identity(), 0, &mut visits)),
+                }
+            }
+            None => {
+                let mut bounds: Option<Rect<f32>> = None;
+                for layer in layers.as_ref().unwrap() {
+                    let glyph_bounds = try!(self.glyph_bounds(layer.glyph_id,
+                                                              &Transform::identity()));
+                    bounds = union(bounds, glyph_bounds)
+                }
+                bounds
+            }
+        };
+
+        // Snap the extent outward to whole pixels. Pixel space has y pointing down.
+        let (left, top, right, bottom) = match bounds {
+            Some(bounds) => (to_i32((bounds.origin.x * scale).floor()),
+                             to_i32((bounds.max_y() * scale).ceil()),
+                             to_i32((bounds.max_x() * scale).ceil()),
+                             to_i32((bounds.origin.y * scale).floor())),
+            None => (0, 0, 0, 0),
+        };
+
+        // Every node of the graph gets a buffer this size, so check it before allocating.
+        let too_big = Error::LimitExceeded(Limit::ImageSize);
+        let width = try!(right.checked_sub(left).ok_or(too_big));
+        let height = try!(top.checked_sub(bottom).ok_or(too_big));
+        let origin = Point2D::new(try!(left.checked_neg().ok_or(too_big)), top);
+        let canvas = Canvas {
+            width: width as u32,
+            height: height as u32,
+        };
+        try!(self.limits.check(Limit::ImageSize, canvas.width));
+        try!(self.limits.check(Limit::ImageSize, canvas.height));
+        let transform =
+            Transform::new(scale, 0.0, 0.0, -scale, origin.x as f32, origin.y as f32);
+
+        let pixels = match paint {
+            Some(_) => {
+                try!(self.render_color_glyph(&canvas, glyph_id, &transform, 0, &mut visits))
+            }
+            None => {
+                let mut pixels = canvas.transparent();
+                for layer in layers.as_ref().unwrap() {
+                    let color = try!(layer.color(&self.palette, self.foreground_color));
+                    let color = premultiply(color, 1.0);
+                    let coverage = try!(self.glyph_coverage(&canvas, layer.glyph_id, &transform));
+                    for (pixel, coverage) in pixels.iter_mut().zip(coverage.into_iter()) {
+                        let source = scale_color(&color, coverage);
+                        *pixel = composite(&source, pixel, CompositeMode::SourceOver)
+                    }
+                }
+                pixels
+            }
+        };
+
+        let mut image = RgbaImage::new(canvas.width, canvas.height);
+        for (destination, pixel) in image.pixels.chunks_mut(4).zip(pixels.iter()) {
+            for (destination, &component) in destination.iter_mut().zip(pixel.iter()) {
+                *destination = (component.max(0.0).min(1.0) * 255.0).round() as u8
+            }
+        }
+
+        Ok(Some(ColorGlyphImage {
+            image: image,
+            origin: origin,
+        }))
+    }
+
+    // Renders the version 1 paint graph of a glyph, clipped to its clip box.
+    fn render_color_glyph(&self,
+                          canvas: &Canvas,
+                          glyph_id: u16,
+                          transform: &Transform,
+                          depth: u32,
+                          visits: &mut u32)
+                          -> Result<Vec<Rgba>, Error> {
+        let paint = match try!(self.colr.base_glyph_paint(glyph_id)) {
+            None => return Ok(canvas.transparent()),
+            Some(paint) => paint,
+        };
+        let mut pixels = try!(self.render_paint(canvas, paint, transform, depth + 1, visits));
+
+        if let Some(clip_box) = try!(self.colr.clip_box(glyph_id)) {
+            let mut mask = CoverageMask::new(canvas.width, canvas.height);
+            mask.fill_path(&rect_path(&clip_box), transform);
+            for (pixel, coverage) in pixels.iter_mut().zip(mask.coverage().into_iter()) {
+                *pixel = scale_color(pixel, coverage)
+            }
+        }
+
+        Ok(pixels)
+    }
+
+    fn render_paint(&self,
+                    canvas: &Canvas,
+                    paint: PaintOffset,
+                    transform: &Transform,
+                    depth: u32,
+                    visits: &mut u32)
+                    -> Result<Vec<Rgba>, Error> {
+        try!(visit(depth, visits));
+
+        match try!(self.colr.paint(paint)) {
+            Paint::ColrLayers { first_layer_index, layer_count } => {
+                let mut pixels = canvas.transparent();
+                for layer_index in 0..(layer_count as u32) {
+                    let layer_paint = try!(self.colr.layer_paint(first_layer_index + layer_index));
+                    let layer =
+                        try!(self.render_paint(canvas, layer_paint, transform, depth + 1, visits));
+                    for (pixel, source) in pixels.iter_mut().zip(layer.iter()) {
+                        *pixel = composite(source, pixel, CompositeMode::SourceOver)
+                    }
+                }
+                Ok(pixels)
+            }
+            Paint::Solid { palette_index, alpha } => {
+                let color = try!(self.stop_color(palette_index, alpha));
+                Ok(vec![color; canvas.pixel_count()])
+            }
+            Paint::LinearGradient { ref color_line, start, end, rotation_point } => {
+                // The gradient runs from `start` to `end` projected onto the line perpendicular
+                // to the one through `start` and `rotation_point`.
+                let direction = rotation_point - start;
+                let normal = Point2D::new(direction.y, -direction.x);
+                let normal_length_squared = dot(&normal, &normal);
+                let end = if normal_length_squared == 0.0 {
+                    end
+                } else {
+                    start + normal * (dot(&(end - start), &normal) / normal_length_squared)
+                };
+                let axis = end - start;
+                let axis_length_squared = dot(&axis, &axis);
+                self.render_gradient(canvas, color_line, transform, |point| {
+                    if axis_length_squared == 0.0 {
+                        return None
+                    }
+                    Some(dot(&(*point - start), &axis) / axis_length_squared)
+                })
+            }
+            Paint::RadialGradient {
+                ref color_line,
+                start_center,
+                start_radius,
+                end_center,
+                end_radius
+            } => {
+                // Find the largest `t` for which the interpolated circle passes through the point
+                // and has a nonnegative radius.
+                let center_delta = end_center - start_center;
+                let radius_delta = end_radius - start_radius;
+                let a = dot(&center_delta, &center_delta) - radius_delta * radius_delta;
+                self.render_gradient(canvas, color_line, transform, |point| {
+                    let point_delta = *point - start_center;
+                    let b = dot(&point_delta, &center_delta) + start_radius * radius_delta;
+                    let c = dot(&point_delta, &point_delta) - start_radius * start_radius;
+                    let valid = |t: f32| start_radius + t * radius_delta >= 0.0;
+                    if a.abs() < 1e-6 {
+                        if b == 0.0 {
+                            return None
+                        }
+                        let t = c / (2.0 * b);
+                        return if valid(t) { Some(t) } else { None }
+                    }
+                    let discriminant = b * b - a * c;
+                    if discriminant < 0.0 {
+                        return None
+                    }
+                    let root = discriminant.sqrt();
+                    let (t0, t1) = ((b + root) / a, (b - root) / a);
+                    let (larger, smaller) = if t0 > t1 { (t0, t1) } else { (t1, t0) };
+                    if valid(larger) {
+                        Some(larger)
+                    } else if valid(smaller) {
+                        Some(smaller)
+                    } else {
+                        None
+                    }
+                })
+            }
+            Paint::SweepGradient { ref color_line, center, start_angle, end_angle } => {
+                self.render_gradient(canvas, color_line, transform, |point| {
+                    let delta = *point - center;
+                    let mut angle = delta.y.atan2(delta.x) * 180.0 / PI;
+                    if angle < 0.0 {
+                        angle += 360.0
+                    }
+                    if start_angle == end_angle {
+                        // Only padding paints anything for an empty sweep.
+                        if color_line.extend != Extend::Pad {
+                            return None
+                        }
+                        return Some(if angle < start_angle { -1.0 } else { 2.0 })
+                    }
+                    Some((angle - start_angle) / (end_angle - start_angle))
+                })
+            }
+            Paint::Glyph { paint, glyph_id } => {
+                let mut pixels =
+                    try!(self.render_paint(canvas, paint, transform, depth + 1, visits));
+                let coverage = try!(self.glyph_coverage(canvas, glyph_id, transform));
+                for (pixel, coverage) in pixels.iter_mut().zip(coverage.into_iter()) {
+                    *pixel = scale_color(pixel, coverage)
+                }
+                Ok(pixels)
+            }
+            Paint::ColrGlyph { glyph_id } => {
+                self.render_color_glyph(canvas, glyph_id, transform, depth + 1, visits)
+            }
+            Paint::Transform { paint, transform: ref paint_transform } => {
+                let transform = transform.pre_transform(paint_transform);
+                self.render_paint(canvas, paint, &transform, depth + 1, visits)
+            }
+            Paint::Composite { source, mode, backdrop } => {
+                let mut pixels =
+                    try!(self.render_paint(canvas, backdrop, transform, depth + 1, visits));
+                let source = try!(self.render_paint(canvas, source, transform, depth + 1, visits));
+                for (pixel, source) in pixels.iter_mut().zip(source.iter()) {
+                    *pixel = composite(source, pixel, mode)
+                }
+                Ok(pixels)
+            }
+        }
+    }
+
+    // Evaluates a gradient at each pixel center. `position` maps a point in paint space to a
+    // position on the color line, or `None` if the gradient doesn't cover that point.
+    fn render_gradient<F>(&self,
+                          canvas: &Canvas,
+                          color_line: &ColorLine,
+                          transform: &Transform,
+                          position: F)
+                          -> Result<Vec<Rgba>, Error>
+                          where F: Fn(&Point2D<f32>) -> Option<f32> {
+        let inverse = match transform.inverse() {
+            None => return Ok(canvas.transparent()),
+            Some(inverse) => inverse,
+        };
+
+        let mut stops = vec![];
+        for stop in &color_line.stops {
+            stops.push((stop.offset, try!(self.stop_color(stop.palette_index, stop.alpha))))
+        }
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+        if stops.is_empty() {
+            return Ok(canvas.transparent())
+        }
+
+        let mut pixels = Vec::with_capacity(canvas.pixel_count());
+        for y in 0..canvas.height {
+            for x in 0..canvas.width {
+                let point = inverse.transform_point(&Point2D::new(x as f32 + 0.5, y as f32 + 0.5));
+                pixels.push(match position(&point) {
+                    Some(t) if t.is_finite() => color_at(&stops, color_line.extend, t),
+                    _ => TRANSPARENT,
+                })
+            }
+        }
+        Ok(pixels)
+    }
+
+    fn stop_color(&self, palette_index: u16, alpha: f32) -> Result<Rgba, Error> {
+        let color = if palette_index == FOREGROUND_PALETTE_INDEX {
+            self.foreground_color
+        } else {
+            *try!(self.palette.get(palette_index as usize).ok_or(Error::Malformed))
+        };
+        Ok(premultiply(color, alpha))
+    }
+
+    fn glyph_coverage(&self, canvas: &Canvas, glyph_id: u16, transform: &Transform)
+                      -> Result<Vec<f32>, Error> {
+        let commands = try!(self.glyf.path_commands(&self.loca, glyph_id as u32));
+        let mut mask = CoverageMask::new(canvas.width, canvas.height);
+        mask.fill_path(&commands, transform);
+        Ok(mask.coverage())
+    }
+
+    fn glyph_bounds(&self, glyph_id: u16, transform: &Transform)
+                    -> Result<Option<Rect<f32>>, Error> {
+        if try!(self.loca.location_of(glyph_id as u32)) ==
+                try!(self.loca.location_of(glyph_id as u32 + 1)) {
+            return Ok(None)
+        }
+        let bounds = try!(self.glyf.bounding_rect(&self.loca, glyph_id as u32));
+        let bounds = Rect::new(Point2D::new(bounds.origin.x as f32, bounds.origin.y as f32),
+                               bounds.size.cast().unwrap());
+        Ok(Some(transform_rect(&bounds, transform)))
+    }
+
+    // Returns the union of the bounds of all glyphs drawn by a paint, in font units. Fills that
+    // aren't clipped to a glyph don't contribute.
+    fn paint_bounds(&self,
+                    paint: PaintOffset,
+                    transform: &Transform,
+                    depth: u32,
+                    visits: &mut u32)
+                    -> Result<Option<Rect<f32>>, Error> {
+        try!(visit(depth, visits));
+
+        match try!(self.colr.paint(paint)) {
+            Paint::ColrLayers { first_layer_index, layer_count } => {
+                let mut bounds = None;
+                for layer_index in 0..(layer_count as u32) {
+                    let layer_paint = try!(self.colr.layer_paint(first_layer_index + layer_index));
+                    let layer_bounds =
+                        try!(self.paint_bounds(layer_paint, transform, depth + 1, visits));
+                    bounds = union(bounds, layer_bounds)
+                }
+                Ok(bounds)
+            }
+            Paint::Glyph { glyph_id, .. } => self.glyph_bounds(glyph_id, transform),
+            Paint::ColrGlyph { glyph_id } => {
+                if let Some(clip_box) = try!(self.colr.clip_box(glyph_id)) {
+                    return Ok(Some(transform_rect(&clip_box, transform)))
+                }
+                match try!(self.colr.base_glyph_paint(glyph_id)) {
+                    None => Ok(None),
+                    Some(paint) => self.paint_bounds(paint, transform, depth + 1, visits),
+                }
+            }
+            Paint::Transform { paint, transform: ref paint_transform } => {
+                let transform = transform.pre_transform(paint_transform);
+                self.paint_bounds(paint, &transform, depth + 1, visits)
+            }
+            Paint::Composite { source, backdrop, .. } => {
+                let source_bounds = try!(self.paint_bounds(source, transform, depth + 1, visits));
+                let backdrop_bounds =
+                    try!(self.paint_bounds(backdrop, transform, depth + 1, visits));
+                Ok(union(source_bounds, backdrop_bounds))
+            }
+            Paint::Solid { .. } |
+            Paint::LinearGradient { .. } |
+            Paint::RadialGradient { .. } |
+            Paint::SweepGradient { .. } => Ok(None),
+        }
+    }
+}
+
+// Counts a visit to a paint at the given depth, failing if the graph nests too deeply or the
+// glyph has visited too many paints.
+fn visit(depth: u32, visits: &mut u32) -> Result<(), Error> {
+    *visits += 1;
+    if depth > MAX_PAINT_DEPTH || *visits > MAX_PAINT_VISITS {
+        return Err(Error::Malformed)
+    }
+    Ok(())
+}
+
+struct Canvas {
+    width: u32,
+    height: u32,
+}
+
+impl Canvas {
+    #[inline]
+    fn pixel_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    #[inline]
+    fn transparent(&self) -> Vec<Rgba> {
+        vec![TRANSPARENT; self.pixel_count()]
+    }
+}
+
+// Evaluates a sorted, nonempty list of color stops at `t`, applying the extend mode over the
+// range between the first and last stops.
+fn color_at(stops: &[(f32, Rgba)], extend: Extend, t: f32) -> Rgba {
+    let (first, last) = (stops[0].0, stops[stops.len() - 1].0);
+    let length = last - first;
+    let t = if length <= 0.0 {
+        t
+    } else {
+        match extend {
+            Extend::Pad => t,
+            Extend::Repeat => first + (t - first) - ((t - first) / length).floor() * length,
+            Extend::Reflect => {
+                let period = 2.0 * length;
+                let offset = (t - first) - ((t - first) / period).floor() * period;
+                if offset > length {
+                    first + period - offset
+                } else {
+                    first + offset
+                }
+            }
+        }
+    };
+
+    if t <= first {
+        return stops[0].1
+    }
+    for window in stops.windows(2) {
+        let ((start_offset, start_color), (end_offset, end_color)) = (window[0], window[1]);
+        if t <= end_offset {
+            let fraction = if end_offset > start_offset {
+                (t - start_offset) / (end_offset - start_offset)
+            } else {
+                1.0
+            };
+            let mut color = TRANSPARENT;
+            for (index, component) in color.iter_mut().enumerate() {
+                *component = start_color[index] + (end_color[index] - start_color[index]) * fraction
+            }
+            return color
+        }
+    }
+    stops[stops.len() - 1].1
+}
+
+// Composites a premultiplied source color onto a premultiplied backdrop color.
+fn composite(source: &Rgba, backdrop: &Rgba, mode: CompositeMode) -> Rgba {
+    let (source_alpha, backdrop_alpha) = (source[3], backdrop[3]);
+
+    // The Porter-Duff operators are weighted sums of the source and backdrop.
+    let porter_duff = |source_factor: f32, backdrop_factor: f32| {
+        let mut result = TRANSPARENT;
+        for (index, component) in result.iter_mut().enumerate() {
+            let sum = source[index] * source_factor + backdrop[index] * backdrop_factor;
+            *component = sum.min(1.0)
+        }
+        result
+    };
+    match mode {
+        CompositeMode::Clear => return TRANSPARENT,
+        CompositeMode::Source => return *source,
+        CompositeMode::Destination => return *backdrop,
+        CompositeMode::SourceOver => return porter_duff(1.0, 1.0 - source_alpha),
+        CompositeMode::DestinationOver => return porter_duff(1.0 - backdrop_alpha, 1.0),
+        CompositeMode::SourceIn => return porter_duff(backdrop_alpha, 0.0),
+        CompositeMode::DestinationIn => return porter_duff(0.0, source_alpha),
+        CompositeMode::SourceOut => return porter_duff(1.0 - backdrop_alpha, 0.0),
+        CompositeMode::DestinationOut => return porter_duff(0.0, 1.0 - source_alpha),
+        CompositeMode::SourceAtop => return porter_duff(backdrop_alpha, 1.0 - source_alpha),
+        CompositeMode::DestinationAtop => return porter_duff(1.0 - backdrop_alpha, source_alpha),
+        CompositeMode::Xor => return porter_duff(1.0 - backdrop_alpha, 1.0 - source_alpha),
+        CompositeMode::Plus => return porter_duff(1.0, 1.0),
+        _ => {}
+    }
+
+    // The blend modes mix unpremultiplied colors where both are present, then composite the
+    // result as with "source over".
+    let unpremultiply = |color: &Rgba| {
+        if color[3] == 0.0 {
+            [0.0; 3]
+        } else {
+            [color[0] / color[3], color[1] / color[3], color[2] / color[3]]
+        }
+    };
+    let (source_color, backdrop_color) = (unpremultiply(source), unpremultiply(backdrop));
+    let blended = blend(&source_color, &backdrop_color, mode);
+
+    let both_alpha = source_alpha * backdrop_alpha;
+    let mut result = TRANSPARENT;
+    for index in 0..3 {
+        result[index] = source[index] * (1.0 - backdrop_alpha) +
+            backdrop[index] * (1.0 - source_alpha) + both_alpha * blended[index]
+    }
+    result[3] = source_alpha + backdrop_alpha - both_alpha;
+    result
+}
+
+// The W3C Compositing and Blending blend functions, on unpremultiplied colors.
+fn blend(source: &[f32; 3], backdrop: &[f32; 3], mode: CompositeMode) -> [f32; 3] {
+    let separable = |function: fn(f32, f32) -> f32| {
+        [function(backdrop[0], source[0]),
+         function(backdrop[1], source[1]),
+         function(backdrop[2], source[2])]
+    };
+    match mode {
+        CompositeMode::Multiply => separable(|b, s| b * s),
+        CompositeMode::Screen => separable(screen),
+        CompositeMode::Overlay => separable(|b, s| hard_light(s, b)),
+        CompositeMode::Darken => separable(|b, s| b.min(s)),
+        CompositeMode::Lighten => separable(|b, s| b.max(s)),
+        CompositeMode::ColorDodge => {
+            separable(|b, s| {
+                if b == 0.0 {
+                    0.0
+                } else if s >= 1.0 {
+                    1.0
+                } else {
+                    (b / (1.0 - s)).min(1.0)
+                }
+            })
+        }
+        CompositeMode::ColorBurn => {
+            separable(|b, s| {
+                if b >= 1.0 {
+                    1.0
+                } else if s == 0.0 {
+                    0.0
+                } else {
+                    1.0 - ((1.0 - b) / s).min(1.0)
+                }
+            })
+        }
+        CompositeMode::HardLight => separable(hard_light),
+        CompositeMode::SoftLight => {
+            separable(|b, s| {
+                if s <= 0.5 {
+                    b - (1.0 - 2.0 * s) * b * (1.0 - b)
+                } else {
+                    let d = if b <= 0.25 {
+                        ((16.0 * b - 12.0) * b + 4.0) * b
+                    } else {
+                        b.sqrt()
+                    };
+                    b + (2.0 * s - 1.0) * (d - b)
+                }
+            })
+        }
+        CompositeMode::Difference => separable(|b, s| (b - s).abs()),
+        CompositeMode::Exclusion => separable(|b, s| b + s - 2.0 * b * s),
+        CompositeMode::Hue => {
+            set_luminosity(&set_saturation(source, saturation(backdrop)), luminosity(backdrop))
+        }
+        CompositeMode::Saturation => {
+            set_luminosity(&set_saturation(backdrop, saturation(source)), luminosity(backdrop))
+        }
+        CompositeMode::Color => set_luminosity(source, luminosity(backdrop)),
+        CompositeMode::Luminosity => set_luminosity(backdrop, luminosity(source)),
+        _ => *source,
+    }
+}
+
+fn screen(backdrop: f32, source: f32) -> f32 {
+    backdrop + source - backdrop * source
+}
+
+fn hard_light(backdrop: f32, source: f32) -> f32 {
+    if source <= 0.5 {
+        backdrop * 2.0 * source
+    } else {
+        screen(backdrop, 2.0 * source - 1.0)
+    }
+}
+
+fn luminosity(color: &[f32; 3]) -> f32 {
+    0.3 * color[0] + 0.59 * color[1] + 0.11 * color[2]
+}
+
+fn set_luminosity(color: &[f32; 3], luminosity_value: f32) -> [f32; 3] {
+    let delta = luminosity_value - luminosity(color);
+    let color = [color[0] + delta, color[1] + delta, color[2] + delta];
+
+    // Clip the color back into gamut, preserving its luminosity.
+    let luminosity_value = luminosity(&color);
+    let min = color[0].min(color[1]).min(color[2]);
+    let max = color[0].max(color[1]).max(color[2]);
+    let mut result = color;
+    for component in &mut result {
+        if min < 0.0 {
+            *component = luminosity_value + (*component - luminosity_value) * luminosity_value /
+                (luminosity_value - min)
+        }
+        if max > 1.0 {
+            *component = luminosity_value + (*component - luminosity_value) *
+                (1.0 - luminosity_value) / (max - luminosity_value)
+        }
+    }
+    result
+}
+
+fn saturation(color: &[f32; 3]) -> f32 {
+    color[0].max(color[1]).max(color[2]) - color[0].min(color[1]).min(color[2])
+}
+
+fn set_saturation(color: &[f32; 3], saturation_value: f32) -> [f32; 3] {
+    let min = color[0].min(color[1]).min(color[2]);
+    let max = color[0].max(color[1]).max(color[2]);
+    if max <= min {
+        return [0.0; 3]
+    }
+    [(color[0] - min) * saturation_value / (max - min),
+     (color[1] - min) * saturation_value / (max - min),
+     (color[2] - min) * saturation_value / (max - min)]
+}
+
+fn premultiply(color: Color, alpha: f32) -> Rgba {
+    let alpha = color.a as f32 / 255.0 * alpha.max(0.0).min(1.0);
+    [color.r as f32 / 255.0 * alpha, color.g as f32 / 255.0 * alpha, color.b as f32 / 255.0 * alpha,
+     alpha]
+}
+
+#[inline]
+fn scale_color(color: &Rgba, factor: f32) -> Rgba {
+    [color[0] * factor, color[1] * factor, color[2] * factor, color[3] * factor]
+}
+
+#[inline]
+fn dot(a: &Point2D<f32>, b: &Point2D<f32>) -> f32 {
+    a.x * b.x + a.y * b.y
+}
+
+fn union(a: Option<Rect<f32>>, b: Option<Rect<f32>>) -> Option<Rect<f32>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.union(&b)),
+        (Some(a), None) => Some(a),
+        (None, b) => b,
+    }
+}
+
+fn rect_path(rect: &Rect<f32>) -> Vec<PathCommand> {
+    vec![
+        PathCommand::MoveTo(rect.origin),
+        PathCommand::LineTo(Point2D::new(rect.max_x(), rect.origin.y)),
+        PathCommand::LineTo(Point2D::new(rect.max_x(), rect.max_y())),
+        PathCommand::LineTo(Point2D::new(rect.origin.x, rect.max_y())),
+        PathCommand::ClosePath,
+    ]
+}
+
+fn transform_rect(rect: &Rect<f32>, transform: &Transform) -> Rect<f32> {
+    let corners = [
+        transform.transform_point(&rect.origin),
+        transform.transform_point(&Point2D::new(rect.max_x(), rect.origin.y)),
+        transform.transform_point(&Point2D::new(rect.origin.x, rect.max_y())),
+        transform.transform_point(&Point2D::new(rect.max_x(), rect.max_y())),
+    ];
+    let (mut min, mut max) = (corners[0], corners[0]);
+    for corner in &corners[1..] {
+        min = Point2D::new(min.x.min(corner.x), min.y.min(corner.y));
+        max = Point2D::new(max.x.max(corner.x), max.y.max(corner.y));
+    }
+    Rect::new(min, Size2D::new(max.x - min.x, max.y - min.y))
+}
@@ -0,0 +1,365 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A minimal PNG decoder, for the bitmaps embedded in color fonts.
+//!
+//! All standard color types and bit depths are supported, as is Adam7 interlacing. Ancillary
+//! chunks other than `tRNS` are ignored, and CRCs aren't checked.
+
+use byteorder::{BigEndian, ReadBytesExt};
+use error::Error;
+use flate2::read::ZlibDecoder;
+use limits::{Limit, Limits};
+use software_rasterizer::RgbaImage;
+use std::io::Read;
+use util::Jump;
+
+static SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+const IHDR: u32 = ((b'I' as u32) << 24) |
+                  ((b'H' as u32) << 16) |
+                  ((b'D' as u32) << 8)  |
+                   (b'R' as u32);
+const PLTE: u32 = ((b'P' as u32) << 24) |
+                  ((b'L' as u32) << 16) |
+                  ((b'T' as u32) << 8)  |
+                   (b'E' as u32);
+const TRNS: u32 = ((b't' as u32) << 24) |
+                  ((b'R' as u32) << 16) |
+                  ((b'N' as u32) << 8)  |
+                   (b'S' as u32);
+const IDAT: u32 = ((b'I' as u32) << 24) |
+                  ((b'D' as u32) << 16) |
+                  ((b'A' as u32) << 8)  |
+                   (b'T' as u32);
+const IEND: u32 = ((b'I' as u32) << 24) |
+                  ((b'E' as u32) << 16) |
+                  ((b'N' as u32) << 8)  |
+                   (b'D' as u32);
+
+const COLOR_TYPE_GRAYSCALE: u8 = 0;
+const COLOR_TYPE_RGB: u8 = 2;
+const COLOR_TYPE_INDEXED: u8 = 3;
+const COLOR_TYPE_GRAYSCALE_ALPHA: u8 = 4;
+const COLOR_TYPE_RGBA: u8 = 6;
+
+// The origin and spacing of the pixels in each of the seven Adam7 passes.
+static ADAM7_PASSES: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+struct Header {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        match self.color_type {
+            COLOR_TYPE_GRAYSCALE | COLOR_TYPE_INDEXED => 1,
+            COLOR_TYPE_GRAYSCALE_ALPHA => 2,
+            COLOR_TYPE_RGB => 3,
+            _ => 4,
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+}
+
+/// Returns true if the data looks like a PNG image.
+#[inline]
+pub fn is_png(bytes: &[u8]) -> bool {
+    bytes.starts_with(&SIGNATURE)
+}
+
+/// Decodes a PNG image to premultiplied RGBA.
+///
+/// Images wider or taller than `limits.max_image_size` fail with `Error::LimitExceeded` before
+/// anything is decompressed.
+pub fn decode(bytes: &[u8], limits: &Limits) -> Result<RgbaImage, Error> {
+    if !is_png(bytes) {
+        return Err(Error::Malformed)
+    }
+
+    let mut reader = &bytes[SIGNATURE.len()..];
+    let mut header = None;
+    let (mut palette, mut transparency, mut compressed) = (vec![], vec![], vec![]);
+    loop {
+        let length = try!(reader.read_u32::<BigEndian>()) as usize;
+        let chunk_type = try!(reader.read_u32::<BigEndian>());
+        if length > reader.len() {
+            return Err(Error::Malformed)
+        }
+        let data = &reader[0..length];
+        try!(reader.jump(length + 4));
+
+        match chunk_type {
+            IHDR => header = Some(try!(read_header(data))),
+            PLTE => palette = data.to_vec(),
+            TRNS => transparency = data.to_vec(),
+            IDAT => compressed.extend_from_slice(data),
+            IEND => break,
+            _ => {}
+        }
+    }
+
+    let header = try!(header.ok_or(Error::Malformed));
+    try!(limits.check(Limit::ImageSize, header.width));
+    try!(limits.check(Limit::ImageSize, header.height));
+
+    // Anything past the scanlines the header calls for is ignored, so don't inflate it.
+    let mut data = vec![];
+    try!(ZlibDecoder::new(&compressed[..]).take(data_length(&header) as u64)
+                                          .read_to_end(&mut data));
+
+    let mut image = RgbaImage::new(header.width, header.height);
+    if !header.interlaced {
+        try!(decode_pass(&header,
+                         &data,
+                         &palette,
+                         &transparency,
+                         &mut image,
+                         (0, 0, 1, 1)));
+        return Ok(image)
+    }
+
+    let mut data = &data[..];
+    for &pass in &ADAM7_PASSES {
+        let (pass_width, pass_height) = pass_size(&header, pass);
+        if pass_width == 0 || pass_height == 0 {
+            continue
+        }
+        let pass_length = (1 + row_length(&header, pass_width)) * pass_height as usize;
+        if pass_length > data.len() {
+            return Err(Error::Malformed)
+        }
+        try!(decode_pass(&header,
+                         &data[0..pass_length],
+                         &palette,
+                         &transparency,
+                         &mut image,
+                         pass));
+        data = &data[pass_length..]
+    }
+    Ok(image)
+}
+
+fn read_header(mut data: &[u8]) -> Result<Header, Error> {
+    let width = try!(data.read_u32::<BigEndian>());
+    let height = try!(data.read_u32::<BigEndian>());
+    let bit_depth = try!(data.read_u8());
+    let color_type = try!(data.read_u8());
+    let compression_method = try!(data.read_u8());
+    let filter_method = try!(data.read_u8());
+    let interlace_method = try!(data.read_u8());
+
+    let valid_bit_depth = match color_type {
+        COLOR_TYPE_GRAYSCALE => [1, 2, 4, 8, 16].contains(&bit_depth),
+        COLOR_TYPE_INDEXED => [1, 2, 4, 8].contains(&bit_depth),
+        COLOR_TYPE_RGB | COLOR_TYPE_GRAYSCALE_ALPHA | COLOR_TYPE_RGBA => {
+            bit_depth == 8 || bit_depth == 16
+        }
+        _ => false,
+    };
+    if !valid_bit_depth || compression_method != 0 || filter_method != 0 ||
+            interlace_method > 1 || width == 0 || height == 0 {
+        return Err(Error::Malformed)
+    }
+
+    Ok(Header {
+        width: width,
+        height: height,
+        bit_depth: bit_depth,
+        color_type: color_type,
+        interlaced: interlace_method == 1,
+    })
+}
+
+fn pass_size(header: &Header, pass: (u32, u32, u32, u32)) -> (u32, u32) {
+    let (x, y, dx, dy) = pass;
+    let size = |length: u32, start: u32, step: u32| {
+        ((length as u64 + step as u64 - 1 - start as u64) / step as u64) as u32
+    };
+    (size(header.width, x, dx), size(header.height, y, dy))
+}
+
+// Returns the number of bytes of filtered scanlines, including their filter type bytes.
+fn data_length(header: &Header) -> usize {
+    let passes = if header.interlaced { &ADAM7_PASSES[..] } else { &[(0, 0, 1, 1)][..] };
+    passes.iter().map(|&pass| {
+        let (pass_width, pass_height) = pass_size(header, pass);
+        if pass_width == 0 {
+            0
+        } else {
+            (1 + row_length(header, pass_width)) * pass_height as usize
+        }
+    }).sum()
+}
+
+#[inline]
+fn row_length(header: &Header, width: u32) -> usize {
+    (width as usize * header.bits_per_pixel() + 7) / 8
+}
+
+// Unfilters one pass (or the whole image, if not interlaced) and writes its pixels to the image.
+fn decode_pass(header: &Header,
+               data: &[u8],
+               palette: &[u8],
+               transparency: &[u8],
+               image: &mut RgbaImage,
+               pass: (u32, u32, u32, u32))
+               -> Result<(), Error> {
+    let (pass_x, pass_y, pass_dx, pass_dy) = pass;
+    let (pass_width, pass_height) = pass_size(header, pass);
+    let row_length = row_length(header, pass_width);
+    let bytes_per_pixel = ((header.bits_per_pixel() + 7) / 8).max(1);
+
+    let mut previous_row = vec![0; row_length];
+    let mut row = vec![0; row_length];
+    for y in 0..pass_height {
+        let start = y as usize * (row_length + 1);
+        if start + row_length + 1 > data.len() {
+            return Err(Error::Malformed)
+        }
+        let filter = data[start];
+        row.copy_from_slice(&data[(start + 1)..(start + 1 + row_length)]);
+        try!(unfilter(filter, &mut row, &previous_row, bytes_per_pixel));
+
+        for x in 0..pass_width {
+            let color = try!(pixel(header, &row, x as usize, palette, transparency));
+            let (image_x, image_y) = (pass_x + x * pass_dx, pass_y + y * pass_dy);
+            let index = (image_y as usize * image.width as usize + image_x as usize) * 4;
+            let alpha = color[3] as u32;
+            for channel in 0..3 {
+                image.pixels[index + channel] = ((color[channel] as u32 * alpha + 127) / 255) as u8
+            }
+            image.pixels[index + 3] = color[3];
+        }
+
+        previous_row.copy_from_slice(&row);
+    }
+    Ok(())
+}
+
+fn unfilter(filter: u8, row: &mut [u8], previous_row: &[u8], bytes_per_pixel: usize)
+            -> Result<(), Error> {
+    for index in 0..row.len() {
+        let left = if index >= bytes_per_pixel { row[index - bytes_per_pixel] } else { 0 };
+        let above = previous_row[index];
+        let upper_left = if index >= bytes_per_pixel {
+            previous_row[index - bytes_per_pixel]
+        } else {
+            0
+        };
+        let predictor = match filter {
+            0 => 0,
+            1 => left,
+            2 => above,
+            3 => ((left as u16 + above as u16) / 2) as u8,
+            4 => paeth(left, above, upper_left),
+            _ => return Err(Error::Malformed),
+        };
+        row[index] = row[index].wrapping_add(predictor)
+    }
+    Ok(())
+}
+
+fn paeth(left: u8, above: u8, upper_left: u8) -> u8 {
+    let estimate = left as i16 + above as i16 - upper_left as i16;
+    let left_distance = (estimate - left as i16).abs();
+    let above_distance = (estimate - above as i16).abs();
+    let upper_left_distance = (estimate - upper_left as i16).abs();
+    if left_distance <= above_distance && left_distance <= upper_left_distance {
+        left
+    } else if above_distance <= upper_left_distance {
+        above
+    } else {
+        upper_left
+    }
+}
+
+// Returns the unpremultiplied 8-bit RGBA color of the pixel at `x` in an unfiltered row.
+fn pixel(header: &Header, row: &[u8], x: usize, palette: &[u8], transparency: &[u8])
+         -> Result<[u8; 4], Error> {
+    let channels = header.channels();
+
+    // Read each channel at its full bit depth.
+    let mut samples = [0u16; 4];
+    for (channel, sample) in samples.iter_mut().enumerate().take(channels) {
+        let index = x * channels + channel;
+        *sample = match header.bit_depth {
+            16 => ((row[index * 2] as u16) << 8) | row[index * 2 + 1] as u16,
+            8 => row[index] as u16,
+            bit_depth => {
+                let bit_depth = bit_depth as usize;
+                let bit = index * bit_depth;
+                let shift = 8 - bit_depth - bit % 8;
+                ((row[bit / 8] >> shift) & ((1 << bit_depth) - 1) as u8) as u16
+            }
+        }
+    }
+
+    // Grayscale and RGB images may name one color as transparent.
+    let is_transparent_key = |channel_count: usize| {
+        transparency.len() >= channel_count * 2 && (0..channel_count).all(|channel| {
+            let key = ((transparency[channel * 2] as u16) << 8) |
+                transparency[channel * 2 + 1] as u16;
+            key == samples[channel]
+        })
+    };
+
+    let to_8_bit = |sample: u16| -> u8 {
+        match header.bit_depth {
+            16 => (sample >> 8) as u8,
+            bit_depth => (sample as u32 * 255 / ((1 << bit_depth) - 1)) as u8,
+        }
+    };
+
+    match header.color_type {
+        COLOR_TYPE_GRAYSCALE => {
+            let gray = to_8_bit(samples[0]);
+            let alpha = if is_transparent_key(1) { 0 } else { 255 };
+            Ok([gray, gray, gray, alpha])
+        }
+        COLOR_TYPE_GRAYSCALE_ALPHA => {
+            let gray = to_8_bit(samples[0]);
+            Ok([gray, gray, gray, to_8_bit(samples[1])])
+        }
+        COLOR_TYPE_RGB => {
+            let alpha = if is_transparent_key(3) { 0 } else { 255 };
+            Ok([to_8_bit(samples[0]), to_8_bit(samples[1]), to_8_bit(samples[2]), alpha])
+        }
+        COLOR_TYPE_INDEXED => {
+            let index = samples[0] as usize;
+            if index * 3 + 3 > palette.len() {
+                return Err(Error::Malformed)
+            }
+            let alpha = transparency.get(index).cloned().unwrap_or(255);
+            Ok([palette[index * 3], palette[index * 3 + 1], palette[index * 3 + 2], alpha])
+        }
+        _ => {
+            Ok([to_8_bit(samples[0]),
+                to_8_bit(samples[1]),
+                to_8_bit(samples[2]),
+                to_8_bit(samples[3])])
+        }
+    }
+}
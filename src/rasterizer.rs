@@ -15,6 +15,7 @@ use compute_shader::program::Program;
 use compute_shader::queue::{Queue, Uniform};
 use compute_shader::texture::Texture;
 use coverage::CoverageBuffer;
+use error::Error;
 use euclid::rect::Rect;
 use glyph_buffer::GlyphBuffers;
 
@@ -22,28 +23,40 @@ use glyph_buffer::GlyphBuffers;
 // TODO(pcwalton): GLSL version.
 static ACCUM_CL_SHADER: &'static str = include_str!("../resources/shaders/accum.cl");
 static DRAW_CL_SHADER: &'static str = include_str!("../resources/shaders/draw.cl");
+static COMPOSITE_CL_SHADER: &'static str = include_str!("../resources/shaders/composite.cl");
+static BLIT_CL_SHADER: &'static str = include_str!("../resources/shaders/blit.cl");
 
 pub struct Rasterizer {
     pub device: Device,
     pub queue: Queue,
     accum_program: Program,
     draw_program: Program,
+    composite_program: Program,
+    blit_program: Program,
 }
 
 impl Rasterizer {
-    pub fn new(device: Device, queue: Queue) -> Result<Rasterizer, ()> {
+    pub fn new(device: Device, queue: Queue) -> Result<Rasterizer, Error> {
         // TODO(pcwalton): GLSL version.
-        // FIXME(pcwalton): Don't panic if these fail to compile; just return an error.
-        let accum_program = device.create_program(ACCUM_CL_SHADER).unwrap();
-        let draw_program = device.create_program(DRAW_CL_SHADER).unwrap();
+        let accum_program = try!(device.create_program(ACCUM_CL_SHADER)
+                                       .map_err(|_| Error::DeviceFailure));
+        let draw_program = try!(device.create_program(DRAW_CL_SHADER)
+                                      .map_err(|_| Error::DeviceFailure));
+        let composite_program = try!(device.create_program(COMPOSITE_CL_SHADER)
+                                           .map_err(|_| Error::DeviceFailure));
+        let blit_program = try!(device.create_program(BLIT_CL_SHADER)
+                                      .map_err(|_| Error::DeviceFailure));
         Ok(Rasterizer {
             device: device,
             queue: queue,
             accum_program: accum_program,
             draw_program: draw_program,
+            composite_program: composite_program,
+            blit_program: blit_program,
         })
     }
 
+    /// Rasterizes the batch's glyphs into `texture`, then copies its embedded bitmaps in.
     pub fn draw_atlas(&self,
                       atlas_rect: &Rect<u32>,
                       atlas_shelf_height: u32,
@@ -51,7 +64,7 @@ impl Rasterizer {
                       batch: &Batch,
                       coverage_buffer: &CoverageBuffer,
                       texture: &Texture)
-                      -> Result<Event, ()> {
+                      -> Result<Event, Error> {
         let draw_uniforms = [
             (0, Uniform::Buffer(&batch.images)),
             (1, Uniform::Buffer(&glyph_buffers.descriptors)),
@@ -59,13 +72,13 @@ impl Rasterizer {
             (3, Uniform::Buffer(&glyph_buffers.operations)),
             (4, Uniform::Buffer(&batch.indices)),
             (5, Uniform::Buffer(&coverage_buffer.buffer)),
-            (6, Uniform::U32(try!(texture.width().map_err(drop)))),
+            (6, Uniform::U32(try!(texture.width().map_err(|_| Error::DeviceFailure)))),
         ];
 
         let draw_event = try!(self.queue.submit_compute(&self.draw_program,
                                                         &[batch.point_count],
                                                         &draw_uniforms,
-                                                        &[]).map_err(drop));
+                                                        &[]).map_err(|_| Error::DeviceFailure));
 
         let atlas_rect_uniform = [
             atlas_rect.origin.x,
@@ -83,10 +96,49 @@ impl Rasterizer {
 
         let accum_columns = atlas_rect.size.width * (atlas_rect.size.height / atlas_shelf_height);
 
-        self.queue.submit_compute(&self.accum_program,
-                                  &[accum_columns],
-                                  &accum_uniforms,
-                                  &[draw_event]).map_err(drop)
+        let accum_event = try!(self.queue.submit_compute(&self.accum_program,
+                                                         &[accum_columns],
+                                                         &accum_uniforms,
+                                                         &[draw_event])
+                                         .map_err(|_| Error::DeviceFailure));
+        if batch.bitmap_count == 0 {
+            return Ok(accum_event)
+        }
+
+        // Accumulation writes the whole atlas, so the bitmaps have to go in after it.
+        let blit_uniforms = [
+            (0, Uniform::Buffer(&batch.bitmaps)),
+            (1, Uniform::Buffer(&batch.bitmap_pixels)),
+            (2, Uniform::Texture(texture)),
+        ];
+
+        self.queue.submit_compute(&self.blit_program,
+                                  &[batch.bitmap_count],
+                                  &blit_uniforms,
+                                  &[accum_event]).map_err(|_| Error::DeviceFailure)
     }
-}
 
+    /// Blends the layers of the batch's color glyphs into an RGBA texture laid out according to
+    /// the batch builder's `color_atlas`. The output is premultiplied.
+    ///
+    /// `coverage_texture` must be the texture that `draw_atlas()` rendered this batch into, and
+    /// `events` should include the event it returned.
+    pub fn composite_color_atlas(&self,
+                                 batch: &Batch,
+                                 coverage_texture: &Texture,
+                                 color_texture: &Texture,
+                                 events: &[Event])
+                                 -> Result<Event, Error> {
+        let composite_uniforms = [
+            (0, Uniform::Buffer(&batch.color_images)),
+            (1, Uniform::Buffer(&batch.color_layers)),
+            (2, Uniform::Texture(coverage_texture)),
+            (3, Uniform::Texture(color_texture)),
+        ];
+
+        self.queue.submit_compute(&self.composite_program,
+                                  &[batch.color_image_count],
+                                  &composite_uniforms,
+                                  events).map_err(|_| Error::DeviceFailure)
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Choosing how to render glyphs at a given size, following the font's `gasp` table.
+//!
+//! A `RenderingMode` is advisory: nothing in Pathfinder applies it on its own. `BatchBuilder`
+//! rasterizes whatever outlines it's given with grayscale antialiasing. To follow a mode,
+//! grid-fit outlines with `hinting::Hinter` (or `autohint::AutoHinter` for fonts without
+//! instructions) before rasterizing them when `grid_fit` is set, and threshold the coverage with
+//! `GrayImage::threshold()` when the mode is aliased. The `render-glyph` example does both.
+
+use otf::FontData;
+use otf::gasp::{DOGRAY, GRIDFIT, GaspTable, SYMMETRIC_GRIDFIT, SYMMETRIC_SMOOTHING};
+use std::u16;
+
+/// How to antialias glyphs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Antialiasing {
+    /// No antialiasing: each pixel is either fully covered or not at all.
+    Aliased,
+    /// Grayscale antialiasing.
+    Grayscale,
+    /// Antialiasing in both directions, which the font asks for on subpixel renderers. For
+    /// grayscale renderers, it's the same as `Grayscale`.
+    SymmetricSmoothing,
+}
+
+/// How to render glyphs at one size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenderingMode {
+    /// Whether to grid-fit outlines.
+    pub grid_fit: bool,
+    /// Whether the font's instructions expect the grid fitting of ClearType-style renderers,
+    /// which mostly leaves horizontal positions alone.
+    pub symmetric_grid_fit: bool,
+    pub antialiasing: Antialiasing,
+}
+
+impl Default for RenderingMode {
+    /// The mode for fonts without a `gasp` table: grayscale antialiasing without grid fitting,
+    /// which is how Pathfinder renders glyphs anyway.
+    #[inline]
+    fn default() -> RenderingMode {
+        RenderingMode {
+            grid_fit: false,
+            symmetric_grid_fit: false,
+            antialiasing: Antialiasing::Grayscale,
+        }
+    }
+}
+
+impl RenderingMode {
+    #[inline]
+    pub fn is_antialiased(&self) -> bool {
+        self.antialiasing != Antialiasing::Aliased
+    }
+}
+
+/// Picks the rendering mode for each size of a font.
+#[derive(Clone, Copy, Debug)]
+pub struct RenderingPolicy<'a> {
+    gasp_table: Option<GaspTable<'a>>,
+}
+
+impl<'a> RenderingPolicy<'a> {
+    /// Reads the font's `gasp` table. Fonts without a valid one get `RenderingMode::default()` at
+    /// every size.
+    pub fn new(font: &'a FontData) -> RenderingPolicy<'a> {
+        RenderingPolicy {
+            gasp_table: font.gasp_table().ok(),
+        }
+    }
+
+    /// Returns the rendering mode for the given size in pixels per em, which is what
+    /// `BatchBuilder` calls the point size.
+    pub fn mode(&self, pixels_per_em: f32) -> RenderingMode {
+        if !(pixels_per_em >= 0.0) {
+            return RenderingMode::default()
+        }
+        let pixels_per_em = pixels_per_em.round().min(u16::MAX as f32) as u16;
+        let behavior = self.gasp_table.and_then(|gasp_table| gasp_table.behavior(pixels_per_em));
+        let behavior = match behavior {
+            Some(behavior) => behavior,
+            None => return RenderingMode::default(),
+        };
+
+        let antialiasing = if behavior.contains(SYMMETRIC_SMOOTHING) {
+            Antialiasing::SymmetricSmoothing
+        } else if behavior.contains(DOGRAY) {
+            Antialiasing::Grayscale
+        } else {
+            Antialiasing::Aliased
+        };
+        RenderingMode {
+            grid_fit: behavior.contains(GRIDFIT),
+            symmetric_grid_fit: behavior.contains(SYMMETRIC_GRIDFIT),
+            antialiasing: antialiasing,
+        }
+    }
+}
@@ -0,0 +1,381 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A CPU rasterizer for paths, for color glyph formats that need more than coverage.
+//!
+//! Coverage is computed with the same signed-area accumulation technique that the GPU rasterizer
+//! uses. For details on the algorithm, see [1].
+//!
+//! [1]: http://nothings.org/gamedev/rasterize/
+
+use error::Error;
+use euclid::Point2D;
+use outline::{PathCommand, Transform};
+
+// Curves are flattened until the control points lie within this distance, in pixels, of the
+// chord.
+const FLATTENING_TOLERANCE: f32 = 0.1;
+const MAX_FLATTENING_SEGMENTS: u32 = 256;
+
+/// The coverage of a path, in the range 0 to 1 per pixel.
+pub struct CoverageMask {
+    pub width: u32,
+    pub height: u32,
+    // Signed area deltas, with two extra columns per row so that segments touching the right edge
+    // don't spill into the next row.
+    accumulation: Vec<f32>,
+    stride: usize,
+}
+
+impl CoverageMask {
+    pub fn new(width: u32, height: u32) -> CoverageMask {
+        let stride = width as usize + 2;
+        CoverageMask {
+            width: width,
+            height: height,
+            accumulation: vec![0.0; stride * height as usize],
+            stride: stride,
+        }
+    }
+
+    /// Adds a path, transformed into pixel coordinates (with y pointing down), to the mask.
+    /// Contours are implicitly closed.
+    pub fn fill_path(&mut self, commands: &[PathCommand], transform: &Transform) {
+        let (mut start, mut current) = (Point2D::new(0.0, 0.0), Point2D::new(0.0, 0.0));
+        for command in commands {
+            match *command {
+                PathCommand::MoveTo(point) => {
+                    self.draw_line(&current, &start);
+                    start = transform.transform_point(&point);
+                    current = start
+                }
+                PathCommand::LineTo(point) => {
+                    let point = transform.transform_point(&point);
+                    self.draw_line(&current, &point);
+                    current = point
+                }
+                PathCommand::QuadTo(control_point, point) => {
+                    let control_point = transform.transform_point(&control_point);
+                    let point = transform.transform_point(&point);
+                    let deviation = length(&(current - control_point * 2.0 + point));
+                    let segments = segment_count(deviation * 2.0);
+                    let mut previous = current;
+                    for segment in 1..(segments + 1) {
+                        let t = segment as f32 / segments as f32;
+                        let u = 1.0 - t;
+                        let next = current * (u * u) + control_point * (2.0 * u * t) +
+                            point * (t * t);
+                        self.draw_line(&previous, &next);
+                        previous = next
+                    }
+                    current = point
+                }
+                PathCommand::CurveTo(control_point_0, control_point_1, point) => {
+                    let control_point_0 = transform.transform_point(&control_point_0);
+                    let control_point_1 = transform.transform_point(&control_point_1);
+                    let point = transform.transform_point(&point);
+                    let deviation =
+                        length(&(current - control_point_0 * 2.0 + control_point_1)).max(
+                            length(&(control_point_0 - control_point_1 * 2.0 + point)));
+                    let segments = segment_count(deviation * 6.0);
+                    let mut previous = current;
+                    for segment in 1..(segments + 1) {
+                        let t = segment as f32 / segments as f32;
+                        let u = 1.0 - t;
+                        let next = current * (u * u * u) + control_point_0 * (3.0 * u * u * t) +
+                            control_point_1 * (3.0 * u * t * t) + point * (t * t * t);
+                        self.draw_line(&previous, &next);
+                        previous = next
+                    }
+                    current = point
+                }
+                PathCommand::ClosePath => {
+                    self.draw_line(&current, &start);
+                    current = start
+                }
+            }
+        }
+        self.draw_line(&current, &start)
+    }
+
+    /// Returns the coverage of each pixel, in row-major order from the top left. Overlapping
+    /// contours are combined with the nonzero winding rule.
+    pub fn coverage(&self) -> Vec<f32> {
+        let mut coverage = Vec::with_capacity(self.width as usize * self.height as usize);
+        for row in self.accumulation.chunks(self.stride) {
+            let mut accumulated = 0.0;
+            for &delta in &row[0..(self.width as usize)] {
+                accumulated += delta;
+                coverage.push(accumulated.abs().min(1.0))
+            }
+        }
+        coverage
+    }
+
+    /// Returns the coverage of each pixel as `coverage()` does, but combining overlapping
+    /// contours with the even-odd rule.
+    pub fn even_odd_coverage(&self) -> Vec<f32> {
+        let mut coverage = Vec::with_capacity(self.width as usize * self.height as usize);
+        for row in self.accumulation.chunks(self.stride) {
+            let mut accumulated = 0.0;
+            for &delta in &row[0..(self.width as usize)] {
+                accumulated += delta;
+                let winding = accumulated.abs() % 2.0;
+                coverage.push(if winding > 1.0 { 2.0 - winding } else { winding })
+            }
+        }
+        coverage
+    }
+
+    // Adds the signed area to the right of a line to the accumulation buffer. Within each row,
+    // the line is clamped horizontally to the mask, which doesn't change the coverage of any
+    // pixel inside it.
+    fn draw_line(&mut self, from: &Point2D<f32>, to: &Point2D<f32>) {
+        let width = self.width as f32;
+        if from.y == to.y || !from.y.is_finite() || !to.y.is_finite() {
+            return
+        }
+
+        let (direction, top, bottom) = if from.y < to.y {
+            (1.0, from, to)
+        } else {
+            (-1.0, to, from)
+        };
+        let dxdy = (bottom.x - top.x) / (bottom.y - top.y);
+
+        let first_row = top.y.max(0.0).floor() as usize;
+        let last_row = (bottom.y.ceil().max(0.0) as usize).min(self.height as usize);
+        let mut x = top.x + (first_row as f32).max(top.y) * dxdy - top.y * dxdy;
+        for row in first_row..last_row {
+            let row_start = row * self.stride;
+            let dy = ((row + 1) as f32).min(bottom.y) - (row as f32).max(top.y);
+            let next_x = x + dxdy * dy;
+            let delta = dy * direction;
+
+            let (row_x, row_next_x) = (x.max(0.0).min(width), next_x.max(0.0).min(width));
+            let (x0, x1) = if row_x < row_next_x {
+                (row_x, row_next_x)
+            } else {
+                (row_next_x, row_x)
+            };
+            let x0_floor = x0.floor();
+            let x0_index = x0_floor as usize;
+            let x1_ceil = x1.ceil();
+            let x1_index = x1_ceil as usize;
+
+            if x1_index <= x0_index + 1 {
+                // The line lies within one pixel in this row.
+                let midpoint = 0.5 * (row_x + row_next_x) - x0_floor;
+                self.accumulation[row_start + x0_index] += delta - delta * midpoint;
+                self.accumulation[row_start + x0_index + 1] += delta * midpoint;
+            } else {
+                // The line spans several pixels: a triangle, some trapezoids, and a triangle.
+                let inverse_width = (x1 - x0).recip();
+                let x0_fraction = x0 - x0_floor;
+                let first_area = 0.5 * inverse_width * (1.0 - x0_fraction) * (1.0 - x0_fraction);
+                let x1_fraction = x1 - x1_ceil + 1.0;
+                let last_area = 0.5 * inverse_width * x1_fraction * x1_fraction;
+
+                self.accumulation[row_start + x0_index] += delta * first_area;
+                if x1_index == x0_index + 2 {
+                    self.accumulation[row_start + x0_index + 1] +=
+                        delta * (1.0 - first_area - last_area);
+                } else {
+                    let second_area = inverse_width * (1.5 - x0_fraction);
+                    self.accumulation[row_start + x0_index + 1] +=
+                        delta * (second_area - first_area);
+                    for column in (x0_index + 2)..(x1_index - 1) {
+                        self.accumulation[row_start + column] += delta * inverse_width;
+                    }
+                    let penultimate_area =
+                        second_area + (x1_index - x0_index - 3) as f32 * inverse_width;
+                    self.accumulation[row_start + x1_index - 1] +=
+                        delta * (1.0 - penultimate_area - last_area);
+                }
+                self.accumulation[row_start + x1_index] += delta * last_area;
+            }
+
+            x = next_x
+        }
+    }
+}
+
+/// An image with premultiplied 8-bit RGBA pixels, in row-major order from the top left.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    #[inline]
+    pub fn new(width: u32, height: u32) -> RgbaImage {
+        RgbaImage {
+            width: width,
+            height: height,
+            pixels: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    /// Returns the pixel at the given position as premultiplied `[r, g, b, a]`.
+    #[inline]
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let index = (y as usize * self.width as usize + x as usize) * 4;
+        [self.pixels[index], self.pixels[index + 1], self.pixels[index + 2], self.pixels[index + 3]]
+    }
+
+    /// Returns this image resized by the given factor. Each destination pixel is the average of
+    /// the source pixels it overlaps, weighted by area.
+    pub fn scaled(&self, scale: f32) -> Result<RgbaImage, Error> {
+        let (width, height, pixels) =
+            try!(resample(&self.pixels, self.width, self.height, 4, scale));
+        Ok(RgbaImage {
+            width: width,
+            height: height,
+            pixels: pixels,
+        })
+    }
+
+    /// Copies `source` into this image with its top left corner at `origin`.
+    pub fn blit(&mut self, source: &RgbaImage, origin: &Point2D<u32>) -> Result<(), Error> {
+        blit(&mut self.pixels, self.width, self.height, &source.pixels, source.width, 4, origin)
+    }
+}
+
+/// An image with 8-bit coverage values, in row-major order from the top left.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GrayImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl GrayImage {
+    #[inline]
+    pub fn new(width: u32, height: u32) -> GrayImage {
+        GrayImage {
+            width: width,
+            height: height,
+            pixels: vec![0; width as usize * height as usize],
+        }
+    }
+
+    #[inline]
+    pub fn pixel(&self, x: u32, y: u32) -> u8 {
+        self.pixels[y as usize * self.width as usize + x as usize]
+    }
+
+    /// Returns this image resized by the given factor, as `RgbaImage::scaled()` does.
+    pub fn scaled(&self, scale: f32) -> Result<GrayImage, Error> {
+        let (width, height, pixels) =
+            try!(resample(&self.pixels, self.width, self.height, 1, scale));
+        Ok(GrayImage {
+            width: width,
+            height: height,
+            pixels: pixels,
+        })
+    }
+
+    /// Makes every pixel fully covered if its coverage is at least `level`, and uncovered
+    /// otherwise, for aliased rendering.
+    pub fn threshold(&mut self, level: u8) {
+        for pixel in &mut self.pixels {
+            *pixel = if *pixel >= level { 255 } else { 0 }
+        }
+    }
+
+    /// Copies `source` into this image with its top left corner at `origin`.
+    pub fn blit(&mut self, source: &GrayImage, origin: &Point2D<u32>) -> Result<(), Error> {
+        blit(&mut self.pixels, self.width, self.height, &source.pixels, source.width, 1, origin)
+    }
+}
+
+// Resizes an image with `channels` bytes per pixel with an area-weighted box filter.
+fn resample(pixels: &[u8], width: u32, height: u32, channels: usize, scale: f32)
+            -> Result<(u32, u32, Vec<u8>), Error> {
+    if !(scale > 0.0) || !scale.is_finite() {
+        return Err(Error::InvalidArgument)
+    }
+    let scaled_width = (width as f32 * scale).ceil() as u32;
+    let scaled_height = (height as f32 * scale).ceil() as u32;
+    let mut scaled_pixels = vec![0; scaled_width as usize * scaled_height as usize * channels];
+
+    let mut sum = vec![0.0; channels];
+    for y in 0..scaled_height {
+        let (top, bottom) = (y as f32 / scale, (y + 1) as f32 / scale);
+        for x in 0..scaled_width {
+            let (left, right) = (x as f32 / scale, (x + 1) as f32 / scale);
+            for sum in &mut sum {
+                *sum = 0.0
+            }
+            let last_row = (bottom.ceil() as u32).min(height);
+            let last_column = (right.ceil() as u32).min(width);
+            for source_y in (top.floor() as u32)..last_row {
+                let coverage_y = bottom.min((source_y + 1) as f32) - top.max(source_y as f32);
+                for source_x in (left.floor() as u32)..last_column {
+                    let coverage_x = right.min((source_x + 1) as f32) - left.max(source_x as f32);
+                    let index = (source_y as usize * width as usize + source_x as usize) * channels;
+                    for (channel, sum) in sum.iter_mut().enumerate() {
+                        *sum += pixels[index + channel] as f32 * coverage_x * coverage_y
+                    }
+                }
+            }
+
+            // Weights are in source pixels, so normalize by the destination pixel's area.
+            let index = (y as usize * scaled_width as usize + x as usize) * channels;
+            for (channel, sum) in sum.iter().enumerate() {
+                let value = sum * scale * scale;
+                scaled_pixels[index + channel] = value.round().max(0.0).min(255.0) as u8
+            }
+        }
+    }
+    Ok((scaled_width, scaled_height, scaled_pixels))
+}
+
+fn blit(pixels: &mut [u8],
+        width: u32,
+        height: u32,
+        source_pixels: &[u8],
+        source_width: u32,
+        channels: usize,
+        origin: &Point2D<u32>)
+        -> Result<(), Error> {
+    let row_length = source_width as usize * channels;
+    let source_height = if row_length == 0 { 0 } else { source_pixels.len() / row_length };
+    if origin.x as u64 + source_width as u64 > width as u64 ||
+            origin.y as u64 + source_height as u64 > height as u64 {
+        return Err(Error::InvalidArgument)
+    }
+    for y in 0..source_height {
+        let source_start = y * row_length;
+        let destination_start =
+            ((origin.y as usize + y) * width as usize + origin.x as usize) * channels;
+        pixels[destination_start..(destination_start + row_length)]
+            .copy_from_slice(&source_pixels[source_start..(source_start + row_length)])
+    }
+    Ok(())
+}
+
+#[inline]
+fn length(vector: &Point2D<f32>) -> f32 {
+    (vector.x * vector.x + vector.y * vector.y).sqrt()
+}
+
+// The number of line segments needed to keep a curve whose second derivative is at most
+// `curvature` pixels long within the flattening tolerance. Each segment of a curve split into `n`
+// strays at most `curvature / (8 * n²)` from it.
+fn segment_count(curvature: f32) -> u32 {
+    if !curvature.is_finite() {
+        return 1
+    }
+    let segments = (curvature / (8.0 * FLATTENING_TOLERANCE)).sqrt().ceil() as u32;
+    segments.max(1).min(MAX_FLATTENING_SEGMENTS)
+}
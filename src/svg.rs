@@ -0,0 +1,859 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Renders glyphs from the `SVG ` table to RGBA images on the CPU.
+//!
+//! Only a small subset of SVG is understood: `path` elements with solid fills, grouped and
+//! transformed by `g` and `svg` elements. Other elements are skipped, along with their children,
+//! and style sheets are ignored. Fills that refer to paint servers such as gradients are an error,
+//! so that callers can fall back to the glyph outline or another color format.
+//!
+//! Group opacity is multiplied into the fills of the group's children, which differs from SVG
+//! only where those children overlap.
+
+use error::Error;
+use euclid::Point2D;
+use limits::{Limit, Limits};
+use otf::cpal::Color;
+use otf::head::HeadTable;
+use otf::svg::{self, SvgTable};
+use outline::{PathCommand, Transform};
+use paint::ColorGlyphImage;
+use software_rasterizer::{CoverageMask, RgbaImage};
+use std::f32::consts::PI;
+use std::str;
+use util::to_i32;
+
+// Bounds the nesting of elements drawn for a glyph.
+const MAX_ELEMENT_DEPTH: u32 = 64;
+
+// A premultiplied RGBA color with components from 0 to 1.
+type Rgba = [f32; 4];
+
+const NAMED_COLORS: [(&'static str, [u8; 3]); 16] = [
+    ("aqua", [0, 255, 255]),
+    ("black", [0, 0, 0]),
+    ("blue", [0, 0, 255]),
+    ("fuchsia", [255, 0, 255]),
+    ("gray", [128, 128, 128]),
+    ("green", [0, 128, 0]),
+    ("lime", [0, 255, 0]),
+    ("maroon", [128, 0, 0]),
+    ("navy", [0, 0, 128]),
+    ("olive", [128, 128, 0]),
+    ("purple", [128, 0, 128]),
+    ("red", [255, 0, 0]),
+    ("silver", [192, 192, 192]),
+    ("teal", [0, 128, 128]),
+    ("white", [255, 255, 255]),
+    ("yellow", [255, 255, 0]),
+];
+
+/// Renders the SVG glyphs of a font with a particular foreground color, which `currentColor`
+/// refers to.
+pub struct SvgGlyphRenderer<'a> {
+    svg: SvgTable<'a>,
+    units_per_em: u16,
+    foreground_color: Color,
+    /// Glyphs whose images would be wider or taller than `max_image_size` fail with
+    /// `Error::LimitExceeded` before anything is drawn.
+    pub limits: Limits,
+}
+
+impl<'a> SvgGlyphRenderer<'a> {
+    pub fn new(svg: SvgTable<'a>, head: &HeadTable, foreground_color: Color)
+               -> SvgGlyphRenderer<'a> {
+        SvgGlyphRenderer {
+            svg: svg,
+            units_per_em: head.units_per_em,
+            foreground_color: foreground_color,
+            limits: Limits::default(),
+        }
+    }
+
+    /// Renders the given glyph at the given size.
+    ///
+    /// Returns `None` if the glyph has no SVG document or the document has no element for it.
+    pub fn render(&self, glyph_id: u16, pixels_per_em: f32)
+                  -> Result<Option<ColorGlyphImage>, Error> {
+        if self.units_per_em == 0 {
+            return Err(Error::Malformed)
+        }
+        let scale = pixels_per_em / self.units_per_em as f32;
+
+        let document = match try!(self.svg.document(glyph_id)) {
+            None => return Ok(None),
+            Some(document) => document,
+        };
+        let text = try!(document.text());
+        let elements = try!(parse_document(&text));
+        let id = svg::glyph_element_id(glyph_id);
+        let glyph_element = match elements.iter().position(|element| {
+            element.attribute("id") == Some(&id[..])
+        }) {
+            None => return Ok(None),
+            Some(glyph_element) => glyph_element,
+        };
+
+        // The glyph element inherits the transforms and fill properties of its ancestors.
+        let mut ancestors = vec![];
+        let mut parent = elements[glyph_element].parent;
+        while let Some(ancestor) = parent {
+            ancestors.push(ancestor);
+            parent = elements[ancestor].parent
+        }
+        let mut style = Style {
+            fill: Some(premultiply(Color::new(0, 0, 0, 255))),
+            fill_opacity: 1.0,
+            opacity: 1.0,
+            even_odd: false,
+        };
+        let mut transform = Transform::identity();
+        for &ancestor in ancestors.iter().rev() {
+            try!(self.apply_element(&elements[ancestor], &mut style, &mut transform))
+        }
+
+        let mut shapes = vec![];
+        try!(self.collect_shapes(&elements, glyph_element, &style, &transform, &mut shapes, 0));
+
+        // Find the extent of the glyph in pixels. SVG glyphs are in font units with y pointing
+        // down, like pixel space.
+        let mut bounds: Option<(Point2D<f32>, Point2D<f32>)> = None;
+        for shape in &shapes {
+            for command in &shape.commands {
+                let points = match *command {
+                    PathCommand::MoveTo(point) | PathCommand::LineTo(point) => vec![point],
+                    PathCommand::QuadTo(point_0, point_1) => vec![point_0, point_1],
+                    PathCommand::CurveTo(point_0, point_1, point_2) => {
+                        vec![point_0, point_1, point_2]
+                    }
+                    PathCommand::ClosePath => vec![],
+                };
+                for point in points {
+                    let point = shape.transform.transform_point(&point) * scale;
+                    bounds = Some(match bounds {
+                        None => (point, point),
+                        Some((min, max)) => {
+                            (Point2D::new(min.x.min(point.x), min.y.min(point.y)),
+                             Point2D::new(max.x.max(point.x), max.y.max(point.y)))
+                        }
+                    })
+                }
+            }
+        }
+        let (left, top, right, bottom) = match bounds {
+            Some((min, max)) => (to_i32(min.x.floor()),
+                                 to_i32(min.y.floor()),
+                                 to_i32(max.x.ceil()),
+                                 to_i32(max.y.ceil())),
+            None => (0, 0, 0, 0),
+        };
+
+        // The coordinates come straight from the document, so check the size before allocating.
+        let too_big = Error::LimitExceeded(Limit::ImageSize);
+        let width = try!(right.checked_sub(left).ok_or(too_big));
+        let height = try!(bottom.checked_sub(top).ok_or(too_big));
+        let origin = Point2D::new(try!(left.checked_neg().ok_or(too_big)),
+                                  try!(top.checked_neg().ok_or(too_big)));
+        let (width, height) = (width as u32, height as u32);
+        try!(self.limits.check(Limit::ImageSize, width));
+        try!(self.limits.check(Limit::ImageSize, height));
+        let pixel_transform =
+            Transform::new(scale, 0.0, 0.0, scale, origin.x as f32, origin.y as f32);
+
+        let mut pixels = vec![[0.0; 4]; width as usize * height as usize];
+        for shape in &shapes {
+            let mut mask = CoverageMask::new(width, height);
+            mask.fill_path(&shape.commands, &pixel_transform.pre_transform(&shape.transform));
+            let coverage = if shape.even_odd {
+                mask.even_odd_coverage()
+            } else {
+                mask.coverage()
+            };
+            for (pixel, coverage) in pixels.iter_mut().zip(coverage.into_iter()) {
+                let alpha = shape.color[3] * coverage;
+                for (component, &source) in pixel.iter_mut().zip(shape.color.iter()) {
+                    *component = source * coverage + *component * (1.0 - alpha)
+                }
+            }
+        }
+
+        let mut image = RgbaImage::new(width, height);
+        for (destination, pixel) in image.pixels.chunks_mut(4).zip(pixels.iter()) {
+            for (destination, &component) in destination.iter_mut().zip(pixel.iter()) {
+                *destination = (component.max(0.0).min(1.0) * 255.0).round() as u8
+            }
+        }
+
+        Ok(Some(ColorGlyphImage {
+            image: image,
+            origin: origin,
+        }))
+    }
+
+    // Collects the paths drawn by an element and its descendants.
+    fn collect_shapes(&self,
+                      elements: &[Element],
+                      element_index: usize,
+                      style: &Style,
+                      transform: &Transform,
+                      shapes: &mut Vec<Shape>,
+                      depth: u32)
+                      -> Result<(), Error> {
+        if depth > MAX_ELEMENT_DEPTH {
+            return Err(Error::Malformed)
+        }
+        let element = &elements[element_index];
+        if element.property("display") == Some("none") {
+            return Ok(())
+        }
+
+        let (mut style, mut transform) = (*style, *transform);
+        try!(self.apply_element(element, &mut style, &mut transform));
+        match element.local_name() {
+            "svg" | "g" => {
+                for &child in &element.children {
+                    try!(self.collect_shapes(elements,
+                                             child,
+                                             &style,
+                                             &transform,
+                                             shapes,
+                                             depth + 1))
+                }
+            }
+            "path" => {
+                let fill = match style.fill {
+                    None => return Ok(()),
+                    Some(fill) => fill,
+                };
+                let commands = try!(parse_path_data(element.attribute("d").unwrap_or("")));
+                let alpha = (style.fill_opacity * style.opacity).max(0.0).min(1.0);
+                shapes.push(Shape {
+                    commands: commands,
+                    transform: transform,
+                    color: [fill[0] * alpha, fill[1] * alpha, fill[2] * alpha, fill[3] * alpha],
+                    even_odd: style.even_odd,
+                })
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Applies the transform and the fill properties an element sets.
+    fn apply_element(&self, element: &Element, style: &mut Style, transform: &mut Transform)
+                     -> Result<(), Error> {
+        if let Some(transform_list) = element.attribute("transform") {
+            *transform = transform.pre_transform(&try!(parse_transform(transform_list)))
+        }
+        if let Some(fill) = element.property("fill") {
+            if fill != "inherit" {
+                style.fill = try!(self.parse_paint(fill))
+            }
+        }
+        if let Some(fill_opacity) = element.property("fill-opacity") {
+            if fill_opacity != "inherit" {
+                style.fill_opacity = try!(parse_opacity(fill_opacity))
+            }
+        }
+        if let Some(fill_rule) = element.property("fill-rule") {
+            match fill_rule {
+                "nonzero" => style.even_odd = false,
+                "evenodd" => style.even_odd = true,
+                "inherit" => {}
+                _ => return Err(Error::Malformed),
+            }
+        }
+        if let Some(opacity) = element.property("opacity") {
+            style.opacity *= try!(parse_opacity(opacity))
+        }
+        Ok(())
+    }
+
+    // Parses a fill, returning `None` for `none`.
+    fn parse_paint(&self, paint: &str) -> Result<Option<Rgba>, Error> {
+        let paint = paint.trim();
+        if paint == "none" {
+            return Ok(None)
+        }
+        if paint == "currentColor" {
+            return Ok(Some(premultiply(self.foreground_color)))
+        }
+
+        if paint.starts_with('#') {
+            let digits = &paint[1..];
+            let value = try!(u32::from_str_radix(digits, 16).map_err(|_| Error::Malformed));
+            let (r, g, b) = match digits.len() {
+                3 => {
+                    (((value >> 8) & 0xf) * 0x11, ((value >> 4) & 0xf) * 0x11, (value & 0xf) * 0x11)
+                }
+                6 => ((value >> 16) & 0xff, (value >> 8) & 0xff, value & 0xff),
+                _ => return Err(Error::Malformed),
+            };
+            return Ok(Some(premultiply(Color::new(r as u8, g as u8, b as u8, 255))))
+        }
+
+        if paint.starts_with("rgb(") && paint.ends_with(')') {
+            let mut components = [0; 3];
+            let arguments: Vec<_> = paint[4..(paint.len() - 1)].split(',').collect();
+            if arguments.len() != 3 {
+                return Err(Error::Malformed)
+            }
+            for (component, argument) in components.iter_mut().zip(arguments.iter()) {
+                let argument = argument.trim();
+                let value = if argument.ends_with('%') {
+                    let percentage: f32 = try!(argument[..(argument.len() - 1)]
+                                                   .parse()
+                                                   .map_err(|_| Error::Malformed));
+                    percentage * 2.55
+                } else {
+                    try!(argument.parse::<f32>().map_err(|_| Error::Malformed))
+                };
+                *component = value.max(0.0).min(255.0).round() as u8
+            }
+            let color = Color::new(components[0], components[1], components[2], 255);
+            return Ok(Some(premultiply(color)))
+        }
+
+        match NAMED_COLORS.iter().find(|&&(name, _)| name.eq_ignore_ascii_case(paint)) {
+            Some(&(_, [r, g, b])) => Ok(Some(premultiply(Color::new(r, g, b, 255)))),
+            None => Err(Error::Malformed),
+        }
+    }
+}
+
+// The inherited fill properties, and the opacity accumulated from ancestors.
+#[derive(Clone, Copy)]
+struct Style {
+    fill: Option<Rgba>,
+    fill_opacity: f32,
+    opacity: f32,
+    even_odd: bool,
+}
+
+struct Shape {
+    commands: Vec<PathCommand>,
+    transform: Transform,
+    color: Rgba,
+    even_odd: bool,
+}
+
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+impl Element {
+    fn local_name(&self) -> &str {
+        match self.name.rfind(':') {
+            None => &self.name,
+            Some(colon) => &self.name[(colon + 1)..],
+        }
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|&&(ref attribute_name, _)| attribute_name == name)
+            .map(|&(_, ref value)| &value[..])
+    }
+
+    // Looks up a presentation property, which the `style` attribute overrides.
+    fn property(&self, name: &str) -> Option<&str> {
+        if let Some(style) = self.attribute("style") {
+            for declaration in style.split(';').rev() {
+                let mut parts = declaration.splitn(2, ':');
+                if let (Some(property), Some(value)) = (parts.next(), parts.next()) {
+                    if property.trim() == name {
+                        return Some(value.trim())
+                    }
+                }
+            }
+        }
+        self.attribute(name).map(str::trim)
+    }
+}
+
+// Parses the elements of an XML document, in document order. Text, comments, processing
+// instructions, and the document type declaration are skipped.
+fn parse_document(text: &[u8]) -> Result<Vec<Element>, Error> {
+    let text = try!(str::from_utf8(text).map_err(|_| Error::Malformed));
+    let mut elements: Vec<Element> = vec![];
+    let mut open_elements: Vec<usize> = vec![];
+    let mut position = 0;
+
+    while let Some(start) = text[position..].find('<') {
+        let rest = &text[(position + start)..];
+        if rest.starts_with("<!--") {
+            position += start + try!(rest.find("-->").ok_or(Error::Malformed)) + 3;
+            continue
+        }
+        if rest.starts_with("<![CDATA[") {
+            position += start + try!(rest.find("]]>").ok_or(Error::Malformed)) + 3;
+            continue
+        }
+        if rest.starts_with("<?") {
+            position += start + try!(rest.find("?>").ok_or(Error::Malformed)) + 2;
+            continue
+        }
+        if rest.starts_with("<!") {
+            // The document type declaration may contain an internal subset in brackets.
+            let mut bracket_depth = 0;
+            let mut end = None;
+            for (index, byte) in rest.bytes().enumerate() {
+                match byte {
+                    b'[' => bracket_depth += 1,
+                    b']' => bracket_depth -= 1,
+                    b'>' if bracket_depth <= 0 => {
+                        end = Some(index);
+                        break
+                    }
+                    _ => {}
+                }
+            }
+            position += start + try!(end.ok_or(Error::Malformed)) + 1;
+            continue
+        }
+
+        if rest.starts_with("</") {
+            let end = try!(rest.find('>').ok_or(Error::Malformed));
+            let name = rest[2..end].trim();
+            match open_elements.pop() {
+                Some(element) if elements[element].name == name => {}
+                _ => return Err(Error::Malformed),
+            }
+            position += start + end + 1;
+            continue
+        }
+
+        // Parse a start tag.
+        let mut tag = &rest[1..];
+        let name_end = tag.find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+                          .unwrap_or(tag.len());
+        let name = tag[..name_end].to_owned();
+        if name.is_empty() {
+            return Err(Error::Malformed)
+        }
+        tag = &tag[name_end..];
+
+        let mut attributes = vec![];
+        let self_closing;
+        loop {
+            tag = tag.trim_left();
+            if tag.starts_with("/>") {
+                self_closing = true;
+                tag = &tag[2..];
+                break
+            }
+            if tag.starts_with('>') {
+                self_closing = false;
+                tag = &tag[1..];
+                break
+            }
+
+            let equals = try!(tag.find('=').ok_or(Error::Malformed));
+            let attribute_name = tag[..equals].trim().to_owned();
+            tag = tag[(equals + 1)..].trim_left();
+            let quote = try!(tag.chars().next().ok_or(Error::Malformed));
+            if quote != '"' && quote != '\'' {
+                return Err(Error::Malformed)
+            }
+            let value_end = try!(tag[1..].find(quote).ok_or(Error::Malformed)) + 1;
+            let value = try!(decode_entities(&tag[1..value_end]));
+            attributes.push((attribute_name, value));
+            tag = &tag[(value_end + 1)..]
+        }
+
+        let index = elements.len();
+        let parent = open_elements.last().cloned();
+        if let Some(parent) = parent {
+            elements[parent].children.push(index)
+        }
+        elements.push(Element {
+            name: name,
+            attributes: attributes,
+            parent: parent,
+            children: vec![],
+        });
+        if !self_closing {
+            open_elements.push(index)
+        }
+        position = text.len() - tag.len()
+    }
+
+    if !open_elements.is_empty() {
+        return Err(Error::Malformed)
+    }
+    Ok(elements)
+}
+
+fn decode_entities(text: &str) -> Result<String, Error> {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(ampersand) = rest.find('&') {
+        decoded.push_str(&rest[..ampersand]);
+        rest = &rest[ampersand..];
+        let semicolon = try!(rest.find(';').ok_or(Error::Malformed));
+        let entity = &rest[1..semicolon];
+        let character = match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ if entity.starts_with("#x") => {
+                let code =
+                    try!(u32::from_str_radix(&entity[2..], 16).map_err(|_| Error::Malformed));
+                try!(::std::char::from_u32(code).ok_or(Error::Malformed))
+            }
+            _ if entity.starts_with('#') => {
+                let code = try!(entity[1..].parse().map_err(|_| Error::Malformed));
+                try!(::std::char::from_u32(code).ok_or(Error::Malformed))
+            }
+            _ => return Err(Error::Malformed),
+        };
+        decoded.push(character);
+        rest = &rest[(semicolon + 1)..]
+    }
+    decoded.push_str(rest);
+    Ok(decoded)
+}
+
+fn parse_opacity(value: &str) -> Result<f32, Error> {
+    let opacity: f32 = try!(value.trim().parse().map_err(|_| Error::Malformed));
+    Ok(opacity.max(0.0).min(1.0))
+}
+
+// Parses a transform list. The transforms apply from last to first.
+fn parse_transform(transform_list: &str) -> Result<Transform, Error> {
+    let mut transform = Transform::identity();
+    let mut rest = transform_list;
+    loop {
+        rest = rest.trim_left_matches(|c: char| c.is_whitespace() || c == ',');
+        if rest.is_empty() {
+            return Ok(transform)
+        }
+
+        let open = try!(rest.find('(').ok_or(Error::Malformed));
+        let close = try!(rest.find(')').ok_or(Error::Malformed));
+        if close < open {
+            return Err(Error::Malformed)
+        }
+        let name = rest[..open].trim();
+        let mut scanner = Scanner::new(&rest[(open + 1)..close]);
+        let mut arguments = vec![];
+        while let Some(argument) = try!(scanner.number()) {
+            arguments.push(argument)
+        }
+        rest = &rest[(close + 1)..];
+
+        let next = match (name, arguments.len()) {
+            ("matrix", 6) => {
+                Transform::new(arguments[0],
+                               arguments[1],
+                               arguments[2],
+                               arguments[3],
+                               arguments[4],
+                               arguments[5])
+            }
+            ("translate", 1) => Transform::translation(arguments[0], 0.0),
+            ("translate", 2) => Transform::translation(arguments[0], arguments[1]),
+            ("scale", 1) => Transform::scale(arguments[0], arguments[0]),
+            ("scale", 2) => Transform::scale(arguments[0], arguments[1]),
+            ("rotate", 1) => Transform::rotation(arguments[0].to_radians()),
+            ("rotate", 3) => {
+                Transform::rotation(arguments[0].to_radians())
+                    .around(&Point2D::new(arguments[1], arguments[2]))
+            }
+            ("skewX", 1) => {
+                Transform::new(1.0, 0.0, arguments[0].to_radians().tan(), 1.0, 0.0, 0.0)
+            }
+            ("skewY", 1) => {
+                Transform::new(1.0, arguments[0].to_radians().tan(), 0.0, 1.0, 0.0, 0.0)
+            }
+            _ => return Err(Error::Malformed),
+        };
+        transform = transform.pre_transform(&next)
+    }
+}
+
+// Parses path data into commands, converting arcs to cubic Bézier curves.
+fn parse_path_data(data: &str) -> Result<Vec<PathCommand>, Error> {
+    let mut commands = vec![];
+    let mut scanner = Scanner::new(data);
+    let (mut current, mut start) = (Point2D::new(0.0, 0.0), Point2D::new(0.0, 0.0));
+    // The control point that the smooth curve commands reflect, if the last command was a curve
+    // of the same kind.
+    let (mut last_cubic_control_point, mut last_quad_control_point) = (None, None);
+    let mut command = None;
+
+    loop {
+        scanner.skip_separators();
+        let letter = match scanner.peek() {
+            None => return Ok(commands),
+            Some(byte) if (byte as char).is_alphabetic() => {
+                scanner.position += 1;
+                byte
+            }
+            // Coordinates without a command letter repeat the last command, except that a move
+            // is followed by implicit lines.
+            Some(_) => {
+                match command {
+                    None => return Err(Error::Malformed),
+                    Some(b'M') => b'L',
+                    Some(b'm') => b'l',
+                    Some(command) => command,
+                }
+            }
+        };
+        command = Some(letter);
+        let relative = letter.is_ascii_lowercase();
+        let origin = if relative { current } else { Point2D::new(0.0, 0.0) };
+
+        let (cubic_control_point, quad_control_point) = match letter.to_ascii_uppercase() {
+            b'M' => {
+                current = origin + try!(scanner.point());
+                start = current;
+                commands.push(PathCommand::MoveTo(current));
+                (None, None)
+            }
+            b'L' => {
+                current = origin + try!(scanner.point());
+                commands.push(PathCommand::LineTo(current));
+                (None, None)
+            }
+            b'H' => {
+                current.x = origin.x + try!(scanner.required_number());
+                commands.push(PathCommand::LineTo(current));
+                (None, None)
+            }
+            b'V' => {
+                current.y = origin.y + try!(scanner.required_number());
+                commands.push(PathCommand::LineTo(current));
+                (None, None)
+            }
+            b'C' | b'S' => {
+                let control_point_0 = if letter.to_ascii_uppercase() == b'C' {
+                    origin + try!(scanner.point())
+                } else {
+                    match last_cubic_control_point {
+                        Some(point) => current * 2.0 - point,
+                        None => current,
+                    }
+                };
+                let control_point_1 = origin + try!(scanner.point());
+                current = origin + try!(scanner.point());
+                commands.push(PathCommand::CurveTo(control_point_0, control_point_1, current));
+                (Some(control_point_1), None)
+            }
+            b'Q' | b'T' => {
+                let control_point = if letter.to_ascii_uppercase() == b'Q' {
+                    origin + try!(scanner.point())
+                } else {
+                    match last_quad_control_point {
+                        Some(point) => current * 2.0 - point,
+                        None => current,
+                    }
+                };
+                current = origin + try!(scanner.point());
+                commands.push(PathCommand::QuadTo(control_point, current));
+                (None, Some(control_point))
+            }
+            b'A' => {
+                let radii = try!(scanner.point());
+                let x_axis_rotation = try!(scanner.required_number());
+                let large_arc = try!(scanner.flag());
+                let sweep = try!(scanner.flag());
+                let end = origin + try!(scanner.point());
+                arc_to(&mut commands, &current, &radii, x_axis_rotation, large_arc, sweep, &end);
+                current = end;
+                (None, None)
+            }
+            b'Z' => {
+                commands.push(PathCommand::ClosePath);
+                current = start;
+                // Coordinates can't follow a close path.
+                command = None;
+                (None, None)
+            }
+            _ => return Err(Error::Malformed),
+        };
+        last_cubic_control_point = cubic_control_point;
+        last_quad_control_point = quad_control_point;
+    }
+}
+
+// Appends an elliptical arc, converted to at most 90° cubic Bézier segments, following the
+// endpoint parameterization in the SVG specification.
+fn arc_to(commands: &mut Vec<PathCommand>,
+          from: &Point2D<f32>,
+          radii: &Point2D<f32>,
+          x_axis_rotation: f32,
+          large_arc: bool,
+          sweep: bool,
+          to: &Point2D<f32>) {
+    if from == to {
+        return
+    }
+    let (mut rx, mut ry) = (radii.x.abs(), radii.y.abs());
+    if rx == 0.0 || ry == 0.0 {
+        commands.push(PathCommand::LineTo(*to));
+        return
+    }
+
+    let (sin, cos) = x_axis_rotation.to_radians().sin_cos();
+    let half_delta = (*from - *to) * 0.5;
+    let x1 = cos * half_delta.x + sin * half_delta.y;
+    let y1 = -sin * half_delta.x + cos * half_delta.y;
+
+    // Scale up radii too small to reach the endpoint.
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut coefficient = (numerator / denominator).max(0.0).sqrt();
+    if large_arc == sweep {
+        coefficient = -coefficient
+    }
+    let (center_x1, center_y1) = (coefficient * rx * y1 / ry, -coefficient * ry * x1 / rx);
+    let center = Point2D::new(cos * center_x1 - sin * center_y1 + (from.x + to.x) * 0.5,
+                              sin * center_x1 + cos * center_y1 + (from.y + to.y) * 0.5);
+
+    let start_vector = Point2D::new((x1 - center_x1) / rx, (y1 - center_y1) / ry);
+    let end_vector = Point2D::new((-x1 - center_x1) / rx, (-y1 - center_y1) / ry);
+    let start_angle = start_vector.y.atan2(start_vector.x);
+    let mut sweep_angle = (start_vector.x * end_vector.y - start_vector.y * end_vector.x)
+        .atan2(start_vector.x * end_vector.x + start_vector.y * end_vector.y);
+    if !sweep && sweep_angle > 0.0 {
+        sweep_angle -= 2.0 * PI
+    } else if sweep && sweep_angle < 0.0 {
+        sweep_angle += 2.0 * PI
+    }
+
+    let point_at = |angle: f32| {
+        let (angle_sin, angle_cos) = angle.sin_cos();
+        Point2D::new(center.x + rx * angle_cos * cos - ry * angle_sin * sin,
+                     center.y + rx * angle_cos * sin + ry * angle_sin * cos)
+    };
+    let tangent_at = |angle: f32| {
+        let (angle_sin, angle_cos) = angle.sin_cos();
+        Point2D::new(-rx * angle_sin * cos - ry * angle_cos * sin,
+                     -rx * angle_sin * sin + ry * angle_cos * cos)
+    };
+
+    let segments = (sweep_angle.abs() / (PI * 0.5)).ceil().max(1.0) as u32;
+    let segment_angle = sweep_angle / segments as f32;
+    let handle_length = 4.0 / 3.0 * (segment_angle * 0.25).tan();
+    for segment in 0..segments {
+        let angle_0 = start_angle + segment_angle * segment as f32;
+        let angle_1 = angle_0 + segment_angle;
+        let end = if segment + 1 == segments { *to } else { point_at(angle_1) };
+        commands.push(PathCommand::CurveTo(point_at(angle_0) + tangent_at(angle_0) * handle_length,
+                                           end - tangent_at(angle_1) * handle_length,
+                                           end))
+    }
+}
+
+// Reads the numbers and flags of path data and transform lists.
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(text: &'a str) -> Scanner<'a> {
+        Scanner {
+            bytes: text.as_bytes(),
+            position: 0,
+        }
+    }
+
+    #[inline]
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).cloned()
+    }
+
+    fn skip_separators(&mut self) {
+        while let Some(byte) = self.peek() {
+            if !(byte as char).is_whitespace() && byte != b',' {
+                break
+            }
+            self.position += 1
+        }
+    }
+
+    // Reads a number, or returns `None` at the end of the input.
+    fn number(&mut self) -> Result<Option<f32>, Error> {
+        self.skip_separators();
+        let start = self.position;
+        if self.peek().is_none() {
+            return Ok(None)
+        }
+        if self.peek() == Some(b'+') || self.peek() == Some(b'-') {
+            self.position += 1
+        }
+        let mut seen_point = false;
+        while let Some(byte) = self.peek() {
+            match byte {
+                b'0'...b'9' => {}
+                b'.' if !seen_point => seen_point = true,
+                _ => break,
+            }
+            self.position += 1
+        }
+        if self.peek() == Some(b'e') || self.peek() == Some(b'E') {
+            self.position += 1;
+            if self.peek() == Some(b'+') || self.peek() == Some(b'-') {
+                self.position += 1
+            }
+            while let Some(b'0'...b'9') = self.peek() {
+                self.position += 1
+            }
+        }
+
+        let text = try!(str::from_utf8(&self.bytes[start..self.position])
+                            .map_err(|_| Error::Malformed));
+        text.parse().map(Some).map_err(|_| Error::Malformed)
+    }
+
+    fn required_number(&mut self) -> Result<f32, Error> {
+        self.number().and_then(|number| number.ok_or(Error::Malformed))
+    }
+
+    fn point(&mut self) -> Result<Point2D<f32>, Error> {
+        let x = try!(self.required_number());
+        let y = try!(self.required_number());
+        Ok(Point2D::new(x, y))
+    }
+
+    // Reads an arc flag, which may be run together with the following number.
+    fn flag(&mut self) -> Result<bool, Error> {
+        self.skip_separators();
+        let flag = match self.peek() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(Error::Malformed),
+        };
+        self.position += 1;
+        Ok(flag)
+    }
+}
+
+fn premultiply(color: Color) -> Rgba {
+    let alpha = color.a as f32 / 255.0;
+    [color.r as f32 / 255.0 * alpha, color.g as f32 / 255.0 * alpha, color.b as f32 / 255.0 * alpha,
+     alpha]
+}
@@ -0,0 +1,259 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use autohint::{AutoHinter, BlueZoneKind, BlueZones};
+use euclid::Point2D;
+use glyph_buffer::GlyphBufferBuilder;
+use memmap::{Mmap, Protection};
+use otf::FontData;
+use outline::PathCommand;
+use tests::TEST_FONT_PATH;
+
+// The bundled font's `cmap` isn't one we can read, but its glyphs are in the standard Macintosh
+// order, which puts the printable ASCII characters at their codes less 29.
+fn glyph_id(character: char) -> u16 {
+    character as u16 - 29
+}
+
+fn outline(font: &FontData, character: char) -> Option<Vec<PathCommand>> {
+    let head = font.head_table().unwrap();
+    let loca = font.loca_table(&head).unwrap();
+    let glyf = font.glyf_table().unwrap();
+    glyf.path_commands(&loca, glyph_id(character) as u32).ok()
+}
+
+fn points(commands: &[PathCommand]) -> Vec<Point2D<f32>> {
+    let mut points = vec![];
+    for command in commands {
+        match *command {
+            PathCommand::MoveTo(point) | PathCommand::LineTo(point) => points.push(point),
+            PathCommand::QuadTo(control_point, point) => {
+                points.push(control_point);
+                points.push(point)
+            }
+            PathCommand::CurveTo(control_point_0, control_point_1, point) => {
+                points.push(control_point_0);
+                points.push(control_point_1);
+                points.push(point)
+            }
+            PathCommand::ClosePath => {}
+        }
+    }
+    points
+}
+
+fn assert_on_pixel_grid(y: f32, units_per_pixel: f32) {
+    let pixels = y / units_per_pixel;
+    assert!((pixels - pixels.round()).abs() < 0.001, "{} isn't on the pixel grid", y)
+}
+
+#[test]
+fn detect_blue_zones() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let blue_zones = BlueZones::detect(|character| outline(&font, character));
+
+    let kinds: Vec<_> = blue_zones.zones.iter().map(|zone| zone.kind).collect();
+    assert_eq!(kinds, [
+        BlueZoneKind::CapHeight,
+        BlueZoneKind::XHeight,
+        BlueZoneKind::Baseline,
+        BlueZoneKind::Descender,
+    ]);
+
+    let cap_height = blue_zones.zones[0];
+    assert_eq!((cap_height.reference, cap_height.overshoot), (1493.0, 1518.0));
+    let x_height = blue_zones.zones[1];
+    assert_eq!((x_height.reference, x_height.overshoot), (1073.0, 1104.0));
+    let baseline = blue_zones.zones[2];
+    assert_eq!(baseline.reference, 0.0);
+    assert!(baseline.overshoot < -40.0 && baseline.overshoot > -50.0);
+    let descender = blue_zones.zones[3];
+    assert_eq!((descender.reference, descender.overshoot), (-446.0, -446.0));
+
+    // Without any reference characters there are no zones.
+    assert!(BlueZones::detect(|_| None).zones.is_empty());
+}
+
+#[test]
+fn autohint_snaps_edges_to_pixels() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let units_per_em = font.head_table().unwrap().units_per_em;
+    let blue_zones = BlueZones::detect(|character| outline(&font, character));
+    let hinter = AutoHinter::new(units_per_em, blue_zones);
+    let units_per_pixel = units_per_em as f32 / 12.0;
+
+    // Every point of "H" lies on a horizontal edge. The cap height of 8.75 pixels rounds to 9,
+    // and the 0.98 pixel crossbar gets a whole pixel.
+    let original = outline(&font, 'H').unwrap();
+    let hinted = hinter.hint(&original, 12.0);
+    assert_eq!(hinted.len(), original.len());
+    let (original_points, hinted_points) = (points(&original), points(&hinted));
+    for (original_point, hinted_point) in original_points.iter().zip(hinted_points.iter()) {
+        assert_eq!(original_point.x, hinted_point.x);
+        assert_on_pixel_grid(hinted_point.y, units_per_pixel)
+    }
+    let top = hinted_points.iter().map(|point| point.y).fold(0.0, f32::max);
+    assert_eq!(top, 9.0 * units_per_pixel);
+    let mut crossbar: Vec<_> = hinted_points.iter().map(|point| point.y).filter(|&y| {
+        y > 0.0 && y < top
+    }).collect();
+    crossbar.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    crossbar.dedup();
+    assert_eq!(crossbar.len(), 2);
+    assert_eq!(((crossbar[1] - crossbar[0]) / units_per_pixel).round(), 1.0);
+
+    // The overshoot of "o" is under half a pixel at this size, so it lines up with the flat
+    // x-height and baseline.
+    let hinted = hinter.hint(&outline(&font, 'o').unwrap(), 12.0);
+    let ys: Vec<_> = points(&hinted).iter().map(|point| point.y).collect();
+    assert_eq!(ys.iter().cloned().fold(0.0, f32::max), 6.0 * units_per_pixel);
+    assert_eq!(ys.iter().cloned().fold(0.0, f32::min), 0.0);
+
+    // At a large size, it overshoots by a pixel.
+    let hinted = hinter.hint(&outline(&font, 'o').unwrap(), 48.0);
+    let ys: Vec<_> = points(&hinted).iter().map(|point| point.y).collect();
+    let units_per_pixel = units_per_em as f32 / 48.0;
+    assert_eq!(ys.iter().cloned().fold(0.0, f32::max), 26.0 * units_per_pixel);
+    assert_eq!(ys.iter().cloned().fold(0.0, f32::min), -units_per_pixel);
+}
+
+#[test]
+fn autohint_cubic_outline() {
+    // A counterclockwise bar, as in a PostScript font, with a bulging right side.
+    let commands = [
+        PathCommand::MoveTo(Point2D::new(0.0, 100.0)),
+        PathCommand::LineTo(Point2D::new(300.0, 100.0)),
+        PathCommand::CurveTo(Point2D::new(350.0, 100.0),
+                             Point2D::new(350.0, 180.0),
+                             Point2D::new(300.0, 180.0)),
+        PathCommand::LineTo(Point2D::new(0.0, 180.0)),
+        PathCommand::ClosePath,
+    ];
+
+    // The bar is 0.8 pixels thick at 1.0 to 1.8 pixels, so it becomes one pixel thick, from 1 to
+    // 2 pixels.
+    let hinter = AutoHinter::new(1000, BlueZones { zones: vec![] });
+    let hinted = hinter.hint(&commands, 10.0);
+    let ys: Vec<_> = points(&hinted).iter().map(|point| point.y).collect();
+    assert_eq!(ys, [100.0, 100.0, 100.0, 200.0, 200.0, 200.0]);
+    match hinted[2] {
+        PathCommand::CurveTo(..) => {}
+        command => panic!("unexpected command {:?}", command),
+    }
+}
+
+#[test]
+fn add_hinted_outline_to_glyph_buffer() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let head = font.head_table().unwrap();
+    let loca = font.loca_table(&head).unwrap();
+    let glyf = font.glyf_table().unwrap();
+
+    // Unhinted, the path commands produce the same points as the glyph itself.
+    let mut glyph_builder = GlyphBufferBuilder::new();
+    glyph_builder.add_glyph(glyph_id('o') as u32, &head, &loca, &glyf).unwrap();
+    let mut outline_builder = GlyphBufferBuilder::new();
+    outline_builder.add_path_commands(&outline(&font, 'o').unwrap(), head.units_per_em).unwrap();
+    assert_eq!(outline_builder.coordinates, glyph_builder.coordinates);
+    assert_eq!(outline_builder.descriptors[0].point_count,
+               glyph_builder.descriptors[0].point_count);
+    assert_eq!(outline_builder.descriptors[0].bottom, glyph_builder.descriptors[0].bottom);
+
+    let blue_zones = BlueZones::detect(|character| outline(&font, character));
+    let hinter = AutoHinter::new(head.units_per_em, blue_zones);
+    let hinted = hinter.hint(&outline(&font, 'o').unwrap(), 12.0);
+    outline_builder.add_path_commands(&hinted, head.units_per_em).unwrap();
+    let descriptor = &outline_builder.descriptors[1];
+    assert_eq!(descriptor.start_point, glyph_builder.descriptors[0].point_count as u32);
+    assert_eq!((descriptor.bottom, descriptor.bottom + descriptor.height), (0, 1024));
+
+}
+
+#[test]
+fn mixed_hinted_and_unhinted_glyphs() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let head = font.head_table().unwrap();
+    let loca = font.loca_table(&head).unwrap();
+    let glyf = font.glyf_table().unwrap();
+    let blue_zones = BlueZones::detect(|character| outline(&font, character));
+    let hinter = AutoHinter::new(head.units_per_em, blue_zones);
+
+    // Alternate between the two ways in, with point counts that don't fill whole bytes of
+    // operations.
+    let mut builder = GlyphBufferBuilder::new();
+    let mut expected_operations = vec![];
+    for (index, &character) in ['o', 'x', 'a', 'H', 'e'].iter().enumerate() {
+        let mut glyph_builder = GlyphBufferBuilder::new();
+        if index % 2 == 0 {
+            builder.add_glyph(glyph_id(character) as u32, &head, &loca, &glyf).unwrap();
+            glyph_builder.add_glyph(glyph_id(character) as u32, &head, &loca, &glyf).unwrap();
+        } else {
+            let hinted = hinter.hint(&outline(&font, character).unwrap(), 12.0);
+            builder.add_path_commands(&hinted, head.units_per_em).unwrap();
+            glyph_builder.add_path_commands(&hinted, head.units_per_em).unwrap();
+        }
+
+        let descriptor = builder.descriptors.last().unwrap();
+        let start_point = descriptor.start_point as usize;
+        let end_point = start_point + descriptor.point_count as usize;
+        assert_eq!(end_point, builder.coordinates.len());
+        assert_eq!(&builder.coordinates[start_point..end_point], &glyph_builder.coordinates[..]);
+        expected_operations.extend(operations(&glyph_builder));
+    }
+    assert_eq!(operations(&builder), expected_operations);
+}
+
+#[test]
+fn cubic_curves_are_approximated() {
+    // A cubic curve that is really a quadratic one becomes exactly that curve.
+    let mut builder = GlyphBufferBuilder::new();
+    let elevated = [
+        PathCommand::MoveTo(Point2D::new(0.0, 0.0)),
+        PathCommand::CurveTo(Point2D::new(20.0, 40.0),
+                             Point2D::new(40.0, 40.0),
+                             Point2D::new(60.0, 0.0)),
+        PathCommand::ClosePath,
+    ];
+    builder.add_path_commands(&elevated, 1000).unwrap();
+    assert_eq!(builder.coordinates, vec![(0, 0), (30, 60), (60, 0), (0, 0)]);
+    assert_eq!(operations(&builder), vec![0, 2, 1, 1]);
+
+    // Others are split, with every on-curve point on the original curve.
+    let mut builder = GlyphBufferBuilder::new();
+    let arch = [
+        PathCommand::MoveTo(Point2D::new(0.0, 0.0)),
+        PathCommand::CurveTo(Point2D::new(0.0, 800.0),
+                             Point2D::new(800.0, 800.0),
+                             Point2D::new(800.0, 0.0)),
+    ];
+    builder.add_path_commands(&arch, 1000).unwrap();
+    let operations = operations(&builder);
+    assert!(builder.coordinates.len() > 4);
+    assert_eq!(builder.coordinates[builder.coordinates.len() - 2], (800, 0));
+    for (&(x, y), &operation) in builder.coordinates.iter().zip(operations.iter()) {
+        if operation == 1 {
+            let distance = distance_to_arch(&Point2D::new(x as f32, y as f32));
+            assert!(distance <= 1.0, "({}, {}) is {} units off the curve", x, y, distance);
+        }
+    }
+}
+
+// Returns the operation of each point in the buffers, unpacked from four to a byte.
+fn operations(builder: &GlyphBufferBuilder) -> Vec<u8> {
+    (0..builder.coordinates.len()).map(|point_index| {
+        (builder.operations[point_index / 4] >> (point_index % 4 * 2)) & 3
+    }).collect()
+}
+
+// Returns roughly how far a point is from the arch in `cubic_curves_are_approximated()`.
+fn distance_to_arch(point: &Point2D<f32>) -> f32 {
+    (0..1001).map(|step| {
+        let t = step as f32 / 1000.0;
+        let (x, y) = (800.0 * t * t * (3.0 - 2.0 * t), 2400.0 * t * (1.0 - t));
+        ((point.x - x) * (point.x - x) + (point.y - y) * (point.y - y)).sqrt()
+    }).fold(f32::INFINITY, f32::min)
+}
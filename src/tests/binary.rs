@@ -0,0 +1,133 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use binary::{FromData, LazyArray, Offset, Offset16, Offset32, Reader};
+use error::Error;
+use otf::Tag;
+use std::usize;
+use std::vec::Vec;
+
+binary_record! {
+    #[derive(PartialEq)]
+    pub struct Point {
+        pub x: i16,
+        pub y: i16,
+    }
+}
+
+binary_record! {
+    /// A record with a nested record and a trailing comma.
+    pub struct Record {
+        pub tag: Tag,
+        /// Where the rest is.
+        pub offset: Offset16,
+        pub point: Point,
+        pub flags: u8,
+    }
+}
+
+// Remembers how many bytes it was parsed from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParsedLength(usize);
+
+impl FromData for ParsedLength {
+    const SIZE: usize = 3;
+
+    #[inline]
+    fn parse(data: &[u8]) -> ParsedLength {
+        ParsedLength(data.len())
+    }
+}
+
+binary_record! {
+    pub struct Lengths {
+        pub first: ParsedLength,
+        pub second: ParsedLength,
+    }
+}
+
+#[test]
+fn records() {
+    assert_eq!(Record::SIZE, 11);
+    let data = [b't', b'e', b's', b't', 0, 12, 0xff, 0xfe, 0, 3, 0x80, 7];
+    let mut reader = Reader::new(&data);
+    let record = reader.read::<Record>().unwrap();
+    assert_eq!(record.tag, Tag::new(b"test"));
+    assert_eq!(record.offset, Offset16(12));
+    assert_eq!(record.point, Point { x: -2, y: 3 });
+    assert_eq!(record.flags, 0x80);
+    assert_eq!(reader.position(), 11);
+    assert_eq!(reader.remaining(), &[7]);
+}
+
+#[test]
+fn reads_past_the_end_fail_without_moving() {
+    let data = [1, 2, 3];
+    let mut reader = Reader::new(&data);
+    assert_eq!(reader.read::<u16>().unwrap(), 0x0102);
+    assert_eq!(reader.read::<u16>().err(), Some(Error::UnexpectedEof));
+    assert_eq!(reader.read::<Point>().err(), Some(Error::UnexpectedEof));
+    assert_eq!(reader.skip_bytes(2).err(), Some(Error::UnexpectedEof));
+    assert_eq!(reader.read_array::<u32>(usize::MAX).err(), Some(Error::UnexpectedEof));
+    assert_eq!(reader.position(), 2);
+    assert_eq!(reader.read::<i8>().unwrap(), 3);
+    assert!(reader.remaining().is_empty());
+
+    assert_eq!(Reader::at(&data, 3).unwrap().remaining(), &[]);
+    assert_eq!(Reader::at(&data, 4).err(), Some(Error::UnexpectedEof));
+}
+
+#[test]
+fn lazy_arrays() {
+    let data = [0, 1, 0, 3, 0, 5, 0, 7, 9];
+    let mut reader = Reader::new(&data);
+    reader.skip::<u16>().unwrap();
+    let array = reader.read_array::<u16>(3).unwrap();
+    assert_eq!(reader.position(), 8);
+    assert_eq!(array.len(), 3);
+    assert_eq!(array.get(2), Some(7));
+    assert_eq!(array.get(3), None);
+    assert_eq!(array.iter().collect::<Vec<_>>(), vec![3, 5, 7]);
+    assert_eq!(array.iter().len(), 3);
+    assert_eq!(array.binary_search_by(|value| value.cmp(&5)), Ok(1));
+    assert_eq!(array.binary_search_by(|value| value.cmp(&6)), Err(2));
+    assert_eq!(array.binary_search_by(|value| value.cmp(&8)), Err(3));
+
+    // A partial value at the end doesn't count.
+    let array = LazyArray::<u16>::new(&data[1..]);
+    assert_eq!(array.len(), 4);
+    assert_eq!(array.into_iter().last(), Some(0x0709));
+    assert!(LazyArray::<u32>::new(&data[..3]).is_empty());
+}
+
+#[test]
+fn offsets() {
+    let data = [0, 0, 0, 6, 0, 0, 42, 43];
+    let mut reader = Reader::new(&data);
+    let null = reader.read::<Offset16>().unwrap();
+    let offset = reader.read::<Offset16>().unwrap();
+    assert!(null.is_null());
+    assert_eq!(null.resolve_nullable(&data), Ok(None));
+    assert_eq!(offset.resolve(&data), Ok(&data[6..]));
+    assert_eq!(offset.resolve_nullable(&data), Ok(Some(&data[6..])));
+
+    // Offsets are relative to whatever they're resolved against, and can point at the very end.
+    assert_eq!(offset.resolve(&data[2..]), Ok(&[][..]));
+    assert_eq!(offset.resolve(&data[3..]).err(), Some(Error::UnexpectedEof));
+    assert_eq!(Offset32(9).resolve(&data).err(), Some(Error::UnexpectedEof));
+    assert_eq!(Reader::new(&data).read::<Offset32>().unwrap(), Offset32(6));
+}
+
+#[test]
+fn values_are_parsed_from_exactly_their_size() {
+    let data = [0; 16];
+    let mut reader = Reader::new(&data);
+    assert_eq!(reader.read::<ParsedLength>().unwrap(), ParsedLength(3));
+    let lengths = reader.read::<Lengths>().unwrap();
+    assert_eq!((lengths.first, lengths.second), (ParsedLength(3), ParsedLength(3)));
+
+    let array = LazyArray::<ParsedLength>::new(&data);
+    assert_eq!(array.get(0), Some(ParsedLength(3)));
+    assert_eq!(array.iter().last(), Some(ParsedLength(3)));
+    assert_eq!(array.binary_search_by(|length| length.0.cmp(&3)), Ok(2));
+}
@@ -0,0 +1,713 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+#[cfg(feature = "gpu")]
+use batch::{BatchBuilder, BitmapDescriptor};
+use bitmap::{self, BitmapGlyph, EmbeddedBitmaps};
+use byteorder::{BigEndian, WriteBytesExt};
+use error::Error;
+use euclid::Point2D;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+#[cfg(feature = "gpu")]
+use glyph_buffer::GlyphBufferBuilder;
+use limits::{Limit, Limits};
+#[cfg(feature = "gpu")]
+use memmap::{Mmap, Protection};
+use otf::{FontData, Tag};
+use otf::cblc::BitmapGlyphMetrics;
+use otf::writer::{FontBuilder, MaxpBuilder};
+use png;
+use software_rasterizer::RgbaImage;
+use std::io::Write;
+#[cfg(feature = "gpu")]
+use std::mem;
+#[cfg(feature = "gpu")]
+use tests::TEST_FONT_PATH;
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 }
+        }
+    }
+    !crc
+}
+
+fn write_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    png.write_u32::<BigEndian>(data.len() as u32).unwrap();
+    let start = png.len();
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.write_u32::<BigEndian>(crc).unwrap();
+}
+
+// Wraps already filtered scanlines in a PNG file.
+fn encode_png(width: u32,
+              height: u32,
+              bit_depth: u8,
+              color_type: u8,
+              interlaced: bool,
+              extra_chunks: &[(&[u8; 4], &[u8])],
+              scanlines: &[u8])
+              -> Vec<u8> {
+    let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    let mut header = vec![];
+    header.write_u32::<BigEndian>(width).unwrap();
+    header.write_u32::<BigEndian>(height).unwrap();
+    header.extend_from_slice(&[bit_depth, color_type, 0, 0, interlaced as u8]);
+    write_chunk(&mut png, b"IHDR", &header);
+    for &(chunk_type, data) in extra_chunks {
+        write_chunk(&mut png, chunk_type, data)
+    }
+
+    let mut encoder = ZlibEncoder::new(vec![], Compression::Default);
+    encoder.write_all(scanlines).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    // Split the image data across two chunks, as encoders may.
+    let middle = compressed.len() / 2;
+    write_chunk(&mut png, b"IDAT", &compressed[..middle]);
+    write_chunk(&mut png, b"IDAT", &compressed[middle..]);
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn paeth(left: u8, above: u8, upper_left: u8) -> u8 {
+    let estimate = left as i16 + above as i16 - upper_left as i16;
+    let (a, b, c) = ((estimate - left as i16).abs(),
+                     (estimate - above as i16).abs(),
+                     (estimate - upper_left as i16).abs());
+    if a <= b && a <= c { left } else if b <= c { above } else { upper_left }
+}
+
+fn filter_row(filter: u8, row: &[u8], previous_row: &[u8], bytes_per_pixel: usize) -> Vec<u8> {
+    let mut filtered = vec![filter];
+    for index in 0..row.len() {
+        let left = if index >= bytes_per_pixel { row[index - bytes_per_pixel] } else { 0 };
+        let upper_left = if index >= bytes_per_pixel {
+            previous_row[index - bytes_per_pixel]
+        } else {
+            0
+        };
+        let above = previous_row[index];
+        let predictor = match filter {
+            0 => 0,
+            1 => left,
+            2 => above,
+            3 => ((left as u16 + above as u16) / 2) as u8,
+            _ => paeth(left, above, upper_left),
+        };
+        filtered.push(row[index].wrapping_sub(predictor))
+    }
+    filtered
+}
+
+fn premultiply(color: [u8; 4]) -> [u8; 4] {
+    let alpha = color[3] as u32;
+    [((color[0] as u32 * alpha + 127) / 255) as u8,
+     ((color[1] as u32 * alpha + 127) / 255) as u8,
+     ((color[2] as u32 * alpha + 127) / 255) as u8,
+     color[3]]
+}
+
+// A non-interlaced 8-bit RGBA image.
+fn encode_rgba_png(width: u32, height: u32, pixels: &[[u8; 4]]) -> Vec<u8> {
+    let mut scanlines = vec![];
+    let mut previous_row = vec![0; width as usize * 4];
+    for y in 0..(height as usize) {
+        let row: Vec<u8> = pixels[(y * width as usize)..((y + 1) * width as usize)]
+            .iter()
+            .flat_map(|pixel| pixel.iter().cloned())
+            .collect();
+        scanlines.extend_from_slice(&filter_row((y % 5) as u8, &row, &previous_row, 4));
+        previous_row = row
+    }
+    encode_png(width, height, 8, 6, false, &[], &scanlines)
+}
+
+fn solid_png(color: [u8; 4]) -> Vec<u8> {
+    encode_rgba_png(2, 2, &[color; 4])
+}
+
+#[test]
+fn png_filters_and_alpha() {
+    // Five rows exercise each of the five filter types.
+    let pixels: Vec<[u8; 4]> = (0..15u32).map(|index| {
+        [(index * 37) as u8, (index * 91 + 7) as u8, (255 - index * 13) as u8, (index * 17) as u8]
+    }).collect();
+    let image = png::decode(&encode_rgba_png(3, 5, &pixels), &Limits::default()).unwrap();
+    assert_eq!((image.width, image.height), (3, 5));
+    for (index, &pixel) in pixels.iter().enumerate() {
+        assert_eq!(image.pixel(index as u32 % 3, index as u32 / 3), premultiply(pixel));
+    }
+
+    assert!(png::decode(b"not a png", &Limits::default()).is_err());
+    let truncated = encode_rgba_png(3, 5, &pixels);
+    assert!(png::decode(&truncated[0..(truncated.len() - 20)], &Limits::default()).is_err());
+}
+
+#[test]
+fn png_limits() {
+    let limits = Limits {
+        max_image_size: 4,
+        ..Limits::default()
+    };
+    let pixels = [RED; 5 * 4];
+    assert!(png::decode(&encode_rgba_png(4, 4, &pixels), &limits).is_ok());
+    assert_eq!(png::decode(&encode_rgba_png(5, 4, &pixels), &limits),
+               Err(Error::LimitExceeded(Limit::ImageSize)));
+    assert_eq!(png::decode(&encode_rgba_png(4, 5, &pixels), &limits),
+               Err(Error::LimitExceeded(Limit::ImageSize)));
+
+    // A stream that inflates to far more than the scanlines is cut off rather than buffered.
+    let png = encode_png(1, 1, 8, 0, false, &[], &vec![0; 1 << 22]);
+    assert_eq!(png::decode(&png, &Limits::default()).unwrap().pixel(0, 0), [0, 0, 0, 255]);
+}
+
+#[test]
+fn png_indexed_with_transparency() {
+    let palette = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+    let transparency = [255, 0];
+
+    // Two bits per pixel: indices 2, 1, 0.
+    let image = png::decode(&encode_png(3,
+                                        1,
+                                        2,
+                                        3,
+                                        false,
+                                        &[(b"PLTE", &palette), (b"tRNS", &transparency)],
+                                        &[0, 0b10_01_00_00]),
+                            &Limits::default()).unwrap();
+    assert_eq!(image.pixel(0, 0), BLUE);
+    assert_eq!(image.pixel(1, 0), [0, 0, 0, 0]);
+    assert_eq!(image.pixel(2, 0), RED);
+}
+
+#[test]
+fn png_interlaced_grayscale() {
+    let (width, height) = (5, 6);
+    let gray = |x: u32, y: u32| (x * 40 + y * 7) as u8;
+
+    let passes = [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4),
+                  (1, 0, 2, 2), (0, 1, 1, 2)];
+    let mut scanlines = vec![];
+    for &(x0, y0, dx, dy) in &passes {
+        let mut y = y0;
+        while y < height {
+            let row: Vec<u8> = (0..).map(|column| x0 + column * dx)
+                                    .take_while(|&x| x < width)
+                                    .map(|x| gray(x, y))
+                                    .collect();
+            if !row.is_empty() {
+                scanlines.push(0);
+                scanlines.extend_from_slice(&row)
+            }
+            y += dy
+        }
+    }
+
+    let png = encode_png(width, height, 8, 0, true, &[], &scanlines);
+    let image = png::decode(&png, &Limits::default()).unwrap();
+    for y in 0..height {
+        for x in 0..width {
+            let value = gray(x, y);
+            assert_eq!(image.pixel(x, y), [value, value, value, 255]);
+        }
+    }
+}
+
+#[test]
+fn image_scaling_and_blitting() {
+    let png = encode_rgba_png(2, 2, &[RED, GREEN, BLUE, [0, 0, 0, 255]]);
+    let image = png::decode(&png, &Limits::default()).unwrap();
+    let halved = image.scaled(0.5).unwrap();
+    assert_eq!((halved.width, halved.height), (1, 1));
+    assert_eq!(halved.pixel(0, 0), [64, 64, 64, 255]);
+
+    let doubled = image.scaled(2.0).unwrap();
+    assert_eq!((doubled.width, doubled.height), (4, 4));
+    assert_eq!(doubled.pixel(1, 1), RED);
+    assert_eq!(doubled.pixel(2, 3), [0, 0, 0, 255]);
+
+    let mut destination = RgbaImage::new(4, 4);
+    destination.blit(&image, &Point2D::new(2, 1)).unwrap();
+    assert_eq!(destination.pixel(2, 1), RED);
+    assert_eq!(destination.pixel(3, 2), [0, 0, 0, 255]);
+    assert_eq!(destination.pixel(1, 1), [0, 0, 0, 0]);
+    assert!(destination.blit(&image, &Point2D::new(3, 0)).is_err());
+}
+
+#[test]
+fn strike_selection() {
+    let ppems = [20, 40, 64, 32];
+    assert_eq!(bitmap::strike_order(&ppems, 30.0), vec![3, 1, 2, 0]);
+    assert_eq!(bitmap::strike_order(&ppems, 64.0), vec![2, 1, 3, 0]);
+    assert_eq!(bitmap::strike_order(&ppems, 100.0), vec![2, 1, 3, 0]);
+}
+
+fn write_bitmap_size(cblc: &mut Vec<u8>,
+                     array_offset: u32,
+                     subtable_count: u32,
+                     glyph_range: (u16, u16),
+                     ppem: u8) {
+    cblc.write_u32::<BigEndian>(array_offset).unwrap();
+    cblc.write_u32::<BigEndian>(0).unwrap();
+    cblc.write_u32::<BigEndian>(subtable_count).unwrap();
+    cblc.write_u32::<BigEndian>(0).unwrap();
+    cblc.extend_from_slice(&[0; 24]);
+    cblc.write_u16::<BigEndian>(glyph_range.0).unwrap();
+    cblc.write_u16::<BigEndian>(glyph_range.1).unwrap();
+    cblc.extend_from_slice(&[ppem, ppem, 32, 1]);
+}
+
+// Two strikes, of 20 and 40 pixels per em. Glyph 5 is red in the first and green in the second,
+// and glyph 6, only in the second, is blue and uses constant metrics from `CBLC`.
+fn make_cbdt_font() -> Vec<u8> {
+    let metrics = [2, 2, 1, 2, 3];
+    let mut cbdt = vec![0, 3, 0, 0];
+
+    let (mut data_offsets, mut format_17_lengths) = (vec![], vec![]);
+    for &color in &[RED, GREEN] {
+        data_offsets.push(cbdt.len() as u32);
+        let png = solid_png(color);
+        cbdt.extend_from_slice(&metrics);
+        cbdt.write_u32::<BigEndian>(png.len() as u32).unwrap();
+        cbdt.extend_from_slice(&png);
+        format_17_lengths.push(metrics.len() as u32 + 4 + png.len() as u32);
+    }
+    data_offsets.push(cbdt.len() as u32);
+    let png = solid_png(BLUE);
+    cbdt.write_u32::<BigEndian>(png.len() as u32).unwrap();
+    cbdt.extend_from_slice(&png);
+    let format_19_length = cbdt.len() as u32 - data_offsets[2];
+
+    let mut cblc = vec![0, 3, 0, 0, 0, 0, 0, 2];
+    write_bitmap_size(&mut cblc, 104, 1, (5, 5), 20);
+    write_bitmap_size(&mut cblc, 128, 2, (5, 6), 40);
+
+    // Index format 1 with PNG image format 17.
+    cblc.write_u16::<BigEndian>(5).unwrap();
+    cblc.write_u16::<BigEndian>(5).unwrap();
+    cblc.write_u32::<BigEndian>(8).unwrap();
+    for &value in &[1, 17] {
+        cblc.write_u16::<BigEndian>(value).unwrap();
+    }
+    for &value in &[data_offsets[0], 0, format_17_lengths[0]] {
+        cblc.write_u32::<BigEndian>(value).unwrap();
+    }
+
+    for &(glyph_id, offset) in &[(5, 16), (6, 32)] {
+        cblc.write_u16::<BigEndian>(glyph_id).unwrap();
+        cblc.write_u16::<BigEndian>(glyph_id).unwrap();
+        cblc.write_u32::<BigEndian>(offset).unwrap();
+    }
+    for &value in &[1, 17] {
+        cblc.write_u16::<BigEndian>(value).unwrap();
+    }
+    for &value in &[data_offsets[1], 0, format_17_lengths[1]] {
+        cblc.write_u32::<BigEndian>(value).unwrap();
+    }
+
+    // Index format 2 with PNG image format 19.
+    for &value in &[2, 19] {
+        cblc.write_u16::<BigEndian>(value).unwrap();
+    }
+    cblc.write_u32::<BigEndian>(data_offsets[2]).unwrap();
+    cblc.write_u32::<BigEndian>(format_19_length).unwrap();
+    cblc.extend_from_slice(&[2, 2, 0xff, 1, 2, 0, 0, 0]);
+
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"CBDT"), cbdt);
+    font_builder.add_table(Tag::new(b"CBLC"), cblc);
+    font_builder.build().unwrap()
+}
+
+#[test]
+fn cbdt_glyphs() {
+    let bytes = make_cbdt_font();
+    let font = FontData::new(&bytes);
+    let (cblc, cbdt) = (font.cblc_table().unwrap(), font.cbdt_table().unwrap());
+
+    let strikes = cblc.strikes().unwrap();
+    assert_eq!(strikes.len(), 2);
+    assert_eq!((strikes[0].ppem_y, strikes[1].ppem_y), (20, 40));
+    let location = cblc.glyph_location(&strikes[1], 6).unwrap().unwrap();
+    assert_eq!(location.image_format, 19);
+    assert_eq!(location.metrics, Some(BitmapGlyphMetrics {
+        width: 2,
+        height: 2,
+        bearing_x: -1,
+        bearing_y: 1,
+        advance: 2,
+    }));
+
+    let limits = Limits::default();
+    let small = BitmapGlyph::from_cbdt(&cblc, &cbdt, 5, 16.0, &limits).unwrap().unwrap();
+    assert_eq!(small.pixels_per_em, 20.0);
+    assert_eq!(small.image.pixel(1, 1), RED);
+    assert_eq!(small.origin, Point2D::new(-1, 2));
+
+    let large = BitmapGlyph::from_cbdt(&cblc, &cbdt, 5, 30.0, &limits).unwrap().unwrap();
+    assert_eq!(large.pixels_per_em, 40.0);
+    assert_eq!(large.image.pixel(0, 0), GREEN);
+
+    // Glyph 6 is only in the larger strike, so we fall back to it.
+    let fallback = BitmapGlyph::from_cbdt(&cblc, &cbdt, 6, 10.0, &limits).unwrap().unwrap();
+    assert_eq!(fallback.pixels_per_em, 40.0);
+    assert_eq!(fallback.image.pixel(0, 1), BLUE);
+    assert_eq!(fallback.origin, Point2D::new(1, 1));
+
+    assert_eq!(BitmapGlyph::from_cbdt(&cblc, &cbdt, 7, 20.0, &limits).unwrap(), None);
+}
+
+// Two strikes, of 16 and 32 pixels per em, of three glyphs. Glyph 1 has an image, and glyph 2
+// duplicates it.
+fn make_sbix_font() -> Vec<u8> {
+    let maxp = MaxpBuilder::new(3).build();
+
+    let mut sbix = vec![];
+    sbix.write_u16::<BigEndian>(1).unwrap();
+    sbix.write_u16::<BigEndian>(3).unwrap();
+    sbix.write_u32::<BigEndian>(2).unwrap();
+    let strike_offsets_start = sbix.len();
+    sbix.extend_from_slice(&[0; 8]);
+
+    for (strike_index, &(ppem, color)) in [(16, RED), (32, GREEN)].iter().enumerate() {
+        let strike_offset = sbix.len();
+        let record = strike_offsets_start + strike_index * 4;
+        (&mut sbix[record..(record + 4)]).write_u32::<BigEndian>(strike_offset as u32).unwrap();
+
+        let png = solid_png(color);
+        let mut strike = vec![];
+        strike.write_u16::<BigEndian>(ppem).unwrap();
+        strike.write_u16::<BigEndian>(72).unwrap();
+        let glyph_1_offset = 4 + 4 * 4;
+        let glyph_2_offset = glyph_1_offset + 8 + png.len() as u32;
+        for &offset in &[glyph_1_offset, glyph_1_offset, glyph_2_offset, glyph_2_offset + 10] {
+            strike.write_u32::<BigEndian>(offset).unwrap();
+        }
+        strike.write_i16::<BigEndian>(1).unwrap();
+        strike.write_i16::<BigEndian>(-1).unwrap();
+        strike.extend_from_slice(b"png ");
+        strike.extend_from_slice(&png);
+        strike.write_i16::<BigEndian>(0).unwrap();
+        strike.write_i16::<BigEndian>(0).unwrap();
+        strike.extend_from_slice(b"dupe");
+        strike.write_u16::<BigEndian>(1).unwrap();
+        sbix.extend_from_slice(&strike);
+    }
+
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"maxp"), maxp);
+    font_builder.add_table(Tag::new(b"sbix"), sbix);
+    font_builder.build().unwrap()
+}
+
+#[test]
+fn sbix_glyphs() {
+    let bytes = make_sbix_font();
+    let font = FontData::new(&bytes);
+    let maxp = font.maxp_table().unwrap();
+    assert_eq!(maxp.num_glyphs, 3);
+    let sbix = font.sbix_table(&maxp).unwrap();
+    assert!(sbix.draws_outlines());
+    assert_eq!(sbix.strikes().unwrap().iter().map(|strike| strike.ppem).collect::<Vec<_>>(),
+               vec![16, 32]);
+
+    let limits = Limits::default();
+    let glyph = BitmapGlyph::from_sbix(&sbix, 1, 12.0, &limits).unwrap().unwrap();
+    assert_eq!(glyph.pixels_per_em, 16.0);
+    assert_eq!(glyph.image.pixel(0, 0), RED);
+    assert_eq!(glyph.origin, Point2D::new(-1, 1));
+
+    let duplicate = BitmapGlyph::from_sbix(&sbix, 2, 20.0, &limits).unwrap().unwrap();
+    assert_eq!(duplicate.pixels_per_em, 32.0);
+    assert_eq!(duplicate.image.pixel(1, 0), GREEN);
+
+    assert_eq!(BitmapGlyph::from_sbix(&sbix, 0, 20.0, &limits).unwrap(), None);
+    assert!(BitmapGlyph::from_sbix(&sbix, 3, 20.0, &limits).is_err());
+}
+
+#[test]
+fn sbix_skips_unsupported_images() {
+    // Turn the 32 pixel strike's image into a TIFF, which the 16 pixel strike stands in for.
+    let mut bytes = make_sbix_font();
+    let second_png = bytes.windows(4).enumerate().filter(|&(_, tag)| tag == b"png ").nth(1);
+    let second_png = second_png.unwrap().0;
+    bytes[second_png..(second_png + 4)].copy_from_slice(b"tiff");
+
+    let font = FontData::new(&bytes);
+    let maxp = font.maxp_table().unwrap();
+    let sbix = font.sbix_table(&maxp).unwrap();
+    let limits = Limits::default();
+    for glyph_id in 1..3 {
+        let glyph = BitmapGlyph::from_sbix(&sbix, glyph_id, 20.0, &limits).unwrap().unwrap();
+        assert_eq!(glyph.pixels_per_em, 16.0);
+        assert_eq!(glyph.image.pixel(0, 0), RED);
+    }
+}
+
+#[test]
+fn bitmap_glyph_limits() {
+    let bytes = make_cbdt_font();
+    let font = FontData::new(&bytes);
+    let (cblc, cbdt) = (font.cblc_table().unwrap(), font.cbdt_table().unwrap());
+    let limits = Limits {
+        max_image_size: 1,
+        ..Limits::default()
+    };
+    assert_eq!(BitmapGlyph::from_cbdt(&cblc, &cbdt, 5, 20.0, &limits),
+               Err(Error::LimitExceeded(Limit::ImageSize)));
+
+    // Scaling is checked before the new image is allocated.
+    let limits = Limits {
+        max_image_size: 4,
+        ..Limits::default()
+    };
+    let glyph = BitmapGlyph::from_cbdt(&cblc, &cbdt, 5, 20.0, &limits).unwrap().unwrap();
+    assert!(glyph.scaled_to(40.0, &limits).is_ok());
+    assert_eq!(glyph.scaled_to(50.0, &limits), Err(Error::LimitExceeded(Limit::ImageSize)));
+    assert_eq!(glyph.scaled_to(1.0e30, &limits), Err(Error::LimitExceeded(Limit::ImageSize)));
+}
+
+#[cfg(feature = "gpu")]
+#[test]
+fn batch_bitmap_glyphs() {
+    let bytes = make_cbdt_font();
+    let font = FontData::new(&bytes);
+    let (cblc, cbdt) = (font.cblc_table().unwrap(), font.cbdt_table().unwrap());
+    let limits = Limits::default();
+    let glyph = BitmapGlyph::from_cbdt(&cblc, &cbdt, 5, 20.0, &limits).unwrap().unwrap();
+
+    let mut batch_builder = BatchBuilder::new(64, 16);
+    let mut color_atlas_pixels = RgbaImage::new(64, 32);
+    let first = batch_builder.add_bitmap_glyph(&glyph, 20.0, &mut color_atlas_pixels).unwrap();
+    let second = batch_builder.add_bitmap_glyph(&glyph, 40.0, &mut color_atlas_pixels).unwrap();
+    assert_eq!(first, Point2D::new(0, 0));
+    assert_eq!(color_atlas_pixels.pixel(1, 1), RED);
+    assert_eq!(color_atlas_pixels.pixel(second.x + 3, second.y + 3), RED);
+    assert_eq!(color_atlas_pixels.pixel(second.x + 4, second.y), [0, 0, 0, 0]);
+}
+
+fn index_subtable(index_format: u16, image_format: u16, image_data_offset: u32, body: &[u8])
+                  -> Vec<u8> {
+    let mut subtable = vec![];
+    subtable.write_u16::<BigEndian>(index_format).unwrap();
+    subtable.write_u16::<BigEndian>(image_format).unwrap();
+    subtable.write_u32::<BigEndian>(image_data_offset).unwrap();
+    subtable.extend_from_slice(body);
+    subtable
+}
+
+// Writes an index subtable array for one glyph per subtable, followed by the subtables.
+fn write_index_subtables(eblc: &mut Vec<u8>, subtables: &[(u16, Vec<u8>)]) {
+    let mut additional_offset = subtables.len() * 8;
+    for &(glyph_id, ref subtable) in subtables {
+        eblc.write_u16::<BigEndian>(glyph_id).unwrap();
+        eblc.write_u16::<BigEndian>(glyph_id).unwrap();
+        eblc.write_u32::<BigEndian>(additional_offset as u32).unwrap();
+        additional_offset += subtable.len()
+    }
+    for &(_, ref subtable) in subtables {
+        eblc.extend_from_slice(subtable)
+    }
+}
+
+// A 12 pixel per em strike at 1 bit per pixel, with glyphs 1 to 5 in image formats 1, 2, 5, 8,
+// and 9 and index formats 1 to 5 respectively; an 8 pixel per em strike at 2 bits per pixel,
+// with glyphs 1 and 2 in image formats 6 and 7; and an `EBSC` table that draws 16 pixels per em
+// by scaling the second strike.
+//
+// Glyphs 1 to 3 of the first strike are the same 3x2 checkerboard. Glyph 4 puts glyphs 1 and 2
+// side by side, and glyph 5 is glyph 1 shifted right by one pixel and clipped.
+fn make_ebdt_font() -> Vec<u8> {
+    let mut ebdt = vec![0, 2, 0, 0];
+    let mut offsets = vec![];
+    let glyphs: [&[u8]; 7] = [
+        &[2, 3, 0, 2, 4, 0b10100000, 0b01000000],
+        &[2, 3, 0, 2, 4, 0b10101000],
+        &[0b10101000],
+        &[2, 6, 0, 2, 7, 0, 0, 2, 0, 1, 0, 0, 0, 2, 3, 0],
+        &[2, 3, 0, 2, 4, 0, 0, 0, 0, 1, 0, 1, 1, 0],
+        &[1, 2, 0, 1, 2, 0, 0, 0, 0b11010000],
+        &[1, 3, 0, 1, 3, 0, 0, 0, 0b01101100],
+    ];
+    for glyph in &glyphs {
+        offsets.push(ebdt.len() as u32);
+        ebdt.extend_from_slice(glyph)
+    }
+    let length = |index: usize| glyphs[index].len() as u32;
+
+    let big_metrics = [2, 3, 0, 2, 4, 0, 0, 0];
+    let mut first_strike = vec![];
+    let mut body = vec![];
+    body.write_u32::<BigEndian>(0).unwrap();
+    body.write_u32::<BigEndian>(length(0)).unwrap();
+    first_strike.push((1, index_subtable(1, 1, offsets[0], &body)));
+    let mut body = vec![];
+    body.write_u16::<BigEndian>(0).unwrap();
+    body.write_u16::<BigEndian>(length(1) as u16).unwrap();
+    first_strike.push((2, index_subtable(3, 2, offsets[1], &body)));
+    let mut body = vec![];
+    body.write_u32::<BigEndian>(length(2)).unwrap();
+    body.extend_from_slice(&big_metrics);
+    first_strike.push((3, index_subtable(2, 5, offsets[2], &body)));
+    let mut body = vec![];
+    body.write_u32::<BigEndian>(1).unwrap();
+    for &(glyph_id, offset) in &[(4, 0), (0, length(3) as u16)] {
+        body.write_u16::<BigEndian>(glyph_id).unwrap();
+        body.write_u16::<BigEndian>(offset).unwrap();
+    }
+    first_strike.push((4, index_subtable(4, 8, offsets[3], &body)));
+    let mut body = vec![];
+    body.write_u32::<BigEndian>(length(4)).unwrap();
+    body.extend_from_slice(&big_metrics);
+    body.write_u32::<BigEndian>(1).unwrap();
+    body.write_u16::<BigEndian>(5).unwrap();
+    first_strike.push((5, index_subtable(5, 9, offsets[4], &body)));
+
+    let mut second_strike = vec![];
+    for &(glyph_id, index, image_format) in &[(1, 5, 6), (2, 6, 7)] {
+        let mut body = vec![];
+        body.write_u32::<BigEndian>(0).unwrap();
+        body.write_u32::<BigEndian>(length(index)).unwrap();
+        second_strike.push((glyph_id, index_subtable(1, image_format, offsets[index], &body)));
+    }
+
+    let mut eblc = vec![0, 2, 0, 0, 0, 0, 0, 2];
+    let first_array_offset = 8 + 2 * 48;
+    let first_array_size = first_strike.len() * 8 +
+        first_strike.iter().map(|&(_, ref subtable)| subtable.len()).sum::<usize>();
+    write_bitmap_size(&mut eblc, first_array_offset, first_strike.len() as u32, (1, 5), 12);
+    eblc[8 + 46] = 1;
+    write_bitmap_size(&mut eblc,
+                      first_array_offset + first_array_size as u32,
+                      second_strike.len() as u32,
+                      (1, 2),
+                      8);
+    eblc[8 + 48 + 46] = 2;
+    write_index_subtables(&mut eblc, &first_strike);
+    write_index_subtables(&mut eblc, &second_strike);
+
+    let mut ebsc = vec![0, 2, 0, 0, 0, 0, 0, 1];
+    ebsc.extend_from_slice(&[0; 24]);
+    ebsc.extend_from_slice(&[16, 16, 8, 8]);
+
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"EBDT"), ebdt);
+    font_builder.add_table(Tag::new(b"EBLC"), eblc);
+    font_builder.add_table(Tag::new(b"EBSC"), ebsc);
+    font_builder.build().unwrap()
+}
+
+fn embedded_bitmaps<'a>(font: &'a FontData<'a>) -> EmbeddedBitmaps<'a> {
+    EmbeddedBitmaps::new(font.eblc_table().unwrap(),
+                         font.ebdt_table().unwrap(),
+                         Some(&font.ebsc_table().unwrap())).unwrap()
+}
+
+#[test]
+fn ebdt_glyphs() {
+    let bytes = make_ebdt_font();
+    let font = FontData::new(&bytes);
+    let bitmaps = embedded_bitmaps(&font);
+
+    let checkerboard = vec![255, 0, 255, 0, 255, 0];
+    for glyph_id in 1..4 {
+        let glyph = bitmaps.glyph(glyph_id, 12.0).unwrap().unwrap();
+        assert_eq!((glyph.image.width, glyph.image.height), (3, 2));
+        assert_eq!(glyph.image.pixels, checkerboard);
+        assert_eq!(glyph.origin, Point2D::new(0, 2));
+    }
+
+    let composite = bitmaps.glyph(4, 12.0).unwrap().unwrap();
+    assert_eq!(composite.image.pixels,
+               vec![255, 0, 255, 255, 0, 255, 0, 255, 0, 0, 255, 0]);
+    let shifted = bitmaps.glyph(5, 12.0).unwrap().unwrap();
+    assert_eq!(shifted.image.pixels, vec![0, 255, 0, 0, 0, 255]);
+
+    assert_eq!(bitmaps.glyph(1, 8.0).unwrap().unwrap().image.pixels, vec![255, 85]);
+    assert_eq!(bitmaps.glyph(2, 8.0).unwrap().unwrap().image.pixels, vec![85, 170, 255]);
+    assert_eq!(bitmaps.glyph(6, 12.0).unwrap(), None);
+}
+
+#[test]
+fn ebdt_exact_sizes_and_scaling() {
+    let bytes = make_ebdt_font();
+    let font = FontData::new(&bytes);
+    let bitmaps = embedded_bitmaps(&font);
+    assert!(bitmaps.has_strike(12.0));
+    assert!(bitmaps.has_strike(16.0));
+    assert!(!bitmaps.has_strike(10.0));
+    assert!(!bitmaps.has_strike(12.5));
+    assert_eq!(bitmaps.glyph(1, 10.0).unwrap(), None);
+    assert_eq!(bitmaps.glyph(1, 12.5).unwrap(), None);
+
+    // `EBSC` draws 16 pixels per em by doubling the 8 pixel per em strike.
+    let scaled = bitmaps.glyph(1, 16.0).unwrap().unwrap();
+    assert_eq!((scaled.image.width, scaled.image.height), (4, 2));
+    assert_eq!(scaled.image.pixels, vec![255, 255, 85, 85, 255, 255, 85, 85]);
+    assert_eq!(scaled.origin, Point2D::new(0, 2));
+}
+
+#[cfg(feature = "gpu")]
+#[test]
+fn batch_embedded_bitmaps() {
+    let bytes = make_ebdt_font();
+    let font = FontData::new(&bytes);
+    let bitmaps = embedded_bitmaps(&font);
+
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let outline_font = FontData::new(unsafe { file.as_slice() });
+    let head = outline_font.head_table().unwrap();
+    let loca = outline_font.loca_table(&head).unwrap();
+    let glyf = outline_font.glyf_table().unwrap();
+    let mut glyph_buffer_builder = GlyphBufferBuilder::new();
+    glyph_buffer_builder.add_glyph(36, &head, &loca, &glyf).unwrap();
+
+    let mut batch_builder = BatchBuilder::new(64, 16);
+    assert_eq!(batch_builder.add_glyph_or_bitmap(&glyph_buffer_builder, 0, 1, 12.0, &bitmaps)
+                            .unwrap(),
+               None);
+    assert_eq!(batch_builder.images.len(), 1);
+    assert!(batch_builder.bitmaps.is_empty());
+
+    // The bitmap at the exact size goes to the draw step with its place in the atlas.
+    batch_builder.use_embedded_bitmaps = true;
+    let origin = batch_builder.add_glyph_or_bitmap(&glyph_buffer_builder, 0, 1, 12.0, &bitmaps)
+                              .unwrap()
+                              .unwrap();
+    assert_eq!(batch_builder.images.len(), 1);
+    let strike_image = bitmaps.glyph(1, 12.0).unwrap().unwrap().image;
+    assert_eq!(batch_builder.bitmaps.len(), 1);
+    let bitmap = batch_builder.bitmaps[0];
+    assert_eq!((bitmap.atlas_x, bitmap.atlas_y), (origin.x, origin.y));
+    assert_eq!((bitmap.width, bitmap.height), (strike_image.width, strike_image.height));
+    let start = bitmap.start_pixel as usize;
+    let end = start + (bitmap.width * bitmap.height) as usize;
+    assert_eq!(&batch_builder.bitmap_pixels[start..end], &strike_image.pixels[..]);
+    assert_eq!(batch_builder.bitmap_pixels[start], 255);
+    assert_eq!(batch_builder.bitmap_pixels[start + 1], 0);
+
+    // Without a strike at this size, the outline is rasterized.
+    assert_eq!(batch_builder.add_glyph_or_bitmap(&glyph_buffer_builder, 0, 1, 13.0, &bitmaps)
+                            .unwrap(),
+               None);
+    assert_eq!(batch_builder.images.len(), 2);
+    assert_eq!(batch_builder.bitmaps.len(), 1);
+}
+
+#[cfg(feature = "gpu")]
+#[test]
+fn bitmap_descriptors_match_the_blit_kernel() {
+    // The `uint2` fields of the struct in `blit.cl` align it to 8 bytes.
+    assert_eq!(mem::size_of::<BitmapDescriptor>(), 24);
+}
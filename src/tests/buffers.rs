@@ -2,8 +2,8 @@
  * http://creativecommons.org/publicdomain/zero/1.0/ */
 
 use batch::GlyphRange;
-use buffers::GlyphBuffers;
 use charmap::CodepointRange;
+use glyph_buffer::GlyphBufferBuilder;
 use memmap::{Mmap, Protection};
 use otf::FontData;
 use test::Bencher;
@@ -24,9 +24,9 @@ fn bench_add_glyphs(bencher: &mut Bencher) {
                                .expect("Couldn't find glyph ranges");
 
         bencher.iter(|| {
-            let mut buffers = GlyphBuffers::new();
+            let mut buffers = GlyphBufferBuilder::new();
             for glyph_id in glyph_ranges.iter().flat_map(GlyphRange::iter) {
-                buffers.add_glyph(glyph_id as u32, &loca, &glyf).unwrap()
+                buffers.add_glyph(glyph_id as u32, &head, &loca, &glyf).unwrap()
             }
         });
     }
@@ -0,0 +1,115 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use byteorder::{BigEndian, ByteOrder};
+use error::Error;
+use memmap::{Mmap, Protection};
+use otf::checksum;
+use otf::collection::FontCollection;
+use otf::{FontData, Tag};
+use tests::TEST_FONT_PATH;
+use tests::collection::make_collection;
+
+fn table_offset(font: &[u8], tag: &[u8; 4]) -> usize {
+    let record = FontData::new(font).table_records().unwrap().find(|record| {
+        record.tag == Tag::new(tag)
+    }).unwrap();
+    record.offset as usize
+}
+
+#[test]
+fn valid_checksums() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let report = FontData::new(unsafe { file.as_slice() }).verify_checksums().unwrap();
+    assert!(report.is_ok());
+    assert!(report.mismatched_tables.is_empty());
+    assert_eq!(report.checksum_adjustment, None);
+}
+
+#[test]
+fn corrupted_table() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let mut bytes = unsafe { file.as_slice() }.to_vec();
+    let word_offset = table_offset(&bytes, b"glyf") + 100;
+    let word = BigEndian::read_u32(&bytes[word_offset..]);
+    BigEndian::write_u32(&mut bytes[word_offset..], word.wrapping_add(1));
+
+    let report = FontData::new(&bytes).verify_checksums().unwrap();
+    assert!(!report.is_ok());
+    assert_eq!(report.mismatched_tables.len(), 1);
+    let mismatch = report.mismatched_tables[0];
+    assert_eq!(mismatch.tag, Tag::new(b"glyf"));
+    assert_eq!(mismatch.stored, 0x59b58b3f);
+    assert_eq!(mismatch.computed.wrapping_sub(mismatch.stored), 1);
+
+    // The whole-font checksum catches it too.
+    let adjustment = report.checksum_adjustment.unwrap();
+    assert_eq!(adjustment.tag, Tag::new(b"head"));
+    assert_eq!(adjustment.stored, 0x6673349e);
+    assert_eq!(adjustment.stored.wrapping_sub(adjustment.computed), 1);
+}
+
+#[test]
+fn corrupted_checksum_adjustment() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let mut bytes = unsafe { file.as_slice() }.to_vec();
+    let adjustment_offset = table_offset(&bytes, b"head") + 8;
+    BigEndian::write_u32(&mut bytes[adjustment_offset..], 0x12345678);
+
+    // `head`'s own checksum skips the adjustment, so only the adjustment is wrong.
+    let report = FontData::new(&bytes).verify_checksums().unwrap();
+    assert!(report.mismatched_tables.is_empty());
+    let adjustment = report.checksum_adjustment.unwrap();
+    assert_eq!((adjustment.stored, adjustment.computed), (0x12345678, 0x6673349e));
+}
+
+#[test]
+fn checksums_of_collection_faces() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let mut collection_bytes = make_collection(unsafe { file.as_slice() });
+
+    // Faces of collections have no meaningful `checkSumAdjustment`, but their tables still do.
+    let face = FontCollection::new(&collection_bytes).unwrap().face(1).unwrap();
+    assert!(face.verify_checksums().unwrap().is_ok());
+
+    let cmap_offset = {
+        let face = FontCollection::new(&collection_bytes).unwrap().face(1).unwrap();
+        let cmap = face.table_records().unwrap().find(|record| record.tag == Tag::new(b"cmap"));
+        cmap.unwrap().offset as usize
+    };
+    collection_bytes[cmap_offset] ^= 0xff;
+    let face = FontCollection::new(&collection_bytes).unwrap().face(1).unwrap();
+    let report = face.verify_checksums().unwrap();
+    assert_eq!(report.checksum_adjustment, None);
+    assert_eq!(report.mismatched_tables.iter().map(|mismatch| mismatch.tag).collect::<Vec<_>>(),
+               [Tag::new(b"cmap")]);
+}
+
+#[test]
+fn truncated_table_fails_verification() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let bytes = unsafe { file.as_slice() };
+    let truncated = &bytes[..(bytes.len() - 16)];
+    match FontData::new(truncated).verify_checksums() {
+        Err(Error::Truncated { .. }) => {}
+        result => panic!("expected a truncation error, got {:?}", result),
+    }
+}
+
+#[test]
+fn fix_checksum_adjustment() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let mut bytes = unsafe { file.as_slice() }.to_vec();
+    let head_offset = table_offset(&bytes, b"head");
+    BigEndian::write_u32(&mut bytes[(head_offset + 8)..], 0x12345678);
+    checksum::fix_checksum_adjustment(&mut bytes).unwrap();
+    assert!(FontData::new(&bytes).verify_checksums().unwrap().is_ok());
+
+    // Malformed fonts are errors, not panics.
+    assert!(checksum::fix_checksum_adjustment(&mut [0; 3]).is_err());
+    assert!(checksum::fix_checksum_adjustment(&mut bytes[..20].to_vec()).is_err());
+    match checksum::fix_checksum_adjustment(&mut bytes[..(head_offset + 20)]) {
+        Err(Error::Truncated { .. }) => {}
+        result => panic!("expected a truncation error, got {:?}", result),
+    }
+}
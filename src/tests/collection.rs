@@ -0,0 +1,108 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use memmap::{Mmap, Protection};
+use otf::{FontData, SFNT_VERSION_OTTO, Tag};
+use otf::collection::{FontCollection, TTCF};
+use otf::writer::{FontBuilder, HeadBuilder, HheaBuilder};
+use tests::{TEST_FONT_PATH, glyph_points};
+
+// Wraps a bare sfnt in a two-face collection. Both faces get their own table directory, but the
+// directories point at the same table data.
+pub fn make_collection(font: &[u8]) -> Vec<u8> {
+    let num_tables = BigEndian::read_u16(&font[4..6]) as usize;
+    let directory_size = 12 + num_tables * 16;
+    let header_size = 12 + 2 * 4;
+    let table_data_offset = header_size + directory_size * 2;
+
+    let mut collection = vec![];
+    collection.write_u32::<BigEndian>(TTCF).unwrap();
+    collection.write_u16::<BigEndian>(1).unwrap();
+    collection.write_u16::<BigEndian>(0).unwrap();
+    collection.write_u32::<BigEndian>(2).unwrap();
+    collection.write_u32::<BigEndian>(header_size as u32).unwrap();
+    collection.write_u32::<BigEndian>((header_size + directory_size) as u32).unwrap();
+
+    for _ in 0..2 {
+        let start = collection.len();
+        collection.extend_from_slice(&font[0..directory_size]);
+        for table_index in 0..num_tables {
+            let offset_position = start + 12 + table_index * 16 + 8;
+            let offset = BigEndian::read_u32(&collection[offset_position..]);
+            BigEndian::write_u32(&mut collection[offset_position..],
+                                 offset + table_data_offset as u32);
+        }
+    }
+
+    collection.extend_from_slice(font);
+    collection
+}
+
+#[test]
+fn collection_faces_match_original_font() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let bytes = unsafe { file.as_slice() };
+    let collection_bytes = make_collection(bytes);
+
+    let font = FontData::new(bytes);
+    let collection = FontCollection::new(&collection_bytes).unwrap();
+    assert_eq!(collection.face_count(), 2);
+    assert!(collection.dsig_table.is_none());
+    assert!(collection.face(2).is_err());
+
+    for face_index in 0..collection.face_count() {
+        let face = collection.face(face_index).unwrap();
+        for glyph_id in 1..32 {
+            assert!(glyph_points(&face, glyph_id) == glyph_points(&font, glyph_id));
+        }
+    }
+}
+
+#[test]
+fn bare_sfnt_is_not_a_collection() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    assert!(FontCollection::new(unsafe { file.as_slice() }).is_err());
+}
+
+#[test]
+fn collection_faces_with_cff_outlines() {
+    // The faces of `.otc` files start with `OTTO`, like bare OpenType fonts with CFF outlines.
+    let mut hhea_builder = HheaBuilder::new(800, -200, 0);
+    hhea_builder.add_metrics(500, 0);
+    let (hhea, hmtx) = hhea_builder.build();
+    let mut font_builder = FontBuilder::new();
+    font_builder.sfnt_version = SFNT_VERSION_OTTO;
+    font_builder.add_table(Tag::new(b"head"), HeadBuilder::new().build());
+    font_builder.add_table(Tag::new(b"hhea"), hhea);
+    font_builder.add_table(Tag::new(b"hmtx"), hmtx);
+    let collection_bytes = make_collection(&font_builder.build().unwrap());
+
+    let collection = FontCollection::new(&collection_bytes).unwrap();
+    for face_index in 0..collection.face_count() {
+        let face = collection.face(face_index).unwrap();
+        assert_eq!(face.head_table().unwrap().units_per_em, 1000);
+        let hhea = face.hhea_table().unwrap();
+        assert_eq!(face.hmtx_table(&hhea).unwrap().metrics(0).unwrap().advance, 500);
+    }
+}
+
+#[test]
+fn collection_headers_with_out_of_range_counts() {
+    let mut header = vec![];
+    header.write_u32::<BigEndian>(TTCF).unwrap();
+    header.write_u16::<BigEndian>(2).unwrap();
+    header.write_u16::<BigEndian>(0).unwrap();
+
+    let mut too_many_fonts = header.clone();
+    too_many_fonts.write_u32::<BigEndian>(0xffffffff).unwrap();
+    assert!(FontCollection::new(&too_many_fonts).is_err());
+
+    // A digital signature that runs off the end of the file.
+    let mut dsig_past_the_end = header;
+    dsig_past_the_end.write_u32::<BigEndian>(0).unwrap();
+    dsig_past_the_end.extend_from_slice(b"DSIG");
+    dsig_past_the_end.write_u32::<BigEndian>(0xffffffff).unwrap();
+    dsig_past_the_end.write_u32::<BigEndian>(0xffffffff).unwrap();
+    assert!(FontCollection::new(&dsig_past_the_end).is_err());
+}
@@ -0,0 +1,187 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+#[cfg(feature = "gpu")]
+use atlas::Atlas;
+#[cfg(feature = "gpu")]
+use batch::{BatchBuilder, ColorGlyphLayer, ColorImageDescriptor, ColorLayerDescriptor};
+use byteorder::{BigEndian, WriteBytesExt};
+#[cfg(feature = "gpu")]
+use euclid::Size2D;
+#[cfg(feature = "gpu")]
+use glyph_buffer::GlyphBufferBuilder;
+#[cfg(feature = "gpu")]
+use memmap::{Mmap, Protection};
+use otf::{FontData, Tag};
+use otf::colr::{ColorLayer, FOREGROUND_PALETTE_INDEX};
+use otf::cpal::{self, Color};
+use otf::writer::FontBuilder;
+#[cfg(feature = "gpu")]
+use std::mem;
+#[cfg(feature = "gpu")]
+use tests::TEST_FONT_PATH;
+
+// Glyph 10 has a red layer under a foreground layer; glyph 20 has a single green layer.
+fn make_colr() -> Vec<u8> {
+    let mut colr = vec![];
+    colr.write_u16::<BigEndian>(0).unwrap();
+    colr.write_u16::<BigEndian>(2).unwrap();
+    colr.write_u32::<BigEndian>(14).unwrap();
+    colr.write_u32::<BigEndian>(14 + 2 * 6).unwrap();
+    colr.write_u16::<BigEndian>(3).unwrap();
+    for &(glyph_id, first_layer_index, num_layers) in &[(10, 0, 2), (20, 2, 1)] {
+        colr.write_u16::<BigEndian>(glyph_id).unwrap();
+        colr.write_u16::<BigEndian>(first_layer_index).unwrap();
+        colr.write_u16::<BigEndian>(num_layers).unwrap();
+    }
+    for &(glyph_id, palette_index) in &[(11, 0), (12, FOREGROUND_PALETTE_INDEX), (21, 1)] {
+        colr.write_u16::<BigEndian>(glyph_id).unwrap();
+        colr.write_u16::<BigEndian>(palette_index).unwrap();
+    }
+    colr
+}
+
+// Two palettes of two colors each. The second is marked as usable on dark backgrounds.
+fn make_cpal() -> Vec<u8> {
+    let mut cpal = vec![];
+    cpal.write_u16::<BigEndian>(1).unwrap();
+    cpal.write_u16::<BigEndian>(2).unwrap();
+    cpal.write_u16::<BigEndian>(2).unwrap();
+    cpal.write_u16::<BigEndian>(4).unwrap();
+    let header_size = 12 + 2 * 2 + 4 * 3;
+    cpal.write_u32::<BigEndian>(header_size).unwrap();
+    cpal.write_u16::<BigEndian>(0).unwrap();
+    cpal.write_u16::<BigEndian>(2).unwrap();
+    cpal.write_u32::<BigEndian>(header_size + 4 * 4).unwrap();
+    cpal.write_u32::<BigEndian>(0).unwrap();
+    cpal.write_u32::<BigEndian>(0).unwrap();
+
+    // BGRA color records.
+    for &color in &[[0, 0, 255, 255], [0, 255, 0, 128], [255, 255, 255, 255], [255, 0, 0, 255]] {
+        cpal.extend_from_slice(&color)
+    }
+
+    cpal.write_u32::<BigEndian>(0).unwrap();
+    cpal.write_u32::<BigEndian>(cpal::USABLE_WITH_DARK_BACKGROUND.bits()).unwrap();
+    cpal
+}
+
+fn make_font() -> Vec<u8> {
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"COLR"), make_colr());
+    font_builder.add_table(Tag::new(b"CPAL"), make_cpal());
+    font_builder.build().unwrap()
+}
+
+#[test]
+fn colr_layers() {
+    let bytes = make_font();
+    let font = FontData::new(&bytes);
+    let colr = font.colr_table().unwrap();
+    assert_eq!(colr.base_glyph_ids().unwrap(), vec![10, 20]);
+    assert_eq!(colr.layers(10).unwrap(), Some(vec![
+        ColorLayer { glyph_id: 11, palette_index: 0 },
+        ColorLayer { glyph_id: 12, palette_index: FOREGROUND_PALETTE_INDEX },
+    ]));
+    assert_eq!(colr.layers(20).unwrap(),
+               Some(vec![ColorLayer { glyph_id: 21, palette_index: 1 }]));
+    assert_eq!(colr.layers(15).unwrap(), None);
+}
+
+#[test]
+fn cpal_palettes_and_selection() {
+    let bytes = make_font();
+    let font = FontData::new(&bytes);
+    let cpal = font.cpal_table().unwrap();
+    assert_eq!(cpal.palette_count(), 2);
+    assert_eq!(cpal.palette_entry_count(), 2);
+    assert_eq!(cpal.palette(0).unwrap(),
+               vec![Color::new(255, 0, 0, 255), Color::new(0, 255, 0, 128)]);
+    assert_eq!(cpal.palette(1).unwrap(),
+               vec![Color::new(255, 255, 255, 255), Color::new(0, 0, 255, 255)]);
+    assert!(cpal.palette(2).is_err());
+
+    assert!(cpal.palette_type(0).unwrap().is_empty());
+    assert_eq!(cpal.palette_type(1).unwrap(), cpal::USABLE_WITH_DARK_BACKGROUND);
+    assert_eq!(cpal.select_palette(cpal::USABLE_WITH_DARK_BACKGROUND).unwrap(), 1);
+    assert_eq!(cpal.select_palette(cpal::USABLE_WITH_LIGHT_BACKGROUND).unwrap(), 0);
+
+    let foreground = Color::new(1, 2, 3, 4);
+    let palette = cpal.palette(0).unwrap();
+    let layers = font.colr_table().unwrap().layers(10).unwrap().unwrap();
+    let colors: Vec<_> = layers.iter().map(|layer| layer.color(&palette, foreground).unwrap())
+                                      .collect();
+    assert_eq!(colors, vec![Color::new(255, 0, 0, 255), foreground]);
+}
+
+#[cfg(feature = "gpu")]
+#[test]
+fn batch_color_glyph_layers() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let head = font.head_table().unwrap();
+    let loca = font.loca_table(&head).unwrap();
+    let glyf = font.glyf_table().unwrap();
+
+    let mut glyph_buffer_builder = GlyphBufferBuilder::new();
+    for &glyph_id in &[36, 37] {
+        glyph_buffer_builder.add_glyph(glyph_id, &head, &loca, &glyf).unwrap();
+    }
+
+    let mut batch_builder = BatchBuilder::new(512, 64);
+    let layers = [
+        ColorGlyphLayer { glyph_index: 0, color: Color::new(255, 0, 0, 255) },
+        ColorGlyphLayer { glyph_index: 1, color: Color::new(0, 0, 255, 128) },
+    ];
+    batch_builder.add_color_glyph(&glyph_buffer_builder, &layers, 24.0).unwrap();
+    assert_eq!(batch_builder.color_images.len(), 1);
+    assert_eq!(batch_builder.color_layers.len(), 2);
+    assert_eq!(batch_builder.images.len(), 2);
+
+    assert!(batch_builder.add_color_glyph(&glyph_buffer_builder, &[], 24.0).is_err());
+}
+
+#[cfg(feature = "gpu")]
+#[test]
+fn color_descriptors_match_the_composite_kernel() {
+    // The `uint2` fields of the structs in `composite.cl` align them to 8 bytes.
+    assert_eq!(mem::size_of::<ColorImageDescriptor>(), 24);
+    assert_eq!(mem::size_of::<ColorLayerDescriptor>(), 32);
+}
+
+#[cfg(feature = "gpu")]
+#[test]
+fn failed_color_glyph_adds_nothing() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let head = font.head_table().unwrap();
+    let loca = font.loca_table(&head).unwrap();
+    let glyf = font.glyf_table().unwrap();
+
+    let mut glyph_buffer_builder = GlyphBufferBuilder::new();
+    for &glyph_id in &[36, 37] {
+        glyph_buffer_builder.add_glyph(glyph_id, &head, &loca, &glyf).unwrap();
+    }
+
+    // The layers' coverage fits, but the composited glyph doesn't.
+    let mut batch_builder = BatchBuilder::new(512, 64);
+    batch_builder.add_glyph(&glyph_buffer_builder, 0, 24.0).unwrap();
+    batch_builder.color_atlas = Atlas::new(4, 64);
+    let layers = [
+        ColorGlyphLayer { glyph_index: 0, color: Color::new(255, 0, 0, 255) },
+        ColorGlyphLayer { glyph_index: 1, color: Color::new(0, 0, 255, 128) },
+    ];
+    let (indices, point_count) = (batch_builder.indices.clone(), batch_builder.point_count);
+    assert!(batch_builder.add_color_glyph(&glyph_buffer_builder, &layers, 24.0).is_err());
+    assert_eq!(batch_builder.indices, indices);
+    assert_eq!(batch_builder.images.len(), 1);
+    assert_eq!(batch_builder.point_count, point_count);
+    assert!(batch_builder.color_images.is_empty());
+    assert!(batch_builder.color_layers.is_empty());
+
+    // The coverage atlas is as it was, so the next glyph goes where the layers would have.
+    let origin = batch_builder.atlas.place(&Size2D::new(1, 1)).unwrap();
+    let mut fresh_batch = BatchBuilder::new(512, 64);
+    fresh_batch.add_glyph(&glyph_buffer_builder, 0, 24.0).unwrap();
+    assert_eq!(origin, fresh_batch.atlas.place(&Size2D::new(1, 1)).unwrap());
+}
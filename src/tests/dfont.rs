@@ -0,0 +1,126 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use byteorder::{BigEndian, WriteBytesExt};
+use memmap::{Mmap, Protection};
+use otf::FontData;
+use otf::dfont::{FOND, ResourceFork, SFNT};
+use otf::loader::{self, LoadedFont};
+use tests::{TEST_FONT_PATH, glyph_points};
+
+static FAMILY_NAME: &'static [u8] = b"Nimbus Sans L";
+static FACE_NAME: &'static [u8] = b"NimbusSanL-Regu";
+
+const FAMILY_ID: i16 = 1234;
+const FOND_ID: i16 = 128;
+const SFNT_ID: i16 = 256;
+
+// Builds a resource fork containing the font as an `sfnt` resource, and a `FOND` resource that
+// associates it, and a bitmap strike that doesn't exist, with a family.
+fn make_dfont(font: &[u8]) -> Vec<u8> {
+    let mut fond = vec![0; 52];
+    fond[2] = (FAMILY_ID >> 8) as u8;
+    fond[3] = FAMILY_ID as u8;
+    fond.write_u16::<BigEndian>(1).unwrap();
+    for &(size, style, id) in &[(12, 0, 300), (0, 0, SFNT_ID)] {
+        fond.write_i16::<BigEndian>(size).unwrap();
+        fond.write_i16::<BigEndian>(style).unwrap();
+        fond.write_i16::<BigEndian>(id).unwrap();
+    }
+
+    let mut data = vec![];
+    data.write_u32::<BigEndian>(fond.len() as u32).unwrap();
+    data.extend_from_slice(&fond);
+    let sfnt_data_offset = data.len();
+    data.write_u32::<BigEndian>(font.len() as u32).unwrap();
+    data.extend_from_slice(font);
+
+    let mut map = vec![0; 24];
+    map.write_u16::<BigEndian>(28).unwrap();
+    map.write_u16::<BigEndian>(28 + 2 + 8 * 2 + 12 * 2).unwrap();
+
+    // Type list.
+    map.write_u16::<BigEndian>(1).unwrap();
+    map.write_u32::<BigEndian>(FOND).unwrap();
+    map.write_u16::<BigEndian>(0).unwrap();
+    map.write_u16::<BigEndian>(2 + 8 * 2).unwrap();
+    map.write_u32::<BigEndian>(SFNT).unwrap();
+    map.write_u16::<BigEndian>(0).unwrap();
+    map.write_u16::<BigEndian>(2 + 8 * 2 + 12).unwrap();
+
+    // Reference lists.
+    for &(id, name_offset, data_offset) in &[(FOND_ID, 0, 0),
+                                             (SFNT_ID, 1 + FAMILY_NAME.len(), sfnt_data_offset)] {
+        map.write_i16::<BigEndian>(id).unwrap();
+        map.write_u16::<BigEndian>(name_offset as u16).unwrap();
+        map.write_u32::<BigEndian>(data_offset as u32).unwrap();
+        map.write_u32::<BigEndian>(0).unwrap();
+    }
+
+    // Name list.
+    for name in &[FAMILY_NAME, FACE_NAME] {
+        map.push(name.len() as u8);
+        map.extend_from_slice(name);
+    }
+
+    let mut dfont = vec![];
+    dfont.write_u32::<BigEndian>(256).unwrap();
+    dfont.write_u32::<BigEndian>((256 + data.len()) as u32).unwrap();
+    dfont.write_u32::<BigEndian>(data.len() as u32).unwrap();
+    dfont.write_u32::<BigEndian>(map.len() as u32).unwrap();
+    dfont.resize(256, 0);
+    dfont.extend_from_slice(&data);
+    dfont.extend_from_slice(&map);
+    dfont
+}
+
+#[test]
+fn dfont_faces() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let bytes = unsafe { file.as_slice() };
+    let dfont = make_dfont(bytes);
+
+    let fork = ResourceFork::new(&dfont).unwrap();
+    assert_eq!(fork.resources(FOND).unwrap().len(), 1);
+    assert!(fork.resources(((b'N' as u32) << 24) | ((b'F' as u32) << 16) |
+                           ((b'N' as u32) << 8) | (b'T' as u32)).unwrap().is_empty());
+
+    let faces = fork.faces().unwrap();
+    assert_eq!(faces.len(), 1);
+    let face = &faces[0];
+    assert_eq!(face.resource_id, SFNT_ID);
+    assert_eq!(face.name, Some(FACE_NAME));
+    let family = face.family.unwrap();
+    assert_eq!(family.family_id, FAMILY_ID);
+    assert_eq!(family.family_name, Some(FAMILY_NAME));
+    assert_eq!(family.style, 0);
+
+    let original = FontData::new(bytes);
+    for glyph_id in 1..32 {
+        assert!(glyph_points(&face.font, glyph_id) == glyph_points(&original, glyph_id));
+    }
+}
+
+#[test]
+fn load_dfont() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let dfont = make_dfont(unsafe { file.as_slice() });
+    let font = loader::load_font(&dfont).unwrap();
+    match font {
+        LoadedFont::Dfont(_) => {}
+        _ => panic!("Expected a resource fork"),
+    }
+    assert_eq!(font.face_count(), 1);
+    assert!(font.face(0).unwrap().head_table().is_ok());
+}
+
+#[test]
+fn dfont_rejects_out_of_bounds_resources() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let mut dfont = make_dfont(unsafe { file.as_slice() });
+
+    // Make the `sfnt` resource's length run past the end of the data area.
+    let sfnt_length_offset = 256 + 4 + 52 + 2 + 6 * 2;
+    dfont[sfnt_length_offset] = 0x7f;
+    assert!(ResourceFork::new(&dfont).unwrap().faces().is_err());
+}
@@ -0,0 +1,105 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use atlas::Atlas;
+use error::Error;
+use euclid::Size2D;
+use hinting::Hinter;
+use memmap::{Mmap, Protection};
+use otf::{FontData, Tag};
+use otf::collection::FontCollection;
+use otf::writer::FontBuilder;
+use std::io;
+use tests::TEST_FONT_PATH;
+
+const GASP: u32 = 0x67617370;
+const HEAD: u32 = 0x68656164;
+const VHEA: u32 = 0x76686561;
+
+fn make_font(tag: u32, data: &[u8]) -> Vec<u8> {
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag(tag), data);
+    font_builder.build().unwrap()
+}
+
+#[test]
+fn missing_table() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    assert_eq!(font.svg_table().err(), Some(Error::MissingTable(0x53564720)));
+    assert_eq!(Error::MissingTable(0x53564720).to_string(), "missing `SVG ` table");
+}
+
+#[test]
+fn unsupported_format() {
+    let bytes = make_font(GASP, &[0, 2, 0, 0]);
+    assert_eq!(FontData::new(&bytes).gasp_table().err(),
+               Some(Error::UnsupportedFormat { table: GASP, format: 2 }));
+
+    // `vhea` shares its parser with `hhea`, but errors name the table that was asked for.
+    let mut vhea = vec![0; 36];
+    vhea[1] = 2;
+    let bytes = make_font(VHEA, &vhea);
+    assert_eq!(FontData::new(&bytes).vhea_table().err(),
+               Some(Error::UnsupportedFormat { table: VHEA, format: 0x20000 }));
+
+    let mut bytes = make_font(GASP, &[0, 0, 0, 0]);
+    bytes[0] = 0xff;
+    assert_eq!(FontData::new(&bytes).gasp_table().err(),
+               Some(Error::UnsupportedSfntVersion(0xff010000)));
+    assert_eq!(FontCollection::new(&bytes).err(), Some(Error::UnsupportedSfntVersion(0xff010000)));
+}
+
+#[test]
+fn truncated_table() {
+    let bytes = make_font(GASP, &[0, 1, 0, 2, 0, 8, 0, 3]);
+    assert_eq!(FontData::new(&bytes).gasp_table().err(),
+               Some(Error::Truncated { table: GASP, table_length: 8 }));
+
+    let bytes = make_font(HEAD, &[0, 1, 0, 0, 0, 1, 0, 0, 0, 0]);
+    let error = FontData::new(&bytes).head_table().err();
+    assert_eq!(error, Some(Error::Truncated { table: HEAD, table_length: 10 }));
+    assert_eq!(error.unwrap().to_string(), "`head` table truncated at 10 bytes");
+
+    // A table record that runs past the end of the file is truncated too.
+    let bytes = make_font(GASP, &[0, 1, 0, 1, 0, 8, 0, 3]);
+    assert_eq!(FontData::new(&bytes[..(bytes.len() - 4)]).gasp_table().err(),
+               Some(Error::Truncated { table: GASP, table_length: 4 }));
+}
+
+#[test]
+fn invalid_glyph_id() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let glyf = font.glyf_table().unwrap();
+    let head = font.head_table().unwrap();
+    let loca = font.loca_table(&head).unwrap();
+    assert_eq!(glyf.simple_glyph(&loca, 60000).err(), Some(Error::InvalidGlyphId(60000)));
+    let hhea = font.hhea_table().unwrap();
+    let hmtx = font.hmtx_table(&hhea).unwrap();
+    assert_eq!(hmtx.metrics(60000).err(), Some(Error::InvalidGlyphId(60000)));
+}
+
+#[test]
+fn invalid_argument() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let hinter = Hinter::new(&font).unwrap();
+    assert_eq!(hinter.hint_glyph(36).err(), Some(Error::InvalidArgument));
+}
+
+#[test]
+fn atlas_full() {
+    let mut atlas = Atlas::new(64, 16);
+    assert!(atlas.place(&Size2D::new(64, 16)).is_ok());
+    assert_eq!(atlas.place(&Size2D::new(65, 8)), Err(Error::AtlasFull));
+    assert_eq!(atlas.place(&Size2D::new(8, 17)), Err(Error::AtlasFull));
+}
+
+#[test]
+fn io_errors() {
+    assert_eq!(Error::from(io::Error::new(io::ErrorKind::UnexpectedEof, "eof")),
+               Error::UnexpectedEof);
+    assert_eq!(Error::from(io::Error::new(io::ErrorKind::InvalidData, "bad")), Error::Malformed);
+    assert_eq!(Error::from(io::Error::new(io::ErrorKind::InvalidInput, "bad")), Error::Malformed);
+}
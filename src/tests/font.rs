@@ -0,0 +1,133 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use byteorder::{BigEndian, WriteBytesExt};
+use error::Error;
+use memmap::{Mmap, Protection};
+use otf::font::{Font, FontBytes};
+use otf::writer::FontBuilder;
+use otf::{FontData, Tag};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::thread;
+use tests::collection::make_collection;
+use tests::{TEST_FONT_PATH, glyph_points};
+
+fn font_points(font: &Font, glyph_id: u32) -> Vec<(i16, i16, bool)> {
+    let glyf = font.glyf_table().unwrap();
+    let loca = font.loca_table().unwrap();
+    let mut points = vec![];
+    glyf.for_each_point(&loca, glyph_id, |point| {
+        points.push((point.position.x, point.position.y, point.on_curve))
+    }).unwrap();
+    points
+}
+
+fn assert_send_sync<T>() where T: Send + Sync {}
+
+#[test]
+fn font_is_send_and_sync() {
+    assert_send_sync::<Font>();
+}
+
+#[test]
+fn font_matches_font_data() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let bytes = unsafe { file.as_slice() }.to_vec();
+    let font_data = FontData::new(&bytes);
+
+    // Nothing modifies the test font while it's mapped.
+    let mmap = Mmap::open_path(TEST_FONT_PATH, Protection::Read).unwrap();
+    let fonts = vec![
+        Font::new(unsafe { FontBytes::from_mmap(mmap) }).unwrap(),
+        Font::new(bytes.clone()).unwrap(),
+        Font::new(Arc::<[u8]>::from(bytes.clone())).unwrap(),
+    ];
+    for font in &fonts {
+        assert_eq!(font.head_table().unwrap().units_per_em, 2048);
+        assert_eq!(font.hmtx_table().unwrap().metrics(36).unwrap(),
+                   font_data.hmtx_table(&font_data.hhea_table().unwrap())
+                            .unwrap()
+                            .metrics(36)
+                            .unwrap());
+        for glyph_id in 1..32 {
+            assert!(font_points(font, glyph_id) == glyph_points(&font_data, glyph_id));
+            assert!(glyph_points(&font.font_data(), glyph_id) == font_points(font, glyph_id));
+        }
+    }
+}
+
+#[test]
+fn font_caches_errors() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = Font::new(unsafe { file.as_slice() }.to_vec()).unwrap();
+    for _ in 0..2 {
+        assert_eq!(font.svg_table().err(), Some(Error::MissingTable(0x53564720)));
+        assert_eq!(font.colr_table().err(), Some(Error::MissingTable(0x434f4c52)));
+        assert_eq!(font.gsub_table().err(), Some(Error::MissingTable(0x47535542)));
+    }
+
+    assert_eq!(Font::new(vec![0, 0, 0, 0]).err(), Some(Error::UnsupportedSfntVersion(0)));
+    assert_eq!(Font::with_face_index(unsafe { file.as_slice() }.to_vec(), 1).err(),
+               Some(Error::InvalidArgument));
+}
+
+#[test]
+fn font_from_collection() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let bytes = unsafe { file.as_slice() };
+    let collection = Arc::<[u8]>::from(make_collection(bytes));
+    let font_data = FontData::new(bytes);
+
+    for face_index in 0..2 {
+        let font = Font::with_face_index(collection.clone(), face_index).unwrap();
+        for glyph_id in 1..32 {
+            assert!(font_points(&font, glyph_id) == glyph_points(&font_data, glyph_id));
+        }
+    }
+    assert!(Font::with_face_index(collection, 2).is_err());
+}
+
+#[test]
+fn font_shared_between_threads() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let bytes = unsafe { file.as_slice() }.to_vec();
+    let expected: Vec<_> = (1..32).map(|glyph_id| {
+        glyph_points(&FontData::new(&bytes), glyph_id)
+    }).collect();
+
+    let font = Arc::new(Font::new(bytes.clone()).unwrap());
+    let threads: Vec<_> = (0..4).map(|_| {
+        let font = font.clone();
+        thread::spawn(move || (1..32).map(|glyph_id| font_points(&font, glyph_id)).collect())
+    }).collect();
+    for thread in threads {
+        let points: Vec<_> = thread.join().unwrap();
+        assert!(points == expected);
+    }
+}
+
+#[test]
+fn font_gsub_table() {
+    // A single substitution of glyph 1 with glyph 2.
+    let mut gsub = vec![];
+    for &value in &[1, 0, 0, 0, 10, 1, 4, 1, 0, 1, 8, 1, 6, 1, 1, 1, 1] {
+        gsub.write_u16::<BigEndian>(value).unwrap()
+    }
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"GSUB"), gsub);
+    let bytes = font_builder.build().unwrap();
+    let font_data = FontData::new(&bytes);
+    let font = Font::new(bytes.clone()).unwrap();
+
+    let mut expected = BTreeSet::new();
+    expected.insert(1);
+    font_data.gsub_table().unwrap().closure(&mut expected).unwrap();
+    assert_eq!(expected.iter().cloned().collect::<Vec<_>>(), vec![1, 2]);
+    for _ in 0..2 {
+        let mut glyph_ids = BTreeSet::new();
+        glyph_ids.insert(1);
+        font.gsub_table().unwrap().closure(&mut glyph_ids).unwrap();
+        assert_eq!(glyph_ids, expected);
+    }
+}
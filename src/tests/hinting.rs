@@ -0,0 +1,224 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use byteorder::{BigEndian, WriteBytesExt};
+use hinting::{HintedGlyph, Hinter};
+use memmap::{Mmap, Protection};
+use otf::writer::{CompositeComponent, FontBuilder, GlyfBuilder, HeadBuilder, HheaBuilder};
+use otf::writer::MaxpBuilder;
+use otf::{FontData, Tag};
+use outline::{PathCommand, Transform};
+use tests::TEST_FONT_PATH;
+
+// The expected values below come from FreeType 2.11 with the version 35 interpreter, in 26.6.
+
+fn check_glyph(glyph: &HintedGlyph,
+               advance_width: i32,
+               contour_end_indices: &[u16],
+               points: &[(i32, i32)]) {
+    assert_eq!(glyph.advance_width, advance_width);
+    assert_eq!(glyph.contour_end_indices, contour_end_indices);
+    let actual: Vec<_> = glyph.points.iter().map(|point| {
+        (point.position.x, point.position.y)
+    }).collect();
+    assert_eq!(actual, points);
+}
+
+#[test]
+fn hint_glyphs_like_freetype() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let mut hinter = Hinter::new(&font).unwrap();
+
+    // "A"
+    hinter.set_size(12, false).unwrap();
+    check_glyph(&hinter.hint_glyph(36).unwrap(), 512, &[7, 10], &[
+        (365, 192), (148, 192), (89, 0), (13, 0), (213, 576), (305, 576), (502, 0), (422, 0),
+        (344, 256), (258, 502), (166, 256),
+    ]);
+
+    // "o"
+    hinter.set_size(10, false).unwrap();
+    check_glyph(&hinter.hint_glyph(82).unwrap(), 384, &[15, 35], &[
+        (160, 320), (237, 320), (280, 275), (320, 233), (320, 158), (320, 87), (280, 45),
+        (237, 0), (160, 0), (83, 0), (40, 45), (0, 87), (0, 160), (0, 233), (40, 275), (83, 320),
+        (160, 256), (126, 256), (101, 239), (64, 215), (64, 160), (64, 121), (84, 96), (95, 82),
+        (115, 73), (135, 64), (160, 64), (194, 64), (219, 81), (256, 105), (256, 159),
+        (256, 199), (236, 224), (225, 239), (205, 247), (185, 256),
+    ]);
+
+    // "a"
+    hinter.set_size(17, false).unwrap();
+    check_glyph(&hinter.hint_glyph(68).unwrap(), 576, &[43, 62], &[
+        (576, 52), (576, -15), (545, 0), (526, 0), (457, 0), (450, 56), (407, 30), (365, 16),
+        (314, 0), (254, 0), (165, 0), (113, 42), (64, 82), (64, 152), (64, 251), (167, 289),
+        (200, 301), (269, 310), (277, 311), (352, 320), (412, 326), (429, 339), (448, 351),
+        (448, 376), (448, 402), (448, 461), (409, 488), (375, 512), (320, 512), (197, 512),
+        (187, 384), (96, 384), (99, 465), (132, 506), (189, 576), (310, 576), (422, 576),
+        (473, 525), (512, 486), (512, 416), (512, 92), (512, 64), (558, 64), (565, 64),
+        (448, 269), (422, 259), (389, 254), (360, 249), (283, 240), (203, 231), (168, 211),
+        (128, 189), (128, 146), (128, 112), (157, 89), (189, 64), (255, 64), (345, 64),
+        (408, 108), (438, 129), (445, 150), (448, 158), (448, 172),
+    ]);
+}
+
+#[test]
+fn hint_advance_widths_like_freetype() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let mut hinter = Hinter::new(&font).unwrap();
+
+    // Glyphs "A", "a", "e", and "o".
+    for &(pixels_per_em, advance_widths) in &[
+        (8, [320, 256, 256, 256]),
+        (11, [448, 384, 384, 384]),
+        (16, [704, 576, 576, 576]),
+        (24, [1024, 832, 832, 832]),
+    ] {
+        hinter.set_size(pixels_per_em, true).unwrap();
+        for (&glyph_id, &advance_width) in [36, 68, 72, 82].iter().zip(advance_widths.iter()) {
+            assert_eq!(hinter.hint_glyph(glyph_id).unwrap().advance_width, advance_width)
+        }
+    }
+
+    // The space has no outline.
+    hinter.set_size(9, false).unwrap();
+    let space = hinter.hint_glyph(3).unwrap();
+    assert!(space.points.is_empty() && space.contour_end_indices.is_empty());
+    assert_eq!(space.advance_width, 192);
+}
+
+#[test]
+fn hint_glyph_requires_size() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let hinter = Hinter::new(&font).unwrap();
+    assert!(hinter.hint_glyph(36).is_err());
+}
+
+#[test]
+fn hinted_path_commands() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let mut hinter = Hinter::new(&font).unwrap();
+    hinter.set_size(12, false).unwrap();
+
+    // "A" is all straight lines, so each point becomes a line except the first of each contour.
+    let commands = hinter.hint_glyph(36).unwrap().path_commands();
+    assert_eq!(commands.len(), 13);
+    match commands[0] {
+        PathCommand::MoveTo(point) => assert_eq!((point.x, point.y), (365.0 / 64.0, 3.0)),
+        command => panic!("unexpected command {:?}", command),
+    }
+    match commands[4] {
+        PathCommand::LineTo(point) => assert_eq!((point.x, point.y), (213.0 / 64.0, 9.0)),
+        command => panic!("unexpected command {:?}", command),
+    }
+    assert_eq!(commands[8], PathCommand::ClosePath);
+    assert_eq!(commands[12], PathCommand::ClosePath);
+}
+
+// Glyph 1 is a square 500 units on a side. Glyph 2 puts two of them side by side, rounding the
+// offset of the first to the grid, and its program moves the first point right a pixel. Glyph 3
+// is the first component alone, with no rounding or program.
+fn make_composite_font() -> Vec<u8> {
+    let mut glyf_builder = GlyfBuilder::new();
+    glyf_builder.add_empty_glyph();
+    glyf_builder.add_simple_glyph(&[&[(0, 0, true), (500, 0, true), (500, 500, true),
+                                      (0, 500, true)]], &[]);
+
+    let mut composite = vec![];
+    composite.write_i16::<BigEndian>(-1).unwrap();
+    for &value in &[130, 0, 1130, 500] {
+        composite.write_i16::<BigEndian>(value).unwrap()
+    }
+    // ARG_1_AND_2_ARE_WORDS | ARGS_ARE_XY_VALUES, with ROUND_XY_TO_GRID | MORE_COMPONENTS on the
+    // first and WE_HAVE_INSTRUCTIONS on the second.
+    for &(flags, dx) in &[(0x0027, 130), (0x0103, 630)] {
+        composite.write_u16::<BigEndian>(flags).unwrap();
+        composite.write_u16::<BigEndian>(1).unwrap();
+        composite.write_i16::<BigEndian>(dx).unwrap();
+        composite.write_i16::<BigEndian>(0).unwrap();
+    }
+    // SVTCA[x], PUSHB[1] 0 64, SHPIX
+    let instructions = [0x01, 0xb1, 0, 64, 0x38];
+    composite.write_u16::<BigEndian>(instructions.len() as u16).unwrap();
+    composite.extend_from_slice(&instructions);
+    glyf_builder.add_raw_glyph(&composite);
+    glyf_builder.add_composite_glyph(&[
+        CompositeComponent::new(1, Transform::translation(130.0, 0.0)),
+    ]);
+
+    let mut hhea_builder = HheaBuilder::new(800, -200, 0);
+    for &(advance, side_bearing) in &[(500, 0), (600, 0), (1200, 130), (600, 130)] {
+        hhea_builder.add_metrics(advance, side_bearing)
+    }
+    let (hhea, hmtx) = hhea_builder.build();
+    let (glyf, loca, index_to_loc_format) = glyf_builder.build();
+    let mut head_builder = HeadBuilder::new();
+    head_builder.index_to_loc_format = index_to_loc_format;
+
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"glyf"), glyf);
+    font_builder.add_table(Tag::new(b"head"), head_builder.build());
+    font_builder.add_table(Tag::new(b"hhea"), hhea);
+    font_builder.add_table(Tag::new(b"hmtx"), hmtx);
+    font_builder.add_table(Tag::new(b"loca"), loca);
+    font_builder.add_table(Tag::new(b"maxp"), MaxpBuilder::new(4).build());
+    font_builder.build().unwrap()
+}
+
+#[test]
+fn hint_composite_glyphs() {
+    let bytes = make_composite_font();
+    let font = FontData::new(&bytes);
+    let mut hinter = Hinter::new(&font).unwrap();
+    hinter.set_size(10, false).unwrap();
+
+    // At 10 pixels per em, the square is 320 wide in 26.6. The offsets scale to 83 and 403, and
+    // the first rounds to 64.
+    check_glyph(&hinter.hint_glyph(2).unwrap(), 768, &[3, 7], &[
+        (128, 0), (384, 0), (384, 320), (64, 320),
+        (403, 0), (723, 0), (723, 320), (403, 320),
+    ]);
+    check_glyph(&hinter.hint_glyph(3).unwrap(), 384, &[3], &[
+        (83, 0), (403, 0), (403, 320), (83, 320),
+    ]);
+}
+
+#[test]
+fn hint_glyphs_with_inverted_bounds() {
+    let mut bytes = make_composite_font();
+    let expected: Vec<_> = {
+        let font = FontData::new(&bytes);
+        let mut hinter = Hinter::new(&font).unwrap();
+        hinter.set_size(10, false).unwrap();
+        (1..3).map(|glyph_id| hinter.hint_glyph(glyph_id).unwrap().points).collect()
+    };
+
+    // Give the square and the composite a `yMin` far above their `yMax`, so that the heights
+    // overflow.
+    let glyph_offsets: Vec<_> = {
+        let font = FontData::new(&bytes);
+        let head = font.head_table().unwrap();
+        let loca = font.loca_table(&head).unwrap();
+        let glyf_record = font.table_records().unwrap().find(|record| {
+            record.tag == Tag::new(b"glyf")
+        }).unwrap();
+        (1..3).map(|glyph_id| {
+            glyf_record.offset as usize + loca.location_of(glyph_id).unwrap() as usize
+        }).collect()
+    };
+    for &offset in &glyph_offsets {
+        (&mut bytes[(offset + 4)..]).write_i16::<BigEndian>(30000).unwrap();
+        (&mut bytes[(offset + 8)..]).write_i16::<BigEndian>(-30000).unwrap();
+    }
+
+    // Only the vertical phantom points depend on `yMax`, so the outlines don't change.
+    let font = FontData::new(&bytes);
+    let mut hinter = Hinter::new(&font).unwrap();
+    hinter.set_size(10, false).unwrap();
+    for (glyph_id, expected) in (1..3).zip(expected) {
+        assert_eq!(hinter.hint_glyph(glyph_id).unwrap().points, expected);
+    }
+}
@@ -0,0 +1,262 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+#[cfg(feature = "gpu")]
+use batch::BatchBuilder;
+use error::Error;
+use euclid::Point2D;
+use glyph_buffer::GlyphBufferBuilder;
+use limits::{Limit, Limits};
+use otf::glyf::GlyfTable;
+use otf::writer::{CompositeComponent, FontBuilder, GlyfBuilder, HeadBuilder};
+use otf::{FontData, Tag};
+use outline::{PathCommand, Transform};
+
+fn make_font(glyphs: &[Glyph]) -> Vec<u8> {
+    let mut glyf_builder = GlyfBuilder::new();
+    for glyph in glyphs {
+        match *glyph {
+            Glyph::Triangle => glyf_builder.add_simple_glyph(&[&TRIANGLE], &[]),
+            Glyph::Composite(ref components) => glyf_builder.add_composite_glyph(components),
+        };
+    }
+    let (glyf, loca, index_to_loc_format) = glyf_builder.build();
+    let mut head_builder = HeadBuilder::new();
+    head_builder.index_to_loc_format = index_to_loc_format;
+
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"glyf"), glyf);
+    font_builder.add_table(Tag::new(b"head"), head_builder.build());
+    font_builder.add_table(Tag::new(b"loca"), loca);
+    font_builder.build().unwrap()
+}
+
+enum Glyph {
+    Triangle,
+    Composite(Vec<CompositeComponent>),
+}
+
+// A triangle with corners at (0, 0), (100, 0), and (0, 100).
+const TRIANGLE: [(i16, i16, bool); 3] = [(0, 0, true), (100, 0, true), (0, 100, true)];
+
+// A composite glyph made of the given components, each a glyph ID, an offset, and an optional
+// uniform scale.
+fn composite(components: &[(u16, i16, i16, Option<f32>)]) -> Glyph {
+    Glyph::Composite(components.iter().map(|&(glyph_id, dx, dy, scale)| {
+        let scale = scale.unwrap_or(1.0);
+        CompositeComponent::new(glyph_id,
+                                Transform::new(scale, 0.0, 0.0, scale, dx as f32, dy as f32))
+    }).collect())
+}
+
+fn points(font: &FontData, glyf: &GlyfTable, glyph_id: u32)
+          -> Result<Vec<(i16, i16, bool)>, Error> {
+    let head = font.head_table().unwrap();
+    let loca = font.loca_table(&head).unwrap();
+    let mut points = vec![];
+    try!(glyf.for_each_point(&loca, glyph_id, |point| {
+        points.push((point.position.x, point.position.y, point.first_point_in_contour))
+    }));
+    Ok(points)
+}
+
+// A square `size` units on a side, as `GlyphBufferBuilder::add_path_commands()` takes it.
+fn square(size: f32) -> Vec<PathCommand> {
+    vec![
+        PathCommand::MoveTo(Point2D::new(0.0, 0.0)),
+        PathCommand::LineTo(Point2D::new(size, 0.0)),
+        PathCommand::LineTo(Point2D::new(size, size)),
+        PathCommand::LineTo(Point2D::new(0.0, size)),
+        PathCommand::ClosePath,
+    ]
+}
+
+#[test]
+fn composite_glyphs_are_transformed_into_place() {
+    let bytes = make_font(&[
+        Glyph::Triangle,
+        composite(&[(0, 1000, 0, None), (0, 0, -50, Some(2.0))]),
+    ]);
+    let font = FontData::new(&bytes);
+    let glyf = font.glyf_table().unwrap();
+    assert_eq!(points(&font, &glyf, 1).unwrap(), vec![
+        (1000, 0, true), (1100, 0, false), (1000, 100, false), (1000, 0, false),
+        (0, -50, true), (200, -50, false), (0, 150, false), (0, -50, false),
+    ]);
+}
+
+#[test]
+fn composite_depth() {
+    // Each glyph after the first nests the one before it.
+    let bytes = make_font(&[
+        Glyph::Triangle,
+        composite(&[(0, 0, 0, None)]),
+        composite(&[(1, 0, 0, None)]),
+        composite(&[(2, 0, 0, None)]),
+    ]);
+    let font = FontData::new(&bytes);
+    let mut glyf = font.glyf_table().unwrap();
+    glyf.limits.max_composite_depth = 2;
+    assert_eq!(points(&font, &glyf, 2).unwrap().len(), 4);
+    assert_eq!(points(&font, &glyf, 3), Err(Error::LimitExceeded(Limit::CompositeDepth)));
+
+    // A composite that contains itself never ends, but the limits stop it.
+    let bytes = make_font(&[composite(&[(0, 0, 0, None)])]);
+    let font = FontData::new(&bytes);
+    let glyf = font.glyf_table().unwrap();
+    assert_eq!(points(&font, &glyf, 0), Err(Error::LimitExceeded(Limit::CompositeDepth)));
+}
+
+#[test]
+fn components_per_glyph() {
+    let bytes = make_font(&[
+        Glyph::Triangle,
+        composite(&[(0, 0, 0, None), (0, 100, 0, None)]),
+        composite(&[(1, 0, 0, None), (0, 200, 0, None)]),
+    ]);
+    let font = FontData::new(&bytes);
+    let mut glyf = font.glyf_table().unwrap();
+    glyf.limits.max_components_per_glyph = 3;
+    assert_eq!(points(&font, &glyf, 1).unwrap().len(), 8);
+
+    // Nested components count too.
+    assert_eq!(points(&font, &glyf, 2), Err(Error::LimitExceeded(Limit::ComponentsPerGlyph)));
+}
+
+#[test]
+fn points_and_contours_per_glyph() {
+    let bytes = make_font(&[Glyph::Triangle, composite(&[(0, 0, 0, None), (0, 100, 0, None)])]);
+    let font = FontData::new(&bytes);
+    let head = font.head_table().unwrap();
+    let loca = font.loca_table(&head).unwrap();
+    let mut glyf = font.glyf_table().unwrap();
+
+    glyf.limits.max_points_per_glyph = 5;
+    assert!(points(&font, &glyf, 0).is_ok());
+    assert_eq!(points(&font, &glyf, 1), Err(Error::LimitExceeded(Limit::PointsPerGlyph)));
+    assert!(glyf.simple_glyph(&loca, 0).is_ok());
+
+    glyf.limits = Limits::default();
+    glyf.limits.max_contours_per_glyph = 1;
+    assert!(points(&font, &glyf, 0).is_ok());
+    assert_eq!(points(&font, &glyf, 1), Err(Error::LimitExceeded(Limit::ContoursPerGlyph)));
+
+    glyf.limits.max_contours_per_glyph = 0;
+    assert_eq!(glyf.simple_glyph(&loca, 0).err(),
+               Some(Error::LimitExceeded(Limit::ContoursPerGlyph)));
+}
+
+#[test]
+fn glyph_buffer_builder_limits() {
+    let bytes = make_font(&[Glyph::Triangle]);
+    let font = FontData::new(&bytes);
+    let head = font.head_table().unwrap();
+    let loca = font.loca_table(&head).unwrap();
+    let glyf = font.glyf_table().unwrap();
+
+    // The builder's limits apply, not the table's.
+    let mut builder = GlyphBufferBuilder::new();
+    builder.limits.max_points_per_glyph = 2;
+    assert_eq!(builder.add_glyph(0, &head, &loca, &glyf),
+               Err(Error::LimitExceeded(Limit::PointsPerGlyph)));
+    assert_eq!(builder.add_path_commands(&square(100.0), 1000),
+               Err(Error::LimitExceeded(Limit::PointsPerGlyph)));
+    assert!(builder.descriptors.is_empty());
+
+    builder.limits = Limits::default();
+    builder.add_glyph(0, &head, &loca, &glyf).unwrap();
+    builder.add_path_commands(&square(100.0), 1000).unwrap();
+    assert_eq!(builder.descriptors.len(), 2);
+}
+
+#[test]
+fn glyph_buffer_builder_failures_leave_buffers_unchanged() {
+    // The second component of glyph 1 doesn't exist, so it fails after the first is loaded.
+    let bytes = make_font(&[Glyph::Triangle, composite(&[(0, 0, 0, None), (5, 0, 0, None)])]);
+    let font = FontData::new(&bytes);
+    let head = font.head_table().unwrap();
+    let loca = font.loca_table(&head).unwrap();
+    let glyf = font.glyf_table().unwrap();
+
+    let mut builder = GlyphBufferBuilder::new();
+    builder.add_glyph(0, &head, &loca, &glyf).unwrap();
+    let (coordinates, operations) = (builder.coordinates.clone(), builder.operations.clone());
+    assert!(builder.add_glyph(1, &head, &loca, &glyf).is_err());
+    builder.limits.max_contours_per_glyph = 0;
+    assert!(builder.add_path_commands(&square(100.0), 1000).is_err());
+    assert_eq!((&builder.coordinates, &builder.operations), (&coordinates, &operations));
+    assert_eq!(builder.descriptors.len(), 1);
+}
+
+#[test]
+fn glyph_buffer_operations_are_packed_across_glyphs() {
+    let bytes = make_font(&[Glyph::Triangle]);
+    let font = FontData::new(&bytes);
+    let head = font.head_table().unwrap();
+    let loca = font.loca_table(&head).unwrap();
+    let glyf = font.glyf_table().unwrap();
+
+    let mut triangle = GlyphBufferBuilder::new();
+    triangle.add_glyph(0, &head, &loca, &glyf).unwrap();
+    let mut square_builder = GlyphBufferBuilder::new();
+    square_builder.add_path_commands(&square(100.0), 1000).unwrap();
+
+    // Glyphs that don't start on a byte boundary share the byte with the glyph before them.
+    let mut builder = GlyphBufferBuilder::new();
+    builder.add_glyph(0, &head, &loca, &glyf).unwrap();
+    builder.add_path_commands(&square(100.0), 1000).unwrap();
+    builder.add_glyph(0, &head, &loca, &glyf).unwrap();
+    builder.add_path_commands(&square(100.0), 1000).unwrap();
+
+    let mut expected = vec![];
+    for _ in 0..2 {
+        expected.extend(unpack_operations(&triangle));
+        expected.extend(unpack_operations(&square_builder));
+    }
+    assert_eq!(unpack_operations(&builder), expected);
+    assert_eq!(builder.operations.len(), (builder.coordinates.len() + 3) / 4);
+    let start_points: Vec<_> = builder.descriptors.iter().map(|descriptor| {
+        descriptor.start_point
+    }).collect();
+    let (triangle_points, square_points) = (triangle.coordinates.len() as u32,
+                                            square_builder.coordinates.len() as u32);
+    assert_eq!(start_points, vec![0,
+                                  triangle_points,
+                                  triangle_points + square_points,
+                                  triangle_points * 2 + square_points]);
+}
+
+// Returns the operation of each point in the buffers, unpacked from four to a byte.
+fn unpack_operations(builder: &GlyphBufferBuilder) -> Vec<u8> {
+    (0..builder.coordinates.len()).map(|point_index| {
+        (builder.operations[point_index / 4] >> (point_index % 4 * 2)) & 3
+    }).collect()
+}
+
+#[cfg(feature = "gpu")]
+#[test]
+fn batch_builder_limits() {
+    let mut glyph_buffer_builder = GlyphBufferBuilder::new();
+    glyph_buffer_builder.add_path_commands(&square(1000.0), 1000).unwrap();
+    let point_count = glyph_buffer_builder.descriptors[0].point_count as u32;
+
+    // A glyph one em across is as many pixels across as the point size.
+    let mut batch_builder = BatchBuilder::new(512, 64);
+    batch_builder.limits.max_image_size = 32;
+    assert_eq!(batch_builder.add_glyph(&glyph_buffer_builder, 0, 48.0),
+               Err(Error::LimitExceeded(Limit::ImageSize)));
+    batch_builder.add_glyph(&glyph_buffer_builder, 0, 32.0).unwrap();
+
+    batch_builder.limits.max_batch_points = point_count * 2;
+    batch_builder.add_glyph(&glyph_buffer_builder, 0, 16.0).unwrap();
+    assert_eq!(batch_builder.add_glyph(&glyph_buffer_builder, 0, 16.0),
+               Err(Error::LimitExceeded(Limit::BatchPoints)));
+    assert_eq!(batch_builder.images.len(), 2);
+    assert_eq!(batch_builder.point_count, point_count * 2);
+}
+
+#[test]
+fn limit_exceeded_display() {
+    assert_eq!(Error::LimitExceeded(Limit::CompositeDepth).to_string(),
+               "limit on composite glyph depth exceeded");
+}
@@ -0,0 +1,75 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use error::Error;
+use memmap::{Mmap, Protection};
+use otf::FontData;
+use otf::loader::{self, FontFormat, LoadError, LoadedFont};
+use tests::{TEST_FONT_PATH, glyph_points};
+use tests::woff::woff_with_empty_tables;
+
+#[test]
+fn detect_formats() {
+    assert_eq!(FontFormat::detect(b"\x00\x01\x00\x00\x00\x10"), FontFormat::TrueType);
+    assert_eq!(FontFormat::detect(b"true\x00\x10"), FontFormat::TrueType);
+    assert_eq!(FontFormat::detect(b"OTTO\x00\x10"), FontFormat::Cff);
+    assert_eq!(FontFormat::detect(b"ttcf\x00\x01\x00\x00"), FontFormat::Collection);
+    assert_eq!(FontFormat::detect(b"wOFF\x00\x01\x00\x00"), FontFormat::Woff);
+    assert_eq!(FontFormat::detect(b"wOF2\x00\x01\x00\x00"), FontFormat::Woff2);
+    assert_eq!(FontFormat::detect(b"\x80\x01\x10\x00\x00\x00%!PS-AdobeFont-1.0"),
+               FontFormat::Type1);
+    assert_eq!(FontFormat::detect(b"%!PS-AdobeFont-1.0: NimbusSanL-Regu"), FontFormat::Type1);
+    assert_eq!(FontFormat::detect(b"STARTFONT 2.1\n"), FontFormat::Bitmap);
+    assert_eq!(FontFormat::detect(b"\x01fcp\x0a\x00\x00\x00"), FontFormat::Bitmap);
+    assert_eq!(FontFormat::detect(b""), FontFormat::Unknown);
+    assert_eq!(FontFormat::detect(b"GIF89a"), FontFormat::Unknown);
+}
+
+#[test]
+fn detect_resource_fork() {
+    // A resource fork with an empty data area and a minimal map.
+    let mut dfont = vec![0x00, 0x00, 0x01, 0x00,
+                         0x00, 0x00, 0x01, 0x00,
+                         0x00, 0x00, 0x00, 0x00,
+                         0x00, 0x00, 0x00, 0x1e];
+    dfont.resize(0x100 + 0x1e, 0);
+    assert_eq!(FontFormat::detect(&dfont), FontFormat::Dfont);
+
+    dfont.push(0);
+    assert_eq!(FontFormat::detect(&dfont), FontFormat::Unknown);
+}
+
+#[test]
+fn load_bare_sfnt() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let bytes = unsafe { file.as_slice() };
+    let font = loader::load_font(bytes).unwrap();
+    match font {
+        LoadedFont::Sfnt(_) => {}
+        _ => panic!("Expected a bare sfnt"),
+    }
+    assert_eq!(font.face_count(), 1);
+    assert!(glyph_points(&font.face(0).unwrap(), 1) == glyph_points(&FontData::new(bytes), 1));
+    assert!(font.face(1).is_err());
+}
+
+#[test]
+fn load_unsupported_and_malformed_fonts() {
+    assert_eq!(loader::load_font(b"%!FontType1-1.0: Foo").err(),
+               Some(LoadError::UnsupportedFormat(FontFormat::Type1)));
+    assert_eq!(loader::load_font(b"STARTFONT 2.1\n").err(),
+               Some(LoadError::UnsupportedFormat(FontFormat::Bitmap)));
+    assert_eq!(loader::load_font(b"wOFF\x00\x01\x00\x00").err(),
+               Some(LoadError::Malformed(FontFormat::Woff, Error::UnexpectedEof)));
+    assert_eq!(loader::load_font(b"wOF2\x00\x01\x00\x00").err(),
+               Some(LoadError::Malformed(FontFormat::Woff2, Error::UnexpectedEof)));
+    assert_eq!(loader::load_font(b"ttcf\x00\x01").err(),
+               Some(LoadError::Malformed(FontFormat::Collection, Error::UnexpectedEof)));
+}
+
+#[test]
+fn load_woff_with_too_many_tables_for_search_range() {
+    let bytes = woff_with_empty_tables(5000);
+    let font = loader::load_font(&bytes).unwrap();
+    assert_eq!(font.face(0).unwrap().table_records().unwrap().count(), 5000);
+}
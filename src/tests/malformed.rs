@@ -0,0 +1,149 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use byteorder::{BigEndian, WriteBytesExt};
+use charmap::CodepointRange;
+use error::Error;
+use otf::{FontData, Tag};
+use otf::writer::{FontBuilder, GlyfBuilder, HeadBuilder};
+
+// Glyph flags.
+const ON_CURVE: u8 = 0x01;
+const REPEAT: u8 = 0x08;
+const THIS_X_IS_SAME: u8 = 0x10;
+const THIS_Y_IS_SAME: u8 = 0x20;
+
+// Builds a font whose only glyph has the given `glyf` data.
+fn make_glyph_font(glyph: &[u8]) -> Vec<u8> {
+    let mut glyf_builder = GlyfBuilder::new();
+    glyf_builder.add_raw_glyph(glyph);
+    let (glyf, loca, index_to_loc_format) = glyf_builder.build();
+    let mut head_builder = HeadBuilder::new();
+    head_builder.units_per_em = 2048;
+    head_builder.index_to_loc_format = index_to_loc_format;
+
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"glyf"), glyf);
+    font_builder.add_table(Tag::new(b"head"), head_builder.build());
+    font_builder.add_table(Tag::new(b"loca"), loca);
+    font_builder.build().unwrap()
+}
+
+// Builds a simple glyph from its contour end points and flags, with no instructions.
+fn make_glyph(end_points: &[u16], flags: &[u8]) -> Vec<u8> {
+    let mut glyph = vec![];
+    glyph.write_i16::<BigEndian>(end_points.len() as i16).unwrap();
+    glyph.extend_from_slice(&[0; 8]);
+    for &end_point in end_points {
+        glyph.write_u16::<BigEndian>(end_point).unwrap();
+    }
+    glyph.write_u16::<BigEndian>(0).unwrap();
+    glyph.extend_from_slice(flags);
+    glyph
+}
+
+fn points(bytes: &[u8]) -> Result<usize, Error> {
+    let font = FontData::new(bytes);
+    let glyf = font.glyf_table().unwrap();
+    let head = font.head_table().unwrap();
+    let loca = font.loca_table(&head).unwrap();
+    let mut count = 0;
+    try!(glyf.for_each_point(&loca, 0, |_| count += 1));
+    Ok(count)
+}
+
+#[test]
+fn glyph_without_contours() {
+    let bytes = make_glyph_font(&make_glyph(&[], &[]));
+    assert_eq!(points(&bytes), Ok(0));
+}
+
+#[test]
+fn glyph_without_flags() {
+    let bytes = make_glyph_font(&make_glyph(&[0], &[]));
+    assert!(points(&bytes).is_err());
+}
+
+#[test]
+fn glyph_flag_repeated_past_last_point() {
+    let flags = [ON_CURVE | REPEAT | THIS_X_IS_SAME | THIS_Y_IS_SAME, 255];
+    let bytes = make_glyph_font(&make_glyph(&[0], &flags));
+    // The point itself, then the one that closes the contour.
+    assert_eq!(points(&bytes), Ok(2));
+}
+
+#[test]
+fn glyph_end_points_out_of_order() {
+    let flag = ON_CURVE | THIS_X_IS_SAME | THIS_Y_IS_SAME;
+    let bytes = make_glyph_font(&make_glyph(&[1, 0], &[flag, flag, flag]));
+    assert_eq!(points(&bytes), Err(Error::Malformed));
+}
+
+#[test]
+fn glyph_with_too_many_points() {
+    let bytes = make_glyph_font(&make_glyph(&[0xffff], &[ON_CURVE]));
+    assert_eq!(points(&bytes), Err(Error::Malformed));
+}
+
+#[test]
+fn cmap_lookups_at_the_edges_of_the_code_space() {
+    // A format 4 subtable mapping 'A'...'Z' to glyphs 1...26, plus the required final segment.
+    let mut subtable = vec![];
+    for &value in &[4, 32, 0, 4, 4, 1, 0, 0x5a, 0xffff, 0, 0x41, 0xffff, 0xffc0, 1, 0, 0] {
+        subtable.write_u16::<BigEndian>(value).unwrap()
+    }
+    let mut cmap = vec![];
+    for &value in &[0, 1, 3, 1, 0, 12] {
+        cmap.write_u16::<BigEndian>(value).unwrap()
+    }
+    cmap.extend_from_slice(&subtable);
+
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"cmap"), cmap);
+    let bytes = font_builder.build().unwrap();
+    let font = FontData::new(&bytes);
+    let cmap = font.cmap_table().unwrap();
+    let lookup = |start, end| {
+        cmap.glyph_ranges_for_codepoint_ranges(&[CodepointRange::new(start, end)])
+            .unwrap()
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(lookup(0x41, 0x5a), vec![(1, 26)]);
+    assert_eq!(lookup(0xffff, 0x10000), vec![(0, 0), (0, 0)]);
+    assert_eq!(lookup(0xfffffffe, 0xffffffff), vec![(0, 0), (0, 0)]);
+}
+
+#[test]
+fn table_record_past_the_end_of_the_font() {
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"head"), HeadBuilder::new().build());
+    let mut bytes = font_builder.build().unwrap();
+    // Point the only table record at the very end of the address space.
+    for byte in &mut bytes[20..28] {
+        *byte = 0xff
+    }
+    assert!(FontData::new(&bytes).table(Tag::new(b"head")).is_err());
+}
+
+#[test]
+fn cmap_format_12_with_too_many_groups() {
+    let mut cmap = vec![];
+    for &value in &[0, 1, 3, 10] {
+        cmap.write_u16::<BigEndian>(value).unwrap()
+    }
+    cmap.write_u32::<BigEndian>(12).unwrap();
+    cmap.write_u16::<BigEndian>(12).unwrap();
+    cmap.write_u16::<BigEndian>(0).unwrap();
+    cmap.write_u32::<BigEndian>(16).unwrap();
+    cmap.write_u32::<BigEndian>(0).unwrap();
+    cmap.write_u32::<BigEndian>(0xffffffff).unwrap();
+
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"cmap"), cmap);
+    let bytes = font_builder.build().unwrap();
+    let font = FontData::new(&bytes);
+    assert!(font.cmap_table().unwrap().glyph_for_codepoint(0x41).is_err());
+}
@@ -8,6 +8,72 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+#[cfg(feature = "std")]
 mod atlas;
+#[cfg(feature = "std")]
+mod autohint;
+mod binary;
+#[cfg(feature = "std")]
+mod bitmap;
+#[cfg(feature = "gpu")]
 mod buffers;
+#[cfg(feature = "std")]
+mod checksum;
+#[cfg(feature = "std")]
+mod collection;
+#[cfg(feature = "std")]
+mod color;
+#[cfg(feature = "std")]
+mod dfont;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
+mod font;
+#[cfg(feature = "std")]
+mod hinting;
+#[cfg(feature = "std")]
+mod limits;
+#[cfg(feature = "std")]
+mod loader;
+#[cfg(feature = "std")]
+mod malformed;
+#[cfg(feature = "std")]
+mod paint;
+#[cfg(feature = "std")]
+mod rendering;
+#[cfg(feature = "std")]
+mod sanitizer;
+#[cfg(feature = "std")]
+mod subset;
+#[cfg(feature = "std")]
+mod svg;
+#[cfg(feature = "std")]
+mod tag;
+#[cfg(feature = "std")]
+mod type1;
+#[cfg(feature = "std")]
+mod woff;
+#[cfg(feature = "std")]
+mod woff2;
+#[cfg(feature = "std")]
+mod writer;
 
+
+#[cfg(feature = "std")]
+use otf::FontData;
+
+#[cfg(feature = "std")]
+pub static TEST_FONT_PATH: &'static str = "resources/tests/nimbus-sans/NimbusSanL-Regu.ttf";
+
+/// Returns the outline of a glyph as `(x, y, on_curve)` triples, for comparing fonts.
+#[cfg(feature = "std")]
+pub fn glyph_points(font: &FontData, glyph_id: u32) -> Vec<(i16, i16, bool)> {
+    let glyf = font.glyf_table().unwrap();
+    let head = font.head_table().unwrap();
+    let loca = font.loca_table(&head).unwrap();
+    let mut points = vec![];
+    glyf.for_each_point(&loca, glyph_id, |point| {
+        points.push((point.position.x, point.position.y, point.on_curve))
+    }).unwrap();
+    points
+}
@@ -0,0 +1,702 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use error::Error;
+use euclid::{Point2D, Rect, Size2D};
+use limits::{Limit, Limits};
+use otf::{FontData, Tag};
+use otf::colr::{ColorLine, ColorStop, Extend, Paint};
+use otf::cpal::Color;
+use otf::writer::{FontBuilder, GlyfBuilder, HeadBuilder};
+use paint::{ColorGlyphImage, ColorGlyphRenderer};
+use png;
+use std::fs::File;
+use std::io::Read;
+
+const RED: u16 = 0;
+const BLUE: u16 = 1;
+const GRAY: u16 = 2;
+
+const EXTEND_PAD: u8 = 0;
+const EXTEND_REPEAT: u8 = 1;
+const EXTEND_REFLECT: u8 = 2;
+
+// Composite modes, by their values in the `PaintComposite` table.
+const SOURCE_OVER: u8 = 3;
+const XOR: u8 = 11;
+const SCREEN: u8 = 13;
+const OVERLAY: u8 = 14;
+const DARKEN: u8 = 15;
+const HARD_LIGHT: u8 = 19;
+const DIFFERENCE: u8 = 21;
+const MULTIPLY: u8 = 23;
+const LUMINOSITY: u8 = 27;
+
+fn make_cpal() -> Vec<u8> {
+    let mut cpal = vec![];
+    cpal.write_u16::<BigEndian>(0).unwrap();
+    cpal.write_u16::<BigEndian>(3).unwrap();
+    cpal.write_u16::<BigEndian>(1).unwrap();
+    cpal.write_u16::<BigEndian>(3).unwrap();
+    cpal.write_u32::<BigEndian>(14).unwrap();
+    cpal.write_u16::<BigEndian>(0).unwrap();
+    for &color in &[[0, 0, 255, 255], [255, 0, 0, 255], [128, 128, 128, 255]] {
+        cpal.extend_from_slice(&color)
+    }
+    cpal
+}
+
+fn f2dot14(value: f32) -> i16 {
+    (value * 16384.0) as i16
+}
+
+// Writes a 24-bit offset from `parent` to the child that starts at the end of `colr`.
+fn link_child(colr: &mut Vec<u8>, parent: usize, field: usize) {
+    let offset = colr.len() - parent;
+    BigEndian::write_u24(&mut colr[(parent + field)..], offset as u32)
+}
+
+fn solid(colr: &mut Vec<u8>, palette_index: u16, alpha: f32) {
+    colr.push(2);
+    colr.write_u16::<BigEndian>(palette_index).unwrap();
+    colr.write_i16::<BigEndian>(f2dot14(alpha)).unwrap();
+}
+
+fn glyph<F>(colr: &mut Vec<u8>, glyph_id: u16, child: F) where F: FnOnce(&mut Vec<u8>) {
+    let start = colr.len();
+    colr.extend_from_slice(&[10, 0, 0, 0]);
+    colr.write_u16::<BigEndian>(glyph_id).unwrap();
+    link_child(colr, start, 1);
+    child(colr)
+}
+
+// Writes one of the paints that transform a child paint, whose arguments are all 16-bit
+// `FWORD`s or `F2DOT14`s.
+fn transform<F>(colr: &mut Vec<u8>, format: u8, arguments: &[i16], child: F)
+                where F: FnOnce(&mut Vec<u8>) {
+    let start = colr.len();
+    colr.extend_from_slice(&[format, 0, 0, 0]);
+    for &argument in arguments {
+        colr.write_i16::<BigEndian>(argument).unwrap();
+    }
+    link_child(colr, start, 1);
+    child(colr)
+}
+
+fn translate<F>(colr: &mut Vec<u8>, dx: i16, dy: i16, child: F) where F: FnOnce(&mut Vec<u8>) {
+    transform(colr, 14, &[dx, dy], child)
+}
+
+fn composite<F, G>(colr: &mut Vec<u8>, mode: u8, source: F, backdrop: G)
+                   where F: FnOnce(&mut Vec<u8>), G: FnOnce(&mut Vec<u8>) {
+    let start = colr.len();
+    colr.extend_from_slice(&[32, 0, 0, 0, mode, 0, 0, 0]);
+    link_child(colr, start, 1);
+    source(colr);
+    link_child(colr, start, 5);
+    backdrop(colr)
+}
+
+// A color line from red to blue.
+fn color_line(colr: &mut Vec<u8>, extend: u8) {
+    colr.push(extend);
+    colr.write_u16::<BigEndian>(2).unwrap();
+    for &(offset, palette_index) in &[(0.0, RED), (1.0, BLUE)] {
+        colr.write_i16::<BigEndian>(f2dot14(offset)).unwrap();
+        colr.write_u16::<BigEndian>(palette_index).unwrap();
+        colr.write_i16::<BigEndian>(f2dot14(1.0)).unwrap();
+    }
+}
+
+// A gradient from red at `start` to blue at `end`, perpendicular to the line from `start` to
+// `rotation_point`.
+fn linear_gradient_with(colr: &mut Vec<u8>, extend: u8, points: &[i16; 6]) {
+    let start = colr.len();
+    colr.extend_from_slice(&[4, 0, 0, 0]);
+    for &coordinate in points {
+        colr.write_i16::<BigEndian>(coordinate).unwrap();
+    }
+    link_child(colr, start, 1);
+    color_line(colr, extend)
+}
+
+// A gradient across the square, from red on the left to blue on the right.
+fn linear_gradient(colr: &mut Vec<u8>) {
+    linear_gradient_with(colr, EXTEND_PAD, &[0, 0, 8, 0, 0, 8])
+}
+
+// A gradient from red on the circle with center `(x0, y0)` and radius `r0` to blue on the one
+// with center `(x1, y1)` and radius `r1`.
+fn radial_gradient(colr: &mut Vec<u8>, extend: u8, circles: &[i16; 6]) {
+    let start = colr.len();
+    colr.extend_from_slice(&[6, 0, 0, 0]);
+    for &value in circles {
+        colr.write_i16::<BigEndian>(value).unwrap();
+    }
+    link_child(colr, start, 1);
+    color_line(colr, extend)
+}
+
+// A full turn around the center of the square, from red to blue.
+fn sweep_gradient(colr: &mut Vec<u8>) {
+    let start = colr.len();
+    colr.extend_from_slice(&[8, 0, 0, 0]);
+    colr.write_i16::<BigEndian>(4).unwrap();
+    colr.write_i16::<BigEndian>(4).unwrap();
+    colr.write_i16::<BigEndian>(f2dot14(-1.0)).unwrap();
+    colr.write_i16::<BigEndian>(f2dot14(1.0)).unwrap();
+    link_child(colr, start, 1);
+    color_line(colr, EXTEND_PAD)
+}
+
+// Glyphs 10 to 14 have version 1 paints, and glyph 20 has version 0 layers:
+//
+// * 10: a red square.
+// * 11: a square with a linear gradient from red on the left to blue on the right.
+// * 12: layers of a red square and a translucent blue square moved right by half its width,
+//   clipped to the union of both.
+// * 13: a gray square multiplied onto a red square.
+// * 14: a square with a sweep gradient.
+// * 20: a blue square.
+fn make_colr() -> Vec<u8> {
+    let base_glyphs = [10, 11, 12, 13, 14];
+    let base_glyph_list_offset = 34;
+    let layer_list_offset = base_glyph_list_offset + 4 + base_glyphs.len() * 6;
+    let clip_list_offset = layer_list_offset + 4 + 2 * 4;
+    let clip_box_offset = 5 + 7;
+    let v0_offset = clip_list_offset + clip_box_offset + 9;
+
+    let mut colr = vec![];
+    colr.write_u16::<BigEndian>(1).unwrap();
+    colr.write_u16::<BigEndian>(1).unwrap();
+    colr.write_u32::<BigEndian>(v0_offset as u32).unwrap();
+    colr.write_u32::<BigEndian>(v0_offset as u32 + 6).unwrap();
+    colr.write_u16::<BigEndian>(1).unwrap();
+    colr.write_u32::<BigEndian>(base_glyph_list_offset as u32).unwrap();
+    colr.write_u32::<BigEndian>(layer_list_offset as u32).unwrap();
+    colr.write_u32::<BigEndian>(clip_list_offset as u32).unwrap();
+    colr.write_u32::<BigEndian>(0).unwrap();
+    colr.write_u32::<BigEndian>(0).unwrap();
+
+    // Leave room for the base glyph and layer lists, and fill them in once the paints are
+    // written.
+    colr.write_u32::<BigEndian>(base_glyphs.len() as u32).unwrap();
+    colr.extend_from_slice(&vec![0; base_glyphs.len() * 6]);
+    colr.write_u32::<BigEndian>(2).unwrap();
+    colr.extend_from_slice(&[0; 2 * 4]);
+
+    colr.push(1);
+    colr.write_u32::<BigEndian>(1).unwrap();
+    colr.write_u16::<BigEndian>(12).unwrap();
+    colr.write_u16::<BigEndian>(12).unwrap();
+    colr.write_u24::<BigEndian>(clip_box_offset as u32).unwrap();
+    colr.push(1);
+    for &value in &[0, 0, 12, 8] {
+        colr.write_i16::<BigEndian>(value).unwrap();
+    }
+
+    colr.write_u16::<BigEndian>(20).unwrap();
+    colr.write_u16::<BigEndian>(0).unwrap();
+    colr.write_u16::<BigEndian>(1).unwrap();
+    colr.write_u16::<BigEndian>(1).unwrap();
+    colr.write_u16::<BigEndian>(BLUE).unwrap();
+
+    let mut paints = vec![];
+    paints.push(colr.len());
+    glyph(&mut colr, 1, |colr| solid(colr, RED, 1.0));
+
+    paints.push(colr.len());
+    glyph(&mut colr, 1, linear_gradient);
+
+    paints.push(colr.len());
+    colr.push(1);
+    colr.push(2);
+    colr.write_u32::<BigEndian>(0).unwrap();
+
+    paints.push(colr.len());
+    composite(&mut colr,
+              MULTIPLY,
+              |colr| glyph(colr, 1, |colr| solid(colr, GRAY, 1.0)),
+              |colr| glyph(colr, 1, |colr| solid(colr, RED, 1.0)));
+
+    paints.push(colr.len());
+    glyph(&mut colr, 1, sweep_gradient);
+
+    let mut layers = vec![];
+    layers.push(colr.len());
+    glyph(&mut colr, 1, |colr| solid(colr, RED, 1.0));
+    layers.push(colr.len());
+    translate(&mut colr, 4, 0, |colr| glyph(colr, 1, |colr| solid(colr, BLUE, 0.5)));
+
+    for (index, (&glyph_id, &paint)) in base_glyphs.iter().zip(paints.iter()).enumerate() {
+        let record = base_glyph_list_offset + 4 + index * 6;
+        BigEndian::write_u16(&mut colr[record..], glyph_id);
+        BigEndian::write_u32(&mut colr[(record + 2)..], (paint - base_glyph_list_offset) as u32);
+    }
+    for (index, &paint) in layers.iter().enumerate() {
+        let record = layer_list_offset + 4 + index * 4;
+        BigEndian::write_u32(&mut colr[record..], (paint - layer_list_offset) as u32);
+    }
+
+    colr
+}
+
+fn make_font() -> Vec<u8> {
+    make_font_with_colr(&make_colr())
+}
+
+// Builds a `COLR` table with only version 1 paints: a base glyph list, whose glyphs must be added
+// in order, and a layer list.
+struct ColrBuilder {
+    paints: Vec<u8>,
+    base_glyphs: Vec<(u16, usize)>,
+    layers: Vec<usize>,
+}
+
+impl ColrBuilder {
+    fn new() -> ColrBuilder {
+        ColrBuilder {
+            paints: vec![],
+            base_glyphs: vec![],
+            layers: vec![],
+        }
+    }
+
+    fn add_glyph<F>(&mut self, glyph_id: u16, paint: F) where F: FnOnce(&mut Vec<u8>) {
+        self.base_glyphs.push((glyph_id, self.paints.len()));
+        paint(&mut self.paints)
+    }
+
+    // Adds a layer painted by `paint`, and returns its index.
+    fn add_layer<F>(&mut self, paint: F) -> u32 where F: FnOnce(&mut Vec<u8>) {
+        self.layers.push(self.paints.len());
+        paint(&mut self.paints);
+        self.layers.len() as u32 - 1
+    }
+
+    // Adds a layer that reuses the paint of another.
+    fn repeat_layer(&mut self, layer_index: u32) -> u32 {
+        let paint = self.layers[layer_index as usize];
+        self.layers.push(paint);
+        self.layers.len() as u32 - 1
+    }
+
+    fn build(&self) -> Vec<u8> {
+        let base_glyph_list_offset = 34;
+        let layer_list_offset = base_glyph_list_offset + 4 + self.base_glyphs.len() * 6;
+        let paints_offset = layer_list_offset + 4 + self.layers.len() * 4;
+
+        let mut colr = vec![];
+        colr.write_u16::<BigEndian>(1).unwrap();
+        colr.extend_from_slice(&[0; 12]);
+        colr.write_u32::<BigEndian>(base_glyph_list_offset as u32).unwrap();
+        colr.write_u32::<BigEndian>(layer_list_offset as u32).unwrap();
+        colr.extend_from_slice(&[0; 12]);
+
+        colr.write_u32::<BigEndian>(self.base_glyphs.len() as u32).unwrap();
+        for &(glyph_id, paint) in &self.base_glyphs {
+            let offset = paints_offset + paint - base_glyph_list_offset;
+            colr.write_u16::<BigEndian>(glyph_id).unwrap();
+            colr.write_u32::<BigEndian>(offset as u32).unwrap();
+        }
+        colr.write_u32::<BigEndian>(self.layers.len() as u32).unwrap();
+        for &paint in &self.layers {
+            colr.write_u32::<BigEndian>((paints_offset + paint - layer_list_offset) as u32)
+                .unwrap();
+        }
+        colr.extend_from_slice(&self.paints);
+        colr
+    }
+}
+
+// Makes a font with the given `COLR` table, in which glyph 1 is an 8x8 square. With 16 units per
+// em, one unit is one pixel at 16 pixels per em.
+fn make_font_with_colr(colr: &[u8]) -> Vec<u8> {
+    let mut glyf_builder = GlyfBuilder::new();
+    glyf_builder.add_empty_glyph();
+    glyf_builder.add_simple_glyph(&[&[(0, 0, true), (8, 0, true), (8, 8, true), (0, 8, true)]],
+                                  &[]);
+    let (glyf, loca, index_to_loc_format) = glyf_builder.build();
+    let mut head_builder = HeadBuilder::new();
+    head_builder.units_per_em = 16;
+    head_builder.bounds = (0, 0, 8, 8);
+    head_builder.index_to_loc_format = index_to_loc_format;
+
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"COLR"), colr);
+    font_builder.add_table(Tag::new(b"CPAL"), make_cpal());
+    font_builder.add_table(Tag::new(b"glyf"), glyf);
+    font_builder.add_table(Tag::new(b"head"), head_builder.build());
+    font_builder.add_table(Tag::new(b"loca"), loca);
+    font_builder.build().unwrap()
+}
+
+fn make_renderer<'a>(font: &'a FontData<'a>) -> ColorGlyphRenderer<'a> {
+    let head = font.head_table().unwrap();
+    ColorGlyphRenderer::new(font.colr_table().unwrap(),
+                            font.glyf_table().unwrap(),
+                            font.loca_table(&head).unwrap(),
+                            &head,
+                            font.cpal_table().unwrap().palette(0).unwrap(),
+                            Color::new(0, 0, 0, 255))
+}
+
+fn assert_pixel_near(actual: [u8; 4], expected: [u8; 4]) {
+    for (&actual_component, &expected_component) in actual.iter().zip(expected.iter()) {
+        assert!((actual_component as i32 - expected_component as i32).abs() <= 1,
+                "expected {:?}, got {:?}",
+                expected,
+                actual)
+    }
+}
+
+#[test]
+fn colr_v1_paint_graph() {
+    let bytes = make_font();
+    let font = FontData::new(&bytes);
+    let colr = font.colr_table().unwrap();
+    assert_eq!(colr.version, 1);
+
+    let paint = colr.base_glyph_paint(11).unwrap().unwrap();
+    let gradient = match colr.paint(paint).unwrap() {
+        Paint::Glyph { paint, glyph_id } => {
+            assert_eq!(glyph_id, 1);
+            paint
+        }
+        paint => panic!("expected a glyph paint, got {:?}", paint),
+    };
+    assert_eq!(colr.paint(gradient).unwrap(), Paint::LinearGradient {
+        color_line: ColorLine {
+            extend: Extend::Pad,
+            stops: vec![
+                ColorStop { offset: 0.0, palette_index: RED, alpha: 1.0 },
+                ColorStop { offset: 1.0, palette_index: BLUE, alpha: 1.0 },
+            ],
+        },
+        start: Point2D::new(0.0, 0.0),
+        end: Point2D::new(8.0, 0.0),
+        rotation_point: Point2D::new(0.0, 8.0),
+    });
+
+    let paint = colr.base_glyph_paint(12).unwrap().unwrap();
+    assert_eq!(colr.paint(paint).unwrap(),
+               Paint::ColrLayers { first_layer_index: 0, layer_count: 2 });
+    assert!(colr.layer_paint(2).is_err());
+
+    assert_eq!(colr.base_glyph_paint(15).unwrap(), None);
+    assert_eq!(colr.base_glyph_paint(20).unwrap(), None);
+    assert_eq!(colr.clip_box(12).unwrap(),
+               Some(Rect::new(Point2D::new(0.0, 0.0), Size2D::new(12.0, 8.0))));
+    assert_eq!(colr.clip_box(10).unwrap(), None);
+}
+
+#[test]
+fn colr_v1_render() {
+    let bytes = make_font();
+    let font = FontData::new(&bytes);
+    let head = font.head_table().unwrap();
+    let palette = font.cpal_table().unwrap().palette(0).unwrap();
+    let renderer = ColorGlyphRenderer::new(font.colr_table().unwrap(),
+                                           font.glyf_table().unwrap(),
+                                           font.loca_table(&head).unwrap(),
+                                           &head,
+                                           palette,
+                                           Color::new(0, 0, 0, 255));
+
+    let solid = renderer.render(10, 16.0).unwrap().unwrap();
+    assert_eq!((solid.image.width, solid.image.height), (8, 8));
+    assert_eq!(solid.origin, Point2D::new(0, 8));
+    assert_eq!(solid.image.pixel(0, 0), [255, 0, 0, 255]);
+    assert_eq!(solid.image.pixel(7, 7), [255, 0, 0, 255]);
+
+    // The gradient is sampled at pixel centers.
+    let linear = renderer.render(11, 16.0).unwrap().unwrap();
+    assert_pixel_near(linear.image.pixel(0, 0), [239, 0, 16, 255]);
+    assert_pixel_near(linear.image.pixel(7, 5), [16, 0, 239, 255]);
+
+    let layers = renderer.render(12, 16.0).unwrap().unwrap();
+    assert_eq!((layers.image.width, layers.image.height), (12, 8));
+    assert_eq!(layers.image.pixel(1, 1), [255, 0, 0, 255]);
+    assert_pixel_near(layers.image.pixel(6, 1), [128, 0, 128, 255]);
+    assert_pixel_near(layers.image.pixel(10, 1), [0, 0, 128, 128]);
+
+    let multiplied = renderer.render(13, 16.0).unwrap().unwrap();
+    assert_pixel_near(multiplied.image.pixel(4, 4), [128, 0, 0, 255]);
+
+    // Angles increase counterclockwise from the positive x axis.
+    let sweep = renderer.render(14, 16.0).unwrap().unwrap();
+    assert_pixel_near(sweep.image.pixel(7, 3), [249, 0, 6, 255]);
+    assert_pixel_near(sweep.image.pixel(0, 4), [122, 0, 133, 255]);
+
+    let fallback = renderer.render(20, 16.0).unwrap().unwrap();
+    assert_eq!((fallback.image.width, fallback.image.height), (8, 8));
+    assert_eq!(fallback.image.pixel(3, 3), [0, 0, 255, 255]);
+
+    assert_eq!(renderer.render(15, 16.0).unwrap(), None);
+}
+
+#[test]
+fn colr_render_limits_image_size() {
+    let bytes = make_font();
+    let font = FontData::new(&bytes);
+    let head = font.head_table().unwrap();
+    let palette = font.cpal_table().unwrap().palette(0).unwrap();
+    let mut renderer = ColorGlyphRenderer::new(font.colr_table().unwrap(),
+                                               font.glyf_table().unwrap(),
+                                               font.loca_table(&head).unwrap(),
+                                               &head,
+                                               palette,
+                                               Color::new(0, 0, 0, 255));
+
+    // Both paint graphs and version 0 layers are checked before their buffers are allocated.
+    assert_eq!(renderer.render(10, 1.0e10), Err(Error::LimitExceeded(Limit::ImageSize)));
+    renderer.limits = Limits {
+        max_image_size: 11,
+        ..Limits::default()
+    };
+    assert!(renderer.render(10, 16.0).unwrap().is_some());
+    assert_eq!(renderer.render(12, 16.0), Err(Error::LimitExceeded(Limit::ImageSize)));
+    assert_eq!(renderer.render(20, 32.0), Err(Error::LimitExceeded(Limit::ImageSize)));
+}
+
+fn colr_layers(colr: &mut Vec<u8>, layer_count: u8, first_layer_index: u32) {
+    colr.push(1);
+    colr.push(layer_count);
+    colr.write_u32::<BigEndian>(first_layer_index).unwrap();
+}
+
+// Glyphs 30 to 36 fill the square with gradients or transform it:
+//
+// * 30: a radial gradient from red at the center to blue at the edges, padded into the corners.
+// * 31: a radial gradient from a red point left of center to the same blue circle.
+// * 32 and 33: a linear gradient from red on the left edge to blue a quarter of the way across,
+//   repeated and reflected.
+// * 34: a red square rotated 45° counterclockwise around its center.
+// * 35: a linear gradient from red on the left edge to blue on the right, scaled to half size
+//   around the center of the square.
+// * 36: a red square skewed 45° around its center, so that its top leans left.
+fn make_gradient_and_transform_font() -> Vec<u8> {
+    let mut builder = ColrBuilder::new();
+    builder.add_glyph(30, |colr| {
+        glyph(colr, 1, |colr| radial_gradient(colr, EXTEND_PAD, &[4, 4, 0, 4, 4, 4]))
+    });
+    builder.add_glyph(31, |colr| {
+        glyph(colr, 1, |colr| radial_gradient(colr, EXTEND_PAD, &[2, 4, 0, 4, 4, 4]))
+    });
+    builder.add_glyph(32, |colr| {
+        glyph(colr, 1, |colr| linear_gradient_with(colr, EXTEND_REPEAT, &[0, 0, 2, 0, 0, 8]))
+    });
+    builder.add_glyph(33, |colr| {
+        glyph(colr, 1, |colr| linear_gradient_with(colr, EXTEND_REFLECT, &[0, 0, 2, 0, 0, 8]))
+    });
+    builder.add_glyph(34, |colr| {
+        transform(colr, 26, &[f2dot14(0.25), 4, 4], |colr| {
+            glyph(colr, 1, |colr| solid(colr, RED, 1.0))
+        })
+    });
+    builder.add_glyph(35, |colr| {
+        transform(colr, 18, &[f2dot14(0.5), f2dot14(0.5), 4, 4], |colr| {
+            glyph(colr, 1, linear_gradient)
+        })
+    });
+    builder.add_glyph(36, |colr| {
+        transform(colr, 30, &[f2dot14(0.25), 0, 4, 4], |colr| {
+            glyph(colr, 1, |colr| solid(colr, RED, 1.0))
+        })
+    });
+    make_font_with_colr(&builder.build())
+}
+
+// The blend modes tested by `colr_v1_blend_modes`, with the colors they give a gray source over
+// a red backdrop.
+const BLEND_MODES: [(u8, [u8; 4]); 9] = [
+    (SOURCE_OVER, [128, 128, 128, 255]),
+    (XOR, [0, 0, 0, 0]),
+    (SCREEN, [255, 128, 128, 255]),
+    (OVERLAY, [255, 0, 0, 255]),
+    (DARKEN, [128, 0, 0, 255]),
+    (HARD_LIGHT, [255, 1, 1, 255]),
+    (DIFFERENCE, [127, 128, 128, 255]),
+    (MULTIPLY, [128, 0, 0, 255]),
+    (LUMINOSITY, [255, 74, 74, 255]),
+];
+
+// Glyph `40 + i` composites a gray square, moved right by half its width, onto a red square with
+// the `i`th of `BLEND_MODES`.
+fn make_blend_mode_font() -> Vec<u8> {
+    let mut builder = ColrBuilder::new();
+    for (index, &(mode, _)) in BLEND_MODES.iter().enumerate() {
+        builder.add_glyph(40 + index as u16, |colr| {
+            composite(colr,
+                      mode,
+                      |colr| translate(colr, 4, 0, |colr| glyph(colr, 1, |colr| {
+                          solid(colr, GRAY, 1.0)
+                      })),
+                      |colr| glyph(colr, 1, |colr| solid(colr, RED, 1.0)))
+        });
+    }
+    make_font_with_colr(&builder.build())
+}
+
+// Returns the color of the pixel at the given point in font units, in an image rendered at one
+// pixel per unit.
+fn pixel_at(image: &ColorGlyphImage, x: f32, y: f32) -> [u8; 4] {
+    image.image.pixel((image.origin.x as f32 + x) as u32, (image.origin.y as f32 - y) as u32)
+}
+
+#[test]
+fn colr_v1_radial_gradients() {
+    let bytes = make_gradient_and_transform_font();
+    let font = FontData::new(&bytes);
+    let renderer = make_renderer(&font);
+
+    // The position on the color line is the distance from the center, over 4.
+    let concentric = renderer.render(30, 16.0).unwrap().unwrap();
+    assert_eq!((concentric.image.width, concentric.image.height), (8, 8));
+    assert_pixel_near(pixel_at(&concentric, 4.5, 4.5), [210, 0, 45, 255]);
+    assert_pixel_near(pixel_at(&concentric, 6.5, 4.5), [93, 0, 162, 255]);
+    assert_eq!(pixel_at(&concentric, 0.5, 0.5), [0, 0, 255, 255]);
+    assert_eq!(pixel_at(&concentric, 7.5, 4.5), concentric.image.pixel(0, 3));
+
+    // With the focus left of center, the gradient is compressed on the left.
+    let focal = renderer.render(31, 16.0).unwrap().unwrap();
+    assert_pixel_near(pixel_at(&focal, 2.5, 4.5), [220, 0, 35, 255]);
+    let (left, right) = (pixel_at(&focal, 1.5, 4.5), pixel_at(&focal, 3.5, 4.5));
+    assert!(left[2] > right[2], "{:?} should be bluer than {:?}", left, right);
+    assert_eq!(pixel_at(&focal, 7.5, 0.5), [0, 0, 255, 255]);
+}
+
+#[test]
+fn colr_v1_extend_modes() {
+    let bytes = make_gradient_and_transform_font();
+    let font = FontData::new(&bytes);
+    let renderer = make_renderer(&font);
+
+    // Repeating starts over at red every two units; reflecting runs back from blue to red.
+    let repeat = renderer.render(32, 16.0).unwrap().unwrap();
+    let reflect = renderer.render(33, 16.0).unwrap().unwrap();
+    for &x in &[0.5, 1.5] {
+        for period in 1..4 {
+            let repeated = pixel_at(&repeat, x + period as f32 * 2.0, 4.5);
+            assert_eq!(repeated, pixel_at(&repeat, x, 4.5));
+            let reflected_x = if period % 2 == 0 { x } else { 2.0 - x };
+            let reflected = pixel_at(&reflect, reflected_x + period as f32 * 2.0, 4.5);
+            assert_eq!(reflected, pixel_at(&reflect, x, 4.5));
+        }
+    }
+    assert_pixel_near(pixel_at(&repeat, 0.5, 4.5), [191, 0, 64, 255]);
+    assert_pixel_near(pixel_at(&repeat, 1.5, 4.5), [64, 0, 191, 255]);
+}
+
+#[test]
+fn colr_v1_transforms() {
+    let bytes = make_gradient_and_transform_font();
+    let font = FontData::new(&bytes);
+    let renderer = make_renderer(&font);
+
+    // The rotated square is a diamond whose corners are 4√2 units from the center.
+    let rotated = renderer.render(34, 16.0).unwrap().unwrap();
+    assert_eq!((rotated.image.width, rotated.image.height), (12, 12));
+    assert_eq!(pixel_at(&rotated, 4.5, 4.5), [255, 0, 0, 255]);
+    assert_eq!(pixel_at(&rotated, 7.5, 4.5), [255, 0, 0, 255]);
+    assert_eq!(pixel_at(&rotated, 0.5, 0.5), [0, 0, 0, 0]);
+    assert_eq!(pixel_at(&rotated, 8.5, 8.5), [0, 0, 0, 0]);
+
+    // The scaled square covers the middle half, with the whole gradient across it.
+    let scaled = renderer.render(35, 16.0).unwrap().unwrap();
+    assert_eq!((scaled.image.width, scaled.image.height), (4, 4));
+    assert_eq!(scaled.origin, Point2D::new(-2, 6));
+    assert_pixel_near(pixel_at(&scaled, 2.5, 4.5), [223, 0, 32, 255]);
+    assert_pixel_near(pixel_at(&scaled, 5.5, 4.5), [32, 0, 223, 255]);
+
+    // The skewed square is a parallelogram, shifted 4 units left at the top and right at the
+    // bottom.
+    let skewed = renderer.render(36, 16.0).unwrap().unwrap();
+    assert_eq!((skewed.image.width, skewed.image.height), (16, 8));
+    assert_eq!(pixel_at(&skewed, -2.5, 7.5), [255, 0, 0, 255]);
+    assert_eq!(pixel_at(&skewed, 10.5, 0.5), [255, 0, 0, 255]);
+    assert_eq!(pixel_at(&skewed, 0.5, 0.5), [0, 0, 0, 0]);
+    assert_eq!(pixel_at(&skewed, 7.5, 7.5), [0, 0, 0, 0]);
+}
+
+#[test]
+fn colr_v1_blend_modes() {
+    let bytes = make_blend_mode_font();
+    let font = FontData::new(&bytes);
+    let renderer = make_renderer(&font);
+
+    for (index, &(mode, expected)) in BLEND_MODES.iter().enumerate() {
+        let image = renderer.render(40 + index as u16, 16.0).unwrap().unwrap();
+        assert_eq!((image.image.width, image.image.height), (12, 8), "mode {}", mode);
+        assert_eq!(pixel_at(&image, 2.5, 4.5), [255, 0, 0, 255], "mode {}", mode);
+        assert_pixel_near(pixel_at(&image, 6.5, 4.5), expected);
+        assert_eq!(pixel_at(&image, 10.5, 4.5), [128, 128, 128, 255], "mode {}", mode);
+    }
+}
+
+#[test]
+fn colr_v1_paint_visit_budget() {
+    // Each level of layers paints the level below twice. A few levels are fine, but the number
+    // of paints to visit doubles with each one.
+    let mut builder = ColrBuilder::new();
+    let mut first_layer_index = builder.add_layer(|colr| {
+        glyph(colr, 1, |colr| solid(colr, RED, 1.0))
+    });
+    builder.repeat_layer(first_layer_index);
+    let mut levels = vec![first_layer_index];
+    for _ in 0..24 {
+        let layer_index = builder.add_layer(|colr| colr_layers(colr, 2, first_layer_index));
+        builder.repeat_layer(layer_index);
+        first_layer_index = layer_index;
+        levels.push(layer_index)
+    }
+    builder.add_glyph(50, |colr| colr_layers(colr, 2, levels[4]));
+    builder.add_glyph(51, |colr| colr_layers(colr, 2, levels[24]));
+
+    let bytes = make_font_with_colr(&builder.build());
+    let font = FontData::new(&bytes);
+    let renderer = make_renderer(&font);
+    let image = renderer.render(50, 16.0).unwrap().unwrap();
+    assert_eq!(image.image.pixel(4, 4), [255, 0, 0, 255]);
+    assert_eq!(renderer.render(51, 16.0), Err(Error::Malformed));
+}
+
+#[test]
+fn colr_v1_reference_images() {
+    let bytes = make_gradient_and_transform_font();
+    let font = FontData::new(&bytes);
+    let renderer = make_renderer(&font);
+
+    let references = [
+        (30, "radial-gradient"),
+        (31, "focal-radial-gradient"),
+        (32, "repeat-gradient"),
+        (33, "reflect-gradient"),
+        (34, "rotate"),
+        (35, "scale"),
+        (36, "skew"),
+    ];
+    for &(glyph_id, name) in &references {
+        let mut png = vec![];
+        let mut file = File::open(format!("resources/tests/colr/{}.png", name)).unwrap();
+        file.read_to_end(&mut png).unwrap();
+        let expected = png::decode(&png, &Limits::default()).unwrap();
+        let actual = renderer.render(glyph_id, 64.0).unwrap().unwrap().image;
+        assert_eq!((actual.width, actual.height), (expected.width, expected.height), "{}", name);
+        for y in 0..actual.height {
+            for x in 0..actual.width {
+                let (actual, expected) = (actual.pixel(x, y), expected.pixel(x, y));
+                for (&actual_component, &expected_component) in actual.iter().zip(&expected) {
+                    assert!((actual_component as i32 - expected_component as i32).abs() <= 2,
+                            "{} at ({}, {}): expected {:?}, got {:?}",
+                            name,
+                            x,
+                            y,
+                            expected,
+                            actual);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use byteorder::{BigEndian, WriteBytesExt};
+use memmap::{Mmap, Protection};
+use otf::{FontData, Tag};
+use otf::gasp::{DOGRAY, GRIDFIT, GaspBehavior, GaspRange, SYMMETRIC_GRIDFIT, SYMMETRIC_SMOOTHING};
+use otf::writer::FontBuilder;
+use rendering::{Antialiasing, RenderingMode, RenderingPolicy};
+use software_rasterizer::GrayImage;
+use tests::TEST_FONT_PATH;
+
+fn make_font(tag: &[u8; 4], data: Vec<u8>) -> Vec<u8> {
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(tag), data);
+    font_builder.build().unwrap()
+}
+
+fn make_font_with_gasp(version: u16, ranges: &[(u16, u16)]) -> Vec<u8> {
+    let mut gasp = vec![];
+    gasp.write_u16::<BigEndian>(version).unwrap();
+    gasp.write_u16::<BigEndian>(ranges.len() as u16).unwrap();
+    for &(max_pixels_per_em, behavior) in ranges {
+        gasp.write_u16::<BigEndian>(max_pixels_per_em).unwrap();
+        gasp.write_u16::<BigEndian>(behavior).unwrap();
+    }
+    make_font(b"gasp", gasp)
+}
+
+#[test]
+fn gasp_version_0() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let gasp = font.gasp_table().unwrap();
+    assert_eq!(gasp.version, 0);
+    assert_eq!(gasp.ranges(), [
+        GaspRange { max_pixels_per_em: 8, behavior: DOGRAY },
+        GaspRange { max_pixels_per_em: 16, behavior: GRIDFIT },
+        GaspRange { max_pixels_per_em: 0xffff, behavior: GRIDFIT | DOGRAY },
+    ]);
+    assert_eq!(gasp.behavior(8), Some(DOGRAY));
+    assert_eq!(gasp.behavior(9), Some(GRIDFIT));
+    assert_eq!(gasp.behavior(100), Some(GRIDFIT | DOGRAY));
+
+    // The symmetric flags mean nothing in version 0.
+    let bytes = make_font_with_gasp(0, &[(0xffff, 0xf)]);
+    let font = FontData::new(&bytes);
+    assert_eq!(font.gasp_table().unwrap().behavior(12), Some(GRIDFIT | DOGRAY));
+}
+
+#[test]
+fn gasp_version_1() {
+    let bytes = make_font_with_gasp(1, &[(10, 0x2), (20, 0x5), (40, 0xf)]);
+    let font = FontData::new(&bytes);
+    let gasp = font.gasp_table().unwrap();
+    assert_eq!(gasp.version, 1);
+    assert_eq!(gasp.behavior(10), Some(DOGRAY));
+    assert_eq!(gasp.behavior(11), Some(GRIDFIT | SYMMETRIC_GRIDFIT));
+    assert_eq!(gasp.behavior(40), Some(GaspBehavior::all()));
+    assert_eq!(gasp.behavior(41), Some(GaspBehavior::all()));
+    assert!(gasp.behavior(40).unwrap().contains(SYMMETRIC_SMOOTHING));
+
+    let bytes = make_font_with_gasp(1, &[]);
+    assert!(FontData::new(&bytes).gasp_table().unwrap().behavior(12).is_none());
+
+    // Unknown versions and truncated tables are rejected.
+    let bytes = make_font_with_gasp(2, &[(0xffff, 0x3)]);
+    assert!(FontData::new(&bytes).gasp_table().is_err());
+    let bytes = make_font(b"gasp", vec![0, 1, 0, 2, 0, 8, 0, 3]);
+    assert!(FontData::new(&bytes).gasp_table().is_err());
+}
+
+#[test]
+fn rendering_policy() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let policy = RenderingPolicy::new(&font);
+    assert_eq!(policy.mode(7.0), RenderingMode {
+        grid_fit: false,
+        symmetric_grid_fit: false,
+        antialiasing: Antialiasing::Grayscale,
+    });
+    assert_eq!(policy.mode(12.0), RenderingMode {
+        grid_fit: true,
+        symmetric_grid_fit: false,
+        antialiasing: Antialiasing::Aliased,
+    });
+    assert!(!policy.mode(16.4).is_antialiased());
+    assert!(policy.mode(16.5).is_antialiased());
+    assert!(policy.mode(72.0).grid_fit);
+
+    let bytes = make_font_with_gasp(1, &[(20, 0x5), (40, 0xf)]);
+    let font = FontData::new(&bytes);
+    let policy = RenderingPolicy::new(&font);
+    assert_eq!(policy.mode(12.0), RenderingMode {
+        grid_fit: true,
+        symmetric_grid_fit: true,
+        antialiasing: Antialiasing::Aliased,
+    });
+    assert_eq!(policy.mode(30.0).antialiasing, Antialiasing::SymmetricSmoothing);
+
+    // Sizes past the last range use it.
+    assert_eq!(policy.mode(100.0), policy.mode(30.0));
+
+    // Without a `gasp` table, glyphs are rendered as usual.
+    let bytes = make_font(b"cvt ", vec![0, 0]);
+    let font = FontData::new(&bytes);
+    let policy = RenderingPolicy::new(&font);
+    assert_eq!(policy.mode(12.0), RenderingMode::default());
+    assert_eq!(RenderingMode::default().antialiasing, Antialiasing::Grayscale);
+}
+
+#[test]
+fn threshold_coverage() {
+    let mut image = GrayImage::new(4, 1);
+    image.pixels = vec![0, 127, 128, 255];
+    image.threshold(128);
+    assert_eq!(image.pixels, [0, 0, 255, 255]);
+}
@@ -0,0 +1,202 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use charmap::CodepointRange;
+use error::Error;
+use memmap::{Mmap, Protection};
+use otf::sanitizer;
+use otf::woff::{self, SfntTable};
+use otf::{FontData, SFNT_VERSION_OTTO, SFNT_VERSION_TRUETYPE, Tag};
+use tests::{TEST_FONT_PATH, glyph_points};
+
+// Returns the tags and data of the tables of a font, in directory order.
+fn tables(font: &[u8]) -> Vec<(Tag, Vec<u8>)> {
+    let font = FontData::new(font);
+    font.table_records().unwrap().map(|record| {
+        (record.tag, font.table(record.tag).unwrap().unwrap().bytes.to_vec())
+    }).collect()
+}
+
+fn build(tables: &[(Tag, Vec<u8>)]) -> Vec<u8> {
+    let mut tables: Vec<_> = tables.iter().map(|&(tag, ref data)| {
+        SfntTable { tag: tag.0, checksum: woff::calculate_checksum(data), data: data }
+    }).collect();
+    tables.sort_by_key(|table| table.tag);
+    woff::build_sfnt(SFNT_VERSION_TRUETYPE, &tables).unwrap()
+}
+
+fn tags(font: &[u8]) -> Vec<Tag> {
+    FontData::new(font).table_records().unwrap().map(|record| record.tag).collect()
+}
+
+fn replace_table(tables: &mut Vec<(Tag, Vec<u8>)>, tag: &[u8; 4], data: Vec<u8>) {
+    tables.iter_mut().find(|table| table.0 == Tag::new(tag)).unwrap().1 = data
+}
+
+fn test_font_tables() -> Vec<(Tag, Vec<u8>)> {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    tables(unsafe { file.as_slice() })
+}
+
+#[test]
+fn valid_font_survives() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let original = FontData::new(unsafe { file.as_slice() });
+    let sanitized = sanitizer::sanitize(&original).unwrap();
+
+    assert_eq!(tags(&sanitized), tags(original.bytes));
+    let font = FontData::new(&sanitized);
+    assert!(font.verify_checksums().unwrap().is_ok());
+    for glyph_id in 0..(font.maxp_table().unwrap().num_glyphs as u32) {
+        assert_eq!(glyph_points(&font, glyph_id), glyph_points(&original, glyph_id));
+    }
+
+    // Sanitizing is idempotent.
+    assert_eq!(sanitizer::sanitize(&font).unwrap(), sanitized);
+}
+
+#[test]
+fn unknown_tables_are_dropped() {
+    let mut tables = test_font_tables();
+    let expected_tags: Vec<_> = tables.iter().map(|table| table.0).collect();
+    tables.push((Tag::new(b"DSIG"), vec![0, 0, 0, 1, 0, 0, 0, 0]));
+    tables.push((Tag::new(b"zzzz"), vec![1, 2, 3]));
+
+    let bytes = build(&tables);
+    let sanitized = sanitizer::sanitize(&FontData::new(&bytes)).unwrap();
+    assert_eq!(tags(&sanitized), expected_tags);
+}
+
+#[test]
+fn invalid_tables_are_dropped() {
+    let mut tables = test_font_tables();
+    replace_table(&mut tables, b"OS/2", vec![0, 3, 0, 0]);
+    replace_table(&mut tables, b"gasp", vec![0, 7, 0, 0]);
+    replace_table(&mut tables, b"vmtx", vec![]);
+
+    let bytes = build(&tables);
+    let sanitized = sanitizer::sanitize(&FontData::new(&bytes)).unwrap();
+    let tags = tags(&sanitized);
+    for tag in &[b"OS/2", b"gasp", b"vhea", b"vmtx"] {
+        assert!(!tags.contains(&Tag::new(tag)), "{} kept", Tag::new(tag));
+    }
+    assert!(tags.contains(&Tag::new(b"glyf")));
+    assert!(tags.contains(&Tag::new(b"name")));
+}
+
+#[test]
+fn missing_required_table() {
+    let mut tables = test_font_tables();
+    tables.retain(|table| table.0 != Tag::new(b"hhea"));
+    let bytes = build(&tables);
+    assert_eq!(sanitizer::sanitize(&FontData::new(&bytes)),
+               Err(Error::MissingTable(Tag::new(b"hhea").0)));
+}
+
+#[test]
+fn cff_fonts_are_rejected() {
+    let mut tables = test_font_tables();
+    tables.push((Tag::new(b"CFF "), vec![1, 0, 4, 4]));
+    let bytes = build(&tables);
+    assert_eq!(sanitizer::sanitize(&FontData::new(&bytes)),
+               Err(Error::UnsupportedSfntVersion(SFNT_VERSION_OTTO)));
+}
+
+#[test]
+fn unrebuildable_outlines_are_rejected() {
+    // Too short to hold an offset for every glyph.
+    let mut tables = test_font_tables();
+    replace_table(&mut tables, b"loca", vec![0, 0, 0, 0]);
+    let bytes = build(&tables);
+    assert!(sanitizer::sanitize(&FontData::new(&bytes)).is_err());
+
+    // `glyf` without `loca`.
+    let mut tables = test_font_tables();
+    tables.retain(|table| table.0 != Tag::new(b"loca"));
+    let bytes = build(&tables);
+    assert!(sanitizer::sanitize(&FontData::new(&bytes)).is_err());
+}
+
+#[test]
+fn malformed_glyph_is_emptied() {
+    // 'A' and 'B'.
+    let (bad_glyph_id, good_glyph_id) = (36, 37);
+
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let original = FontData::new(unsafe { file.as_slice() });
+    let head = original.head_table().unwrap();
+    let loca = original.loca_table(&head).unwrap();
+    let glyph_offset = loca.location_of(bad_glyph_id).unwrap() as usize;
+
+    // Claim far more points than the glyph has flags for.
+    let mut tables = tables(original.bytes);
+    let mut glyf = original.glyf_table().unwrap().table.bytes.to_vec();
+    BigEndian::write_u16(&mut glyf[(glyph_offset + 10)..], 0xfffe);
+    replace_table(&mut tables, b"glyf", glyf);
+
+    let bytes = build(&tables);
+    let sanitized = sanitizer::sanitize(&FontData::new(&bytes)).unwrap();
+    let font = FontData::new(&sanitized);
+    assert_eq!(glyph_points(&font, bad_glyph_id), vec![]);
+    assert_eq!(glyph_points(&font, good_glyph_id), glyph_points(&original, good_glyph_id));
+}
+
+#[test]
+fn invalid_cmap_subtables_are_dropped() {
+    // A format 4 subtable mapping 'A'...'Z' to glyphs 36...61.
+    let mut valid_subtable = vec![];
+    for &value in &[4, 32, 0, 4, 4, 1, 0, 0x5a, 0xffff, 0, 0x41, 0xffff, 0xffe3, 1, 0, 0] {
+        valid_subtable.write_u16::<BigEndian>(value).unwrap()
+    }
+    // The same, but with its segments out of order.
+    let mut invalid_subtable = vec![];
+    for &value in &[4, 32, 0, 4, 4, 1, 0, 0xffff, 0x5a, 0, 0xffff, 0x41, 1, 0xffe3, 0, 0] {
+        invalid_subtable.write_u16::<BigEndian>(value).unwrap()
+    }
+
+    // The invalid subtable comes first, so it's the one that would be used.
+    let mut cmap = vec![];
+    for &value in &[0, 2, 0, 3, 0, 20, 3, 1, 0, 52] {
+        cmap.write_u16::<BigEndian>(value).unwrap()
+    }
+    cmap.extend_from_slice(&invalid_subtable);
+    cmap.extend_from_slice(&valid_subtable);
+
+    let mut tables = test_font_tables();
+    replace_table(&mut tables, b"cmap", cmap);
+    let bytes = build(&tables);
+    let sanitized = sanitizer::sanitize(&FontData::new(&bytes)).unwrap();
+
+    let font = FontData::new(&sanitized);
+    let cmap = font.cmap_table().unwrap();
+    let glyph_ranges =
+        cmap.glyph_ranges_for_codepoint_ranges(&[CodepointRange::new(0x41, 0x5a)]).unwrap();
+    assert_eq!(glyph_ranges.iter().map(|range| (range.start, range.end)).collect::<Vec<_>>(),
+               vec![(36, 61)]);
+}
+
+#[test]
+fn cmap_records_with_invalid_offsets_are_dropped() {
+    // A format 4 subtable mapping 'A'...'Z' to glyphs 36...61, after a record pointing past the
+    // end of the table.
+    let mut cmap = vec![];
+    for &value in &[0, 2, 0, 3, 0xffff, 0xfff0, 3, 1, 0, 20] {
+        cmap.write_u16::<BigEndian>(value).unwrap()
+    }
+    for &value in &[4, 32, 0, 4, 4, 1, 0, 0x5a, 0xffff, 0, 0x41, 0xffff, 0xffe3, 1, 0, 0] {
+        cmap.write_u16::<BigEndian>(value).unwrap()
+    }
+
+    let mut tables = test_font_tables();
+    replace_table(&mut tables, b"cmap", cmap);
+    let bytes = build(&tables);
+    let sanitized = sanitizer::sanitize(&FontData::new(&bytes)).unwrap();
+
+    let font = FontData::new(&sanitized);
+    let cmap = font.cmap_table().unwrap();
+    let glyph_ranges =
+        cmap.glyph_ranges_for_codepoint_ranges(&[CodepointRange::new(0x41, 0x5a)]).unwrap();
+    assert_eq!(glyph_ranges.iter().map(|range| (range.start, range.end)).collect::<Vec<_>>(),
+               vec![(36, 61)]);
+}
@@ -0,0 +1,182 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use byteorder::{BigEndian, WriteBytesExt};
+use error::Error;
+use memmap::{Mmap, Protection};
+use otf::{FontData, Tag};
+use otf::sanitizer;
+use otf::subset::SubsetBuilder;
+use otf::writer::{CompositeComponent, FontBuilder, GlyfBuilder, HeadBuilder, HheaBuilder};
+use otf::writer::MaxpBuilder;
+use outline::Transform;
+use tests::{TEST_FONT_PATH, glyph_points};
+
+fn u16s(values: &[u16]) -> Vec<u8> {
+    let mut data = vec![];
+    for &value in values {
+        data.write_u16::<BigEndian>(value).unwrap()
+    }
+    data
+}
+
+// A font with the given glyphs, each advancing by 100 times its glyph ID, and an optional
+// `GSUB` table.
+fn make_font(glyf_builder: &GlyfBuilder, gsub: Option<&[u8]>) -> Vec<u8> {
+    let (glyf, loca, index_to_loc_format) = glyf_builder.build();
+    let mut head_builder = HeadBuilder::new();
+    head_builder.index_to_loc_format = index_to_loc_format;
+    let mut hhea_builder = HheaBuilder::new(800, -200, 0);
+    for glyph_id in 0..glyf_builder.glyph_count() {
+        hhea_builder.add_metrics(glyph_id as u16 * 100, 0)
+    }
+    let (hhea, hmtx) = hhea_builder.build();
+
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"glyf"), glyf);
+    font_builder.add_table(Tag::new(b"head"), head_builder.build());
+    font_builder.add_table(Tag::new(b"hhea"), hhea);
+    font_builder.add_table(Tag::new(b"hmtx"), hmtx);
+    font_builder.add_table(Tag::new(b"loca"), loca);
+    font_builder.add_table(Tag::new(b"maxp"),
+                           MaxpBuilder::new(glyf_builder.glyph_count() as u16).build());
+    if let Some(gsub) = gsub {
+        font_builder.add_table(Tag::new(b"GSUB"), gsub)
+    }
+    font_builder.build().unwrap()
+}
+
+// Adds a triangle `size` units on a side.
+fn add_triangle(glyf_builder: &mut GlyfBuilder, size: i16) -> u16 {
+    glyf_builder.add_simple_glyph(&[&[(0, 0, true), (size, 0, true), (0, size, true)]], &[])
+}
+
+// Glyph 3 is made of glyph 2, which the others don't use.
+fn composite_font() -> Vec<u8> {
+    let mut glyf_builder = GlyfBuilder::new();
+    glyf_builder.add_empty_glyph();
+    for &size in &[100, 200] {
+        add_triangle(&mut glyf_builder, size);
+    }
+    glyf_builder.add_composite_glyph(&[CompositeComponent::new(2,
+                                                               Transform::translation(50.0, 0.0))]);
+    add_triangle(&mut glyf_builder, 300);
+    make_font(&glyf_builder, None)
+}
+
+#[test]
+fn subset_codepoints() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let original = FontData::new(unsafe { file.as_slice() });
+
+    let mut builder = SubsetBuilder::new();
+    for character in "Hello".chars() {
+        builder.add_codepoint(character as u32)
+    }
+    let subset = builder.build(&original).unwrap();
+
+    // `.notdef`, 'H', 'e', 'l', and 'o'.
+    assert_eq!(subset.original_glyph_ids, vec![Some(0), Some(43), Some(72), Some(79), Some(82)]);
+    let font = FontData::new(&subset.data);
+    assert!(font.verify_checksums().unwrap().is_ok());
+    assert!(sanitizer::sanitize(&font).is_ok());
+    assert_eq!(font.maxp_table().unwrap().num_glyphs, 5);
+
+    let cmap = font.cmap_table().unwrap();
+    let hhea = font.hhea_table().unwrap();
+    let hmtx = font.hmtx_table(&hhea).unwrap();
+    let original_hmtx = original.hmtx_table(&original.hhea_table().unwrap()).unwrap();
+    for character in "Hello".chars() {
+        let original_glyph_id = original.cmap_table()
+                                        .unwrap()
+                                        .glyph_for_codepoint(character as u32)
+                                        .unwrap()
+                                        .unwrap();
+        let glyph_id = cmap.glyph_for_codepoint(character as u32).unwrap().unwrap();
+        assert_eq!(subset.glyph_id(original_glyph_id), Some(glyph_id));
+        assert_eq!(glyph_points(&font, glyph_id as u32),
+                   glyph_points(&original, original_glyph_id as u32));
+        assert_eq!(hmtx.metrics(glyph_id).unwrap(),
+                   original_hmtx.metrics(original_glyph_id).unwrap());
+    }
+    assert_eq!(cmap.glyph_for_codepoint('A' as u32).unwrap(), None);
+    assert_eq!(subset.glyph_id(36), None);
+}
+
+#[test]
+fn retain_glyph_ids() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let original = FontData::new(unsafe { file.as_slice() });
+
+    let mut builder = SubsetBuilder::new();
+    builder.retain_glyph_ids = true;
+    builder.add_codepoint('B' as u32);
+    builder.add_glyph_id(40);
+    let subset = builder.build(&original).unwrap();
+
+    let font = FontData::new(&subset.data);
+    assert_eq!(font.maxp_table().unwrap().num_glyphs, 41);
+    assert_eq!(subset.glyph_id(37), Some(37));
+    assert_eq!(font.cmap_table().unwrap().glyph_for_codepoint('B' as u32).unwrap(), Some(37));
+    for glyph_id in 0..41 {
+        let expected = match glyph_id {
+            0 | 37 | 40 => glyph_points(&original, glyph_id),
+            _ => vec![],
+        };
+        assert_eq!(glyph_points(&font, glyph_id), expected);
+    }
+}
+
+#[test]
+fn composite_components_are_kept_and_renumbered() {
+    let bytes = composite_font();
+    let original = FontData::new(&bytes);
+
+    let mut builder = SubsetBuilder::new();
+    builder.add_glyph_id(3);
+    let subset = builder.build(&original).unwrap();
+    assert_eq!(subset.original_glyph_ids, vec![Some(0), Some(2), Some(3)]);
+
+    let font = FontData::new(&subset.data);
+    let glyf = font.glyf_table().unwrap();
+    let loca = font.loca_table(&font.head_table().unwrap()).unwrap();
+    assert_eq!(glyf.components(&loca, 2).unwrap()[0].glyph_id, 1);
+    assert_eq!(glyph_points(&font, 2), glyph_points(&original, 3));
+
+    let hhea = font.hhea_table().unwrap();
+    assert_eq!(font.hmtx_table(&hhea).unwrap().metrics(2).unwrap().advance, 300);
+}
+
+#[test]
+fn gsub_closure() {
+    // A single substitution of glyph 1 with glyph 2.
+    let gsub = u16s(&[1, 0, 0, 0, 10, 1, 4, 1, 0, 1, 8, 1, 6, 1, 1, 1, 1]);
+    let mut glyf_builder = GlyfBuilder::new();
+    glyf_builder.add_empty_glyph();
+    for &size in &[100, 200, 300] {
+        add_triangle(&mut glyf_builder, size);
+    }
+    let bytes = make_font(&glyf_builder, Some(&gsub));
+    let original = FontData::new(&bytes);
+
+    let mut builder = SubsetBuilder::new();
+    builder.add_glyph_id(1);
+    let subset = builder.build(&original).unwrap();
+    assert_eq!(subset.original_glyph_ids, vec![Some(0), Some(1), Some(2)]);
+
+    // The layout tables only survive if glyph IDs do.
+    let font = FontData::new(&subset.data);
+    assert!(font.table(Tag::new(b"GSUB")).unwrap().is_none());
+    builder.retain_glyph_ids = true;
+    let subset = builder.build(&original).unwrap();
+    let font = FontData::new(&subset.data);
+    assert_eq!(font.table(Tag::new(b"GSUB")).unwrap().unwrap().bytes, &gsub[..]);
+}
+
+#[test]
+fn invalid_glyph_id() {
+    let bytes = composite_font();
+    let mut builder = SubsetBuilder::new();
+    builder.add_glyph_id(5);
+    assert_eq!(builder.build(&FontData::new(&bytes)).err(), Some(Error::InvalidGlyphId(5)));
+}
@@ -0,0 +1,180 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+#[cfg(feature = "gpu")]
+use batch::BatchBuilder;
+use byteorder::{BigEndian, WriteBytesExt};
+use error::Error;
+use euclid::Point2D;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use limits::{Limit, Limits};
+use otf::{FontData, Tag};
+use otf::cpal::Color;
+use otf::svg;
+use otf::writer::{FontBuilder, HeadBuilder};
+#[cfg(feature = "gpu")]
+use software_rasterizer::RgbaImage;
+use std::io::Write;
+use svg::SvgGlyphRenderer;
+
+const GREEN: Color = Color { r: 0, g: 255, b: 0, a: 255 };
+
+// Glyph 1 is a red square beside a half-transparent blue one, each 8 units wide; glyph 2 is a
+// foreground-colored square placed by its parent's transform, at half opacity.
+const FIRST_DOCUMENT: &'static str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN" "http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd">
+<svg xmlns="http://www.w3.org/2000/svg" version="1.1">
+  <!-- <path id="glyph1" d="M0 0h100v100z"/> -->
+  <defs><path id="unused" d="M0 0h100v100z"/></defs>
+  <g id="glyph1" fill="#f00">
+    <path d="M0-8h8v8H0z"/>
+    <path d="M8 -8 l8 0 0 8 -8 0 z" style="fill: rgb(0, 0, 255); fill-opacity: 0.5"/>
+  </g>
+  <g transform="translate(0 -16) scale(2)" fill="currentColor">
+    <path id="glyph2" d="M0 0h4v4h-4z" opacity=".5"/>
+  </g>
+</svg>"##;
+
+// Glyph 3 is a 12 unit square with a 6 unit hole, glyph 4 is a circle of radius 8, and glyph 5
+// uses a gradient. Glyph 6 is in range but has no element.
+const SECOND_DOCUMENT: &'static str = r##"<svg xmlns="http://www.w3.org/2000/svg">
+  <g id="glyph3">
+    <path fill-rule="evenodd" fill="black" d="M0 -12 H12 V0 H0 Z M3 -9 H9 V-3 H3 Z"/>
+    <path fill="none" d="M0 0 L100 100"/>
+  </g>
+  <path id="glyph4" fill="#00f" d="M0-8A8 8 0 1 1 16-8 8 8 0 1 1 0-8z"/>
+  <path id="glyph5" fill="url(#gradient)" d="M0 0h4v4h-4z"/>
+</svg>"##;
+
+// The second document is gzip-compressed.
+fn make_svg() -> Vec<u8> {
+    let mut encoder = GzEncoder::new(vec![], Compression::Default);
+    encoder.write_all(SECOND_DOCUMENT.as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let mut svg = vec![];
+    svg.write_u16::<BigEndian>(0).unwrap();
+    svg.write_u32::<BigEndian>(10).unwrap();
+    svg.write_u32::<BigEndian>(0).unwrap();
+    svg.write_u16::<BigEndian>(2).unwrap();
+    let first_offset = 2 + 2 * 12;
+    let second_offset = first_offset + FIRST_DOCUMENT.len();
+    for &(start, end, offset, length) in &[(1, 2, first_offset, FIRST_DOCUMENT.len()),
+                                           (3, 6, second_offset, compressed.len())] {
+        svg.write_u16::<BigEndian>(start).unwrap();
+        svg.write_u16::<BigEndian>(end).unwrap();
+        svg.write_u32::<BigEndian>(offset as u32).unwrap();
+        svg.write_u32::<BigEndian>(length as u32).unwrap();
+    }
+    svg.extend_from_slice(FIRST_DOCUMENT.as_bytes());
+    svg.extend_from_slice(&compressed);
+    svg
+}
+
+fn make_font() -> Vec<u8> {
+    let mut head_builder = HeadBuilder::new();
+    head_builder.units_per_em = 16;
+
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"SVG "), make_svg());
+    font_builder.add_table(Tag::new(b"head"), head_builder.build());
+    font_builder.build().unwrap()
+}
+
+#[test]
+fn svg_documents() {
+    let bytes = make_font();
+    let font = FontData::new(&bytes);
+    let svg_table = font.svg_table().unwrap();
+
+    let first = svg_table.document(2).unwrap().unwrap();
+    assert_eq!((first.start_glyph_id, first.end_glyph_id), (1, 2));
+    assert_eq!(&first.text().unwrap()[..], FIRST_DOCUMENT.as_bytes());
+
+    let second = svg_table.document(5).unwrap().unwrap();
+    assert_eq!((second.start_glyph_id, second.end_glyph_id), (3, 6));
+    assert!(second.data.starts_with(&[0x1f, 0x8b]));
+    assert_eq!(&second.text().unwrap()[..], SECOND_DOCUMENT.as_bytes());
+
+    assert_eq!(svg_table.document(0).unwrap(), None);
+    assert_eq!(svg_table.document(7).unwrap(), None);
+    assert_eq!(svg::glyph_element_id(42), "glyph42");
+}
+
+#[test]
+fn svg_render() {
+    let bytes = make_font();
+    let font = FontData::new(&bytes);
+    let head = font.head_table().unwrap();
+    let renderer = SvgGlyphRenderer::new(font.svg_table().unwrap(), &head, GREEN);
+
+    let squares = renderer.render(1, 16.0).unwrap().unwrap();
+    assert_eq!((squares.image.width, squares.image.height), (16, 8));
+    assert_eq!(squares.origin, Point2D::new(0, 8));
+    assert_eq!(squares.image.pixel(3, 4), [255, 0, 0, 255]);
+    assert_eq!(squares.image.pixel(12, 4), [0, 0, 128, 128]);
+
+    let doubled = renderer.render(1, 32.0).unwrap().unwrap();
+    assert_eq!((doubled.image.width, doubled.image.height), (32, 16));
+    assert_eq!(doubled.origin, Point2D::new(0, 16));
+
+    let transformed = renderer.render(2, 16.0).unwrap().unwrap();
+    assert_eq!((transformed.image.width, transformed.image.height), (8, 8));
+    assert_eq!(transformed.origin, Point2D::new(0, 16));
+    assert_eq!(transformed.image.pixel(4, 4), [0, 128, 0, 128]);
+
+    let frame = renderer.render(3, 16.0).unwrap().unwrap();
+    assert_eq!((frame.image.width, frame.image.height), (12, 12));
+    assert_eq!(frame.image.pixel(1, 1), [0, 0, 0, 255]);
+    assert_eq!(frame.image.pixel(6, 6), [0, 0, 0, 0]);
+
+    // The area of the circle should be close to πr², less what flattening cuts off.
+    let circle = renderer.render(4, 16.0).unwrap().unwrap();
+    let center = Point2D::new(circle.origin.x as u32 + 8, circle.origin.y as u32 - 8);
+    assert_eq!(circle.image.pixel(center.x, center.y), [0, 0, 255, 255]);
+    assert_eq!(circle.image.pixel(center.x - 8, center.y - 8), [0, 0, 0, 0]);
+    let area = circle.image.pixels.chunks(4).map(|pixel| pixel[3] as f32 / 255.0).sum::<f32>();
+    assert!((area - 64.0 * ::std::f32::consts::PI).abs() < 3.0);
+
+    assert!(renderer.render(5, 16.0).is_err());
+    assert_eq!(renderer.render(6, 16.0).unwrap(), None);
+    assert_eq!(renderer.render(7, 16.0).unwrap(), None);
+}
+
+#[cfg(feature = "gpu")]
+#[test]
+fn batch_svg_glyphs() {
+    let bytes = make_font();
+    let font = FontData::new(&bytes);
+    let head = font.head_table().unwrap();
+    let renderer = SvgGlyphRenderer::new(font.svg_table().unwrap(), &head, GREEN);
+    let glyph = renderer.render(1, 16.0).unwrap().unwrap();
+
+    let mut batch_builder = BatchBuilder::new(64, 16);
+    let mut color_atlas_pixels = RgbaImage::new(64, 32);
+    let first = batch_builder.add_rendered_color_glyph(&glyph, &mut color_atlas_pixels).unwrap();
+    let second = batch_builder.add_rendered_color_glyph(&glyph, &mut color_atlas_pixels).unwrap();
+    assert_eq!(first, Point2D::new(0, 0));
+    assert_eq!(color_atlas_pixels.pixel(3, 4), [255, 0, 0, 255]);
+    assert_eq!(color_atlas_pixels.pixel(second.x + 12, second.y + 4), [0, 0, 128, 128]);
+}
+
+#[test]
+fn svg_render_limits_image_size() {
+    let bytes = make_font();
+    let font = FontData::new(&bytes);
+    let head = font.head_table().unwrap();
+    let mut renderer = SvgGlyphRenderer::new(font.svg_table().unwrap(), &head, GREEN);
+
+    // Coordinates this far out don't fit in an `i32`, let alone an image.
+    assert_eq!(renderer.render(1, 1.0e10), Err(Error::LimitExceeded(Limit::ImageSize)));
+    assert_eq!(renderer.render(1, -1.0e10), Err(Error::LimitExceeded(Limit::ImageSize)));
+
+    renderer.limits = Limits {
+        max_image_size: 15,
+        ..Limits::default()
+    };
+    assert_eq!(renderer.render(1, 16.0), Err(Error::LimitExceeded(Limit::ImageSize)));
+    assert!(renderer.render(3, 16.0).unwrap().is_some());
+}
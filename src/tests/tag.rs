@@ -0,0 +1,67 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use error::Error;
+use memmap::{Mmap, Protection};
+use otf::font::Font;
+use otf::{FontData, Tag};
+use tests::TEST_FONT_PATH;
+
+#[test]
+fn parse_and_display_tags() {
+    let cmap: Tag = "cmap".parse().unwrap();
+    assert_eq!(cmap, Tag(0x636d6170));
+    assert_eq!(cmap, Tag::new(b"cmap"));
+    assert_eq!(cmap.to_bytes(), *b"cmap");
+    assert_eq!(cmap.to_string(), "cmap");
+    assert_eq!(format!("{:?}", cmap), "Tag(\"cmap\")");
+
+    // Short tags are padded with spaces.
+    assert_eq!("cvt".parse::<Tag>().unwrap(), Tag::new(b"cvt "));
+    assert_eq!("cvt".parse::<Tag>().unwrap().to_string(), "cvt ");
+
+    assert_eq!("".parse::<Tag>(), Err(Error::InvalidArgument));
+    assert_eq!("glyph".parse::<Tag>(), Err(Error::InvalidArgument));
+    assert_eq!("mét".parse::<Tag>(), Err(Error::InvalidArgument));
+    assert_eq!(Tag(0x41000142).to_string(), "A\\x00\\x01B");
+}
+
+#[test]
+fn enumerate_table_records() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let font = FontData::new(unsafe { file.as_slice() });
+    let records = font.table_records().unwrap();
+    assert_eq!(records.len(), 16);
+
+    let tags: Vec<_> = records.clone().map(|record| record.tag.to_string()).collect();
+    assert_eq!(tags, ["OS/2", "cmap", "cvt ", "fpgm", "gasp", "glyf", "head", "hhea", "hmtx",
+                      "loca", "maxp", "name", "post", "prep", "vhea", "vmtx"]);
+    for record in records {
+        let table = font.table(record.tag).unwrap().unwrap();
+        assert_eq!(table.bytes.len(), record.length as usize);
+        assert_eq!(table.bytes.as_ptr(), font.bytes[(record.offset as usize)..].as_ptr());
+    }
+
+    // Tables the crate doesn't parse are still available raw.
+    let name = font.table("name".parse().unwrap()).unwrap().unwrap();
+    assert_eq!(&name.bytes[0..2], &[0, 0]);
+    assert!(font.table("STAT".parse().unwrap()).unwrap().is_none());
+
+    // `Font` keeps the same records.
+    let owned = Font::new(font.bytes.to_vec()).unwrap();
+    let owned_tags: Vec<_> = owned.table_records().iter().map(|record| record.tag).collect();
+    let tags: Vec<_> = font.table_records().unwrap().map(|record| record.tag).collect();
+    assert_eq!(owned_tags, tags);
+    assert_eq!(owned.table(Tag::new(b"post")).unwrap().unwrap().bytes,
+               font.table(Tag::new(b"post")).unwrap().unwrap().bytes);
+    assert!(owned.table(Tag::new(b"DSIG")).unwrap().is_none());
+}
+
+#[test]
+fn truncated_table_directory() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let bytes = unsafe { file.as_slice() };
+    let font = FontData::new(&bytes[0..100]);
+    assert_eq!(font.table_records().err(), Some(Error::UnexpectedEof));
+    assert_eq!(Font::new(bytes[0..100].to_vec()).err(), Some(Error::UnexpectedEof));
+}
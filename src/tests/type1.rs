@@ -0,0 +1,288 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+use charmap::CodepointRange;
+use euclid::Point2D;
+use otf::loader::FontFormat;
+use outline::PathCommand;
+use type1::Type1Font;
+
+const HSBW: u8 = 13;
+const RMOVETO: u8 = 21;
+const RLINETO: u8 = 5;
+const HLINETO: u8 = 6;
+const VLINETO: u8 = 7;
+const RRCURVETO: u8 = 8;
+const VHCURVETO: u8 = 30;
+const HVCURVETO: u8 = 31;
+const CLOSEPATH: u8 = 9;
+const CALLSUBR: u8 = 10;
+const RETURN: u8 = 11;
+const ENDCHAR: u8 = 14;
+const HSTEM: u8 = 1;
+const ESCAPE: u8 = 12;
+const SEAC: u8 = 6;
+const DIV: u8 = 12;
+const CALLOTHERSUBR: u8 = 16;
+const POP: u8 = 17;
+const SETCURRENTPOINT: u8 = 33;
+
+static CLEARTEXT: &'static [u8] = b"%!PS-AdobeFont-1.0: TestFont 001.000
+12 dict begin
+/FontName /TestFont def
+/FontMatrix [0.001 0 0 0.001 0 0] readonly def
+/Encoding 256 array
+0 1 255 {1 index exch /.notdef put} for
+dup 65 /A put
+dup 66 /B put
+dup 67 /C put
+dup 193 /Aacute put
+readonly def
+currentdict end
+currentfile eexec
+";
+
+enum Item {
+    Number(i32),
+    Operator(u8),
+    Escape(u8),
+}
+
+fn charstring(items: &[Item]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for item in items {
+        match *item {
+            Item::Number(value) if value >= -107 && value <= 107 => {
+                bytes.push((value + 139) as u8)
+            }
+            Item::Number(value) if value >= 108 && value <= 1131 => {
+                bytes.push((247 + (value - 108) / 256) as u8);
+                bytes.push(((value - 108) % 256) as u8)
+            }
+            Item::Number(value) if value >= -1131 && value <= -108 => {
+                bytes.push((251 + (-value - 108) / 256) as u8);
+                bytes.push(((-value - 108) % 256) as u8)
+            }
+            Item::Number(value) => {
+                bytes.push(255);
+                bytes.write_i32::<BigEndian>(value).unwrap()
+            }
+            Item::Operator(operator) => bytes.push(operator),
+            Item::Escape(operator) => {
+                bytes.push(ESCAPE);
+                bytes.push(operator)
+            }
+        }
+    }
+    bytes
+}
+
+fn encrypt(plain: &[u8], key: u16) -> Vec<u8> {
+    let mut r = key;
+    plain.iter().map(|&byte| {
+        let cipher = byte ^ (r >> 8) as u8;
+        r = (cipher as u16).wrapping_add(r).wrapping_mul(52845).wrapping_add(22719);
+        cipher
+    }).collect()
+}
+
+fn encrypted_charstring(items: &[Item]) -> Vec<u8> {
+    let mut plain = vec![0x12, 0x34, 0x56, 0x78];
+    plain.extend_from_slice(&charstring(items));
+    encrypt(&plain, 4330)
+}
+
+// Builds the encrypted portion: the standard flex and hint replacement subrs, one more subr with
+// hints in it, and glyphs exercising lines, curves, `div`, flex, hint replacement, and `seac`.
+fn make_private() -> Vec<u8> {
+    use self::Item::{Escape as E, Number as N, Operator as O};
+
+    let subrs = vec![
+        encrypted_charstring(&[N(3), N(0), E(CALLOTHERSUBR), E(POP), E(POP),
+                               E(SETCURRENTPOINT), O(RETURN)]),
+        encrypted_charstring(&[N(0), N(1), E(CALLOTHERSUBR), O(RETURN)]),
+        encrypted_charstring(&[N(0), N(2), E(CALLOTHERSUBR), O(RETURN)]),
+        encrypted_charstring(&[O(RETURN)]),
+        encrypted_charstring(&[N(1), N(3), E(CALLOTHERSUBR), E(POP), O(CALLSUBR), O(RETURN)]),
+        encrypted_charstring(&[N(100), N(50), O(HSTEM), O(RETURN)]),
+    ];
+
+    let mut flex = vec![N(0), N(1000), O(HSBW), N(0), N(0), O(RMOVETO), N(1), O(CALLSUBR)];
+    for &(dx, dy) in &[(300, -20), (-200, 20), (100, -20), (100, 0), (100, 0), (100, 20),
+                       (100, 0)] {
+        flex.extend(vec![N(dx), N(dy), O(RMOVETO), N(2), O(CALLSUBR)]);
+    }
+    flex.extend(vec![N(50), N(600), N(0), N(0), O(CALLSUBR), N(0), N(100), O(RLINETO),
+                     O(CLOSEPATH), O(ENDCHAR)]);
+
+    let glyphs = vec![
+        ("A", encrypted_charstring(&[N(50), N(600), O(HSBW), N(5), N(4), O(CALLSUBR),
+                                     N(0), N(0), O(RMOVETO), N(400), O(HLINETO),
+                                     N(700), O(VLINETO), N(-400), N(0), O(RLINETO),
+                                     O(CLOSEPATH), O(ENDCHAR)])),
+        (".notdef", encrypted_charstring(&[N(0), N(500), O(HSBW), O(ENDCHAR)])),
+        ("B", encrypted_charstring(&[N(0), N(500), O(HSBW), N(100), N(100), O(RMOVETO),
+                                     N(50), N(0), N(50), N(50), N(0), N(50), O(RRCURVETO),
+                                     N(50), N(-50), N(50), N(-50), O(VHCURVETO),
+                                     N(-50), N(-50), N(-50), N(-50), O(HVCURVETO),
+                                     N(301), N(2), E(DIV), N(0), O(RLINETO),
+                                     O(CLOSEPATH), O(ENDCHAR)])),
+        ("C", encrypted_charstring(&flex)),
+        ("acute", encrypted_charstring(&[N(100), N(300), O(HSBW), N(0), N(600), O(RMOVETO),
+                                         N(100), N(100), O(RLINETO), O(CLOSEPATH),
+                                         O(ENDCHAR)])),
+        ("Aacute", encrypted_charstring(&[N(50), N(600), O(HSBW), N(100), N(200), N(50),
+                                          N(65), N(194), E(SEAC)])),
+    ];
+
+    let mut private = vec![0xab, 0xcd, 0xef, 0x01];
+    private.extend_from_slice(b"dup /Private 8 dict dup begin
+/RD{string currentfile exch readstring pop}executeonly def
+/ND{noaccess def}executeonly def
+/NP{noaccess put}executeonly def
+/lenIV 4 def
+");
+    private.extend_from_slice(format!("/Subrs {} array\n", subrs.len()).as_bytes());
+    for (index, subr) in subrs.iter().enumerate() {
+        private.extend_from_slice(format!("dup {} {} RD ", index, subr.len()).as_bytes());
+        private.extend_from_slice(subr);
+        private.extend_from_slice(b" NP\n");
+    }
+    private.extend_from_slice(b"ND\n");
+    private.extend_from_slice(format!("2 index /CharStrings {} dict dup begin\n",
+                                      glyphs.len()).as_bytes());
+    for &(name, ref glyph) in &glyphs {
+        private.extend_from_slice(format!("/{} {} RD ", name, glyph.len()).as_bytes());
+        private.extend_from_slice(glyph);
+        private.extend_from_slice(b" ND\n");
+    }
+    private.extend_from_slice(b"end\nend\nreadonly put\nnoaccess put\n\
+dup/FontName get exch definefont pop\nmark currentfile closefile\n");
+
+    encrypt(&private, 55665)
+}
+
+fn trailer() -> Vec<u8> {
+    let mut trailer = vec![];
+    for _ in 0..8 {
+        trailer.extend_from_slice(&[b'0'; 64]);
+        trailer.push(b'\n');
+    }
+    trailer.extend_from_slice(b"cleartomark\n");
+    trailer
+}
+
+fn make_pfb() -> Vec<u8> {
+    let mut pfb = vec![];
+    for &(segment_type, ref data) in &[(1, CLEARTEXT.to_vec()), (2, make_private()),
+                                       (1, trailer())] {
+        pfb.push(0x80);
+        pfb.push(segment_type);
+        pfb.write_u32::<LittleEndian>(data.len() as u32).unwrap();
+        pfb.extend_from_slice(data);
+    }
+    pfb.extend_from_slice(&[0x80, 3]);
+    pfb
+}
+
+fn make_pfa() -> Vec<u8> {
+    let mut pfa = CLEARTEXT.to_vec();
+    for line in make_private().chunks(32) {
+        for byte in line {
+            pfa.extend_from_slice(format!("{:02x}", byte).as_bytes());
+        }
+        pfa.push(b'\n');
+    }
+    pfa.extend_from_slice(&trailer());
+    pfa
+}
+
+fn point(x: f32, y: f32) -> Point2D<f32> {
+    Point2D::new(x, y)
+}
+
+#[test]
+fn type1_glyph_names_and_encoding() {
+    let font = Type1Font::new(&make_pfb()).unwrap();
+    assert_eq!(font.font_name, Some("TestFont".to_owned()));
+    assert_eq!(font.units_per_em(), 1000);
+    assert_eq!(font.glyph_count(), 6);
+    assert_eq!(font.glyph_name(0), Some(".notdef"));
+    assert_eq!(font.glyph_id("A"), Some(1));
+    assert_eq!(font.glyph_id("Aacute"), Some(5));
+    assert_eq!(font.glyph_id_for_code(193), Some(5));
+    assert_eq!(font.glyph_id_for_code(68), None);
+
+    let glyph_ranges = font.glyph_ranges_for_codepoint_ranges(&[CodepointRange::new(64, 67)])
+                           .unwrap();
+    let glyph_ranges: Vec<_> = glyph_ranges.iter().map(|range| (range.start, range.end))
+                                                  .collect();
+    assert_eq!(glyph_ranges, vec![(0, 0), (1, 3)]);
+}
+
+#[test]
+fn type1_outlines() {
+    let font = Type1Font::new(&make_pfb()).unwrap();
+
+    let a = font.outline(font.glyph_id("A").unwrap()).unwrap();
+    assert_eq!(a.side_bearing, point(50.0, 0.0));
+    assert_eq!(a.advance, point(600.0, 0.0));
+    assert_eq!(a.commands, vec![
+        PathCommand::MoveTo(point(50.0, 0.0)),
+        PathCommand::LineTo(point(450.0, 0.0)),
+        PathCommand::LineTo(point(450.0, 700.0)),
+        PathCommand::LineTo(point(50.0, 700.0)),
+        PathCommand::ClosePath,
+    ]);
+
+    let b = font.outline(font.glyph_id("B").unwrap()).unwrap();
+    assert_eq!(b.commands, vec![
+        PathCommand::MoveTo(point(100.0, 100.0)),
+        PathCommand::CurveTo(point(150.0, 100.0), point(200.0, 150.0), point(200.0, 200.0)),
+        PathCommand::CurveTo(point(200.0, 250.0), point(150.0, 300.0), point(100.0, 300.0)),
+        PathCommand::CurveTo(point(50.0, 300.0), point(0.0, 250.0), point(0.0, 200.0)),
+        PathCommand::LineTo(point(150.5, 200.0)),
+        PathCommand::ClosePath,
+    ]);
+}
+
+#[test]
+fn type1_flex() {
+    let font = Type1Font::new(&make_pfb()).unwrap();
+    let c = font.outline(font.glyph_id("C").unwrap()).unwrap();
+    assert_eq!(c.commands, vec![
+        PathCommand::MoveTo(point(0.0, 0.0)),
+        PathCommand::CurveTo(point(100.0, 0.0), point(200.0, -20.0), point(300.0, -20.0)),
+        PathCommand::CurveTo(point(400.0, -20.0), point(500.0, 0.0), point(600.0, 0.0)),
+        PathCommand::LineTo(point(600.0, 100.0)),
+        PathCommand::ClosePath,
+    ]);
+}
+
+#[test]
+fn type1_seac() {
+    let font = Type1Font::new(&make_pfb()).unwrap();
+    let a = font.outline(font.glyph_id("A").unwrap()).unwrap();
+    let aacute = font.outline(font.glyph_id("Aacute").unwrap()).unwrap();
+    assert_eq!(aacute.advance, point(600.0, 0.0));
+    assert_eq!(&aacute.commands[..a.commands.len()], &a.commands[..]);
+    assert_eq!(&aacute.commands[a.commands.len()..], &[
+        PathCommand::MoveTo(point(250.0, 650.0)),
+        PathCommand::LineTo(point(350.0, 750.0)),
+        PathCommand::ClosePath,
+    ]);
+}
+
+#[test]
+fn type1_pfa_matches_pfb() {
+    let (pfa, pfb) = (make_pfa(), make_pfb());
+    assert_eq!(FontFormat::detect(&pfa), FontFormat::Type1);
+    assert_eq!(FontFormat::detect(&pfb), FontFormat::Type1);
+
+    let (pfa, pfb) = (Type1Font::new(&pfa).unwrap(), Type1Font::new(&pfb).unwrap());
+    for glyph_id in 0..pfb.glyph_count() {
+        assert_eq!(pfa.glyph_name(glyph_id), pfb.glyph_name(glyph_id));
+        assert_eq!(pfa.outline(glyph_id).unwrap(), pfb.outline(glyph_id).unwrap());
+    }
+}
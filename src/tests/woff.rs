@@ -0,0 +1,184 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use error::Error;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use memmap::{Mmap, Protection};
+use otf::FontData;
+use otf::woff::{MAX_DECODED_SIZE, WOFF, WoffFont};
+use std::io::Write;
+use tests::{TEST_FONT_PATH, glyph_points};
+
+static METADATA: &'static [u8] = b"<?xml version=\"1.0\"?><metadata version=\"1.0\"/>";
+static PRIVATE_DATA: &'static [u8] = b"\x01\x02\x03\x04\x05";
+
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::Default);
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn pad(bytes: &mut Vec<u8>) {
+    while bytes.len() % 4 != 0 {
+        bytes.push(0)
+    }
+}
+
+// A minimal WOFF encoder. Tables that don't shrink are stored uncompressed, as the spec requires.
+fn encode_woff(font: &[u8]) -> Vec<u8> {
+    let num_tables = BigEndian::read_u16(&font[4..6]) as usize;
+    let mut table_data = vec![];
+    let mut directory = vec![];
+    let data_start = 44 + num_tables * 20;
+    for table_index in 0..num_tables {
+        let record = &font[(12 + table_index * 16)..];
+        let offset = BigEndian::read_u32(&record[8..]) as usize;
+        let length = BigEndian::read_u32(&record[12..]) as usize;
+        let original = &font[offset..(offset + length)];
+        let compressed = compress(original);
+        let stored = if compressed.len() < original.len() { &compressed[..] } else { original };
+
+        directory.write_u32::<BigEndian>(BigEndian::read_u32(&record[0..])).unwrap();
+        directory.write_u32::<BigEndian>((data_start + table_data.len()) as u32).unwrap();
+        directory.write_u32::<BigEndian>(stored.len() as u32).unwrap();
+        directory.write_u32::<BigEndian>(length as u32).unwrap();
+        directory.write_u32::<BigEndian>(BigEndian::read_u32(&record[4..])).unwrap();
+        table_data.extend_from_slice(stored);
+        pad(&mut table_data);
+    }
+
+    let compressed_metadata = compress(METADATA);
+    let meta_offset = data_start + table_data.len();
+    table_data.extend_from_slice(&compressed_metadata);
+    pad(&mut table_data);
+    let priv_offset = data_start + table_data.len();
+    table_data.extend_from_slice(PRIVATE_DATA);
+
+    let mut woff = vec![];
+    woff.write_u32::<BigEndian>(WOFF).unwrap();
+    woff.write_u32::<BigEndian>(0x10000).unwrap();
+    woff.write_u32::<BigEndian>((data_start + table_data.len()) as u32).unwrap();
+    woff.write_u16::<BigEndian>(num_tables as u16).unwrap();
+    woff.write_u16::<BigEndian>(0).unwrap();
+    woff.write_u32::<BigEndian>(font.len() as u32).unwrap();
+    woff.write_u16::<BigEndian>(1).unwrap();
+    woff.write_u16::<BigEndian>(0).unwrap();
+    woff.write_u32::<BigEndian>(meta_offset as u32).unwrap();
+    woff.write_u32::<BigEndian>(compressed_metadata.len() as u32).unwrap();
+    woff.write_u32::<BigEndian>(METADATA.len() as u32).unwrap();
+    woff.write_u32::<BigEndian>(priv_offset as u32).unwrap();
+    woff.write_u32::<BigEndian>(PRIVATE_DATA.len() as u32).unwrap();
+    woff.extend_from_slice(&directory);
+    woff.extend_from_slice(&table_data);
+    woff
+}
+
+#[test]
+fn woff_round_trips() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let bytes = unsafe { file.as_slice() };
+    let woff = WoffFont::new(&encode_woff(bytes)).unwrap();
+
+    assert_eq!(woff.flavor, 0x10000);
+    assert_eq!(woff.metadata.as_ref().map(|metadata| &metadata[..]), Some(METADATA));
+    assert_eq!(woff.private_data.as_ref().map(|data| &data[..]), Some(PRIVATE_DATA));
+
+    let original = FontData::new(bytes);
+    let decoded = woff.font_data();
+    assert_eq!(decoded.head_table().unwrap().units_per_em,
+               original.head_table().unwrap().units_per_em);
+    for glyph_id in 1..32 {
+        assert!(glyph_points(&decoded, glyph_id) == glyph_points(&original, glyph_id));
+    }
+}
+
+#[test]
+fn woff_rejects_truncated_files() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let woff = encode_woff(unsafe { file.as_slice() });
+    assert!(WoffFont::new(&woff[..(woff.len() - 1)]).is_err());
+    assert!(WoffFont::new(&woff[..100]).is_err());
+}
+
+#[test]
+fn woff_rejects_wrong_original_length() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let mut woff = encode_woff(unsafe { file.as_slice() });
+
+    // Bump the `origLength` of the `glyf` table, which is always compressed.
+    let glyf_entry = 44 + 5 * 20;
+    assert_eq!(&woff[glyf_entry..(glyf_entry + 4)], b"glyf");
+    let orig_length = BigEndian::read_u32(&woff[(glyf_entry + 12)..]);
+    BigEndian::write_u32(&mut woff[(glyf_entry + 12)..], orig_length + 4);
+    let total_sfnt_size = BigEndian::read_u32(&woff[16..]);
+    BigEndian::write_u32(&mut woff[16..], total_sfnt_size + 4);
+    assert_eq!(WoffFont::new(&woff).err(), Some(Error::Malformed));
+}
+
+#[test]
+fn woff_rejects_corrupt_compressed_tables() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let mut woff = encode_woff(unsafe { file.as_slice() });
+
+    // Replace the zlib header of the `glyf` table with one that uses a reserved method.
+    let glyf_entry = 44 + 5 * 20;
+    assert_eq!(&woff[glyf_entry..(glyf_entry + 4)], b"glyf");
+    let offset = BigEndian::read_u32(&woff[(glyf_entry + 4)..]) as usize;
+    woff[offset] = 0x7f;
+    assert_eq!(WoffFont::new(&woff).err(), Some(Error::Malformed));
+}
+
+#[test]
+fn woff_rejects_fonts_bigger_than_total_sfnt_size() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let mut woff = encode_woff(unsafe { file.as_slice() });
+    let total_sfnt_size = BigEndian::read_u32(&woff[16..]);
+    BigEndian::write_u32(&mut woff[16..], total_sfnt_size - 4);
+    assert_eq!(WoffFont::new(&woff).err(), Some(Error::Malformed));
+}
+
+#[test]
+fn woff_rejects_declared_sizes_over_the_limit() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let woff = encode_woff(unsafe { file.as_slice() });
+
+    // `totalSfntSize` and the metadata's `metaOrigLength`.
+    for &field in &[16, 32] {
+        let mut woff = woff.clone();
+        BigEndian::write_u32(&mut woff[field..], MAX_DECODED_SIZE + 1);
+        assert_eq!(WoffFont::new(&woff).err(), Some(Error::Malformed));
+    }
+}
+
+/// A WOFF font of `num_tables` empty tables.
+pub fn woff_with_empty_tables(num_tables: usize) -> Vec<u8> {
+    let data_start = 44 + num_tables * 20;
+    let mut woff = vec![];
+    woff.write_u32::<BigEndian>(WOFF).unwrap();
+    woff.write_u32::<BigEndian>(0x10000).unwrap();
+    woff.write_u32::<BigEndian>(data_start as u32).unwrap();
+    woff.write_u16::<BigEndian>(num_tables as u16).unwrap();
+    woff.write_u16::<BigEndian>(0).unwrap();
+    woff.write_u32::<BigEndian>((12 + num_tables * 16) as u32).unwrap();
+    woff.extend_from_slice(&[0; 24]);
+    for tag in 0..num_tables {
+        woff.write_u32::<BigEndian>(tag as u32 + 1).unwrap();
+        woff.write_u32::<BigEndian>(data_start as u32).unwrap();
+        woff.extend_from_slice(&[0; 12]);
+    }
+    woff
+}
+
+// A directory of more than 4095 tables overflows `searchRange` and `rangeShift`.
+#[test]
+fn woff_with_many_tables() {
+    let font = WoffFont::new(&woff_with_empty_tables(5000)).unwrap();
+    let header = &font.sfnt[4..12];
+    assert_eq!(BigEndian::read_u16(&header[0..]), 5000);
+    assert_eq!(BigEndian::read_u16(&header[2..]), 0xffff);
+    assert_eq!(BigEndian::read_u16(&header[4..]), 12);
+    assert_eq!(BigEndian::read_u16(&header[6..]), (5000 - 4096) * 16);
+    assert_eq!(font.font_data().table_records().unwrap().count(), 5000);
+}
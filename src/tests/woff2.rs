@@ -0,0 +1,517 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use brotli::CompressorWriter;
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use error::Error;
+use memmap::{Mmap, Protection};
+use otf::FontData;
+use otf::collection::TTCF;
+use otf::woff::MAX_DECODED_SIZE;
+use otf::woff2::{WOFF2, Woff2Font};
+use std::io::Write;
+use tests::{TEST_FONT_PATH, glyph_points};
+
+static KNOWN_TAGS: [&'static [u8; 4]; 13] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post",
+    b"cvt ", b"fpgm", b"glyf", b"loca", b"prep",
+];
+
+static METADATA: &'static [u8] = b"<?xml version=\"1.0\"?><metadata version=\"1.0\"/>";
+
+struct Table<'a> {
+    tag: &'a [u8],
+    data: &'a [u8],
+}
+
+fn tables(font: &[u8]) -> Vec<Table> {
+    let num_tables = BigEndian::read_u16(&font[4..6]) as usize;
+    (0..num_tables).map(|table_index| {
+        let record = &font[(12 + table_index * 16)..];
+        let offset = BigEndian::read_u32(&record[8..]) as usize;
+        let length = BigEndian::read_u32(&record[12..]) as usize;
+        Table {
+            tag: &record[0..4],
+            data: &font[offset..(offset + length)],
+        }
+    }).collect()
+}
+
+fn find_table<'a>(tables: &[Table<'a>], tag: &[u8]) -> &'a [u8] {
+    tables.iter().find(|table| table.tag == tag).unwrap().data
+}
+
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut compressed = vec![];
+    {
+        let mut writer = CompressorWriter::new(&mut compressed, 4096, 11, 22);
+        writer.write_all(data).unwrap();
+    }
+    compressed
+}
+
+fn write_255_u16(output: &mut Vec<u8>, value: u16) {
+    if value < 253 {
+        output.push(value as u8)
+    } else if value < 506 {
+        output.push(255);
+        output.push((value - 253) as u8)
+    } else if value < 762 {
+        output.push(254);
+        output.push((value - 506) as u8)
+    } else {
+        output.push(253);
+        output.write_u16::<BigEndian>(value).unwrap()
+    }
+}
+
+fn write_uint_base128(output: &mut Vec<u8>, value: u32) {
+    let mut length = 1;
+    while length < 5 && (value >> (7 * length)) != 0 {
+        length += 1
+    }
+    for index in (0..length).rev() {
+        let byte = ((value >> (7 * index)) & 0x7f) as u8;
+        output.push(if index == 0 { byte } else { byte | 0x80 })
+    }
+}
+
+// Writes a point delta with the triplet encoding from the reference encoder.
+fn write_triplet(flags: &mut Vec<u8>, glyphs: &mut Vec<u8>, on_curve: bool, x: i32, y: i32) {
+    let (abs_x, abs_y) = (x.abs(), y.abs());
+    let on_curve_bit = if on_curve { 0 } else { 128 };
+    let x_sign_bit = if x < 0 { 0 } else { 1 };
+    let y_sign_bit = if y < 0 { 0 } else { 1 };
+    let xy_sign_bits = x_sign_bit + 2 * y_sign_bit;
+    if x == 0 && abs_y < 1280 {
+        flags.push((on_curve_bit + ((abs_y & 0xf00) >> 7) + y_sign_bit) as u8);
+        glyphs.push((abs_y & 0xff) as u8);
+    } else if y == 0 && abs_x < 1280 {
+        flags.push((on_curve_bit + 10 + ((abs_x & 0xf00) >> 7) + x_sign_bit) as u8);
+        glyphs.push((abs_x & 0xff) as u8);
+    } else if abs_x < 65 && abs_y < 65 {
+        flags.push((on_curve_bit + 20 + ((abs_x - 1) & 0x30) + (((abs_y - 1) & 0x30) >> 2) +
+                    xy_sign_bits) as u8);
+        glyphs.push(((((abs_x - 1) & 0xf) << 4) | ((abs_y - 1) & 0xf)) as u8);
+    } else if abs_x < 769 && abs_y < 769 {
+        flags.push((on_curve_bit + 84 + 12 * (((abs_x - 1) & 0x300) >> 8) +
+                    (((abs_y - 1) & 0x300) >> 6) + xy_sign_bits) as u8);
+        glyphs.push(((abs_x - 1) & 0xff) as u8);
+        glyphs.push(((abs_y - 1) & 0xff) as u8);
+    } else if abs_x < 4096 && abs_y < 4096 {
+        flags.push((on_curve_bit + 120 + xy_sign_bits) as u8);
+        glyphs.push((abs_x >> 4) as u8);
+        glyphs.push((((abs_x & 0xf) << 4) | (abs_y >> 8)) as u8);
+        glyphs.push((abs_y & 0xff) as u8);
+    } else {
+        flags.push((on_curve_bit + 124 + xy_sign_bits) as u8);
+        glyphs.write_u16::<BigEndian>(abs_x as u16).unwrap();
+        glyphs.write_u16::<BigEndian>(abs_y as u16).unwrap();
+    }
+}
+
+// Applies the WOFF2 `glyf` transform. Returns the transformed table and the x-minima of every
+// glyph, which the `hmtx` transform needs.
+fn transform_glyf(glyf: &[u8], loca: &[u8], index_format: i16, num_glyphs: u16)
+                  -> (Vec<u8>, Vec<i16>) {
+    let location = |glyph_id: usize| {
+        if index_format == 0 {
+            BigEndian::read_u16(&loca[(glyph_id * 2)..]) as usize * 2
+        } else {
+            BigEndian::read_u32(&loca[(glyph_id * 4)..]) as usize
+        }
+    };
+
+    let (mut n_contours, mut n_points, mut flags, mut glyphs) = (vec![], vec![], vec![], vec![]);
+    let (mut composites, mut bboxes, mut instructions) = (vec![], vec![], vec![]);
+    let mut bbox_bitmap = vec![0; ((num_glyphs as usize + 31) >> 5) << 2];
+    let mut x_mins = vec![];
+    for glyph_id in 0..(num_glyphs as usize) {
+        let (start, end) = (location(glyph_id), location(glyph_id + 1));
+        if start == end {
+            n_contours.write_i16::<BigEndian>(0).unwrap();
+            x_mins.push(0);
+            continue
+        }
+
+        let glyph = &glyf[start..end];
+        let number_of_contours = BigEndian::read_i16(glyph);
+        let bbox = &glyph[2..10];
+        x_mins.push(BigEndian::read_i16(bbox));
+        n_contours.write_i16::<BigEndian>(number_of_contours).unwrap();
+
+        if number_of_contours < 0 {
+            bbox_bitmap[glyph_id >> 3] |= 0x80 >> (glyph_id & 7);
+            bboxes.extend_from_slice(bbox);
+
+            let mut offset = 10;
+            let mut have_instructions = false;
+            loop {
+                let component_flags = BigEndian::read_u16(&glyph[offset..]);
+                have_instructions |= component_flags & 0x100 != 0;
+                let mut length = 4 + if component_flags & 1 != 0 { 4 } else { 2 };
+                if component_flags & 0x8 != 0 {
+                    length += 2
+                } else if component_flags & 0x40 != 0 {
+                    length += 4
+                } else if component_flags & 0x80 != 0 {
+                    length += 8
+                }
+                composites.extend_from_slice(&glyph[offset..(offset + length)]);
+                offset += length;
+                if component_flags & 0x20 == 0 {
+                    break
+                }
+            }
+            if have_instructions {
+                let instruction_length = BigEndian::read_u16(&glyph[offset..]) as usize;
+                write_255_u16(&mut glyphs, instruction_length as u16);
+                instructions.extend_from_slice(&glyph[(offset + 2)..
+                                                      (offset + 2 + instruction_length)]);
+            }
+            continue
+        }
+
+        // Simple glyph: unpack the points.
+        let mut offset = 10;
+        let mut last_end_point = -1;
+        for _ in 0..number_of_contours {
+            let end_point = BigEndian::read_u16(&glyph[offset..]) as i32;
+            write_255_u16(&mut n_points, (end_point - last_end_point) as u16);
+            last_end_point = end_point;
+            offset += 2;
+        }
+        let point_count = (last_end_point + 1) as usize;
+        let instruction_length = BigEndian::read_u16(&glyph[offset..]) as usize;
+        let glyph_instructions = &glyph[(offset + 2)..(offset + 2 + instruction_length)];
+        offset += 2 + instruction_length;
+
+        let mut point_flags = vec![];
+        while point_flags.len() < point_count {
+            let flag = glyph[offset];
+            offset += 1;
+            point_flags.push(flag);
+            if flag & 0x8 != 0 {
+                for _ in 0..glyph[offset] {
+                    point_flags.push(flag)
+                }
+                offset += 1;
+            }
+        }
+
+        let mut read_coordinates = |short_flag: u8, same_flag: u8| {
+            let mut coordinates = vec![];
+            let mut value = 0i32;
+            for &flag in &point_flags {
+                if flag & short_flag != 0 {
+                    let delta = glyph[offset] as i32;
+                    offset += 1;
+                    value += if flag & same_flag != 0 { delta } else { -delta }
+                } else if flag & same_flag == 0 {
+                    value += BigEndian::read_i16(&glyph[offset..]) as i32;
+                    offset += 2;
+                }
+                coordinates.push(value)
+            }
+            coordinates
+        };
+        let x_coordinates = read_coordinates(0x2, 0x10);
+        let y_coordinates = read_coordinates(0x4, 0x20);
+
+        let (mut last_x, mut last_y) = (0, 0);
+        for point_index in 0..point_count {
+            let (x, y) = (x_coordinates[point_index], y_coordinates[point_index]);
+            write_triplet(&mut flags,
+                          &mut glyphs,
+                          point_flags[point_index] & 1 != 0,
+                          x - last_x,
+                          y - last_y);
+            last_x = x;
+            last_y = y;
+        }
+        write_255_u16(&mut glyphs, instruction_length as u16);
+        instructions.extend_from_slice(glyph_instructions);
+
+        // Only store the bounding box if it can't be recomputed from the points.
+        let computed_bbox = [*x_coordinates.iter().min().unwrap() as i16,
+                             *y_coordinates.iter().min().unwrap() as i16,
+                             *x_coordinates.iter().max().unwrap() as i16,
+                             *y_coordinates.iter().max().unwrap() as i16];
+        if (0..4).any(|index| BigEndian::read_i16(&bbox[(index * 2)..]) != computed_bbox[index]) {
+            bbox_bitmap[glyph_id >> 3] |= 0x80 >> (glyph_id & 7);
+            bboxes.extend_from_slice(bbox);
+        }
+    }
+
+    let mut bbox_stream = bbox_bitmap;
+    bbox_stream.extend_from_slice(&bboxes);
+
+    let mut transformed = vec![];
+    transformed.write_u16::<BigEndian>(0).unwrap();
+    transformed.write_u16::<BigEndian>(0).unwrap();
+    transformed.write_u16::<BigEndian>(num_glyphs).unwrap();
+    transformed.write_u16::<BigEndian>(index_format as u16).unwrap();
+    let streams = [n_contours, n_points, flags, glyphs, composites, bbox_stream, instructions];
+    for stream in &streams {
+        transformed.write_u32::<BigEndian>(stream.len() as u32).unwrap();
+    }
+    for stream in &streams {
+        transformed.extend_from_slice(stream);
+    }
+    (transformed, x_mins)
+}
+
+// Applies the WOFF2 `hmtx` transform, omitting the left side bearings that equal `xMin`.
+fn transform_hmtx(hmtx: &[u8], number_of_h_metrics: usize, x_mins: &[i16]) -> Option<Vec<u8>> {
+    let lsb = |glyph_id: usize| {
+        if glyph_id < number_of_h_metrics {
+            BigEndian::read_i16(&hmtx[(glyph_id * 4 + 2)..])
+        } else {
+            BigEndian::read_i16(&hmtx[(number_of_h_metrics * 4 + (glyph_id -
+                                                                   number_of_h_metrics) * 2)..])
+        }
+    };
+    if (0..x_mins.len()).any(|glyph_id| lsb(glyph_id) != x_mins[glyph_id]) {
+        return None
+    }
+
+    let mut transformed = vec![0x3];
+    for glyph_id in 0..number_of_h_metrics {
+        transformed.extend_from_slice(&hmtx[(glyph_id * 4)..(glyph_id * 4 + 2)]);
+    }
+    Some(transformed)
+}
+
+// A minimal WOFF2 encoder. If `face_count` is greater than one, produces a collection in which
+// every face shares all of the tables.
+fn encode_woff2(font: &[u8], face_count: u16) -> Vec<u8> {
+    let tables = tables(font);
+    let head = find_table(&tables, b"head");
+    let index_format = BigEndian::read_i16(&head[50..]);
+    let num_glyphs = BigEndian::read_u16(&find_table(&tables, b"maxp")[4..]);
+    let number_of_h_metrics = BigEndian::read_u16(&find_table(&tables, b"hhea")[34..]) as usize;
+    let (transformed_glyf, x_mins) = transform_glyf(find_table(&tables, b"glyf"),
+                                                    find_table(&tables, b"loca"),
+                                                    index_format,
+                                                    num_glyphs);
+    let transformed_hmtx = transform_hmtx(find_table(&tables, b"hmtx"),
+                                          number_of_h_metrics,
+                                          &x_mins).expect("Test font should have lsb == xMin");
+
+    // The transformed `loca` must immediately follow `glyf`.
+    let mut ordered_tables: Vec<&Table> = tables.iter().filter(|table| table.tag != b"loca")
+                                                .collect();
+    let glyf_index = ordered_tables.iter().position(|table| table.tag == b"glyf").unwrap();
+    ordered_tables.insert(glyf_index + 1,
+                          tables.iter().find(|table| table.tag == b"loca").unwrap());
+
+    let mut directory = vec![];
+    let mut stream = vec![];
+    for table in ordered_tables {
+        let tag_index = KNOWN_TAGS.iter().position(|tag| &tag[..] == table.tag);
+        let (transform_version, transformed) = match table.tag {
+            b"glyf" => (0, Some(&transformed_glyf[..])),
+            b"loca" => (0, Some(&[][..])),
+            b"hmtx" => (1, Some(&transformed_hmtx[..])),
+            _ => (0, None),
+        };
+        match tag_index {
+            Some(tag_index) => directory.push((transform_version << 6) | tag_index as u8),
+            None => {
+                directory.push((transform_version << 6) | 63);
+                directory.extend_from_slice(table.tag);
+            }
+        }
+        write_uint_base128(&mut directory, table.data.len() as u32);
+        match transformed {
+            Some(transformed) => {
+                write_uint_base128(&mut directory, transformed.len() as u32);
+                stream.extend_from_slice(transformed);
+            }
+            None => stream.extend_from_slice(table.data),
+        }
+    }
+
+    let flavor = if face_count > 1 {
+        directory.write_u32::<BigEndian>(0x00010000).unwrap();
+        write_255_u16(&mut directory, face_count);
+        for _ in 0..face_count {
+            write_255_u16(&mut directory, tables.len() as u16);
+            directory.write_u32::<BigEndian>(0x00010000).unwrap();
+            for table_index in 0..tables.len() {
+                write_255_u16(&mut directory, table_index as u16);
+            }
+        }
+        TTCF
+    } else {
+        0x00010000
+    };
+
+    let compressed_stream = compress(&stream);
+    let compressed_metadata = compress(METADATA);
+    let stream_offset = 48 + directory.len();
+    let meta_offset = (stream_offset + compressed_stream.len() + 3) & !3;
+    let length = meta_offset + compressed_metadata.len();
+
+    let mut woff2 = vec![];
+    woff2.write_u32::<BigEndian>(WOFF2).unwrap();
+    woff2.write_u32::<BigEndian>(flavor).unwrap();
+    woff2.write_u32::<BigEndian>(length as u32).unwrap();
+    woff2.write_u16::<BigEndian>(tables.len() as u16).unwrap();
+    woff2.write_u16::<BigEndian>(0).unwrap();
+    woff2.write_u32::<BigEndian>(font.len() as u32).unwrap();
+    woff2.write_u32::<BigEndian>(compressed_stream.len() as u32).unwrap();
+    woff2.write_u16::<BigEndian>(1).unwrap();
+    woff2.write_u16::<BigEndian>(0).unwrap();
+    woff2.write_u32::<BigEndian>(meta_offset as u32).unwrap();
+    woff2.write_u32::<BigEndian>(compressed_metadata.len() as u32).unwrap();
+    woff2.write_u32::<BigEndian>(METADATA.len() as u32).unwrap();
+    woff2.write_u32::<BigEndian>(0).unwrap();
+    woff2.write_u32::<BigEndian>(0).unwrap();
+    woff2.extend_from_slice(&directory);
+    woff2.extend_from_slice(&compressed_stream);
+    while woff2.len() < meta_offset {
+        woff2.push(0)
+    }
+    woff2.extend_from_slice(&compressed_metadata);
+    woff2
+}
+
+#[test]
+fn woff2_round_trips() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let bytes = unsafe { file.as_slice() };
+    let woff2 = Woff2Font::new(&encode_woff2(bytes, 1)).unwrap();
+    assert!(!woff2.is_collection());
+    assert_eq!(woff2.metadata.as_ref().map(|metadata| &metadata[..]), Some(METADATA));
+    assert!(woff2.private_data.is_none());
+
+    let original = FontData::new(bytes);
+    let decoded = woff2.font_data();
+    let num_glyphs = BigEndian::read_u16(&find_table(&tables(bytes), b"maxp")[4..]) as u32;
+    for glyph_id in 0..num_glyphs {
+        assert_eq!(glyph_points(&decoded, glyph_id), glyph_points(&original, glyph_id));
+    }
+
+    // The untransformed tables, and `hmtx`, should come back exactly.
+    let (original_tables, decoded_tables) = (tables(bytes), tables(&woff2.sfnt));
+    for tag in &[b"cmap", b"hmtx", b"maxp", b"name", b"post"] {
+        assert_eq!(find_table(&original_tables, &tag[..]), find_table(&decoded_tables, &tag[..]));
+    }
+}
+
+#[test]
+fn woff2_collection_round_trips() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let bytes = unsafe { file.as_slice() };
+    let woff2 = Woff2Font::new(&encode_woff2(bytes, 2)).unwrap();
+    assert!(woff2.is_collection());
+
+    let original = FontData::new(bytes);
+    let collection = woff2.collection().unwrap();
+    assert_eq!(collection.face_count(), 2);
+    for face_index in 0..2 {
+        let face = collection.face(face_index).unwrap();
+        for glyph_id in 0..64 {
+            assert_eq!(glyph_points(&face, glyph_id), glyph_points(&original, glyph_id));
+        }
+    }
+}
+
+#[test]
+fn woff2_rejects_truncated_files() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let woff2 = encode_woff2(unsafe { file.as_slice() }, 1);
+    for &length in &[0, 47, 100, woff2.len() / 2, woff2.len() - 1] {
+        assert!(Woff2Font::new(&woff2[..length]).is_err());
+    }
+}
+
+// A font with one table with an arbitrary tag, whose data is `data` but which declares the given
+// `origLength`.
+fn woff2_with_one_table(data: &[u8], orig_length: u32, total_sfnt_size: u32) -> Vec<u8> {
+    let mut directory = vec![63];
+    directory.extend_from_slice(b"test");
+    write_uint_base128(&mut directory, orig_length);
+
+    let compressed_stream = compress(data);
+    let mut woff2 = vec![];
+    woff2.write_u32::<BigEndian>(WOFF2).unwrap();
+    woff2.write_u32::<BigEndian>(0x00010000).unwrap();
+    woff2.write_u32::<BigEndian>((48 + directory.len() + compressed_stream.len()) as u32).unwrap();
+    woff2.write_u16::<BigEndian>(1).unwrap();
+    woff2.write_u16::<BigEndian>(0).unwrap();
+    woff2.write_u32::<BigEndian>(total_sfnt_size).unwrap();
+    woff2.write_u32::<BigEndian>(compressed_stream.len() as u32).unwrap();
+    woff2.extend_from_slice(&[0; 24]);
+    woff2.extend_from_slice(&directory);
+    woff2.extend_from_slice(&compressed_stream);
+    woff2
+}
+
+#[test]
+fn woff2_rejects_fonts_bigger_than_total_sfnt_size() {
+    let font = Woff2Font::new(&woff2_with_one_table(&[1, 2, 3, 4], 4, 12 + 16 + 4)).unwrap();
+    let table = font.font_data().table("test".parse().unwrap()).unwrap().unwrap();
+    assert_eq!(table.bytes, &[1, 2, 3, 4]);
+
+    // Tables that don't fit in `totalSfntSize` are rejected before anything is decompressed, and so
+    // is a `totalSfntSize` over the limit, so a table declaring 512 MiB allocates nothing.
+    let woff2 = woff2_with_one_table(&[1, 2, 3, 4], 4, 12 + 16);
+    assert_eq!(Woff2Font::new(&woff2).err(), Some(Error::Malformed));
+    let woff2 = woff2_with_one_table(&[0; 1024], 512 << 20, 128);
+    assert_eq!(Woff2Font::new(&woff2).err(), Some(Error::Malformed));
+    let woff2 = woff2_with_one_table(&[0; 1024], 512 << 20, MAX_DECODED_SIZE + 1);
+    assert_eq!(Woff2Font::new(&woff2).err(), Some(Error::Malformed));
+}
+
+#[test]
+fn woff2_rejects_metadata_over_the_limit() {
+    let file = Mmap::open_path(TEST_FONT_PATH, Protection::Read).expect("Couldn't open test font");
+    let mut woff2 = encode_woff2(unsafe { file.as_slice() }, 1);
+    assert!(Woff2Font::new(&woff2).unwrap().metadata.is_some());
+    BigEndian::write_u32(&mut woff2[36..], MAX_DECODED_SIZE + 1);
+    assert_eq!(Woff2Font::new(&woff2).err(), Some(Error::Malformed));
+}
+
+// A face of more than 4095 tables overflows `searchRange` and `rangeShift`.
+#[test]
+fn woff2_collection_with_many_tables() {
+    let num_tables = 5000;
+    let mut directory = vec![];
+    for tag in 0..num_tables {
+        directory.push(63);
+        directory.write_u32::<BigEndian>(tag as u32 + 1).unwrap();
+        write_uint_base128(&mut directory, 0);
+    }
+    directory.write_u32::<BigEndian>(0x00010000).unwrap();
+    write_255_u16(&mut directory, 1);
+    write_255_u16(&mut directory, num_tables);
+    directory.write_u32::<BigEndian>(0x00010000).unwrap();
+    for table_index in 0..num_tables {
+        write_255_u16(&mut directory, table_index);
+    }
+
+    let compressed_stream = compress(&[]);
+    let mut woff2 = vec![];
+    woff2.write_u32::<BigEndian>(WOFF2).unwrap();
+    woff2.write_u32::<BigEndian>(TTCF).unwrap();
+    woff2.write_u32::<BigEndian>((48 + directory.len() + compressed_stream.len()) as u32).unwrap();
+    woff2.write_u16::<BigEndian>(num_tables).unwrap();
+    woff2.write_u16::<BigEndian>(0).unwrap();
+    woff2.write_u32::<BigEndian>(12 + 4 + 12 + num_tables as u32 * 16).unwrap();
+    woff2.write_u32::<BigEndian>(compressed_stream.len() as u32).unwrap();
+    woff2.extend_from_slice(&[0; 24]);
+    woff2.extend_from_slice(&directory);
+    woff2.extend_from_slice(&compressed_stream);
+
+    let woff2 = Woff2Font::new(&woff2).unwrap();
+    let collection = woff2.collection().unwrap();
+    let face = collection.face(0).unwrap();
+    assert_eq!(face.table_records().unwrap().count(), num_tables as usize);
+    let header = &woff2.sfnt[20..28];
+    assert_eq!(BigEndian::read_u16(&header[0..]), num_tables);
+    assert_eq!(BigEndian::read_u16(&header[2..]), 0xffff);
+    assert_eq!(BigEndian::read_u16(&header[4..]), 12);
+    assert_eq!(BigEndian::read_u16(&header[6..]), (5000 - 4096) * 16);
+}
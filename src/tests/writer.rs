@@ -0,0 +1,245 @@
+/* Any copyright is dedicated to the Public Domain.
+ * http://creativecommons.org/publicdomain/zero/1.0/ */
+
+use byteorder::{BigEndian, ByteOrder};
+use error::Error;
+use euclid::{Point2D, Rect, Size2D};
+use otf::hmtx::GlyphMetrics;
+use otf::sanitizer;
+use otf::writer::{CmapBuilder, CompositeComponent, FontBuilder, GlyfBuilder, HeadBuilder};
+use otf::writer::{HheaBuilder, MaxpBuilder, NameBuilder};
+use otf::{FontData, Tag};
+use outline::Transform;
+use std::f32::consts::FRAC_PI_2;
+use tests::glyph_points;
+
+// Two contours, with coordinates that need words, bytes, and no bytes at all.
+const OUTLINE: [&'static [(i16, i16, bool)]; 2] = [
+    &[(0, 0, true), (1000, 0, true), (1000, 700, false), (980, 700, true), (-300, -200, true)],
+    &[(100, 100, true), (100, 150, true), (150, 150, true)],
+];
+
+// Builds a font with the given glyphs and cmap, giving each glyph an advance of 600.
+fn build_font(glyf_builder: &GlyfBuilder, cmap_builder: &CmapBuilder) -> Vec<u8> {
+    let (glyf, loca, index_to_loc_format) = glyf_builder.build();
+    let mut head_builder = HeadBuilder::new();
+    head_builder.units_per_em = 2048;
+    head_builder.index_to_loc_format = index_to_loc_format;
+    let mut hhea_builder = HheaBuilder::new(1800, -400, 0);
+    for _ in 0..glyf_builder.glyph_count() {
+        hhea_builder.add_metrics(600, 0)
+    }
+    let (hhea, hmtx) = hhea_builder.build();
+
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"cmap"), cmap_builder.build());
+    font_builder.add_table(Tag::new(b"glyf"), glyf);
+    font_builder.add_table(Tag::new(b"head"), head_builder.build());
+    font_builder.add_table(Tag::new(b"hhea"), hhea);
+    font_builder.add_table(Tag::new(b"hmtx"), hmtx);
+    font_builder.add_table(Tag::new(b"loca"), loca);
+    font_builder.add_table(Tag::new(b"maxp"),
+                           MaxpBuilder::new(glyf_builder.glyph_count() as u16).build());
+    font_builder.build().unwrap()
+}
+
+fn simple_glyph_points(font: &FontData, glyph_id: u32) -> Vec<(i16, i16, bool)> {
+    let loca = font.loca_table(&font.head_table().unwrap()).unwrap();
+    let glyph = font.glyf_table().unwrap().simple_glyph(&loca, glyph_id).unwrap().unwrap();
+    glyph.points.iter().map(|point| (point.position.x, point.position.y, point.on_curve)).collect()
+}
+
+fn subtable_count(font: &FontData) -> u16 {
+    BigEndian::read_u16(&font.table(Tag::new(b"cmap")).unwrap().unwrap().bytes[2..])
+}
+
+#[test]
+fn simple_and_composite_glyphs() {
+    let many_points: Vec<_> = (0..300).map(|x| (x, 0, true)).collect();
+    let mut glyf_builder = GlyfBuilder::new();
+    assert_eq!(glyf_builder.add_empty_glyph(), 0);
+    assert_eq!(glyf_builder.add_simple_glyph(&OUTLINE, &[0xb0, 0x00]), 1);
+    glyf_builder.add_composite_glyph(&[
+        CompositeComponent::new(1, Transform::translation(10.0, -20.0)),
+        CompositeComponent::new(1, Transform::new(2.0, 0.0, 0.0, 2.0, 1000.0, 0.0)),
+    ]);
+    glyf_builder.add_composite_glyph(&[CompositeComponent::new(1, Transform::rotation(FRAC_PI_2))]);
+    glyf_builder.add_simple_glyph(&[&many_points], &[]);
+    let bytes = build_font(&glyf_builder, &CmapBuilder::new());
+    let font = FontData::new(&bytes);
+
+    let glyf = font.glyf_table().unwrap();
+    let loca = font.loca_table(&font.head_table().unwrap()).unwrap();
+    let expected: Vec<_> = OUTLINE.iter().flat_map(|contour| contour.iter()).cloned().collect();
+    assert_eq!(simple_glyph_points(&font, 1), expected);
+    let glyph = glyf.simple_glyph(&loca, 1).unwrap().unwrap();
+    assert_eq!(glyph.contour_end_indices, vec![4, 7]);
+    assert_eq!(glyph.instructions, &[0xb0, 0x00]);
+    assert_eq!(glyph.bounds, Rect::new(Point2D::new(-300, -200), Size2D::new(1300, 900)));
+
+    // The components are moved into place, and the composite's bounds cover them.
+    let outline = glyph_points(&font, 1);
+    let mut expected: Vec<_> = outline.iter().map(|&(x, y, on_curve)| {
+        (x + 10, y - 20, on_curve)
+    }).collect();
+    expected.extend(outline.iter().map(|&(x, y, on_curve)| (x * 2 + 1000, y * 2, on_curve)));
+    assert_eq!(glyph_points(&font, 2), expected);
+    assert_eq!(glyf.bounding_rect(&loca, 2).unwrap(),
+               Rect::new(Point2D::new(-290, -400), Size2D::new(3290, 1800)));
+    assert_eq!(glyph_points(&font, 3),
+               outline.iter().map(|&(x, y, on_curve)| (-y, x, on_curve)).collect::<Vec<_>>());
+
+    // More identical flags than one repeat count can hold.
+    assert_eq!(simple_glyph_points(&font, 4), many_points);
+}
+
+#[test]
+fn cmap_subtables() {
+    let mut glyf_builder = GlyfBuilder::new();
+    for _ in 0..4 {
+        glyf_builder.add_simple_glyph(&OUTLINE, &[]);
+    }
+
+    // 'A' and 'B' map to consecutive glyphs, which share a segment.
+    let mut cmap_builder = CmapBuilder::new();
+    cmap_builder.add_mapping('A' as u32, 1);
+    cmap_builder.add_mapping('B' as u32, 2);
+    cmap_builder.add_mapping('Z' as u32, 1);
+    for &format_12 in &[false, true] {
+        cmap_builder.format_12 = format_12;
+        let bytes = build_font(&glyf_builder, &cmap_builder);
+        let font = FontData::new(&bytes);
+        assert_eq!(subtable_count(&font), if format_12 { 2 } else { 1 });
+        let cmap = font.cmap_table().unwrap();
+        assert_eq!(cmap.glyph_for_codepoint('A' as u32).unwrap(), Some(1));
+        assert_eq!(cmap.glyph_for_codepoint('B' as u32).unwrap(), Some(2));
+        assert_eq!(cmap.glyph_for_codepoint('C' as u32).unwrap(), None);
+        assert_eq!(cmap.glyph_for_codepoint('Z' as u32).unwrap(), Some(1));
+    }
+
+    // Codepoints outside the BMP need format 12.
+    cmap_builder.format_12 = false;
+    cmap_builder.add_mapping(0x1f600, 3);
+    let bytes = build_font(&glyf_builder, &cmap_builder);
+    let font = FontData::new(&bytes);
+    assert_eq!(subtable_count(&font), 2);
+    let cmap = font.cmap_table().unwrap();
+    assert_eq!(cmap.glyph_for_codepoint(0x1f600).unwrap(), Some(3));
+    assert_eq!(cmap.glyph_for_codepoint('B' as u32).unwrap(), Some(2));
+
+    // So do more segments than format 4 has room for.
+    let mut cmap_builder = CmapBuilder::new();
+    for codepoint in 0..10000 {
+        cmap_builder.add_mapping(codepoint * 2, 1)
+    }
+    let bytes = build_font(&glyf_builder, &cmap_builder);
+    let font = FontData::new(&bytes);
+    assert_eq!(subtable_count(&font), 1);
+    let cmap = font.cmap_table().unwrap();
+    assert_eq!(cmap.glyph_for_codepoint(19998).unwrap(), Some(1));
+    assert_eq!(cmap.glyph_for_codepoint(19999).unwrap(), None);
+}
+
+#[test]
+fn metrics() {
+    let mut hhea_builder = HheaBuilder::new(800, -200, 90);
+    for &(advance, side_bearing) in &[(500, 0), (600, -10), (600, 20), (600, 30)] {
+        hhea_builder.add_metrics(advance, side_bearing)
+    }
+    let (hhea, hmtx) = hhea_builder.build();
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"hhea"), hhea);
+    font_builder.add_table(Tag::new(b"hmtx"), hmtx);
+    let bytes = font_builder.build().unwrap();
+    let font = FontData::new(&bytes);
+
+    // The glyphs after the second share its advance.
+    let hhea = font.hhea_table().unwrap();
+    assert_eq!((hhea.ascender, hhea.descender, hhea.line_gap), (800, -200, 90));
+    assert_eq!(hhea.number_of_metrics, 2);
+    assert_eq!(font.table(Tag::new(b"hmtx")).unwrap().unwrap().bytes.len(), 12);
+    let hmtx = font.hmtx_table(&hhea).unwrap();
+    for (glyph_id, metrics) in hhea_builder.metrics.iter().enumerate() {
+        assert_eq!(hmtx.metrics(glyph_id as u16).unwrap(), *metrics);
+    }
+    assert_eq!(hmtx.metrics(4), Err(Error::InvalidGlyphId(4)));
+    assert_eq!(hhea_builder.metrics[3], GlyphMetrics { advance: 600, side_bearing: 30 });
+}
+
+#[test]
+fn table_directory() {
+    let mut font_builder = FontBuilder::new();
+    font_builder.add_table(Tag::new(b"zzzz"), vec![1, 2, 3]);
+    font_builder.add_table(Tag::new(b"abcd"), vec![4, 5, 6, 7, 8]);
+    font_builder.add_table(Tag::new(b"mmmm"), vec![9]);
+    font_builder.remove_table(Tag::new(b"mmmm"));
+    font_builder.add_table(Tag::new(b"zzzz"), vec![10, 11, 12]);
+    assert!(font_builder.has_table(Tag::new(b"abcd")));
+    assert!(!font_builder.has_table(Tag::new(b"mmmm")));
+
+    let bytes = font_builder.build().unwrap();
+    let font = FontData::new(&bytes);
+    let records: Vec<_> = font.table_records().unwrap().collect();
+    assert_eq!(records.iter().map(|record| record.tag).collect::<Vec<_>>(),
+               vec![Tag::new(b"abcd"), Tag::new(b"zzzz")]);
+    assert!(records.iter().all(|record| record.offset % 4 == 0));
+    assert_eq!(bytes.len() % 4, 0);
+    assert_eq!(font.table(Tag::new(b"zzzz")).unwrap().unwrap().bytes, &[10, 11, 12]);
+    assert!(font.verify_checksums().unwrap().is_ok());
+}
+
+#[test]
+fn long_loca() {
+    // A glyph too big for 16-bit offsets.
+    let mut glyf_builder = GlyfBuilder::new();
+    glyf_builder.add_raw_glyph(&vec![0; 0x20000]);
+    glyf_builder.add_simple_glyph(&OUTLINE, &[]);
+    let bytes = build_font(&glyf_builder, &CmapBuilder::new());
+    let font = FontData::new(&bytes);
+
+    let head = font.head_table().unwrap();
+    assert_eq!(head.index_to_loc_format, 1);
+    assert_eq!(head.units_per_em, 2048);
+    assert_eq!(font.loca_table(&head).unwrap().location_of(1).unwrap(), 0x20000);
+    assert_eq!(simple_glyph_points(&font, 1).len(), 8);
+}
+
+#[test]
+fn complete_font() {
+    let mut glyf_builder = GlyfBuilder::new();
+    glyf_builder.add_empty_glyph();
+    glyf_builder.add_simple_glyph(&OUTLINE, &[]);
+    let mut cmap_builder = CmapBuilder::new();
+    cmap_builder.add_mapping('A' as u32, 1);
+    let mut name_builder = NameBuilder::new();
+    name_builder.add_name(1, "Test");
+    name_builder.add_record(1, 0, 0, 1, b"Test");
+
+    let mut font_builder = FontBuilder::new();
+    let bytes = build_font(&glyf_builder, &cmap_builder);
+    for record in FontData::new(&bytes).table_records().unwrap() {
+        let table = FontData::new(&bytes).table(record.tag).unwrap().unwrap().bytes.to_vec();
+        font_builder.add_table(record.tag, table);
+    }
+    font_builder.add_table(Tag::new(b"name"), name_builder.build().unwrap());
+    let bytes = font_builder.build().unwrap();
+    let font = FontData::new(&bytes);
+    assert!(font.verify_checksums().unwrap().is_ok());
+
+    // The Macintosh record sorts first.
+    let name = font.table(Tag::new(b"name")).unwrap().unwrap().bytes;
+    assert_eq!(BigEndian::read_u16(&name[2..]), 2);
+    assert_eq!(BigEndian::read_u16(&name[6..]), 1);
+
+    // Everything the builders write is valid, so sanitizing changes nothing.
+    assert_eq!(sanitizer::sanitize(&font).unwrap(), bytes);
+}
+
+#[test]
+fn name_too_long() {
+    let mut name_builder = NameBuilder::new();
+    name_builder.add_record(3, 1, 0x409, 1, &vec![0; 0x8000]);
+    assert!(name_builder.build().is_ok());
+    name_builder.add_record(3, 1, 0x409, 2, &vec![0; 0x8000]);
+    assert_eq!(name_builder.build(), Err(Error::InvalidArgument));
+}
@@ -0,0 +1,988 @@
+// Copyright 2017 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! PostScript Type 1 fonts, in either binary (`.pfb`) or ASCII (`.pfa`) form.
+//!
+//! Glyphs have no numeric IDs in Type 1, so they're numbered in the order they appear in the
+//! `CharStrings` dictionary, with `.notdef` moved to glyph 0 as in TrueType and CFF fonts.
+//!
+//! See "Adobe Type 1 Font Format", version 1.1.
+
+use byteorder::{ByteOrder, LittleEndian};
+use charmap::{CodepointRange, GlyphRange};
+use error::Error;
+use euclid::Point2D;
+use outline::{Outline, PathCommand};
+use std::str;
+use std::u16;
+
+const PFB_SEGMENT_MARKER: u8 = 0x80;
+const PFB_SEGMENT_ASCII: u8 = 1;
+const PFB_SEGMENT_BINARY: u8 = 2;
+const PFB_SEGMENT_EOF: u8 = 3;
+
+const EEXEC_KEY: u16 = 55665;
+const CHARSTRING_KEY: u16 = 4330;
+const EEXEC_PREFIX_LENGTH: usize = 4;
+const DEFAULT_LEN_IV: i32 = 4;
+
+const MISSING_GLYPH: u16 = 0;
+const NOTDEF: &'static [u8] = b".notdef";
+
+// Limits from the "Type 1 Font Format" specification, appendix 1.
+const MAX_OPERAND_STACK_DEPTH: usize = 24;
+const MAX_SUBR_NESTING_DEPTH: u32 = 10;
+// The number of points recorded between `1 callothersubr` and `0 callothersubr` in a flex.
+const FLEX_POINT_COUNT: usize = 7;
+
+const OTHERSUBR_FLEX_END: i32 = 0;
+const OTHERSUBR_FLEX_START: i32 = 1;
+const OTHERSUBR_FLEX_POINT: i32 = 2;
+const OTHERSUBR_HINT_REPLACEMENT: i32 = 3;
+
+/// A Type 1 font.
+#[derive(Clone, Debug)]
+pub struct Type1Font {
+    /// The PostScript name of the font, from `/FontName`.
+    pub font_name: Option<String>,
+    /// The transform from character space to text space, from `/FontMatrix`.
+    pub font_matrix: [f32; 6],
+    glyph_names: Vec<Vec<u8>>,
+    charstrings: Vec<Vec<u8>>,
+    subrs: Vec<Vec<u8>>,
+    encoding: Vec<Option<Vec<u8>>>,
+}
+
+impl Type1Font {
+    /// Parses a Type 1 font in either PFB or PFA form.
+    pub fn new(bytes: &[u8]) -> Result<Type1Font, Error> {
+        let (cleartext, encrypted) = if bytes.first() == Some(&PFB_SEGMENT_MARKER) {
+            try!(read_pfb_segments(bytes))
+        } else {
+            try!(split_pfa(bytes))
+        };
+
+        let mut font = Type1Font {
+            font_name: None,
+            font_matrix: [0.001, 0.0, 0.0, 0.001, 0.0, 0.0],
+            glyph_names: vec![],
+            charstrings: vec![],
+            subrs: vec![],
+            encoding: vec![None; 256],
+        };
+        try!(font.parse_cleartext(&cleartext));
+
+        let mut private = decrypt(&encrypted, EEXEC_KEY);
+        if private.len() < EEXEC_PREFIX_LENGTH {
+            return Err(Error::Malformed)
+        }
+        private.drain(0..EEXEC_PREFIX_LENGTH);
+        try!(font.parse_private(&private));
+
+        if font.glyph_names.is_empty() {
+            return Err(Error::Malformed)
+        }
+        Ok(font)
+    }
+
+    /// Returns the number of glyphs in the font.
+    #[inline]
+    pub fn glyph_count(&self) -> u16 {
+        self.glyph_names.len() as u16
+    }
+
+    /// Returns the number of font units per em, derived from the font matrix.
+    pub fn units_per_em(&self) -> u16 {
+        if self.font_matrix[0] <= 0.0 {
+            1000
+        } else {
+            (1.0 / self.font_matrix[0]).round() as u16
+        }
+    }
+
+    /// Returns the PostScript name of a glyph.
+    pub fn glyph_name(&self, glyph_id: u16) -> Option<&str> {
+        self.glyph_names.get(glyph_id as usize).and_then(|name| str::from_utf8(name).ok())
+    }
+
+    /// Looks up a glyph by its PostScript name.
+    pub fn glyph_id(&self, name: &str) -> Option<u16> {
+        self.glyph_id_for_name(name.as_bytes())
+    }
+
+    /// Returns the glyph that the font's encoding maps the given character code to.
+    pub fn glyph_id_for_code(&self, code: u8) -> Option<u16> {
+        self.encoding[code as usize].as_ref().and_then(|name| self.glyph_id_for_name(name))
+    }
+
+    /// Maps codepoints to glyphs through the font's built-in encoding, which covers codepoints 0
+    /// through 255 only. This mirrors `CmapTable::glyph_ranges_for_codepoint_ranges()`: unmapped
+    /// codepoints map to glyph 0.
+    pub fn glyph_ranges_for_codepoint_ranges(&self, codepoint_ranges: &[CodepointRange])
+                                             -> Result<Vec<GlyphRange>, Error> {
+        let mut glyph_ranges: Vec<GlyphRange> = vec![];
+        for codepoint_range in codepoint_ranges {
+            let mut first_in_range = true;
+            for codepoint in codepoint_range.iter() {
+                let glyph_id = if codepoint <= 0xff {
+                    self.glyph_id_for_code(codepoint as u8).unwrap_or(MISSING_GLYPH)
+                } else {
+                    MISSING_GLYPH
+                };
+
+                // Coalesce runs of consecutive glyphs, but never across input ranges.
+                if !first_in_range && glyph_id != MISSING_GLYPH {
+                    if let Some(last) = glyph_ranges.last_mut() {
+                        if last.end != MISSING_GLYPH && last.end < u16::MAX &&
+                                last.end + 1 == glyph_id {
+                            last.end = glyph_id;
+                            continue
+                        }
+                    }
+                }
+
+                glyph_ranges.push(GlyphRange {
+                    start: glyph_id,
+                    end: glyph_id,
+                });
+                first_in_range = false
+            }
+        }
+
+        Ok(glyph_ranges)
+    }
+
+    /// Runs the charstring for the given glyph and returns its outline, with curves left as
+    /// cubics.
+    pub fn outline(&self, glyph_id: u16) -> Result<Outline, Error> {
+        let mut interpreter = CharstringInterpreter::new(self);
+        try!(interpreter.run_glyph(glyph_id, Point2D::new(0.0, 0.0), false));
+        Ok(Outline {
+            commands: interpreter.commands,
+            side_bearing: interpreter.side_bearing,
+            advance: interpreter.advance,
+        })
+    }
+
+    fn glyph_id_for_name(&self, name: &[u8]) -> Option<u16> {
+        self.glyph_names.iter().position(|glyph_name| &glyph_name[..] == name).map(|id| id as u16)
+    }
+
+    fn parse_cleartext(&mut self, cleartext: &[u8]) -> Result<(), Error> {
+        let mut lexer = Lexer::new(cleartext);
+        while let Some(token) = lexer.next() {
+            match token {
+                Token::Literal(b"FontName") => {
+                    if let Some(Token::Literal(name)) = lexer.next() {
+                        self.font_name = str::from_utf8(name).ok().map(|name| name.to_owned())
+                    }
+                }
+                Token::Literal(b"FontMatrix") => {
+                    match lexer.next() {
+                        Some(Token::ArrayStart) | Some(Token::ProcedureStart) => {}
+                        _ => return Err(Error::Malformed),
+                    }
+                    for value in &mut self.font_matrix {
+                        match lexer.next() {
+                            Some(Token::Number(number)) => *value = number,
+                            _ => return Err(Error::Malformed),
+                        }
+                    }
+                }
+                Token::Literal(b"Encoding") => try!(self.parse_encoding(&mut lexer)),
+                Token::Executable(b"eexec") => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Reads either `StandardEncoding` or a sequence of `dup <code> /<name> put` entries.
+    fn parse_encoding(&mut self, lexer: &mut Lexer) -> Result<(), Error> {
+        match lexer.next() {
+            Some(Token::Executable(b"StandardEncoding")) => {
+                for (code, name) in STANDARD_ENCODING.iter().enumerate() {
+                    if !name.is_empty() {
+                        self.encoding[code] = Some(name.as_bytes().to_vec())
+                    }
+                }
+                return Ok(())
+            }
+            Some(Token::Number(_)) => {}
+            _ => return Err(Error::Malformed),
+        }
+
+        loop {
+            match lexer.next() {
+                Some(Token::Executable(b"dup")) => {}
+                Some(Token::Executable(b"def")) | None => return Ok(()),
+                Some(_) => continue,
+            }
+            let code = match lexer.next() {
+                Some(Token::Number(code)) if code >= 0.0 && code < 256.0 => code as usize,
+                _ => return Err(Error::Malformed),
+            };
+            match lexer.next() {
+                Some(Token::Literal(name)) => self.encoding[code] = Some(name.to_vec()),
+                _ => return Err(Error::Malformed),
+            }
+        }
+    }
+
+    fn parse_private(&mut self, private: &[u8]) -> Result<(), Error> {
+        let mut len_iv = DEFAULT_LEN_IV;
+        let mut lexer = Lexer::new(private);
+        while let Some(token) = lexer.next() {
+            match token {
+                Token::Literal(b"lenIV") => {
+                    match lexer.next() {
+                        Some(Token::Number(value)) => len_iv = value as i32,
+                        _ => return Err(Error::Malformed),
+                    }
+                }
+                Token::Literal(b"Subrs") => try!(self.parse_subrs(&mut lexer, len_iv)),
+                Token::Literal(b"CharStrings") => {
+                    try!(self.parse_charstrings(&mut lexer, len_iv));
+                    break
+                }
+                _ => {}
+            }
+        }
+
+        // Make `.notdef` glyph 0.
+        if let Some(notdef_index) = self.glyph_id_for_name(NOTDEF) {
+            let notdef_name = self.glyph_names.remove(notdef_index as usize);
+            let notdef_charstring = self.charstrings.remove(notdef_index as usize);
+            self.glyph_names.insert(0, notdef_name);
+            self.charstrings.insert(0, notdef_charstring);
+        }
+        Ok(())
+    }
+
+    // Reads `<count> array` followed by `count` entries of the form `dup <index> <length> RD
+    // <binary> NP`. `RD` and `NP` are conventionally `RD`/`-|` and `NP`/`|`, but since they're
+    // just procedures defined earlier in the Private dictionary, any name is accepted.
+    fn parse_subrs(&mut self, lexer: &mut Lexer, len_iv: i32) -> Result<(), Error> {
+        let count = match lexer.next() {
+            Some(Token::Number(count)) if count >= 0.0 => count as usize,
+            _ => return Err(Error::Malformed),
+        };
+        self.subrs = vec![vec![]; count];
+
+        let mut read = 0;
+        while read < count {
+            match lexer.next() {
+                Some(Token::Executable(b"dup")) => {}
+                Some(_) => continue,
+                None => return Err(Error::Malformed),
+            }
+            let index = match lexer.next() {
+                Some(Token::Number(index)) if index >= 0.0 && (index as usize) < count => {
+                    index as usize
+                }
+                _ => return Err(Error::Malformed),
+            };
+            self.subrs[index] = try!(read_charstring(lexer, len_iv));
+            read += 1
+        }
+        Ok(())
+    }
+
+    // Reads entries of the form `/<name> <length> RD <binary> ND` until `end`.
+    fn parse_charstrings(&mut self, lexer: &mut Lexer, len_iv: i32) -> Result<(), Error> {
+        loop {
+            match lexer.next() {
+                Some(Token::Literal(name)) => {
+                    let charstring = try!(read_charstring(lexer, len_iv));
+                    self.glyph_names.push(name.to_vec());
+                    self.charstrings.push(charstring);
+                }
+                Some(Token::Executable(b"end")) | None => return Ok(()),
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+// Reads `<length> RD <binary>` and decrypts the binary data.
+fn read_charstring(lexer: &mut Lexer, len_iv: i32) -> Result<Vec<u8>, Error> {
+    let length = match lexer.next() {
+        Some(Token::Number(length)) if length >= 0.0 => length as usize,
+        _ => return Err(Error::Malformed),
+    };
+    match lexer.next() {
+        Some(Token::Executable(_)) => {}
+        _ => return Err(Error::Malformed),
+    }
+    let encrypted = try!(lexer.read_binary(length));
+
+    // A `lenIV` of -1 means that charstrings aren't encrypted at all.
+    if len_iv < 0 {
+        return Ok(encrypted.to_vec())
+    }
+    let mut charstring = decrypt(encrypted, CHARSTRING_KEY);
+    if charstring.len() < len_iv as usize {
+        return Err(Error::Malformed)
+    }
+    charstring.drain(0..(len_iv as usize));
+    Ok(charstring)
+}
+
+// Splits a PFB file into its cleartext and its encrypted portion. ASCII segments make up the
+// former, and binary segments the latter.
+fn read_pfb_segments(bytes: &[u8]) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let (mut cleartext, mut encrypted) = (vec![], vec![]);
+    let mut reader = bytes;
+    loop {
+        if reader.len() < 2 || reader[0] != PFB_SEGMENT_MARKER {
+            return Err(Error::Malformed)
+        }
+        let segment_type = reader[1];
+        if segment_type == PFB_SEGMENT_EOF {
+            break
+        }
+
+        if reader.len() < 6 {
+            return Err(Error::Malformed)
+        }
+        let length = LittleEndian::read_u32(&reader[2..]) as usize;
+        reader = &reader[6..];
+        if length > reader.len() {
+            return Err(Error::Malformed)
+        }
+        let (segment, rest) = reader.split_at(length);
+        match segment_type {
+            // Everything after the first binary segment is the trailer of zeros and
+            // `cleartomark`, which we have no use for.
+            PFB_SEGMENT_ASCII if encrypted.is_empty() => cleartext.extend_from_slice(segment),
+            PFB_SEGMENT_ASCII => {}
+            PFB_SEGMENT_BINARY => encrypted.extend_from_slice(segment),
+            _ => return Err(Error::Malformed),
+        }
+        reader = rest;
+        if reader.is_empty() {
+            break
+        }
+    }
+    Ok((cleartext, encrypted))
+}
+
+// Splits a PFA file at `eexec`. The encrypted portion may be either hex or binary; the spec says
+// to treat it as hex if the first four bytes are all hex digits.
+fn split_pfa(bytes: &[u8]) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let eexec_position = match bytes.windows(5).position(|window| window == b"eexec") {
+        None => return Err(Error::Malformed),
+        Some(position) => position + 5,
+    };
+    let cleartext = &bytes[..eexec_position];
+    let mut encrypted = &bytes[eexec_position..];
+    while let Some((&byte, rest)) = encrypted.split_first() {
+        if !is_whitespace(byte) {
+            break
+        }
+        encrypted = rest
+    }
+
+    if encrypted.len() >= 4 && encrypted[0..4].iter().all(|&byte| hex_value(byte).is_some()) {
+        let mut decoded = vec![];
+        let mut high_nibble = None;
+        for &byte in encrypted {
+            if is_whitespace(byte) {
+                continue
+            }
+            let nibble = match hex_value(byte) {
+                None => break,
+                Some(nibble) => nibble,
+            };
+            match high_nibble.take() {
+                None => high_nibble = Some(nibble),
+                Some(high) => decoded.push((high << 4) | nibble),
+            }
+        }
+        Ok((cleartext.to_vec(), decoded))
+    } else {
+        Ok((cleartext.to_vec(), encrypted.to_vec()))
+    }
+}
+
+fn decrypt(cipher: &[u8], key: u16) -> Vec<u8> {
+    const C1: u16 = 52845;
+    const C2: u16 = 22719;
+
+    let mut r = key;
+    cipher.iter().map(|&byte| {
+        let plain = byte ^ (r >> 8) as u8;
+        r = (byte as u16).wrapping_add(r).wrapping_mul(C1).wrapping_add(C2);
+        plain
+    }).collect()
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'...b'9' => Some(byte - b'0'),
+        b'a'...b'f' => Some(byte - b'a' + 10),
+        b'A'...b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[inline]
+fn is_whitespace(byte: u8) -> bool {
+    match byte {
+        b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' | b'\0' => true,
+        _ => false,
+    }
+}
+
+#[inline]
+fn is_delimiter(byte: u8) -> bool {
+    match byte {
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%' => true,
+        _ => is_whitespace(byte),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token<'a> {
+    /// A literal name such as `/FontName`, without the slash.
+    Literal(&'a [u8]),
+    /// An executable name such as `def`.
+    Executable(&'a [u8]),
+    Number(f32),
+    /// A string, hex string, or dictionary delimiter. Their contents are never needed.
+    Other,
+    ArrayStart,
+    ArrayEnd,
+    ProcedureStart,
+    ProcedureEnd,
+}
+
+// Just enough of a PostScript tokenizer to pick the font program apart.
+struct Lexer<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(data: &'a [u8]) -> Lexer<'a> {
+        Lexer {
+            data: data,
+            position: 0,
+        }
+    }
+
+    fn peek_byte(&self) -> Option<u8> {
+        self.data.get(self.position).cloned()
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        // Skip whitespace and comments.
+        loop {
+            match self.peek_byte() {
+                Some(byte) if is_whitespace(byte) => self.position += 1,
+                Some(b'%') => {
+                    while let Some(byte) = self.peek_byte() {
+                        if byte == b'\r' || byte == b'\n' {
+                            break
+                        }
+                        self.position += 1
+                    }
+                }
+                Some(_) => break,
+                None => return None,
+            }
+        }
+
+        let start = self.position;
+        self.position += 1;
+        match self.data[start] {
+            b'[' => Some(Token::ArrayStart),
+            b']' => Some(Token::ArrayEnd),
+            b'{' => Some(Token::ProcedureStart),
+            b'}' => Some(Token::ProcedureEnd),
+            b'(' => {
+                let mut depth = 1;
+                while let Some(byte) = self.peek_byte() {
+                    self.position += 1;
+                    match byte {
+                        b'\\' => self.position += 1,
+                        b'(' => depth += 1,
+                        b')' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Some(Token::Other)
+            }
+            b'<' => {
+                if self.peek_byte() == Some(b'<') {
+                    self.position += 1;
+                } else {
+                    while let Some(byte) = self.peek_byte() {
+                        self.position += 1;
+                        if byte == b'>' {
+                            break
+                        }
+                    }
+                }
+                Some(Token::Other)
+            }
+            b'>' => {
+                if self.peek_byte() == Some(b'>') {
+                    self.position += 1;
+                }
+                Some(Token::Other)
+            }
+            b'/' => Some(Token::Literal(self.read_regular())),
+            _ => {
+                self.position = start;
+                let word = self.read_regular();
+                let looks_numeric = match word[0] {
+                    b'0'...b'9' | b'-' | b'+' | b'.' => true,
+                    _ => false,
+                };
+                match str::from_utf8(word).ok().and_then(|word| word.parse::<f32>().ok()) {
+                    Some(number) if looks_numeric => Some(Token::Number(number)),
+                    _ => Some(Token::Executable(word)),
+                }
+            }
+        }
+    }
+
+    fn read_regular(&mut self) -> &'a [u8] {
+        let start = self.position;
+        while let Some(byte) = self.peek_byte() {
+            if is_delimiter(byte) {
+                break
+            }
+            self.position += 1
+        }
+        &self.data[start..self.position]
+    }
+
+    // Reads binary data following an `RD` token, skipping the single space that separates them.
+    fn read_binary(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        let start = self.position + 1;
+        match start.checked_add(length) {
+            Some(end) if end <= self.data.len() => {
+                self.position = end;
+                Ok(&self.data[start..end])
+            }
+            _ => Err(Error::Malformed),
+        }
+    }
+}
+
+struct CharstringInterpreter<'a> {
+    font: &'a Type1Font,
+    stack: Vec<f32>,
+    // Values left by `callothersubr` for `pop` to retrieve, with the next one last.
+    postscript_stack: Vec<f32>,
+    commands: Vec<PathCommand>,
+    current_point: Point2D<f32>,
+    // Where the origin of the glyph being run lies. This is only nonzero for the accent of a
+    // `seac` glyph.
+    origin: Point2D<f32>,
+    side_bearing: Point2D<f32>,
+    advance: Point2D<f32>,
+    // The points recorded so far, if a flex is in progress.
+    flex_points: Option<Vec<Point2D<f32>>>,
+    finished: bool,
+}
+
+impl<'a> CharstringInterpreter<'a> {
+    fn new(font: &'a Type1Font) -> CharstringInterpreter<'a> {
+        CharstringInterpreter {
+            font: font,
+            stack: vec![],
+            postscript_stack: vec![],
+            commands: vec![],
+            current_point: Point2D::new(0.0, 0.0),
+            origin: Point2D::new(0.0, 0.0),
+            side_bearing: Point2D::new(0.0, 0.0),
+            advance: Point2D::new(0.0, 0.0),
+            flex_points: None,
+            finished: false,
+        }
+    }
+
+    // Runs a whole glyph. Components of `seac` glyphs don't contribute their metrics.
+    fn run_glyph(&mut self, glyph_id: u16, origin: Point2D<f32>, is_component: bool)
+                 -> Result<(), Error> {
+        let font = self.font;
+        let charstring = try!(font.charstrings.get(glyph_id as usize).ok_or(Error::Malformed));
+
+        let (side_bearing, advance) = (self.side_bearing, self.advance);
+        self.stack.clear();
+        self.origin = origin;
+        self.current_point = origin;
+        self.finished = false;
+        try!(self.run(charstring, 0, is_component));
+
+        if is_component {
+            self.side_bearing = side_bearing;
+            self.advance = advance;
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, charstring: &[u8], depth: u32, is_component: bool) -> Result<(), Error> {
+        if depth > MAX_SUBR_NESTING_DEPTH {
+            return Err(Error::Malformed)
+        }
+
+        let mut reader = charstring;
+        while let Some((&byte, rest)) = reader.split_first() {
+            reader = rest;
+
+            // Numbers.
+            if byte >= 32 {
+                let value = match byte {
+                    32...246 => byte as i32 - 139,
+                    247...250 => {
+                        let (&next, rest) = try!(reader.split_first().ok_or(Error::Malformed));
+                        reader = rest;
+                        (byte as i32 - 247) * 256 + next as i32 + 108
+                    }
+                    251...254 => {
+                        let (&next, rest) = try!(reader.split_first().ok_or(Error::Malformed));
+                        reader = rest;
+                        -(byte as i32 - 251) * 256 - next as i32 - 108
+                    }
+                    _ => {
+                        if reader.len() < 4 {
+                            return Err(Error::Malformed)
+                        }
+                        let value = ((reader[0] as u32) << 24) | ((reader[1] as u32) << 16) |
+                            ((reader[2] as u32) << 8) | (reader[3] as u32);
+                        reader = &reader[4..];
+                        value as i32
+                    }
+                };
+                if self.stack.len() >= MAX_OPERAND_STACK_DEPTH {
+                    return Err(Error::Malformed)
+                }
+                self.stack.push(value as f32);
+                continue
+            }
+
+            // Operators.
+            let operator = if byte == 12 {
+                let (&next, rest) = try!(reader.split_first().ok_or(Error::Malformed));
+                reader = rest;
+                1200 + next as u32
+            } else {
+                byte as u32
+            };
+
+            match operator {
+                // hstem, vstem, dotsection, vstem3, hstem3: we don't do hinting.
+                1 | 3 | 1200 | 1201 | 1202 => {}
+                // vmoveto
+                4 => {
+                    let dy = try!(self.arg(0));
+                    self.move_to(Point2D::new(0.0, dy))
+                }
+                // rlineto
+                5 => {
+                    let (dx, dy) = (try!(self.arg(0)), try!(self.arg(1)));
+                    self.line_to(Point2D::new(dx, dy))
+                }
+                // hlineto
+                6 => {
+                    let dx = try!(self.arg(0));
+                    self.line_to(Point2D::new(dx, 0.0))
+                }
+                // vlineto
+                7 => {
+                    let dy = try!(self.arg(0));
+                    self.line_to(Point2D::new(0.0, dy))
+                }
+                // rrcurveto
+                8 => {
+                    let d1 = Point2D::new(try!(self.arg(0)), try!(self.arg(1)));
+                    let d2 = Point2D::new(try!(self.arg(2)), try!(self.arg(3)));
+                    let d3 = Point2D::new(try!(self.arg(4)), try!(self.arg(5)));
+                    self.curve_to(d1, d2, d3)
+                }
+                // closepath
+                9 => self.commands.push(PathCommand::ClosePath),
+                // callsubr
+                10 => {
+                    let index = try!(self.stack.pop().ok_or(Error::Malformed));
+                    let font = self.font;
+                    let subr = try!(font.subrs.get(index as usize).ok_or(Error::Malformed));
+                    try!(self.run(subr, depth + 1, is_component));
+                    if self.finished {
+                        return Ok(())
+                    }
+                    continue
+                }
+                // return
+                11 => return Ok(()),
+                // hsbw
+                13 => {
+                    let (sbx, wx) = (try!(self.arg(0)), try!(self.arg(1)));
+                    self.set_side_bearing_and_width(Point2D::new(sbx, 0.0),
+                                                    Point2D::new(wx, 0.0))
+                }
+                // endchar
+                14 => {
+                    self.finished = true;
+                    return Ok(())
+                }
+                // rmoveto
+                21 => {
+                    let (dx, dy) = (try!(self.arg(0)), try!(self.arg(1)));
+                    self.move_to(Point2D::new(dx, dy))
+                }
+                // hmoveto
+                22 => {
+                    let dx = try!(self.arg(0));
+                    self.move_to(Point2D::new(dx, 0.0))
+                }
+                // vhcurveto
+                30 => {
+                    let d1 = Point2D::new(0.0, try!(self.arg(0)));
+                    let d2 = Point2D::new(try!(self.arg(1)), try!(self.arg(2)));
+                    let d3 = Point2D::new(try!(self.arg(3)), 0.0);
+                    self.curve_to(d1, d2, d3)
+                }
+                // hvcurveto
+                31 => {
+                    let d1 = Point2D::new(try!(self.arg(0)), 0.0);
+                    let d2 = Point2D::new(try!(self.arg(1)), try!(self.arg(2)));
+                    let d3 = Point2D::new(0.0, try!(self.arg(3)));
+                    self.curve_to(d1, d2, d3)
+                }
+                // seac
+                1206 => {
+                    if is_component {
+                        return Err(Error::Malformed)
+                    }
+                    let (asb, adx, ady) = (try!(self.arg(0)), try!(self.arg(1)), try!(self.arg(2)));
+                    let (base_code, accent_code) = (try!(self.arg(3)), try!(self.arg(4)));
+                    try!(self.seac(asb, Point2D::new(adx, ady), base_code, accent_code));
+                    self.finished = true;
+                    return Ok(())
+                }
+                // sbw
+                1207 => {
+                    let side_bearing = Point2D::new(try!(self.arg(0)), try!(self.arg(1)));
+                    let advance = Point2D::new(try!(self.arg(2)), try!(self.arg(3)));
+                    self.set_side_bearing_and_width(side_bearing, advance)
+                }
+                // div
+                1212 => {
+                    let divisor = try!(self.stack.pop().ok_or(Error::Malformed));
+                    let dividend = try!(self.stack.pop().ok_or(Error::Malformed));
+                    if divisor == 0.0 {
+                        return Err(Error::Malformed)
+                    }
+                    self.stack.push(dividend / divisor);
+                    continue
+                }
+                // callothersubr
+                1216 => {
+                    try!(self.call_othersubr());
+                    continue
+                }
+                // pop
+                1217 => {
+                    let value = try!(self.postscript_stack.pop().ok_or(Error::Malformed));
+                    if self.stack.len() >= MAX_OPERAND_STACK_DEPTH {
+                        return Err(Error::Malformed)
+                    }
+                    self.stack.push(value);
+                    continue
+                }
+                // setcurrentpoint
+                1233 => {
+                    let (x, y) = (try!(self.arg(0)), try!(self.arg(1)));
+                    self.current_point = self.origin + Point2D::new(x, y)
+                }
+                _ => return Err(Error::Malformed),
+            }
+
+            self.stack.clear()
+        }
+
+        Ok(())
+    }
+
+    fn arg(&self, index: usize) -> Result<f32, Error> {
+        self.stack.get(index).cloned().ok_or(Error::Malformed)
+    }
+
+    fn set_side_bearing_and_width(&mut self, side_bearing: Point2D<f32>, advance: Point2D<f32>) {
+        self.side_bearing = side_bearing;
+        self.advance = advance;
+        self.current_point = self.origin + side_bearing
+    }
+
+    fn move_to(&mut self, delta: Point2D<f32>) {
+        self.current_point = self.current_point + delta;
+
+        // Within a flex, `rmoveto` only positions the next point to be recorded.
+        if self.flex_points.is_none() {
+            self.commands.push(PathCommand::MoveTo(self.current_point))
+        }
+    }
+
+    fn line_to(&mut self, delta: Point2D<f32>) {
+        self.current_point = self.current_point + delta;
+        self.commands.push(PathCommand::LineTo(self.current_point))
+    }
+
+    fn curve_to(&mut self, d1: Point2D<f32>, d2: Point2D<f32>, d3: Point2D<f32>) {
+        let control_point_0 = self.current_point + d1;
+        let control_point_1 = control_point_0 + d2;
+        self.current_point = control_point_1 + d3;
+        self.commands.push(PathCommand::CurveTo(control_point_0,
+                                                control_point_1,
+                                                self.current_point))
+    }
+
+    // Implements the standard OtherSubrs 0 to 3. Anything else is treated as a procedure that
+    // leaves its arguments on the PostScript stack, which is what unknown OtherSubrs usually do.
+    fn call_othersubr(&mut self) -> Result<(), Error> {
+        let othersubr = try!(self.stack.pop().ok_or(Error::Malformed)) as i32;
+        let arg_count = try!(self.stack.pop().ok_or(Error::Malformed)) as usize;
+        if arg_count > self.stack.len() {
+            return Err(Error::Malformed)
+        }
+        let args_start = self.stack.len() - arg_count;
+        let args: Vec<f32> = self.stack.drain(args_start..).collect();
+
+        match othersubr {
+            OTHERSUBR_FLEX_START => self.flex_points = Some(vec![]),
+            OTHERSUBR_FLEX_POINT => {
+                let current_point = self.current_point;
+                match self.flex_points {
+                    Some(ref mut flex_points) => flex_points.push(current_point),
+                    None => return Err(Error::Malformed),
+                }
+            }
+            OTHERSUBR_FLEX_END => {
+                // The first point is the reference point, which only matters to renderers that
+                // choose to flatten the flex into a straight line.
+                let flex_points = try!(self.flex_points.take().ok_or(Error::Malformed));
+                if flex_points.len() != FLEX_POINT_COUNT || args.len() != 3 {
+                    return Err(Error::Malformed)
+                }
+                self.commands.push(PathCommand::CurveTo(flex_points[1],
+                                                        flex_points[2],
+                                                        flex_points[3]));
+                self.commands.push(PathCommand::CurveTo(flex_points[4],
+                                                        flex_points[5],
+                                                        flex_points[6]));
+                self.current_point = flex_points[6];
+
+                // Subr 0 follows this with `pop pop setcurrentpoint`, so leave the end point,
+                // relative to the glyph origin, for it to pick up.
+                let end_point = flex_points[6] - self.origin;
+                self.postscript_stack.push(end_point.y);
+                self.postscript_stack.push(end_point.x);
+            }
+            OTHERSUBR_HINT_REPLACEMENT => {
+                // Subr 3's argument is the number of the subr containing the new hints, which the
+                // charstring then pops and calls. Since we don't hint, we just hand it back.
+                if args.len() != 1 {
+                    return Err(Error::Malformed)
+                }
+                self.postscript_stack.push(args[0]);
+            }
+            _ => self.postscript_stack.extend(args.iter().rev()),
+        }
+        Ok(())
+    }
+
+    // Composes an accented character from two glyphs named by their codes in the standard
+    // encoding. The accent's origin is placed at `accent_offset` from the composite glyph's side
+    // bearing point, less the accent's own side bearing `accent_side_bearing`.
+    fn seac(&mut self,
+            accent_side_bearing: f32,
+            accent_offset: Point2D<f32>,
+            base_code: f32,
+            accent_code: f32)
+            -> Result<(), Error> {
+        let base_glyph_id = try!(self.standard_glyph_id(base_code));
+        let accent_glyph_id = try!(self.standard_glyph_id(accent_code));
+
+        let composite_side_bearing = self.side_bearing;
+        try!(self.run_glyph(base_glyph_id, Point2D::new(0.0, 0.0), true));
+        let accent_origin = Point2D::new(composite_side_bearing.x + accent_offset.x -
+                                         accent_side_bearing,
+                                         accent_offset.y);
+        self.run_glyph(accent_glyph_id, accent_origin, true)
+    }
+
+    fn standard_glyph_id(&self, code: f32) -> Result<u16, Error> {
+        if code < 0.0 || code >= 256.0 {
+            return Err(Error::Malformed)
+        }
+        let name = STANDARD_ENCODING[code as usize];
+        if name.is_empty() {
+            return Err(Error::Malformed)
+        }
+        self.font.glyph_id(name).ok_or(Error::Malformed)
+    }
+}
+
+/// Adobe StandardEncoding, which `seac` always uses regardless of the font's own encoding.
+pub static STANDARD_ENCODING: [&'static str; 256] = [
+    // 0x00
+    "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    // 0x10
+    "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    // 0x20
+    "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand", "quoteright",
+    "parenleft", "parenright", "asterisk", "plus", "comma", "hyphen", "period", "slash",
+    // 0x30
+    "zero", "one", "two", "three", "four", "five", "six", "seven",
+    "eight", "nine", "colon", "semicolon", "less", "equal", "greater", "question",
+    // 0x40
+    "at", "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O",
+    // 0x50
+    "P", "Q", "R", "S", "T", "U", "V", "W",
+    "X", "Y", "Z", "bracketleft", "backslash", "bracketright", "asciicircum", "underscore",
+    // 0x60
+    "quoteleft", "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o",
+    // 0x70
+    "p", "q", "r", "s", "t", "u", "v", "w",
+    "x", "y", "z", "braceleft", "bar", "braceright", "asciitilde", "",
+    // 0x80
+    "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    // 0x90
+    "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    // 0xa0
+    "", "exclamdown", "cent", "sterling", "fraction", "yen", "florin", "section",
+    "currency", "quotesingle", "quotedblleft", "guillemotleft", "guilsinglleft",
+    "guilsinglright", "fi", "fl",
+    // 0xb0
+    "", "endash", "dagger", "daggerdbl", "periodcentered", "", "paragraph", "bullet",
+    "quotesinglbase", "quotedblbase", "quotedblright", "guillemotright", "ellipsis",
+    "perthousand", "", "questiondown",
+    // 0xc0
+    "", "grave", "acute", "circumflex", "tilde", "macron", "breve", "dotaccent",
+    "dieresis", "", "ring", "cedilla", "", "hungarumlaut", "ogonek", "caron",
+    // 0xd0
+    "emdash", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
+    // 0xe0
+    "", "AE", "", "ordfeminine", "", "", "", "", "Lslash", "Oslash", "OE", "ordmasculine",
+    "", "", "", "",
+    // 0xf0
+    "", "ae", "", "", "", "dotlessi", "", "", "lslash", "oslash", "oe", "germandbls",
+    "", "", "", "",
+];
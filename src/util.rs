@@ -8,21 +8,76 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use byteorder::ByteOrder;
+use error::Error;
+#[cfg(feature = "std")]
+use std::i32;
+use std::mem;
+
 /// A faster version of `Seek` that supports only forward motion from the current position.
 pub trait Jump {
     /// Moves the pointer forward `n` bytes from the *current* position.
-    fn jump(&mut self, n: usize) -> Result<(), ()>;
+    fn jump(&mut self, n: usize) -> Result<(), Error>;
 }
 
 impl<'a> Jump for &'a [u8] {
     #[inline]
-    fn jump(&mut self, n: usize) -> Result<(), ()> {
+    fn jump(&mut self, n: usize) -> Result<(), Error> {
         if n <= self.len() {
             *self = &(*self)[n..];
             Ok(())
         } else {
-            Err(())
+            Err(Error::UnexpectedEof)
         }
     }
 }
 
+/// Reads integers from the front of a byte slice, like `byteorder::ReadBytesExt` but without
+/// needing `std::io`. Running out of data is an `UnexpectedEof` error.
+pub trait ReadBytes {
+    fn read_u8(&mut self) -> Result<u8, Error>;
+    fn read_i8(&mut self) -> Result<i8, Error>;
+    fn read_u16<T: ByteOrder>(&mut self) -> Result<u16, Error>;
+    fn read_i16<T: ByteOrder>(&mut self) -> Result<i16, Error>;
+    fn read_u32<T: ByteOrder>(&mut self) -> Result<u32, Error>;
+}
+
+impl<'a> ReadBytes for &'a [u8] {
+    #[inline]
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        let value = *try!(self.first().ok_or(Error::UnexpectedEof));
+        *self = &(*self)[1..];
+        Ok(value)
+    }
+
+    #[inline]
+    fn read_i8(&mut self) -> Result<i8, Error> {
+        self.read_u8().map(|value| value as i8)
+    }
+
+    #[inline]
+    fn read_u16<T: ByteOrder>(&mut self) -> Result<u16, Error> {
+        let bytes = *self;
+        try!(self.jump(mem::size_of::<u16>()));
+        Ok(T::read_u16(bytes))
+    }
+
+    #[inline]
+    fn read_i16<T: ByteOrder>(&mut self) -> Result<i16, Error> {
+        self.read_u16::<T>().map(|value| value as i16)
+    }
+
+    #[inline]
+    fn read_u32<T: ByteOrder>(&mut self) -> Result<u32, Error> {
+        let bytes = *self;
+        try!(self.jump(mem::size_of::<u32>()));
+        Ok(T::read_u32(bytes))
+    }
+}
+
+/// Converts a pixel coordinate to an integer, saturating coordinates that are out of range.
+#[cfg(feature = "std")]
+#[inline]
+pub fn to_i32(value: f32) -> i32 {
+    value.max(i32::MIN as f32).min(i32::MAX as f32) as i32
+}